@@ -0,0 +1,151 @@
+//! Code implementing the "bench aggregate" sub command.
+
+use std::time::Instant;
+
+use clap::{command, Parser};
+use rand::{Rng as _, SeedableRng as _};
+
+use crate::common::GenomeRelease;
+use crate::seqvars::aggregate;
+
+use super::synth;
+
+/// Command line arguments for `bench aggregate` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Benchmark seqvars aggregate on synthetic data", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum, default_value_t = GenomeRelease::Grch37)]
+    pub genome_release: GenomeRelease,
+    /// Number of synthetic trio cases.
+    #[arg(long, default_value_t = 10)]
+    pub num_cases: usize,
+    /// Number of variants per case.
+    ///
+    /// The variants are drawn from a shared pool of twice this size, so cases share
+    /// about half of their variants.
+    #[arg(long, default_value_t = 100_000)]
+    pub num_variants: usize,
+    /// Seed for generating the synthetic data.
+    #[arg(long, default_value_t = 42)]
+    pub rng_seed: u64,
+    /// Number of threads to use, defaults to number of cores.
+    #[arg(long)]
+    pub num_threads: Option<usize>,
+    /// Directory to keep the synthetic data and outputs in; a temporary directory is
+    /// used if not given.
+    #[arg(long)]
+    pub path_work_dir: Option<String>,
+    /// Use the bulk import of `seqvars aggregate`.
+    #[arg(long, default_value_t = false)]
+    pub bulk: bool,
+    /// Path to the JSONL file to append the report to.
+    #[arg(long)]
+    pub path_report: Option<String>,
+}
+
+/// Build the `seqvars aggregate` arguments for the synthetic `path_input` in `work_dir`.
+fn aggregate_args(args: &Args, work_dir: &str, path_input: Vec<String>) -> aggregate::Args {
+    aggregate::Args {
+        genomebuild: args.genome_release,
+        path_out_rocksdb: format!("{}/rocksdb", work_dir),
+        path_input,
+        cf_counts: "counts".into(),
+        cf_carriers: "carriers".into(),
+        num_threads: args.num_threads,
+        path_wal_dir: None,
+        mt_min_heteroplasmy: 0.1,
+        mt_min_homoplasmy: 0.95,
+        xtr_as_par: false,
+        with_strata: false,
+        on_duplicate_case: aggregate::OnDuplicateCase::Fail,
+        remove: false,
+        bulk: args.bulk,
+    }
+}
+
+/// Main entry point for `bench aggregate` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let (work_dir, _tmp_dir) = super::work_dir(args.path_work_dir.as_deref())?;
+
+    tracing::info!("Generating synthetic data in {}...", &work_dir);
+    let before_generate = Instant::now();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(args.rng_seed);
+    let pool = synth::generate_variants(args.genome_release, 2 * args.num_variants, &mut rng)?;
+    let mut path_input = Vec::new();
+    let mut num_variants = 0;
+    for case_no in 0..args.num_cases {
+        let path = format!("{}/case-{}.vcf.gz", &work_dir, case_no);
+        let case_uuid = uuid::Builder::from_random_bytes(rng.gen()).into_uuid();
+        let variants = pool
+            .iter()
+            .filter(|_| rng.gen_bool(0.5))
+            .collect::<Vec<_>>();
+        num_variants += synth::write_case(
+            &path,
+            args.genome_release,
+            &case_uuid,
+            &format!("case-{}", case_no),
+            variants,
+            &mut rng,
+        )
+        .await?;
+        path_input.push(path);
+    }
+    drop(pool);
+    let generate = before_generate.elapsed();
+    tracing::info!(
+        "... done generating {} cases with {} variants in {:?}",
+        args.num_cases,
+        num_variants,
+        generate
+    );
+
+    tracing::info!("Running aggregation...");
+    let before_run = Instant::now();
+    aggregate::run(args_common, &aggregate_args(args, &work_dir, path_input)).await?;
+    let run = before_run.elapsed();
+    tracing::info!("... done running aggregation in {:?}", run);
+
+    super::Report::new(
+        "aggregate",
+        args.rng_seed,
+        args.num_cases,
+        num_variants,
+        generate,
+        run,
+    )
+    .write(args.path_report.as_deref())
+}
+
+#[cfg(test)]
+mod test {
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_report = format!("{}/report.jsonl", tmpdir.to_string_lossy());
+        let args = super::Args {
+            genome_release: crate::common::GenomeRelease::Grch37,
+            num_cases: 2,
+            num_variants: 500,
+            rng_seed: 42,
+            num_threads: None,
+            path_work_dir: Some(tmpdir.to_string_lossy().to_string()),
+            bulk: false,
+            path_report: Some(path_report.clone()),
+        };
+
+        super::run(&Default::default(), &args).await?;
+
+        let report: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path_report)?.trim())?;
+        assert_eq!(report["benchmark"], "aggregate");
+        assert_eq!(report["num_cases"], 2);
+
+        Ok(())
+    }
+}
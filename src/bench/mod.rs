@@ -0,0 +1,129 @@
+//! Benchmarks of the performance-sensitive code paths on synthetic data.
+//!
+//! Each benchmark generates synthetic ingested VCF files of configurable size, runs the
+//! pipeline on them, and reports the run time and peak memory usage as one JSON line.
+//! Appending the reports of each release to the same file allows to track performance
+//! regressions on the operator's hardware.
+
+use std::io::Write as _;
+
+use crate::common::worker_version;
+
+pub mod aggregate;
+pub mod seqvars_query;
+pub mod synth;
+
+/// Report of one benchmark run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Report {
+    /// Name of the benchmark.
+    pub benchmark: String,
+    /// Version of the worker.
+    pub worker_version: String,
+    /// Seed used for generating the synthetic data.
+    pub rng_seed: u64,
+    /// Number of cases.
+    pub num_cases: usize,
+    /// Total number of variants in all cases.
+    pub num_variants: usize,
+    /// Time for generating the synthetic data in seconds.
+    pub generate_secs: f64,
+    /// Time for running the pipeline in seconds.
+    pub run_secs: f64,
+    /// Number of variants processed per second.
+    pub variants_per_sec: f64,
+    /// Peak resident set size of the process in bytes, if available.
+    pub peak_rss_bytes: Option<u64>,
+}
+
+impl Report {
+    /// Construct the report of `benchmark` from the size of the input and the timings.
+    pub fn new(
+        benchmark: &str,
+        rng_seed: u64,
+        num_cases: usize,
+        num_variants: usize,
+        generate: std::time::Duration,
+        run: std::time::Duration,
+    ) -> Self {
+        Self {
+            benchmark: benchmark.to_string(),
+            worker_version: worker_version().to_string(),
+            rng_seed,
+            num_cases,
+            num_variants,
+            generate_secs: generate.as_secs_f64(),
+            run_secs: run.as_secs_f64(),
+            variants_per_sec: num_variants as f64 / run.as_secs_f64().max(f64::EPSILON),
+            peak_rss_bytes: crate::common::peak_rss_size()
+                .map_err(|e| tracing::warn!("could not determine peak RSS: {}", e))
+                .ok()
+                .flatten(),
+        }
+    }
+
+    /// Log the report and append it as one JSON line to `path_report` if given.
+    pub fn write(&self, path_report: Option<&str>) -> Result<(), anyhow::Error> {
+        let line = serde_json::to_string(self)?;
+        tracing::info!("benchmark report: {}", &line);
+        if let Some(path_report) = path_report {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path_report)
+                .and_then(|mut file| writeln!(file, "{}", &line))
+                .map_err(|e| anyhow::anyhow!("could not write report to {}: {}", path_report, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the working directory for the synthetic data and outputs.
+///
+/// If `path_work_dir` is given, it is created and kept; otherwise, a temporary directory
+/// is used that is removed when the returned guard is dropped.
+pub fn work_dir(
+    path_work_dir: Option<&str>,
+) -> Result<(String, Option<tempfile::TempDir>), anyhow::Error> {
+    if let Some(path_work_dir) = path_work_dir {
+        std::fs::create_dir_all(path_work_dir).map_err(|e| {
+            anyhow::anyhow!("could not create work directory {}: {}", path_work_dir, e)
+        })?;
+        Ok((path_work_dir.to_string(), None))
+    } else {
+        let tmp_dir = tempfile::TempDir::new()
+            .map_err(|e| anyhow::anyhow!("could not create temporary directory: {}", e))?;
+        Ok((tmp_dir.path().to_string_lossy().to_string(), Some(tmp_dir)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn report_write() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/report.jsonl", tmpdir.to_string_lossy());
+        let report = super::Report::new(
+            "seqvars-query",
+            42,
+            1,
+            1_000,
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(2),
+        );
+        assert_eq!(report.variants_per_sec, 500.0);
+
+        report.write(Some(&path))?;
+        report.write(Some(&path))?;
+
+        let lines = std::fs::read_to_string(&path)?
+            .lines()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["benchmark"], "seqvars-query");
+        assert_eq!(lines[1]["num_variants"], 1_000);
+
+        Ok(())
+    }
+}
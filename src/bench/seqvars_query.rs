@@ -0,0 +1,226 @@
+//! Code implementing the "bench seqvars-query" sub command.
+
+use std::time::Instant;
+
+use clap::{command, Parser};
+use rand::SeedableRng as _;
+
+use crate::common::GenomeRelease;
+use crate::seqvars::query;
+
+use super::synth;
+
+/// Command line arguments for `bench seqvars-query` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Benchmark seqvars query on synthetic data", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum, default_value_t = GenomeRelease::Grch37)]
+    pub genome_release: GenomeRelease,
+    /// Number of variants of the synthetic trio.
+    #[arg(long, default_value_t = 1_000_000)]
+    pub num_variants: usize,
+    /// Seed for generating the synthetic data and running the query.
+    #[arg(long, default_value_t = 42)]
+    pub rng_seed: u64,
+    /// Path to worker database to use for querying; the query is run with
+    /// `--no-databases` if not given.
+    #[arg(long)]
+    pub path_db: Option<String>,
+    /// Path to the query JSON file; defaults to a query for rare coding variants.
+    ///
+    /// The sample names of the synthetic trio are `bench_index`, `bench_father`, and
+    /// `bench_mother`.
+    #[arg(long)]
+    pub path_query_json: Option<String>,
+    /// Directory to keep the synthetic data and outputs in; a temporary directory is
+    /// used if not given.
+    #[arg(long)]
+    pub path_work_dir: Option<String>,
+    /// Path to the JSONL file to append the report to.
+    #[arg(long)]
+    pub path_report: Option<String>,
+}
+
+/// Prefix of the sample names of the synthetic trio.
+const SAMPLE_PREFIX: &str = "bench";
+
+/// Build the default query for rare coding variants in `samples`.
+fn default_query(samples: &[String]) -> serde_json::Value {
+    serde_json::json!({
+        "genotype": {
+            "recessiveMode": "RECESSIVE_MODE_DISABLED",
+            "sampleGenotypes": samples.iter().map(|sample| serde_json::json!({
+                "sample": sample,
+                "genotype": "GENOTYPE_CHOICE_ANY",
+                "enabled": true,
+            })).collect::<Vec<_>>(),
+        },
+        "quality": {
+            "sampleQualities": samples.iter().map(|sample| serde_json::json!({
+                "sample": sample,
+                "filterActive": true,
+                "minDpHet": 10,
+                "minDpHom": 5,
+                "minGq": 10,
+                "minAb": 0.2,
+                "minAd": 3,
+            })).collect::<Vec<_>>(),
+        },
+        "frequency": {
+            "gnomadExomes": {"enabled": true, "maxAf": 0.01},
+            "gnomadGenomes": {"enabled": true, "maxAf": 0.01},
+            "gnomadMtdna": {},
+            "helixmtdb": {},
+            "inhouse": {},
+        },
+        "consequence": {
+            "variantTypes": ["VARIANT_TYPE_SNV", "VARIANT_TYPE_INDEL"],
+            "transcriptTypes": ["TRANSCRIPT_TYPE_CODING"],
+            "consequences": [
+                "CONSEQUENCE_SPLICE_ACCEPTOR_VARIANT",
+                "CONSEQUENCE_SPLICE_DONOR_VARIANT",
+                "CONSEQUENCE_STOP_GAINED",
+                "CONSEQUENCE_FRAMESHIFT_VARIANT",
+                "CONSEQUENCE_MISSENSE_VARIANT",
+                "CONSEQUENCE_SPLICE_REGION_VARIANT",
+            ],
+        },
+        "locus": {},
+        "clinvar": {},
+    })
+}
+
+/// Build the `seqvars query` arguments for the synthetic input in `work_dir`.
+fn query_args(args: &Args, work_dir: &str, path_query_json: String) -> query::Args {
+    query::Args {
+        genome_release: args.genome_release,
+        result_set_id: None,
+        case_uuid: None,
+        path_db: args.path_db.clone(),
+        path_inhouse_db: None,
+        no_databases: args.path_db.is_none(),
+        path_query_json,
+        path_gene_panel: Vec::new(),
+        path_ped: None,
+        path_input: format!("{}/input.vcf.gz", work_dir),
+        annotation_source: Default::default(),
+        path_output: format!("{}/output.jsonl", work_dir),
+        output_format: query::output::OutputFormat::Jsonl,
+        output_mode: query::output::OutputMode::Variant,
+        shard_size: 100_000,
+        writer_queue_size: 1_000,
+        resume: false,
+        auto_compress: false,
+        auto_compress_sample_size: 1_000,
+        auto_compress_io_mb_per_sec: 100.0,
+        embed_gene_cache: false,
+        canonical_json: false,
+        path_alignments: Vec::new(),
+        path_alignments_reference: None,
+        alignment_evidence_max_records: 100,
+        path_prior_classifications: None,
+        path_rna_evidence: None,
+        path_reference: None,
+        context_sequence_flank: 50,
+        anonymize: false,
+        path_anonymize_key: None,
+        recessive_score: false,
+        inhouse_carrier_uuids: false,
+        max_results: None,
+        rng_seed: Some(args.rng_seed),
+        max_tad_distance: 10_000,
+        warm_up: false,
+        rocksdb_readahead_size: 0,
+        checkpoint_dir: None,
+        instrument: true,
+        heap_profile: None,
+        estimate_only: false,
+    }
+}
+
+/// Main entry point for `bench seqvars-query` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let (work_dir, _tmp_dir) = super::work_dir(args.path_work_dir.as_deref())?;
+
+    tracing::info!("Generating synthetic data in {}...", &work_dir);
+    let before_generate = Instant::now();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(args.rng_seed);
+    let variants = synth::generate_variants(args.genome_release, args.num_variants, &mut rng)?;
+    let path_query_json = if let Some(path_query_json) = args.path_query_json.as_ref() {
+        path_query_json.clone()
+    } else {
+        let path_query_json = format!("{}/query.json", &work_dir);
+        std::fs::write(
+            &path_query_json,
+            serde_json::to_string_pretty(&default_query(&synth::sample_names(SAMPLE_PREFIX)))?,
+        )?;
+        path_query_json
+    };
+    let query_args = query_args(args, &work_dir, path_query_json);
+    let num_variants = synth::write_case(
+        &query_args.path_input,
+        args.genome_release,
+        &uuid::Uuid::nil(),
+        SAMPLE_PREFIX,
+        &variants,
+        &mut rng,
+    )
+    .await?;
+    drop(variants);
+    let generate = before_generate.elapsed();
+    tracing::info!(
+        "... done generating {} variants in {:?}",
+        num_variants,
+        generate
+    );
+
+    tracing::info!("Running query...");
+    let before_run = Instant::now();
+    query::run(args_common, &query_args).await?;
+    let run = before_run.elapsed();
+    tracing::info!("... done running query in {:?}", run);
+
+    super::Report::new(
+        "seqvars-query",
+        args.rng_seed,
+        1,
+        num_variants,
+        generate,
+        run,
+    )
+    .write(args.path_report.as_deref())
+}
+
+#[cfg(test)]
+mod test {
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_report = format!("{}/report.jsonl", tmpdir.to_string_lossy());
+        let args = super::Args {
+            genome_release: crate::common::GenomeRelease::Grch37,
+            num_variants: 1_000,
+            rng_seed: 42,
+            path_db: None,
+            path_query_json: None,
+            path_work_dir: Some(tmpdir.to_string_lossy().to_string()),
+            path_report: Some(path_report.clone()),
+        };
+
+        super::run(&Default::default(), &args).await?;
+
+        let report: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path_report)?.trim())?;
+        assert_eq!(report["benchmark"], "seqvars-query");
+        assert!(
+            std::path::Path::new(&format!("{}/output.jsonl", tmpdir.to_string_lossy())).exists()
+        );
+
+        Ok(())
+    }
+}
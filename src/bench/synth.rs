@@ -0,0 +1,482 @@
+//! Generation of synthetic ingested sequence variant VCF files.
+//!
+//! The files look like the output of `seqvars ingest` for a trio: the variants are
+//! distributed over the autosomes and chrX proportional to their length, carry gnomAD
+//! frequencies that are skewed towards rare variants, and one `ANN` entry with a
+//! consequence drawn with the approximate proportions of a whole genome.
+
+use mehari::annotate::seqvars::ann::{
+    Allele, AnnField, Consequence, FeatureBiotype, PutativeImpact, Rank,
+};
+use mehari::common::noodles::open_vcf_writer;
+use noodles::{core::Position, vcf};
+use rand::{seq::SliceRandom as _, Rng as _};
+use tokio::io::AsyncWriteExt as _;
+
+use crate::common::{worker_version, GenomeRelease};
+use crate::flush_and_shutdown;
+use crate::seqvars::ingest::header::build_output_header;
+
+/// Consequences with their relative weights.
+const CONSEQUENCES: &[(Consequence, u32)] = &[
+    (Consequence::IntergenicVariant, 400),
+    (Consequence::IntronVariant, 400),
+    (Consequence::UpstreamGeneVariant, 60),
+    (Consequence::DownstreamGeneVariant, 60),
+    (Consequence::ThreePrimeUtrExonVariant, 20),
+    (Consequence::SynonymousVariant, 20),
+    (Consequence::MissenseVariant, 20),
+    (Consequence::SpliceRegionVariant, 10),
+    (Consequence::FrameshiftVariant, 4),
+    (Consequence::StopGained, 3),
+    (Consequence::SpliceDonorVariant, 2),
+    (Consequence::SpliceAcceptorVariant, 1),
+];
+
+/// Number of synthetic genes the variants are assigned to.
+const NUM_GENES: usize = 20_000;
+
+/// Fraction of variants that are not in gnomAD.
+const NOVEL_FRACTION: f64 = 0.1;
+
+/// Number of individuals in gnomAD exomes and genomes.
+const GNOMAD_EXOMES_SIZE: i32 = 125_000;
+const GNOMAD_GENOMES_SIZE: i32 = 75_000;
+
+/// Names of the trio members, the index comes first.
+pub const SAMPLES: [&str; 3] = ["index", "father", "mother"];
+
+/// Carrier counts of a variant in a gnomAD cohort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Counts {
+    /// Number of alleles.
+    an: i32,
+    /// Number of hom. alt. carriers.
+    hom: i32,
+    /// Number of het. carriers.
+    het: i32,
+}
+
+impl Counts {
+    /// Expected counts for allele frequency `af` in a cohort of `size` individuals
+    /// under Hardy-Weinberg equilibrium.
+    fn new(af: f64, size: i32) -> Self {
+        let size_f = f64::from(size);
+        Self {
+            an: 2 * size,
+            hom: (size_f * af * af).round() as i32,
+            het: (size_f * 2.0 * af * (1.0 - af)).round() as i32,
+        }
+    }
+}
+
+/// A synthetic variant.
+#[derive(Debug, Clone)]
+pub struct SynthVariant {
+    /// Chromosome name.
+    pub chrom: String,
+    /// 1-based position.
+    pub pos: usize,
+    /// Reference allele.
+    pub ref_allele: String,
+    /// Alternate allele.
+    pub alt_allele: String,
+    /// Counts in gnomAD exomes and genomes, `None` for novel variants.
+    gnomad: Option<(Counts, Counts)>,
+    /// The annotation.
+    ann: AnnField,
+}
+
+/// Return a random sequence of `len` bases.
+fn random_bases(rng: &mut rand::rngs::StdRng, len: usize) -> String {
+    (0..len)
+        .map(|_| *[b'A', b'C', b'G', b'T'].choose(rng).expect("not empty") as char)
+        .collect()
+}
+
+/// Draw the alleles of a variant, about 10% are indels of up to 5 bases.
+fn random_alleles(rng: &mut rand::rngs::StdRng) -> (String, String) {
+    let anchor = random_bases(rng, 1);
+    if rng.gen_bool(0.05) {
+        let inserted = random_bases(rng, rng.gen_range(1..=5));
+        (anchor.clone(), format!("{}{}", anchor, inserted))
+    } else if rng.gen_bool(0.05) {
+        let deleted = random_bases(rng, rng.gen_range(1..=5));
+        (format!("{}{}", anchor, deleted), anchor)
+    } else {
+        let alt = loop {
+            let alt = random_bases(rng, 1);
+            if alt != anchor {
+                break alt;
+            }
+        };
+        (anchor, alt)
+    }
+}
+
+/// Draw the annotation of a variant with `alt_allele`.
+fn random_ann(rng: &mut rand::rngs::StdRng, alt_allele: &str) -> AnnField {
+    let total = CONSEQUENCES.iter().map(|(_, weight)| weight).sum::<u32>();
+    let mut value = rng.gen_range(0..total);
+    let consequence = CONSEQUENCES
+        .iter()
+        .find_map(|(consequence, weight)| {
+            if value < *weight {
+                Some(*consequence)
+            } else {
+                value -= weight;
+                None
+            }
+        })
+        .expect("value below total");
+    let gene_no = rng.gen_range(1..=NUM_GENES);
+    let exon_count = rng.gen_range(1..=30);
+    let distance = match consequence {
+        Consequence::IntergenicVariant
+        | Consequence::UpstreamGeneVariant
+        | Consequence::DownstreamGeneVariant => rng.gen_range(1..5_000),
+        Consequence::IntronVariant => rng.gen_range(1..2_000),
+        Consequence::SpliceRegionVariant
+        | Consequence::SpliceDonorVariant
+        | Consequence::SpliceAcceptorVariant => rng.gen_range(1..8),
+        _ => 0,
+    };
+
+    AnnField {
+        allele: Allele::Alt {
+            alternative: alt_allele.to_string(),
+        },
+        consequences: vec![consequence],
+        putative_impact: PutativeImpact::from(consequence),
+        gene_symbol: format!("GENE{}", gene_no),
+        gene_id: format!("HGNC:{}", gene_no),
+        feature_id: format!("NM_{:06}.1", gene_no),
+        feature_biotype: vec![FeatureBiotype::Coding, FeatureBiotype::ManeSelect],
+        rank: Some(Rank {
+            ord: rng.gen_range(1..=exon_count),
+            total: exon_count,
+        }),
+        distance: Some(distance),
+        strand: if rng.gen_bool(0.5) { 1 } else { -1 },
+        ..Default::default()
+    }
+}
+
+/// Generate `count` random variants sorted by position.
+///
+/// The variants are distributed over the autosomes and chrX of `genome_release`
+/// proportional to the chromosome length.
+pub fn generate_variants(
+    genome_release: GenomeRelease,
+    count: usize,
+    rng: &mut rand::rngs::StdRng,
+) -> Result<Vec<SynthVariant>, anyhow::Error> {
+    let header = build_header(genome_release, &uuid::Uuid::nil(), "synthetic")?;
+    let contigs = header
+        .contigs()
+        .iter()
+        .filter(|(name, _)| !["Y", "MT", "chrY", "chrM"].contains(&name.as_str()))
+        .map(|(name, contig)| (name.clone(), contig.length().unwrap_or_default()))
+        .collect::<Vec<_>>();
+    let genome_length = contigs.iter().map(|(_, length)| length).sum::<usize>();
+
+    let mut result = Vec::with_capacity(count);
+    for (chrom, length) in &contigs {
+        let chrom_count = count * length / genome_length;
+        let mut positions = (0..chrom_count)
+            .map(|_| rng.gen_range(1..length - 10))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions.dedup();
+        for pos in positions {
+            let (ref_allele, alt_allele) = random_alleles(rng);
+            let gnomad = (!rng.gen_bool(NOVEL_FRACTION)).then(|| {
+                // Log-uniform allele frequency between 1e-5 and 0.5.
+                let af = 10f64.powf(rng.gen_range(-5.0..-0.3));
+                (
+                    Counts::new(af, GNOMAD_EXOMES_SIZE),
+                    Counts::new(af, GNOMAD_GENOMES_SIZE),
+                )
+            });
+            let ann = random_ann(rng, &alt_allele);
+            result.push(SynthVariant {
+                chrom: chrom.clone(),
+                pos,
+                ref_allele,
+                alt_allele,
+                gnomad,
+                ann,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// Return the sample names of the trio of the case with `prefix`.
+pub fn sample_names(prefix: &str) -> Vec<String> {
+    SAMPLES
+        .iter()
+        .map(|name| format!("{}_{}", prefix, name))
+        .collect()
+}
+
+/// Build the header of the ingested VCF of the trio with `sample_prefix`.
+fn build_header(
+    genome_release: GenomeRelease,
+    case_uuid: &uuid::Uuid,
+    sample_prefix: &str,
+) -> Result<vcf::Header, anyhow::Error> {
+    let names = sample_names(sample_prefix);
+    let input_header: vcf::Header = format!(
+        "##fileformat=VCFv4.2\n\
+        ##GATKCommandLine=<ID=HaplotypeCaller,Version=\"synthetic\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}\n",
+        names.join("\t")
+    )
+    .parse()
+    .map_err(|e| anyhow::anyhow!("problem building synthetic input header: {}", e))?;
+
+    let mut pedigree = mehari::ped::PedigreeByName::default();
+    for (name, sex, disease) in [
+        (
+            &names[0],
+            mehari::ped::Sex::Female,
+            mehari::ped::Disease::Affected,
+        ),
+        (
+            &names[1],
+            mehari::ped::Sex::Male,
+            mehari::ped::Disease::Unaffected,
+        ),
+        (
+            &names[2],
+            mehari::ped::Sex::Female,
+            mehari::ped::Disease::Unaffected,
+        ),
+    ] {
+        let is_index = name == &names[0];
+        pedigree.individuals.insert(
+            name.clone(),
+            mehari::ped::Individual {
+                family: "FAM".into(),
+                name: name.clone(),
+                father: is_index.then(|| names[1].clone()),
+                mother: is_index.then(|| names[2].clone()),
+                sex,
+                disease,
+            },
+        );
+    }
+
+    build_output_header(
+        &input_header,
+        &Some(pedigree),
+        &None,
+        genome_release,
+        &chrono::Utc::now().format("%Y%m%d").to_string(),
+        case_uuid,
+        worker_version(),
+    )
+}
+
+/// Draw the genotypes of index, father, and mother for a variant of the index.
+///
+/// The genotypes are Mendelian except for about 1% of de novo variants.
+fn random_genotypes(rng: &mut rand::rngs::StdRng) -> [&'static str; 3] {
+    let index_hom = rng.gen_bool(0.3);
+    let de_novo = !index_hom && rng.gen_bool(0.01);
+    let father_transmits = index_hom || (!de_novo && rng.gen_bool(0.5));
+    let mother_transmits = index_hom || (!de_novo && !father_transmits);
+    let mut parent = |transmits: bool| {
+        if transmits {
+            if rng.gen_bool(0.2) {
+                "1/1"
+            } else {
+                "0/1"
+            }
+        } else if rng.gen_bool(0.05) {
+            "0/1"
+        } else {
+            "0/0"
+        }
+    };
+    let father = parent(father_transmits);
+    let mother = parent(mother_transmits);
+    [if index_hom { "1/1" } else { "0/1" }, father, mother]
+}
+
+/// Build the sample values for `genotype`, with random depth and matching allelic depths.
+fn sample_values(
+    rng: &mut rand::rngs::StdRng,
+    genotype: &str,
+) -> Vec<Option<vcf::variant::record_buf::samples::sample::Value>> {
+    use vcf::variant::record_buf::samples::sample::value::{Array, Value};
+
+    let dp = rng.gen_range(15..60);
+    let alt_fraction = match genotype {
+        "0/0" => 0.0,
+        "0/1" => rng.gen_range(0.3..0.7),
+        _ => 1.0,
+    };
+    let alt_depth = (f64::from(dp) * alt_fraction).round() as i32;
+    vec![
+        Some(Value::String(genotype.to_string())),
+        Some(Value::Integer(rng.gen_range(30..=99))),
+        Some(Value::Integer(dp)),
+        Some(Value::Array(Array::Integer(vec![
+            Some(dp - alt_depth),
+            Some(alt_depth),
+        ]))),
+    ]
+}
+
+/// Write the ingested VCF for the trio with `sample_prefix` and `case_uuid` to `path`.
+///
+/// The index is a carrier of each of `variants`.
+pub async fn write_case<'a, I>(
+    path: &str,
+    genome_release: GenomeRelease,
+    case_uuid: &uuid::Uuid,
+    sample_prefix: &str,
+    variants: I,
+    rng: &mut rand::rngs::StdRng,
+) -> Result<usize, anyhow::Error>
+where
+    I: IntoIterator<Item = &'a SynthVariant>,
+{
+    use vcf::variant::record::samples::keys::key;
+    use vcf::variant::record_buf::info::field::{value::Array, Value};
+
+    let header = build_header(genome_release, case_uuid, sample_prefix)?;
+    let names = sample_names(sample_prefix);
+    // Index of each trio member in the header sample order.
+    let sample_order = header
+        .sample_names()
+        .iter()
+        .map(|name| {
+            names
+                .iter()
+                .position(|n| n == name)
+                .expect("sample from header")
+        })
+        .collect::<Vec<_>>();
+    let keys: vcf::variant::record_buf::samples::Keys = [
+        key::GENOTYPE,
+        key::CONDITIONAL_GENOTYPE_QUALITY,
+        key::READ_DEPTH,
+        key::READ_DEPTHS,
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+
+    let mut writer = open_vcf_writer(path).await?;
+    writer
+        .write_header(&header)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not write header to {}: {}", path, e))?;
+
+    let mut count = 0;
+    for variant in variants {
+        let mut info = vcf::variant::record_buf::Info::default();
+        if let Some((exomes, genomes)) = variant.gnomad {
+            for (prefix, counts) in [("gnomad_exomes", exomes), ("gnomad_genomes", genomes)] {
+                for (suffix, value) in [("an", counts.an), ("hom", counts.hom), ("het", counts.het)]
+                {
+                    info.insert(
+                        format!("{}_{}", prefix, suffix),
+                        Some(Value::Integer(value)),
+                    );
+                }
+            }
+        }
+        info.insert(
+            "ANN".into(),
+            Some(Value::Array(Array::String(vec![Some(
+                variant.ann.to_string(),
+            )]))),
+        );
+
+        let genotypes = random_genotypes(rng);
+        let values = sample_order
+            .iter()
+            .map(|idx| sample_values(rng, genotypes[*idx]))
+            .collect();
+
+        let record = vcf::variant::RecordBuf::builder()
+            .set_reference_sequence_name(variant.chrom.clone())
+            .set_variant_start(Position::try_from(variant.pos)?)
+            .set_reference_bases(variant.ref_allele.clone())
+            .set_alternate_bases(vcf::variant::record_buf::AlternateBases::from(vec![
+                variant.alt_allele.clone(),
+            ]))
+            .set_info(info)
+            .set_samples(vcf::variant::record_buf::Samples::new(keys.clone(), values))
+            .build();
+        writer
+            .write_variant_record(&header, &record)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not write record to {}: {}", path, e))?;
+        count += 1;
+    }
+    flush_and_shutdown!(writer);
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng as _;
+
+    use crate::common::GenomeRelease;
+
+    #[test]
+    fn generate_variants() -> Result<(), anyhow::Error> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let variants = super::generate_variants(GenomeRelease::Grch37, 1_000, &mut rng)?;
+
+        // Some variants are lost to rounding and duplicate positions.
+        assert!(variants.len() > 950 && variants.len() <= 1_000);
+        assert!(variants
+            .windows(2)
+            .all(|pair| pair[0].chrom != pair[1].chrom || pair[0].pos < pair[1].pos));
+        assert!(variants.iter().all(|v| v.chrom != "Y" && v.chrom != "MT"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_case() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/case.vcf", tmpdir.to_string_lossy());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let variants = super::generate_variants(GenomeRelease::Grch38, 100, &mut rng)?;
+
+        let count = super::write_case(
+            &path,
+            GenomeRelease::Grch38,
+            &uuid::Uuid::nil(),
+            "case-1",
+            &variants,
+            &mut rng,
+        )
+        .await?;
+        assert_eq!(count, variants.len());
+
+        let mut reader = noodles::vcf::io::reader::Builder::default().build_from_path(&path)?;
+        let header = reader.read_header()?;
+        assert_eq!(
+            header.sample_names().iter().collect::<Vec<_>>(),
+            vec!["case-1_father", "case-1_index", "case-1_mother"]
+        );
+        let (pedigree, _) = crate::common::extract_pedigree_and_case_uuid(&header)?;
+        assert_eq!(
+            pedigree.individuals["case-1_index"].father.as_deref(),
+            Some("case-1_father")
+        );
+        assert_eq!(reader.record_bufs(&header).count(), variants.len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//! Shared code for the `query-batch` sub commands.
+//!
+//! The jobs are read from a JSONL manifest and the status of each job is written as one
+//! JSON line to a summary file.
+
+use std::io::{BufRead as _, Write as _};
+
+use super::errors::ErrorClass;
+
+/// Load the jobs from the JSONL manifest at `path`, skipping empty lines.
+pub fn load_manifest<J>(path: &str) -> Result<Vec<J>, anyhow::Error>
+where
+    J: serde::de::DeserializeOwned,
+{
+    let reader = std::fs::File::open(path)
+        .map(std::io::BufReader::new)
+        .map_err(|e| anyhow::anyhow!("could not open manifest {}: {}", path, e))?;
+    let mut jobs = Vec::new();
+    for (no, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| anyhow::anyhow!("could not read manifest {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let job = serde_json::from_str(&line).map_err(|e| {
+            anyhow::anyhow!("invalid job in line {} of manifest {}: {}", no + 1, path, e)
+        })?;
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+/// Status of one job, written to the summary file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct JobSummary {
+    /// Identifier of the job.
+    pub id: String,
+    /// Whether the job succeeded.
+    pub success: bool,
+    /// The class of the error, if the job failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_class: Option<String>,
+    /// The error message, if the job failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Run time of the job in seconds.
+    pub elapsed_secs: f64,
+}
+
+impl JobSummary {
+    /// Construct from the `result` of the job `id` that took `elapsed`.
+    pub fn new(id: &str, result: &Result<(), anyhow::Error>, elapsed: std::time::Duration) -> Self {
+        Self {
+            id: id.to_string(),
+            success: result.is_ok(),
+            error_class: result.as_ref().err().map(|e| ErrorClass::of(e).to_string()),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            elapsed_secs: elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// Writes the job summaries to a JSONL file, flushing after each job.
+pub struct SummaryWriter {
+    /// Path to the summary file, for error messages.
+    path: String,
+    /// Writer for the summary file.
+    writer: std::io::BufWriter<std::fs::File>,
+}
+
+impl SummaryWriter {
+    /// Create the summary file at `path`.
+    pub fn create(path: &str) -> Result<Self, anyhow::Error> {
+        let writer = std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .map_err(|e| anyhow::anyhow!("could not create summary file {}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            writer,
+        })
+    }
+
+    /// Write `summary` and flush, so the summary is complete even if a later job aborts.
+    pub fn write(&mut self, summary: &JobSummary) -> Result<(), anyhow::Error> {
+        writeln!(self.writer, "{}", serde_json::to_string(summary)?)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| anyhow::anyhow!("could not write summary {}: {}", &self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write as _;
+    use std::time::Duration;
+
+    use crate::common::errors::{ClassifyExt as _, ErrorClass};
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Job {
+        path_input: String,
+        id: Option<String>,
+    }
+
+    #[test]
+    fn load_manifest() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/jobs.jsonl", tmpdir.to_string_lossy());
+        let mut file = std::fs::File::create(&path)?;
+        writeln!(file, r#"{{"id": "case-1", "path_input": "in1.vcf"}}"#)?;
+        writeln!(file)?;
+        writeln!(file, r#"{{"path_input": "in2.vcf"}}"#)?;
+        drop(file);
+
+        assert_eq!(
+            super::load_manifest::<Job>(&path)?,
+            vec![
+                Job {
+                    path_input: "in1.vcf".into(),
+                    id: Some("case-1".into()),
+                },
+                Job {
+                    path_input: "in2.vcf".into(),
+                    id: None,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_manifest_unknown_field() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/jobs.jsonl", tmpdir.to_string_lossy());
+        std::fs::write(&path, r#"{"path_input": "in.vcf", "unknown": 1}"#)?;
+
+        assert!(super::load_manifest::<Job>(&path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn summary_writer() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/summary.jsonl", tmpdir.to_string_lossy());
+        let mut writer = super::SummaryWriter::create(&path)?;
+        writer.write(&super::JobSummary::new(
+            "case-1",
+            &Ok(()),
+            Duration::from_secs(2),
+        ))?;
+        let failed: Result<(), anyhow::Error> =
+            Err(anyhow::anyhow!("could not load query")).classify(ErrorClass::Config);
+        writer.write(&super::JobSummary::new(
+            "case-2",
+            &failed,
+            Duration::from_millis(500),
+        ))?;
+        drop(writer);
+
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "{\"id\":\"case-1\",\"success\":true,\"elapsed_secs\":2.0}\n\
+            {\"id\":\"case-2\",\"success\":false,\"error_class\":\"configuration error\",\
+            \"error\":\"configuration error: could not load query\",\"elapsed_secs\":0.5}\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Canonical JSON serialization for byte-identical outputs.
+//!
+//! In canonical form, object keys are sorted, floating point numbers are rounded to 7
+//! significant digits (the precision of `f32`) and written in their shortest
+//! representation, and no whitespace is used.  Identical values thus give identical
+//! bytes, e.g., for hash-based caching of results.
+
+/// Return `value` with the keys of all objects sorted.
+pub fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    map_values(value, &|value| value)
+}
+
+/// Return `value` with sorted keys and rounded floating point numbers.
+pub fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+    map_values(value, &|value| match value {
+        serde_json::Value::Number(number) if number.is_f64() => number
+            .as_f64()
+            .and_then(|number| format!("{:.6e}", number).parse::<f64>().ok())
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        value => value,
+    })
+}
+
+/// Sort the keys of all objects in `value` and apply `f` to all other values.
+fn map_values(
+    value: serde_json::Value,
+    f: &impl Fn(serde_json::Value) -> serde_json::Value,
+) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries = map.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, map_values(value, f)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(values) => serde_json::Value::Array(
+            values
+                .into_iter()
+                .map(|value| map_values(value, f))
+                .collect(),
+        ),
+        value => f(value),
+    }
+}
+
+/// Serialize `value` to JSON, in canonical form if `canonical` is set.
+pub fn to_string<T: serde::Serialize + ?Sized>(
+    value: &T,
+    canonical: bool,
+) -> Result<String, serde_json::Error> {
+    if canonical {
+        serde_json::to_string(&canonicalize(serde_json::to_value(value)?))
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[derive(serde::Serialize)]
+    struct Record {
+        name: String,
+        score: f32,
+        count: u32,
+        nested: std::collections::HashMap<String, f64>,
+    }
+
+    #[test]
+    fn to_string_canonical() -> Result<(), anyhow::Error> {
+        let record = Record {
+            name: "x".to_string(),
+            score: 0.1,
+            count: 3,
+            nested: [("b".to_string(), 2.0), ("a".to_string(), 1.0 / 3.0)]
+                .into_iter()
+                .collect(),
+        };
+
+        assert_eq!(
+            super::to_string(&record, true)?,
+            r#"{"count":3,"name":"x","nested":{"a":0.3333333,"b":2.0},"score":0.1}"#
+        );
+        assert!(super::to_string(&record, false)?.starts_with(r#"{"name":"x","#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sort_keys_keeps_numbers() {
+        let value = serde_json::json!({"b": [{"d": 1.0 / 3.0, "c": 1}], "a": null});
+
+        assert_eq!(
+            serde_json::to_string(&super::sort_keys(value)).unwrap(),
+            r#"{"a":null,"b":[{"c":1,"d":0.3333333333333333}]}"#
+        );
+    }
+}
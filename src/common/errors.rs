@@ -0,0 +1,125 @@
+//! Classification of errors into failure classes with dedicated process exit codes.
+//!
+//! The class is attached to an `anyhow::Error` as context with `ClassifyExt::classify()`
+//! and retrieved in `main()` with `ErrorClass::of()`.  Unclassified errors are internal
+//! errors.
+
+/// Failure class of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum ErrorClass {
+    /// Invalid configuration, e.g., query JSON, pedigree, or command line arguments.
+    #[strum(serialize = "configuration error")]
+    Config,
+    /// Invalid or inconsistent input files.
+    #[strum(serialize = "input error")]
+    Input,
+    /// Missing or broken databases.
+    #[strum(serialize = "database error")]
+    Db,
+    /// Internal error, e.g., a bug or an I/O problem.
+    #[strum(serialize = "internal error")]
+    Internal,
+    /// Cancelled by the user, e.g., with Ctrl-C.
+    #[strum(serialize = "cancelled")]
+    Cancelled,
+}
+
+impl ErrorClass {
+    /// Return the process exit code for the class.
+    ///
+    /// Configuration errors share the code `2` with command line parsing errors and
+    /// cancellation uses `130` as for termination by `SIGINT`.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorClass::Internal => 1,
+            ErrorClass::Config => 2,
+            ErrorClass::Input => 3,
+            ErrorClass::Db => 4,
+            ErrorClass::Cancelled => 130,
+        }
+    }
+
+    /// Return the outermost class attached to `err`, defaulting to `Internal`.
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.downcast_ref::<ErrorClass>()
+            .copied()
+            .unwrap_or(ErrorClass::Internal)
+    }
+}
+
+/// Extension trait for attaching an `ErrorClass` to the error of a `Result`.
+pub trait ClassifyExt<T> {
+    /// Attach `class` to the error, if any.
+    fn classify(self, class: ErrorClass) -> Result<T, anyhow::Error>;
+}
+
+impl<T, E> ClassifyExt<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn classify(self, class: ErrorClass) -> Result<T, anyhow::Error> {
+        self.map_err(|e| e.into().context(class))
+    }
+}
+
+/// Log `message` as a warning or, in `strict` mode, fail with an error of `class`.
+pub fn warn_or_fail<D>(strict: bool, class: ErrorClass, message: D) -> Result<(), anyhow::Error>
+where
+    D: std::fmt::Display + Send + Sync + 'static,
+{
+    if strict {
+        Err(anyhow::anyhow!("{} (failing because of --strict)", message).context(class))
+    } else {
+        tracing::warn!("{}", message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{warn_or_fail, ClassifyExt as _, ErrorClass};
+
+    #[test]
+    fn classify() {
+        let err = Err::<(), _>(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no such file",
+        ))
+        .classify(ErrorClass::Input)
+        .map_err(|e| e.context("could not load input"))
+        .unwrap_err();
+
+        assert_eq!(ErrorClass::of(&err), ErrorClass::Input);
+        assert_eq!(ErrorClass::of(&err).exit_code(), 3);
+        assert_eq!(
+            format!("{:#}", err),
+            "could not load input: input error: no such file"
+        );
+    }
+
+    #[test]
+    fn classify_outermost_wins() {
+        let err = Err::<(), _>(anyhow::anyhow!("broken"))
+            .classify(ErrorClass::Db)
+            .classify(ErrorClass::Config)
+            .unwrap_err();
+
+        assert_eq!(ErrorClass::of(&err), ErrorClass::Config);
+    }
+
+    #[test]
+    fn unclassified_is_internal() {
+        assert_eq!(
+            ErrorClass::of(&anyhow::anyhow!("oops")),
+            ErrorClass::Internal
+        );
+    }
+
+    #[test]
+    fn warn_or_fail_strict() {
+        assert!(warn_or_fail(false, ErrorClass::Input, "unknown caller").is_ok());
+
+        let err = warn_or_fail(true, ErrorClass::Input, "unknown caller").unwrap_err();
+        assert_eq!(ErrorClass::of(&err), ErrorClass::Input);
+    }
+}
@@ -0,0 +1,246 @@
+//! Estimation of the resources needed by a query without running it.
+//!
+//! Only the header and index of the input VCF file and the sizes of the database files
+//! are inspected; the estimate is meant for resource allocation by a scheduler.
+
+use std::path::Path;
+
+use indexmap::IndexMap;
+use noodles::csi::BinningIndex as _;
+
+use super::errors::{ClassifyExt as _, ErrorClass};
+
+/// Approximate number of compressed bytes per VCF record, used without an index.
+const BYTES_PER_RECORD: u64 = 100;
+
+/// Statistics of the input VCF file from its header and index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct InputStats {
+    /// Number of samples in the header.
+    pub sample_count: usize,
+    /// Number of records per contig from the index; empty without index.
+    pub record_counts: IndexMap<String, u64>,
+    /// Total number of records; approximated from the file size without index.
+    pub record_count: u64,
+    /// Whether `record_count` was taken from the index.
+    pub from_index: bool,
+    /// Size of the input file in bytes, if local.
+    pub file_size: Option<u64>,
+}
+
+impl InputStats {
+    /// Collect the statistics of the VCF file at `path`.
+    ///
+    /// The index is looked for at `{path}.tbi` and `{path}.csi`; indices of files on S3
+    /// are not considered.
+    pub async fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let header = super::noodles::open_vcf_reader(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path, e))
+            .classify(ErrorClass::Input)?
+            .read_header()
+            .await
+            .classify(ErrorClass::Input)?;
+        let file_size = std::fs::metadata(path).ok().map(|metadata| metadata.len());
+
+        let record_counts = record_counts_from_index(path)?;
+        let (record_count, from_index) = match record_counts.as_ref() {
+            Some(record_counts) => (record_counts.values().sum(), true),
+            None => (file_size.unwrap_or_default() / BYTES_PER_RECORD, false),
+        };
+
+        Ok(Self {
+            sample_count: header.sample_names().len(),
+            record_counts: record_counts.unwrap_or_default(),
+            record_count,
+            from_index,
+            file_size,
+        })
+    }
+}
+
+/// Read the mapped record counts per contig from the tabix or CSI index of `path`.
+fn record_counts_from_index(path: &str) -> Result<Option<IndexMap<String, u64>>, anyhow::Error> {
+    let path_tbi = format!("{}.tbi", path);
+    let path_csi = format!("{}.csi", path);
+    let index: Box<dyn noodles::csi::BinningIndex> = if Path::new(&path_tbi).exists() {
+        Box::new(
+            noodles::tabix::read(&path_tbi)
+                .map_err(|e| anyhow::anyhow!("could not read tabix index {}: {}", &path_tbi, e))?,
+        )
+    } else if Path::new(&path_csi).exists() {
+        Box::new(
+            noodles::csi::read(&path_csi)
+                .map_err(|e| anyhow::anyhow!("could not read CSI index {}: {}", &path_csi, e))?,
+        )
+    } else {
+        return Ok(None);
+    };
+
+    let Some(names) = index
+        .header()
+        .map(|header| header.reference_sequence_names())
+    else {
+        return Ok(None);
+    };
+    let mut result = IndexMap::new();
+    for (name, reference_sequence) in names.iter().zip(index.reference_sequences()) {
+        match reference_sequence.metadata() {
+            Some(metadata) => {
+                result.insert(name.clone(), metadata.mapped_record_count());
+            }
+            // Without metadata, the index does not know the record counts.
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(result))
+}
+
+/// Return the total size of the files below `path`; missing paths have size 0.
+pub fn disk_usage<P: AsRef<Path>>(path: P) -> u64 {
+    let Ok(metadata) = std::fs::metadata(path.as_ref()) else {
+        return 0;
+    };
+    if metadata.is_dir() {
+        std::fs::read_dir(path.as_ref())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| disk_usage(entry.path()))
+                    .sum()
+            })
+            .unwrap_or_default()
+    } else {
+        metadata.len()
+    }
+}
+
+/// Return the size of the worker databases below `path_db` that are loaded into memory.
+pub fn worker_db_usage(path_db: &str, genome_release: super::GenomeRelease) -> u64 {
+    disk_usage(format!("{}/worker/noref", path_db))
+        + disk_usage(format!("{}/worker/{}", path_db, genome_release))
+}
+
+/// Parameters of the cost model of a query command.
+#[derive(Debug, Clone)]
+pub struct CostModel {
+    /// Number of input records processed per second.
+    pub records_per_second: f64,
+    /// Number of bytes of in-memory databases loaded per second.
+    pub db_bytes_per_second: f64,
+    /// Memory used independent of input and databases.
+    pub base_memory: u64,
+    /// Memory per input record held at the same time.
+    pub memory_per_record: u64,
+    /// Memory per byte of in-memory database files.
+    pub memory_per_db_byte: f64,
+    /// Bytes written per output record.
+    pub output_bytes_per_record: u64,
+}
+
+/// Resource estimate for running a query.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Estimate {
+    /// Statistics of the input file.
+    pub input: InputStats,
+    /// Total size of the database files.
+    pub database_size: u64,
+    /// Size of the database files that are loaded into memory.
+    pub in_memory_database_size: u64,
+    /// Approximate runtime in seconds.
+    pub runtime_seconds: f64,
+    /// Approximate peak memory in bytes.
+    pub memory_bytes: u64,
+    /// Approximate temporary disk space in bytes, assuming all records pass.
+    pub temp_disk_bytes: u64,
+}
+
+impl Estimate {
+    /// Compute the estimate with `model` given the input statistics, the database
+    /// sizes, and the number of input records held in memory at the same time.
+    pub fn new(
+        model: &CostModel,
+        input: InputStats,
+        database_size: u64,
+        in_memory_database_size: u64,
+        records_in_memory: u64,
+    ) -> Self {
+        let runtime_seconds = input.record_count as f64 / model.records_per_second
+            + in_memory_database_size as f64 / model.db_bytes_per_second;
+        let memory_bytes = model.base_memory
+            + (in_memory_database_size as f64 * model.memory_per_db_byte) as u64
+            + records_in_memory * model.memory_per_record;
+        let temp_disk_bytes = input.record_count * model.output_bytes_per_record;
+        Self {
+            input,
+            database_size,
+            in_memory_database_size,
+            runtime_seconds,
+            memory_bytes,
+            temp_disk_bytes,
+        }
+    }
+
+    /// Write the estimate as JSON to stdout.
+    pub fn print(&self) -> Result<(), anyhow::Error> {
+        println!("{}", serde_json::to_string_pretty(self)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CostModel, Estimate, InputStats};
+
+    #[tokio::test]
+    async fn input_stats_from_index() -> Result<(), anyhow::Error> {
+        let stats = InputStats::load("tests/strucvars/ingest/delly2-min.vcf.gz").await?;
+
+        assert_eq!(stats.sample_count, 3);
+        assert!(stats.from_index);
+        assert_eq!(
+            stats.record_counts,
+            indexmap::IndexMap::from([("1".to_string(), 1)])
+        );
+        assert_eq!(stats.record_count, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn input_stats_without_index() -> Result<(), anyhow::Error> {
+        let path = "tests/strucvars/ingest/delly2-min.vcf";
+        let stats = InputStats::load(path).await?;
+
+        let file_size = std::fs::metadata(path)?.len();
+        assert_eq!(stats.sample_count, 3);
+        assert!(!stats.from_index);
+        assert!(stats.record_counts.is_empty());
+        assert_eq!(stats.record_count, file_size / super::BYTES_PER_RECORD);
+        assert_eq!(stats.file_size, Some(file_size));
+
+        Ok(())
+    }
+
+    #[test]
+    fn estimate() {
+        let model = CostModel {
+            records_per_second: 1_000.0,
+            db_bytes_per_second: 100.0,
+            base_memory: 1_000,
+            memory_per_record: 10,
+            memory_per_db_byte: 2.0,
+            output_bytes_per_record: 5,
+        };
+        let input = InputStats {
+            record_count: 10_000,
+            ..Default::default()
+        };
+
+        let estimate = Estimate::new(&model, input, 5_000, 500, 100);
+
+        assert_eq!(estimate.runtime_seconds, 15.0);
+        assert_eq!(estimate.memory_bytes, 3_000);
+        assert_eq!(estimate.temp_disk_bytes, 50_000);
+    }
+}
@@ -0,0 +1,565 @@
+//! Small boolean expression language for advanced query filters.
+//!
+//! Expressions combine comparisons of annotated fields with literals, e.g.,
+//!
+//! ```text
+//! gnomad_af < 0.001 && (cadd_phred > 25 || spliceai > 0.5)
+//! ```
+//!
+//! The grammar is as follows; `and`, `or`, and `not` may be used instead of the
+//! symbolic operators.
+//!
+//! ```text
+//! expr       := or
+//! or         := and ( "||" and )*
+//! and        := unary ( "&&" unary )*
+//! unary      := "!" unary | comparison
+//! comparison := operand ( ( "<" | "<=" | ">" | ">=" | "==" | "!=" ) operand )?
+//! operand    := NUMBER | STRING | "true" | "false" | FIELD | "(" expr ")"
+//! ```
+//!
+//! Expressions are compiled once with `Expression::parse()` and evaluated for each record
+//! with `Expression::eval()`.  Comparisons involving fields without a value for the record
+//! evaluate to `false`.
+
+use std::fmt;
+
+/// Error type for parsing expressions.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("unexpected character {1:?} at position {0}")]
+    UnexpectedCharacter(usize, char),
+    #[error("unterminated string literal starting at position {0}")]
+    UnterminatedString(usize),
+    #[error("invalid number {1:?} at position {0}")]
+    InvalidNumber(usize, String),
+    #[error("unexpected token {1} at position {0}")]
+    UnexpectedToken(usize, String),
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("unknown field {0:?}")]
+    UnknownField(String),
+}
+
+/// A value that a field or literal evaluates to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Numeric value.
+    Number(f64),
+    /// String value.
+    String(String),
+    /// Boolean value.
+    Bool(bool),
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+/// Comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum CmpOp {
+    #[strum(serialize = "<")]
+    Lt,
+    #[strum(serialize = "<=")]
+    Le,
+    #[strum(serialize = ">")]
+    Gt,
+    #[strum(serialize = ">=")]
+    Ge,
+    #[strum(serialize = "==")]
+    Eq,
+    #[strum(serialize = "!=")]
+    Ne,
+}
+
+impl CmpOp {
+    /// Apply the operator to the given values.
+    ///
+    /// Numbers are compared numerically, strings lexicographically, and booleans only
+    /// for (in)equality.  Values of different types are never equal.
+    fn apply(&self, lhs: &Value, rhs: &Value) -> bool {
+        let ordering = match (lhs, rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => lhs.partial_cmp(rhs),
+            (Value::String(lhs), Value::String(rhs)) => Some(lhs.cmp(rhs)),
+            (Value::Bool(lhs), Value::Bool(rhs)) => {
+                return match self {
+                    CmpOp::Eq => lhs == rhs,
+                    CmpOp::Ne => lhs != rhs,
+                    _ => false,
+                }
+            }
+            _ => return matches!(self, CmpOp::Ne),
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self {
+            CmpOp::Lt => ordering.is_lt(),
+            CmpOp::Le => ordering.is_le(),
+            CmpOp::Gt => ordering.is_gt(),
+            CmpOp::Ge => ordering.is_ge(),
+            CmpOp::Eq => ordering.is_eq(),
+            CmpOp::Ne => ordering.is_ne(),
+        }
+    }
+}
+
+/// Abstract syntax tree of a compiled expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Literal value.
+    Literal(Value),
+    /// Reference to an annotated field.
+    Field(String),
+    /// Logical negation.
+    Not(Box<Expr>),
+    /// Logical conjunction.
+    And(Box<Expr>, Box<Expr>),
+    /// Logical disjunction.
+    Or(Box<Expr>, Box<Expr>),
+    /// Comparison of two operands.
+    Cmp(CmpOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluate to a value, `None` if a referenced field has no value.
+    fn value<F>(&self, lookup: &mut F) -> Option<Value>
+    where
+        F: FnMut(&str) -> Option<Value>,
+    {
+        match self {
+            Expr::Literal(value) => Some(value.clone()),
+            Expr::Field(name) => lookup(name),
+            _ => Some(Value::Bool(self.test(lookup))),
+        }
+    }
+
+    /// Evaluate as a condition.
+    fn test<F>(&self, lookup: &mut F) -> bool
+    where
+        F: FnMut(&str) -> Option<Value>,
+    {
+        match self {
+            Expr::Literal(_) | Expr::Field(_) => match self.value(lookup) {
+                Some(Value::Bool(value)) => value,
+                Some(Value::Number(value)) => value != 0.0,
+                Some(Value::String(value)) => !value.is_empty(),
+                None => false,
+            },
+            Expr::Not(expr) => !expr.test(lookup),
+            Expr::And(lhs, rhs) => lhs.test(lookup) && rhs.test(lookup),
+            Expr::Or(lhs, rhs) => lhs.test(lookup) || rhs.test(lookup),
+            Expr::Cmp(op, lhs, rhs) => match (lhs.value(lookup), rhs.value(lookup)) {
+                (Some(lhs), Some(rhs)) => op.apply(&lhs, &rhs),
+                _ => false,
+            },
+        }
+    }
+
+    /// Call `f` for each field referenced in the expression.
+    fn visit_fields<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Expr::Literal(_) => (),
+            Expr::Field(name) => f(name),
+            Expr::Not(expr) => expr.visit_fields(f),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) | Expr::Cmp(_, lhs, rhs) => {
+                lhs.visit_fields(f);
+                rhs.visit_fields(f);
+            }
+        }
+    }
+}
+
+/// Token produced by the lexer.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Ident(String),
+    And,
+    Or,
+    Not,
+    Cmp(CmpOp),
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::String(value) => write!(f, "{:?}", value),
+            Token::Ident(value) => write!(f, "{:?}", value),
+            Token::And => write!(f, "\"&&\""),
+            Token::Or => write!(f, "\"||\""),
+            Token::Not => write!(f, "\"!\""),
+            Token::Cmp(op) => write!(f, "\"{}\"", op),
+            Token::LParen => write!(f, "\"(\""),
+            Token::RParen => write!(f, "\")\""),
+        }
+    }
+}
+
+/// Split `input` into tokens with their character positions.
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, Error> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if next == Some('&') => Token::And,
+            '|' if next == Some('|') => Token::Or,
+            '<' if next == Some('=') => Token::Cmp(CmpOp::Le),
+            '>' if next == Some('=') => Token::Cmp(CmpOp::Ge),
+            '=' if next == Some('=') => Token::Cmp(CmpOp::Eq),
+            '!' if next == Some('=') => Token::Cmp(CmpOp::Ne),
+            '<' => Token::Cmp(CmpOp::Lt),
+            '>' => Token::Cmp(CmpOp::Gt),
+            '!' => Token::Not,
+            '"' | '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&other| other == c)
+                    .map(|pos| i + 1 + pos)
+                    .ok_or(Error::UnterminatedString(start))?;
+                let value = chars[i + 1..end].iter().collect::<String>();
+                i = end + 1;
+                result.push((start, Token::String(value)));
+                continue;
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric()
+                        || chars[i] == '.'
+                        || (matches!(chars[i], '+' | '-') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let text = chars[start..i].iter().collect::<String>();
+                let value = text
+                    .parse::<f64>()
+                    .map_err(|_| Error::InvalidNumber(start, text))?;
+                result.push((start, Token::Number(value)));
+                continue;
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let text = chars[start..i].iter().collect::<String>();
+                let token = match text.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Ident(text),
+                };
+                result.push((start, token));
+                continue;
+            }
+            _ => return Err(Error::UnexpectedCharacter(start, c)),
+        };
+        i += match token {
+            Token::And | Token::Or => 2,
+            Token::Cmp(CmpOp::Le | CmpOp::Ge | CmpOp::Eq | CmpOp::Ne) => 2,
+            _ => 1,
+        };
+        result.push((start, token));
+    }
+    Ok(result)
+}
+
+/// Recursive descent parser over the tokens.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), Error> {
+        let result = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(Error::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(result)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.parse_unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Error> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            Ok(Expr::Not(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_comparison()
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, Error> {
+        let lhs = self.parse_operand()?;
+        if let Some(Token::Cmp(op)) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let rhs = self.parse_operand()?;
+            Ok(Expr::Cmp(op, Box::new(lhs), Box::new(rhs)))
+        } else {
+            Ok(lhs)
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr, Error> {
+        let (pos, token) = self.next()?;
+        match token {
+            Token::Number(value) => Ok(Expr::Literal(Value::Number(value))),
+            Token::String(value) => Ok(Expr::Literal(Value::String(value))),
+            Token::Ident(name) => Ok(match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                _ => Expr::Field(name),
+            }),
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                match self.next()? {
+                    (_, Token::RParen) => Ok(expr),
+                    (pos, token) => Err(Error::UnexpectedToken(pos, token.to_string())),
+                }
+            }
+            _ => Err(Error::UnexpectedToken(pos, token.to_string())),
+        }
+    }
+}
+
+/// A compiled expression together with its source text.
+///
+/// Serializes to and deserializes from the source text.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    /// The source text.
+    source: String,
+    /// The compiled expression.
+    expr: Expr,
+}
+
+impl Expression {
+    /// Compile the expression from `source`.
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if let Some((pos, token)) = parser.tokens.get(parser.pos) {
+            return Err(Error::UnexpectedToken(*pos, token.to_string()));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// Return the source text.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Return the names of the fields referenced in the expression, in order of appearance.
+    pub fn fields(&self) -> Vec<&str> {
+        let mut result = Vec::new();
+        self.expr.visit_fields(&mut |name| {
+            if !result.contains(&name) {
+                result.push(name)
+            }
+        });
+        result
+    }
+
+    /// Ensure that all referenced fields are in `known`.
+    pub fn check_fields(&self, known: &[&str]) -> Result<(), Error> {
+        match self.fields().into_iter().find(|name| !known.contains(name)) {
+            Some(name) => Err(Error::UnknownField(name.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluate the expression, obtaining field values from `lookup`.
+    pub fn eval<F>(&self, mut lookup: F) -> bool
+    where
+        F: FnMut(&str) -> Option<Value>,
+    {
+        self.expr.test(&mut lookup)
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Expression {}
+
+impl std::str::FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl serde::Serialize for Expression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Self::parse(&source).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Expression, Value};
+
+    fn lookup(name: &str) -> Option<Value> {
+        match name {
+            "gnomad_af" => Some(Value::Number(0.0005)),
+            "cadd_phred" => Some(Value::Number(21.0)),
+            "spliceai" => Some(Value::Number(0.8)),
+            "chrom" => Some(Value::from("X")),
+            "flag" => Some(Value::Bool(true)),
+            _ => None,
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("gnomad_af < 0.001", true)]
+    #[case("gnomad_af >= 1e-3", false)]
+    #[case("gnomad_af < 0.001 && (cadd_phred > 25 || spliceai > 0.5)", true)]
+    #[case("gnomad_af < 0.001 && cadd_phred > 25 || spliceai > 0.9", false)]
+    #[case("gnomad_af < 0.001 and not (cadd_phred > 25)", true)]
+    #[case("!(chrom == 'X')", false)]
+    #[case("chrom != \"Y\"", true)]
+    #[case("flag", true)]
+    #[case("flag == false", false)]
+    #[case("revel > 0.5", false)]
+    #[case("!(revel > 0.5)", true)]
+    #[case("chrom > 1", false)]
+    fn eval(#[case] source: &str, #[case] expected: bool) {
+        let expression = Expression::parse(source).unwrap();
+        assert_eq!(expression.eval(lookup), expected, "{}", source);
+    }
+
+    #[rstest::rstest]
+    #[case("gnomad_af <", Error::UnexpectedEnd)]
+    #[case("(gnomad_af < 1", Error::UnexpectedEnd)]
+    #[case("gnomad_af < 1)", Error::UnexpectedToken(13, "\")\"".to_string()))]
+    #[case("gnomad_af = 1", Error::UnexpectedCharacter(10, '='))]
+    #[case("chrom == 'X", Error::UnterminatedString(9))]
+    #[case("gnomad_af < 1.2.3", Error::InvalidNumber(12, "1.2.3".to_string()))]
+    #[case("&& gnomad_af", Error::UnexpectedToken(0, "\"&&\"".to_string()))]
+    fn parse_error(#[case] source: &str, #[case] expected: Error) {
+        assert_eq!(Expression::parse(source).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn fields() {
+        let expression =
+            Expression::parse("gnomad_af < 0.001 && (cadd_phred > 25 || gnomad_af > 0)").unwrap();
+        assert_eq!(expression.fields(), vec!["gnomad_af", "cadd_phred"]);
+        assert!(expression
+            .check_fields(&["gnomad_af", "cadd_phred"])
+            .is_ok());
+        assert_eq!(
+            expression.check_fields(&["gnomad_af"]),
+            Err(Error::UnknownField("cadd_phred".to_string()))
+        );
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let expression: Expression = serde_json::from_str("\"gnomad_af < 0.001\"").unwrap();
+        assert_eq!(
+            serde_json::to_string(&expression).unwrap(),
+            "\"gnomad_af < 0.001\""
+        );
+        assert!(serde_json::from_str::<Expression>("\"gnomad_af <\"").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Fingerprint of the effective query settings for reproducibility.
+//!
+//! The fingerprint is the hex-encoded SHA-1 digest of a canonical JSON document with
+//! the settings and the software and database versions.  Object keys are sorted and no
+//! whitespace is used, so the fingerprint does not depend on the field order of the
+//! query JSON file or on the formatting.
+
+use sha1::Digest as _;
+
+/// Compute the fingerprint of `settings` and the `versions` as `(name, version)` pairs.
+///
+/// The order of `versions` does not matter.
+pub fn settings_fingerprint<T: serde::Serialize>(
+    settings: &T,
+    versions: &[(&str, &str)],
+) -> Result<String, anyhow::Error> {
+    let mut versions = versions.to_vec();
+    versions.sort();
+    let document = serde_json::json!({
+        "settings": serde_json::to_value(settings)
+            .map_err(|e| anyhow::anyhow!("could not serialize settings: {}", e))?,
+        "versions": versions
+            .iter()
+            .map(|(name, version)| serde_json::json!({"name": name, "version": version}))
+            .collect::<Vec<_>>(),
+    });
+    let canonical = serde_json::to_string(&super::canonical_json::sort_keys(document))?;
+
+    let digest = sha1::Sha1::digest(canonical.as_bytes());
+    Ok(base16ct::lower::encode_string(&digest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::settings_fingerprint;
+
+    #[test]
+    fn canonical_key_order() -> Result<(), anyhow::Error> {
+        let a: serde_json::Value = serde_json::from_str(r#"{"b": 1, "a": {"y": 2, "x": [3]}}"#)?;
+        let b: serde_json::Value = serde_json::from_str(r#"{"a": {"x": [3], "y": 2}, "b": 1}"#)?;
+
+        assert_eq!(
+            settings_fingerprint(&a, &[("worker", "1.0"), ("cadd", "1.7")])?,
+            settings_fingerprint(&b, &[("cadd", "1.7"), ("worker", "1.0")])?,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn depends_on_settings_and_versions() -> Result<(), anyhow::Error> {
+        let settings = serde_json::json!({"max_af": 0.01});
+        let fingerprint = settings_fingerprint(&settings, &[("cadd", "1.7")])?;
+
+        assert_eq!(fingerprint.len(), 40);
+        assert_ne!(
+            fingerprint,
+            settings_fingerprint(&serde_json::json!({"max_af": 0.02}), &[("cadd", "1.7")])?
+        );
+        assert_ne!(
+            fingerprint,
+            settings_fingerprint(&settings, &[("cadd", "1.6")])?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+//! Consistency checks between the metadata of ingested VCF headers and query arguments,
+//! and of the contig lengths of input VCF headers and the genome release.
+//!
+//! Metadata that is missing from the header (e.g., for files from older ingest
+//! versions) is not checked; a warning is logged instead.
+
+use ::noodles::vcf;
+
+use super::{
+    errors::{ClassifyExt as _, ErrorClass},
+    GenomeRelease,
+};
+
+/// Error type for `check()`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("VCF header has genome build {header} but query uses --genome-release {expected}")]
+    GenomeRelease {
+        header: GenomeRelease,
+        expected: GenomeRelease,
+    },
+    #[error("invalid genome build in VCF header: {0}")]
+    InvalidGenomeRelease(String),
+    #[error("VCF header has case UUID {header} but query uses --case-uuid {expected}")]
+    CaseUuid {
+        header: uuid::Uuid,
+        expected: uuid::Uuid,
+    },
+    #[error("invalid x-varfish-case-uuid in VCF header: {0}")]
+    InvalidCaseUuid(String),
+    #[error("query samples {missing:?} not found in VCF header samples {header:?}")]
+    MissingSamples {
+        missing: Vec<String>,
+        header: Vec<String>,
+    },
+    #[error(
+        "VCF header has contig {contig} with length {header} but it has length {expected} in \
+        {genome_release}, the file is probably from another genome build"
+    )]
+    ContigLength {
+        contig: String,
+        header: usize,
+        expected: usize,
+        genome_release: GenomeRelease,
+    },
+}
+
+/// Return the first value of the unstructured header record `key`, if any.
+fn unstructured_value<'a>(header: &'a vcf::Header, key: &str) -> Option<&'a str> {
+    match header.other_records().get(key)? {
+        vcf::header::record::value::Collection::Unstructured(lines) => {
+            lines.first().map(|line| line.as_str())
+        }
+        vcf::header::record::value::Collection::Structured(_) => None,
+    }
+}
+
+/// Return the genome build from `##x-varfish-genome-build` or the contig assemblies.
+fn header_genome_release(header: &vcf::Header) -> Result<Option<GenomeRelease>, Error> {
+    let value = unstructured_value(header, "x-varfish-genome-build").or_else(|| {
+        header
+            .contigs()
+            .values()
+            .find_map(|contig| contig.other_fields().get("assembly"))
+            .map(|assembly| assembly.as_str())
+    });
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| Error::InvalidGenomeRelease(value.to_string()))
+        })
+        .transpose()
+}
+
+/// Return the case UUID from `##x-varfish-case-uuid`, if any.
+pub fn header_case_uuid(header: &vcf::Header) -> Result<Option<uuid::Uuid>, Error> {
+    unstructured_value(header, "x-varfish-case-uuid")
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| Error::InvalidCaseUuid(value.to_string()))
+        })
+        .transpose()
+}
+
+/// Check `header` of an ingested VCF file against the query's `genome_release`,
+/// optional `case_uuid`, and `samples`.
+///
+/// # Errors
+///
+/// Returns the first inconsistency found.
+pub fn check<'a, I>(
+    header: &vcf::Header,
+    genome_release: GenomeRelease,
+    case_uuid: Option<uuid::Uuid>,
+    samples: I,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    match header_genome_release(header)? {
+        Some(header_release) if header_release != genome_release => {
+            return Err(Error::GenomeRelease {
+                header: header_release,
+                expected: genome_release,
+            });
+        }
+        Some(_) => (),
+        None => tracing::warn!("no genome build in VCF header, cannot check --genome-release"),
+    }
+
+    if let Some(expected) = case_uuid {
+        match header_case_uuid(header)? {
+            Some(header_uuid) => {
+                if header_uuid != expected {
+                    return Err(Error::CaseUuid {
+                        header: header_uuid,
+                        expected,
+                    });
+                }
+            }
+            None => {
+                tracing::warn!("no x-varfish-case-uuid in VCF header, cannot check --case-uuid")
+            }
+        }
+    }
+
+    let missing = samples
+        .into_iter()
+        .filter(|sample| !header.sample_names().contains(*sample))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Error::MissingSamples {
+            missing,
+            header: header.sample_names().iter().cloned().collect(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check the lengths of the canonical contigs in the `##contig` lines of `header`
+/// against `genome_release`.
+///
+/// Contigs without a length and non-canonical contigs are not checked.  The names are
+/// matched with and without `chr` prefix, such that, e.g., hg19 files with `chrM` of
+/// length 16571 are caught for GRCh37.
+///
+/// # Errors
+///
+/// Returns the first mismatch found.
+pub fn check_contig_lengths(
+    header: &vcf::Header,
+    genome_release: GenomeRelease,
+) -> Result<(), Error> {
+    let chrom_map = super::build_chrom_map();
+    let expected_lengths = genome_release.contig_lengths();
+    for (name, contig) in header.contigs() {
+        let (Some(idx), Some(length)) = (chrom_map.get(name.as_str()), contig.length()) else {
+            continue;
+        };
+        let (_, expected) = expected_lengths[*idx];
+        if length != expected {
+            return Err(Error::ContigLength {
+                contig: name.to_string(),
+                header: length,
+                expected,
+                genome_release,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the contig lengths of `header` of the input file `path` as by
+/// `check_contig_lengths()`, only logging a warning on mismatches if `lenient`.
+pub fn check_input_contig_lengths(
+    header: &vcf::Header,
+    genome_release: GenomeRelease,
+    path: &str,
+    lenient: bool,
+) -> Result<(), anyhow::Error> {
+    match check_contig_lengths(header, genome_release) {
+        Ok(()) => Ok(()),
+        Err(e) if lenient => {
+            tracing::warn!("{} in {} (ignored because of --lenient)", e, path);
+            Ok(())
+        }
+        Err(e) => Err(anyhow::anyhow!(
+            "inconsistent input file {}: {} (use --lenient to only warn)",
+            path,
+            e
+        ))
+        .classify(ErrorClass::Input),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, check_contig_lengths, Error};
+    use crate::common::GenomeRelease;
+
+    const CASE_UUID: &str = "11111111-2222-3333-4444-555555555555";
+
+    /// Build header with the given extra meta lines and samples `index` and `father`.
+    fn build_header(meta: &str) -> noodles::vcf::Header {
+        format!(
+            "##fileformat=VCFv4.2\n{}#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tindex\tfather\n",
+            meta
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn consistent() {
+        let header = build_header(&format!(
+            "##x-varfish-genome-build=GRCh37\n##x-varfish-case-uuid={}\n",
+            CASE_UUID
+        ));
+
+        assert_eq!(
+            check(
+                &header,
+                GenomeRelease::Grch37,
+                Some(CASE_UUID.parse().unwrap()),
+                ["index", "father"],
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn missing_metadata_is_not_checked() {
+        let header = build_header("");
+
+        assert_eq!(
+            check(
+                &header,
+                GenomeRelease::Grch38,
+                Some(uuid::Uuid::nil()),
+                ["index"],
+            ),
+            Ok(())
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::build_header("##x-varfish-genome-build=GRCh38\n")]
+    #[case::contig_assembly("##contig=<ID=1,length=1000,assembly=\"GRCh38\">\n")]
+    fn genome_release_mismatch(#[case] meta: &str) {
+        let header = build_header(meta);
+
+        assert_eq!(
+            check(&header, GenomeRelease::Grch37, None, ["index"]),
+            Err(Error::GenomeRelease {
+                header: GenomeRelease::Grch38,
+                expected: GenomeRelease::Grch37,
+            })
+        );
+    }
+
+    #[test]
+    fn case_uuid_mismatch() {
+        let header = build_header(&format!("##x-varfish-case-uuid={}\n", CASE_UUID));
+
+        assert_eq!(
+            check(
+                &header,
+                GenomeRelease::Grch37,
+                Some(uuid::Uuid::nil()),
+                ["index"]
+            ),
+            Err(Error::CaseUuid {
+                header: CASE_UUID.parse().unwrap(),
+                expected: uuid::Uuid::nil(),
+            })
+        );
+    }
+
+    #[test]
+    fn missing_samples() {
+        let header = build_header("");
+
+        assert_eq!(
+            check(&header, GenomeRelease::Grch37, None, ["index", "mother"]),
+            Err(Error::MissingSamples {
+                missing: vec![String::from("mother")],
+                header: vec![String::from("index"), String::from("father")],
+            })
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::grch37("##contig=<ID=1,length=249250621>\n##contig=<ID=MT,length=16569>\n")]
+    #[case::no_length("##contig=<ID=MT>\n")]
+    #[case::non_canonical("##contig=<ID=GL000192.1,length=547496>\n")]
+    fn contig_lengths_consistent(#[case] meta: &str) {
+        let header = build_header(meta);
+
+        assert_eq!(check_contig_lengths(&header, GenomeRelease::Grch37), Ok(()));
+    }
+
+    #[rstest::rstest]
+    #[case::hg19(
+        "##contig=<ID=chr1,length=249250621>\n##contig=<ID=chrM,length=16571>\n",
+        "chrM",
+        16571,
+        16569
+    )]
+    #[case::grch38("##contig=<ID=chr1,length=248956422>\n", "chr1", 248956422, 249250621)]
+    fn contig_lengths_mismatch(
+        #[case] meta: &str,
+        #[case] contig: &str,
+        #[case] header_length: usize,
+        #[case] expected: usize,
+    ) {
+        let header = build_header(meta);
+
+        assert_eq!(
+            check_contig_lengths(&header, GenomeRelease::Grch37),
+            Err(Error::ContigLength {
+                contig: contig.to_string(),
+                header: header_length,
+                expected,
+                genome_release: GenomeRelease::Grch37,
+            })
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Optional heap profiling at stage boundaries of long-running pipelines.
+//!
+//! Heap profile dumps require building with the `heap-profile` feature which makes
+//! jemalloc the global allocator.  Without the feature, only the RSS is reported.
+
+use byte_unit::Byte;
+
+/// Format `bytes` with an appropriate unit.
+fn fmt_bytes(bytes: u64) -> String {
+    format!(
+        "{:.2}",
+        Byte::from_u64(bytes).get_appropriate_unit(byte_unit::UnitType::Decimal)
+    )
+}
+
+/// Helper that reports allocation statistics and dumps heap profiles at stage boundaries.
+#[derive(Debug, Default)]
+pub struct HeapProfiler {
+    /// Path prefix for the heap profile dumps; profiling is disabled if `None`.
+    prefix: Option<String>,
+    /// Number of stages seen so far.
+    count: usize,
+}
+
+impl HeapProfiler {
+    /// Create a new profiler, activating heap profiling if `prefix` is given.
+    pub fn new(prefix: Option<String>) -> Result<Self, anyhow::Error> {
+        if prefix.is_some() {
+            imp::activate()?;
+        }
+        Ok(Self { prefix, count: 0 })
+    }
+
+    /// Report statistics and dump heap profile after the stage with the given `name`.
+    ///
+    /// The profile is written to `{prefix}.{count:02}.{name}.heap`.
+    pub fn stage(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        let Some(prefix) = self.prefix.as_ref() else {
+            return Ok(());
+        };
+        self.count += 1;
+        tracing::info!("heap stats after stage {}: {}", name, imp::stats()?);
+        let path = format!("{}.{:02}.{}.heap", prefix, self.count, name);
+        imp::dump(&path)
+    }
+}
+
+/// Implementation based on jemalloc.
+#[cfg(feature = "heap-profile")]
+mod imp {
+    use tikv_jemalloc_ctl::{epoch, raw, stats};
+
+    use super::fmt_bytes;
+
+    /// Activate profiling; requires `prof:true` in the jemalloc configuration.
+    pub fn activate() -> Result<(), anyhow::Error> {
+        // SAFETY: `prof.active` is a boolean option.
+        unsafe { raw::write(b"prof.active\0", true) }
+            .map_err(|e| anyhow::anyhow!("could not activate heap profiling: {}", e))
+    }
+
+    /// Return allocation statistics as a string.
+    pub fn stats() -> Result<String, anyhow::Error> {
+        epoch::advance().map_err(|e| anyhow::anyhow!("could not advance epoch: {}", e))?;
+        let allocated = stats::allocated::read()
+            .map_err(|e| anyhow::anyhow!("could not read allocated bytes: {}", e))?;
+        let resident = stats::resident::read()
+            .map_err(|e| anyhow::anyhow!("could not read resident bytes: {}", e))?;
+        Ok(format!(
+            "allocated={}, resident={}",
+            fmt_bytes(allocated as u64),
+            fmt_bytes(resident as u64)
+        ))
+    }
+
+    /// Dump heap profile to `path`.
+    pub fn dump(path: &str) -> Result<(), anyhow::Error> {
+        tracing::info!("writing heap profile to {}", path);
+        let c_path = std::ffi::CString::new(path)
+            .map_err(|e| anyhow::anyhow!("invalid heap profile path {}: {}", path, e))?;
+        // SAFETY: `prof.dump` takes a NUL-terminated path that outlives the call.
+        unsafe { raw::write(b"prof.dump\0", c_path.as_ptr()) }
+            .map_err(|e| anyhow::anyhow!("could not write heap profile to {}: {}", path, e))
+    }
+}
+
+/// Fallback implementation reporting the RSS only.
+#[cfg(not(feature = "heap-profile"))]
+mod imp {
+    use super::fmt_bytes;
+
+    /// Warn that profiling is not available.
+    pub fn activate() -> Result<(), anyhow::Error> {
+        tracing::warn!("built without `heap-profile` feature, will only report RSS");
+        Ok(())
+    }
+
+    /// Return the RSS as a string.
+    pub fn stats() -> Result<String, anyhow::Error> {
+        let rss =
+            crate::common::rss_size().map_err(|e| anyhow::anyhow!("could not get RSS: {}", e))?;
+        Ok(format!("rss={}", fmt_bytes(rss)))
+    }
+
+    /// No-op as profiling is not available.
+    pub fn dump(_path: &str) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn heap_profiler_disabled_smoke() -> Result<(), anyhow::Error> {
+        let mut profiler = super::HeapProfiler::new(None)?;
+        profiler.stage("iterate")?;
+        assert_eq!(profiler.count, 0);
+
+        Ok(())
+    }
+}
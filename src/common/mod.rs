@@ -9,8 +9,19 @@ use clap::Parser;
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use indexmap::IndexMap;
 
+pub mod batch;
+pub mod canonical_json;
+pub mod errors;
+pub mod estimate;
+pub mod expression;
+pub mod fingerprint;
+pub mod header_check;
+pub mod heap_profile;
 pub mod noodles;
+pub mod ped;
+pub mod query_json;
 pub mod s3;
+pub mod telemetry;
 
 /// Commonly used command line arguments.
 #[derive(Parser, Debug)]
@@ -18,12 +29,39 @@ pub struct Args {
     /// Verbosity of the program
     #[clap(flatten)]
     pub verbose: Verbosity<InfoLevel>,
+    /// Number of worker threads for decompressing bgzf input files.
+    #[arg(long, global = true, default_value_t = 1)]
+    pub threads: usize,
+    /// Fail on conditions that are otherwise only warned about, e.g., unmappable genes
+    /// or missing coverage files.
+    #[arg(long, global = true, default_value_t = false)]
+    pub strict: bool,
+    /// Optional OTLP endpoint to export tracing spans to, e.g., `http://localhost:4317`;
+    /// export requires building with the `otel` feature.
+    #[arg(long, global = true)]
+    pub otel_endpoint: Option<String>,
+    /// Size of the parts of multipart uploads to S3 in MiB, at least 5.
+    #[arg(long, global = true, default_value_t = 64)]
+    pub s3_part_size: u64,
+    /// Number of attempts for each request when uploading to S3.
+    #[arg(long, global = true, default_value_t = 3)]
+    pub s3_upload_attempts: u32,
+    /// Delay in seconds before retrying a failed request to S3, doubled for each
+    /// further retry.
+    #[arg(long, global = true, default_value_t = 1.0)]
+    pub s3_retry_delay: f64,
 }
 
 impl Default for Args {
     fn default() -> Self {
         Self {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         }
     }
 }
@@ -45,6 +83,12 @@ pub fn rss_size() -> Result<u64, procfs::ProcError> {
     Ok(stat.rss * page_size)
 }
 
+/// Obtain peak RSS size in bytes, if known to the kernel.
+pub fn peak_rss_size() -> Result<Option<u64>, procfs::ProcError> {
+    let me = procfs::process::Process::myself()?;
+    Ok(me.status()?.vmhwm.map(|kib| kib * 1024))
+}
+
 /// Helper to print the current memory resident set size via `tracing`.
 pub fn trace_rss_now() {
     tracing::debug!(
@@ -61,6 +105,64 @@ pub const CHROMS: &[&str] = &[
     "18", "19", "20", "21", "22", "X", "Y", "M",
 ];
 
+/// Contig names and lengths of GRCh37, in the order of `CHROMS`.
+pub const CONTIG_LENGTHS_37: &[(&str, usize); 25] = &[
+    ("1", 249250621),
+    ("2", 243199373),
+    ("3", 198022430),
+    ("4", 191154276),
+    ("5", 180915260),
+    ("6", 171115067),
+    ("7", 159138663),
+    ("8", 146364022),
+    ("9", 141213431),
+    ("10", 135534747),
+    ("11", 135006516),
+    ("12", 133851895),
+    ("13", 115169878),
+    ("14", 107349540),
+    ("15", 102531392),
+    ("16", 90354753),
+    ("17", 81195210),
+    ("18", 78077248),
+    ("19", 59128983),
+    ("20", 63025520),
+    ("21", 48129895),
+    ("22", 51304566),
+    ("X", 155270560),
+    ("Y", 59373566),
+    ("MT", 16569),
+];
+
+/// Contig names and lengths of GRCh38, in the order of `CHROMS`.
+pub const CONTIG_LENGTHS_38: &[(&str, usize); 25] = &[
+    ("chr1", 248956422),
+    ("chr2", 242193529),
+    ("chr3", 198295559),
+    ("chr4", 190214555),
+    ("chr5", 181538259),
+    ("chr6", 170805979),
+    ("chr7", 159345973),
+    ("chr8", 145138636),
+    ("chr9", 138394717),
+    ("chr10", 133797422),
+    ("chr11", 135086622),
+    ("chr12", 133275309),
+    ("chr13", 114364328),
+    ("chr14", 107043718),
+    ("chr15", 101991189),
+    ("chr16", 90338345),
+    ("chr17", 83257441),
+    ("chr18", 80373285),
+    ("chr19", 58617616),
+    ("chr20", 64444167),
+    ("chr21", 46709983),
+    ("chr22", 50818468),
+    ("chrX", 156040895),
+    ("chrY", 57227415),
+    ("chrM", 16569),
+];
+
 /// Build mapping of chromosome names to chromosome counts.
 pub fn build_chrom_map() -> IndexMap<String, usize> {
     let mut result = IndexMap::new();
@@ -147,6 +249,14 @@ impl GenomeRelease {
             GenomeRelease::Grch38 => String::from("GRCh38"),
         }
     }
+
+    /// Return the names and lengths of the canonical contigs, in the order of `CHROMS`.
+    pub fn contig_lengths(&self) -> &'static [(&'static str, usize); 25] {
+        match self {
+            GenomeRelease::Grch37 => CONTIG_LENGTHS_37,
+            GenomeRelease::Grch38 => CONTIG_LENGTHS_38,
+        }
+    }
 }
 
 impl From<GenomeRelease> for Assembly {
@@ -207,15 +317,96 @@ pub fn strip_gt_leading_slash(gt_str: &str) -> &str {
 impl std::str::FromStr for Genotype {
     type Err = anyhow::Error;
 
+    /// Parse genotype string, any non-reference allele is counted as alternate allele.
+    fn from_str(gt_str: &str) -> Result<Self, Self::Err> {
+        Ok(gt_str.parse::<ParsedGenotype>()?.genotype())
+    }
+}
+
+/// Structured information from parsing a haploid or diploid `GT` value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedGenotype {
+    /// The allele indices, `None` for no-call (`.`).
+    pub alleles: Vec<Option<usize>>,
+    /// Whether the genotype is phased, i.e., uses `|` as the separator.
+    pub phased: bool,
+}
+
+impl ParsedGenotype {
+    /// Return the ploidy.
+    pub fn ploidy(&self) -> usize {
+        self.alleles.len()
+    }
+
+    /// Return whether any allele is a no-call.
+    pub fn is_no_call(&self) -> bool {
+        self.alleles.iter().any(Option::is_none)
+    }
+
+    /// Return the `Genotype` counting all non-reference alleles as alternate.
+    ///
+    /// After allele splitting, there should only be index 1 but e.g., `1/2` can remain
+    /// in records that were not normalized properly.  We count these as hom. alt.
+    pub fn genotype(&self) -> Genotype {
+        self.to_genotype(|allele| allele != 0)
+    }
+
+    /// Return the `Genotype` with respect to the alternate allele `allele_no`.
+    ///
+    /// All other alleles, including other alternate alleles, are counted as reference,
+    /// consistent with how `seqvars ingest` splits multi-allelic records.
+    pub fn genotype_for_allele(&self, allele_no: usize) -> Genotype {
+        self.to_genotype(|allele| allele == allele_no)
+    }
+
+    /// Build `Genotype` given predicate whether an allele is alternate.
+    fn to_genotype<F: Fn(usize) -> bool>(&self, is_alt: F) -> Genotype {
+        if self.is_no_call() {
+            return Genotype::WithNoCall;
+        }
+        let alt_count = self
+            .alleles
+            .iter()
+            .flatten()
+            .filter(|allele| is_alt(**allele))
+            .count();
+        match (self.ploidy(), alt_count) {
+            (_, 0) => Genotype::HomRef,
+            (1, _) | (2, 2) => Genotype::HomAlt,
+            _ => Genotype::Het,
+        }
+    }
+}
+
+impl std::str::FromStr for ParsedGenotype {
+    type Err = anyhow::Error;
+
     fn from_str(gt_str: &str) -> Result<Self, Self::Err> {
         let gt_str = strip_gt_leading_slash(gt_str);
-        Ok(match gt_str {
-            "0/0" | "0|0" | "0" => Genotype::HomRef,
-            "0/1" | "1/0" | "0|1" | "1|0" => Genotype::Het,
-            "1/1" | "1|1" | "1" => Genotype::HomAlt,
-            "./." | "./0" | "./1" | "0/." | "1/." => Genotype::WithNoCall,
-            _ => anyhow::bail!("invalid genotype value: {:?}", gt_str),
-        })
+        if gt_str.is_empty() {
+            anyhow::bail!("empty genotype value");
+        }
+        let phased = gt_str.contains('|');
+        if phased && gt_str.contains('/') {
+            anyhow::bail!("mixed phased and unphased genotype value: {:?}", gt_str);
+        }
+        let alleles = gt_str
+            .split(['/', '|'])
+            .map(|allele| match allele {
+                "." => Ok(None),
+                _ => allele
+                    .parse::<usize>()
+                    .map(Some)
+                    .map_err(|e| anyhow::anyhow!("invalid genotype value: {:?}: {}", gt_str, e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if alleles.len() > 2 {
+            anyhow::bail!(
+                "only haploid and diploid genotypes are supported: {:?}",
+                gt_str
+            );
+        }
+        Ok(Self { alleles, phased })
     }
 }
 
@@ -238,6 +429,56 @@ impl std::str::FromStr for Chrom {
     }
 }
 
+/// Special region on the gonosomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GonosomalRegion {
+    /// Pseudoautosomal region 1.
+    Par1,
+    /// Pseudoautosomal region 2.
+    Par2,
+    /// X-transposed region; X-Y identity is lower than in the PARs and the boundaries
+    /// are approximate.
+    Xtr,
+}
+
+impl GonosomalRegion {
+    /// Return the region at `chrom` and `pos` (1-based) for `genome_release`, if any.
+    pub fn from_pos(chrom: Chrom, pos: usize, genome_release: GenomeRelease) -> Option<Self> {
+        let regions: [(Self, std::ops::RangeInclusive<usize>); 3] = match (chrom, genome_release) {
+            (Chrom::X, GenomeRelease::Grch37) => [
+                (Self::Par1, 60001..=2699520),
+                (Self::Par2, 154931044..=155260560),
+                (Self::Xtr, 88400001..=92000000),
+            ],
+            (Chrom::X, GenomeRelease::Grch38) => [
+                (Self::Par1, 10001..=2781479),
+                (Self::Par2, 155701383..=156030895),
+                (Self::Xtr, 89145001..=92745000),
+            ],
+            (Chrom::Y, GenomeRelease::Grch37) => [
+                (Self::Par1, 10001..=2649520),
+                (Self::Par2, 59034050..=59363566),
+                (Self::Xtr, 2917959..=6102616),
+            ],
+            (Chrom::Y, GenomeRelease::Grch38) => [
+                (Self::Par1, 10001..=2781479),
+                (Self::Par2, 56887903..=57217415),
+                (Self::Xtr, 3049918..=6234575),
+            ],
+            (Chrom::Auto, _) => return None,
+        };
+        regions
+            .into_iter()
+            .find(|(_, range)| range.contains(&pos))
+            .map(|(region, _)| region)
+    }
+
+    /// Whether the region is one of the pseudoautosomal regions.
+    pub fn is_par(&self) -> bool {
+        matches!(self, Self::Par1 | Self::Par2)
+    }
+}
+
 /// The version of `varfish-server-worker` package.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -246,6 +487,33 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[rstest::rstest]
+    #[case(Chrom::X, 60001, GenomeRelease::Grch37, Some(GonosomalRegion::Par1))]
+    #[case(Chrom::X, 88400000, GenomeRelease::Grch37, None)]
+    #[case(Chrom::X, 88400001, GenomeRelease::Grch37, Some(GonosomalRegion::Xtr))]
+    #[case(Chrom::X, 92000000, GenomeRelease::Grch37, Some(GonosomalRegion::Xtr))]
+    #[case(Chrom::X, 92000001, GenomeRelease::Grch37, None)]
+    #[case(Chrom::X, 89145001, GenomeRelease::Grch38, Some(GonosomalRegion::Xtr))]
+    #[case(
+        Chrom::X,
+        156030895,
+        GenomeRelease::Grch38,
+        Some(GonosomalRegion::Par2)
+    )]
+    #[case(Chrom::Y, 3049918, GenomeRelease::Grch38, Some(GonosomalRegion::Xtr))]
+    #[case(Chrom::Auto, 90000000, GenomeRelease::Grch37, None)]
+    fn gonosomal_region_from_pos(
+        #[case] chrom: Chrom,
+        #[case] pos: usize,
+        #[case] genome_release: GenomeRelease,
+        #[case] expected: Option<GonosomalRegion>,
+    ) {
+        assert_eq!(
+            GonosomalRegion::from_pos(chrom, pos, genome_release),
+            expected
+        );
+    }
+
     #[test]
     fn numeric_gene_id_simple() -> Result<(), anyhow::Error> {
         assert_eq!(1, numeric_gene_id("ENSG0000000001")?);
@@ -265,6 +533,21 @@ pub fn worker_version() -> &'static str {
     }
 }
 
+/// Return the name-based (version 5) UUID of `name` in `namespace`.
+///
+/// This is used for deterministic record UUIDs that do not change between runs.
+pub fn uuid_v5(namespace: &uuid::Uuid, name: &str) -> uuid::Uuid {
+    use sha1::Digest as _;
+
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(namespace.as_bytes());
+    hasher.update(name.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_sha1_bytes(bytes).into_uuid()
+}
+
 /// Add contigs for GRCh37.
 pub fn add_contigs_37(
     builder: vcf::header::Builder,
@@ -331,7 +614,13 @@ pub fn add_contigs_37(
     Ok(builder)
 }
 
+/// Family name used for individuals without `Family` field in the `PEDIGREE` header.
+pub const DEFAULT_FAMILY: &str = "FAM";
+
 /// Extract a PedigreeByName from the VCF header.
+///
+/// The family of each individual is read from the optional `Family` field of the
+/// `PEDIGREE` header records, so files may contain more than one family.
 pub fn extract_pedigree_and_case_uuid(
     header: &vcf::Header,
 ) -> Result<(mehari::ped::PedigreeByName, uuid::Uuid), anyhow::Error> {
@@ -371,7 +660,7 @@ pub fn extract_pedigree_and_case_uuid(
             pedigree.individuals.insert(
                 sample_name.clone(),
                 mehari::ped::Individual {
-                    family: "FAM".into(),
+                    family: DEFAULT_FAMILY.into(),
                     name: sample_name.clone(),
                     sex: sex_value,
                     disease: disease_value,
@@ -387,12 +676,16 @@ pub fn extract_pedigree_and_case_uuid(
         .ok_or_else(|| anyhow::anyhow!("no PEDIGREE record in VCF header"))?
     {
         for (sample_name, pedigree_values) in sample_map.iter() {
+            let family_value = pedigree_values.other_fields().get("Family");
             let father_value = pedigree_values.other_fields().get("Father");
             let mother_value = pedigree_values.other_fields().get("Mother");
 
             let individual = pedigree.individuals.get_mut(sample_name).ok_or_else(|| {
                 anyhow::anyhow!("individual {} not found in SAMPLE header", sample_name)
             })?;
+            if let Some(family_value) = family_value {
+                individual.family = family_value.clone();
+            }
             if let Some(father_value) = father_value {
                 individual.father = Some(father_value.clone());
             }
@@ -523,6 +816,20 @@ where
     Ok(tmp)
 }
 
+/// Return the genotype of `sample`; missing `GT` values are treated as no-call.
+pub fn sample_genotype(
+    sample: &vcf::variant::record_buf::samples::Sample<'_>,
+) -> Result<Genotype, anyhow::Error> {
+    use vcf::variant::record::samples::keys::key;
+    if let Some(Some(vcf::variant::record_buf::samples::sample::value::Value::Genotype(gt))) =
+        sample.get(key::GENOTYPE)
+    {
+        std::str::FromStr::from_str(&genotype_to_string(&gt)?)
+    } else {
+        Ok(Genotype::WithNoCall)
+    }
+}
+
 #[cfg(test)]
 pub(crate) fn read_to_bytes<P>(path: P) -> Result<Vec<u8>, anyhow::Error>
 where
@@ -557,6 +864,10 @@ pub enum TadSet {
     /// hESC
     #[default]
     Hesc,
+    /// IMR90
+    Imr90,
+    /// Custom TADs from `--path-custom-tads`.
+    Custom,
 }
 
 #[macro_export]
@@ -669,6 +980,82 @@ mod test {
         super::trace_rss_now();
     }
 
+    #[test]
+    fn uuid_v5() {
+        assert_eq!(
+            super::uuid_v5(&uuid::Uuid::NAMESPACE_DNS, "www.example.com").to_string(),
+            "2ed6657d-e927-568b-95e1-2665a8aea6a2"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("0", super::Genotype::HomRef)]
+    #[case("1", super::Genotype::HomAlt)]
+    #[case("2", super::Genotype::HomAlt)]
+    #[case(".", super::Genotype::WithNoCall)]
+    #[case("0/0", super::Genotype::HomRef)]
+    #[case("0|0", super::Genotype::HomRef)]
+    #[case("0/1", super::Genotype::Het)]
+    #[case("1|0", super::Genotype::Het)]
+    #[case("0/2", super::Genotype::Het)]
+    #[case("1/1", super::Genotype::HomAlt)]
+    #[case("1/2", super::Genotype::HomAlt)]
+    #[case("1|2", super::Genotype::HomAlt)]
+    #[case("2/2", super::Genotype::HomAlt)]
+    #[case("./.", super::Genotype::WithNoCall)]
+    #[case(".|.", super::Genotype::WithNoCall)]
+    #[case("./1", super::Genotype::WithNoCall)]
+    #[case("0/.", super::Genotype::WithNoCall)]
+    #[case("/0/1", super::Genotype::Het)]
+    fn genotype_from_str(#[case] gt_str: &str, #[case] expected: super::Genotype) {
+        assert_eq!(gt_str.parse::<super::Genotype>().unwrap(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case("")]
+    #[case("0/1/2")]
+    #[case("0/1|2")]
+    #[case("a/1")]
+    #[case("0//1")]
+    fn genotype_from_str_invalid(#[case] gt_str: &str) {
+        assert!(gt_str.parse::<super::ParsedGenotype>().is_err());
+    }
+
+    #[rstest::rstest]
+    #[case("1/2", 1, super::Genotype::Het)]
+    #[case("1/2", 2, super::Genotype::Het)]
+    #[case("2/2", 1, super::Genotype::HomRef)]
+    #[case("2/2", 2, super::Genotype::HomAlt)]
+    #[case("0|2", 2, super::Genotype::Het)]
+    #[case("2", 2, super::Genotype::HomAlt)]
+    #[case("2", 1, super::Genotype::HomRef)]
+    fn parsed_genotype_for_allele(
+        #[case] gt_str: &str,
+        #[case] allele_no: usize,
+        #[case] expected: super::Genotype,
+    ) {
+        let parsed = gt_str.parse::<super::ParsedGenotype>().unwrap();
+        assert_eq!(parsed.genotype_for_allele(allele_no), expected);
+    }
+
+    #[test]
+    fn parsed_genotype_structure() {
+        let parsed = "1|2".parse::<super::ParsedGenotype>().unwrap();
+        assert_eq!(
+            parsed,
+            super::ParsedGenotype {
+                alleles: vec![Some(1), Some(2)],
+                phased: true,
+            }
+        );
+        assert_eq!(parsed.ploidy(), 2);
+        assert!(!parsed.is_no_call());
+
+        let parsed = "./1".parse::<super::ParsedGenotype>().unwrap();
+        assert!(!parsed.phased);
+        assert!(parsed.is_no_call());
+    }
+
     #[test]
     fn build_chrom_map_snapshot() {
         let map = super::build_chrom_map();
@@ -777,6 +1164,38 @@ mod test {
         insta::assert_debug_snapshot!(case_uuid);
     }
 
+    #[test]
+    fn extract_pedigree_multiple_families() -> Result<(), anyhow::Error> {
+        let header: noodles::vcf::Header = concat!(
+            "##fileformat=VCFv4.2\n",
+            "##SAMPLE=<ID=a,Sex=Male,Disease=Affected>\n",
+            "##SAMPLE=<ID=b,Sex=Female,Disease=Unaffected>\n",
+            "##SAMPLE=<ID=c,Sex=Female,Disease=Affected>\n",
+            "##PEDIGREE=<ID=a,Family=FAM1>\n",
+            "##PEDIGREE=<ID=b,Family=FAM2>\n",
+            "##PEDIGREE=<ID=c,Family=FAM2,Mother=b>\n",
+            "##x-varfish-case-uuid=00000000-0000-0000-0000-000000000000\n",
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ta\tb\tc\n",
+        )
+        .parse()?;
+
+        let (pedigree, _) = super::extract_pedigree_and_case_uuid(&header)?;
+        assert_eq!(
+            pedigree
+                .individuals
+                .values()
+                .map(|i| (i.name.as_str(), i.family.as_str(), i.mother.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", "FAM1", None),
+                ("b", "FAM2", None),
+                ("c", "FAM2", Some("b"))
+            ]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn file_identifier_mappings() -> Result<(), anyhow::Error> {
         let mapping = super::id_mapping::FileIdentifierMappings::load_from_json(
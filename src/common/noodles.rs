@@ -1,6 +1,7 @@
 //! Helper code for using noodles with S3.
 //!
-//! At the moment, we only support reading but not yet writing.
+//! At the moment, we only support reading but not yet writing; local output files
+//! are uploaded with `super::s3::OutputPathHelper` instead.
 //!
 //! The main reason is that the awslabs SDK for Rust currently supports easily
 //! getting an `AsyncRead` from an S3 object but not an `AsyncWrite`.  For
@@ -16,9 +17,67 @@ use noodles::csi::{self as csi, binning_index::index::reference_sequence::bin::C
 use noodles::tabix;
 use noodles::vcf;
 use noodles::vcf::variant::Record;
+use std::io::Read as _;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{path::Path, pin::Pin};
 use tokio::io::{AsyncBufRead, BufReader};
 
+/// Number of worker threads for bgzf decompression.
+static DECOMPRESSION_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+/// Set the number of worker threads to use for decompressing bgzf input files.
+pub fn set_decompression_threads(threads: usize) {
+    DECOMPRESSION_THREADS.store(threads.max(1), Ordering::Relaxed);
+}
+
+/// Return whether the file at `path` starts with a bgzf block header.
+pub fn is_bgzf<P>(path: P) -> Result<bool, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    // gzip header with FEXTRA flag set and "BC" extra subfield
+    let mut buf = [0u8; 14];
+    match std::fs::File::open(path.as_ref())?.read_exact(&mut buf) {
+        Ok(()) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    Ok(buf[0..3] == [0x1f, 0x8b, 0x08] && buf[3] & 0x04 != 0 && buf[12..14] == *b"BC")
+}
+
+/// Open local plain text or gzip file, using multi-threaded decompression for bgzf.
+///
+/// The number of worker threads is set with `set_decompression_threads()`.
+pub async fn open_read_maybe_bgzf_mt<P>(
+    path: P,
+) -> Result<Pin<Box<dyn AsyncBufRead>>, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    let threads = DECOMPRESSION_THREADS.load(Ordering::Relaxed);
+    let worker_count = match NonZeroUsize::new(threads) {
+        Some(worker_count) if threads > 1 && is_bgzf(path.as_ref()).unwrap_or(false) => {
+            worker_count
+        }
+        _ => return open_read_maybe_gz(path).await,
+    };
+
+    tracing::debug!(
+        "Opening {} as bgzf with {} worker threads (async)",
+        path.as_ref().display(),
+        threads
+    );
+    let file = tokio::fs::File::open(path.as_ref())
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {}: {}", path.as_ref().display(), e))?;
+    Ok(Box::pin(
+        bgzf::r#async::reader::Builder::default()
+            .set_worker_count(worker_count)
+            .build_with_reader(file),
+    ))
+}
+
 /// Build TBI for file at `path_src` and write to `path_dst`.
 pub async fn build_tbi<S, D>(path_src: S, path_dst: D) -> Result<(), anyhow::Error>
 where
@@ -168,48 +227,94 @@ where
     }
 }
 
-/// Helper function that opens a list of paths as VCF readers.
-pub async fn open_vcf_readers(paths: &[String]) -> Result<Vec<VariantReader>, anyhow::Error> {
-    let mut result = Vec::new();
-    for path in paths.iter() {
-        let buf_read = if super::s3::s3_mode() && !path.starts_with('/') {
-            s3_open_read_maybe_gz(path).await?
-        } else {
-            open_read_maybe_gz(path).await?
-        };
-        result.push(VariantReader::Vcf(AsyncVcfReader::new(buf_read)));
-    }
-    Ok(result)
-}
-
-/// Helper function that opens one VCF reader at the given path.
+/// Open plain text or gzip file for reading from a local file, standard input, or S3.
 ///
 /// The behaviour is as follows:
 ///
 /// - If `path_in` is "-" then open stdin and read as plain text.
+/// - If `path_in` is an `s3://bucket/key` URL then read the object with ranged requests.
 /// - If environment variable `AWS_ACCESS_KEY_ID` is set then enable S3 mode.
 /// - If `path_in` is absolute or S3 mode is disabled then open `path_in` as local file
 /// - Otherwise, attempt to open `path_in` as S3 object.
-pub async fn open_vcf_reader(path_in: &str) -> Result<VariantReader, anyhow::Error> {
-    if super::s3::s3_mode() && path_in != "-" && !path_in.starts_with('/') {
+pub async fn open_read(path_in: &str) -> Result<Pin<Box<dyn AsyncBufRead>>, anyhow::Error> {
+    if let Some((bucket, key)) = super::s3::parse_s3_url(path_in)? {
+        tracing::debug!("Opening S3 URL {} for reading (async)", path_in);
+        let reader = super::s3::open_read(bucket, key).await?;
+        if is_gz(path_in) {
+            let mut decoder = GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+            Ok(Box::pin(BufReader::new(decoder)))
+        } else {
+            Ok(reader)
+        }
+    } else if super::s3::s3_mode() && path_in != "-" && !path_in.starts_with('/') {
         tracing::debug!("Opening S3 object {} for reading (async)", path_in);
-        Ok(VariantReader::Vcf(vcf::AsyncReader::new(
-            s3_open_read_maybe_gz(path_in)
-                .await
-                .map_err(|e| anyhow::anyhow!("could not build VCF reader from S3 file: {}", e))?,
-        )))
+        s3_open_read_maybe_gz(path_in).await
     } else {
         tracing::debug!("Opening local file {} for reading (async)", path_in);
-        Ok(VariantReader::Vcf(vcf::AsyncReader::new(
-            open_read_maybe_gz(path_in).await.map_err(|e| {
-                anyhow::anyhow!("could not build VCF reader from local file: {}", e)
-            })?,
-        )))
+        open_read_maybe_bgzf_mt(path_in).await
     }
 }
 
+/// Helper function that opens a list of paths as VCF readers.
+///
+/// See `open_read()` for the supported paths.
+pub async fn open_vcf_readers(paths: &[String]) -> Result<Vec<VariantReader>, anyhow::Error> {
+    let mut result = Vec::new();
+    for path in paths.iter() {
+        result.push(open_vcf_reader(path).await?);
+    }
+    Ok(result)
+}
+
+/// Helper function that opens one VCF reader at the given path.
+///
+/// See `open_read()` for the supported paths.
+pub async fn open_vcf_reader(path_in: &str) -> Result<VariantReader, anyhow::Error> {
+    Ok(VariantReader::Vcf(AsyncVcfReader::new(
+        open_read(path_in)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not build VCF reader for {}: {}", path_in, e))?,
+    )))
+}
+
 #[cfg(test)]
 mod test {
+    #[rstest::rstest]
+    #[case("tests/seqvars/ingest/NA12878_dragen.vcf.gz", true)]
+    #[case("tests/seqvars/ingest/NA12878_dragen.vcf", false)]
+    fn is_bgzf(#[case] path: &str, #[case] expected: bool) -> Result<(), anyhow::Error> {
+        assert_eq!(super::is_bgzf(path)?, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_vcf_reader_multithreaded() -> Result<(), anyhow::Error> {
+        use futures::TryStreamExt as _;
+        use mehari::common::noodles::NoodlesVariantReader as _;
+
+        let path_in = "tests/seqvars/ingest/NA12878_dragen.vcf.gz";
+        let mut counts = Vec::new();
+        for threads in [1, 4] {
+            super::set_decompression_threads(threads);
+            let mut reader = super::open_vcf_reader(path_in).await?;
+            let header = reader.read_header().await?;
+            let records = reader
+                .records(&header)
+                .await
+                .try_collect::<Vec<_>>()
+                .await?;
+            counts.push(records.len());
+        }
+        super::set_decompression_threads(1);
+
+        assert_eq!(counts[0], counts[1]);
+        assert!(counts[0] > 0);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn build_tbi() -> Result<(), anyhow::Error> {
         let tmpdir = temp_testdir::TempDir::default();
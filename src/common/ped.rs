@@ -0,0 +1,394 @@
+//! Reading and validation of pedigree (PED) files.
+//!
+//! Two formats are accepted.  The classic PLINK PED format has the six columns family,
+//! name, father, mother, sex, and disease separated by tabs or whitespace; lines starting
+//! with `#` are comments.  The extended VarFish format starts with a header line
+//! `#family<TAB>name<TAB>father<TAB>mother<TAB>sex<TAB>disease` that may list the
+//! columns in any order, contains further columns that are ignored, and allows the
+//! words `male`/`female` and `affected`/`unaffected` for sex and disease.
+//!
+//! After parsing, the sexes of parents and the parent links are validated.  Errors name
+//! the line, sample, and field they refer to.
+
+use std::collections::HashMap;
+
+use mehari::ped::{Disease, Individual, PedigreeByName, Sex};
+
+/// Field of a PED record that an error refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Field {
+    /// The family identifier.
+    Family,
+    /// The sample name.
+    Name,
+    /// The father's name.
+    Father,
+    /// The mother's name.
+    Mother,
+    /// The sex.
+    Sex,
+    /// The disease status.
+    Disease,
+}
+
+/// The fields in the order of the classic format and of the `Field` discriminants.
+const FIELDS: [Field; 6] = [
+    Field::Family,
+    Field::Name,
+    Field::Father,
+    Field::Mother,
+    Field::Sex,
+    Field::Disease,
+];
+
+/// Error type for reading PED files.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("could not read PED file: {0}")]
+    Io(String),
+    #[error("PED file has no samples")]
+    Empty,
+    #[error("line {line}: {reason}")]
+    Columns { line: usize, reason: String },
+    #[error("line {line}, sample {sample:?}, field {field}: {reason}")]
+    Invalid {
+        line: usize,
+        sample: String,
+        field: Field,
+        reason: String,
+    },
+}
+
+/// Map the column name of an extended PED header to its field.
+fn header_field(name: &str) -> Option<Field> {
+    match name.to_lowercase().as_str() {
+        "family" | "family_id" | "fid" => Some(Field::Family),
+        "name" | "sample" | "individual" | "individual_id" | "iid" => Some(Field::Name),
+        "father" | "paternal_id" | "pid" => Some(Field::Father),
+        "mother" | "maternal_id" | "mid" => Some(Field::Mother),
+        "sex" => Some(Field::Sex),
+        "disease" | "affected" | "phenotype" => Some(Field::Disease),
+        _ => None,
+    }
+}
+
+/// Split `line` at tabs or, if it contains none, at whitespace.
+fn split(line: &str) -> Vec<&str> {
+    if line.contains('\t') {
+        line.split('\t').map(str::trim).collect()
+    } else {
+        line.split_whitespace().collect()
+    }
+}
+
+/// Parse the extended header `line` into the column index of each field in `FIELDS`.
+///
+/// Returns `None` if `line` is a comment rather than a header.
+fn parse_header(line_no: usize, line: &str) -> Result<Option<[usize; 6]>, Error> {
+    let names = split(line.trim_start_matches('#'));
+    if names.first().and_then(|name| header_field(name)).is_none() {
+        return Ok(None);
+    }
+    let mut columns = [usize::MAX; 6];
+    for (idx, name) in names.iter().enumerate() {
+        if let Some(field) = header_field(name) {
+            if columns[field as usize] != usize::MAX {
+                return Err(Error::Columns {
+                    line: line_no,
+                    reason: format!("duplicate column for {} in header", field),
+                });
+            }
+            columns[field as usize] = idx;
+        }
+    }
+    if let Some(pos) = columns.iter().position(|idx| *idx == usize::MAX) {
+        return Err(Error::Columns {
+            line: line_no,
+            reason: format!("missing column for {} in header", FIELDS[pos]),
+        });
+    }
+    Ok(Some(columns))
+}
+
+/// Parse a parent name where `0`, `.`, and empty values denote a founder.
+fn parse_parent(value: &str) -> Option<String> {
+    match value {
+        "" | "0" | "." => None,
+        _ => Some(value.to_string()),
+    }
+}
+
+/// Parse the sex in PED or word encoding.
+fn parse_sex(value: &str) -> Option<Sex> {
+    match value.to_lowercase().as_str() {
+        "1" | "m" | "male" => Some(Sex::Male),
+        "2" | "f" | "female" => Some(Sex::Female),
+        "0" | "-9" | "." | "other" | "unknown" => Some(Sex::Unknown),
+        _ => None,
+    }
+}
+
+/// Parse the disease status in PED or word encoding.
+fn parse_disease(value: &str) -> Option<Disease> {
+    match value.to_lowercase().as_str() {
+        "1" | "unaffected" => Some(Disease::Unaffected),
+        "2" | "affected" => Some(Disease::Affected),
+        "0" | "-9" | "." | "unknown" => Some(Disease::Unknown),
+        _ => None,
+    }
+}
+
+/// Parse and validate the pedigree in `text`.
+pub fn parse(text: &str) -> Result<PedigreeByName, Error> {
+    let mut columns: Option<[usize; 6]> = None;
+    let mut seen_record = false;
+    let mut individuals = indexmap::IndexMap::new();
+    let mut lines = HashMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            if !seen_record && columns.is_none() {
+                columns = parse_header(line_no, line)?;
+            }
+            continue;
+        }
+        seen_record = true;
+
+        let layout = columns.unwrap_or([0, 1, 2, 3, 4, 5]);
+        let cells = split(line);
+        let cell = |field: Field| cells.get(layout[field as usize]).copied();
+        let sample = cell(Field::Name).unwrap_or_default().to_string();
+        let invalid = |field: Field, reason: String| Error::Invalid {
+            line: line_no,
+            sample: sample.clone(),
+            field,
+            reason,
+        };
+        let Some(disease) = cell(Field::Disease) else {
+            return Err(Error::Columns {
+                line: line_no,
+                reason: format!(
+                    "expected at least {} columns but got {}",
+                    layout.iter().max().expect("six columns") + 1,
+                    cells.len()
+                ),
+            });
+        };
+        let family = cell(Field::Family).unwrap_or_default();
+        if family.is_empty() {
+            return Err(invalid(Field::Family, "empty family".into()));
+        }
+        if sample.is_empty() || sample == "0" {
+            return Err(invalid(Field::Name, "empty or 0 sample name".into()));
+        }
+        let sex = cell(Field::Sex).unwrap_or_default();
+        let sex = parse_sex(sex)
+            .ok_or_else(|| invalid(Field::Sex, format!("invalid value {:?}", sex)))?;
+        let disease = parse_disease(disease)
+            .ok_or_else(|| invalid(Field::Disease, format!("invalid value {:?}", disease)))?;
+        if lines.contains_key(&sample) {
+            return Err(invalid(
+                Field::Name,
+                format!("duplicate sample, first seen in line {}", lines[&sample]),
+            ));
+        }
+
+        lines.insert(sample.clone(), line_no);
+        individuals.insert(
+            sample.clone(),
+            Individual {
+                family: family.to_string(),
+                name: sample.clone(),
+                father: cell(Field::Father).and_then(parse_parent),
+                mother: cell(Field::Mother).and_then(parse_parent),
+                sex,
+                disease,
+            },
+        );
+    }
+
+    if individuals.is_empty() {
+        return Err(Error::Empty);
+    }
+    let pedigree = PedigreeByName { individuals };
+    validate(&pedigree, &lines)?;
+    Ok(pedigree)
+}
+
+/// Validate the parent links of `pedigree`; `lines` maps the samples to their lines.
+fn validate(pedigree: &PedigreeByName, lines: &HashMap<String, usize>) -> Result<(), Error> {
+    for individual in pedigree.individuals.values() {
+        let invalid = |field: Field, reason: String| Error::Invalid {
+            line: lines[&individual.name],
+            sample: individual.name.clone(),
+            field,
+            reason,
+        };
+        for (field, parent, wrong_sex) in [
+            (Field::Father, &individual.father, Sex::Female),
+            (Field::Mother, &individual.mother, Sex::Male),
+        ] {
+            let Some(parent) = parent.as_ref() else {
+                continue;
+            };
+            if *parent == individual.name {
+                return Err(invalid(field, "sample is its own parent".into()));
+            }
+            let Some(parent_individual) = pedigree.individuals.get(parent) else {
+                return Err(invalid(
+                    field,
+                    format!("parent {} is not in the pedigree", parent),
+                ));
+            };
+            if parent_individual.family != individual.family {
+                return Err(invalid(
+                    field,
+                    format!(
+                        "parent {} is in family {} instead of {}",
+                        parent, parent_individual.family, individual.family
+                    ),
+                ));
+            }
+            if parent_individual.sex == wrong_sex {
+                return Err(invalid(
+                    field,
+                    format!("parent {} has sex {:?}", parent, wrong_sex),
+                ));
+            }
+            if is_ancestor(pedigree, &individual.name, parent) {
+                return Err(invalid(
+                    field,
+                    format!("sample is an ancestor of its parent {}", parent),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return whether `name` is an ancestor of or equal to `start`.
+fn is_ancestor(pedigree: &PedigreeByName, name: &str, start: &str) -> bool {
+    let mut stack = vec![start];
+    let mut visited = std::collections::HashSet::new();
+    while let Some(current) = stack.pop() {
+        if current == name {
+            return true;
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+        if let Some(individual) = pedigree.individuals.get(current) {
+            stack.extend(individual.father.as_deref());
+            stack.extend(individual.mother.as_deref());
+        }
+    }
+    false
+}
+
+/// Load and validate the pedigree from the PED file at `path`.
+pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<PedigreeByName, Error> {
+    let text = std::fs::read_to_string(path.as_ref()).map_err(|e| Error::Io(e.to_string()))?;
+    parse(&text)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::ped::{Disease, PedigreeByName, Sex};
+
+    use super::{Error, Field};
+
+    #[rstest::rstest]
+    #[case("tests/seqvars/ingest/Case_1.ped")]
+    #[case("tests/seqvars/ingest/clair3_glnexus.ped")]
+    #[case("tests/strucvars/ingest/delly2-min.ped")]
+    fn load_classic(#[case] path: &str) -> Result<(), anyhow::Error> {
+        assert_eq!(super::load(path)?, PedigreeByName::from_path(path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_extended() -> Result<(), anyhow::Error> {
+        let text = "#name\tfamily\tfather\tmother\tsex\taffected\thpo_terms\n\
+            index\tFAM\tfather\tmother\tfemale\taffected\tHP:0000118\n\
+            father\tFAM\t0\t0\tmale\tunaffected\t\n\
+            mother\tFAM\t.\t.\tF\t1\t\n";
+
+        let pedigree = super::parse(text)?;
+
+        assert_eq!(
+            pedigree.individuals.keys().collect::<Vec<_>>(),
+            vec!["index", "father", "mother"]
+        );
+        let index = &pedigree.individuals["index"];
+        assert_eq!(index.father.as_deref(), Some("father"));
+        assert_eq!(index.sex, Sex::Female);
+        assert_eq!(index.disease, Disease::Affected);
+        assert_eq!(pedigree.individuals["mother"].mother, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_whitespace_with_comment() -> Result<(), anyhow::Error> {
+        let pedigree = super::parse("# exported pedigree\nFAM index 0 0 1 2\n")?;
+
+        assert_eq!(pedigree.individuals["index"].sex, Sex::Male);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::empty("# nothing\n", Error::Empty)]
+    #[case::columns("FAM\tindex\t0\t0\t1\n", Error::Columns {
+        line: 1,
+        reason: "expected at least 6 columns but got 5".into(),
+    })]
+    #[case::header("#name\tfamily\tsex\n", Error::Columns {
+        line: 1,
+        reason: "missing column for father in header".into(),
+    })]
+    #[case::sex("FAM\tindex\t0\t0\tx\t2\n", Error::Invalid {
+        line: 1,
+        sample: "index".into(),
+        field: Field::Sex,
+        reason: "invalid value \"x\"".into(),
+    })]
+    #[case::duplicate("FAM\tindex\t0\t0\t1\t2\nFAM\tindex\t0\t0\t1\t2\n", Error::Invalid {
+        line: 2,
+        sample: "index".into(),
+        field: Field::Name,
+        reason: "duplicate sample, first seen in line 1".into(),
+    })]
+    #[case::missing_parent("FAM\tindex\tfather\t0\t1\t2\n", Error::Invalid {
+        line: 1,
+        sample: "index".into(),
+        field: Field::Father,
+        reason: "parent father is not in the pedigree".into(),
+    })]
+    #[case::parent_sex("FAM\tindex\t0\tmother\t1\t2\nFAM\tmother\t0\t0\t1\t1\n", Error::Invalid {
+        line: 1,
+        sample: "index".into(),
+        field: Field::Mother,
+        reason: "parent mother has sex Male".into(),
+    })]
+    #[case::family("FAM\tindex\tfather\t0\t1\t2\nOTHER\tfather\t0\t0\t1\t1\n", Error::Invalid {
+        line: 1,
+        sample: "index".into(),
+        field: Field::Father,
+        reason: "parent father is in family OTHER instead of FAM".into(),
+    })]
+    #[case::cycle("FAM\ta\tb\t0\t1\t2\nFAM\tb\ta\t0\t1\t1\n", Error::Invalid {
+        line: 1,
+        sample: "a".into(),
+        field: Field::Father,
+        reason: "sample is an ancestor of its parent b".into(),
+    })]
+    fn parse_invalid(#[case] text: &str, #[case] expected: Error) {
+        assert_eq!(super::parse(text).unwrap_err(), expected);
+    }
+}
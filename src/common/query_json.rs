@@ -0,0 +1,141 @@
+//! Reading of query JSON files from local files, standard input, S3, or HTTP(S).
+
+use std::io::Read as _;
+
+use tokio::io::AsyncReadExt as _;
+
+/// Source of a query JSON file as given to `--path-query-json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuerySource {
+    /// Read from standard input, given as `-`.
+    Stdin,
+    /// Fetch from an `http://` or `https://` URL.
+    Url(String),
+    /// Read from an `s3://bucket/key` object, using the S3 configuration from the environment.
+    S3 { bucket: String, key: String },
+    /// Read from a local file.
+    Path(String),
+}
+
+impl QuerySource {
+    /// Determine the source from the command line argument `path`.
+    pub fn parse(path: &str) -> Result<Self, anyhow::Error> {
+        if path == "-" {
+            Ok(Self::Stdin)
+        } else if path.starts_with("http://") || path.starts_with("https://") {
+            Ok(Self::Url(path.to_string()))
+        } else if let Some((bucket, key)) = super::s3::parse_s3_url(path)? {
+            Ok(Self::S3 {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+        } else {
+            Ok(Self::Path(path.to_string()))
+        }
+    }
+
+    /// Read the raw bytes from the source.
+    async fn read_raw(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut buf = Vec::new();
+        match self {
+            Self::Stdin => {
+                tokio::io::stdin().read_to_end(&mut buf).await?;
+            }
+            Self::Url(url) => {
+                buf.extend(reqwest::get(url).await?.error_for_status()?.bytes().await?);
+            }
+            Self::S3 { bucket, key } => {
+                let client = aws_sdk_s3::Client::from_conf(super::s3::config_from_env().await?);
+                let object = client.get_object().bucket(bucket).key(key).send().await?;
+                buf.extend(object.body.collect().await?.into_bytes());
+            }
+            Self::Path(path) => {
+                buf = tokio::fs::read(path).await?;
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// Return `buf`, decompressed if it starts with the gzip magic bytes.
+fn maybe_gunzip(buf: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if buf.starts_with(&[0x1f, 0x8b]) {
+        let mut result = Vec::new();
+        flate2::read::MultiGzDecoder::new(buf.as_slice()).read_to_end(&mut result)?;
+        Ok(result)
+    } else {
+        Ok(buf)
+    }
+}
+
+/// Read and deserialize the query JSON from `path`.
+///
+/// The `path` can be a local file, `-` for standard input, an `s3://` URL, or an
+/// `http(s)://` URL.  Gzip-compressed data is decompressed transparently.
+pub async fn read<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, anyhow::Error> {
+    let buf = QuerySource::parse(path)?
+        .read_raw()
+        .await
+        .and_then(maybe_gunzip)
+        .map_err(|e| anyhow::anyhow!("could not read query {}: {}", path, e))?;
+    serde_json::from_slice(&buf)
+        .map_err(|e| anyhow::anyhow!("could not parse query {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write as _;
+
+    use super::QuerySource;
+
+    #[rstest::rstest]
+    #[case("-", QuerySource::Stdin)]
+    #[case("query.json", QuerySource::Path("query.json".into()))]
+    #[case("/tmp/query.json.gz", QuerySource::Path("/tmp/query.json.gz".into()))]
+    #[case(
+        "https://example.com/query.json",
+        QuerySource::Url("https://example.com/query.json".into())
+    )]
+    #[case(
+        "s3://bucket/path/query.json",
+        QuerySource::S3 { bucket: "bucket".into(), key: "path/query.json".into() }
+    )]
+    fn parse(#[case] path: &str, #[case] expected: QuerySource) -> Result<(), anyhow::Error> {
+        assert_eq!(QuerySource::parse(path)?, expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("s3://bucket")]
+    #[case("s3:///key")]
+    fn parse_invalid(#[case] path: &str) {
+        assert!(QuerySource::parse(path).is_err());
+    }
+
+    #[rstest::rstest]
+    #[case(false)]
+    #[case(true)]
+    #[tokio::test]
+    async fn read(#[case] gzip: bool) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/query.json", tmpdir.to_string_lossy());
+        let json = br#"{"maxAf": 0.01}"#;
+        if gzip {
+            let mut encoder = flate2::write::GzEncoder::new(
+                std::fs::File::create(&path)?,
+                flate2::Compression::default(),
+            );
+            encoder.write_all(json)?;
+            encoder.finish()?;
+        } else {
+            std::fs::write(&path, json)?;
+        }
+
+        let value: serde_json::Value = super::read(&path).await?;
+
+        assert_eq!(value, serde_json::json!({"maxAf": 0.01}));
+
+        Ok(())
+    }
+}
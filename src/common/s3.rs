@@ -30,31 +30,143 @@ pub async fn config_from_env() -> Result<aws_sdk_s3::config::Config, anyhow::Err
     }
 }
 
-pub async fn upload_file(src: &str, dst: &str) -> Result<(), anyhow::Error> {
-    let client = aws_sdk_s3::Client::from_conf(config_from_env().await?);
+/// Minimal size of a multipart upload part as required by S3, except for the last part.
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
 
-    let (bucket, key) = if let Some((bucket, key)) = dst.split_once('/') {
-        (bucket.to_string(), key.to_string())
-    } else {
-        anyhow::bail!("invalid S3 path: {}", dst);
+/// Maximal number of parts of a multipart upload as allowed by S3.
+const MAX_PARTS: u64 = 10_000;
+
+/// Configuration of uploads to S3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadConfig {
+    /// Size of the parts of multipart uploads in bytes; smaller files are uploaded
+    /// with a single request.
+    pub part_size: u64,
+    /// Number of attempts for each request.
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for each further retry.
+    pub retry_delay: std::time::Duration,
+}
+
+impl UploadConfig {
+    /// The default configuration.
+    pub const DEFAULT: Self = Self {
+        part_size: 64 * 1024 * 1024,
+        attempts: 3,
+        retry_delay: std::time::Duration::from_secs(1),
     };
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Configuration of uploads to S3.
+static UPLOAD_CONFIG: std::sync::Mutex<UploadConfig> = std::sync::Mutex::new(UploadConfig::DEFAULT);
 
+/// Set the configuration of uploads to S3.
+pub fn set_upload_config(config: UploadConfig) -> Result<(), anyhow::Error> {
+    if config.part_size < MIN_PART_SIZE {
+        anyhow::bail!(
+            "S3 part size must be at least {} MiB but is {} bytes",
+            MIN_PART_SIZE / 1024 / 1024,
+            config.part_size
+        );
+    }
+    if config.attempts == 0 {
+        anyhow::bail!("number of S3 upload attempts must be at least 1");
+    }
+    *UPLOAD_CONFIG.lock().expect("poisoned lock") = config;
+    Ok(())
+}
+
+/// Return the configuration of uploads to S3.
+fn upload_config() -> UploadConfig {
+    *UPLOAD_CONFIG.lock().expect("poisoned lock")
+}
+
+/// Run `f` until it succeeds, at most `config.attempts` times with exponential backoff.
+async fn with_retry<T, F, Fut>(
+    config: &UploadConfig,
+    what: &str,
+    mut f: F,
+) -> Result<T, anyhow::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, anyhow::Error>>,
+{
+    let mut delay = config.retry_delay;
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(result) => return Ok(result),
+            Err(e) if attempt < config.attempts => {
+                tracing::warn!(
+                    "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                    what,
+                    attempt,
+                    config.attempts,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Return the offsets and lengths of the parts for uploading `size` bytes.
+///
+/// The part size is increased if necessary to stay within the maximal number of parts.
+fn part_ranges(size: u64, part_size: u64) -> Vec<(u64, u64)> {
+    let part_size = part_size.max((size + MAX_PARTS - 1) / MAX_PARTS);
+    (0..size)
+        .step_by(part_size as usize)
+        .map(|offset| (offset, part_size.min(size - offset)))
+        .collect()
+}
+
+/// Split S3 path `dst` into bucket and key.
+fn bucket_and_key(dst: &str) -> Result<(&str, &str), anyhow::Error> {
+    dst.split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("invalid S3 path: {}", dst))
+}
+
+/// Upload file `src` to S3 path `dst`, retrying with exponential backoff on failure.
+///
+/// Files larger than the configured part size are streamed from disk with a
+/// multipart upload, retrying each part on its own.
+pub async fn upload_file_with_retry(src: &str, dst: &str) -> Result<(), anyhow::Error> {
+    let config = upload_config();
+    let size = std::fs::metadata(src)
+        .map_err(|e| anyhow::anyhow!("could not open file {:?}: {}", src, e))?
+        .len();
+    let client = aws_sdk_s3::Client::from_conf(config_from_env().await?);
+    let (bucket, key) = bucket_and_key(dst)?;
     tracing::debug!("will upload to bucket {:?} and key {:?}", &bucket, &key);
 
-    // // Create bucket if it does not exist yet.
-    // let exists = client
-    //     .bucket_exists(&minio::s3::args::BucketExistsArgs::new(&bucket).unwrap())
-    //     .await
-    //     .unwrap();
-    // tracing::debug!("!!");
-    // if !exists {
-    //     client
-    //         .make_bucket(&minio::s3::args::MakeBucketArgs::new(&bucket).unwrap())
-    //         .await
-    //         .unwrap();
-    // }
-    // tracing::debug!("!!");
+    if size <= config.part_size {
+        with_retry(&config, &format!("upload of {:?}", src), || {
+            upload_file(&client, src, bucket, key)
+        })
+        .await
+    } else {
+        upload_file_multipart(&client, &config, src, size, bucket, key).await
+    }
+}
 
+/// Upload file `src` to `bucket` and `key` with a single request.
+async fn upload_file(
+    client: &aws_sdk_s3::Client,
+    src: &str,
+    bucket: &str,
+    key: &str,
+) -> Result<(), anyhow::Error> {
     let body = aws_sdk_s3::primitives::ByteStream::from_path(std::path::Path::new(src))
         .await
         .map_err(|e| anyhow::anyhow!("could not open file {:?}: {}", src, e))?;
@@ -70,11 +182,308 @@ pub async fn upload_file(src: &str, dst: &str) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Upload file `src` of `size` bytes to `bucket` and `key` with a multipart upload.
+///
+/// The upload is aborted on failure so no incomplete parts are left behind.
+async fn upload_file_multipart(
+    client: &aws_sdk_s3::Client,
+    config: &UploadConfig,
+    src: &str,
+    size: u64,
+    bucket: &str,
+    key: &str,
+) -> Result<(), anyhow::Error> {
+    let upload_id = with_retry(config, &format!("starting upload of {:?}", src), || async {
+        client
+            .create_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("could not start upload of {:?}: {}", src, e))?
+            .upload_id
+            .ok_or_else(|| anyhow::anyhow!("no upload ID for upload of {:?}", src))
+    })
+    .await?;
+
+    let result = upload_parts(client, config, src, size, bucket, key, &upload_id).await;
+    if result.is_err() {
+        if let Err(e) = client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .send()
+            .await
+        {
+            tracing::warn!("could not abort upload of {:?}: {}", src, e);
+        }
+    }
+    result
+}
+
+/// Upload the parts of file `src` and complete the multipart upload `upload_id`.
+async fn upload_parts(
+    client: &aws_sdk_s3::Client,
+    config: &UploadConfig,
+    src: &str,
+    size: u64,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(), anyhow::Error> {
+    use aws_sdk_s3::primitives::{ByteStream, Length};
+    use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+
+    let ranges = part_ranges(size, config.part_size);
+    let mut parts = Vec::with_capacity(ranges.len());
+    for (i, (offset, length)) in ranges.iter().copied().enumerate() {
+        let part_number = i as i32 + 1;
+        tracing::debug!(
+            "uploading part {}/{} of {:?} ({} bytes)",
+            part_number,
+            ranges.len(),
+            src,
+            length
+        );
+        let what = format!("upload of part {} of {:?}", part_number, src);
+        let e_tag = with_retry(config, &what, || async {
+            let body = ByteStream::read_from()
+                .path(src)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not open file {:?}: {}", src, e))?;
+            client
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not {}: {}", &what, e))?
+                .e_tag
+                .ok_or_else(|| anyhow::anyhow!("no ETag for {}", &what))
+        })
+        .await?;
+        parts.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .e_tag(e_tag)
+                .build(),
+        );
+    }
+
+    with_retry(
+        config,
+        &format!("completing upload of {:?}", src),
+        || async {
+            client
+                .complete_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts.clone()))
+                        .build(),
+                )
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not complete upload of {:?}: {}", src, e))?;
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Size of the ranges requested when reading objects from S3.
+const READ_RANGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Parse `path` as `s3://bucket/key` URL, returning `None` if it is no S3 URL.
+pub fn parse_s3_url(path: &str) -> Result<Option<(&str, &str)>, anyhow::Error> {
+    match path
+        .strip_prefix("s3://")
+        .map(|bucket_key| bucket_key.split_once('/'))
+    {
+        None => Ok(None),
+        Some(Some((bucket, key))) if !bucket.is_empty() && !key.is_empty() => {
+            Ok(Some((bucket, key)))
+        }
+        Some(_) => anyhow::bail!("invalid S3 URL: {}", path),
+    }
+}
+
+/// Return the offsets and lengths of the ranges for reading `size` bytes.
+fn read_ranges(size: u64) -> impl Iterator<Item = (u64, u64)> {
+    (0..size)
+        .step_by(READ_RANGE_SIZE as usize)
+        .map(move |offset| (offset, READ_RANGE_SIZE.min(size - offset)))
+}
+
+/// Return a stream of the content of the object at `bucket` and `key`.
+///
+/// The object is read with ranged requests of `READ_RANGE_SIZE` bytes, retrying each
+/// request as configured for uploads, so the object is never held in memory as a whole.
+pub async fn object_stream(
+    bucket: &str,
+    key: &str,
+) -> Result<
+    impl futures::Stream<Item = Result<bytes::Bytes, std::io::Error>> + Send + 'static,
+    anyhow::Error,
+> {
+    let config = upload_config();
+    let client = aws_sdk_s3::Client::from_conf(config_from_env().await?);
+    let size = with_retry(
+        &config,
+        &format!("reading size of s3://{}/{}", bucket, key),
+        || async {
+            client
+                .head_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not access s3://{}/{}: {}", bucket, key, e))
+        },
+    )
+    .await?
+    .content_length
+    .unwrap_or_default()
+    .max(0) as u64;
+    tracing::debug!("Reading s3://{}/{} with {} bytes", bucket, key, size);
+
+    let (bucket, key) = (bucket.to_string(), key.to_string());
+    let ranges = futures::stream::iter(read_ranges(size));
+    Ok(futures::StreamExt::then(ranges, move |(offset, length)| {
+        let (client, bucket, key) = (client.clone(), bucket.clone(), key.clone());
+        async move {
+            let range = format!("bytes={}-{}", offset, offset + length - 1);
+            let what = format!("reading {} of s3://{}/{}", &range, &bucket, &key);
+            with_retry(&config, &what, || async {
+                client
+                    .get_object()
+                    .bucket(&bucket)
+                    .key(&key)
+                    .range(&range)
+                    .send()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("could not start {}: {}", &what, e))?
+                    .body
+                    .collect()
+                    .await
+                    .map(|data| data.into_bytes())
+                    .map_err(|e| anyhow::anyhow!("could not finish {}: {}", &what, e))
+            })
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+    }))
+}
+
+/// Open the object at `bucket` and `key` for asynchronous reading.
+pub async fn open_read(
+    bucket: &str,
+    key: &str,
+) -> Result<std::pin::Pin<Box<dyn tokio::io::AsyncBufRead + Send>>, anyhow::Error> {
+    Ok(Box::pin(tokio_util::io::StreamReader::new(Box::pin(
+        object_stream(bucket, key).await?,
+    ))))
+}
+
+/// Reader for synchronous reading of an S3 object.
+///
+/// The ranges are read in a background thread with its own runtime, so the reader can
+/// be used both within and outside of an asynchronous context.
+struct SyncObjectReader {
+    /// Receiver of the ranges from the background thread.
+    receiver: std::sync::mpsc::Receiver<Result<bytes::Bytes, std::io::Error>>,
+    /// The current range.
+    current: bytes::Bytes,
+}
+
+impl std::io::Read for SyncObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.receiver.recv() {
+                Ok(data) => self.current = data?,
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.len());
+        buf[..len].copy_from_slice(&self.current.split_to(len));
+        Ok(len)
+    }
+}
+
+/// Open the object at `bucket` and `key` for synchronous reading.
+pub fn open_read_sync(bucket: &str, key: &str) -> Box<dyn std::io::Read + Send> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(2);
+    let (bucket, key) = (bucket.to_string(), key.to_string());
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        runtime.block_on(async {
+            use futures::StreamExt as _;
+
+            let stream = match object_stream(&bucket, &key).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = sender.send(Err(std::io::Error::new(std::io::ErrorKind::Other, e)));
+                    return;
+                }
+            };
+            let mut stream = std::pin::pin!(stream);
+            while let Some(data) = stream.next().await {
+                let is_err = data.is_err();
+                // Stop on error or when the reader has been dropped.
+                if sender.send(data).is_err() || is_err {
+                    break;
+                }
+            }
+        });
+    });
+    Box::new(SyncObjectReader {
+        receiver,
+        current: bytes::Bytes::new(),
+    })
+}
+
+/// Open local file or `s3://` URL `path` for synchronous reading, decompressing
+/// gzip files.
+pub fn open_read_maybe_gz(path: &str) -> Result<Box<dyn std::io::BufRead>, anyhow::Error> {
+    match parse_s3_url(path)? {
+        Some((bucket, key)) => {
+            tracing::debug!("Opening S3 object {} for reading", path);
+            let reader = open_read_sync(bucket, key);
+            if is_gz(path) {
+                Ok(Box::new(std::io::BufReader::new(
+                    flate2::read::MultiGzDecoder::new(reader),
+                )))
+            } else {
+                Ok(Box::new(std::io::BufReader::new(reader)))
+            }
+        }
+        None => mehari::common::io::std::open_read_maybe_gz(path),
+    }
+}
+
 /// Helper struct to encapsulate VCF S3 file upload and TBI creation.
 pub struct OutputPathHelper {
-    /// Temporary directory to use.
+    /// Temporary directory to use, `None` if a persistent local directory is used.
     #[allow(dead_code)] // keep around for RAII
-    tmpdir: tempfile::TempDir,
+    tmpdir: Option<tempfile::TempDir>,
     /// Original output path.
     path_out_orig: String,
     /// Effective output path.
@@ -86,28 +495,70 @@ impl OutputPathHelper {
         let tmpdir = tempfile::tempdir().map_err(|e| {
             anyhow::anyhow!("could not create temporary directory for S3 upload: {}", e)
         })?;
+        let path_out_effective = Self::effective_path(path_out, tmpdir.path());
         Ok(Self {
             path_out_orig: path_out.to_string(),
-            path_out_effective: if s3_mode() {
-                tracing::debug!(
-                    "S3 mode, using temporary directory: {}",
-                    tmpdir.path().display()
-                );
-                let p = std::path::Path::new(path_out);
-                format!(
-                    "{}",
-                    tmpdir
-                        .path()
-                        .join(p.file_name().expect("no file name"))
-                        .display()
-                )
-            } else {
-                path_out.to_string()
-            },
-            tmpdir,
+            path_out_effective,
+            tmpdir: Some(tmpdir),
         })
     }
 
+    /// Construct with the local files in S3 mode written to `local_dir`.
+    ///
+    /// Other than with `new()`, the local directory is not removed when the helper is
+    /// dropped so an interrupted upload can be resumed.
+    pub fn with_local_dir(
+        path_out: &str,
+        local_dir: &std::path::Path,
+    ) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(local_dir).map_err(|e| {
+            anyhow::anyhow!(
+                "could not create local directory {} for S3 upload: {}",
+                local_dir.display(),
+                e
+            )
+        })?;
+        Ok(Self {
+            path_out_orig: path_out.to_string(),
+            path_out_effective: Self::effective_path(path_out, local_dir),
+            tmpdir: None,
+        })
+    }
+
+    /// Return the effective output path for `path_out` with local directory `local_dir`.
+    fn effective_path(path_out: &str, local_dir: &std::path::Path) -> String {
+        if s3_mode() {
+            tracing::debug!("S3 mode, using local directory: {}", local_dir.display());
+            let p = std::path::Path::new(path_out);
+            format!(
+                "{}",
+                local_dir
+                    .join(p.file_name().expect("no file name"))
+                    .display()
+            )
+        } else {
+            path_out.to_string()
+        }
+    }
+
+    /// Return the uploads necessary for this output, empty if not in S3 mode.
+    pub fn pending_uploads(&self) -> Vec<PendingUpload> {
+        if !s3_mode() {
+            return Vec::new();
+        }
+        let mut result = vec![PendingUpload {
+            src: self.path_out_effective.clone(),
+            dst: self.path_out_orig.clone(),
+        }];
+        if is_gz(&self.path_out_orig) {
+            result.push(PendingUpload {
+                src: format!("{}.tbi", &self.path_out_effective),
+                dst: format!("{}.tbi", &self.path_out_orig),
+            });
+        }
+        result
+    }
+
     /// Return output path.
     pub fn path_out(&self) -> &str {
         &self.path_out_effective
@@ -135,13 +586,8 @@ impl OutputPathHelper {
     pub async fn upload_for_s3(&self) -> Result<(), anyhow::Error> {
         if s3_mode() {
             tracing::info!("Uploading to S3...");
-            upload_file(&self.path_out_effective, &self.path_out_orig).await?;
-            if is_gz(&self.path_out_orig) {
-                upload_file(
-                    &format!("{}.tbi", &self.path_out_effective),
-                    &format!("{}.tbi", &self.path_out_orig),
-                )
-                .await?;
+            for PendingUpload { src, dst } in self.pending_uploads() {
+                upload_file_with_retry(&src, &dst).await?;
             }
             tracing::info!("... done uploading to S3");
         }
@@ -149,3 +595,191 @@ impl OutputPathHelper {
         Ok(())
     }
 }
+
+/// A file that is to be uploaded to S3.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PendingUpload {
+    /// Local source path.
+    pub src: String,
+    /// Destination S3 path.
+    pub dst: String,
+}
+
+/// State persisted for resuming an interrupted upload to S3.
+///
+/// The state is written once all local output files are complete and removed once
+/// all uploads succeeded.  It lives in a directory derived from the output path,
+/// next to the local output files.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ResumeState {
+    /// The original output path.
+    pub path_out: String,
+    /// The uploads still to perform.
+    pub uploads: Vec<PendingUpload>,
+}
+
+impl ResumeState {
+    /// Name of the state file in the resume directory.
+    const FILE_NAME: &'static str = "resume-state.json";
+
+    /// Return the local directory for output files and state of `path_out`.
+    pub fn dir_for(path_out: &str) -> std::path::PathBuf {
+        let name = path_out
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        std::env::temp_dir().join(format!("varfish-worker-resume-{}", name))
+    }
+
+    /// Load the state for `path_out`, if any.
+    pub fn load(path_out: &str) -> Result<Option<Self>, anyhow::Error> {
+        let path = Self::dir_for(path_out).join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let state: Self = serde_json::from_reader(std::fs::File::open(&path)?)
+            .map_err(|e| anyhow::anyhow!("could not read state file {}: {}", path.display(), e))?;
+        for upload in &state.uploads {
+            if !std::path::Path::new(&upload.src).exists() {
+                anyhow::bail!(
+                    "local file {} of state file {} is missing",
+                    &upload.src,
+                    path.display()
+                );
+            }
+        }
+        Ok(Some(state))
+    }
+
+    /// Write out the state file.
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let dir = Self::dir_for(&self.path_out);
+        std::fs::create_dir_all(&dir)?;
+        // Write to temporary file and rename so we never see a partial state file.
+        let path = dir.join(Self::FILE_NAME);
+        let path_tmp = dir.join(format!("{}.tmp", Self::FILE_NAME));
+        std::fs::write(&path_tmp, serde_json::to_string(self)?)
+            .map_err(|e| anyhow::anyhow!("could not write state file: {}", e))?;
+        std::fs::rename(&path_tmp, &path)
+            .map_err(|e| anyhow::anyhow!("could not rename state file: {}", e))?;
+        Ok(())
+    }
+
+    /// Perform the uploads and remove the state and local files on success.
+    pub async fn upload_and_clear(&self) -> Result<(), anyhow::Error> {
+        tracing::info!("Uploading to S3...");
+        for PendingUpload { src, dst } in &self.uploads {
+            upload_file_with_retry(src, dst).await?;
+        }
+        tracing::info!("... done uploading to S3");
+        let dir = Self::dir_for(&self.path_out);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(|e| {
+                anyhow::anyhow!("could not remove directory {}: {}", dir.display(), e)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_s3_url, part_ranges, read_ranges, with_retry, PendingUpload, ResumeState,
+        UploadConfig, READ_RANGE_SIZE,
+    };
+
+    #[rstest::rstest]
+    #[case("s3://bucket/path/to/file.vcf.gz", Some(("bucket", "path/to/file.vcf.gz")))]
+    #[case("bucket/path/to/file.vcf.gz", None)]
+    #[case("/path/to/file.vcf.gz", None)]
+    fn parse_s3_url_cases(#[case] path: &str, #[case] expected: Option<(&str, &str)>) {
+        assert_eq!(parse_s3_url(path).unwrap(), expected);
+    }
+
+    #[test]
+    fn read_ranges_cover_object() {
+        let size = 2 * READ_RANGE_SIZE + 1;
+        assert_eq!(
+            read_ranges(size).collect::<Vec<_>>(),
+            vec![
+                (0, READ_RANGE_SIZE),
+                (READ_RANGE_SIZE, READ_RANGE_SIZE),
+                (2 * READ_RANGE_SIZE, 1)
+            ]
+        );
+        assert_eq!(read_ranges(0).count(), 0);
+    }
+
+    #[rstest::rstest]
+    #[case(0, 10, vec![])]
+    #[case(10, 10, vec![(0, 10)])]
+    #[case(25, 10, vec![(0, 10), (10, 10), (20, 5)])]
+    #[case(30_000, 1, vec![(0, 3), (3, 3)])]
+    fn part_ranges_cases(
+        #[case] size: u64,
+        #[case] part_size: u64,
+        #[case] expected: Vec<(u64, u64)>,
+    ) {
+        let result = part_ranges(size, part_size);
+        assert_eq!(result[..expected.len().min(result.len())], expected[..]);
+        assert_eq!(result.iter().map(|(_, len)| len).sum::<u64>(), size);
+        assert!(result.len() <= 10_000);
+    }
+
+    #[tokio::test]
+    async fn with_retry_succeeds_after_failures() {
+        let config = UploadConfig {
+            retry_delay: std::time::Duration::ZERO,
+            ..UploadConfig::DEFAULT
+        };
+        let mut calls = 0;
+        let result = with_retry(&config, "test", || {
+            calls += 1;
+            let calls = calls;
+            async move {
+                if calls < 3 {
+                    anyhow::bail!("failure {}", calls)
+                }
+                Ok(calls)
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<(), _> = with_retry(&config, "test", || {
+            calls += 1;
+            async { anyhow::bail!("failure") }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn resume_state_roundtrip() -> Result<(), anyhow::Error> {
+        let path_out = format!("bucket/{}/out.jsonl", uuid::Uuid::new_v4());
+        assert_eq!(ResumeState::load(&path_out)?, None);
+
+        let dir = ResumeState::dir_for(&path_out);
+        std::fs::create_dir_all(&dir)?;
+        let src = dir.join("out.jsonl").to_string_lossy().to_string();
+        std::fs::write(&src, "{}\n")?;
+
+        let state = ResumeState {
+            path_out: path_out.clone(),
+            uploads: vec![PendingUpload {
+                src,
+                dst: path_out.clone(),
+            }],
+        };
+        state.save()?;
+        assert_eq!(ResumeState::load(&path_out)?, Some(state));
+
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(ResumeState::load(&path_out)?, None);
+
+        Ok(())
+    }
+}
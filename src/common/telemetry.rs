@@ -0,0 +1,176 @@
+//! Optional export of `tracing` spans to an OpenTelemetry collector via OTLP.
+//!
+//! Span export requires building with the `otel` feature.  The VarFish server can pass
+//! its trace context in the `TRACEPARENT` environment variable (W3C trace context) so
+//! that the spans of the worker become part of the server-side request trace.
+
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// Name of the service in the exported spans.
+const SERVICE_NAME: &str = "varfish-server-worker";
+
+/// Name of the environment variable with the W3C trace context of the caller.
+const TRACEPARENT: &str = "TRACEPARENT";
+
+/// Boxed layer for the tracing subscriber.
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync + 'static>;
+
+/// Handle to the span export that flushes outstanding spans on shutdown.
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    /// The OTLP endpoint, if export is configured.
+    endpoint: Option<String>,
+    /// The tracer provider of the export.
+    provider: imp::Provider,
+}
+
+impl Telemetry {
+    /// Set up the span export to `endpoint`, if given.
+    pub fn new(endpoint: Option<&str>) -> Result<Self, anyhow::Error> {
+        let provider = match endpoint {
+            Some(endpoint) => imp::provider(endpoint)?,
+            None => Default::default(),
+        };
+        Ok(Self {
+            endpoint: endpoint.map(String::from),
+            provider,
+        })
+    }
+
+    /// Return the layer that exports the spans, if any.
+    pub fn layer<S>(&self) -> Option<BoxedLayer<S>>
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    {
+        imp::layer(&self.provider)
+    }
+
+    /// Create the root span for running `command`.
+    ///
+    /// The parent of the span is taken from the `TRACEPARENT` environment variable, if set.
+    pub fn root_span(&self, command: &str) -> tracing::Span {
+        let span = tracing::info_span!("run", command = command);
+        if let Some(endpoint) = self.endpoint.as_ref() {
+            imp::set_parent_from_env(endpoint, &span);
+        }
+        span
+    }
+
+    /// Flush outstanding spans and shut down the export.
+    pub fn shutdown(self) -> Result<(), anyhow::Error> {
+        imp::shutdown(self.provider)
+    }
+}
+
+#[cfg(feature = "otel")]
+mod imp {
+    use opentelemetry::{propagation::TextMapPropagator as _, trace::TracerProvider as _};
+    use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::{BoxedLayer, SERVICE_NAME, TRACEPARENT};
+
+    pub type Provider = Option<opentelemetry_sdk::trace::TracerProvider>;
+
+    /// Build the tracer provider exporting to `endpoint`.
+    pub fn provider(endpoint: &str) -> Result<Provider, anyhow::Error> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| {
+                anyhow::anyhow!("could not build OTLP exporter for {}: {}", endpoint, e)
+            })?;
+        Ok(Some(
+            opentelemetry_sdk::trace::TracerProvider::builder()
+                .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+                .with_resource(opentelemetry_sdk::Resource::new(vec![
+                    opentelemetry::KeyValue::new("service.name", SERVICE_NAME),
+                ]))
+                .build(),
+        ))
+    }
+
+    /// Return the layer exporting to the tracer of `provider`.
+    pub fn layer<S>(provider: &Provider) -> Option<BoxedLayer<S>>
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    {
+        provider.as_ref().map(|provider| {
+            Box::new(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))
+                as BoxedLayer<S>
+        })
+    }
+
+    /// Set the parent of `span` from the `TRACEPARENT` environment variable.
+    pub fn set_parent_from_env(_endpoint: &str, span: &tracing::Span) {
+        if let Ok(traceparent) = std::env::var(TRACEPARENT) {
+            let carrier =
+                std::collections::HashMap::from([("traceparent".to_string(), traceparent)]);
+            span.set_parent(
+                opentelemetry_sdk::propagation::TraceContextPropagator::new().extract(&carrier),
+            );
+        }
+    }
+
+    /// Flush outstanding spans and shut down `provider`.
+    pub fn shutdown(provider: Provider) -> Result<(), anyhow::Error> {
+        if let Some(provider) = provider {
+            provider
+                .shutdown()
+                .map_err(|e| anyhow::anyhow!("could not shut down span export: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+mod imp {
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::{BoxedLayer, TRACEPARENT};
+
+    /// Placeholder as span export is not available.
+    #[derive(Debug, Default)]
+    pub struct Provider;
+
+    /// No-op as span export is not available.
+    pub fn provider(_endpoint: &str) -> Result<Provider, anyhow::Error> {
+        Ok(Provider)
+    }
+
+    /// No layer as span export is not available.
+    pub fn layer<S>(_provider: &Provider) -> Option<BoxedLayer<S>>
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    {
+        None
+    }
+
+    /// Warn that span export is not available.
+    pub fn set_parent_from_env(endpoint: &str, _span: &tracing::Span) {
+        tracing::warn!(
+            "built without `otel` feature, not exporting spans to {} (ignoring {})",
+            endpoint,
+            TRACEPARENT
+        );
+    }
+
+    /// No-op as span export is not available.
+    pub fn shutdown(_provider: Provider) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn telemetry_disabled_smoke() -> Result<(), anyhow::Error> {
+        let telemetry = super::Telemetry::new(None)?;
+        assert!(telemetry.layer::<tracing_subscriber::Registry>().is_none());
+        let _span = telemetry.root_span("seqvars query");
+        telemetry.shutdown()?;
+
+        Ok(())
+    }
+}
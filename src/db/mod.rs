@@ -0,0 +1,3 @@
+//! Maintenance of the worker and annonars database bundle.
+
+pub mod prune;
@@ -0,0 +1,409 @@
+//! Code implementing the "db prune" sub command.
+//!
+//! The position-keyed annonars databases of the genome release, e.g., frequencies,
+//! ClinVar, and CADD, are restricted to the given regions and the extents of the given
+//! genes.  All other files of the bundle, e.g., the worker and mehari databases, are
+//! copied unchanged.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use clap::{command, Parser};
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::common::GenomeRelease;
+use crate::seqvars::ingest::path_component;
+use crate::seqvars::query::schema::query::{GenomicRegion, Range};
+
+/// Number of entries to write in one batch.
+const BATCH_SIZE: usize = 10_000;
+
+/// Command line arguments for `db prune` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Restrict database bundle to genes and regions", long_about = None)]
+pub struct Args {
+    /// Genome release to prune the databases for.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to the database bundle to prune.
+    #[arg(long)]
+    pub path_db: String,
+    /// Path to the directory to write the pruned bundle to.
+    #[arg(long)]
+    pub path_output: String,
+    /// Path to gene panel file(s) with one gene symbol or identifier per line or in
+    /// PanelApp JSON format.
+    #[arg(long)]
+    pub genes: Vec<String>,
+    /// Path to BED file(s) with regions to keep.
+    #[arg(long)]
+    pub regions: Vec<String>,
+    /// Number of bases to add on each side of the genes and regions.
+    #[arg(long, default_value_t = 1_000)]
+    pub padding: i32,
+}
+
+/// Read the regions from the BED file at `path`.
+///
+/// Header lines starting with `#`, `track`, or `browser` are skipped.
+fn read_bed(path: &str) -> Result<Vec<GenomicRegion>, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("could not read BED file {}: {}", path, e))?;
+    content
+        .lines()
+        .filter(|line| {
+            !line.trim().is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with("track")
+                && !line.starts_with("browser")
+        })
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() < 3 {
+                anyhow::bail!("too few columns in BED line {:?} of {}", line, path);
+            }
+            let begin: i32 = fields[1]
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid start in BED line {:?}: {}", line, e))?;
+            let end: i32 = fields[2]
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid end in BED line {:?}: {}", line, e))?;
+            Ok(GenomicRegion {
+                chrom: annonars::common::cli::canonicalize(fields[0]),
+                range: Some(Range {
+                    start: begin + 1,
+                    stop: end,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Return the extents of the transcripts of the genes with `hgnc_ids`.
+fn gene_regions(
+    dbs: &crate::strucvars::query::Databases,
+    hgnc_ids: &HashSet<String>,
+) -> Vec<GenomicRegion> {
+    let acc_to_chrom = dbs
+        .chrom_to_acc
+        .iter()
+        .map(|(chrom, acc)| (acc.as_str(), chrom.as_str()))
+        .collect::<HashMap<_, _>>();
+    let Some(tx_db) = dbs.mehari_tx_db.tx_db.as_ref() else {
+        return Vec::new();
+    };
+    tx_db
+        .transcripts
+        .iter()
+        .filter(|tx| hgnc_ids.contains(&tx.gene_id))
+        .filter_map(|tx| {
+            let chrom = acc_to_chrom.get(tx.genome_alignments.first()?.contig.as_str())?;
+            let (begin, end) = crate::strucvars::query::tx_effects::tx_extent(tx)?;
+            Some(GenomicRegion {
+                chrom: chrom.to_string(),
+                range: Some(Range {
+                    start: begin + 1,
+                    stop: end,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Add `padding` to `regions`, then sort and merge overlapping regions.
+fn merge_regions(regions: Vec<GenomicRegion>, padding: i32) -> Vec<GenomicRegion> {
+    let mut regions = regions
+        .into_iter()
+        .filter_map(|region| {
+            let range = region.range?;
+            Some((
+                region.chrom,
+                (range.start - padding).max(1),
+                range.stop.saturating_add(padding),
+            ))
+        })
+        .collect::<Vec<_>>();
+    regions.sort();
+
+    let mut result: Vec<(String, i32, i32)> = Vec::new();
+    for (chrom, start, stop) in regions {
+        match result.last_mut() {
+            Some((last_chrom, _, last_stop)) if *last_chrom == chrom && start <= *last_stop + 1 => {
+                *last_stop = (*last_stop).max(stop);
+            }
+            _ => result.push((chrom, start, stop)),
+        }
+    }
+    result
+        .into_iter()
+        .map(|(chrom, start, stop)| GenomicRegion {
+            chrom,
+            range: Some(Range { start, stop }),
+        })
+        .collect()
+}
+
+/// Return whether `key` falls into one of the `[lower, upper)` key `ranges`.
+fn in_ranges(key: &[u8], ranges: &[(Vec<u8>, Vec<u8>)]) -> bool {
+    ranges
+        .iter()
+        .any(|(lower, upper)| lower.as_slice() <= key && key < upper.as_slice())
+}
+
+/// Copy the RocksDB at `path_in` to `path_out`, keeping the entries in the key `ranges`.
+///
+/// The `meta` column family is copied completely.  The values of the index column
+/// families, e.g., `dbsnp_by_rsid` or `clinvar_by_accession`, are variant keys and
+/// are kept if the variant is kept.  All other column families are keyed by position.
+/// Returns the number of entries written.
+fn prune_rocksdb(
+    path_in: &Path,
+    path_out: &Path,
+    ranges: &[(Vec<u8>, Vec<u8>)],
+) -> Result<usize, anyhow::Error> {
+    let cf_names = rocksdb::DB::list_cf(&rocksdb::Options::default(), path_in)?;
+    let db_in = rocksdb::DB::open_cf_for_read_only(
+        &rocksdb::Options::default(),
+        path_in,
+        &cf_names,
+        false,
+    )?;
+    let options = rocksdb_utils_lookup::tune_options(rocksdb::Options::default(), None);
+    let db_out = rocksdb::DB::open_cf_with_opts(
+        &options,
+        path_out,
+        cf_names
+            .iter()
+            .map(|name| (name.clone(), options.clone()))
+            .collect::<Vec<_>>(),
+    )?;
+
+    let mut count = 0;
+    for cf_name in &cf_names {
+        let cf_in = db_in.cf_handle(cf_name).expect("listed above");
+        let cf_out = db_out.cf_handle(cf_name).expect("created above");
+        let is_index = cf_name.contains("_by_");
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut write = |key: &[u8], value: &[u8]| -> Result<(), anyhow::Error> {
+            batch.put_cf(&cf_out, key, value);
+            count += 1;
+            if batch.len() >= BATCH_SIZE {
+                db_out.write(std::mem::take(&mut batch))?;
+            }
+            Ok(())
+        };
+        if cf_name == "meta" || cf_name == "default" || is_index {
+            for item in db_in.iterator_cf(&cf_in, rocksdb::IteratorMode::Start) {
+                let (key, value) = item?;
+                if !is_index || in_ranges(&value, ranges) {
+                    write(&key, &value)?;
+                }
+            }
+        } else {
+            for (lower, upper) in ranges {
+                let mut read_opts = rocksdb::ReadOptions::default();
+                read_opts.set_iterate_lower_bound(lower.clone());
+                read_opts.set_iterate_upper_bound(upper.clone());
+                let mut iter = db_in.raw_iterator_cf_opt(&cf_in, read_opts);
+                iter.seek(lower);
+                while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                    write(key, value)?;
+                    iter.next();
+                }
+                iter.status()?;
+            }
+        }
+        db_out.write(batch)?;
+    }
+
+    rocksdb_utils_lookup::force_compaction_cf(&db_out, &cf_names, Some("  "), true)?;
+    Ok(count)
+}
+
+/// Copy the directory `src` recursively to `dst`, skipping the directory `skip`.
+fn copy_dir(src: &Path, dst: &Path, skip: &Path) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path == skip {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &dst.join(entry.file_name()), skip)?;
+        } else {
+            std::fs::copy(&path, dst.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Main entry point for `db prune` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    if args.genes.is_empty() && args.regions.is_empty() {
+        return Err(anyhow::anyhow!("either --genes or --regions must be given"))
+            .classify(ErrorClass::Config);
+    }
+
+    let mut regions = Vec::new();
+    for path in &args.regions {
+        regions.extend(read_bed(path).classify(ErrorClass::Input)?);
+    }
+    if !args.genes.is_empty() {
+        let dbs =
+            crate::strucvars::query::Databases::load(&args.path_db, args.genome_release, 10_000)?;
+        let mut hgnc_ids = HashSet::new();
+        for path in &args.genes {
+            let panel = crate::seqvars::query::gene_panel::load(path)?;
+            hgnc_ids.extend(crate::strucvars::query::translate_genes(
+                &panel.genes,
+                &dbs.in_memory_dbs,
+                args_common.strict,
+            )?);
+        }
+        let gene_regions = gene_regions(&dbs, &hgnc_ids);
+        tracing::info!(
+            "resolved {} genes to {} transcript regions",
+            hgnc_ids.len(),
+            gene_regions.len()
+        );
+        regions.extend(gene_regions);
+    }
+    let regions = merge_regions(regions, args.padding);
+    tracing::info!("pruning to {} regions", regions.len());
+    let ranges = regions
+        .iter()
+        .filter_map(|region| {
+            let range = crate::seqvars::query::annonars::region_key_range(region);
+            if range.is_none() {
+                tracing::warn!("cannot prune databases to contig {}", &region.chrom);
+            }
+            range
+        })
+        .collect::<Vec<_>>();
+
+    let path_db = Path::new(&args.path_db);
+    let path_output = Path::new(&args.path_output);
+    let path_release = Path::new("annonars").join(path_component(args.genome_release));
+    tracing::info!("copying databases that are not pruned...");
+    copy_dir(path_db, path_output, &path_db.join(&path_release)).classify(ErrorClass::Internal)?;
+
+    for entry in std::fs::read_dir(path_db.join(&path_release)).classify(ErrorClass::Db)? {
+        let entry = entry?;
+        let path_out = path_output.join(&path_release).join(entry.file_name());
+        let path_rocksdb = entry.path().join("rocksdb");
+        if !path_rocksdb.is_dir() {
+            copy_dir(&entry.path(), &path_out, &path_rocksdb).classify(ErrorClass::Internal)?;
+            continue;
+        }
+        tracing::info!("pruning {}...", path_rocksdb.display());
+        let before_pruning = std::time::Instant::now();
+        copy_dir(&entry.path(), &path_out, &path_rocksdb).classify(ErrorClass::Internal)?;
+        let count = prune_rocksdb(&path_rocksdb, &path_out.join("rocksdb"), &ranges)
+            .map_err(|e| anyhow::anyhow!("could not prune {}: {}", path_rocksdb.display(), e))
+            .classify(ErrorClass::Db)?;
+        tracing::info!(
+            "...kept {} entries in {:?}",
+            count,
+            before_pruning.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::query::schema::query::{GenomicRegion, Range};
+
+    fn region(chrom: &str, start: i32, stop: i32) -> GenomicRegion {
+        GenomicRegion {
+            chrom: chrom.to_string(),
+            range: Some(Range { start, stop }),
+        }
+    }
+
+    #[test]
+    fn read_bed() -> Result<(), anyhow::Error> {
+        let tmp_dir = tempfile::TempDir::new()?;
+        let path = tmp_dir.path().join("regions.bed");
+        std::fs::write(
+            &path,
+            "track name=test\n# comment\nchr1\t99\t200\tA\nX\t0\t10\n",
+        )?;
+
+        assert_eq!(
+            super::read_bed(path.to_str().unwrap())?,
+            vec![region("1", 100, 200), region("X", 1, 10)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_regions() {
+        let regions = vec![
+            region("2", 100, 200),
+            region("1", 500, 600),
+            region("1", 100, 200),
+            region("1", 250, 300),
+        ];
+
+        assert_eq!(
+            super::merge_regions(regions, 25),
+            vec![
+                region("1", 75, 325),
+                region("1", 475, 625),
+                region("2", 75, 225)
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_rocksdb() -> Result<(), anyhow::Error> {
+        let tmp_dir = tempfile::TempDir::new()?;
+        let path_in = tmp_dir.path().join("in");
+        let path_out = tmp_dir.path().join("out");
+        let var_key =
+            |pos: i32| -> Vec<u8> { annonars::common::keys::Var::from("1", pos, "A", "G").into() };
+
+        {
+            let mut options = rocksdb::Options::default();
+            options.create_if_missing(true);
+            options.create_missing_column_families(true);
+            let db = rocksdb::DB::open_cf(&options, &path_in, ["meta", "data", "data_by_rsid"])?;
+            let cf_meta = db.cf_handle("meta").unwrap();
+            let cf_data = db.cf_handle("data").unwrap();
+            let cf_by_rsid = db.cf_handle("data_by_rsid").unwrap();
+            db.put_cf(&cf_meta, "genome-release", "grch37")?;
+            for (rsid, pos) in [(1i32, 100), (2, 1_000)] {
+                db.put_cf(&cf_data, var_key(pos), "value")?;
+                db.put_cf(&cf_by_rsid, rsid.to_le_bytes(), var_key(pos))?;
+            }
+        }
+
+        let ranges =
+            vec![crate::seqvars::query::annonars::region_key_range(&region("1", 50, 150)).unwrap()];
+        assert_eq!(super::prune_rocksdb(&path_in, &path_out, &ranges)?, 3);
+
+        let db = rocksdb::DB::open_cf_for_read_only(
+            &rocksdb::Options::default(),
+            &path_out,
+            ["meta", "data", "data_by_rsid"],
+            false,
+        )?;
+        let cf_meta = db.cf_handle("meta").unwrap();
+        let cf_data = db.cf_handle("data").unwrap();
+        let cf_by_rsid = db.cf_handle("data_by_rsid").unwrap();
+        assert!(db.get_cf(&cf_meta, "genome-release")?.is_some());
+        assert!(db.get_cf(&cf_data, var_key(100))?.is_some());
+        assert!(db.get_cf(&cf_data, var_key(1_000))?.is_none());
+        assert!(db.get_cf(&cf_by_rsid, 1i32.to_le_bytes())?.is_some());
+        assert!(db.get_cf(&cf_by_rsid, 2i32.to_le_bytes())?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+//! Comparison of files written by different worker versions.
+
+pub mod results;
@@ -0,0 +1,479 @@
+//! Code implementing the "diff results" sub command.
+//!
+//! The records of two result files are matched by their variant and compared field by
+//! field.  UUIDs, timestamps, and resource measurements differ between any two runs and
+//! are ignored, as are differences of floating point values within a relative tolerance.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead as _, Write as _};
+
+use clap::{command, Parser};
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+
+/// The kind of result file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResultKind {
+    /// Detect from the first line of the file.
+    Auto,
+    /// JSONL file written by `seqvars query`.
+    Seqvars,
+    /// TSV file written by `strucvars query`.
+    Strucvars,
+}
+
+/// Command line arguments for `diff results` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Compare two query result files", long_about = None)]
+pub struct Args {
+    /// Path to the result file of the old version.
+    #[arg(long)]
+    pub old: String,
+    /// Path to the result file of the new version.
+    #[arg(long)]
+    pub new: String,
+    /// The kind of the result files.
+    #[arg(long, value_enum, default_value_t = ResultKind::Auto)]
+    pub kind: ResultKind,
+    /// Relative tolerance for comparing floating point values.
+    #[arg(long, default_value_t = 1e-6)]
+    pub float_tolerance: f64,
+    /// Additional field names to ignore at any level.
+    #[arg(long)]
+    pub ignore_field: Vec<String>,
+    /// Path to the JSONL file to write the differences to; written to stdout if not given.
+    #[arg(long)]
+    pub path_output: Option<String>,
+    /// Fail with an input error if the files differ.
+    #[arg(long, default_value_t = false)]
+    pub fail_on_diff: bool,
+}
+
+/// Field names that are ignored in addition to all fields ending in `uuid`.
+const IGNORED_FIELDS: &[&str] = &["resources", "stageLatencies", "compression"];
+
+/// Columns of the `strucvars query` output that identify a record.
+const STRUCVARS_KEY_COLUMNS: &[&str] = &[
+    "chromosome",
+    "start",
+    "chromosome2",
+    "end",
+    "sv_type",
+    "sv_sub_type",
+    "pe_orientation",
+];
+
+/// Difference of one field of a record.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FieldDiff {
+    /// Path of the field, e.g., `variantAnnotation.gene.identity.hgncId`.
+    pub path: String,
+    /// The old value, `None` if the field was added.
+    pub old: Option<serde_json::Value>,
+    /// The new value, `None` if the field was removed.
+    pub new: Option<serde_json::Value>,
+}
+
+/// Difference of one record.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum RecordDiff {
+    /// The record is only in the new file.
+    Added { key: String },
+    /// The record is only in the old file.
+    Removed { key: String },
+    /// The record is in both files with differing fields.
+    Changed { key: String, fields: Vec<FieldDiff> },
+}
+
+/// Open local file or `s3://` URL `path` for reading, decompressing `.gz` and `.zst` files.
+pub(crate) fn open_read(path: &str) -> Result<Box<dyn std::io::BufRead>, anyhow::Error> {
+    if path.ends_with(".zst") {
+        let reader: Box<dyn std::io::Read> = match crate::common::s3::parse_s3_url(path)? {
+            Some((bucket, key)) => crate::common::s3::open_read_sync(bucket, key),
+            None => Box::new(std::fs::File::open(path)?),
+        };
+        Ok(Box::new(std::io::BufReader::new(
+            zstd::stream::read::Decoder::new(reader)?,
+        )))
+    } else {
+        crate::common::s3::open_read_maybe_gz(path)
+    }
+}
+
+/// Detect the kind of the result file at `path` from its first line.
+fn detect_kind(path: &str) -> Result<ResultKind, anyhow::Error> {
+    let mut first_line = String::new();
+    open_read(path)?.read_line(&mut first_line)?;
+    if first_line.trim_start().starts_with('{') {
+        Ok(ResultKind::Seqvars)
+    } else {
+        Ok(ResultKind::Strucvars)
+    }
+}
+
+/// Load the records of the `seqvars query` JSONL output from `reader`, keyed by variant.
+///
+/// The header and trailer lines are keyed as `header` and `trailer`.
+fn load_seqvars(
+    reader: Box<dyn std::io::BufRead>,
+) -> Result<BTreeMap<String, serde_json::Value>, anyhow::Error> {
+    let mut result = BTreeMap::new();
+    let mut seen_record = false;
+    for (no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("invalid JSON in line {}: {}", no + 1, e))?;
+        let key = if let Some(vcf_variant) = value.get("vcfVariant") {
+            seen_record = true;
+            format!(
+                "{}:{}:{}:{}",
+                vcf_variant["chrom"].as_str().unwrap_or_default(),
+                vcf_variant["pos"],
+                vcf_variant["refAllele"].as_str().unwrap_or_default(),
+                vcf_variant["altAllele"].as_str().unwrap_or_default(),
+            )
+        } else if seen_record {
+            "trailer".to_string()
+        } else {
+            "header".to_string()
+        };
+        insert_unique(&mut result, key, value);
+    }
+    Ok(result)
+}
+
+/// Load the records of the `strucvars query` TSV output from `reader`, keyed by the
+/// variant columns.
+///
+/// The JSON `payload` column is parsed so it is compared field by field.
+fn load_strucvars(
+    reader: Box<dyn std::io::BufRead>,
+) -> Result<BTreeMap<String, serde_json::Value>, anyhow::Error> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .quoting(false)
+        .from_reader(reader);
+    let mut result = BTreeMap::new();
+    for row in csv_reader.deserialize() {
+        let row: BTreeMap<String, String> = row?;
+        let key = STRUCVARS_KEY_COLUMNS
+            .iter()
+            .map(|column| row.get(*column).map(String::as_str).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(":");
+        let value = row
+            .into_iter()
+            .map(|(column, value)| {
+                let value = if column == "payload" {
+                    serde_json::from_str(&value)
+                        .map_err(|e| anyhow::anyhow!("invalid payload of record {}: {}", &key, e))?
+                } else {
+                    serde_json::Value::String(value)
+                };
+                Ok((column, value))
+            })
+            .collect::<Result<serde_json::Map<_, _>, anyhow::Error>>()?;
+        insert_unique(&mut result, key, serde_json::Value::Object(value));
+    }
+    Ok(result)
+}
+
+/// Insert `value` with `key` into `map`, appending `#2`, `#3`, ... for duplicate keys.
+fn insert_unique(
+    map: &mut BTreeMap<String, serde_json::Value>,
+    key: String,
+    value: serde_json::Value,
+) {
+    let mut unique_key = key.clone();
+    let mut no = 1;
+    while map.contains_key(&unique_key) {
+        no += 1;
+        unique_key = format!("{}#{}", &key, no);
+    }
+    map.insert(unique_key, value);
+}
+
+/// Flatten `value` into `out` with the path of each leaf as the key, skipping the
+/// ignored fields.
+fn flatten(
+    value: &serde_json::Value,
+    path: &str,
+    ignore: &[String],
+    out: &mut BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                if key.to_lowercase().ends_with("uuid")
+                    || IGNORED_FIELDS.contains(&key.as_str())
+                    || ignore.contains(key)
+                {
+                    continue;
+                }
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten(value, &path, ignore, out);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for (idx, value) in values.iter().enumerate() {
+                flatten(value, &format!("{}[{}]", path, idx), ignore, out);
+            }
+        }
+        _ => {
+            out.insert(path.to_string(), value.clone());
+        }
+    }
+}
+
+/// Return whether the leaf values `old` and `new` are equal, with floating point values
+/// compared with relative tolerance `float_tolerance`.
+fn values_equal(old: &serde_json::Value, new: &serde_json::Value, float_tolerance: f64) -> bool {
+    match (old.as_f64(), new.as_f64()) {
+        (Some(old), Some(new)) => {
+            (old - new).abs() <= float_tolerance * old.abs().max(new.abs()).max(1.0)
+        }
+        _ => old == new,
+    }
+}
+
+/// Compare the records `old` and `new` field by field.
+fn diff_record(
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    ignore: &[String],
+    float_tolerance: f64,
+) -> Vec<FieldDiff> {
+    let mut old_fields = BTreeMap::new();
+    flatten(old, "", ignore, &mut old_fields);
+    let mut new_fields = BTreeMap::new();
+    flatten(new, "", ignore, &mut new_fields);
+
+    let mut paths = old_fields
+        .keys()
+        .chain(new_fields.keys())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let old = old_fields.get(path);
+            let new = new_fields.get(path);
+            let equal = match (old, new) {
+                (Some(old), Some(new)) => values_equal(old, new, float_tolerance),
+                _ => false,
+            };
+            (!equal).then(|| FieldDiff {
+                path: path.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+/// Compare the keyed records `old` and `new`.
+fn diff_records(
+    old: &BTreeMap<String, serde_json::Value>,
+    new: &BTreeMap<String, serde_json::Value>,
+    ignore: &[String],
+    float_tolerance: f64,
+) -> Vec<RecordDiff> {
+    let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| match (old.get(key), new.get(key)) {
+            (Some(_), None) => Some(RecordDiff::Removed { key: key.clone() }),
+            (None, Some(_)) => Some(RecordDiff::Added { key: key.clone() }),
+            (Some(old), Some(new)) => {
+                let fields = diff_record(old, new, ignore, float_tolerance);
+                (!fields.is_empty()).then(|| RecordDiff::Changed {
+                    key: key.clone(),
+                    fields,
+                })
+            }
+            (None, None) => unreachable!("key from either map"),
+        })
+        .collect()
+}
+
+/// Load the records of the result file at `path` of `kind`.
+fn load(
+    path: &str,
+    kind: ResultKind,
+) -> Result<BTreeMap<String, serde_json::Value>, anyhow::Error> {
+    let kind = if kind == ResultKind::Auto {
+        detect_kind(path)?
+    } else {
+        kind
+    };
+    let reader = open_read(path)?;
+    match kind {
+        ResultKind::Seqvars => load_seqvars(reader),
+        ResultKind::Strucvars => load_strucvars(reader),
+        ResultKind::Auto => unreachable!("detected above"),
+    }
+    .map_err(|e| anyhow::anyhow!("could not load results from {}: {}", path, e))
+}
+
+/// Main entry point for `diff results` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let old = load(&args.old, args.kind).classify(ErrorClass::Input)?;
+    let new = load(&args.new, args.kind).classify(ErrorClass::Input)?;
+    tracing::info!("Comparing {} old to {} new records", old.len(), new.len());
+    let diffs = diff_records(&old, &new, &args.ignore_field, args.float_tolerance);
+
+    let mut writer: Box<dyn std::io::Write> = if let Some(path_output) = &args.path_output {
+        Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_output).map_err(|e| {
+                anyhow::anyhow!("could not create output file {}: {}", path_output, e)
+            })?,
+        ))
+    } else {
+        Box::new(std::io::stdout().lock())
+    };
+    for diff in &diffs {
+        writeln!(writer, "{}", serde_json::to_string(diff)?)?;
+    }
+    writer.flush()?;
+
+    let count = |pred: fn(&RecordDiff) -> bool| diffs.iter().filter(|diff| pred(diff)).count();
+    tracing::info!(
+        "{} added, {} removed, {} changed records",
+        count(|diff| matches!(diff, RecordDiff::Added { .. })),
+        count(|diff| matches!(diff, RecordDiff::Removed { .. })),
+        count(|diff| matches!(diff, RecordDiff::Changed { .. })),
+    );
+    if args.fail_on_diff && !diffs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} and {} differ in {} records",
+            &args.old,
+            &args.new,
+            diffs.len()
+        ))
+        .classify(ErrorClass::Input);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{FieldDiff, RecordDiff};
+
+    #[test]
+    fn load_seqvars() -> Result<(), anyhow::Error> {
+        let data = "{\"genomeRelease\":\"GENOME_RELEASE_GRCH37\"}\n\
+            {\"uuid\":\"a\",\"vcfVariant\":{\"chrom\":\"1\",\"pos\":100,\"refAllele\":\"A\",\"altAllele\":\"G\"}}\n\
+            {\"geneCache\":[]}\n";
+        let records = super::load_seqvars(Box::new(std::io::Cursor::new(data)))?;
+
+        assert_eq!(
+            records.keys().collect::<Vec<_>>(),
+            vec!["1:100:A:G", "header", "trailer"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_strucvars() -> Result<(), anyhow::Error> {
+        let data = "sodar_uuid\tchromosome\tstart\tchromosome2\tend\tsv_type\tsv_sub_type\tpe_orientation\tpayload\n\
+            a\t1\t100\t1\t200\tDEL\tDEL\t3to5\t{\"overlap_counts\":{\"g1k\":1}}\n\
+            b\t1\t100\t1\t200\tDEL\tDEL\t3to5\t{}\n";
+        let records = super::load_strucvars(Box::new(std::io::Cursor::new(data)))?;
+
+        assert_eq!(
+            records.keys().collect::<Vec<_>>(),
+            vec!["1:100:1:200:DEL:DEL:3to5", "1:100:1:200:DEL:DEL:3to5#2"]
+        );
+        assert_eq!(
+            records["1:100:1:200:DEL:DEL:3to5"]["payload"]["overlap_counts"]["g1k"],
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff_records() {
+        let old = [
+            (
+                "a".to_string(),
+                json!({"uuid": "1", "score": 0.5, "gene": "BRCA1"}),
+            ),
+            (
+                "b".to_string(),
+                json!({"uuid": "2", "resources": {"memoryUsed": 1}}),
+            ),
+            ("c".to_string(), json!({"uuid": "3"})),
+        ]
+        .into_iter()
+        .collect();
+        let new = [
+            (
+                "a".to_string(),
+                json!({"uuid": "4", "score": 0.5000000001, "gene": "BRCA2", "tags": ["x"]}),
+            ),
+            (
+                "b".to_string(),
+                json!({"uuid": "5", "resources": {"memoryUsed": 2}}),
+            ),
+            ("d".to_string(), json!({"uuid": "6"})),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            super::diff_records(&old, &new, &[], 1e-6),
+            vec![
+                RecordDiff::Changed {
+                    key: "a".into(),
+                    fields: vec![
+                        FieldDiff {
+                            path: "gene".into(),
+                            old: Some(json!("BRCA1")),
+                            new: Some(json!("BRCA2")),
+                        },
+                        FieldDiff {
+                            path: "tags[0]".into(),
+                            old: None,
+                            new: Some(json!("x")),
+                        },
+                    ],
+                },
+                RecordDiff::Removed { key: "c".into() },
+                RecordDiff::Added { key: "d".into() },
+            ]
+        );
+        assert_eq!(
+            super::diff_records(&old, &new, &["gene".to_string(), "tags".to_string()], 1e-6).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn record_diff_serialize() -> Result<(), anyhow::Error> {
+        assert_eq!(
+            serde_json::to_string(&RecordDiff::Added { key: "a".into() })?,
+            r#"{"change":"added","key":"a"}"#
+        );
+
+        Ok(())
+    }
+}
@@ -1,13 +1,28 @@
 //! VarFish Server Worker main executable
 
+pub mod bench;
 pub mod common;
+pub mod db;
+pub mod diff;
 pub mod pbs;
+pub mod schema;
+pub mod selftest;
 pub mod seqvars;
 pub mod strucvars;
 
 use clap::{Args, Parser, Subcommand};
 use console::{Emoji, Term};
 
+/// Use jemalloc with profiling enabled (but inactive until `--heap-profile` is given).
+#[cfg(feature = "heap-profile")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(feature = "heap-profile")]
+#[allow(non_upper_case_globals)]
+#[export_name = "_rjem_malloc_conf"]
+pub static malloc_conf: &[u8] = b"prof:true,prof_active:false,lg_prof_sample:19\0";
+
 /// CLI parser based on clap.
 #[derive(Debug, Parser)]
 #[command(
@@ -34,6 +49,16 @@ enum Commands {
     Strucvars(Strucvars),
     /// Sequence variant related commands.
     Seqvars(Seqvars),
+    /// Benchmark related commands.
+    Bench(Bench),
+    /// Commands for comparing outputs of different versions.
+    Diff(Diff),
+    /// Database bundle related commands.
+    Db(Db),
+    /// Output schema related commands.
+    Schema(Schema),
+    /// Run bundled inputs through all pipeline stages.
+    Selftest(selftest::Args),
 }
 
 /// Parsing of "strucvars *" sub commands.
@@ -49,8 +74,11 @@ struct Strucvars {
 #[derive(Debug, Subcommand)]
 enum StrucvarsCommands {
     Aggregate(strucvars::aggregate::cli::Args),
+    BinToTxt(strucvars::bin_to_txt::Args),
     Ingest(strucvars::ingest::Args),
+    Qc(strucvars::qc::Args),
     Query(strucvars::query::Args),
+    QueryBatch(strucvars::query::batch::Args),
     TxtToBin(strucvars::txt_to_bin::cli::Args),
 }
 
@@ -66,41 +94,234 @@ struct Seqvars {
 /// Enum supporting the parsing of "strucvars *" sub commands.
 #[derive(Debug, Subcommand)]
 enum SeqvarsCommands {
-    Aggregate(seqvars::aggregate::Args),
+    Aggregate(SeqvarsAggregate),
+    ExportVcf(seqvars::export_vcf::Args),
     Ingest(seqvars::ingest::Args),
     Prefilter(seqvars::prefilter::Args),
+    Qc(seqvars::qc::Args),
     Query(seqvars::query::Args),
+    QueryBatch(seqvars::query::batch::Args),
+    Resolve(seqvars::resolve::Args),
+}
+
+/// Parsing of "seqvars aggregate" and its sub commands.
+///
+/// Without a sub command, the in-house database is built/updated.
+#[derive(Debug, Args)]
+#[command(
+    about = "build/update seqvars inhouse dabase",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct SeqvarsAggregate {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: Option<SeqvarsAggregateCommands>,
+    /// Arguments for building/updating the database.
+    #[command(flatten)]
+    args: Option<seqvars::aggregate::Args>,
+}
+
+/// Enum supporting the parsing of "seqvars aggregate *" sub commands.
+#[derive(Debug, Subcommand)]
+enum SeqvarsAggregateCommands {
+    Stats(seqvars::aggregate::stats::Args),
+}
+
+/// Parsing of "bench *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Bench {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: BenchCommands,
+}
+
+/// Enum supporting the parsing of "bench *" sub commands.
+#[derive(Debug, Subcommand)]
+enum BenchCommands {
+    Aggregate(bench::aggregate::Args),
+    SeqvarsQuery(bench::seqvars_query::Args),
+}
+
+/// Parsing of "diff *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Diff {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: DiffCommands,
+}
+
+/// Enum supporting the parsing of "diff *" sub commands.
+#[derive(Debug, Subcommand)]
+enum DiffCommands {
+    Results(diff::results::Args),
+}
+
+/// Parsing of "db *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Db {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: DbCommands,
+}
+
+/// Enum supporting the parsing of "db *" sub commands.
+#[derive(Debug, Subcommand)]
+enum DbCommands {
+    Prune(db::prune::Args),
+}
+
+/// Parsing of "schema *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Schema {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: SchemaCommands,
+}
+
+/// Enum supporting the parsing of "schema *" sub commands.
+#[derive(Debug, Subcommand)]
+enum SchemaCommands {
+    Labels(schema::labels::Args),
 }
 
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> std::process::ExitCode {
+    use common::errors::ErrorClass;
+
     let cli = Cli::parse();
+    let result = tokio::select! {
+        result = run(&cli) => result,
+        _ = tokio::signal::ctrl_c() => {
+            Err(anyhow::anyhow!("interrupted by user").context(ErrorClass::Cancelled))
+        }
+    };
 
-    // Build a tracing subscriber according to the configuration in `cli.common`.
-    let collector = tracing_subscriber::fmt()
-        .with_target(false)
-        .with_max_level(match cli.common.verbose.log_level() {
-            Some(level) => match level {
-                log::Level::Error => tracing::Level::ERROR,
-                log::Level::Warn => tracing::Level::WARN,
-                log::Level::Info => tracing::Level::INFO,
-                log::Level::Debug => tracing::Level::DEBUG,
-                log::Level::Trace => tracing::Level::TRACE,
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::ExitCode::from(ErrorClass::of(&e).exit_code())
+        }
+    }
+}
+
+/// Return the name of the sub command selected in `command`, e.g., `"seqvars query"`.
+fn command_name(command: &Commands) -> &'static str {
+    match command {
+        Commands::Seqvars(seqvars) => match &seqvars.command {
+            SeqvarsCommands::Aggregate(aggregate) => match &aggregate.command {
+                Some(SeqvarsAggregateCommands::Stats(_)) => "seqvars aggregate stats",
+                None => "seqvars aggregate",
             },
-            None => tracing::Level::INFO,
-        })
-        .compact()
-        .finish();
+            SeqvarsCommands::ExportVcf(_) => "seqvars export-vcf",
+            SeqvarsCommands::Ingest(_) => "seqvars ingest",
+            SeqvarsCommands::Prefilter(_) => "seqvars prefilter",
+            SeqvarsCommands::Qc(_) => "seqvars qc",
+            SeqvarsCommands::Query(_) => "seqvars query",
+            SeqvarsCommands::QueryBatch(_) => "seqvars query-batch",
+            SeqvarsCommands::Resolve(_) => "seqvars resolve",
+        },
+        Commands::Strucvars(strucvars) => match &strucvars.command {
+            StrucvarsCommands::Aggregate(_) => "strucvars aggregate",
+            StrucvarsCommands::BinToTxt(_) => "strucvars bin-to-txt",
+            StrucvarsCommands::Ingest(_) => "strucvars ingest",
+            StrucvarsCommands::Qc(_) => "strucvars qc",
+            StrucvarsCommands::Query(_) => "strucvars query",
+            StrucvarsCommands::QueryBatch(_) => "strucvars query-batch",
+            StrucvarsCommands::TxtToBin(_) => "strucvars txt-to-bin",
+        },
+        Commands::Bench(bench) => match &bench.command {
+            BenchCommands::Aggregate(_) => "bench aggregate",
+            BenchCommands::SeqvarsQuery(_) => "bench seqvars-query",
+        },
+        Commands::Diff(diff) => match &diff.command {
+            DiffCommands::Results(_) => "diff results",
+        },
+        Commands::Db(db) => match &db.command {
+            DbCommands::Prune(_) => "db prune",
+        },
+        Commands::Schema(schema) => match &schema.command {
+            SchemaCommands::Labels(_) => "schema labels",
+        },
+        Commands::Selftest(_) => "selftest",
+    }
+}
+
+/// Run the sub command selected in `cli`.
+async fn run(cli: &Cli) -> Result<(), anyhow::Error> {
+    use common::errors::ClassifyExt as _;
+    use tracing::Instrument as _;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    // Build a tracing subscriber according to the configuration in `cli.common`, with
+    // optional export of the spans.
+    let telemetry = common::telemetry::Telemetry::new(cli.common.otel_endpoint.as_deref())?;
+    let level = match cli.common.verbose.log_level() {
+        Some(level) => match level {
+            log::Level::Error => tracing::Level::ERROR,
+            log::Level::Warn => tracing::Level::WARN,
+            log::Level::Info => tracing::Level::INFO,
+            log::Level::Debug => tracing::Level::DEBUG,
+            log::Level::Trace => tracing::Level::TRACE,
+        },
+        None => tracing::Level::INFO,
+    };
+    let collector = tracing_subscriber::registry()
+        .with(tracing_subscriber::filter::LevelFilter::from_level(level))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .compact(),
+        )
+        .with(telemetry.layer());
     tracing::subscriber::set_global_default(collector)?;
+    common::noodles::set_decompression_threads(cli.common.threads);
+    std::time::Duration::try_from_secs_f64(cli.common.s3_retry_delay)
+        .map_err(|e| anyhow::anyhow!("invalid S3 retry delay: {}", e))
+        .and_then(|retry_delay| {
+            common::s3::set_upload_config(common::s3::UploadConfig {
+                part_size: cli.common.s3_part_size * 1024 * 1024,
+                attempts: cli.common.s3_upload_attempts,
+                retry_delay,
+            })
+        })
+        .classify(common::errors::ErrorClass::Config)?;
 
-    // Install collector and go into sub commands.
+    let span = telemetry.root_span(command_name(&cli.command));
+    let result = run_command(cli).instrument(span).await;
+    telemetry.shutdown()?;
+    result
+}
+
+/// Run the sub command selected in `cli` after setting up tracing.
+async fn run_command(cli: &Cli) -> Result<(), anyhow::Error> {
+    // Go into sub commands.
     let term = Term::stderr();
     match &cli.command {
         Commands::Seqvars(seqvars) => match &seqvars.command {
-            SeqvarsCommands::Aggregate(args) => {
-                // Note that aggregate is not async as it uses Rayon and will
-                // block internally for the read files.
-                seqvars::aggregate::run(&cli.common, args).await?;
+            SeqvarsCommands::Aggregate(aggregate) => match (&aggregate.command, &aggregate.args) {
+                (Some(SeqvarsAggregateCommands::Stats(args)), _) => {
+                    seqvars::aggregate::stats::run(&cli.common, args).await?;
+                }
+                (None, Some(args)) => {
+                    // Note that aggregate is not async as it uses Rayon and will
+                    // block internally for the read files.
+                    seqvars::aggregate::run(&cli.common, args).await?;
+                }
+                (None, None) => {
+                    return Err(anyhow::anyhow!(
+                        "seqvars aggregate requires --genomebuild and --path-out-rocksdb"
+                    )
+                    .context(common::errors::ErrorClass::Config));
+                }
+            },
+            SeqvarsCommands::ExportVcf(args) => {
+                seqvars::export_vcf::run(&cli.common, args).await?;
             }
             SeqvarsCommands::Ingest(args) => {
                 seqvars::ingest::run(&cli.common, args).await?;
@@ -108,24 +329,68 @@ async fn main() -> Result<(), anyhow::Error> {
             SeqvarsCommands::Prefilter(args) => {
                 seqvars::prefilter::run(&cli.common, args).await?;
             }
+            SeqvarsCommands::Qc(args) => {
+                seqvars::qc::run(&cli.common, args).await?;
+            }
             SeqvarsCommands::Query(args) => {
                 seqvars::query::run(&cli.common, args).await?;
             }
+            SeqvarsCommands::QueryBatch(args) => {
+                seqvars::query::batch::run(&cli.common, args).await?;
+            }
+            SeqvarsCommands::Resolve(args) => {
+                seqvars::resolve::run(&cli.common, args).await?;
+            }
         },
         Commands::Strucvars(strucvars) => match &strucvars.command {
             StrucvarsCommands::Aggregate(args) => {
                 strucvars::aggregate::cli::run(&cli.common, args).await?;
             }
+            StrucvarsCommands::BinToTxt(args) => {
+                strucvars::bin_to_txt::run(&cli.common, args)?;
+            }
             StrucvarsCommands::Ingest(args) => {
                 strucvars::ingest::run(&cli.common, args).await?;
             }
+            StrucvarsCommands::Qc(args) => {
+                strucvars::qc::run(&cli.common, args).await?;
+            }
             StrucvarsCommands::Query(args) => {
                 strucvars::query::run(&cli.common, args).await?;
             }
+            StrucvarsCommands::QueryBatch(args) => {
+                strucvars::query::batch::run(&cli.common, args).await?;
+            }
             StrucvarsCommands::TxtToBin(args) => {
                 strucvars::txt_to_bin::cli::run(&cli.common, args)?;
             }
         },
+        Commands::Bench(bench) => match &bench.command {
+            BenchCommands::Aggregate(args) => {
+                bench::aggregate::run(&cli.common, args).await?;
+            }
+            BenchCommands::SeqvarsQuery(args) => {
+                bench::seqvars_query::run(&cli.common, args).await?;
+            }
+        },
+        Commands::Diff(diff) => match &diff.command {
+            DiffCommands::Results(args) => {
+                diff::results::run(&cli.common, args).await?;
+            }
+        },
+        Commands::Db(db) => match &db.command {
+            DbCommands::Prune(args) => {
+                db::prune::run(&cli.common, args).await?;
+            }
+        },
+        Commands::Schema(schema) => match &schema.command {
+            SchemaCommands::Labels(args) => {
+                schema::labels::run(&cli.common, args)?;
+            }
+        },
+        Commands::Selftest(args) => {
+            selftest::run(&cli.common, args).await?;
+        }
     }
     term.write_line(&format!("All done. Have a nice day!{}", Emoji(" 😃", "")))?;
 
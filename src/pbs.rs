@@ -40,6 +40,16 @@ pub mod varfish {
                         }
                     }
                 }
+
+                impl From<crate::common::GonosomalRegion> for GonosomalRegion {
+                    fn from(region: crate::common::GonosomalRegion) -> Self {
+                        match region {
+                            crate::common::GonosomalRegion::Par1 => GonosomalRegion::Par1,
+                            crate::common::GonosomalRegion::Par2 => GonosomalRegion::Par2,
+                            crate::common::GonosomalRegion::Xtr => GonosomalRegion::Xtr,
+                        }
+                    }
+                }
             }
         }
 
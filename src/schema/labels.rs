@@ -0,0 +1,320 @@
+//! Code implementing the "schema labels" sub command.
+//!
+//! The values of the enumerations in the output are mapped to their stable codes, i.e.,
+//! the protobuf names as written to the JSON output, and English labels.  The values are
+//! enumerated from the generated protobuf code and the labels are assigned by exhaustive
+//! matches, so new values cannot be added without a label.
+
+use std::io::Write as _;
+
+use clap::{command, Parser};
+use indexmap::IndexMap;
+
+use crate::pbs::varfish::v1::seqvars::{output as pbs_output, query as pbs_query};
+
+/// Command line arguments for `schema labels` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Export labels of output enumeration values", long_about = None)]
+pub struct Args {
+    /// Path to the JSON file to write the labels to; written to stdout if not given.
+    #[arg(long)]
+    pub path_output: Option<String>,
+}
+
+/// A value of an output enumeration.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Label {
+    /// Stable code as written to the output, e.g., `CONSEQUENCE_MISSENSE_VARIANT`.
+    pub code: &'static str,
+    /// Integer value in the protobuf schema.
+    pub value: i32,
+    /// English label.
+    pub label: &'static str,
+}
+
+/// The labels of all output enumerations.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LabelMap {
+    /// Version of the worker that the labels were exported from.
+    pub worker_version: &'static str,
+    /// Labels by fully qualified protobuf enumeration name.
+    pub enums: IndexMap<&'static str, Vec<Label>>,
+}
+
+impl LabelMap {
+    /// Build the label map from the generated protobuf enumerations.
+    pub fn build() -> Self {
+        let enums = IndexMap::from([
+            (
+                "varfish.v1.seqvars.query.Consequence",
+                labels(pbs_query::Consequence::as_str_name, consequence),
+            ),
+            (
+                "varfish.v1.seqvars.query.ClinvarGermlineAggregateDescription",
+                labels(
+                    pbs_query::ClinvarGermlineAggregateDescription::as_str_name,
+                    clinvar_germline_aggregate_description,
+                ),
+            ),
+            (
+                "varfish.v1.seqvars.output.TranscriptType",
+                labels(pbs_output::TranscriptType::as_str_name, transcript_type),
+            ),
+            (
+                "varfish.v1.seqvars.output.VariantLocation",
+                labels(pbs_output::VariantLocation::as_str_name, variant_location),
+            ),
+            (
+                "varfish.v1.seqvars.output.LofConfidence",
+                labels(pbs_output::LofConfidence::as_str_name, lof_confidence),
+            ),
+            (
+                "varfish.v1.seqvars.output.ModeOfInheritance",
+                labels(
+                    pbs_output::ModeOfInheritance::as_str_name,
+                    mode_of_inheritance,
+                ),
+            ),
+            (
+                "varfish.v1.seqvars.output.ClingenDosageScore",
+                labels(
+                    pbs_output::ClingenDosageScore::as_str_name,
+                    clingen_dosage_score,
+                ),
+            ),
+            (
+                "varfish.v1.seqvars.output.GonosomalRegion",
+                labels(pbs_output::GonosomalRegion::as_str_name, gonosomal_region),
+            ),
+            (
+                "varfish.v1.seqvars.output.AggregateGermlineReviewStatus",
+                labels(
+                    pbs_output::AggregateGermlineReviewStatus::as_str_name,
+                    aggregate_germline_review_status,
+                ),
+            ),
+        ]);
+        Self {
+            worker_version: crate::common::worker_version(),
+            enums,
+        }
+    }
+}
+
+/// Return the labels of all values of the enumeration `E`.
+///
+/// The protobuf values are enumerated from zero until the first undefined value.
+fn labels<E>(code: fn(&E) -> &'static str, label: fn(E) -> &'static str) -> Vec<Label>
+where
+    E: TryFrom<i32>,
+{
+    (0..)
+        .map_while(|value| {
+            E::try_from(value).ok().map(|e| Label {
+                code: code(&e),
+                value,
+                label: label(e),
+            })
+        })
+        .collect()
+}
+
+/// Label of a `Consequence`, following the Sequence Ontology term.
+fn consequence(value: pbs_query::Consequence) -> &'static str {
+    use pbs_query::Consequence::*;
+    match value {
+        Unspecified => "Unspecified",
+        TranscriptAblation => "Transcript ablation",
+        ExonLossVariant => "Exon loss variant",
+        SpliceAcceptorVariant => "Splice acceptor variant",
+        SpliceDonorVariant => "Splice donor variant",
+        StopGained => "Stop gained",
+        FrameshiftVariant => "Frameshift variant",
+        StopLost => "Stop lost",
+        StartLost => "Start lost",
+        TranscriptAmplification => "Transcript amplification",
+        FeatureElongation => "Feature elongation",
+        FeatureTruncation => "Feature truncation",
+        DisruptiveInframeInsertion => "Disruptive inframe insertion",
+        DisruptiveInframeDeletion => "Disruptive inframe deletion",
+        ConservativeInframeInsertion => "Conservative inframe insertion",
+        ConservativeInframeDeletion => "Conservative inframe deletion",
+        MissenseVariant => "Missense variant",
+        SpliceDonorFifthBaseVariant => "Splice donor 5th base variant",
+        SpliceRegionVariant => "Splice region variant",
+        SpliceDonorRegionVariant => "Splice donor region variant",
+        SplicePolypyrimidineTractVariant => "Splice polypyrimidine tract variant",
+        StartRetainedVariant => "Start retained variant",
+        StopRetainedVariant => "Stop retained variant",
+        SynonymousVariant => "Synonymous variant",
+        CodingSequenceVariant => "Coding sequence variant",
+        MatureMirnaVariant => "Mature miRNA variant",
+        FivePrimeUtrExonVariant => "5' UTR exon variant",
+        FivePrimeUtrIntronVariant => "5' UTR intron variant",
+        ThreePrimeUtrExonVariant => "3' UTR exon variant",
+        ThreePrimeUtrIntronVariant => "3' UTR intron variant",
+        NonCodingTranscriptExonVariant => "Non-coding transcript exon variant",
+        NonCodingTranscriptIntronVariant => "Non-coding transcript intron variant",
+        UpstreamGeneVariant => "Upstream gene variant",
+        DownstreamGeneVariant => "Downstream gene variant",
+        TfbsAblation => "TFBS ablation",
+        TfbsAmplification => "TFBS amplification",
+        TfBindingSiteVariant => "TF binding site variant",
+        RegulatoryRegionAblation => "Regulatory region ablation",
+        RegulatoryRegionAmplification => "Regulatory region amplification",
+        RegulatoryRegionVariant => "Regulatory region variant",
+        IntergenicVariant => "Intergenic variant",
+        IntronVariant => "Intron variant",
+        GeneVariant => "Gene variant",
+    }
+}
+
+/// Label of a `ClinvarGermlineAggregateDescription`.
+fn clinvar_germline_aggregate_description(
+    value: pbs_query::ClinvarGermlineAggregateDescription,
+) -> &'static str {
+    use pbs_query::ClinvarGermlineAggregateDescription::*;
+    match value {
+        Unspecified => "Unspecified",
+        Pathogenic => "Pathogenic",
+        LikelyPathogenic => "Likely pathogenic",
+        UncertainSignificance => "Uncertain significance",
+        LikelyBenign => "Likely benign",
+        Benign => "Benign",
+    }
+}
+
+/// Label of a `TranscriptType`.
+fn transcript_type(value: pbs_output::TranscriptType) -> &'static str {
+    use pbs_output::TranscriptType::*;
+    match value {
+        Unspecified => "Unspecified",
+        Coding => "Coding",
+        NonCoding => "Non-coding",
+    }
+}
+
+/// Label of a `VariantLocation`.
+fn variant_location(value: pbs_output::VariantLocation) -> &'static str {
+    use pbs_output::VariantLocation::*;
+    match value {
+        Unspecified => "Unspecified",
+        Upstream => "Upstream",
+        Exon => "Exon",
+        Intron => "Intron",
+        Downstream => "Downstream",
+    }
+}
+
+/// Label of a `LofConfidence`.
+fn lof_confidence(value: pbs_output::LofConfidence) -> &'static str {
+    use pbs_output::LofConfidence::*;
+    match value {
+        Unspecified => "Unspecified",
+        High => "High confidence",
+        Low => "Low confidence",
+    }
+}
+
+/// Label of a `ModeOfInheritance`, following the HPO term.
+fn mode_of_inheritance(value: pbs_output::ModeOfInheritance) -> &'static str {
+    use pbs_output::ModeOfInheritance::*;
+    match value {
+        Unspecified => "Unspecified",
+        AutosomalDominant => "Autosomal dominant",
+        AutosomalRecessive => "Autosomal recessive",
+        XLinkedDominant => "X-linked dominant",
+        XLinkedRecessive => "X-linked recessive",
+        YLinked => "Y-linked",
+        Mitochondrial => "Mitochondrial",
+    }
+}
+
+/// Label of a `ClingenDosageScore`, following the ClinGen dosage sensitivity curation.
+fn clingen_dosage_score(value: pbs_output::ClingenDosageScore) -> &'static str {
+    use pbs_output::ClingenDosageScore::*;
+    match value {
+        Unspecified => "Unspecified",
+        SufficientEvidenceAvailable => "Sufficient evidence for dosage pathogenicity",
+        SomeEvidenceAvailable => "Some evidence for dosage pathogenicity",
+        LittleEvidence => "Little evidence for dosage pathogenicity",
+        NoEvidenceAvailable => "No evidence available",
+        Recessive => "Gene associated with autosomal recessive phenotype",
+        Unlikely => "Dosage sensitivity unlikely",
+    }
+}
+
+/// Label of a `GonosomalRegion`.
+fn gonosomal_region(value: pbs_output::GonosomalRegion) -> &'static str {
+    use pbs_output::GonosomalRegion::*;
+    match value {
+        Unspecified => "Unspecified",
+        Par1 => "Pseudoautosomal region 1",
+        Par2 => "Pseudoautosomal region 2",
+        Xtr => "X-transposed region",
+    }
+}
+
+/// Label of an `AggregateGermlineReviewStatus`, following the ClinVar review status.
+fn aggregate_germline_review_status(
+    value: pbs_output::AggregateGermlineReviewStatus,
+) -> &'static str {
+    use pbs_output::AggregateGermlineReviewStatus::*;
+    match value {
+        Unspecified => "Unspecified",
+        NoClassificationProvided => "No classification provided",
+        NoAssertionCriteriaProvided => "No assertion criteria provided",
+        CriteriaProvidedSingleSubmitter => "Criteria provided, single submitter",
+        CriteriaProvidedMultipleSubmittersNoConflicts => {
+            "Criteria provided, multiple submitters, no conflicts"
+        }
+        CriteriaProvidedConflictingClassifications => {
+            "Criteria provided, conflicting classifications"
+        }
+        ReviewedByExpertPanel => "Reviewed by expert panel",
+        PracticeGuideline => "Practice guideline",
+        NoClassificationsFromUnflaggedRecords => "No classifications from unflagged records",
+        NoClassificationForTheSingleVariant => "No classification for the single variant",
+    }
+}
+
+/// Main entry point for `schema labels` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let label_map = LabelMap::build();
+    let mut writer: Box<dyn std::io::Write> = if let Some(path_output) = &args.path_output {
+        Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_output).map_err(|e| {
+                anyhow::anyhow!("could not create output file {}: {}", path_output, e)
+            })?,
+        ))
+    } else {
+        Box::new(std::io::stdout().lock())
+    };
+    writeln!(writer, "{}", serde_json::to_string_pretty(&label_map)?)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::LabelMap;
+
+    #[test]
+    fn label_map() {
+        let label_map = LabelMap::build();
+
+        let consequences = &label_map.enums["varfish.v1.seqvars.query.Consequence"];
+        assert_eq!(consequences.len(), 43);
+        assert_eq!(consequences[16].code, "CONSEQUENCE_MISSENSE_VARIANT");
+        assert_eq!(consequences[16].value, 16);
+        assert_eq!(consequences[16].label, "Missense variant");
+        for labels in label_map.enums.values() {
+            assert_eq!(labels[0].value, 0);
+            assert!(labels[0].code.ends_with("_UNSPECIFIED"));
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Export of machine-readable information on the output schema.
+
+pub mod labels;
@@ -0,0 +1,381 @@
+//! Implementation of the `selftest` subcommand.
+//!
+//! Bundled mini inputs are taken through the full pipeline of `seqvars ingest`,
+//! `seqvars aggregate`, and `seqvars query` (and `strucvars ingest`, `strucvars aggregate`,
+//! `strucvars txt-to-bin`, and `strucvars query`) with the databases of the deployment.
+//! The output of each stage is checked to be readable by the next stage so that
+//! incompatibilities between the stages surface before production data is processed.
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser as _;
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+
+/// Bundled small variant VCF file of a trio.
+const SEQVARS_VCF: &str = include_str!("../../tests/seqvars/ingest/Case_1.vcf");
+/// Pedigree of the bundled small variant VCF file.
+const SEQVARS_PED: &str = include_str!("../../tests/seqvars/ingest/Case_1.ped");
+/// Query for the bundled small variants.
+const SEQVARS_QUERY: &str = include_str!("../../tests/seqvars/query/Case_1.query.json");
+/// Bundled structural variant VCF file of a singleton.
+const STRUCVARS_VCF: &str = include_str!("../../tests/strucvars/ingest/manta-min.vcf");
+/// Pedigree of the bundled structural variant VCF file.
+const STRUCVARS_PED: &str = include_str!("../../tests/strucvars/ingest/manta-min.ped");
+/// Query for the bundled structural variants.
+const STRUCVARS_QUERY: &str = include_str!("../../tests/selftest/strucvars.query.json");
+
+/// Case UUID of the bundled inputs.
+const CASE_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Command line arguments for `selftest` sub command.
+#[derive(clap::Parser, Debug)]
+#[command(
+    author,
+    version,
+    about = "Run bundled inputs through all pipeline stages",
+    long_about = None
+)]
+pub struct Args {
+    /// Path to the mehari database used by `seqvars ingest`.
+    #[arg(long)]
+    pub path_mehari_db: String,
+    /// Path to the worker database used by `seqvars query`.
+    #[arg(long)]
+    pub path_db: String,
+    /// Path to the worker database used by `strucvars query`, defaults to `--path-db`.
+    #[arg(long)]
+    pub path_strucvars_db: Option<String>,
+    /// Directory to keep the intermediate files in, a temporary directory is used if not given.
+    #[arg(long)]
+    pub path_work_dir: Option<String>,
+    /// Skip the structural variant stages.
+    #[arg(long, default_value_t = false)]
+    pub skip_strucvars: bool,
+}
+
+/// Run the stage `name` by awaiting `f`, adding the stage name to the error.
+///
+/// The class of the error is kept such that the exit code reflects the failure of the stage.
+async fn run_stage<F, T>(name: &str, f: F) -> Result<T, anyhow::Error>
+where
+    F: std::future::Future<Output = Result<T, anyhow::Error>>,
+{
+    tracing::info!("Running stage `{}`", name);
+    let before = std::time::Instant::now();
+    let result = f
+        .await
+        .map_err(|e| e.context(format!("self test stage `{}` failed", name)))?;
+    tracing::info!("... stage `{}` done in {:?}", name, before.elapsed());
+    Ok(result)
+}
+
+/// Write the bundled `contents` to `file_name` in `work_dir` and return its path.
+fn write_input(work_dir: &Path, file_name: &str, contents: &str) -> Result<String, anyhow::Error> {
+    let path = work_dir.join(file_name);
+    std::fs::write(&path, contents)
+        .map_err(|e| anyhow::anyhow!("could not write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// Check that the in-house counts written by `seqvars aggregate` are used by `seqvars query`.
+fn check_seqvars_result(path_output: &str) -> Result<(), anyhow::Error> {
+    let result_set = crate::diff::results::open_read(path_output)
+        .and_then(crate::seqvars::export_vcf::read_result_set)?;
+    if result_set.header.case_uuid != CASE_UUID {
+        anyhow::bail!(
+            "result header has case UUID {} instead of {}",
+            result_set.header.case_uuid,
+            CASE_UUID
+        );
+    }
+    if result_set.records.is_empty() {
+        anyhow::bail!("query returned no records");
+    }
+    let with_inhouse = result_set
+        .records
+        .iter()
+        .filter(|record| {
+            record
+                .variant_annotation
+                .as_ref()
+                .and_then(|annotation| annotation.variant.as_ref())
+                .and_then(|variant| variant.frequency.as_ref())
+                .and_then(|frequency| frequency.inhouse.as_ref())
+                .map_or(false, |inhouse| {
+                    inhouse.het + inhouse.homalt + inhouse.hemialt > 0
+                })
+        })
+        .count();
+    if with_inhouse == 0 {
+        anyhow::bail!(
+            "none of the {} records has in-house carriers",
+            result_set.records.len()
+        );
+    }
+    tracing::info!(
+        "... {} records, {} with in-house carriers",
+        result_set.records.len(),
+        with_inhouse
+    );
+    Ok(())
+}
+
+/// Check that the output of `strucvars query` has the expected columns.
+fn check_strucvars_result(path_output: &str) -> Result<(), anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .quoting(false)
+        .from_path(path_output)?;
+    let columns = reader
+        .headers()?
+        .iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let expected = crate::strucvars::query::result_columns()?;
+    if columns != expected {
+        anyhow::bail!("result has columns {:?} instead of {:?}", columns, expected);
+    }
+    tracing::info!("... {} records", reader.records().count());
+    Ok(())
+}
+
+/// Run the small variant stages in `work_dir`.
+async fn run_seqvars(
+    args_common: &crate::common::Args,
+    args: &Args,
+    work_dir: &Path,
+) -> Result<(), anyhow::Error> {
+    let path_vcf = write_input(work_dir, "seqvars.vcf", SEQVARS_VCF)?;
+    let path_ped = write_input(work_dir, "seqvars.ped", SEQVARS_PED)?;
+    let path_query_json = write_input(work_dir, "seqvars.query.json", SEQVARS_QUERY)?;
+    let path_ingested = work_dir.join("seqvars.ingested.vcf").display().to_string();
+    let path_rocksdb = work_dir.join("seqvars.inhouse").display().to_string();
+    let path_output = work_dir.join("seqvars.query.jsonl").display().to_string();
+
+    let ingest_args = crate::seqvars::ingest::Args::try_parse_from([
+        "ingest",
+        "--file-date",
+        "20240101",
+        "--case-uuid",
+        CASE_UUID,
+        "--genomebuild",
+        "grch37",
+        "--path-mehari-db",
+        args.path_mehari_db.as_str(),
+        "--path-ped",
+        path_ped.as_str(),
+        "--path-in",
+        path_vcf.as_str(),
+        "--path-out",
+        path_ingested.as_str(),
+    ])?;
+    run_stage(
+        "seqvars ingest",
+        crate::seqvars::ingest::run(args_common, &ingest_args),
+    )
+    .await?;
+
+    let aggregate_args = crate::seqvars::aggregate::Args::try_parse_from([
+        "aggregate",
+        "--genomebuild",
+        "grch37",
+        "--path-out-rocksdb",
+        path_rocksdb.as_str(),
+        "--path-input",
+        path_ingested.as_str(),
+    ])?;
+    run_stage(
+        "seqvars aggregate",
+        crate::seqvars::aggregate::run(args_common, &aggregate_args),
+    )
+    .await?;
+
+    let query_args = crate::seqvars::query::Args::try_parse_from([
+        "query",
+        "--genome-release",
+        "grch37",
+        "--case-uuid",
+        CASE_UUID,
+        "--path-db",
+        args.path_db.as_str(),
+        "--path-inhouse-db",
+        path_rocksdb.as_str(),
+        "--path-query-json",
+        path_query_json.as_str(),
+        "--path-input",
+        path_ingested.as_str(),
+        "--path-output",
+        path_output.as_str(),
+        "--rng-seed",
+        "42",
+    ])?;
+    run_stage(
+        "seqvars query",
+        crate::seqvars::query::run(args_common, &query_args),
+    )
+    .await?;
+
+    run_stage("seqvars check", async {
+        check_seqvars_result(&path_output)
+    })
+    .await
+}
+
+/// Run the structural variant stages in `work_dir`.
+async fn run_strucvars(
+    args_common: &crate::common::Args,
+    args: &Args,
+    work_dir: &Path,
+) -> Result<(), anyhow::Error> {
+    let path_vcf = write_input(work_dir, "strucvars.vcf", STRUCVARS_VCF)?;
+    let path_ped = write_input(work_dir, "strucvars.ped", STRUCVARS_PED)?;
+    let path_query_json = write_input(work_dir, "strucvars.query.json", STRUCVARS_QUERY)?;
+    let path_ingested = work_dir
+        .join("strucvars.ingested.vcf")
+        .display()
+        .to_string();
+    let path_inhouse_tsv = work_dir.join("strucvars.inhouse.tsv").display().to_string();
+    let path_inhouse_bin = work_dir.join("strucvars.inhouse.bin").display().to_string();
+    let path_output = work_dir.join("strucvars.query.tsv").display().to_string();
+
+    let ingest_args = crate::strucvars::ingest::Args::try_parse_from([
+        "ingest",
+        "--file-date",
+        "20240101",
+        "--case-uuid",
+        CASE_UUID,
+        "--genomebuild",
+        "grch37",
+        "--path-ped",
+        path_ped.as_str(),
+        "--path-in",
+        path_vcf.as_str(),
+        "--path-out",
+        path_ingested.as_str(),
+        "--rng-seed",
+        "42",
+    ])?;
+    run_stage(
+        "strucvars ingest",
+        crate::strucvars::ingest::run(args_common, &ingest_args),
+    )
+    .await?;
+
+    let aggregate_args = crate::strucvars::aggregate::cli::Args::try_parse_from([
+        "aggregate",
+        "--genome-release",
+        "grch37",
+        "--path-output",
+        path_inhouse_tsv.as_str(),
+        path_ingested.as_str(),
+    ])?;
+    run_stage(
+        "strucvars aggregate",
+        crate::strucvars::aggregate::cli::run(args_common, &aggregate_args),
+    )
+    .await?;
+
+    let txt_to_bin_args = crate::strucvars::txt_to_bin::cli::Args::try_parse_from([
+        "txt-to-bin",
+        "--input-type",
+        "strucvar-inhouse",
+        "--path-input",
+        path_inhouse_tsv.as_str(),
+        "--path-output",
+        path_inhouse_bin.as_str(),
+    ])?;
+    run_stage("strucvars txt-to-bin", async {
+        crate::strucvars::txt_to_bin::cli::run(args_common, &txt_to_bin_args)?;
+        let bg_db: crate::pbs::varfish::v1::strucvars::bgdb::BackgroundDatabase =
+            crate::strucvars::query::intervals::read_pb(Path::new(&path_inhouse_bin))?;
+        if bg_db.records.is_empty() {
+            anyhow::bail!("in-house database has no records");
+        }
+        crate::strucvars::query::bgdbs::load_bg_db_records(Path::new(&path_inhouse_bin))?;
+        Ok(())
+    })
+    .await?;
+
+    let query_args = crate::strucvars::query::Args::try_parse_from([
+        "query",
+        "--genome-release",
+        "grch37",
+        "--path-db",
+        args.path_strucvars_db.as_deref().unwrap_or(&args.path_db),
+        "--path-query-json",
+        path_query_json.as_str(),
+        "--path-input",
+        path_ingested.as_str(),
+        "--path-output",
+        path_output.as_str(),
+        "--rng-seed",
+        "42",
+    ])?;
+    run_stage(
+        "strucvars query",
+        crate::strucvars::query::run(args_common, &query_args),
+    )
+    .await?;
+
+    run_stage("strucvars check", async {
+        check_strucvars_result(&path_output)
+    })
+    .await
+}
+
+/// Main entry point for `selftest` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let work_dir = if let Some(path_work_dir) = args.path_work_dir.as_ref() {
+        std::fs::create_dir_all(path_work_dir)
+            .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_work_dir, e))
+            .classify(ErrorClass::Config)?;
+        PathBuf::from(path_work_dir)
+    } else {
+        tmp_dir.path().to_path_buf()
+    };
+
+    run_seqvars(args_common, args, &work_dir).await?;
+    if args.skip_strucvars {
+        tracing::info!("Skipping structural variant stages");
+    } else {
+        run_strucvars(args_common, args, &work_dir).await?;
+    }
+
+    tracing::info!("All self test stages passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn run() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let args_common = Default::default();
+        let args = super::Args {
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_db: "tests/seqvars/query/db".into(),
+            path_strucvars_db: Some("tests/strucvars/query/db".into()),
+            path_work_dir: Some(tmpdir.to_string_lossy().to_string()),
+            skip_strucvars: false,
+        };
+        super::run(&args_common, &args).await?;
+
+        for file_name in [
+            "seqvars.ingested.vcf",
+            "seqvars.query.jsonl",
+            "strucvars.ingested.vcf",
+            "strucvars.inhouse.bin",
+            "strucvars.query.tsv",
+        ] {
+            assert!(tmpdir.join(file_name).exists(), "{} missing", file_name);
+        }
+
+        Ok(())
+    }
+}
@@ -15,28 +15,47 @@ pub struct Counts {
     pub count_homalt: u32,
     /// Number of hemi. alt. carriers.
     pub count_hemialt: u32,
+    /// Number of heteroplasmic carriers (chrMT only).
+    pub count_heteroplasmic: u32,
+    /// Number of homoplasmic carriers (chrMT only).
+    pub count_homoplasmic: u32,
 }
 
 impl Counts {
     /// Convert to a byte vector.
     pub fn to_vec(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(20);
+        let mut buf = Vec::with_capacity(28);
         buf.extend_from_slice(&self.count_homref.to_le_bytes());
         buf.extend_from_slice(&self.count_hemiref.to_le_bytes());
         buf.extend_from_slice(&self.count_het.to_le_bytes());
         buf.extend_from_slice(&self.count_homalt.to_le_bytes());
         buf.extend_from_slice(&self.count_hemialt.to_le_bytes());
+        buf.extend_from_slice(&self.count_heteroplasmic.to_le_bytes());
+        buf.extend_from_slice(&self.count_homoplasmic.to_le_bytes());
         buf
     }
 
     /// Convert from a byte vector.
+    ///
+    /// Buffers written before the chrMT counts were added only have 20 bytes, the
+    /// chrMT counts are zero then.
     pub fn from_vec(buf: &[u8]) -> Self {
+        let (count_heteroplasmic, count_homoplasmic) = if buf.len() >= 28 {
+            (
+                LittleEndian::read_u32(&buf[20..24]),
+                LittleEndian::read_u32(&buf[24..28]),
+            )
+        } else {
+            (0, 0)
+        };
         Self {
             count_homref: LittleEndian::read_u32(&buf[0..4]),
             count_hemiref: LittleEndian::read_u32(&buf[4..8]),
             count_het: LittleEndian::read_u32(&buf[8..12]),
             count_homalt: LittleEndian::read_u32(&buf[12..16]),
             count_hemialt: LittleEndian::read_u32(&buf[16..20]),
+            count_heteroplasmic,
+            count_homoplasmic,
         }
     }
 
@@ -47,6 +66,41 @@ impl Counts {
         self.count_het += other.count_het;
         self.count_homalt += other.count_homalt;
         self.count_hemialt += other.count_hemialt;
+        self.count_heteroplasmic += other.count_heteroplasmic;
+        self.count_homoplasmic += other.count_homoplasmic;
+    }
+
+    /// Subtract other from self, e.g., the counts of a case to remove.
+    pub fn subtract(&mut self, other: Self) {
+        self.count_homref = self.count_homref.saturating_sub(other.count_homref);
+        self.count_hemiref = self.count_hemiref.saturating_sub(other.count_hemiref);
+        self.count_het = self.count_het.saturating_sub(other.count_het);
+        self.count_homalt = self.count_homalt.saturating_sub(other.count_homalt);
+        self.count_hemialt = self.count_hemialt.saturating_sub(other.count_hemialt);
+        self.count_heteroplasmic = self
+            .count_heteroplasmic
+            .saturating_sub(other.count_heteroplasmic);
+        self.count_homoplasmic = self
+            .count_homoplasmic
+            .saturating_sub(other.count_homoplasmic);
+    }
+
+    /// Remove the contribution of a carrier with the given genotype.
+    ///
+    /// This is the inverse of the counting in `seqvars aggregate` for carriers with
+    /// non-reference genotype.  Reference genotypes are not stored in the carrier list
+    /// and thus cannot be removed.
+    pub fn remove_carrier(&mut self, genotype: Genotype) {
+        let (count, value) = match genotype {
+            Genotype::HomRef => (&mut self.count_homref, 1),
+            Genotype::HemiRef => (&mut self.count_hemiref, 1),
+            Genotype::Het => (&mut self.count_het, 1),
+            Genotype::HomAlt => (&mut self.count_homalt, 2),
+            Genotype::HemiAlt => (&mut self.count_hemialt, 1),
+            Genotype::Heteroplasmic => (&mut self.count_heteroplasmic, 1),
+            Genotype::Homoplasmic => (&mut self.count_homoplasmic, 1),
+        };
+        *count = count.saturating_sub(value);
     }
 }
 
@@ -59,6 +113,8 @@ pub enum Genotype {
     Het,
     HomAlt,
     HemiAlt,
+    Heteroplasmic,
+    Homoplasmic,
 }
 
 impl Genotype {
@@ -70,6 +126,8 @@ impl Genotype {
             Genotype::Het => 2,
             Genotype::HomAlt => 3,
             Genotype::HemiAlt => 4,
+            Genotype::Heteroplasmic => 5,
+            Genotype::Homoplasmic => 6,
         }
     }
 }
@@ -91,25 +149,132 @@ impl TryFrom<u8> for Genotype {
             2 => Ok(Genotype::Het),
             3 => Ok(Genotype::HomAlt),
             4 => Ok(Genotype::HemiAlt),
+            5 => Ok(Genotype::Heteroplasmic),
+            6 => Ok(Genotype::Homoplasmic),
             _ => Err(GenotypeTryFromByteError::InvalidByte(byte)),
         }
     }
 }
 
-/// Store one carrier by UUID and index in the pedigree.
+/// Affection status of a carrier.
+#[derive(Debug, Default, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub enum Affection {
+    #[default]
+    Unknown,
+    Affected,
+    Unaffected,
+}
+
+/// Sex of a carrier.
+#[derive(Debug, Default, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub enum Sex {
+    #[default]
+    Unknown,
+    Male,
+    Female,
+}
+
+/// Coarse strata of a carrier, unknown if not recorded.
+#[derive(Debug, Default, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Strata {
+    /// Affection status of the carrier.
+    pub affection: Affection,
+    /// Sex of the carrier.
+    pub sex: Sex,
+}
+
+impl Strata {
+    /// Create from the pedigree entry of the carrier.
+    pub fn from_individual(individual: &mehari::ped::Individual) -> Self {
+        Self {
+            affection: match individual.disease {
+                mehari::ped::Disease::Affected => Affection::Affected,
+                mehari::ped::Disease::Unaffected => Affection::Unaffected,
+                mehari::ped::Disease::Unknown => Affection::Unknown,
+            },
+            sex: match individual.sex {
+                mehari::ped::Sex::Male => Sex::Male,
+                mehari::ped::Sex::Female => Sex::Female,
+                mehari::ped::Sex::Unknown => Sex::Unknown,
+            },
+        }
+    }
+
+    /// Convert to a byte, the affection in the lower and the sex in the upper nibble.
+    pub fn to_byte(self) -> u8 {
+        let affection = match self.affection {
+            Affection::Unknown => 0,
+            Affection::Affected => 1,
+            Affection::Unaffected => 2,
+        };
+        let sex = match self.sex {
+            Sex::Unknown => 0,
+            Sex::Male => 1,
+            Sex::Female => 2,
+        };
+        affection | (sex << 4)
+    }
+}
+
+/// Error for `TryFrom<u8>` for `Strata`.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StrataTryFromByteError {
+    #[error("invalid strata byte: {0}")]
+    InvalidByte(u8),
+}
+
+impl TryFrom<u8> for Strata {
+    type Error = StrataTryFromByteError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        let affection = match byte & 0x0f {
+            0 => Affection::Unknown,
+            1 => Affection::Affected,
+            2 => Affection::Unaffected,
+            _ => return Err(StrataTryFromByteError::InvalidByte(byte)),
+        };
+        let sex = match byte >> 4 {
+            0 => Sex::Unknown,
+            1 => Sex::Male,
+            2 => Sex::Female,
+            _ => return Err(StrataTryFromByteError::InvalidByte(byte)),
+        };
+        Ok(Self { affection, sex })
+    }
+}
+
+/// Store one carrier by UUID, family, and index in the family.
 #[derive(Debug, Default, Clone, PartialOrd, Ord, PartialEq, Eq)]
 pub struct Carrier {
     /// Case UUID.
     pub uuid: uuid::Uuid,
-    /// Index of the carrier in the pedigree.
+    /// Index of the carrier's family in the case, `0` for single-family cases.
+    pub family: u8,
+    /// Index of the carrier in its family.
     pub index: u8,
     /// Genotype of the carrier.
     pub genotype: Genotype,
+    /// Strata of the carrier, if recorded.
+    pub strata: Strata,
+}
+
+/// Error for `TryFrom<&[u8]>` for `CarrierList`.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CarrierListTryFromBytesError {
+    #[error("invalid length {len} of carrier list with {count} carriers")]
+    InvalidLength { len: usize, count: usize },
+    #[error(transparent)]
+    InvalidGenotype(#[from] GenotypeTryFromByteError),
+    #[error(transparent)]
+    InvalidStrata(#[from] StrataTryFromByteError),
 }
 
 /// Carrier UUIDs.
 ///
-/// We store the UUIDs serialized as byte vectors and the index of the carrier in the pedigree.
+/// We store the UUIDs serialized as byte vectors, followed by the family index, the
+/// index of the carrier in the family, the genotype, and the strata.  Buffers written
+/// before multi-family cases were supported lack the family index, it is `0` then.
+/// Buffers written before the strata were supported lack them, they are unknown then.
 #[derive(Debug, Default, Clone)]
 pub struct CarrierList {
     /// List of carrier UUIDs.
@@ -117,14 +282,23 @@ pub struct CarrierList {
 }
 
 impl CarrierList {
+    /// Length of one serialized carrier.
+    const CARRIER_LEN: usize = 20;
+    /// Length of one serialized carrier without strata.
+    const NO_STRATA_CARRIER_LEN: usize = 19;
+    /// Length of one serialized carrier without family index and strata.
+    const LEGACY_CARRIER_LEN: usize = 18;
+
     /// Convert to a byte vector.
     pub fn to_vec(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(2 + 18 * self.carriers.len());
+        let mut buf = Vec::with_capacity(2 + Self::CARRIER_LEN * self.carriers.len());
         buf.extend_from_slice(&(self.carriers.len() as u16).to_le_bytes());
         for carrier in &self.carriers {
             buf.extend_from_slice(&carrier.uuid.as_u128().to_le_bytes());
+            buf.push(carrier.family);
             buf.push(carrier.index);
             buf.push(carrier.genotype.to_byte());
+            buf.push(carrier.strata.to_byte());
         }
         buf
     }
@@ -140,23 +314,53 @@ impl CarrierList {
         self.carriers.sort();
         self.carriers.dedup();
     }
+
+    /// Remove and return all carriers from the cases with the given UUIDs.
+    pub fn remove_cases(&mut self, uuids: &std::collections::HashSet<uuid::Uuid>) -> Vec<Carrier> {
+        let (removed, kept) = std::mem::take(&mut self.carriers)
+            .into_iter()
+            .partition(|carrier| uuids.contains(&carrier.uuid));
+        self.carriers = kept;
+        removed
+    }
 }
 
 impl TryFrom<&[u8]> for CarrierList {
-    type Error = GenotypeTryFromByteError;
+    type Error = CarrierListTryFromBytesError;
 
     fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
-        let mut carriers = Vec::with_capacity((buf.len() - 2) / 18);
         let num_carriers = LittleEndian::read_u16(&buf[0..2]) as usize;
-        for i in 0..num_carriers {
-            let uuid =
-                uuid::Uuid::from_u128(LittleEndian::read_u128(&buf[2 + 18 * i..2 + 18 * i + 16]));
-            let index = buf[2 + 18 * i + 16];
-            let genotype = Genotype::try_from(buf[2 + 18 * i + 17])?;
+        let carrier_len = if buf.len() == 2 + Self::CARRIER_LEN * num_carriers {
+            Self::CARRIER_LEN
+        } else if buf.len() == 2 + Self::NO_STRATA_CARRIER_LEN * num_carriers {
+            Self::NO_STRATA_CARRIER_LEN
+        } else if buf.len() == 2 + Self::LEGACY_CARRIER_LEN * num_carriers {
+            Self::LEGACY_CARRIER_LEN
+        } else {
+            return Err(CarrierListTryFromBytesError::InvalidLength {
+                len: buf.len(),
+                count: num_carriers,
+            });
+        };
+
+        let mut carriers = Vec::with_capacity(num_carriers);
+        for chunk in buf[2..].chunks_exact(carrier_len) {
+            let uuid = uuid::Uuid::from_u128(LittleEndian::read_u128(&chunk[0..16]));
+            let (family, rest) = if carrier_len == Self::LEGACY_CARRIER_LEN {
+                (0, &chunk[16..])
+            } else {
+                (chunk[16], &chunk[17..])
+            };
+            let strata = match rest.get(2) {
+                Some(byte) => Strata::try_from(*byte)?,
+                None => Strata::default(),
+            };
             carriers.push(Carrier {
                 uuid,
-                index,
-                genotype,
+                family,
+                index: rest[0],
+                genotype: Genotype::try_from(rest[1])?,
+                strata,
             });
         }
         Ok(Self { carriers })
@@ -175,40 +379,208 @@ mod test {
             count_het: 3,
             count_homalt: 4,
             count_hemialt: 5,
+            count_heteroplasmic: 6,
+            count_homoplasmic: 7,
         };
 
         let buf = counts.to_vec();
         insta::assert_debug_snapshot!(&buf);
-        assert_eq!(buf.len(), 20);
+        assert_eq!(buf.len(), 28);
 
         let counts2 = Counts::from_vec(&buf);
         insta::assert_debug_snapshot!(&counts2);
     }
 
+    #[test]
+    fn test_counts_from_vec_legacy() {
+        let buf = [1u8, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0];
+        let counts = Counts::from_vec(&buf);
+        assert_eq!(counts.count_hemialt, 5);
+        assert_eq!(counts.count_heteroplasmic, 0);
+        assert_eq!(counts.count_homoplasmic, 0);
+    }
+
     #[test]
     fn test_carrier_list() -> Result<(), anyhow::Error> {
         let carrier_list = CarrierList {
             carriers: vec![
                 Carrier {
                     uuid: uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+                    family: 0,
                     index: 0,
                     genotype: Genotype::HomRef,
+                    strata: Strata::default(),
                 },
                 Carrier {
                     uuid: uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+                    family: 1,
                     index: 1,
                     genotype: Genotype::HemiAlt,
+                    strata: Strata {
+                        affection: Affection::Affected,
+                        sex: Sex::Male,
+                    },
                 },
             ],
         };
 
         let buf = carrier_list.to_vec();
         insta::assert_debug_snapshot!(&buf);
-        assert_eq!(buf.len(), 38);
+        assert_eq!(buf.len(), 42);
 
         let carrier_list2 = CarrierList::try_from(buf.as_slice())?;
         insta::assert_debug_snapshot!(&carrier_list2);
 
         Ok(())
     }
+
+    #[test]
+    fn test_carrier_list_legacy() -> Result<(), anyhow::Error> {
+        let mut buf = vec![1u8, 0];
+        buf.extend_from_slice(&1u128.to_le_bytes());
+        buf.extend_from_slice(&[2, 4]);
+
+        let carrier_list = CarrierList::try_from(buf.as_slice())?;
+        assert_eq!(
+            carrier_list.carriers,
+            vec![Carrier {
+                uuid: uuid::Uuid::from_u128(1),
+                family: 0,
+                index: 2,
+                genotype: Genotype::HemiAlt,
+                strata: Strata::default(),
+            }]
+        );
+
+        assert!(CarrierList::try_from(&buf[..buf.len() - 1]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_carrier_list_no_strata() -> Result<(), anyhow::Error> {
+        let mut buf = vec![1u8, 0];
+        buf.extend_from_slice(&1u128.to_le_bytes());
+        buf.extend_from_slice(&[1, 2, 4]);
+
+        let carrier_list = CarrierList::try_from(buf.as_slice())?;
+        assert_eq!(
+            carrier_list.carriers,
+            vec![Carrier {
+                uuid: uuid::Uuid::from_u128(1),
+                family: 1,
+                index: 2,
+                genotype: Genotype::HemiAlt,
+                strata: Strata::default(),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(Affection::Unknown, Sex::Unknown)]
+    #[case(Affection::Affected, Sex::Female)]
+    #[case(Affection::Unaffected, Sex::Male)]
+    fn test_strata_byte(
+        #[case] affection: Affection,
+        #[case] sex: Sex,
+    ) -> Result<(), anyhow::Error> {
+        let strata = Strata { affection, sex };
+
+        assert_eq!(Strata::try_from(strata.to_byte())?, strata);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strata_invalid_byte() {
+        assert!(Strata::try_from(0x03).is_err());
+        assert!(Strata::try_from(0x30).is_err());
+    }
+
+    #[test]
+    fn test_remove_cases() {
+        let uuid_keep = uuid::Uuid::from_u128(1);
+        let uuid_remove = uuid::Uuid::from_u128(2);
+        let mut counts = Counts {
+            count_homref: 1,
+            count_het: 1,
+            count_homalt: 2,
+            ..Default::default()
+        };
+        let mut carrier_list = CarrierList {
+            carriers: vec![
+                Carrier {
+                    uuid: uuid_keep,
+                    family: 0,
+                    index: 0,
+                    genotype: Genotype::Het,
+                    strata: Strata::default(),
+                },
+                Carrier {
+                    uuid: uuid_remove,
+                    family: 0,
+                    index: 0,
+                    genotype: Genotype::HomAlt,
+                    strata: Strata::default(),
+                },
+            ],
+        };
+
+        let removed = carrier_list.remove_cases(&[uuid_remove].into_iter().collect());
+        for carrier in &removed {
+            counts.remove_carrier(carrier.genotype);
+        }
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            carrier_list.carriers,
+            vec![Carrier {
+                uuid: uuid_keep,
+                family: 0,
+                index: 0,
+                genotype: Genotype::Het,
+                strata: Strata::default(),
+            }]
+        );
+        assert_eq!(
+            (counts.count_homref, counts.count_het, counts.count_homalt),
+            (1, 1, 0)
+        );
+
+        // Counts do not underflow.
+        counts.remove_carrier(Genotype::HomAlt);
+        assert_eq!(counts.count_homalt, 0);
+    }
+
+    #[test]
+    fn test_counts_subtract() {
+        let mut counts = Counts {
+            count_homref: 3,
+            count_het: 2,
+            count_homalt: 2,
+            count_homoplasmic: 1,
+            ..Default::default()
+        };
+
+        counts.subtract(Counts {
+            count_homref: 1,
+            count_het: 1,
+            count_homalt: 4,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            counts.to_vec(),
+            Counts {
+                count_homref: 2,
+                count_het: 1,
+                count_homalt: 0,
+                count_homoplasmic: 1,
+                ..Default::default()
+            }
+            .to_vec()
+        );
+    }
 }
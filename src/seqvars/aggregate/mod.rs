@@ -1,14 +1,20 @@
 //! Implementation of `seqvars aggregate` subcommand.
 
 pub mod ds;
+pub mod stats;
 
 use futures::TryStreamExt as _;
 use mehari::common::noodles::NoodlesVariantReader as _;
 use noodles::vcf;
 use rayon::prelude::*;
-use std::{str::FromStr as _, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr as _,
+    sync::Arc,
+};
 
-use crate::common::{self, genotype_to_string, Chrom, Genotype};
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::common::{self, genotype_to_string, Chrom, Genotype, GonosomalRegion};
 
 /// Command line arguments for `seqvars aggregate` subcommand.
 #[derive(Debug, clap::Parser)]
@@ -37,40 +43,208 @@ pub struct Args {
     /// Optional path to RocksDB WAL directory.
     #[arg(long)]
     pub path_wal_dir: Option<String>,
+
+    /// Minimal alternate allele fraction on chrMT to count a heteroplasmic carrier.
+    #[arg(long, default_value_t = 0.1)]
+    pub mt_min_heteroplasmy: f32,
+    /// Minimal alternate allele fraction on chrMT to count a homoplasmic carrier.
+    #[arg(long, default_value_t = 0.95)]
+    pub mt_min_homoplasmy: f32,
+
+    /// Treat the X-transposed region (XTR) like the PARs, i.e., count male calls there
+    /// as diploid rather than hemizygous.
+    #[arg(long, default_value_t = false)]
+    pub xtr_as_par: bool,
+
+    /// Record the affection status and sex of each carrier from the pedigree, so that
+    /// `seqvars query` can report stratified in-house counts.
+    #[arg(long, default_value_t = false)]
+    pub with_strata: bool,
+
+    /// How to handle input cases whose UUID is already in the database.
+    #[arg(long, value_enum, default_value_t = OnDuplicateCase::Fail)]
+    pub on_duplicate_case: OnDuplicateCase,
+
+    /// Remove the cases of the input files from the database instead of importing them.
+    ///
+    /// The input files must be the ones the cases were imported from, as their counts,
+    /// including hom. ref. calls, are subtracted.
+    #[arg(long, default_value_t = false, conflicts_with = "on_duplicate_case")]
+    pub remove: bool,
+
+    /// Import in bulk: aggregate the input files in memory, sharded by chromosome, and
+    /// write each shard with write batches instead of one transaction per variant.
+    ///
+    /// This avoids the write contention of many parallel imports at the cost of
+    /// keeping the aggregated variants of all input files in memory.
+    #[arg(long, default_value_t = false, conflicts_with = "remove")]
+    pub bulk: bool,
+}
+
+/// Handling of input cases that have already been imported into the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnDuplicateCase {
+    /// Fail before importing anything.
+    #[default]
+    Fail,
+    /// Skip the input file of the case.
+    Skip,
+    /// Remove the prior carriers of the case from the database before importing.
+    ///
+    /// Hom. ref. calls are not stored as carriers, so their prior counts remain.
+    Replace,
+}
+
+/// Alternate allele fraction thresholds for classifying chrMT calls.
+#[derive(Debug, Clone, Copy)]
+pub struct MtThresholds {
+    /// Minimal alternate allele fraction for heteroplasmic carriers.
+    pub min_heteroplasmy: f32,
+    /// Minimal alternate allele fraction for homoplasmic carriers.
+    pub min_homoplasmy: f32,
 }
 
-/// Returns whether the given coordinate is in PAR for `chrom`, `pos` (1-based) and `genombuild`.
-fn is_par(chrom: Chrom, pos: usize, genomebuild: crate::common::GenomeRelease) -> bool {
-    match (chrom, genomebuild) {
-        (Chrom::X, crate::common::GenomeRelease::Grch37) => {
-            (60001..=2699520).contains(&pos) || (154931044..=155260560).contains(&pos)
+impl Default for MtThresholds {
+    fn default() -> Self {
+        Self {
+            min_heteroplasmy: 0.1,
+            min_homoplasmy: 0.95,
         }
-        (Chrom::X, crate::common::GenomeRelease::Grch38) => {
-            (10001..=2781479).contains(&pos) || (155701383..=156030895).contains(&pos)
+    }
+}
+
+impl From<&Args> for MtThresholds {
+    fn from(args: &Args) -> Self {
+        Self {
+            min_heteroplasmy: args.mt_min_heteroplasmy,
+            min_homoplasmy: args.mt_min_homoplasmy,
         }
-        (Chrom::Y, crate::common::GenomeRelease::Grch37) => {
-            (10001..=2649520).contains(&pos) || (59034050..=59363566).contains(&pos)
+    }
+}
+
+/// Return the alternate allele fraction from `FORMAT/AD` and `FORMAT/DP`, if available.
+///
+/// Records are expected to be split by `seqvars ingest`, so the second `AD` value
+/// is the depth of the alternate allele.  If `DP` is missing, the sum of `AD` is used.
+fn alt_allele_fraction(sample: &vcf::variant::record_buf::samples::Sample<'_>) -> Option<f32> {
+    use noodles::vcf::variant::record::samples::keys::key;
+    use vcf::variant::record_buf::samples::sample::value::{Array, Value};
+
+    let ad = if let Some(Some(Value::Array(Array::Integer(ad)))) = sample.get(key::READ_DEPTHS) {
+        ad.clone()
+    } else {
+        return None;
+    };
+    let alt_ad = ad.get(1).copied().flatten()?;
+    let dp = if let Some(Some(Value::Integer(dp))) = sample.get(key::READ_DEPTH) {
+        *dp
+    } else {
+        ad.iter().flatten().sum()
+    };
+    if dp > 0 {
+        Some(alt_ad as f32 / dp as f32)
+    } else {
+        None
+    }
+}
+
+/// Classify the chrMT call of `sample` with the given (parsed) `genotype`.
+///
+/// Uses the alternate allele fraction if available and falls back to the genotype.
+fn mt_carrier_genotype(
+    sample: &vcf::variant::record_buf::samples::Sample<'_>,
+    genotype: Genotype,
+    mt_thresholds: MtThresholds,
+) -> ds::Genotype {
+    if let Some(alt_af) = alt_allele_fraction(sample) {
+        if alt_af >= mt_thresholds.min_homoplasmy {
+            ds::Genotype::Homoplasmic
+        } else if alt_af >= mt_thresholds.min_heteroplasmy {
+            ds::Genotype::Heteroplasmic
+        } else {
+            ds::Genotype::HomRef
         }
-        (Chrom::Y, crate::common::GenomeRelease::Grch38) => {
-            (10001..=2781479).contains(&pos) || (56887903..=57217415).contains(&pos)
+    } else {
+        match genotype {
+            Genotype::HomRef | Genotype::WithNoCall => ds::Genotype::HomRef,
+            Genotype::Het => ds::Genotype::Heteroplasmic,
+            Genotype::HomAlt => ds::Genotype::Homoplasmic,
         }
-        _ => false,
     }
 }
 
+/// Returns whether the given coordinate is to be counted as diploid in males.
+///
+/// This is the case in the PARs and, if `xtr_as_par` is set, in the XTR.
+fn is_diploid_in_males(
+    chrom: Chrom,
+    pos: usize,
+    genomebuild: crate::common::GenomeRelease,
+    xtr_as_par: bool,
+) -> bool {
+    match GonosomalRegion::from_pos(chrom, pos, genomebuild) {
+        Some(GonosomalRegion::Xtr) => xtr_as_par,
+        Some(region) => region.is_par(),
+        None => false,
+    }
+}
+
+/// Return the family index and the index in the family of each individual in `pedigree`.
+///
+/// Families are numbered in the order of their first individual in the pedigree.
+fn carrier_indices(
+    pedigree: &mehari::ped::PedigreeByName,
+) -> Result<HashMap<String, (u8, u8)>, anyhow::Error> {
+    let mut families: Vec<(&str, u8)> = Vec::new();
+    let mut result = HashMap::new();
+    for (name, individual) in pedigree.individuals.iter() {
+        let family_no = match families
+            .iter()
+            .position(|(family, _)| *family == individual.family)
+        {
+            Some(family_no) => family_no,
+            None => {
+                families.push((individual.family.as_str(), 0));
+                families.len() - 1
+            }
+        };
+        let index = &mut families[family_no].1;
+        result.insert(
+            name.clone(),
+            (
+                u8::try_from(family_no)
+                    .map_err(|_| anyhow::anyhow!("too many families in pedigree"))?,
+                *index,
+            ),
+        );
+        *index = index.checked_add(1).ok_or_else(|| {
+            anyhow::anyhow!("too many individuals in family {}", individual.family)
+        })?;
+    }
+    Ok(result)
+}
+
 /// Extract counts and carrier data from a single VCF record.
+///
+/// Carriers are identified by the family and index in the family from `carrier_indices`.
+/// If `with_strata` is set, the strata of the carriers are taken from `pedigree`.
+#[allow(clippy::too_many_arguments)]
 fn handle_record(
     input_record: &vcf::variant::RecordBuf,
     input_header: &vcf::Header,
     pedigree: &mehari::ped::PedigreeByName,
+    carrier_indices: &HashMap<String, (u8, u8)>,
     case_uuid: &uuid::Uuid,
     genomebuild: crate::common::GenomeRelease,
+    mt_thresholds: MtThresholds,
+    xtr_as_par: bool,
+    with_strata: bool,
 ) -> Result<(ds::Counts, ds::CarrierList), anyhow::Error> {
-    let chrom: Chrom = annonars::common::cli::canonicalize(
+    let canonical_chrom = annonars::common::cli::canonicalize(
         input_record.reference_sequence_name().to_string().as_str(),
-    )
-    .as_str()
-    .parse()?;
+    );
+    let chrom: Chrom = canonical_chrom.as_str().parse()?;
+    let is_mt = canonical_chrom == "MT";
 
     let mut res_counts = ds::Counts::default();
     let mut res_carriers = ds::CarrierList::default();
@@ -107,79 +281,100 @@ fn handle_record(
             NoPar,
         }
         use _IsPar::*;
-        let is_par = if is_par(chrom, start, genomebuild) {
+        let is_par = if is_diploid_in_males(chrom, start, genomebuild, xtr_as_par) {
             IsPar
         } else {
             NoPar
         };
 
-        let carrier_genotype = match (chrom, is_par, individual.sex, genotype) {
-            (_, _, _, Genotype::WithNoCall) => continue,
-            // On the autosomes, male/female are handled the same.
-            (Chrom::Auto, _, _, Genotype::HomRef) => {
-                res_counts.count_homref += 1;
-                ds::Genotype::HomRef
-            }
-            (Chrom::Auto, _, _, Genotype::Het) => {
-                res_counts.count_het += 1;
-                ds::Genotype::Het
-            }
-            (Chrom::Auto, _, _, Genotype::HomAlt) => {
-                res_counts.count_homalt += 2;
-                ds::Genotype::HomAlt
+        // On chrMT, we count heteroplasmic and homoplasmic carriers based on the
+        // alternate allele fraction rather than the diploid genotype.
+        let carrier_genotype = if is_mt {
+            if genotype == Genotype::WithNoCall {
+                continue;
             }
-            // On the gonomosomes, we handle call male variant calls as hemizygous outside PAR.
-            (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::HomRef)
-            | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::HomRef) => {
-                res_counts.count_hemiref += 1;
-                ds::Genotype::HemiRef
+            let carrier_genotype = mt_carrier_genotype(sample, genotype, mt_thresholds);
+            match carrier_genotype {
+                ds::Genotype::Heteroplasmic => res_counts.count_heteroplasmic += 1,
+                ds::Genotype::Homoplasmic => res_counts.count_homoplasmic += 1,
+                _ => res_counts.count_homref += 1,
             }
-            (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::Het)
-            | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::Het)
-            | (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::HomAlt)
-            | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::HomAlt) => {
-                res_counts.count_hemialt += 1;
-                ds::Genotype::HemiAlt
-            }
-            // For female samples, we handle chrX as biallelic, same as male inside PAR on
-            // chrX/chrY.  Note that read mapping pipelines N-mask the PAR on chrY, so we
-            // don't count twice.
-            (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::HomRef)
-            | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::HomRef)
-            | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::HomRef) => {
-                res_counts.count_homref += 1;
-                ds::Genotype::HomRef
-            }
-            (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::Het)
-            | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::Het)
-            | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::Het) => {
-                res_counts.count_het += 1;
-                ds::Genotype::Het
-            }
-            (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::HomAlt)
-            | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::HomAlt)
-            | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::HomAlt) => {
-                res_counts.count_homalt += 2;
-                ds::Genotype::HomAlt
+            carrier_genotype
+        } else {
+            match (chrom, is_par, individual.sex, genotype) {
+                (_, _, _, Genotype::WithNoCall) => continue,
+                // On the autosomes, male/female are handled the same.
+                (Chrom::Auto, _, _, Genotype::HomRef) => {
+                    res_counts.count_homref += 1;
+                    ds::Genotype::HomRef
+                }
+                (Chrom::Auto, _, _, Genotype::Het) => {
+                    res_counts.count_het += 1;
+                    ds::Genotype::Het
+                }
+                (Chrom::Auto, _, _, Genotype::HomAlt) => {
+                    res_counts.count_homalt += 2;
+                    ds::Genotype::HomAlt
+                }
+                // On the gonomosomes, we handle call male variant calls as hemizygous outside PAR.
+                (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::HomRef)
+                | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::HomRef) => {
+                    res_counts.count_hemiref += 1;
+                    ds::Genotype::HemiRef
+                }
+                (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::Het)
+                | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::Het)
+                | (Chrom::X, NoPar, mehari::ped::Sex::Male, Genotype::HomAlt)
+                | (Chrom::Y, NoPar, mehari::ped::Sex::Male, Genotype::HomAlt) => {
+                    res_counts.count_hemialt += 1;
+                    ds::Genotype::HemiAlt
+                }
+                // For female samples, we handle chrX as biallelic, same as male inside PAR on
+                // chrX/chrY.  Note that read mapping pipelines N-mask the PAR on chrY, so we
+                // don't count twice.
+                (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::HomRef)
+                | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::HomRef)
+                | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::HomRef) => {
+                    res_counts.count_homref += 1;
+                    ds::Genotype::HomRef
+                }
+                (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::Het)
+                | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::Het)
+                | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::Het) => {
+                    res_counts.count_het += 1;
+                    ds::Genotype::Het
+                }
+                (Chrom::X, IsPar, mehari::ped::Sex::Male, Genotype::HomAlt)
+                | (Chrom::Y, IsPar, mehari::ped::Sex::Male, Genotype::HomAlt)
+                | (Chrom::X, _, mehari::ped::Sex::Female, Genotype::HomAlt) => {
+                    res_counts.count_homalt += 2;
+                    ds::Genotype::HomAlt
+                }
+                // We ignore calls to chrY for female samples.
+                (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::HomRef)
+                | (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::Het)
+                | (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::HomAlt) => ds::Genotype::HomRef,
+                // Do not count samples with unknown sex on gonomosomes.
+                (Chrom::X, _, mehari::ped::Sex::Unknown, _)
+                | (Chrom::Y, _, mehari::ped::Sex::Unknown, _) => ds::Genotype::HomRef,
             }
-            // We ignore calls to chrY for female samples.
-            (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::HomRef)
-            | (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::Het)
-            | (Chrom::Y, _, mehari::ped::Sex::Female, Genotype::HomAlt) => ds::Genotype::HomRef,
-            // Do not count samples with unknown sex on gonomosomes.
-            (Chrom::X, _, mehari::ped::Sex::Unknown, _)
-            | (Chrom::Y, _, mehari::ped::Sex::Unknown, _) => ds::Genotype::HomRef,
         };
 
         if carrier_genotype != ds::Genotype::HomRef {
+            let (family, index) = carrier_indices
+                .get(name.as_str())
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("individual {} not found in pedigree", &name))?;
             res_carriers.carriers.push(ds::Carrier {
                 uuid: *case_uuid,
-                index: pedigree
-                    .individuals
-                    .get_index_of(name.as_str())
-                    .ok_or_else(|| anyhow::anyhow!("individual {} not found in pedigree", &name))?
-                    as u8,
+                family,
+                index,
                 genotype: carrier_genotype,
+                strata: if with_strata {
+                    ds::Strata::from_individual(individual)
+                } else {
+                    Default::default()
+                },
             });
         }
     }
@@ -187,15 +382,21 @@ fn handle_record(
     Ok((res_counts, res_carriers))
 }
 
-/// Import one VCF file into the database.
+/// Import one VCF file into the database, or remove it from the database if `remove`
+/// is set.
 ///
 /// This function is `async` because we potentially need to read from S3.
+#[allow(clippy::too_many_arguments)]
 async fn import_vcf(
     db: &Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>,
     path_input: &str,
     cf_counts: &str,
     cf_carriers: &str,
     genomebuild: crate::common::GenomeRelease,
+    mt_thresholds: MtThresholds,
+    xtr_as_par: bool,
+    with_strata: bool,
+    remove: bool,
 ) -> Result<(), anyhow::Error> {
     let mut input_reader = common::noodles::open_vcf_reader(path_input)
         .await
@@ -206,6 +407,8 @@ async fn import_vcf(
     let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
 
     let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+    let carrier_indices = carrier_indices(&pedigree)?;
+    let case_uuids = HashSet::from([case_uuid]);
     let mut prev = std::time::Instant::now();
 
     let mut records = input_reader.records(&input_header).await;
@@ -215,8 +418,12 @@ async fn import_vcf(
             &record_buf,
             &input_header,
             &pedigree,
+            &carrier_indices,
             &case_uuid,
             genomebuild,
+            mt_thresholds,
+            xtr_as_par,
+            with_strata,
         )?;
         // Obtain annonars variant key from current allele for RocksDB lookup.
         let vcf_var = annonars::common::keys::Var::from_vcf_allele(&record_buf, 0);
@@ -256,9 +463,14 @@ async fn import_vcf(
                     })?
                     .unwrap_or_default();
 
-            // Aggregate the data.
-            db_counts_data.aggregate(this_counts_data);
-            db_carrier_data.aggregate(this_carrier_data);
+            // Aggregate the data or remove the case from it.
+            if remove {
+                db_counts_data.subtract(this_counts_data);
+                db_carrier_data.remove_cases(&case_uuids);
+            } else {
+                db_counts_data.aggregate(this_counts_data);
+                db_carrier_data.aggregate(this_carrier_data);
+            }
 
             // Write data for variant back to database.
             transaction
@@ -314,13 +526,349 @@ async fn import_vcf(
     Ok(())
 }
 
-/// Perform the parallel import of VCF files.
+/// Aggregated counts and carriers by variant key, sharded by chromosome.
+type Shards = HashMap<String, BTreeMap<Vec<u8>, (ds::Counts, ds::CarrierList)>>;
+
+/// Number of variants to write per batch in `write_shard`.
+const WRITE_BATCH_SIZE: usize = 100_000;
+
+/// Aggregate the counts and carriers of `other` into `shards`.
+fn merge_shards(shards: &mut Shards, other: Shards) {
+    for (chrom, entries) in other {
+        let shard = shards.entry(chrom).or_default();
+        for (key, (counts, carriers)) in entries {
+            match shard.entry(key) {
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    entry.insert((counts, carriers));
+                }
+                std::collections::btree_map::Entry::Occupied(mut entry) => {
+                    let (shard_counts, shard_carriers) = entry.get_mut();
+                    shard_counts.aggregate(counts);
+                    shard_carriers.aggregate(carriers);
+                }
+            }
+        }
+    }
+}
+
+/// Read one VCF file into `Shards` for the bulk import.
+async fn read_vcf_shards(
+    path_input: &str,
+    genomebuild: crate::common::GenomeRelease,
+    mt_thresholds: MtThresholds,
+    xtr_as_par: bool,
+    with_strata: bool,
+) -> Result<Shards, anyhow::Error> {
+    let mut input_reader = common::noodles::open_vcf_reader(path_input)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path_input, e))?;
+    let input_header = input_reader.read_header().await?;
+
+    let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+    let carrier_indices = carrier_indices(&pedigree)?;
+
+    let mut shards = Shards::default();
+    let mut records = input_reader.records(&input_header).await;
+    while let Some(record_buf) = records.try_next().await? {
+        let (counts, carriers) = handle_record(
+            &record_buf,
+            &input_header,
+            &pedigree,
+            &carrier_indices,
+            &case_uuid,
+            genomebuild,
+            mt_thresholds,
+            xtr_as_par,
+            with_strata,
+        )?;
+        let vcf_var = annonars::common::keys::Var::from_vcf_allele(&record_buf, 0);
+        let shard = shards.entry(vcf_var.chrom.clone()).or_default();
+        let key: Vec<u8> = vcf_var.into();
+        // Input files are split by `seqvars ingest`, so keys are unique within a file.
+        shard.insert(key, (counts, carriers));
+    }
+
+    Ok(shards)
+}
+
+/// Aggregate the `entries` of one shard with the data in the database and write them
+/// back in batches.
+///
+/// The shards have disjoint keys, so they can be written in parallel without conflicts.
+/// Returns the number of variants written.
+fn write_shard(
+    db: &rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+    cf_counts: &str,
+    cf_carriers: &str,
+    entries: BTreeMap<Vec<u8>, (ds::Counts, ds::CarrierList)>,
+) -> Result<usize, anyhow::Error> {
+    let cf_counts = db.cf_handle(cf_counts).expect("checked earlier");
+    let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
+
+    let mut batch = rocksdb::WriteBatchWithTransaction::<true>::default();
+    let mut count = 0;
+    for (key, (counts, carriers)) in entries {
+        let mut db_counts_data = db
+            .get_cf(&cf_counts, &key)
+            .map_err(|e| anyhow::anyhow!("problem accessing counts data: {}", e))?
+            .map(|buffer| ds::Counts::from_vec(&buffer))
+            .unwrap_or_default();
+        let mut db_carrier_data = db
+            .get_cf(&cf_carriers, &key)
+            .map_err(|e| anyhow::anyhow!("problem accessing carrier data: {}", e))?
+            .map(|buffer| ds::CarrierList::try_from(buffer.as_slice()))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("problem decoding carrier data: {}", e))?
+            .unwrap_or_default();
+        db_counts_data.aggregate(counts);
+        db_carrier_data.aggregate(carriers);
+
+        batch.put_cf(&cf_counts, &key, db_counts_data.to_vec());
+        batch.put_cf(&cf_carriers, &key, db_carrier_data.to_vec());
+        count += 1;
+        if batch.len() >= 2 * WRITE_BATCH_SIZE {
+            db.write(std::mem::take(&mut batch))
+                .map_err(|e| anyhow::anyhow!("problem writing batch: {}", e))?;
+        }
+    }
+    db.write(batch)
+        .map_err(|e| anyhow::anyhow!("problem writing batch: {}", e))?;
+
+    Ok(count)
+}
+
+/// Perform the bulk import of VCF files.
+///
+/// The files are read in parallel and aggregated into per-thread shards that are merged
+/// afterwards.  Each chromosome shard is then written by one thread.
+#[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
+async fn vcf_import_bulk(
+    db: &Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>,
+    path_input: &[&str],
+    cf_counts: &str,
+    cf_carriers: &str,
+    genomebuild: crate::common::GenomeRelease,
+    mt_thresholds: MtThresholds,
+    xtr_as_par: bool,
+    with_strata: bool,
+) -> Result<(), anyhow::Error> {
+    let handle = tokio::runtime::Handle::current();
+    let shards = path_input
+        .par_iter()
+        .map(|path_input| {
+            tokio::task::block_in_place(|| {
+                handle
+                    .block_on(read_vcf_shards(
+                        path_input,
+                        genomebuild,
+                        mt_thresholds,
+                        xtr_as_par,
+                        with_strata,
+                    ))
+                    .map_err(|e| {
+                        anyhow::anyhow!("processing VCF file {} failed: {}", path_input, e)
+                    })
+            })
+        })
+        .try_fold(Shards::default, |mut shards, other| {
+            merge_shards(&mut shards, other?);
+            Ok::<_, anyhow::Error>(shards)
+        })
+        .try_reduce(Shards::default, |mut shards, other| {
+            merge_shards(&mut shards, other);
+            Ok(shards)
+        })?;
+    tracing::info!("  aggregated variants on {} chromosomes", shards.len());
+
+    let count = shards
+        .into_par_iter()
+        .map(|(chrom, entries)| {
+            write_shard(db, cf_counts, cf_carriers, entries)
+                .map_err(|e| anyhow::anyhow!("writing chromosome {} failed: {}", chrom, e))
+        })
+        .try_reduce(|| 0, |a, b| Ok(a + b))?;
+    tracing::info!("  wrote {} variants", count);
+
+    Ok(())
+}
+
+/// Read the case UUIDs from the headers of the VCF files `path_input`.
+async fn read_case_uuids(path_input: &[String]) -> Result<Vec<uuid::Uuid>, anyhow::Error> {
+    let mut result = Vec::with_capacity(path_input.len());
+    for path in path_input {
+        let mut input_reader = common::noodles::open_vcf_reader(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path, e))?;
+        let input_header = input_reader.read_header().await?;
+        let (_, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+        result.push(case_uuid);
+    }
+    Ok(result)
+}
+
+/// Return the UUIDs of all cases with carriers in the database.
+fn existing_case_uuids(
+    db: &rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+    cf_carriers: &str,
+) -> Result<HashSet<uuid::Uuid>, anyhow::Error> {
+    let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
+    let mut result = HashSet::new();
+    for item in db.iterator_cf(&cf_carriers, rocksdb::IteratorMode::Start) {
+        let (_, value) =
+            item.map_err(|e| anyhow::anyhow!("problem iterating carrier data: {}", e))?;
+        let carrier_list = ds::CarrierList::try_from(value.as_ref())
+            .map_err(|e| anyhow::anyhow!("problem decoding carrier data: {}", e))?;
+        result.extend(carrier_list.carriers.iter().map(|carrier| carrier.uuid));
+    }
+    Ok(result)
+}
+
+/// Decide which input files to import given their `case_uuids` and the UUIDs of
+/// `existing` cases in the database.
+///
+/// Returns the paths to import and the UUIDs of the cases to remove from the database
+/// first.  Cases occurring in more than one input file are always an error.
+fn plan_import<'a>(
+    path_input: &'a [String],
+    case_uuids: &[uuid::Uuid],
+    existing: &HashSet<uuid::Uuid>,
+    on_duplicate_case: OnDuplicateCase,
+) -> Result<(Vec<&'a str>, HashSet<uuid::Uuid>), anyhow::Error> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    let mut to_remove = HashSet::new();
+    for (path, case_uuid) in path_input.iter().zip(case_uuids) {
+        if !seen.insert(*case_uuid) {
+            return Err(anyhow::anyhow!(
+                "case {} occurs in more than one input file, e.g., {}",
+                case_uuid,
+                path
+            ))
+            .classify(ErrorClass::Input);
+        }
+        if existing.contains(case_uuid) {
+            match on_duplicate_case {
+                OnDuplicateCase::Fail => {
+                    return Err(anyhow::anyhow!(
+                        "case {} from {} is already in the database (see --on-duplicate-case)",
+                        case_uuid,
+                        path
+                    ))
+                    .classify(ErrorClass::Input);
+                }
+                OnDuplicateCase::Skip => {
+                    tracing::warn!(
+                        "skipping {} as case {} is already in the database",
+                        path,
+                        case_uuid
+                    );
+                    continue;
+                }
+                OnDuplicateCase::Replace => {
+                    tracing::warn!(
+                        "replacing case {} from {} that is already in the database",
+                        case_uuid,
+                        path
+                    );
+                    to_remove.insert(*case_uuid);
+                }
+            }
+        }
+        paths.push(path.as_str());
+    }
+    Ok((paths, to_remove))
+}
+
+/// Decide which input files to remove from the database given their `case_uuids` and
+/// the UUIDs of `existing` cases in the database.
+///
+/// Input files of cases that are not in the database are skipped.  Cases occurring in
+/// more than one input file are an error.
+fn plan_removal<'a>(
+    path_input: &'a [String],
+    case_uuids: &[uuid::Uuid],
+    existing: &HashSet<uuid::Uuid>,
+) -> Result<Vec<&'a str>, anyhow::Error> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    for (path, case_uuid) in path_input.iter().zip(case_uuids) {
+        if !seen.insert(*case_uuid) {
+            return Err(anyhow::anyhow!(
+                "case {} occurs in more than one input file, e.g., {}",
+                case_uuid,
+                path
+            ))
+            .classify(ErrorClass::Input);
+        }
+        if !existing.contains(case_uuid) {
+            tracing::warn!(
+                "skipping {} as case {} is not in the database",
+                path,
+                case_uuid
+            );
+            continue;
+        }
+        paths.push(path.as_str());
+    }
+    Ok(paths)
+}
+
+/// Remove the carriers of the cases with the given `uuids` and their contribution to
+/// the counts from the database.
+///
+/// Returns the number of affected variants.
+fn remove_cases(
+    db: &rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+    cf_counts: &str,
+    cf_carriers: &str,
+    uuids: &HashSet<uuid::Uuid>,
+) -> Result<usize, anyhow::Error> {
+    let cf_counts = db.cf_handle(cf_counts).expect("checked earlier");
+    let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
+
+    let mut result = 0;
+    for item in db.iterator_cf(&cf_carriers, rocksdb::IteratorMode::Start) {
+        let (key, value) =
+            item.map_err(|e| anyhow::anyhow!("problem iterating carrier data: {}", e))?;
+        let mut carrier_list = ds::CarrierList::try_from(value.as_ref())
+            .map_err(|e| anyhow::anyhow!("problem decoding carrier data: {}", e))?;
+        let removed = carrier_list.remove_cases(uuids);
+        if removed.is_empty() {
+            continue;
+        }
+
+        let mut counts = db
+            .get_cf(&cf_counts, &key)
+            .map_err(|e| anyhow::anyhow!("problem accessing counts data: {}", e))?
+            .map(|buffer| ds::Counts::from_vec(&buffer))
+            .unwrap_or_default();
+        for carrier in &removed {
+            counts.remove_carrier(carrier.genotype);
+        }
+
+        db.put_cf(&cf_counts, &key, counts.to_vec())
+            .map_err(|e| anyhow::anyhow!("problem writing counts data: {}", e))?;
+        db.put_cf(&cf_carriers, &key, carrier_list.to_vec())
+            .map_err(|e| anyhow::anyhow!("problem writing carrier data: {}", e))?;
+        result += 1;
+    }
+    Ok(result)
+}
+
+/// Perform the parallel import (or removal if `remove` is set) of VCF files.
+#[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
 async fn vcf_import(
     db: &Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>,
     path_input: &[&str],
     cf_counts: &str,
     cf_carriers: &str,
     genomebuild: crate::common::GenomeRelease,
+    mt_thresholds: MtThresholds,
+    xtr_as_par: bool,
+    with_strata: bool,
+    remove: bool,
 ) -> Result<(), anyhow::Error> {
     let handle = tokio::runtime::Handle::current();
     path_input.par_iter().try_for_each(|path_input| {
@@ -332,6 +880,10 @@ async fn vcf_import(
                     cf_counts,
                     cf_carriers,
                     genomebuild,
+                    mt_thresholds,
+                    xtr_as_par,
+                    with_strata,
+                    remove,
                 ))
                 .map_err(|e| anyhow::anyhow!("processing VCF file {} failed: {}", path_input, e))
         })
@@ -386,13 +938,15 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     // scope for the transaction database
     {
-        let db: Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>> =
-            Arc::new(rocksdb::TransactionDB::open_cf_descriptors(
+        let db: Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>> = Arc::new(
+            rocksdb::TransactionDB::open_cf_descriptors(
                 &options,
                 &tx_options,
                 &args.path_out_rocksdb,
                 cf_descriptors,
-            )?);
+            )
+            .classify(ErrorClass::Db)?,
+        );
         tracing::info!("  writing meta information");
         let cf_meta = db.cf_handle("meta").unwrap();
         db.put_cf(&cf_meta, "varfish-worker-version", common::worker_version())?;
@@ -404,19 +958,62 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         db.put_cf(&cf_meta, "db-name", "seqvars-aggregation")?;
         tracing::info!("... done opening RocksDB");
 
-        tracing::info!("Importing VCF files ...");
+        tracing::info!("Checking for cases already in the database ...");
+        let case_uuids = read_case_uuids(&path_input)
+            .await
+            .classify(ErrorClass::Input)?;
+        let existing = existing_case_uuids(&db, &args.cf_carriers).classify(ErrorClass::Db)?;
+        let (paths, to_remove) = if args.remove {
+            (
+                plan_removal(&path_input, &case_uuids, &existing)?,
+                HashSet::new(),
+            )
+        } else {
+            plan_import(&path_input, &case_uuids, &existing, args.on_duplicate_case)?
+        };
+        if !to_remove.is_empty() {
+            let count = remove_cases(&db, &args.cf_counts, &args.cf_carriers, &to_remove)
+                .classify(ErrorClass::Db)?;
+            tracing::info!(
+                "  removed {} prior case(s) from {} variant(s)",
+                to_remove.len(),
+                count
+            );
+        }
+        tracing::info!("... done checking for cases already in the database");
+
+        let action = if args.remove { "removing" } else { "importing" };
+        tracing::info!("{} VCF files ...", action);
         let before_import = std::time::Instant::now();
-        let paths = path_input.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-        vcf_import(
-            &db,
-            &paths,
-            &args.cf_counts,
-            &args.cf_carriers,
-            args.genomebuild,
-        )
-        .await?;
+        if args.bulk {
+            vcf_import_bulk(
+                &db,
+                &paths,
+                &args.cf_counts,
+                &args.cf_carriers,
+                args.genomebuild,
+                MtThresholds::from(args),
+                args.xtr_as_par,
+                args.with_strata,
+            )
+            .await?;
+        } else {
+            vcf_import(
+                &db,
+                &paths,
+                &args.cf_counts,
+                &args.cf_carriers,
+                args.genomebuild,
+                MtThresholds::from(args),
+                args.xtr_as_par,
+                args.with_strata,
+                args.remove,
+            )
+            .await?;
+        }
         tracing::info!(
-            "... done importing VCF files in {:?}",
+            "... done {} VCF files in {:?}",
+            action,
             before_import.elapsed()
         );
     }
@@ -453,78 +1050,201 @@ mod test {
 
     #[test]
     fn test_is_par() {
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             60000,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             60001,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             2699520,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             2699521,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             154931043,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             154931044,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             155260560,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             155260561,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             155260561,
-            crate::common::GenomeRelease::Grch38
+            crate::common::GenomeRelease::Grch38,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             155701383,
-            crate::common::GenomeRelease::Grch38
+            crate::common::GenomeRelease::Grch38,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::X,
             156030895,
-            crate::common::GenomeRelease::Grch38
+            crate::common::GenomeRelease::Grch38,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::X,
             156030896,
-            crate::common::GenomeRelease::Grch38
+            crate::common::GenomeRelease::Grch38,
+            false
         ));
-        assert!(!super::is_par(
+        assert!(!super::is_diploid_in_males(
             super::Chrom::Y,
             10000,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
-        assert!(super::is_par(
+        assert!(super::is_diploid_in_males(
             super::Chrom::Y,
             10001,
-            crate::common::GenomeRelease::Grch37
+            crate::common::GenomeRelease::Grch37,
+            false
         ));
     }
 
+    #[rstest::rstest]
+    #[case(2699520, false, true)]
+    #[case(88400001, false, false)]
+    #[case(88400001, true, true)]
+    #[case(92000001, true, false)]
+    fn is_diploid_in_males(#[case] pos: usize, #[case] xtr_as_par: bool, #[case] expected: bool) {
+        assert_eq!(
+            super::is_diploid_in_males(
+                super::Chrom::X,
+                pos,
+                crate::common::GenomeRelease::Grch37,
+                xtr_as_par
+            ),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::new_case(OnDuplicateCase::Fail, &[2], Some((vec!["b.vcf"], vec![])))]
+    #[case::fail(OnDuplicateCase::Fail, &[1, 2], None)]
+    #[case::skip(OnDuplicateCase::Skip, &[1, 2], Some((vec!["b.vcf"], vec![])))]
+    #[case::replace(OnDuplicateCase::Replace, &[1, 2], Some((vec!["a.vcf", "b.vcf"], vec![1])))]
+    #[case::within_run(OnDuplicateCase::Skip, &[1, 1], None)]
+    fn plan_import(
+        #[case] on_duplicate_case: OnDuplicateCase,
+        #[case] case_uuids: &[u128],
+        #[case] expected: Option<(Vec<&str>, Vec<u128>)>,
+    ) {
+        let path_input = vec![String::from("a.vcf"), String::from("b.vcf")];
+        let case_uuids = case_uuids
+            .iter()
+            .map(|uuid| uuid::Uuid::from_u128(*uuid))
+            .collect::<Vec<_>>();
+        let existing = [uuid::Uuid::from_u128(1)].into_iter().collect();
+
+        let result = super::plan_import(&path_input, &case_uuids, &existing, on_duplicate_case);
+
+        match expected {
+            Some((paths, to_remove)) => {
+                let (result_paths, result_to_remove) = result.unwrap();
+                assert_eq!(result_paths, paths);
+                assert_eq!(
+                    result_to_remove,
+                    to_remove
+                        .into_iter()
+                        .map(uuid::Uuid::from_u128)
+                        .collect::<HashSet<_>>()
+                );
+            }
+            None => {
+                let err = result.unwrap_err();
+                assert_eq!(ErrorClass::of(&err), ErrorClass::Input);
+            }
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::existing(&[1, 2], Some(vec!["a.vcf"]))]
+    #[case::none_existing(&[2, 3], Some(vec![]))]
+    #[case::within_run(&[1, 1], None)]
+    fn plan_removal(#[case] case_uuids: &[u128], #[case] expected: Option<Vec<&str>>) {
+        let path_input = vec![String::from("a.vcf"), String::from("b.vcf")];
+        let case_uuids = case_uuids
+            .iter()
+            .map(|uuid| uuid::Uuid::from_u128(*uuid))
+            .collect::<Vec<_>>();
+        let existing = [uuid::Uuid::from_u128(1)].into_iter().collect();
+
+        let result = super::plan_removal(&path_input, &case_uuids, &existing);
+
+        match expected {
+            Some(paths) => assert_eq!(result.unwrap(), paths),
+            None => {
+                let err = result.unwrap_err();
+                assert_eq!(ErrorClass::of(&err), ErrorClass::Input);
+            }
+        }
+    }
+
+    #[test]
+    fn carrier_indices() -> Result<(), anyhow::Error> {
+        let mut pedigree = mehari::ped::PedigreeByName::default();
+        for (name, family) in [("a", "FAM1"), ("b", "FAM2"), ("c", "FAM1"), ("d", "FAM2")] {
+            pedigree.individuals.insert(
+                name.to_string(),
+                mehari::ped::Individual {
+                    family: family.to_string(),
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let carrier_indices = super::carrier_indices(&pedigree)?;
+        let mut carrier_indices = carrier_indices.into_iter().collect::<Vec<_>>();
+        carrier_indices.sort();
+        assert_eq!(
+            carrier_indices,
+            vec![
+                ("a".to_string(), (0, 0)),
+                ("b".to_string(), (1, 0)),
+                ("c".to_string(), (0, 1)),
+                ("d".to_string(), (1, 1)),
+            ]
+        );
+
+        Ok(())
+    }
+
     #[tracing_test::traced_test]
     #[test]
     fn handle_record_snapshot() -> Result<(), anyhow::Error> {
@@ -544,12 +1264,17 @@ mod test {
             }
 
             let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&header)?;
+            let carrier_indices = super::carrier_indices(&pedigree)?;
             let (counts, carriers) = super::handle_record(
                 &record_buf,
                 &header,
                 &pedigree,
+                &carrier_indices,
                 &case_uuid,
                 crate::common::GenomeRelease::Grch37,
+                Default::default(),
+                false,
+                true,
             )?;
 
             insta::assert_debug_snapshot!(counts);
@@ -558,4 +1283,109 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::af_homoplasmic("1", Some(vec![Some(1), Some(99)]), Some(100), ds::Genotype::Homoplasmic)]
+    #[case::af_heteroplasmic("0/1", Some(vec![Some(70), Some(30)]), Some(100), ds::Genotype::Heteroplasmic)]
+    #[case::af_below_threshold("0/1", Some(vec![Some(98), Some(2)]), Some(100), ds::Genotype::HomRef)]
+    #[case::af_no_dp("1/1", Some(vec![Some(50), Some(50)]), None, ds::Genotype::Heteroplasmic)]
+    #[case::gt_haploid_alt("1", None, None, ds::Genotype::Homoplasmic)]
+    #[case::gt_het("0/1", None, Some(100), ds::Genotype::Heteroplasmic)]
+    #[case::gt_single_ad("1/1", Some(vec![Some(0)]), Some(100), ds::Genotype::Homoplasmic)]
+    #[case::gt_ref("0", None, None, ds::Genotype::HomRef)]
+    fn mt_carrier_genotype(
+        #[case] gt_str: &str,
+        #[case] ad: Option<Vec<Option<i32>>>,
+        #[case] dp: Option<i32>,
+        #[case] expected: ds::Genotype,
+    ) -> Result<(), anyhow::Error> {
+        use noodles::vcf::variant::record::samples::keys::key;
+        use vcf::variant::record_buf::samples::sample::value::{Array, Value};
+
+        let samples = vcf::variant::record_buf::samples::Samples::new(
+            [key::READ_DEPTHS, key::READ_DEPTH]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            vec![vec![
+                ad.map(|ad| Value::Array(Array::Integer(ad))),
+                dp.map(Value::Integer),
+            ]],
+        );
+        let sample = samples.get_index(0).expect("one sample");
+
+        let genotype: Genotype = gt_str.parse()?;
+        assert_eq!(
+            super::mt_carrier_genotype(&sample, genotype, Default::default()),
+            expected
+        );
+
+        Ok(())
+    }
+
+    /// Open a fresh aggregation database in `path` with the default column families.
+    fn open_test_db(
+        path: &std::path::Path,
+    ) -> Result<Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>, anyhow::Error> {
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        Ok(Arc::new(rocksdb::TransactionDB::open_cf(
+            &options,
+            &rocksdb::TransactionDBOptions::default(),
+            path,
+            ["counts", "carriers"],
+        )?))
+    }
+
+    /// Return all entries of column family `cf` of `db`.
+    fn dump_cf(
+        db: &rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+        cf: &str,
+    ) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>, anyhow::Error> {
+        let cf = db.cf_handle(cf).expect("must exist");
+        Ok(db
+            .iterator_cf(&cf, rocksdb::IteratorMode::Start)
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn vcf_import_bulk() -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let path_input = ["tests/seqvars/aggregate/ingest.vcf"];
+
+        let db_tx = open_test_db(&tmp_dir.join("tx"))?;
+        super::vcf_import(
+            &db_tx,
+            &path_input,
+            "counts",
+            "carriers",
+            crate::common::GenomeRelease::Grch37,
+            Default::default(),
+            false,
+            true,
+            false,
+        )
+        .await?;
+        let db_bulk = open_test_db(&tmp_dir.join("bulk"))?;
+        super::vcf_import_bulk(
+            &db_bulk,
+            &path_input,
+            "counts",
+            "carriers",
+            crate::common::GenomeRelease::Grch37,
+            Default::default(),
+            false,
+            true,
+        )
+        .await?;
+
+        for cf in ["counts", "carriers"] {
+            let expected = dump_cf(&db_tx, cf)?;
+            assert!(!expected.is_empty());
+            assert_eq!(dump_cf(&db_bulk, cf)?, expected);
+        }
+
+        Ok(())
+    }
 }
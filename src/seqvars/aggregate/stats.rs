@@ -0,0 +1,258 @@
+//! Implementation of `seqvars aggregate stats` subcommand.
+//!
+//! Reports cohort-level summaries of an in-house database built with `seqvars aggregate`
+//! for monitoring its growth and health.
+
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+
+use super::ds;
+
+/// Command line arguments for `seqvars aggregate stats` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "report statistics of seqvars inhouse database", long_about = None)]
+pub struct Args {
+    /// Path to the RocksDB built with `seqvars aggregate`.
+    #[clap(long)]
+    pub path_rocksdb: String,
+    /// Column family name for the count data.
+    #[clap(long, default_value = "counts")]
+    pub cf_counts: String,
+    /// Column family name for the carrier UUID data.
+    #[clap(long, default_value = "carriers")]
+    pub cf_carriers: String,
+    /// Optional path to the output JSON file, written to stdout if not given.
+    #[clap(long)]
+    pub path_output: Option<String>,
+}
+
+/// Genotype counts over a set of variants.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct GenotypeStats {
+    /// Number of variants.
+    pub variant_count: u64,
+    /// Number of variants with exactly one carrier.
+    pub singleton_count: u64,
+    /// Fraction of variants with exactly one carrier.
+    pub singleton_fraction: Option<f64>,
+    /// Number of het. calls.
+    pub count_het: u64,
+    /// Number of hom. alt. calls.
+    pub count_homalt: u64,
+    /// Number of hemi. alt. calls.
+    pub count_hemialt: u64,
+    /// Number of heteroplasmic calls (chrMT only).
+    pub count_heteroplasmic: u64,
+    /// Number of homoplasmic calls (chrMT only).
+    pub count_homoplasmic: u64,
+    /// Ratio of het. to hom. alt. calls.
+    pub het_hom_ratio: Option<f64>,
+}
+
+impl GenotypeStats {
+    /// Add the `counts` of one variant.
+    fn add(&mut self, counts: &ds::Counts) {
+        let carriers = counts.count_het
+            + counts.count_homalt
+            + counts.count_hemialt
+            + counts.count_heteroplasmic
+            + counts.count_homoplasmic;
+        self.variant_count += 1;
+        if carriers == 1 {
+            self.singleton_count += 1;
+        }
+        self.count_het += counts.count_het as u64;
+        self.count_homalt += counts.count_homalt as u64;
+        self.count_hemialt += counts.count_hemialt as u64;
+        self.count_heteroplasmic += counts.count_heteroplasmic as u64;
+        self.count_homoplasmic += counts.count_homoplasmic as u64;
+    }
+
+    /// Compute the fraction and ratio fields from the counts.
+    fn finish(&mut self) {
+        self.singleton_fraction = (self.variant_count > 0)
+            .then(|| self.singleton_count as f64 / self.variant_count as f64);
+        self.het_hom_ratio =
+            (self.count_homalt > 0).then(|| self.count_het as f64 / self.count_homalt as f64);
+    }
+}
+
+/// Statistics of the in-house database.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Stats {
+    /// Genome release from the database metadata.
+    pub genome_release: Option<String>,
+    /// Version of the worker that last wrote to the database.
+    pub worker_version: Option<String>,
+    /// Number of distinct cases with carriers.
+    pub case_count: u64,
+    /// Counts over all variants.
+    pub total: GenotypeStats,
+    /// Counts by chromosome, in database order.
+    pub by_chrom: IndexMap<String, GenotypeStats>,
+}
+
+impl Stats {
+    /// Build the statistics from the `counts` by variant key and the `carriers` lists.
+    fn from_entries<C, L>(counts: C, carriers: L) -> Result<Self, anyhow::Error>
+    where
+        C: IntoIterator<Item = Result<(Vec<u8>, ds::Counts), anyhow::Error>>,
+        L: IntoIterator<Item = Result<ds::CarrierList, anyhow::Error>>,
+    {
+        let mut result = Self::default();
+        for entry in counts {
+            let (key, counts) = entry?;
+            if key.len() < 2 {
+                anyhow::bail!("invalid variant key {:?}", &key);
+            }
+            let chrom = annonars::common::keys::chrom_key_to_name(&key[0..2]);
+            result.total.add(&counts);
+            result.by_chrom.entry(chrom).or_default().add(&counts);
+        }
+        result.total.finish();
+        result.by_chrom.values_mut().for_each(GenotypeStats::finish);
+
+        let mut case_uuids = HashSet::new();
+        for carrier_list in carriers {
+            case_uuids.extend(carrier_list?.carriers.iter().map(|carrier| carrier.uuid));
+        }
+        result.case_count = case_uuids.len() as u64;
+
+        Ok(result)
+    }
+
+    /// Compute the statistics of the database at `args.path_rocksdb`.
+    fn load(args: &Args) -> Result<Self, anyhow::Error> {
+        let cf_names = &["meta", &args.cf_counts, &args.cf_carriers];
+        let db = rocksdb::DB::open_cf_for_read_only(
+            &rocksdb::Options::default(),
+            &args.path_rocksdb,
+            cf_names,
+            true,
+        )
+        .map_err(|e| anyhow::anyhow!("could not open RocksDB {}: {}", &args.path_rocksdb, e))?;
+        let cf_meta = db.cf_handle("meta").expect("opened above");
+        let cf_counts = db.cf_handle(&args.cf_counts).expect("opened above");
+        let cf_carriers = db.cf_handle(&args.cf_carriers).expect("opened above");
+
+        let meta = |key: &str| -> Result<Option<String>, anyhow::Error> {
+            Ok(db
+                .get_cf(&cf_meta, key)?
+                .map(|value| String::from_utf8_lossy(&value).to_string()))
+        };
+
+        let mut result = Self::from_entries(
+            db.iterator_cf(&cf_counts, rocksdb::IteratorMode::Start)
+                .map(|item| {
+                    let (key, value) =
+                        item.map_err(|e| anyhow::anyhow!("problem iterating counts: {}", e))?;
+                    Ok((key.to_vec(), ds::Counts::from_vec(&value)))
+                }),
+            db.iterator_cf(&cf_carriers, rocksdb::IteratorMode::Start)
+                .map(|item| {
+                    let (_, value) =
+                        item.map_err(|e| anyhow::anyhow!("problem iterating carrier data: {}", e))?;
+                    ds::CarrierList::try_from(value.as_ref())
+                        .map_err(|e| anyhow::anyhow!("problem decoding carrier data: {}", e))
+                }),
+        )?;
+        result.genome_release = meta("genome-release")?;
+        result.worker_version = meta("varfish-worker-version")?;
+        Ok(result)
+    }
+}
+
+/// Main entry point for `seqvars aggregate stats` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("Computing statistics of {} ...", &args.path_rocksdb);
+    let before_stats = std::time::Instant::now();
+    let stats = Stats::load(args).classify(ErrorClass::Db)?;
+    tracing::info!(
+        "... done computing statistics of {} variants in {:?}",
+        stats.total.variant_count,
+        before_stats.elapsed()
+    );
+
+    let json = serde_json::to_string_pretty(&stats)?;
+    if let Some(path_output) = args.path_output.as_ref() {
+        std::fs::write(path_output, format!("{}\n", json))
+            .map_err(|e| anyhow::anyhow!("could not write statistics to {}: {}", path_output, e))?;
+    } else {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ds, Stats};
+
+    fn counts(count_het: u32, count_homalt: u32) -> ds::Counts {
+        ds::Counts {
+            count_het,
+            count_homalt,
+            ..Default::default()
+        }
+    }
+
+    fn key(chrom: &str, pos: i32) -> Vec<u8> {
+        annonars::common::keys::Var::from(chrom, pos, "A", "G").into()
+    }
+
+    fn carriers(uuids: &[u128]) -> ds::CarrierList {
+        ds::CarrierList {
+            carriers: uuids
+                .iter()
+                .map(|uuid| ds::Carrier {
+                    uuid: uuid::Uuid::from_u128(*uuid),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn from_entries() -> Result<(), anyhow::Error> {
+        let stats = Stats::from_entries(
+            vec![
+                (key("1", 100), counts(1, 0)),
+                (key("1", 200), counts(2, 1)),
+                (key("X", 100), counts(0, 1)),
+            ]
+            .into_iter()
+            .map(Ok),
+            vec![carriers(&[1]), carriers(&[1, 2]), carriers(&[3])]
+                .into_iter()
+                .map(Ok),
+        )?;
+
+        assert_eq!(stats.case_count, 3);
+        assert_eq!(stats.total.variant_count, 3);
+        assert_eq!(stats.total.singleton_count, 2);
+        assert_eq!(stats.total.het_hom_ratio, Some(1.5));
+        assert_eq!(stats.by_chrom.keys().collect::<Vec<_>>(), vec!["1", "X"]);
+        assert_eq!(stats.by_chrom["1"].singleton_fraction, Some(0.5));
+        assert_eq!(stats.by_chrom["X"].het_hom_ratio, Some(0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_entries_empty() -> Result<(), anyhow::Error> {
+        let stats = Stats::from_entries(Vec::new(), Vec::new())?;
+
+        assert_eq!(stats.case_count, 0);
+        assert_eq!(stats.total.singleton_fraction, None);
+        assert_eq!(stats.total.het_hom_ratio, None);
+        assert!(stats.by_chrom.is_empty());
+
+        Ok(())
+    }
+}
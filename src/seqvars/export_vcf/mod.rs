@@ -0,0 +1,810 @@
+//! Implementation of `seqvars export-vcf` subcommand.
+//!
+//! The JSONL result set written by `seqvars query` is converted back into a VCF file so it
+//! can be loaded into genome browsers.  The annotation of each record is written to `INFO`
+//! fields and the call information to `FORMAT` fields; the query provenance from the result
+//! set header is kept in `##x-varfish-*` header lines.
+
+use std::io::BufRead as _;
+
+use mehari::common::noodles::open_vcf_writer;
+use noodles::vcf;
+use tokio::io::AsyncWriteExt;
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+use crate::{common, flush_and_shutdown};
+
+/// Command line arguments for `seqvars export-vcf` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(
+    author,
+    version,
+    about = "export seqvars query results to VCF",
+    long_about = None
+)]
+pub struct Args {
+    /// Path to the JSONL result file written by `seqvars query`.
+    #[arg(long)]
+    pub path_input: String,
+    /// Path to the output VCF file, bgzip-compressed and indexed if ending in `.vcf.gz`.
+    #[arg(long)]
+    pub path_output: String,
+}
+
+/// Header and records of a `seqvars query` result file.
+#[derive(Debug, Default)]
+pub(crate) struct ResultSet {
+    /// The header line.
+    pub header: pbs_output::OutputHeader,
+    /// The records, sorted by coordinate.
+    pub records: Vec<pbs_output::OutputRecord>,
+}
+
+/// Read the result set from `reader`.
+///
+/// The first line is the header, lines with a `vcfVariant` are records, and the trailer
+/// lines are skipped.
+pub(crate) fn read_result_set(
+    reader: Box<dyn std::io::BufRead>,
+) -> Result<ResultSet, anyhow::Error> {
+    let mut header = None;
+    let mut records = Vec::new();
+    for (no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("invalid JSON in line {}: {}", no + 1, e))?;
+        if header.is_none() {
+            header = Some(
+                serde_json::from_value(value)
+                    .map_err(|e| anyhow::anyhow!("invalid header in line {}: {}", no + 1, e))?,
+            );
+        } else if value.get("vcfVariant").is_some() {
+            records.push(
+                serde_json::from_value::<pbs_output::OutputRecord>(value)
+                    .map_err(|e| anyhow::anyhow!("invalid record in line {}: {}", no + 1, e))?,
+            );
+        }
+    }
+
+    records.sort_by(|a, b| {
+        let key = |record: &pbs_output::OutputRecord| {
+            record.vcf_variant.as_ref().map(|v| {
+                (
+                    v.chrom_no,
+                    v.pos,
+                    v.ref_allele.clone(),
+                    v.alt_allele.clone(),
+                )
+            })
+        };
+        key(a).cmp(&key(b))
+    });
+
+    Ok(ResultSet {
+        header: header.ok_or_else(|| anyhow::anyhow!("result file has no header line"))?,
+        records,
+    })
+}
+
+/// Return the names of the samples in the order of their first appearance in `records`.
+fn sample_names(records: &[pbs_output::OutputRecord]) -> Vec<String> {
+    let mut result = indexmap::IndexSet::new();
+    for record in records {
+        let call_infos = record
+            .variant_annotation
+            .as_ref()
+            .and_then(|annotation| annotation.call.as_ref())
+            .map(|call| call.call_infos.as_slice())
+            .unwrap_or_default();
+        for call_info in call_infos {
+            result.insert(call_info.sample.clone());
+        }
+    }
+    result.into_iter().collect()
+}
+
+/// Convert the score `key` into a valid `INFO` key.
+fn score_info_key(key: &str) -> String {
+    let key = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if key.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", key)
+    } else {
+        key
+    }
+}
+
+/// Return the `INFO` type and description of each score in the result set, by score key.
+///
+/// The columns declared in the header come first, followed by the undeclared scores of the
+/// records with the type derived from their first value.
+fn score_infos(
+    result_set: &ResultSet,
+) -> indexmap::IndexMap<String, (vcf::header::record::value::map::info::Type, String)> {
+    use pbjson_types::value::Kind;
+    use vcf::header::record::value::map::info::Type;
+
+    let mut result = indexmap::IndexMap::new();
+    for column in &result_set.header.variant_score_columns {
+        let ty = match pbs_output::VariantScoreColumnType::try_from(column.r#type) {
+            Ok(pbs_output::VariantScoreColumnType::Number) => Type::Float,
+            _ => Type::String,
+        };
+        result.insert(column.name.clone(), (ty, column.description.clone()));
+    }
+    for record in &result_set.records {
+        let Some(scores) = record
+            .variant_annotation
+            .as_ref()
+            .and_then(|annotation| annotation.variant.as_ref())
+            .and_then(|variant| variant.scores.as_ref())
+        else {
+            continue;
+        };
+        for entry in &scores.entries {
+            let ty = match entry.value.as_ref().and_then(|value| value.kind.as_ref()) {
+                Some(Kind::NumberValue(_)) => Type::Float,
+                Some(Kind::BoolValue(_)) => Type::Flag,
+                Some(Kind::StringValue(_)) => Type::String,
+                _ => continue,
+            };
+            result
+                .entry(entry.key.clone())
+                .or_insert_with(|| (ty, format!("Score {}", entry.key)));
+        }
+    }
+    result
+}
+
+/// Prefixes and labels of the population frequency `INFO` fields.
+const NUCLEAR_FREQUENCIES: &[(&str, &str)] = &[
+    ("gnomad_exomes", "gnomAD exomes"),
+    ("gnomad_genomes", "gnomAD genomes"),
+    ("inhouse", "in-house"),
+];
+
+/// Prefixes and labels of the mitochondrial frequency `INFO` fields.
+const MITOCHONDRIAL_FREQUENCIES: &[(&str, &str)] =
+    &[("gnomad_mtdna", "gnomAD-mtDNA"), ("helixmtdb", "HelixMtDb")];
+
+/// Add the `INFO` and `FORMAT` definitions to `builder`.
+fn add_fields(
+    builder: vcf::header::Builder,
+    score_infos: &indexmap::IndexMap<String, (vcf::header::record::value::map::info::Type, String)>,
+) -> vcf::header::Builder {
+    use vcf::header::record::value::{
+        map::{
+            format::{Number as FormatNumber, Type as FormatType},
+            info::{Number, Type},
+            Format, Info,
+        },
+        Map,
+    };
+    use vcf::variant::record::samples::keys::key;
+
+    let mut builder = builder;
+    let add_info = |builder: vcf::header::Builder,
+                    name: String,
+                    number: Number,
+                    ty: Type,
+                    description: String| {
+        builder.add_info(name, Map::<Info>::new(number, ty, description))
+    };
+
+    for (key, label) in [
+        ("gene_symbol", "Gene symbol"),
+        ("hgnc_id", "HGNC ID of the gene"),
+        ("tx_accession", "Accession of the transcript"),
+        ("hgvs_t", "HGVS description on the transcript"),
+        ("hgvs_p", "HGVS description on the protein"),
+    ] {
+        builder = add_info(
+            builder,
+            key.to_string(),
+            Number::Count(1),
+            Type::String,
+            label.to_string(),
+        );
+    }
+    builder = add_info(
+        builder,
+        "consequences".to_string(),
+        Number::Unknown,
+        Type::String,
+        "Consequences of the variant on the transcript".to_string(),
+    );
+
+    for (prefix, label) in NUCLEAR_FREQUENCIES {
+        for (suffix, ty, description) in [
+            ("an", Type::Integer, "Number of covered alleles"),
+            ("het", Type::Integer, "Number of heterozygous carriers"),
+            ("hom", Type::Integer, "Number of homozygous carriers"),
+            ("hemi", Type::Integer, "Number of hemizygous carriers"),
+            ("af", Type::Float, "Allele frequency"),
+        ] {
+            builder = add_info(
+                builder,
+                format!("{}_{}", prefix, suffix),
+                Number::Count(1),
+                ty,
+                format!("{} in {}", description, label),
+            );
+        }
+    }
+    for (prefix, label) in MITOCHONDRIAL_FREQUENCIES {
+        for (suffix, ty, description) in [
+            ("an", Type::Integer, "Number of covered alleles"),
+            ("het", Type::Integer, "Number of heteroplasmic carriers"),
+            ("hom", Type::Integer, "Number of homoplasmic carriers"),
+            ("af", Type::Float, "Allele frequency"),
+        ] {
+            builder = add_info(
+                builder,
+                format!("{}_{}", prefix, suffix),
+                Number::Count(1),
+                ty,
+                format!("{} in {}", description, label),
+            );
+        }
+    }
+    builder = add_info(
+        builder,
+        "inhouse_carrier_uuids".to_string(),
+        Number::Unknown,
+        Type::String,
+        "Case UUIDs of the in-house carriers".to_string(),
+    );
+
+    for (key, ty, description) in [
+        ("clinvar_vcv", Type::String, "ClinVar VCV accession"),
+        (
+            "clinvar_significance",
+            Type::String,
+            "ClinVar aggregate germline significance",
+        ),
+        (
+            "clinvar_effective_significance",
+            Type::String,
+            "ClinVar effective (worst) germline significance",
+        ),
+        (
+            "clinvar_review_status",
+            Type::String,
+            "ClinVar aggregate germline review status",
+        ),
+        (
+            "clinvar_submissions",
+            Type::Integer,
+            "Number of current ClinVar germline submissions",
+        ),
+    ] {
+        builder = add_info(
+            builder,
+            key.to_string(),
+            Number::Count(1),
+            ty,
+            description.to_string(),
+        );
+    }
+    builder = add_info(
+        builder,
+        "clinvar_conflicting".to_string(),
+        Number::Count(0),
+        Type::Flag,
+        "ClinVar submissions with assertion criteria conflict".to_string(),
+    );
+
+    for (key, (ty, description)) in score_infos {
+        let number = if *ty == Type::Flag {
+            Number::Count(0)
+        } else {
+            Number::Count(1)
+        };
+        builder = add_info(
+            builder,
+            score_info_key(key),
+            number,
+            *ty,
+            description.clone(),
+        );
+    }
+
+    builder
+        .add_format(key::GENOTYPE, Map::<Format>::from(key::GENOTYPE))
+        .add_format(key::READ_DEPTH, Map::<Format>::from(key::READ_DEPTH))
+        .add_format(
+            key::READ_DEPTHS,
+            Map::<Format>::new(
+                FormatNumber::ReferenceAlternateBases,
+                FormatType::Integer,
+                "Read depth for each allele, the reference depth is not kept in the results",
+            ),
+        )
+        .add_format(
+            key::CONDITIONAL_GENOTYPE_QUALITY,
+            Map::<Format>::from(key::CONDITIONAL_GENOTYPE_QUALITY),
+        )
+        .add_format(key::PHASE_SET, Map::<Format>::from(key::PHASE_SET))
+}
+
+/// Build the VCF header for `result_set` with the given `sample_names`.
+fn build_header(
+    result_set: &ResultSet,
+    sample_names: &[String],
+    score_infos: &indexmap::IndexMap<String, (vcf::header::record::value::map::info::Type, String)>,
+) -> Result<vcf::Header, anyhow::Error> {
+    use vcf::header::record::value::{map::Other, Map};
+    use vcf::header::record::Value;
+
+    let header = &result_set.header;
+    let builder = add_fields(vcf::Header::builder(), score_infos);
+    let builder = match pbs_output::GenomeRelease::try_from(header.genome_release) {
+        Ok(pbs_output::GenomeRelease::Grch37) => {
+            crate::seqvars::ingest::header::add_contigs_37(builder)
+        }
+        Ok(pbs_output::GenomeRelease::Grch38) => {
+            crate::seqvars::ingest::header::add_contigs_38(builder)
+        }
+        _ => anyhow::bail!("result file header has no genome release"),
+    }
+    .map_err(|e| anyhow::anyhow!("problem adding contigs: {}", e))?;
+
+    let mut builder = builder.insert(
+        "x-varfish-case-uuid".parse()?,
+        Value::String(header.case_uuid.clone()),
+    )?;
+    let versions = header
+        .versions
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.version.as_str()))
+        .chain(std::iter::once(("export-vcf", common::worker_version())));
+    for (name, version) in versions {
+        builder = builder.insert(
+            "x-varfish-version".parse()?,
+            Value::Map(
+                name.to_string(),
+                Map::<Other>::builder()
+                    .insert("Version".parse()?, version)
+                    .build()?,
+            ),
+        )?;
+    }
+    if !header.settings_fingerprint.is_empty() {
+        builder = builder.insert(
+            "x-varfish-settings-fingerprint".parse()?,
+            Value::String(header.settings_fingerprint.clone()),
+        )?;
+    }
+    for panel in &header.gene_panels {
+        builder = builder.insert(
+            "x-varfish-gene-panel".parse()?,
+            Value::Map(
+                panel.checksum.clone(),
+                Map::<Other>::builder()
+                    .insert("Path".parse()?, panel.path.clone())
+                    .insert("Name".parse()?, panel.name.clone().unwrap_or_default())
+                    .insert(
+                        "Version".parse()?,
+                        panel.version.clone().unwrap_or_default(),
+                    )
+                    .build()?,
+            ),
+        )?;
+    }
+    if let Some(query) = header.query.as_ref() {
+        builder = builder.insert(
+            "x-varfish-query".parse()?,
+            Value::String(
+                serde_json::to_string(query)
+                    .map_err(|e| anyhow::anyhow!("could not convert query to JSON: {}", e))?,
+            ),
+        )?;
+    }
+
+    for name in sample_names {
+        builder = builder.add_sample_name(name.clone());
+    }
+
+    Ok(builder.build())
+}
+
+/// Convert `value` into an `INFO` string value, VCF does not allow whitespace there.
+fn info_string(value: &str) -> vcf::variant::record_buf::info::field::Value {
+    vcf::variant::record_buf::info::field::Value::String(value.replace(char::is_whitespace, "_"))
+}
+
+/// Strip `prefix` from the protobuf enum name `name` and convert it to lower case.
+fn enum_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix).unwrap_or(name).to_lowercase()
+}
+
+/// Build the `INFO` fields of `record`.
+fn build_info(record: &pbs_output::OutputRecord) -> vcf::variant::record_buf::Info {
+    use pbjson_types::value::Kind;
+    use vcf::variant::record_buf::info::field::{value::Array, Value};
+
+    let mut result: Vec<(String, Option<Value>)> = Vec::new();
+    let annotation = record.variant_annotation.as_ref();
+
+    if let Some(gene) = annotation.and_then(|annotation| annotation.gene.as_ref()) {
+        if let Some(identity) = gene.identity.as_ref() {
+            result.push((
+                "gene_symbol".into(),
+                Some(info_string(&identity.gene_symbol)),
+            ));
+            result.push(("hgnc_id".into(), Some(info_string(&identity.hgnc_id))));
+        }
+        if let Some(consequences) = gene.consequences.as_ref() {
+            for (key, value) in [
+                ("tx_accession", &consequences.tx_accession),
+                ("hgvs_t", &consequences.hgvs_t),
+                ("hgvs_p", &consequences.hgvs_p),
+            ] {
+                if let Some(value) = value {
+                    result.push((key.into(), Some(info_string(value))));
+                }
+            }
+            let names = consequences
+                .consequences
+                .iter()
+                .filter_map(|value| {
+                    crate::pbs::varfish::v1::seqvars::query::Consequence::try_from(*value).ok()
+                })
+                .map(|value| Some(enum_name(value.as_str_name(), "CONSEQUENCE_")))
+                .collect::<Vec<_>>();
+            if !names.is_empty() {
+                result.push((
+                    "consequences".into(),
+                    Some(Value::Array(Array::String(names))),
+                ));
+            }
+        }
+    }
+
+    let variant = annotation.and_then(|annotation| annotation.variant.as_ref());
+    if let Some(frequency) = variant.and_then(|variant| variant.frequency.as_ref()) {
+        for ((prefix, _), nuclear) in NUCLEAR_FREQUENCIES.iter().zip([
+            &frequency.gnomad_exomes,
+            &frequency.gnomad_genomes,
+            &frequency.inhouse,
+        ]) {
+            if let Some(nuclear) = nuclear {
+                result.extend([
+                    (format!("{}_an", prefix), Some(Value::Integer(nuclear.an))),
+                    (format!("{}_het", prefix), Some(Value::Integer(nuclear.het))),
+                    (
+                        format!("{}_hom", prefix),
+                        Some(Value::Integer(nuclear.homalt)),
+                    ),
+                    (
+                        format!("{}_hemi", prefix),
+                        Some(Value::Integer(nuclear.hemialt)),
+                    ),
+                    (format!("{}_af", prefix), Some(Value::Float(nuclear.af))),
+                ]);
+            }
+        }
+        for ((prefix, _), mitochondrial) in MITOCHONDRIAL_FREQUENCIES
+            .iter()
+            .zip([&frequency.gnomad_mtdna, &frequency.helixmtdb])
+        {
+            if let Some(mitochondrial) = mitochondrial {
+                result.extend([
+                    (
+                        format!("{}_an", prefix),
+                        Some(Value::Integer(mitochondrial.an)),
+                    ),
+                    (
+                        format!("{}_het", prefix),
+                        Some(Value::Integer(mitochondrial.het)),
+                    ),
+                    (
+                        format!("{}_hom", prefix),
+                        Some(Value::Integer(mitochondrial.homalt)),
+                    ),
+                    (
+                        format!("{}_af", prefix),
+                        Some(Value::Float(mitochondrial.af)),
+                    ),
+                ]);
+            }
+        }
+        if !frequency.inhouse_carrier_uuids.is_empty() {
+            result.push((
+                "inhouse_carrier_uuids".into(),
+                Some(Value::Array(Array::String(
+                    frequency
+                        .inhouse_carrier_uuids
+                        .iter()
+                        .cloned()
+                        .map(Some)
+                        .collect(),
+                ))),
+            ));
+        }
+    }
+
+    if let Some(clinvar) = variant.and_then(|variant| variant.clinvar.as_ref()) {
+        result.push((
+            "clinvar_vcv".into(),
+            Some(info_string(&clinvar.vcv_accession)),
+        ));
+        if !clinvar.germline_significance_description.is_empty() {
+            result.push((
+                "clinvar_significance".into(),
+                Some(info_string(&clinvar.germline_significance_description)),
+            ));
+        }
+        if !clinvar
+            .effective_germline_significance_description
+            .is_empty()
+        {
+            result.push((
+                "clinvar_effective_significance".into(),
+                Some(info_string(
+                    &clinvar.effective_germline_significance_description,
+                )),
+            ));
+        }
+        if let Ok(review_status) =
+            pbs_output::AggregateGermlineReviewStatus::try_from(clinvar.germline_review_status)
+        {
+            if review_status != pbs_output::AggregateGermlineReviewStatus::Unspecified {
+                result.push((
+                    "clinvar_review_status".into(),
+                    Some(info_string(&enum_name(
+                        review_status.as_str_name(),
+                        "AGGREGATE_GERMLINE_REVIEW_STATUS_",
+                    ))),
+                ));
+            }
+        }
+        result.push((
+            "clinvar_submissions".into(),
+            Some(Value::Integer(clinvar.submission_count as i32)),
+        ));
+        if clinvar.has_conflicting_submissions {
+            result.push(("clinvar_conflicting".into(), Some(Value::Flag)));
+        }
+    }
+
+    if let Some(scores) = variant.and_then(|variant| variant.scores.as_ref()) {
+        for entry in &scores.entries {
+            let value = match entry.value.as_ref().and_then(|value| value.kind.as_ref()) {
+                Some(Kind::NumberValue(value)) => Value::Float(*value as f32),
+                Some(Kind::StringValue(value)) => info_string(value),
+                Some(Kind::BoolValue(true)) => Value::Flag,
+                _ => continue,
+            };
+            result.push((score_info_key(&entry.key), Some(value)));
+        }
+    }
+
+    result.into_iter().collect()
+}
+
+/// Build the `FORMAT` fields of `record` for the samples in `sample_names`.
+fn build_samples(
+    record: &pbs_output::OutputRecord,
+    sample_names: &[String],
+) -> vcf::variant::record_buf::Samples {
+    use vcf::variant::record::samples::keys::key;
+    use vcf::variant::record_buf::samples::sample::value::{Array, Value};
+
+    let call_infos = record
+        .variant_annotation
+        .as_ref()
+        .and_then(|annotation| annotation.call.as_ref())
+        .map(|call| call.call_infos.as_slice())
+        .unwrap_or_default();
+
+    let keys = [
+        key::GENOTYPE,
+        key::READ_DEPTH,
+        key::READ_DEPTHS,
+        key::CONDITIONAL_GENOTYPE_QUALITY,
+        key::PHASE_SET,
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+    let values = sample_names
+        .iter()
+        .map(|name| {
+            let Some(call_info) = call_infos
+                .iter()
+                .find(|call_info| &call_info.sample == name)
+            else {
+                return vec![Some(Value::String("./.".into())), None, None, None, None];
+            };
+            vec![
+                Some(Value::String(
+                    call_info.genotype.clone().unwrap_or_else(|| "./.".into()),
+                )),
+                call_info.dp.map(Value::Integer),
+                call_info
+                    .ad
+                    .map(|ad| Value::Array(Array::Integer(vec![None, Some(ad)]))),
+                call_info.gq.map(|gq| Value::Integer(gq.round() as i32)),
+                call_info.ps.map(Value::Integer),
+            ]
+        })
+        .collect();
+
+    vcf::variant::record_buf::Samples::new(keys, values)
+}
+
+/// Build the VCF record for `record`.
+fn build_record(
+    record: &pbs_output::OutputRecord,
+    sample_names: &[String],
+) -> Result<vcf::variant::RecordBuf, anyhow::Error> {
+    let vcf_variant = record
+        .vcf_variant
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("record {} has no variant", &record.uuid))?;
+
+    let dbsnp_id = record
+        .variant_annotation
+        .as_ref()
+        .and_then(|annotation| annotation.variant.as_ref())
+        .and_then(|variant| variant.dbids.as_ref())
+        .and_then(|dbids| dbids.dbsnp_id.clone());
+
+    Ok(vcf::variant::RecordBuf::builder()
+        .set_reference_sequence_name(vcf_variant.chrom.clone())
+        .set_variant_start(
+            noodles::core::Position::try_from(vcf_variant.pos as usize).map_err(|e| {
+                anyhow::anyhow!("invalid position {} of record: {}", vcf_variant.pos, e)
+            })?,
+        )
+        .set_ids(dbsnp_id.into_iter().collect())
+        .set_reference_bases(vcf_variant.ref_allele.clone())
+        .set_alternate_bases(vcf::variant::record_buf::AlternateBases::from(vec![
+            vcf_variant.alt_allele.clone(),
+        ]))
+        .set_info(build_info(record))
+        .set_samples(build_samples(record, sample_names))
+        .build())
+}
+
+/// Main entry point for `seqvars export-vcf` sub command.
+pub async fn run(args_common: &common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    tracing::info!("Reading result file {}", &args.path_input);
+    let result_set = crate::diff::results::open_read(&args.path_input)
+        .and_then(read_result_set)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", &args.path_input, e))
+        .classify(ErrorClass::Input)?;
+    let sample_names = sample_names(&result_set.records);
+    let score_infos = score_infos(&result_set);
+    let header =
+        build_header(&result_set, &sample_names, &score_infos).classify(ErrorClass::Input)?;
+
+    let out_path_helper = crate::common::s3::OutputPathHelper::new(&args.path_output)?;
+    let mut writer = open_vcf_writer(out_path_helper.path_out()).await?;
+    writer
+        .write_header(&header)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not write header: {}", e))?;
+    for record in &result_set.records {
+        let record = build_record(record, &sample_names).classify(ErrorClass::Input)?;
+        writer
+            .write_variant_record(&header, &record)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not write record: {}", e))?;
+    }
+    flush_and_shutdown!(writer);
+    out_path_helper.create_tbi_for_bgzf().await?;
+    out_path_helper.upload_for_s3().await?;
+
+    tracing::info!(
+        "Wrote {} records to {}",
+        result_set.records.len(),
+        &args.path_output
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    /// A result file with a header, one record, and a trailer.
+    const RESULT_SET: &str = r#"{"genomeRelease":"GENOME_RELEASE_GRCH37","versions":[{"name":"varfish-server-worker","version":"0.16.0"}],"caseUuid":"00000000-0000-0000-0000-000000000000","settingsFingerprint":"abc123","variantScoreColumns":[{"name":"cadd_phred","label":"CADD","description":"CADD PHRED score","type":"VARIANT_SCORE_COLUMN_TYPE_NUMBER"}]}
+{"uuid":"11111111-1111-1111-1111-111111111111","caseUuid":"00000000-0000-0000-0000-000000000000","vcfVariant":{"genomeRelease":"GENOME_RELEASE_GRCH37","chrom":"17","chromNo":17,"pos":41244000,"refAllele":"T","altAllele":"C"},"variantAnnotation":{"gene":{"identity":{"hgncId":"HGNC:1100","geneSymbol":"BRCA1"},"consequences":{"hgvsT":"c.3548A>G","hgvsP":"p.K1183R","consequences":["CONSEQUENCE_MISSENSE_VARIANT"]}},"variant":{"dbids":{"dbsnpId":"rs16942"},"frequency":{"gnomadExomes":{"an":251000,"het":100,"homalt":20,"af":0.001}},"clinvar":{"vcvAccession":"VCV000041818","germlineSignificanceDescription":"Benign","germlineReviewStatus":"AGGREGATE_GERMLINE_REVIEW_STATUS_REVIEWED_BY_EXPERT_PANEL","submissionCount":12},"scores":{"entries":[{"key":"cadd_phred","value":1.5}]}},"call":{"callInfos":[{"sample":"index","genotype":"0/1","dp":30,"ad":14,"gq":99.0},{"sample":"father","genotype":"0/0","dp":25,"ad":0,"gq":60.0}]}}}
+{"geneCache":[{"hgncId":"HGNC:1100","symbol":"BRCA1"}]}
+"#;
+
+    fn write_vcf() -> Result<String, anyhow::Error> {
+        let result_set = super::read_result_set(Box::new(std::io::Cursor::new(RESULT_SET)))?;
+        let sample_names = super::sample_names(&result_set.records);
+        let score_infos = super::score_infos(&result_set);
+        let header = super::build_header(&result_set, &sample_names, &score_infos)?;
+
+        let mut writer = noodles::vcf::io::Writer::new(Vec::new());
+        writer.write_header(&header)?;
+        for record in &result_set.records {
+            writer.write_variant_record(&header, &super::build_record(record, &sample_names)?)?;
+        }
+        Ok(String::from_utf8(writer.into_inner())?)
+    }
+
+    #[test]
+    fn read_result_set() -> Result<(), anyhow::Error> {
+        let result_set = super::read_result_set(Box::new(std::io::Cursor::new(RESULT_SET)))?;
+
+        assert_eq!(result_set.header.settings_fingerprint, "abc123");
+        assert_eq!(result_set.records.len(), 1);
+        assert_eq!(
+            super::sample_names(&result_set.records),
+            vec!["index".to_string(), "father".to_string()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_result_set_no_header() {
+        assert!(super::read_result_set(Box::new(std::io::Cursor::new(""))).is_err());
+    }
+
+    #[test]
+    fn export_header() -> Result<(), anyhow::Error> {
+        let vcf = write_vcf()?;
+
+        assert!(vcf.contains("##x-varfish-case-uuid=00000000-0000-0000-0000-000000000000\n"));
+        assert!(vcf.contains("##x-varfish-version=<ID=varfish-server-worker,Version=0.16.0>\n"));
+        assert!(vcf.contains("##x-varfish-settings-fingerprint=abc123\n"));
+        assert!(vcf.contains(
+            "##INFO=<ID=cadd_phred,Number=1,Type=Float,Description=\"CADD PHRED score\">\n"
+        ));
+        assert!(vcf.contains("##contig=<ID=17,length=81195210"));
+        assert!(vcf.contains("\tFORMAT\tindex\tfather\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_record() -> Result<(), anyhow::Error> {
+        let vcf = write_vcf()?;
+        let record = vcf
+            .lines()
+            .find(|line| !line.starts_with('#'))
+            .expect("one record");
+        let fields = record.split('\t').collect::<Vec<_>>();
+
+        assert_eq!(&fields[..5], &["17", "41244000", "rs16942", "T", "C"]);
+        let info = fields[7].split(';').collect::<Vec<_>>();
+        for expected in [
+            "gene_symbol=BRCA1",
+            "hgvs_p=p.K1183R",
+            "consequences=missense_variant",
+            "gnomad_exomes_an=251000",
+            "gnomad_exomes_hom=20",
+            "clinvar_vcv=VCV000041818",
+            "clinvar_review_status=reviewed_by_expert_panel",
+            "cadd_phred=1.5",
+        ] {
+            assert!(info.contains(&expected), "{} not in {:?}", expected, info);
+        }
+        assert_eq!(fields[8], "GT:DP:AD:GQ:PS");
+        assert!(fields[9].starts_with("0/1:30:.,14:99"), "{}", fields[9]);
+        assert!(fields[10].starts_with("0/0:25:.,0:60"), "{}", fields[10]);
+
+        Ok(())
+    }
+}
@@ -25,7 +25,7 @@ pub enum VariantCaller {
 
 impl VariantCaller {
     /// Return a string with the name of the variant caller for the VCF header string.
-    fn name(&self) -> &'static str {
+    pub fn name(&self) -> &'static str {
         match self {
             VariantCaller::GatkHaplotypeCaller { .. } => "GatkHaplotypeCaller",
             VariantCaller::GatkUnifiedGenotyper { .. } => "GatkUnifiedGenotyper",
@@ -87,41 +87,15 @@ impl VariantCaller {
 }
 
 /// Add contigs for GRCh37.
-fn add_contigs_37(builder: vcf::header::Builder) -> Result<vcf::header::Builder, anyhow::Error> {
+pub(crate) fn add_contigs_37(
+    builder: vcf::header::Builder,
+) -> Result<vcf::header::Builder, anyhow::Error> {
     use vcf::header::record::value::map::Contig;
     use vcf::header::record::value::Map;
 
     let mut builder = builder;
 
-    let specs: &[(&str, usize); 25] = &[
-        ("1", 249250621),
-        ("2", 243199373),
-        ("3", 198022430),
-        ("4", 191154276),
-        ("5", 180915260),
-        ("6", 171115067),
-        ("7", 159138663),
-        ("8", 146364022),
-        ("9", 141213431),
-        ("10", 135534747),
-        ("11", 135006516),
-        ("12", 133851895),
-        ("13", 115169878),
-        ("14", 107349540),
-        ("15", 102531392),
-        ("16", 90354753),
-        ("17", 81195210),
-        ("18", 78077248),
-        ("19", 59128983),
-        ("20", 63025520),
-        ("21", 48129895),
-        ("22", 51304566),
-        ("X", 155270560),
-        ("Y", 59373566),
-        ("MT", 16569),
-    ];
-
-    for (contig, length) in specs {
+    for (contig, length) in GenomeRelease::Grch37.contig_lengths() {
         builder = builder.add_contig(
             *contig,
             Map::<Contig>::builder()
@@ -146,41 +120,15 @@ fn add_contigs_37(builder: vcf::header::Builder) -> Result<vcf::header::Builder,
 }
 
 /// Add contigs for GRCh38.
-fn add_contigs_38(builder: vcf::header::Builder) -> Result<vcf::header::Builder, anyhow::Error> {
+pub(crate) fn add_contigs_38(
+    builder: vcf::header::Builder,
+) -> Result<vcf::header::Builder, anyhow::Error> {
     use vcf::header::record::value::map::Contig;
     use vcf::header::record::value::Map;
 
     let mut builder = builder;
 
-    let specs: &[(&str, usize); 25] = &[
-        ("chr1", 248956422),
-        ("chr2", 242193529),
-        ("chr3", 198295559),
-        ("chr4", 190214555),
-        ("chr5", 181538259),
-        ("chr6", 170805979),
-        ("chr7", 159345973),
-        ("chr8", 145138636),
-        ("chr9", 138394717),
-        ("chr10", 133797422),
-        ("chr11", 135086622),
-        ("chr12", 133275309),
-        ("chr13", 114364328),
-        ("chr14", 107043718),
-        ("chr15", 101991189),
-        ("chr16", 90338345),
-        ("chr17", 83257441),
-        ("chr18", 80373285),
-        ("chr19", 58617616),
-        ("chr20", 64444167),
-        ("chr21", 46709983),
-        ("chr22", 50818468),
-        ("chrX", 156040895),
-        ("chrY", 57227415),
-        ("chrM", 16569),
-    ];
-
-    for (contig, length) in specs {
+    for (contig, length) in GenomeRelease::Grch38.contig_lengths() {
         builder = builder.add_contig(
             *contig,
             Map::<Contig>::builder()
@@ -373,6 +321,13 @@ pub fn build_output_header(
     }
     .map_err(|e| anyhow::anyhow!("problem adding contigs: {}", e))?;
 
+    // Carry over the `FILTER` definitions from the input so the values can be preserved.
+    for (id, filter) in input_header.filters() {
+        if id != "PASS" {
+            builder = builder.add_filter(id.clone(), filter.clone());
+        }
+    }
+
     if let Some(pedigree) = pedigree {
         let ped_idv = pedigree
             .individuals
@@ -409,6 +364,13 @@ pub fn build_output_header(
             )
         }
 
+        let multi_family = pedigree
+            .individuals
+            .values()
+            .map(|i| &i.family)
+            .collect::<HashSet<_>>()
+            .len()
+            > 1;
         let mut sample_names = Vec::new();
         for src_name in input_header.sample_names() {
             let dst_name = if let Some(id_mapping) = id_mapping {
@@ -440,8 +402,11 @@ pub fn build_output_header(
                 ),
             )?;
 
-            // Add PEDIGREE entry.
+            // Add PEDIGREE entry, with family only for multi-family pedigrees.
             let mut map_builder = Map::<Other>::builder();
+            if multi_family {
+                map_builder = map_builder.insert("Family".parse()?, i.family.clone());
+            }
             if let Some(father) = i.father.as_ref() {
                 map_builder = map_builder.insert("Father".parse()?, father.clone());
             }
@@ -534,6 +499,59 @@ pub fn build_output_header(
     Ok(builder.build())
 }
 
+/// Add the `INFO/callers` field for records merged from several input files.
+pub fn add_callers_info(header: &mut vcf::Header) {
+    use vcf::header::record::value::{
+        map::{info::Number, info::Type, Info},
+        Map,
+    };
+
+    header.infos_mut().insert(
+        super::merge::CALLERS_KEY.to_string(),
+        Map::<Info>::new(
+            Number::Unknown,
+            Type::String,
+            "Callers that called the variant",
+        ),
+    );
+}
+
+/// Keys and descriptions of the `INFO` fields with the in-house counts.
+pub const INHOUSE_INFOS: &[(&str, &str)] = &[
+    ("inhouse_an", "Number of alleles in the in-house database"),
+    (
+        "inhouse_ac",
+        "Number of alternate alleles in the in-house database",
+    ),
+    (
+        "inhouse_hom",
+        "Number of hom. alt. carriers in the in-house database",
+    ),
+    (
+        "inhouse_het",
+        "Number of het. carriers in the in-house database",
+    ),
+    (
+        "inhouse_hemi",
+        "Number of hemi. alt. carriers in the in-house database",
+    ),
+];
+
+/// Add the `INFO` fields for the in-house counts from `--path-inhouse-rocksdb`.
+pub fn add_inhouse_info(header: &mut vcf::Header) {
+    use vcf::header::record::value::{
+        map::{info::Number, info::Type, Info},
+        Map,
+    };
+
+    for (key, description) in INHOUSE_INFOS {
+        header.infos_mut().insert(
+            key.to_string(),
+            Map::<Info>::new(Number::Count(1), Type::Integer, *description),
+        );
+    }
+}
+
 #[cfg(test)]
 mod test {
     use mehari::ped::PedigreeByName;
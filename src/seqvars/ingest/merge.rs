@@ -0,0 +1,271 @@
+//! Merging of the per-allele records from the VCF files of several variant callers.
+//!
+//! The input files must be sorted in the same contig order.  The records are collected by
+//! position and released once all inputs have moved past it, so only the records of the
+//! current positions are kept in memory.
+
+use std::collections::BTreeMap;
+
+use indexmap::IndexMap;
+use noodles::vcf;
+
+/// Name of the `INFO` field with the callers of a merged record.
+pub const CALLERS_KEY: &str = "callers";
+
+/// Position of a record as index of its contig and 1-based position.
+pub type Position = (usize, usize);
+
+/// Trim the common suffix and then the common prefix of `reference` and `alternative`,
+/// keeping at least one base of each.
+///
+/// Returns the adjusted 1-based position and the alleles.  Variants are not left-aligned
+/// as this would require the reference sequence.
+pub fn normalize(pos: usize, reference: &str, alternative: &str) -> (usize, String, String) {
+    let (mut reference, mut alternative) = (reference.as_bytes(), alternative.as_bytes());
+    while reference.len() > 1 && alternative.len() > 1 && reference.last() == alternative.last() {
+        reference = &reference[..reference.len() - 1];
+        alternative = &alternative[..alternative.len() - 1];
+    }
+    let mut pos = pos;
+    while reference.len() > 1 && alternative.len() > 1 && reference[0] == alternative[0] {
+        reference = &reference[1..];
+        alternative = &alternative[1..];
+        pos += 1;
+    }
+    (
+        pos,
+        String::from_utf8_lossy(reference).to_string(),
+        String::from_utf8_lossy(alternative).to_string(),
+    )
+}
+
+/// A merged record with the indices of the input files that called it.
+#[derive(Debug)]
+struct Entry {
+    /// The record from the input file with the smallest index.
+    record: vcf::variant::RecordBuf,
+    /// Index of the input file of `record`.
+    input_idx: usize,
+    /// Indices of the input files with the variant, in order of appearance.
+    callers: Vec<usize>,
+}
+
+/// Merges the single-allele records of several input files.
+#[derive(Debug, Default)]
+pub struct AlleleMerger {
+    /// Whether to normalize the alleles before merging.
+    normalize: bool,
+    /// Index of each contig in the order of first appearance.
+    contig_idx: IndexMap<String, usize>,
+    /// The pending records by position and alleles.
+    entries: BTreeMap<Position, IndexMap<(String, String), Entry>>,
+    /// Number of records merged into an existing record.
+    duplicates: usize,
+}
+
+impl AlleleMerger {
+    /// Construct merger, normalizing alleles with `normalize()` if `normalize` is set.
+    pub fn new(normalize: bool) -> Self {
+        Self {
+            normalize,
+            ..Default::default()
+        }
+    }
+
+    /// Return the position of `record`, registering its contig if seen for the first time.
+    pub fn position(&mut self, record: &vcf::variant::RecordBuf) -> Position {
+        let next_idx = self.contig_idx.len();
+        let contig_idx = *self
+            .contig_idx
+            .entry(record.reference_sequence_name().to_string())
+            .or_insert(next_idx);
+        (
+            contig_idx,
+            record.variant_start().map(usize::from).unwrap_or_default(),
+        )
+    }
+
+    /// Add the single-allele `record` from the input file with index `input_idx`.
+    ///
+    /// The record of the input file with the smallest index is kept for duplicates.
+    pub fn insert(
+        &mut self,
+        input_idx: usize,
+        mut record: vcf::variant::RecordBuf,
+    ) -> Result<(), anyhow::Error> {
+        let alternative = record
+            .alternate_bases()
+            .as_ref()
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("record without alternate allele"))?;
+        if self.normalize {
+            let pos = record
+                .variant_start()
+                .ok_or_else(|| anyhow::anyhow!("missing start position"))?;
+            let (pos, reference, alternative) =
+                normalize(usize::from(pos), record.reference_bases(), &alternative);
+            *record.variant_start_mut() = Some(noodles::core::Position::try_from(pos)?);
+            *record.reference_bases_mut() = reference;
+            *record.alternate_bases_mut() =
+                vcf::variant::record_buf::AlternateBases::from(vec![alternative]);
+        }
+
+        let position = self.position(&record);
+        let alleles = (
+            record.reference_bases().to_string(),
+            record.alternate_bases().as_ref()[0].clone(),
+        );
+        let at_position = self.entries.entry(position).or_default();
+        if let Some(entry) = at_position.get_mut(&alleles) {
+            self.duplicates += 1;
+            if !entry.callers.contains(&input_idx) {
+                entry.callers.push(input_idx);
+            }
+            if input_idx < entry.input_idx {
+                entry.record = record;
+                entry.input_idx = input_idx;
+            }
+        } else {
+            at_position.insert(
+                alleles,
+                Entry {
+                    record,
+                    input_idx,
+                    callers: vec![input_idx],
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Remove and return the merged records before `watermark` or all records if `None`,
+    /// together with the indices of the input files that called them.
+    pub fn pop_before(
+        &mut self,
+        watermark: Option<Position>,
+    ) -> Vec<(vcf::variant::RecordBuf, Vec<usize>)> {
+        let pending = match watermark {
+            Some(watermark) => {
+                let rest = self.entries.split_off(&watermark);
+                std::mem::replace(&mut self.entries, rest)
+            }
+            None => std::mem::take(&mut self.entries),
+        };
+        pending
+            .into_values()
+            .flat_map(|at_position| at_position.into_values())
+            .map(|entry| (entry.record, entry.callers))
+            .collect()
+    }
+
+    /// Return the number of records merged into an existing record.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles::vcf;
+
+    use super::{normalize, AlleleMerger};
+
+    #[rstest::rstest]
+    #[case::snv(100, "A", "G", (100, "A", "G"))]
+    #[case::split_deletion(100, "ATT", "AT", (100, "AT", "A"))]
+    #[case::split_insertion(100, "AT", "ATTT", (100, "A", "ATT"))]
+    #[case::mnv_context(100, "ACG", "ATG", (101, "C", "T"))]
+    #[case::complex(100, "GCAT", "GTTT", (101, "CA", "TT"))]
+    fn normalize_alleles(
+        #[case] pos: usize,
+        #[case] reference: &str,
+        #[case] alternative: &str,
+        #[case] expected: (usize, &str, &str),
+    ) {
+        let (pos, reference, alternative) = normalize(pos, reference, alternative);
+
+        assert_eq!((pos, reference.as_str(), alternative.as_str()), expected);
+    }
+
+    fn record(
+        chrom: &str,
+        pos: usize,
+        reference: &str,
+        alternative: &str,
+    ) -> vcf::variant::RecordBuf {
+        vcf::variant::RecordBuf::builder()
+            .set_reference_sequence_name(chrom)
+            .set_variant_start(noodles::core::Position::try_from(pos).unwrap())
+            .set_reference_bases(reference)
+            .set_alternate_bases(vcf::variant::record_buf::AlternateBases::from(vec![
+                alternative.to_string(),
+            ]))
+            .build()
+    }
+
+    fn summarize(
+        merged: Vec<(vcf::variant::RecordBuf, Vec<usize>)>,
+    ) -> Vec<(String, usize, String, String, Vec<usize>)> {
+        merged
+            .into_iter()
+            .map(|(record, callers)| {
+                (
+                    record.reference_sequence_name().to_string(),
+                    record.variant_start().map(usize::from).unwrap(),
+                    record.reference_bases().to_string(),
+                    record.alternate_bases().as_ref()[0].clone(),
+                    callers,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_callers() -> Result<(), anyhow::Error> {
+        let mut merger = AlleleMerger::new(true);
+
+        merger.insert(0, record("1", 100, "A", "G"))?;
+        merger.insert(0, record("1", 200, "ATT", "AT"))?;
+        merger.insert(1, record("1", 100, "A", "G"))?;
+        merger.insert(1, record("1", 200, "AT", "A"))?;
+        merger.insert(1, record("1", 300, "C", "T"))?;
+        let watermark = merger.position(&record("1", 300, "C", "T"));
+
+        assert_eq!(
+            summarize(merger.pop_before(Some(watermark))),
+            vec![
+                ("1".into(), 100, "A".into(), "G".into(), vec![0, 1]),
+                ("1".into(), 200, "AT".into(), "A".into(), vec![0, 1]),
+            ]
+        );
+        assert_eq!(
+            summarize(merger.pop_before(None)),
+            vec![("1".into(), 300, "C".into(), "T".into(), vec![1])]
+        );
+        assert_eq!(merger.duplicates(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keep_allele_order_without_normalization() -> Result<(), anyhow::Error> {
+        let mut merger = AlleleMerger::new(false);
+
+        merger.insert(0, record("2", 100, "ACG", "ATG"))?;
+        merger.insert(0, record("2", 100, "ACG", "A"))?;
+        merger.insert(0, record("1", 50, "C", "T"))?;
+
+        assert_eq!(
+            summarize(merger.pop_before(None)),
+            vec![
+                ("2".into(), 100, "ACG".into(), "ATG".into(), vec![0]),
+                ("2".into(), 100, "ACG".into(), "A".into(), vec![0]),
+                ("1".into(), 50, "C".into(), "T".into(), vec![0]),
+            ]
+        );
+
+        Ok(())
+    }
+}
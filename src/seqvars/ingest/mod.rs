@@ -3,8 +3,10 @@
 use std::sync::{Arc, OnceLock};
 
 use crate::{
+    common::errors::{ClassifyExt as _, ErrorClass},
     common::{self, genotype_to_string, strip_gt_leading_slash, worker_version, GenomeRelease},
     flush_and_shutdown,
+    seqvars::prefilter::{self, PrefilterWriters},
 };
 use futures::TryStreamExt as _;
 use mehari::common::noodles::{open_vcf_writer, AsyncVcfWriter};
@@ -17,6 +19,8 @@ use thousands::Separable;
 use tokio::io::AsyncWriteExt;
 
 pub mod header;
+pub mod merge;
+pub mod normalize;
 
 /// Command line arguments for `seqvars ingest` subcommand.
 #[derive(Debug, clap::Parser)]
@@ -38,9 +42,10 @@ pub struct Args {
     /// Path to the pedigree file.
     #[clap(long)]
     pub path_ped: String,
-    /// Path to input file.
-    #[clap(long)]
-    pub path_in: String,
+    /// Path to input files, e.g., of several variant callers; variants called in more
+    /// than one file are merged.
+    #[clap(long, required = true)]
+    pub path_in: Vec<String>,
     /// Path to output file.
     #[clap(long)]
     pub path_out: String,
@@ -51,6 +56,144 @@ pub struct Args {
     /// Per-file identifier mapping, either a JSON or @-prefixed path to JSON.
     #[clap(long)]
     pub id_mapping: Option<String>,
+    /// Skip records that do not have `PASS` (or no value) in the `FILTER` column.
+    #[clap(long, default_value_t = false)]
+    pub only_pass: bool,
+    /// Recompute missing or no-call genotypes from `FORMAT/PL` (or `FORMAT/GL`) and
+    /// mark them with `FORMAT/GTPL`.
+    #[clap(long, default_value_t = false)]
+    pub gt_from_pl: bool,
+    /// Minimal Phred-scaled likelihood gap between the most and second most likely
+    /// genotype for `--gt-from-pl`.
+    #[clap(long, default_value_t = 20)]
+    pub gt_from_pl_min_gap: i32,
+    /// Prefilter parameters or @ with path to JSONL file as for `seqvars prefilter`;
+    /// the prefiltered files are written along with the output file.
+    #[clap(long)]
+    pub prefilter_json: Vec<String>,
+    /// How to handle records with symbolic structural ALT alleles such as `<DEL>` or
+    /// breakends; `<*>` and `<NON_REF>` are always removed.
+    #[clap(long, value_enum, default_value_t = SymbolicAllelePolicy::Skip)]
+    pub symbolic_alleles: SymbolicAllelePolicy,
+    /// Path to the VCF file for records with symbolic structural ALT alleles, required
+    /// for `--symbolic-alleles strucvars`.
+    #[clap(long)]
+    pub path_out_symbolic: Option<String>,
+    /// Path to the in-house RocksDB folder written by `seqvars aggregate`; if given, the
+    /// in-house counts are written to the `INFO/inhouse_*` fields.
+    #[clap(long)]
+    pub path_inhouse_rocksdb: Option<String>,
+    /// Only warn about `##contig` lengths of the input files that do not match
+    /// `--genomebuild` instead of failing.
+    #[clap(long, default_value_t = false)]
+    pub lenient: bool,
+    /// Treat the input files as gVCF files, e.g., from DRAGEN; reference blocks are
+    /// skipped and ALT alleles that are not called in any sample are dropped.
+    #[clap(long, default_value_t = false)]
+    pub gvcf: bool,
+    /// Normalize the split alleles by trimming their common suffix and prefix, also for
+    /// a single input file.
+    #[clap(long, default_value_t = false)]
+    pub normalize: bool,
+    /// Path to the indexed reference FASTA file for left-aligning indels with
+    /// `--normalize`.
+    #[clap(long, requires = "normalize")]
+    pub path_reference: Option<String>,
+}
+
+/// Handling of records with symbolic structural ALT alleles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SymbolicAllelePolicy {
+    /// Skip the alleles and count them.
+    #[default]
+    Skip,
+    /// Write the records to `--path-out-symbolic` as candidates for `strucvars ingest`.
+    Strucvars,
+    /// Fail on the first such record.
+    Fail,
+}
+
+/// Kind of an ALT allele.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlleleKind {
+    /// Sequence allele, including the spanning deletion `*`.
+    Sequence,
+    /// Placeholder for any unobserved allele in gVCF files, `<*>` or `<NON_REF>`.
+    Placeholder,
+    /// Symbolic structural allele such as `<DEL>`, `<DUP:TANDEM>`, or a breakend.
+    Structural,
+}
+
+impl AlleleKind {
+    /// Determine the kind of the ALT `allele`.
+    fn of(allele: &str) -> Self {
+        if allele == "<*>" || allele == "<NON_REF>" {
+            AlleleKind::Placeholder
+        } else if (allele.starts_with('<') && allele.ends_with('>'))
+            || allele.contains('[')
+            || allele.contains(']')
+            || (allele.len() > 1 && (allele.starts_with('.') || allele.ends_with('.')))
+        {
+            AlleleKind::Structural
+        } else {
+            AlleleKind::Sequence
+        }
+    }
+}
+
+/// `FORMAT` key that marks genotypes recomputed from the likelihoods.
+pub const GT_FROM_PL_KEY: &str = "GTPL";
+
+/// Counts of records skipped because of their `FILTER` value.
+#[derive(Debug, Default)]
+struct FilterSkipCounts {
+    /// Total number of skipped records.
+    pub total: usize,
+    /// Number of skipped records by filter value.
+    pub by_filter: indexmap::IndexMap<String, usize>,
+}
+
+/// Counts of removed symbolic ALT alleles.
+#[derive(Debug, Default)]
+struct SymbolicAlleleCounts {
+    /// Number of removed `<*>` and `<NON_REF>` alleles.
+    pub placeholder: usize,
+    /// Number of skipped or routed structural alleles by allele.
+    pub by_allele: indexmap::IndexMap<String, usize>,
+}
+
+/// Counts of records and alleles skipped with `--gvcf`.
+#[derive(Debug, Default)]
+struct GvcfCounts {
+    /// Number of skipped reference blocks.
+    pub reference_blocks: usize,
+    /// Number of skipped ALT alleles that are not called in any sample.
+    pub uncalled_alleles: usize,
+}
+
+/// Return whether `record` is a gVCF reference block, i.e., has no ALT alleles other
+/// than `<*>` or `<NON_REF>`.
+fn is_reference_block(record: &vcf::variant::RecordBuf) -> bool {
+    record
+        .alternate_bases()
+        .as_ref()
+        .iter()
+        .all(|allele| AlleleKind::of(allele) == AlleleKind::Placeholder)
+}
+
+/// Return whether the ALT allele `allele_no` (1-based) is called in any sample of
+/// `record`.
+fn is_allele_called(record: &vcf::variant::RecordBuf, allele_no: usize) -> bool {
+    let allele_no = allele_no.to_string();
+    record.samples().values().any(|sample| {
+        match sample.get(noodles::vcf::variant::record::samples::keys::key::GENOTYPE) {
+            Some(Some(vcf::variant::record_buf::samples::sample::value::Value::Genotype(gt))) => {
+                genotype_to_string(gt)
+                    .is_ok_and(|gt| gt.split(['/', '|']).any(|allele| allele == allele_no))
+            }
+            _ => false,
+        }
+    })
 }
 
 /// Return path component fo rth egiven assembly.
@@ -226,16 +369,64 @@ fn transform_format_value(
     }
 }
 
+/// Return the Phred-scaled genotype likelihoods of `sample` from `FORMAT/PL` or, if
+/// missing, from `FORMAT/GL`.
+fn phred_likelihoods(sample: &vcf::variant::record_buf::samples::Sample<'_>) -> Option<Vec<f64>> {
+    use vcf::variant::record_buf::samples::sample::value::{Array, Value};
+
+    match sample.get("PL") {
+        Some(Some(Value::Array(Array::Integer(pls)))) => pls
+            .iter()
+            .map(|pl| pl.map(f64::from))
+            .collect::<Option<Vec<_>>>(),
+        _ => match sample.get("GL") {
+            Some(Some(Value::Array(Array::Float(gls)))) => gls
+                .iter()
+                .map(|gl| gl.map(|gl| -10.0 * f64::from(gl)))
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        },
+    }
+}
+
+/// Return the most likely diploid genotype of `sample` for the allele `allele_no` out of
+/// `allele_count` alternate alleles.
+///
+/// The genotype is only returned if its likelihood is better than the second best by at
+/// least `min_gap` (Phred-scaled).  Genotypes with other alternate alleles are ignored.
+fn genotype_from_likelihoods(
+    sample: &vcf::variant::record_buf::samples::Sample<'_>,
+    allele_no: usize,
+    allele_count: usize,
+    min_gap: i32,
+) -> Option<&'static str> {
+    let likelihoods = phred_likelihoods(sample)?;
+    // The VCF order of the genotype j/k with j <= k is k * (k + 1) / 2 + j.
+    if likelihoods.len() != (allele_count + 1) * (allele_count + 2) / 2 {
+        return None;
+    }
+    let offset = allele_no * (allele_no + 1) / 2;
+    let mut candidates = [
+        ("0/0", likelihoods[0]),
+        ("0/1", likelihoods[offset]),
+        ("1/1", likelihoods[offset + allele_no]),
+    ];
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    (candidates[1].1 - candidates[0].1 >= f64::from(min_gap)).then_some(candidates[0].0)
+}
+
 /// Copy the `FORMAT/GQ` fields for all samples.
 ///
 /// The implementation assumes that there are no duplicates in the output keys when mapped
-/// from input keys.
+/// from input keys.  If `gt_from_pl_min_gap` is given, missing and no-call genotypes are
+/// recomputed from the likelihoods and marked in `FORMAT/GTPL`.
 fn copy_format(
     record_buf: &vcf::variant::RecordBuf,
     builder: vcf::variant::record_buf::builder::Builder,
     idx_output_to_input: &[usize],
     allele_no: usize,
     known_format_keys: &KnownFormatKeys,
+    gt_from_pl_min_gap: Option<i32>,
 ) -> Result<vcf::variant::record_buf::builder::Builder, anyhow::Error> {
     let keys_from_input_known = record_buf
         .samples()
@@ -245,12 +436,12 @@ fn copy_format(
         .filter(|k| known_format_keys.known_keys.contains(*k))
         .cloned()
         .collect::<Vec<_>>();
-    let output_keys = keys_from_input_known
+    let mut output_keys = keys_from_input_known
         .iter()
         .map(|k| known_format_keys.known_to_output(k).clone())
         .collect::<Vec<_>>();
 
-    let values = idx_output_to_input
+    let mut values = idx_output_to_input
         .iter()
         .copied()
         .map(|input_idx| {
@@ -276,19 +467,84 @@ fn copy_format(
         })
         .collect::<Vec<_>>();
 
+    if let Some(min_gap) = gt_from_pl_min_gap {
+        use vcf::variant::record::samples::keys::key;
+        use vcf::variant::record_buf::samples::sample::value::Value;
+
+        let gt_idx = if let Some(gt_idx) = output_keys.iter().position(|k| k == key::GENOTYPE) {
+            gt_idx
+        } else {
+            output_keys.push(key::GENOTYPE.to_string());
+            values.iter_mut().for_each(|values| values.push(None));
+            output_keys.len() - 1
+        };
+        output_keys.push(GT_FROM_PL_KEY.to_string());
+        let allele_count = record_buf.alternate_bases().as_ref().len();
+        for (values, input_idx) in values.iter_mut().zip(idx_output_to_input.iter().copied()) {
+            let is_no_call = match &values[gt_idx] {
+                Some(Value::String(gt)) => ["./.", ".|.", "."].contains(&gt.as_str()),
+                Some(_) => false,
+                None => true,
+            };
+            let refined_gt = if is_no_call {
+                let sample = record_buf
+                    .samples()
+                    .get_index(input_idx)
+                    .expect("input_idx must be valid here");
+                genotype_from_likelihoods(&sample, allele_no, allele_count, min_gap)
+            } else {
+                None
+            };
+            if let Some(refined_gt) = refined_gt {
+                values[gt_idx] = Some(Value::String(refined_gt.to_string()));
+            }
+            values.push(Some(Value::Integer(i32::from(refined_gt.is_some()))));
+        }
+    }
+
     let genotypes =
         vcf::variant::record_buf::samples::Samples::new(output_keys.into_iter().collect(), values);
 
     Ok(builder.set_samples(genotypes))
 }
 
-/// Process the variants from `input_reader` to `output_writer`.
+/// Write the in-house `frequencies` to the `INFO` fields of `record`.
+fn write_inhouse_info(
+    frequencies: &crate::seqvars::query::schema::data::InHouseFrequencies,
+    record: &mut vcf::variant::RecordBuf,
+) {
+    use crate::seqvars::query::schema::data::Ac as _;
+
+    let values = [
+        frequencies.an,
+        frequencies.ac(),
+        frequencies.hom,
+        frequencies.het,
+        frequencies.hemi,
+    ];
+    for ((key, _), value) in header::INHOUSE_INFOS.iter().zip(values) {
+        record.info_mut().insert(
+            key.to_string(),
+            Some(vcf::variant::record_buf::info::field::Value::Integer(value)),
+        );
+    }
+}
+
+/// Process the variants from `input_readers` to `output_writer`.
+///
+/// The variants of several input files are merged with `merge::AlleleMerger` and the
+/// callers of each variant are written to `INFO/callers`.
+#[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
 async fn process_variants(
     output_writer: &mut AsyncVcfWriter,
-    input_reader: &mut VariantReader,
+    mut prefilter_writers: Option<&mut PrefilterWriters>,
+    mut symbolic_writer: Option<&mut AsyncVcfWriter>,
+    input_readers: &mut [VariantReader],
     output_header: &vcf::Header,
-    input_header: &vcf::Header,
-    id_mapping: &Option<indexmap::IndexMap<String, String>>,
+    input_headers: &[vcf::Header],
+    id_mappings: &[Option<indexmap::IndexMap<String, String>>],
+    caller_names: &[String],
     args: &Args,
 ) -> Result<(), anyhow::Error> {
     // Open the frequency RocksDB database in read only mode.
@@ -321,6 +577,17 @@ async fn process_variants(
         rocksdb::DB::open_cf_for_read_only(&options, &rocksdb_path, ["meta", "clinvar"], false)?;
     let clinvar_anno = mehari::annotate::seqvars::ClinvarAnnotator::new(db_clinvar);
 
+    // Open the in-house RocksDB database, if any.
+    let inhouse_db = args
+        .path_inhouse_rocksdb
+        .as_ref()
+        .map(|path| {
+            tracing::info!("Opening in-house database");
+            crate::seqvars::query::inhouse::Dbs::with_path(path, &format!("{}", args.genomebuild))
+        })
+        .transpose()
+        .classify(ErrorClass::Db)?;
+
     // Open the serialized transcripts.
     tracing::info!("Opening transcript database");
     let tx_db = mehari::annotate::seqvars::load_tx_db(format!(
@@ -342,60 +609,90 @@ async fn process_variants(
     );
     tracing::info!("... done building transcript interval trees");
 
-    // Build mapping from output sample index to input sample index.
-    let idx_output_to_input = {
-        let output_sample_to_idx = output_header
-            .sample_names()
-            .iter()
-            .enumerate()
-            .map(|(idx, name)| (name, idx))
-            .collect::<std::collections::HashMap<_, _>>();
-        let mut res = vec![usize::MAX; output_header.sample_names().len()];
-        for (input_idx, sample) in input_header.sample_names().iter().enumerate() {
-            let sample = if let Some(id_mapping) = id_mapping {
-                id_mapping.get(sample).expect("checked earlier")
-            } else {
-                sample
-            };
-            res[output_sample_to_idx[sample]] = input_idx;
-        }
-        res
-    };
+    // Build mapping from output sample index to input sample index for each input file.
+    let idx_output_to_input = input_headers
+        .iter()
+        .zip(id_mappings.iter())
+        .map(|(input_header, id_mapping)| {
+            let output_sample_to_idx = output_header
+                .sample_names()
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| (name, idx))
+                .collect::<std::collections::HashMap<_, _>>();
+            if input_header.sample_names().len() != output_sample_to_idx.len() {
+                anyhow::bail!(
+                    "input files have different numbers of samples: {} != {}",
+                    input_header.sample_names().len(),
+                    output_sample_to_idx.len()
+                );
+            }
+            let mut res = vec![usize::MAX; output_header.sample_names().len()];
+            for (input_idx, sample) in input_header.sample_names().iter().enumerate() {
+                let sample = if let Some(id_mapping) = id_mapping {
+                    id_mapping.get(sample).expect("checked earlier")
+                } else {
+                    sample
+                };
+                let output_idx = output_sample_to_idx.get(sample).ok_or_else(|| {
+                    anyhow::anyhow!("sample {} not found in first input file", sample)
+                })?;
+                res[*output_idx] = input_idx;
+            }
+            Ok(res)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .classify(ErrorClass::Input)?;
 
-    // Read through input file, construct output records, and annotate these.
+    // Read through input files, construct single-allele records, merge these, and annotate
+    // the merged records.  For each input, the next record is kept and the records are
+    // consumed in order of position from all inputs.
     let start = std::time::Instant::now();
     let mut prev = std::time::Instant::now();
     let mut total_written = 0usize;
+    let mut skip_counts = FilterSkipCounts::default();
+    let mut symbolic_counts = SymbolicAlleleCounts::default();
+    let mut gvcf_counts = GvcfCounts::default();
     let known_format_keys = KNOWN_FORMAT_KEYS.get_or_init(Default::default);
-    let mut records = input_reader.records(input_header).await;
-    while let Some(input_record) = records.try_next().await? {
-        for (allele_no, alt_allele) in input_record.alternate_bases().as_ref().iter().enumerate() {
-            let allele_no = allele_no + 1;
-            // Construct record with first few fields describing one variant allele.
-            let builder = noodles::vcf::variant::RecordBuf::builder()
-                .set_reference_sequence_name(input_record.reference_sequence_name())
-                .set_variant_start(
-                    input_record
-                        .variant_start()
-                        .ok_or_else(|| anyhow::anyhow!("missing start position"))?,
-                )
-                .set_reference_bases(input_record.reference_bases())
-                .set_alternate_bases(vcf::variant::record_buf::AlternateBases::from(vec![
-                    alt_allele.clone(),
-                ]));
-
-            // Copy over the well-known FORMAT fields and construct output record.
-            let builder = copy_format(
-                &input_record,
-                builder,
-                &idx_output_to_input,
-                allele_no,
-                known_format_keys,
-            )?;
-
-            // Build the output `RecordBuf`.
-            let mut output_record = builder.build();
-
+    let mut merger = merge::AlleleMerger::new(input_readers.len() > 1);
+    let mut normalizer = args
+        .normalize
+        .then(|| normalize::Normalizer::new(args.path_reference.as_deref()))
+        .transpose()
+        .classify(ErrorClass::Config)?;
+    // Left-aligned records may move before the current input position, so records are
+    // only released once the inputs have moved past the maximal shift.
+    let release_lag = if normalizer
+        .as_ref()
+        .is_some_and(|normalizer| normalizer.left_aligns())
+    {
+        normalize::MAX_SHIFT
+    } else {
+        0
+    };
+    let mut inputs = Vec::new();
+    for (input_reader, input_header) in input_readers.iter_mut().zip(input_headers.iter()) {
+        inputs.push(input_reader.records(input_header).await);
+    }
+    let mut next_records = Vec::new();
+    for records in inputs.iter_mut() {
+        next_records.push(records.try_next().await?);
+    }
+    'records: loop {
+        let next = next_records
+            .iter()
+            .enumerate()
+            .filter_map(|(input_idx, record)| {
+                record
+                    .as_ref()
+                    .map(|record| (merger.position(record), input_idx))
+            })
+            .min();
+
+        // Annotate and write out the records before the next input record.
+        let watermark =
+            next.map(|((contig_idx, pos), _)| (contig_idx, pos.saturating_sub(release_lag)));
+        for (mut output_record, callers) in merger.pop_before(watermark) {
             // Obtain annonars variant key from current allele for RocksDB lookup.
             let vcf_var = annonars::common::keys::Var::from_vcf_allele(&output_record, 0);
 
@@ -432,6 +729,13 @@ async fn process_variants(
                 clinvar_anno.annotate_record_clinvar(&key, &mut output_record)?;
             }
 
+            // Annotate with in-house counts, if any.
+            if let Some(inhouse_db) = inhouse_db.as_ref() {
+                if let Some(frequencies) = inhouse_db.frequencies(&vcf_var)? {
+                    write_inhouse_info(&frequencies, &mut output_record);
+                }
+            }
+
             let annonars::common::keys::Var {
                 chrom,
                 pos,
@@ -460,31 +764,249 @@ async fn process_variants(
                 }
             }
 
+            // Record the callers of the variant if more than one file is merged.
+            if caller_names.len() > 1 {
+                output_record.info_mut().insert(
+                    merge::CALLERS_KEY.to_string(),
+                    Some(vcf::variant::record_buf::info::field::Value::Array(
+                        vcf::variant::record_buf::info::field::value::Array::String(
+                            callers
+                                .iter()
+                                .map(|input_idx| Some(caller_names[*input_idx].clone()))
+                                .collect(),
+                        ),
+                    )),
+                );
+            }
+
             // Write out the record.
             output_writer
                 .write_variant_record(output_header, &output_record)
                 .await?;
+            if let Some(prefilter_writers) = prefilter_writers.as_deref_mut() {
+                prefilter_writers.write(&output_record).await?;
+            }
             total_written += 1;
+
+            if let Some(max_var_count) = args.max_var_count {
+                if total_written >= max_var_count {
+                    tracing::warn!(
+                        "Stopping after {} records as requested by --max-var-count",
+                        total_written
+                    );
+                    break 'records;
+                }
+            }
         }
-        if let Some(max_var_count) = args.max_var_count {
-            if total_written >= max_var_count {
-                tracing::warn!(
-                    "Stopping after {} records as requested by --max-var-count",
-                    total_written
-                );
-                break;
+
+        // Consume the next input record, stopping after all inputs are exhausted.
+        let Some((_, input_idx)) = next else {
+            break;
+        };
+        let input_record = next_records[input_idx]
+            .take()
+            .expect("next record must exist");
+        next_records[input_idx] = inputs[input_idx].try_next().await?;
+        let input_header = &input_headers[input_idx];
+
+        // Skip gVCF reference blocks before any other processing.
+        if args.gvcf && is_reference_block(&input_record) {
+            gvcf_counts.reference_blocks += 1;
+            continue;
+        }
+
+        // Skip records that did not pass the caller's filters, if requested.
+        if args.only_pass
+            && input_record
+                .filters()
+                .as_ref()
+                .iter()
+                .any(|filter| filter != "PASS")
+        {
+            skip_counts.total += 1;
+            for filter in input_record.filters().as_ref() {
+                *skip_counts.by_filter.entry(filter.clone()).or_default() += 1;
+            }
+            continue;
+        }
+
+        // Handle symbolic structural alleles according to `args.symbolic_alleles`.
+        let structural = input_record
+            .alternate_bases()
+            .as_ref()
+            .iter()
+            .filter(|allele| AlleleKind::of(allele) == AlleleKind::Structural)
+            .collect::<Vec<_>>();
+        if !structural.is_empty() {
+            if args.symbolic_alleles == SymbolicAllelePolicy::Fail {
+                return Err(anyhow::anyhow!(
+                    "symbolic ALT allele {} at {}:{:?}, see --symbolic-alleles",
+                    structural[0],
+                    input_record.reference_sequence_name(),
+                    input_record.variant_start()
+                ))
+                .classify(ErrorClass::Input);
+            }
+            for allele in &structural {
+                *symbolic_counts
+                    .by_allele
+                    .entry(allele.to_string())
+                    .or_default() += 1;
+            }
+            if let Some(symbolic_writer) = symbolic_writer.as_deref_mut() {
+                symbolic_writer
+                    .write_variant_record(input_header, &input_record)
+                    .await?;
             }
         }
+
+        for (allele_no, alt_allele) in input_record.alternate_bases().as_ref().iter().enumerate() {
+            let allele_no = allele_no + 1;
+            match AlleleKind::of(alt_allele) {
+                AlleleKind::Sequence => (),
+                AlleleKind::Placeholder => {
+                    symbolic_counts.placeholder += 1;
+                    continue;
+                }
+                AlleleKind::Structural => continue,
+            }
+            // In gVCF files, alleles that were considered but not called are written out
+            // along with the `<NON_REF>` allele.
+            if args.gvcf && !is_allele_called(&input_record, allele_no) {
+                gvcf_counts.uncalled_alleles += 1;
+                continue;
+            }
+            // Construct record with first few fields describing one variant allele.
+            let builder = noodles::vcf::variant::RecordBuf::builder()
+                .set_reference_sequence_name(input_record.reference_sequence_name())
+                .set_variant_start(
+                    input_record
+                        .variant_start()
+                        .ok_or_else(|| anyhow::anyhow!("missing start position"))?,
+                )
+                .set_reference_bases(input_record.reference_bases())
+                .set_alternate_bases(vcf::variant::record_buf::AlternateBases::from(vec![
+                    alt_allele.clone(),
+                ]))
+                .set_filters(input_record.filters().clone());
+
+            // Copy over the well-known FORMAT fields and construct output record.
+            let builder = copy_format(
+                &input_record,
+                builder,
+                &idx_output_to_input[input_idx],
+                allele_no,
+                known_format_keys,
+                args.gt_from_pl.then_some(args.gt_from_pl_min_gap),
+            )?;
+
+            let mut record = builder.build();
+            if let Some(normalizer) = normalizer.as_mut() {
+                normalizer.normalize(&mut record)?;
+            }
+            merger.insert(input_idx, record)?;
+        }
     }
     tracing::info!(
         "... annotated {} records in {:?}",
         total_written.separate_with_commas(),
         start.elapsed()
     );
+    if total_written == 0 {
+        tracing::info!("no records to write, wrote output with header only");
+    }
+    if args.only_pass {
+        tracing::info!(
+            "skipped {} non-PASS records",
+            skip_counts.total.separate_with_commas()
+        );
+        for (filter, count) in &skip_counts.by_filter {
+            tracing::info!("  {}: {}", filter, count.separate_with_commas());
+        }
+    }
+    if let Some(normalizer) = normalizer.as_ref() {
+        let counts = normalizer.counts();
+        tracing::info!(
+            "trimmed {} and left-aligned {} records",
+            counts.trimmed.separate_with_commas(),
+            counts.left_aligned.separate_with_commas()
+        );
+        if counts.ref_mismatch > 0 || counts.missing_contig > 0 {
+            tracing::warn!(
+                "could not left-align {} records with reference mismatch and {} records on \
+                contigs missing from the reference",
+                counts.ref_mismatch.separate_with_commas(),
+                counts.missing_contig.separate_with_commas()
+            );
+        }
+    }
+    if input_headers.len() > 1 {
+        tracing::info!(
+            "merged {} duplicate records of {} input files",
+            merger.duplicates().separate_with_commas(),
+            input_headers.len()
+        );
+    }
+    tracing::info!(
+        "removed {} <*>/<NON_REF> alleles",
+        symbolic_counts.placeholder.separate_with_commas()
+    );
+    if args.gvcf {
+        tracing::info!(
+            "skipped {} gVCF reference blocks and {} uncalled alleles",
+            gvcf_counts.reference_blocks.separate_with_commas(),
+            gvcf_counts.uncalled_alleles.separate_with_commas()
+        );
+    }
+    if !symbolic_counts.by_allele.is_empty() {
+        tracing::info!(
+            "{} {} symbolic structural alleles",
+            if symbolic_writer.is_some() {
+                "routed"
+            } else {
+                "skipped"
+            },
+            symbolic_counts
+                .by_allele
+                .values()
+                .sum::<usize>()
+                .separate_with_commas()
+        );
+        for (allele, count) in &symbolic_counts.by_allele {
+            tracing::info!("  {}: {}", allele, count.separate_with_commas());
+        }
+    }
 
     Ok(())
 }
 
+/// Return the names of the callers of the input files for `INFO/callers`.
+///
+/// The names are guessed from the headers and suffixed with the 1-based index of the input
+/// file if several files have the same caller.
+fn caller_names(input_headers: &[vcf::Header]) -> Vec<String> {
+    let names = input_headers
+        .iter()
+        .map(|input_header| {
+            header::VariantCaller::guess(input_header)
+                .unwrap_or(header::VariantCaller::Other)
+                .name()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            if names.iter().filter(|other| *other == name).count() > 1 {
+                format!("{}_{}", name, idx + 1)
+            } else {
+                name.clone()
+            }
+        })
+        .collect()
+}
+
 /// Main entry point for `seqvars ingest` sub command.
 pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
     let before_anything = std::time::Instant::now();
@@ -494,22 +1016,37 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     common::trace_rss_now();
 
     tracing::info!("loading pedigree...");
-    let pedigree = mehari::ped::PedigreeByName::from_path(&args.path_ped)
-        .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?;
+    let pedigree = crate::common::ped::load(&args.path_ped)
+        .map_err(|e| {
+            anyhow::Error::new(e).context(format!("problem parsing PED file {}", &args.path_ped))
+        })
+        .classify(ErrorClass::Config)?;
     tracing::info!("pedigre = {:#?}", &pedigree);
 
-    tracing::info!("opening input file...");
-    let mut input_reader = common::noodles::open_vcf_reader(&args.path_in)
-        .await
-        .map_err(|e| anyhow::anyhow!("could not build VCF reader: {}", e))?;
+    tracing::info!("opening input files...");
+    let mut input_readers = Vec::new();
+    for path_in in &args.path_in {
+        input_readers.push(
+            common::noodles::open_vcf_reader(path_in)
+                .await
+                .map_err(|e| anyhow::anyhow!("could not build VCF reader for {}: {}", path_in, e))
+                .classify(ErrorClass::Input)?,
+        );
+    }
+    if args.path_in.len() > 1 && args.symbolic_alleles == SymbolicAllelePolicy::Strucvars {
+        return Err(anyhow::anyhow!(
+            "--symbolic-alleles strucvars requires a single --path-in"
+        ))
+        .classify(ErrorClass::Config);
+    }
 
     tracing::info!("loading file identifier mappings...");
-    let id_mapping = args
+    let id_mappings = args
         .id_mapping
         .as_ref()
         .map(
-            |id_mapping| -> Result<Option<indexmap::IndexMap<_, _>>, anyhow::Error> {
-                let id_mappings = if id_mapping.starts_with('@') {
+            |id_mapping| -> Result<crate::common::id_mapping::FileIdentifierMappings, anyhow::Error> {
+                if id_mapping.starts_with('@') {
                     crate::common::id_mapping::FileIdentifierMappings::load_from_path(
                         id_mapping.trim_start_matches('@'),
                     )
@@ -529,49 +1066,103 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
                                 &e,
                             )
                         })
-                }?;
-
-                if id_mappings.file_names().contains(&args.path_in) {
-                    tracing::debug!("- we have an ID mapping for {}", &args.path_in);
-                    Ok(Some(
-                        id_mappings
-                            .mapping_for_file(&args.path_in)
-                            .cloned()
-                            .expect("checked above"),
-                    ))
-                } else {
-                    tracing::debug!("- we have no ID mapping for {}", &args.path_in);
-                    Ok(None)
                 }
             },
         )
-        .transpose()?
-        .flatten();
+        .transpose()?;
+    let id_mappings = args
+        .path_in
+        .iter()
+        .map(|path_in| {
+            let id_mapping = id_mappings
+                .as_ref()
+                .and_then(|id_mappings| id_mappings.mapping_for_file(path_in).cloned());
+            if id_mapping.is_some() {
+                tracing::debug!("- we have an ID mapping for {}", path_in);
+            } else {
+                tracing::debug!("- we have no ID mapping for {}", path_in);
+            }
+            id_mapping
+        })
+        .collect::<Vec<_>>();
+
+    tracing::info!("loading prefilter params...");
+    let prefilter_params =
+        prefilter::load_params(&args.prefilter_json).classify(ErrorClass::Config)?;
 
     tracing::info!("processing header...");
-    let mut input_header = input_reader
-        .read_header()
-        .await
-        .map_err(|e| anyhow::anyhow!("problem reading VCF header: {}", e))?;
-    let output_header = header::build_output_header(
-        &input_header,
+    let mut input_headers = Vec::new();
+    for (input_reader, path_in) in input_readers.iter_mut().zip(args.path_in.iter()) {
+        input_headers.push(
+            input_reader
+                .read_header()
+                .await
+                .map_err(|e| anyhow::anyhow!("problem reading VCF header of {}: {}", path_in, e))?,
+        );
+    }
+    for (input_header, path_in) in input_headers.iter().zip(args.path_in.iter()) {
+        crate::common::header_check::check_input_contig_lengths(
+            input_header,
+            args.genomebuild,
+            path_in,
+            args.lenient,
+        )?;
+    }
+    let caller_names = caller_names(&input_headers);
+    let mut output_header = header::build_output_header(
+        &input_headers[0],
         &Some(pedigree),
-        &id_mapping,
+        &id_mappings[0],
         args.genomebuild,
         &args.file_date,
         &args.case_uuid,
         worker_version(),
     )
     .map_err(|e| anyhow::anyhow!("problem building output header: {}", e))?;
+    if args.path_in.len() > 1 {
+        header::add_callers_info(&mut output_header);
+    }
+    if args.path_inhouse_rocksdb.is_some() {
+        header::add_inhouse_info(&mut output_header);
+    }
+    if args.gt_from_pl {
+        use vcf::header::record::value::{
+            map::format::{Number, Type},
+            map::Format,
+            Map,
+        };
+        output_header.formats_mut().insert(
+            GT_FROM_PL_KEY.to_string(),
+            Map::<Format>::new(
+                Number::Count(1),
+                Type::Integer,
+                "Whether the genotype was recomputed from the likelihoods (1) or not (0)",
+            ),
+        );
+    }
 
     // Work around glnexus issue with RNC.
-    if let Some(format) = input_header.formats_mut().get_mut("RNC") {
-        *format.number_mut() = vcf::header::record::value::map::format::Number::Count(1);
-        *format.type_mut() = vcf::header::record::value::map::format::Type::String;
+    for input_header in input_headers.iter_mut() {
+        if let Some(format) = input_header.formats_mut().get_mut("RNC") {
+            *format.number_mut() = vcf::header::record::value::map::format::Number::Count(1);
+            *format.type_mut() = vcf::header::record::value::map::format::Type::String;
+        }
     }
 
     // Use output file helper.
     let out_path_helper = crate::common::s3::OutputPathHelper::new(&args.path_out)?;
+    let symbolic_out_path_helper = match (args.symbolic_alleles, args.path_out_symbolic.as_ref()) {
+        (SymbolicAllelePolicy::Strucvars, Some(path)) => {
+            Some(crate::common::s3::OutputPathHelper::new(path)?)
+        }
+        (SymbolicAllelePolicy::Strucvars, None) => {
+            return Err(anyhow::anyhow!(
+                "--symbolic-alleles strucvars requires --path-out-symbolic"
+            ))
+            .classify(ErrorClass::Config);
+        }
+        _ => None,
+    };
 
     {
         let mut output_writer = open_vcf_writer(out_path_helper.path_out()).await?;
@@ -579,22 +1170,50 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
             .write_header(&output_header)
             .await
             .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
+        let mut prefilter_writers = if prefilter_params.is_empty() {
+            None
+        } else {
+            Some(PrefilterWriters::open(prefilter_params, &output_header).await?)
+        };
+        let mut symbolic_writer = if let Some(path) = symbolic_out_path_helper.as_ref() {
+            let mut writer = open_vcf_writer(path.path_out()).await?;
+            writer
+                .write_header(&input_headers[0])
+                .await
+                .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
+            Some(writer)
+        } else {
+            None
+        };
 
         process_variants(
             &mut output_writer,
-            &mut input_reader,
+            prefilter_writers.as_mut(),
+            symbolic_writer.as_mut(),
+            &mut input_readers,
             &output_header,
-            &input_header,
-            &id_mapping,
+            &input_headers,
+            &id_mappings,
+            &caller_names,
             args,
         )
         .await?;
 
         flush_and_shutdown!(output_writer);
+        if let Some(prefilter_writers) = prefilter_writers {
+            prefilter_writers.finish().await?;
+        }
+        if let Some(symbolic_writer) = symbolic_writer {
+            flush_and_shutdown!(symbolic_writer);
+        }
     }
 
     out_path_helper.create_tbi_for_bgzf().await?;
     out_path_helper.upload_for_s3().await?;
+    if let Some(symbolic_out_path_helper) = symbolic_out_path_helper {
+        symbolic_out_path_helper.create_tbi_for_bgzf().await?;
+        symbolic_out_path_helper.upload_for_s3().await?;
+    }
 
     tracing::info!(
         "All of `seqvars ingest` completed in {:?}",
@@ -635,13 +1254,24 @@ mod test {
             path_mehari_db: "tests/seqvars/ingest/db".into(),
             path_ped: path.replace(".vcf", ".ped"),
             genomebuild: GenomeRelease::Grch37,
-            path_in: path.into(),
+            path_in: vec![path.into()],
             path_out: tmpdir
                 .join("out.vcf")
                 .to_str()
                 .expect("invalid path")
                 .into(),
             id_mapping: None,
+            only_pass: false,
+            gt_from_pl: false,
+            gt_from_pl_min_gap: 20,
+            prefilter_json: Vec::new(),
+            symbolic_alleles: super::SymbolicAllelePolicy::Skip,
+            path_out_symbolic: None,
+            path_inhouse_rocksdb: None,
+            lenient: false,
+            gvcf: false,
+            normalize: false,
+            path_reference: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -669,9 +1299,20 @@ mod test {
             path_mehari_db: "tests/seqvars/ingest/db".into(),
             path_ped,
             genomebuild: GenomeRelease::Grch37,
-            path_in,
+            path_in: vec![path_in],
             path_out,
             id_mapping: None,
+            only_pass: false,
+            gt_from_pl: false,
+            gt_from_pl_min_gap: 20,
+            prefilter_json: Vec::new(),
+            symbolic_alleles: super::SymbolicAllelePolicy::Skip,
+            path_out_symbolic: None,
+            path_inhouse_rocksdb: None,
+            lenient: false,
+            gvcf: false,
+            normalize: false,
+            path_reference: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -708,7 +1349,7 @@ mod test {
             path_mehari_db: "tests/seqvars/ingest/db".into(),
             path_ped,
             genomebuild: GenomeRelease::Grch37,
-            path_in: path.into(),
+            path_in: vec![path.into()],
             path_out,
             id_mapping: Some(
                 r#"
@@ -745,6 +1386,17 @@ mod test {
                 "#
                 .to_string(),
             ),
+            only_pass: false,
+            gt_from_pl: false,
+            gt_from_pl_min_gap: 20,
+            prefilter_json: Vec::new(),
+            symbolic_alleles: super::SymbolicAllelePolicy::Skip,
+            path_out_symbolic: None,
+            path_inhouse_rocksdb: None,
+            lenient: false,
+            gvcf: false,
+            normalize: false,
+            path_reference: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -752,4 +1404,121 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest]
+    #[case::pl_hom_ref("PL", vec![0, 30, 300], 1, 1, 20, Some("0/0"))]
+    #[case::pl_het("PL", vec![40, 0, 300], 1, 1, 20, Some("0/1"))]
+    #[case::pl_hom_alt("PL", vec![300, 60, 0], 1, 1, 20, Some("1/1"))]
+    #[case::pl_gap_too_small("PL", vec![10, 0, 300], 1, 1, 20, None)]
+    #[case::pl_second_allele("PL", vec![50, 60, 100, 30, 0, 200], 2, 2, 20, Some("0/1"))]
+    #[case::pl_wrong_length("PL", vec![0, 30], 1, 1, 20, None)]
+    #[case::gl_het("GL", vec![-4, 0, -30], 1, 1, 20, Some("0/1"))]
+    fn genotype_from_likelihoods(
+        #[case] key: &str,
+        #[case] likelihoods: Vec<i32>,
+        #[case] allele_no: usize,
+        #[case] allele_count: usize,
+        #[case] min_gap: i32,
+        #[case] expected: Option<&str>,
+    ) {
+        use noodles::vcf::variant::record_buf::samples::{
+            sample::value::{Array, Value},
+            Keys, Sample,
+        };
+
+        let keys: Keys = [key.to_string()].into_iter().collect();
+        let value = if key == "GL" {
+            Value::Array(Array::Float(
+                likelihoods.iter().map(|&gl| Some(gl as f32)).collect(),
+            ))
+        } else {
+            Value::Array(Array::Integer(likelihoods.into_iter().map(Some).collect()))
+        };
+        let values = [Some(value)];
+        let sample = Sample::new(&keys, &values);
+
+        assert_eq!(
+            super::genotype_from_likelihoods(&sample, allele_no, allele_count, min_gap),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::snv("G", super::AlleleKind::Sequence)]
+    #[case::indel("GTT", super::AlleleKind::Sequence)]
+    #[case::spanning_deletion("*", super::AlleleKind::Sequence)]
+    #[case::gvcf_any("<*>", super::AlleleKind::Placeholder)]
+    #[case::gvcf_non_ref("<NON_REF>", super::AlleleKind::Placeholder)]
+    #[case::del("<DEL>", super::AlleleKind::Structural)]
+    #[case::dup_tandem("<DUP:TANDEM>", super::AlleleKind::Structural)]
+    #[case::breakend("G]17:198982]", super::AlleleKind::Structural)]
+    #[case::single_breakend("G.", super::AlleleKind::Structural)]
+    fn allele_kind(#[case] allele: &str, #[case] expected: super::AlleleKind) {
+        assert_eq!(super::AlleleKind::of(allele), expected);
+    }
+
+    #[test]
+    fn gvcf_records() -> Result<(), anyhow::Error> {
+        use noodles::vcf;
+
+        let path = "tests/seqvars/ingest/example_dragen.gvcf.vcf";
+        let mut vcf_reader = vcf::io::reader::Builder::default().build_from_path(path)?;
+        let header = vcf_reader.read_header()?;
+
+        let mut result = Vec::new();
+        for record in vcf_reader.record_bufs(&header) {
+            let record = record?;
+            let called = (1..=record.alternate_bases().as_ref().len())
+                .filter(|allele_no| super::is_allele_called(&record, *allele_no))
+                .collect::<Vec<_>>();
+            result.push((super::is_reference_block(&record), called));
+        }
+
+        assert_eq!(
+            result,
+            vec![
+                (true, vec![]),
+                (false, vec![1]),
+                (true, vec![]),
+                (false, vec![]),
+                (false, vec![1, 2]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_inhouse_info() {
+        use noodles::vcf::variant::record_buf::info::field::Value;
+
+        let frequencies = crate::seqvars::query::schema::data::InHouseFrequencies {
+            an: 100,
+            hom: 2,
+            het: 5,
+            hemi: 1,
+            strata: None,
+            carrier_uuids: Vec::new(),
+        };
+        let mut record = noodles::vcf::variant::RecordBuf::default();
+        super::write_inhouse_info(&frequencies, &mut record);
+
+        let values = [
+            "inhouse_an",
+            "inhouse_ac",
+            "inhouse_hom",
+            "inhouse_het",
+            "inhouse_hemi",
+        ]
+        .iter()
+        .map(|key| record.info().get(*key).cloned().flatten())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [100, 10, 2, 5, 1]
+                .into_iter()
+                .map(|value| Some(Value::Integer(value)))
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -0,0 +1,247 @@
+//! Normalization of the single-allele records for `--normalize`.
+//!
+//! The alleles are trimmed with `merge::normalize()` and, if a reference FASTA file is
+//! given, indels are left-aligned such that the same variant gets the same position and
+//! alleles regardless of the representation chosen by the variant caller.
+
+use noodles::{core::Position, fasta, vcf};
+
+use crate::seqvars::query::evidence::contig_name_candidates;
+
+use super::merge;
+
+/// Maximal number of bases that an indel is shifted to the left.
+///
+/// Records are released from the merger only after the inputs have moved this far past
+/// them, so the output stays sorted.
+pub const MAX_SHIFT: usize = 1_000;
+
+/// Counts of normalized records.
+#[derive(Debug, Default)]
+pub struct NormalizeCounts {
+    /// Number of records with trimmed alleles.
+    pub trimmed: usize,
+    /// Number of left-aligned indels.
+    pub left_aligned: usize,
+    /// Number of records whose reference allele does not match the reference sequence.
+    pub ref_mismatch: usize,
+    /// Number of records on contigs that are not in the reference.
+    pub missing_contig: usize,
+}
+
+/// Left-align the indel at the 1-based `pos` with the trimmed and anchored alleles
+/// `reference` and `alternative`.
+///
+/// `bases` are the upper case reference bases starting at the 1-based position
+/// `bases_start` and extending at least to the end of `reference`.  Returns `None` if the
+/// record is not an anchored indel or cannot be shifted.
+fn left_align(
+    pos: usize,
+    reference: &str,
+    alternative: &str,
+    bases_start: usize,
+    bases: &[u8],
+) -> Option<(usize, String, String)> {
+    let (reference, alternative) = (
+        reference.to_ascii_uppercase().into_bytes(),
+        alternative.to_ascii_uppercase().into_bytes(),
+    );
+    let is_deletion = reference.len() > alternative.len();
+    let (short, long) = if is_deletion {
+        (&alternative, &reference)
+    } else {
+        (&reference, &alternative)
+    };
+    if short.len() != 1 || long.len() < 2 || short[0] != long[0] {
+        return None;
+    }
+
+    let mut idx = pos.checked_sub(bases_start)?;
+    let mut indel = long[1..].to_vec();
+    while idx > 0 && indel.last() == Some(&bases[idx]) {
+        indel.rotate_right(1);
+        idx -= 1;
+    }
+    if idx + bases_start == pos {
+        return None;
+    }
+
+    let anchor = bases[idx];
+    let long = std::iter::once(anchor)
+        .chain(indel)
+        .map(char::from)
+        .collect::<String>();
+    let short = char::from(anchor).to_string();
+    let (reference, alternative) = if is_deletion {
+        (long, short)
+    } else {
+        (short, long)
+    };
+    Some((idx + bases_start, reference, alternative))
+}
+
+/// Normalizes the alleles of single-allele records.
+pub struct Normalizer {
+    /// Path to the FASTA file, for error messages.
+    path: Option<String>,
+    /// The indexed reader for left-alignment, if any.
+    reader: Option<fasta::io::IndexedReader<fasta::io::BufReader<std::fs::File>>>,
+    /// Counts of normalized records.
+    counts: NormalizeCounts,
+}
+
+impl Normalizer {
+    /// Construct normalizer, left-aligning indels with the indexed FASTA file at `path`
+    /// if given.
+    pub fn new(path: Option<&str>) -> Result<Self, anyhow::Error> {
+        let reader = path
+            .map(|path| {
+                fasta::io::indexed_reader::Builder::default()
+                    .build_from_path(path)
+                    .map_err(|e| anyhow::anyhow!("could not open reference {}: {}", path, e))
+            })
+            .transpose()?;
+        Ok(Self {
+            path: path.map(str::to_string),
+            reader,
+            counts: Default::default(),
+        })
+    }
+
+    /// Return whether indels are left-aligned.
+    pub fn left_aligns(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    /// Return the counts of normalized records.
+    pub fn counts(&self) -> &NormalizeCounts {
+        &self.counts
+    }
+
+    /// Read the upper case reference bases `start..=end` (1-based) of `chrom`.
+    ///
+    /// Returns `None` if the contig is not in the reference or `end` is beyond its end.
+    fn read(
+        &mut self,
+        chrom: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(None);
+        };
+        let Some((contig_name, contig_length)) =
+            contig_name_candidates(chrom).into_iter().find_map(|name| {
+                reader
+                    .index()
+                    .iter()
+                    .find(|record| record.name() == name.as_bytes())
+                    .map(|record| (name, record.length() as usize))
+            })
+        else {
+            return Ok(None);
+        };
+        if end > contig_length {
+            return Ok(None);
+        }
+
+        let region = noodles::core::Region::new(
+            contig_name,
+            Position::try_from(start)?..=Position::try_from(end)?,
+        );
+        let record = reader.query(&region).map_err(|e| {
+            anyhow::anyhow!(
+                "could not query {}: {}",
+                self.path.as_deref().unwrap_or_default(),
+                e
+            )
+        })?;
+        Ok(Some(record.sequence().as_ref().to_ascii_uppercase()))
+    }
+
+    /// Normalize the single-allele `record` in place.
+    pub fn normalize(&mut self, record: &mut vcf::variant::RecordBuf) -> Result<(), anyhow::Error> {
+        let alternative = record
+            .alternate_bases()
+            .as_ref()
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("record without alternate allele"))?;
+        if alternative == "*" {
+            return Ok(());
+        }
+        let input_pos = usize::from(
+            record
+                .variant_start()
+                .ok_or_else(|| anyhow::anyhow!("missing start position"))?,
+        );
+
+        let (mut pos, mut reference, mut alternative) =
+            merge::normalize(input_pos, record.reference_bases(), &alternative);
+        if pos != input_pos || reference.len() != record.reference_bases().len() {
+            self.counts.trimmed += 1;
+        }
+
+        if self.left_aligns() && reference.len() != alternative.len() {
+            let bases_start = pos.saturating_sub(MAX_SHIFT).max(1);
+            let bases_end = pos + reference.len() - 1;
+            match self.read(record.reference_sequence_name(), bases_start, bases_end)? {
+                None => self.counts.missing_contig += 1,
+                Some(bases)
+                    if !bases[pos - bases_start..].eq_ignore_ascii_case(reference.as_bytes()) =>
+                {
+                    self.counts.ref_mismatch += 1
+                }
+                Some(bases) => {
+                    if let Some(aligned) =
+                        left_align(pos, &reference, &alternative, bases_start, &bases)
+                    {
+                        (pos, reference, alternative) = aligned;
+                        self.counts.left_aligned += 1;
+                    }
+                }
+            }
+        }
+
+        *record.variant_start_mut() = Some(Position::try_from(pos)?);
+        *record.reference_bases_mut() = reference;
+        *record.alternate_bases_mut() =
+            vcf::variant::record_buf::AlternateBases::from(vec![alternative]);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::left_align;
+
+    //                          1234567890
+    const BASES: &[u8] = b"GCACACATTT";
+
+    #[rstest::rstest]
+    #[case::deletion_repeat(5, "ACA", "A", Some((1, "GCA", "G")))]
+    #[case::deletion_homopolymer(9, "TT", "T", Some((7, "AT", "A")))]
+    #[case::insertion_repeat(7, "A", "ACA", Some((1, "G", "GCA")))]
+    #[case::insertion_unique(8, "T", "TG", None)]
+    #[case::already_left(1, "GCA", "G", None)]
+    #[case::snv(3, "A", "G", None)]
+    #[case::complex(3, "AC", "GTT", None)]
+    fn left_align_indel(
+        #[case] pos: usize,
+        #[case] reference: &str,
+        #[case] alternative: &str,
+        #[case] expected: Option<(usize, &str, &str)>,
+    ) {
+        let result = left_align(pos, reference, alternative, 1, BASES);
+
+        assert_eq!(
+            result.as_ref().map(|(pos, reference, alternative)| (
+                *pos,
+                reference.as_str(),
+                alternative.as_str()
+            )),
+            expected
+        );
+    }
+}
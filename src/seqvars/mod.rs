@@ -1,4 +1,7 @@
 pub mod aggregate;
+pub mod export_vcf;
 pub mod ingest;
 pub mod prefilter;
+pub mod qc;
 pub mod query;
+pub mod resolve;
@@ -1,5 +1,7 @@
 //! Implementation of `seqvars prefilter` subcommand.
 
+pub mod regions;
+
 use std::io::BufRead;
 
 use futures::TryStreamExt as _;
@@ -13,7 +15,7 @@ use crate::{common, flush_and_shutdown};
 
 /// Arguments for the `seqvars prefilter` subcommand.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
-struct PrefilterParams {
+pub(crate) struct PrefilterParams {
     /// Path to output file.
     pub prefilter_path: String,
     /// Maximal allele population frequency.
@@ -32,10 +34,14 @@ pub struct Args {
     /// Prefilter parameters or @ with path to JSONL file.
     #[clap(long)]
     pub params: Vec<String>,
+    /// Optional BED files, optionally gzip-compressed; only variants overlapping any of
+    /// the regions are kept.
+    #[clap(long)]
+    pub path_regions_bed: Vec<String>,
 }
 
 /// Load prefilter params from strings or files with such strings.
-fn load_params(params: &[String]) -> Result<Vec<PrefilterParams>, anyhow::Error> {
+pub(crate) fn load_params(params: &[String]) -> Result<Vec<PrefilterParams>, anyhow::Error> {
     let mut result = Vec::new();
 
     for param in params {
@@ -139,37 +145,121 @@ fn get_freq_and_distance(
     Ok((f64::max(gnomad_exomes_freq, gnomad_genomes_freq), exon_dist))
 }
 
+/// Writers for the prefiltered output files, one for each set of params.
+pub(crate) struct PrefilterWriters {
+    /// The params of each output file.
+    params: Vec<PrefilterParams>,
+    /// The writers of each output file.
+    writers: Vec<AsyncVcfWriter>,
+    /// The headers of each output file.
+    headers: Vec<vcf::Header>,
+    /// The output path helpers of each output file.
+    out_path_helpers: Vec<crate::common::s3::OutputPathHelper>,
+}
+
+impl PrefilterWriters {
+    /// Open one output file for each of `params_list` and write `header` with the
+    /// params (without the path) to it.
+    pub async fn open(
+        params_list: Vec<PrefilterParams>,
+        header: &vcf::Header,
+    ) -> Result<Self, anyhow::Error> {
+        let mut writers = Vec::new();
+        let mut headers = Vec::new();
+        let mut out_path_helpers = Vec::new();
+        for params in params_list.iter() {
+            let header_params = PrefilterParams {
+                prefilter_path: "<stripped>".into(),
+                ..params.clone()
+            };
+            let mut header = header.clone();
+            header.insert(
+                "x-varfish-prefilter-params"
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("{}", e))?,
+                vcf::header::record::Value::from(
+                    serde_json::to_string(&header_params)
+                        .map_err(|e| anyhow::anyhow!("failed to serialize params: {}", e))?,
+                ),
+            )?;
+
+            out_path_helpers.push(crate::common::s3::OutputPathHelper::new(
+                &params.prefilter_path,
+            )?);
+            let mut writer =
+                open_vcf_writer(out_path_helpers.last().expect("just pushed").path_out()).await?;
+            writer.write_header(&header).await.map_err(|e| {
+                anyhow::anyhow!(
+                    "could not write header to {}: {}",
+                    &params.prefilter_path,
+                    e
+                )
+            })?;
+            writers.push(writer);
+            headers.push(header);
+        }
+
+        Ok(Self {
+            params: params_list,
+            writers,
+            headers,
+            out_path_helpers,
+        })
+    }
+
+    /// Write `record` to each output file whose params it passes.
+    pub async fn write(&mut self, record: &vcf::variant::RecordBuf) -> Result<(), anyhow::Error> {
+        let (frequency, exon_distance) = get_freq_and_distance(record)?;
+        if let Some(exon_distance) = exon_distance {
+            for ((params, writer), header) in self
+                .params
+                .iter()
+                .zip(self.writers.iter_mut())
+                .zip(self.headers.iter())
+            {
+                if frequency <= params.max_freq && exon_distance <= params.max_exon_dist {
+                    writer
+                        .write_variant_record(header, record)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("failed to write record: {}", e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush and close the output files, then create the indices and upload to S3.
+    pub async fn finish(mut self) -> Result<(), anyhow::Error> {
+        for writer in self.writers.drain(..) {
+            flush_and_shutdown!(writer);
+        }
+        for out_path_helper in self.out_path_helpers.drain(..) {
+            out_path_helper.create_tbi_for_bgzf().await?;
+            out_path_helper.upload_for_s3().await?;
+        }
+        Ok(())
+    }
+}
+
 /// Perform the actual prefiltration.
 async fn run_filtration(
     input_reader: &mut mehari::common::noodles::VariantReader,
     input_header: &vcf::Header,
-    output_writers: &mut [AsyncVcfWriter],
-    output_headers: &[vcf::Header],
-    params: &[PrefilterParams],
+    output_writers: &mut PrefilterWriters,
+    regions: Option<&regions::Regions>,
 ) -> Result<(), anyhow::Error> {
     let start = std::time::Instant::now();
     let mut prev = std::time::Instant::now();
     let mut total_written = 0usize;
+    let mut total_outside = 0usize;
 
     let mut records = input_reader.records(input_header).await;
     while let Some(input_record) = records.try_next().await? {
-        let (frequency, exon_distance) = get_freq_and_distance(&input_record)?;
-        if let Some(exon_distance) = exon_distance {
-            for ((writer_params, output_writer), output_header) in params
-                .iter()
-                .zip(output_writers.iter_mut())
-                .zip(output_headers.iter())
-            {
-                if frequency <= writer_params.max_freq
-                    && exon_distance <= writer_params.max_exon_dist
-                {
-                    output_writer
-                        .write_variant_record(output_header, &input_record)
-                        .await
-                        .map_err(|e| anyhow::anyhow!("failed to write record: {}", e))?;
-                }
-            }
+        if regions.is_some_and(|regions| !regions.overlaps_record(&input_record)) {
+            total_outside += 1;
+            continue;
         }
+        output_writers.write(&input_record).await?;
 
         let vcf_var = annonars::common::keys::Var::from_vcf_allele(&input_record, 0);
         if prev.elapsed().as_secs() >= 60 {
@@ -185,6 +275,12 @@ async fn run_filtration(
         total_written.separate_with_commas(),
         start.elapsed()
     );
+    if regions.is_some() {
+        tracing::info!(
+            "skipped {} records outside of the regions",
+            total_outside.separate_with_commas()
+        );
+    }
 
     Ok(())
 }
@@ -197,6 +293,12 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     tracing::info!("loading prefilter params...");
     let params_list = load_params(&args.params)?;
+    let regions = if args.path_regions_bed.is_empty() {
+        None
+    } else {
+        tracing::info!("loading regions...");
+        Some(regions::Regions::load(&args.path_regions_bed)?)
+    };
     tracing::info!("opening input file...");
     let mut reader = common::noodles::open_vcf_reader(&args.path_in)
         .await
@@ -206,68 +308,16 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         .await
         .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
 
-    {
-        tracing::info!("opening output files...");
-        let mut output_writers = Vec::new();
-        let mut out_path_helpers = Vec::new();
-        let output_headers = {
-            let mut output_headers = Vec::new();
-            for params in params_list.iter() {
-                let header_params = PrefilterParams {
-                    prefilter_path: "<stripped>".into(),
-                    ..params.clone()
-                };
-                let mut header = header.clone();
-                header.insert(
-                    "x-varfish-prefilter-params"
-                        .parse()
-                        .map_err(|e| anyhow::anyhow!("{}", e))?,
-                    vcf::header::record::Value::from(
-                        serde_json::to_string(&header_params)
-                            .map_err(|e| anyhow::anyhow!("failed to serialize params: {}", e))?,
-                    ),
-                )?;
-
-                out_path_helpers.push(crate::common::s3::OutputPathHelper::new(
-                    &params.prefilter_path,
-                )?);
-                let mut writer =
-                    open_vcf_writer(out_path_helpers.last().expect("just pushed").path_out())
-                        .await?;
-                writer.write_header(&header).await.map_err(|e| {
-                    anyhow::anyhow!(
-                        "could not write header to {}: {}",
-                        &params.prefilter_path,
-                        e
-                    )
-                })?;
-                output_writers.push(writer);
-                output_headers.push(header);
-            }
-            output_headers
-        };
+    tracing::info!("opening output files...");
+    let mut output_writers = PrefilterWriters::open(params_list, &header).await?;
 
-        common::trace_rss_now();
-
-        tracing::info!("starting filtration...");
-        run_filtration(
-            &mut reader,
-            &header,
-            &mut output_writers,
-            &output_headers,
-            &params_list,
-        )
-        .await?;
-        tracing::info!("... done with filtration");
-
-        for output_writer in output_writers.drain(..) {
-            flush_and_shutdown!(output_writer);
-        }
-        for out_path_helper in out_path_helpers.drain(..) {
-            out_path_helper.create_tbi_for_bgzf().await?;
-            out_path_helper.upload_for_s3().await?;
-        }
-    };
+    common::trace_rss_now();
+
+    tracing::info!("starting filtration...");
+    run_filtration(&mut reader, &header, &mut output_writers, regions.as_ref()).await?;
+    tracing::info!("... done with filtration");
+
+    output_writers.finish().await?;
 
     tracing::info!(
         "All of `seqvars prefilter` completed in {:?}",
@@ -292,6 +342,7 @@ mod test {
                 }}"#,
                 tmpdir.to_path_buf().to_str().unwrap()
             )],
+            path_regions_bed: Vec::new(),
         };
 
         super::run(&crate::common::Args::default(), &args).await?;
@@ -325,6 +376,7 @@ mod test {
         let args = super::Args {
             path_in: "tests/seqvars/prefilter/ingest.vcf".into(),
             params: vec![format!("@{}", params_file.to_str().unwrap())],
+            path_regions_bed: Vec::new(),
         };
 
         super::run(&crate::common::Args::default(), &args).await?;
@@ -367,6 +419,7 @@ mod test {
                     tmpdir.to_path_buf().to_str().unwrap()
                 ),
             ],
+            path_regions_bed: Vec::new(),
         };
 
         super::run(&crate::common::Args::default(), &args).await?;
@@ -393,4 +446,37 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::plain("tests/seqvars/prefilter/regions.bed", false)]
+    #[case::bgzip("tests/seqvars/prefilter/regions.bed.gz", true)]
+    #[tokio::test]
+    async fn regions_bed(
+        #[case] path_regions_bed: &str,
+        #[case] expect_17: bool,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = format!("{}/out-1.vcf", tmpdir.to_path_buf().to_str().unwrap());
+
+        let args = super::Args {
+            path_in: "tests/seqvars/prefilter/ingest.vcf".into(),
+            params: vec![format!(
+                r#"{{"prefilter_path": "{}", "max_freq": 1, "max_exon_dist": 1000}}"#,
+                &path_out
+            )],
+            path_regions_bed: vec![path_regions_bed.into()],
+        };
+
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        let records = std::fs::read_to_string(&path_out)?
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').next().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(records.contains(&"17".to_string()), expect_17);
+        assert!(!records.contains(&"MT".to_string()));
+
+        Ok(())
+    }
 }
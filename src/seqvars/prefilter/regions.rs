@@ -0,0 +1,153 @@
+//! Regions from BED files for region-based prefiltering, e.g., capture kit targets.
+
+use std::io::BufRead as _;
+
+use indexmap::IndexMap;
+use mehari::common::io::std::open_read_maybe_gz;
+use noodles::vcf;
+
+use crate::{common::build_chrom_map, strucvars::query::intervals::IntervalDb};
+
+/// Regions on the canonical chromosomes with interval trees for overlap queries.
+#[derive(Debug)]
+pub struct Regions {
+    /// Mapping from chromosome name to index.
+    chrom_map: IndexMap<String, usize>,
+    /// The regions by chromosome.
+    db: IntervalDb<()>,
+}
+
+impl Regions {
+    /// Load the regions from the BED files at `paths`, optionally gzip-compressed.
+    ///
+    /// `track` and `browser` lines and comments are ignored as are columns after the
+    /// third.  Regions on non-canonical contigs are skipped.
+    pub fn load(paths: &[String]) -> Result<Self, anyhow::Error> {
+        let chrom_map = build_chrom_map();
+        let mut db = IntervalDb::new(paths.join(","));
+        let mut skipped = 0usize;
+        for path in paths {
+            let reader = open_read_maybe_gz(path)
+                .map_err(|e| anyhow::anyhow!("could not open BED file {}: {}", path, e))?;
+            for (lineno, line) in reader.lines().enumerate() {
+                let line =
+                    line.map_err(|e| anyhow::anyhow!("could not read BED file {}: {}", path, e))?;
+                if line.trim().is_empty()
+                    || line.starts_with('#')
+                    || line.starts_with("track")
+                    || line.starts_with("browser")
+                {
+                    continue;
+                }
+                let mut fields = line.split('\t');
+                let (Some(chrom), Some(begin), Some(end)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    anyhow::bail!("{}:{}: expected at least 3 columns", path, lineno + 1);
+                };
+                let parse = |value: &str| {
+                    value.trim().parse::<i32>().map_err(|e| {
+                        anyhow::anyhow!(
+                            "{}:{}: invalid position {:?}: {}",
+                            path,
+                            lineno + 1,
+                            value,
+                            e
+                        )
+                    })
+                };
+                let (begin, end) = (parse(begin)?, parse(end)?);
+                if begin > end {
+                    anyhow::bail!("{}:{}: begin after end", path, lineno + 1);
+                }
+                match chrom_map.get(chrom) {
+                    Some(chrom_idx) => db.insert(*chrom_idx, begin..end, ()),
+                    None => skipped += 1,
+                }
+            }
+        }
+        db.index();
+        if skipped > 0 {
+            tracing::warn!("skipped {} regions on non-canonical contigs", skipped);
+        }
+        tracing::info!("loaded {} regions", db.meta.record_count);
+
+        Ok(Self { chrom_map, db })
+    }
+
+    /// Return whether the 0-based interval `begin..end` on `chrom` overlaps a region.
+    pub fn overlaps(&self, chrom: &str, begin: i32, end: i32) -> bool {
+        self.chrom_map
+            .get(chrom)
+            .is_some_and(|chrom_idx| self.db.any(*chrom_idx, begin..end))
+    }
+
+    /// Return whether the reference allele of `record` overlaps a region.
+    pub fn overlaps_record(&self, record: &vcf::variant::RecordBuf) -> bool {
+        let Some(start) = record.variant_start() else {
+            return false;
+        };
+        let begin = usize::from(start) as i32 - 1;
+        let end = begin + record.reference_bases().len().max(1) as i32;
+        self.overlaps(record.reference_sequence_name(), begin, end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Regions;
+
+    #[rstest::rstest]
+    #[case("17", 41210124, 41210125, true)]
+    #[case("chr17", 41210125, 41210126, false)]
+    #[case("17", 41210299, 41210310, true)]
+    #[case("X", 999, 1000, false)]
+    #[case("X", 1999, 2000, true)]
+    #[case("1", 150, 151, false)]
+    fn load_plain(
+        #[case] chrom: &str,
+        #[case] begin: i32,
+        #[case] end: i32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let regions = Regions::load(&["tests/seqvars/prefilter/regions.bed".into()])?;
+
+        assert_eq!(regions.db.meta.record_count, 3);
+        assert_eq!(regions.overlaps(chrom, begin, end), expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1", 150, 151, true)]
+    #[case("chr1", 98, 99, false)]
+    #[case("17", 41210125, 41210126, true)]
+    #[case("GL000192.1", 10, 11, false)]
+    fn load_bgzip(
+        #[case] chrom: &str,
+        #[case] begin: i32,
+        #[case] end: i32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let regions = Regions::load(&["tests/seqvars/prefilter/regions.bed.gz".into()])?;
+
+        assert_eq!(regions.db.meta.record_count, 2);
+        assert_eq!(regions.overlaps(chrom, begin, end), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_multiple() -> Result<(), anyhow::Error> {
+        let regions = Regions::load(&[
+            "tests/seqvars/prefilter/regions.bed".into(),
+            "tests/seqvars/prefilter/regions.bed.gz".into(),
+        ])?;
+
+        assert_eq!(regions.db.meta.record_count, 5);
+        assert!(regions.overlaps("1", 150, 151));
+        assert!(regions.overlaps("X", 1500, 1501));
+
+        Ok(())
+    }
+}
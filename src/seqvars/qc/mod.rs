@@ -0,0 +1,427 @@
+//! Implementation of `seqvars qc` subcommand.
+//!
+//! Computes per-sample variant statistics of a file written by `seqvars ingest` for the
+//! quality control views, e.g., Ts/Tv ratios, the chrX het. rate for checking the sex,
+//! and the Mendelian error rate of the trios in the pedigree.
+
+use futures::TryStreamExt as _;
+use indexmap::IndexMap;
+use mehari::common::noodles::NoodlesVariantReader as _;
+use noodles::vcf;
+
+use crate::common::{
+    self,
+    errors::{ClassifyExt as _, ErrorClass},
+    sample_genotype, Chrom, GenomeRelease, Genotype, GonosomalRegion,
+};
+
+/// Command line arguments for `seqvars qc` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "compute per-sample statistics of ingested seqvars VCF", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+    /// Path to the input VCF file written by `seqvars ingest`.
+    #[clap(long)]
+    pub path_input: String,
+    /// Optional path to the output JSON file, written to stdout if not given.
+    #[clap(long)]
+    pub path_output: Option<String>,
+}
+
+/// Return `numerator / denominator` or `None` if `denominator` is zero.
+fn ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+/// Variant statistics of one sample.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct SampleStats {
+    /// Sex from the pedigree.
+    pub sex: String,
+    /// Number of SNVs with a het. or hom. alt. call.
+    pub count_snv: u64,
+    /// Number of indels with a het. or hom. alt. call.
+    pub count_indel: u64,
+    /// Number of het. calls.
+    pub count_het: u64,
+    /// Number of hom. alt. calls.
+    pub count_homalt: u64,
+    /// Ratio of het. to hom. alt. calls.
+    pub het_hom_ratio: Option<f64>,
+    /// Number of called transition SNVs.
+    pub count_transitions: u64,
+    /// Number of called transversion SNVs.
+    pub count_transversions: u64,
+    /// Ratio of transitions to transversions.
+    pub ts_tv_ratio: Option<f64>,
+    /// Number of records with a no-call.
+    pub count_no_call: u64,
+    /// Fraction of records with a no-call.
+    pub missingness: Option<f64>,
+    /// Number of het. calls on chrX outside the PARs.
+    pub chrx_count_het: u64,
+    /// Number of hom. alt. calls on chrX outside the PARs.
+    pub chrx_count_homalt: u64,
+    /// Fraction of het. calls among the chrX calls outside the PARs; close to zero for
+    /// males.
+    pub chrx_het_rate: Option<f64>,
+}
+
+/// Mendelian errors of one trio.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct TrioStats {
+    /// Name of the child.
+    pub index: String,
+    /// Name of the father.
+    pub father: String,
+    /// Name of the mother.
+    pub mother: String,
+    /// Number of autosomal records with calls in all members and an alternate allele in
+    /// any of them.
+    pub count_informative: u64,
+    /// Number of informative records with a Mendelian error.
+    pub count_mendelian_error: u64,
+    /// Fraction of informative records with a Mendelian error.
+    pub mendelian_error_rate: Option<f64>,
+}
+
+/// The QC report of an ingested file.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Report {
+    /// The case UUID from the header.
+    pub case_uuid: uuid::Uuid,
+    /// Number of records in the file.
+    pub record_count: u64,
+    /// Statistics by sample, in file order.
+    pub samples: IndexMap<String, SampleStats>,
+    /// Statistics of the trios in the pedigree.
+    pub trios: Vec<TrioStats>,
+}
+
+/// Kind of a single-allele record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VariantKind {
+    /// Transition SNV.
+    Transition,
+    /// Transversion SNV.
+    Transversion,
+    /// Insertion or deletion.
+    Indel,
+    /// Other variants, e.g., MNVs.
+    Other,
+}
+
+impl VariantKind {
+    /// Determine the kind of the variant with the alleles `reference` and `alternative`.
+    fn of(reference: &str, alternative: &str) -> Self {
+        if reference.len() != alternative.len() {
+            return Self::Indel;
+        }
+        match (
+            reference.to_ascii_uppercase().as_str(),
+            alternative.to_ascii_uppercase().as_str(),
+        ) {
+            ("A", "G") | ("G", "A") | ("C", "T") | ("T", "C") => Self::Transition,
+            (reference, alternative) if reference.len() == 1 && reference != alternative => {
+                Self::Transversion
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Return the number of alternate alleles of `genotype`, `None` for no-calls.
+fn alt_dosage(genotype: Genotype) -> Option<u8> {
+    match genotype {
+        Genotype::HomRef => Some(0),
+        Genotype::Het => Some(1),
+        Genotype::HomAlt => Some(2),
+        Genotype::WithNoCall => None,
+    }
+}
+
+/// Return whether the child's diploid dosage `child` cannot be inherited from parents
+/// with dosages `father` and `mother`.
+fn is_mendelian_error(child: u8, father: u8, mother: u8) -> bool {
+    let transmitted = |parent: u8| -> std::ops::RangeInclusive<u8> {
+        match parent {
+            0 => 0..=0,
+            1 => 0..=1,
+            _ => 1..=1,
+        }
+    };
+    let (father, mother) = (transmitted(father), transmitted(mother));
+    !(father.start() + mother.start()..=father.end() + mother.end()).contains(&child)
+}
+
+/// Accumulates the statistics record by record.
+#[derive(Debug)]
+struct Collector {
+    /// The assumed genome build.
+    genomebuild: GenomeRelease,
+    /// The report being built.
+    report: Report,
+    /// Sample indices of the child, father, and mother of each trio.
+    trio_indices: Vec<(usize, usize, usize)>,
+}
+
+impl Collector {
+    /// Construct collector for the samples of `header`.
+    fn new(header: &vcf::Header, genomebuild: GenomeRelease) -> Result<Self, anyhow::Error> {
+        let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(header)?;
+        let sample_names = header.sample_names();
+        let mut samples = IndexMap::new();
+        for name in sample_names {
+            let individual = pedigree
+                .individuals
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("individual {} not found in pedigree", name))?;
+            samples.insert(
+                name.clone(),
+                SampleStats {
+                    sex: format!("{:?}", individual.sex),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut trios = Vec::new();
+        let mut trio_indices = Vec::new();
+        for (index_idx, name) in sample_names.iter().enumerate() {
+            let individual = &pedigree.individuals[name];
+            let parent_idx = |parent: &Option<String>| {
+                parent
+                    .as_ref()
+                    .and_then(|parent| sample_names.get_index_of(parent))
+            };
+            if let (Some(father_idx), Some(mother_idx)) = (
+                parent_idx(&individual.father),
+                parent_idx(&individual.mother),
+            ) {
+                trio_indices.push((index_idx, father_idx, mother_idx));
+                trios.push(TrioStats {
+                    index: name.clone(),
+                    father: sample_names[father_idx].clone(),
+                    mother: sample_names[mother_idx].clone(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(Self {
+            genomebuild,
+            report: Report {
+                case_uuid,
+                samples,
+                trios,
+                ..Default::default()
+            },
+            trio_indices,
+        })
+    }
+
+    /// Add the single-allele `record`.
+    fn add(&mut self, record: &vcf::variant::RecordBuf) -> Result<(), anyhow::Error> {
+        let canonical_chrom = annonars::common::cli::canonicalize(record.reference_sequence_name());
+        let chrom: Chrom = canonical_chrom.as_str().parse()?;
+        let pos: usize = record
+            .variant_start()
+            .ok_or_else(|| anyhow::anyhow!("missing variant start in record {:?}", &record))?
+            .into();
+        let is_chrx = matches!(chrom, Chrom::X)
+            && !GonosomalRegion::from_pos(chrom, pos, self.genomebuild)
+                .is_some_and(|region| region.is_par());
+        let is_autosomal = matches!(chrom, Chrom::Auto) && canonical_chrom != "MT";
+        let alternative = record
+            .alternate_bases()
+            .as_ref()
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("record without alternate allele"))?;
+        let kind = VariantKind::of(record.reference_bases(), alternative);
+
+        self.report.record_count += 1;
+        let genotypes = record
+            .samples()
+            .values()
+            .map(|sample| sample_genotype(&sample))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (stats, genotype) in self.report.samples.values_mut().zip(genotypes.iter()) {
+            match genotype {
+                Genotype::HomRef => continue,
+                Genotype::WithNoCall => {
+                    stats.count_no_call += 1;
+                    continue;
+                }
+                Genotype::Het => {
+                    stats.count_het += 1;
+                    stats.chrx_count_het += is_chrx as u64;
+                }
+                Genotype::HomAlt => {
+                    stats.count_homalt += 1;
+                    stats.chrx_count_homalt += is_chrx as u64;
+                }
+            }
+            match kind {
+                VariantKind::Transition => {
+                    stats.count_snv += 1;
+                    stats.count_transitions += 1;
+                }
+                VariantKind::Transversion => {
+                    stats.count_snv += 1;
+                    stats.count_transversions += 1;
+                }
+                VariantKind::Indel => stats.count_indel += 1,
+                VariantKind::Other => (),
+            }
+        }
+
+        if is_autosomal {
+            for (trio, (index_idx, father_idx, mother_idx)) in
+                self.report.trios.iter_mut().zip(self.trio_indices.iter())
+            {
+                let dosages = (
+                    alt_dosage(genotypes[*index_idx]),
+                    alt_dosage(genotypes[*father_idx]),
+                    alt_dosage(genotypes[*mother_idx]),
+                );
+                if let (Some(child), Some(father), Some(mother)) = dosages {
+                    if child + father + mother > 0 {
+                        trio.count_informative += 1;
+                        if is_mendelian_error(child, father, mother) {
+                            trio.count_mendelian_error += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the fraction and ratio fields and return the report.
+    fn finish(mut self) -> Report {
+        let record_count = self.report.record_count;
+        for stats in self.report.samples.values_mut() {
+            stats.het_hom_ratio = ratio(stats.count_het, stats.count_homalt);
+            stats.ts_tv_ratio = ratio(stats.count_transitions, stats.count_transversions);
+            stats.missingness = ratio(stats.count_no_call, record_count);
+            stats.chrx_het_rate = ratio(
+                stats.chrx_count_het,
+                stats.chrx_count_het + stats.chrx_count_homalt,
+            );
+        }
+        for trio in self.report.trios.iter_mut() {
+            trio.mendelian_error_rate = ratio(trio.count_mendelian_error, trio.count_informative);
+        }
+        self.report
+    }
+}
+
+/// Compute the report of the file at `args.path_input`.
+async fn build_report(args: &Args) -> Result<Report, anyhow::Error> {
+    let mut input_reader = common::noodles::open_vcf_reader(&args.path_input)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {}: {}", &args.path_input, e))?;
+    let input_header = input_reader.read_header().await.map_err(|e| {
+        anyhow::anyhow!("problem reading VCF header of {}: {}", &args.path_input, e)
+    })?;
+
+    let mut collector = Collector::new(&input_header, args.genomebuild)?;
+    let mut records = input_reader.records(&input_header).await;
+    while let Some(record) = records.try_next().await? {
+        collector.add(&record)?;
+    }
+    Ok(collector.finish())
+}
+
+/// Main entry point for `seqvars qc` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("Computing statistics of {} ...", &args.path_input);
+    let before_stats = std::time::Instant::now();
+    let report = build_report(args).await.classify(ErrorClass::Input)?;
+    tracing::info!(
+        "... done computing statistics of {} records in {:?}",
+        report.record_count,
+        before_stats.elapsed()
+    );
+
+    let json = serde_json::to_string_pretty(&report)?;
+    if let Some(path_output) = args.path_output.as_ref() {
+        std::fs::write(path_output, format!("{}\n", json))
+            .map_err(|e| anyhow::anyhow!("could not write report to {}: {}", path_output, e))?;
+    } else {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+
+    #[rstest::rstest]
+    #[case(0, 0, 0, false)]
+    #[case(1, 0, 1, false)]
+    #[case(2, 1, 1, false)]
+    #[case(2, 0, 1, true)]
+    #[case(0, 2, 0, true)]
+    #[case(1, 2, 2, true)]
+    #[case(1, 2, 0, false)]
+    fn is_mendelian_error(
+        #[case] child: u8,
+        #[case] father: u8,
+        #[case] mother: u8,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(super::is_mendelian_error(child, father, mother), expected);
+    }
+
+    #[tokio::test]
+    async fn build_report() -> Result<(), anyhow::Error> {
+        let args = super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_input: "tests/seqvars/qc/trio.vcf".into(),
+            path_output: None,
+        };
+
+        let report = super::build_report(&args).await?;
+
+        assert_eq!(report.record_count, 6);
+        let father = &report.samples["father"];
+        assert_eq!(father.sex, "Male");
+        assert_eq!(
+            (father.count_snv, father.count_indel),
+            (3, 2),
+            "{:?}",
+            father
+        );
+        assert_eq!(father.het_hom_ratio, Some(4.0));
+        assert_eq!(father.ts_tv_ratio, None);
+        assert_eq!(father.chrx_het_rate, Some(1.0));
+        let index = &report.samples["index"];
+        assert_eq!(index.ts_tv_ratio, Some(2.0));
+        assert_eq!(index.count_no_call, 1);
+        assert_eq!(index.missingness, Some(1.0 / 6.0));
+        let mother = &report.samples["mother"];
+        assert_eq!(mother.chrx_het_rate, Some(0.0));
+        assert_eq!(
+            report.trios,
+            vec![super::TrioStats {
+                index: "index".into(),
+                father: "father".into(),
+                mother: "mother".into(),
+                count_informative: 3,
+                count_mendelian_error: 1,
+                mendelian_error_rate: Some(1.0 / 3.0),
+            }]
+        );
+
+        Ok(())
+    }
+}
@@ -1,14 +1,21 @@
 //! Code connecting to annonars RocksDB databases for CADD and dbNSFP.
 
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+};
 
 use crate::{common::GenomeRelease, seqvars::ingest::path_component};
 
 use prost::Message as _;
 
 use super::{
-    hpo::{load_hgnc_to_inheritance_map, HgncToMoiMap},
-    schema::data::VariantRecord,
+    hpo::{load_hgnc_to_inheritance_map_cached, HgncToMoiMap, PhenotypeScores},
+    schema::{
+        data::{VariantRecord, VcfVariant},
+        query::GenomicRegion,
+    },
 };
 
 /// Bundle the types needed for databases.
@@ -123,12 +130,73 @@ impl AnnonarsDbs {
     }
 }
 
+/// Return the annonars key range `[lower, upper)` covering `region`.
+///
+/// Returns `None` for contigs that cannot be represented in the annonars keys.
+pub(crate) fn region_key_range(region: &GenomicRegion) -> Option<(Vec<u8>, Vec<u8>)> {
+    let name = region.chrom.strip_prefix("chr").unwrap_or(&region.chrom);
+    if name.is_empty() || name.len() > 2 {
+        return None;
+    }
+    let (start, stop) = region
+        .range
+        .as_ref()
+        .map(|range| (range.start, range.stop))
+        .unwrap_or((0, i32::MAX - 1));
+    let lower: Vec<u8> = annonars::common::keys::Pos::from(&region.chrom, start).into();
+    let upper: Vec<u8> = annonars::common::keys::Pos::from(&region.chrom, stop + 1).into();
+    Some((lower, upper))
+}
+
+/// Decode annonars variant key, i.e., chromosome, big endian position, and `REF>ALT`.
+fn vcf_variant_from_key(key: &[u8]) -> Result<VcfVariant, anyhow::Error> {
+    if key.len() < 6 {
+        anyhow::bail!("variant key too short: {:?}", key);
+    }
+    let chrom = annonars::common::keys::chrom_key_to_name(&key[0..2]);
+    let pos = i32::from_be_bytes(key[2..6].try_into()?);
+    let (ref_allele, alt_allele) = std::str::from_utf8(&key[6..])
+        .map_err(|e| anyhow::anyhow!("invalid alleles in variant key: {}", e))?
+        .split_once('>')
+        .ok_or_else(|| anyhow::anyhow!("no allele separator in variant key: {:?}", key))?;
+    Ok(VcfVariant {
+        chrom,
+        pos,
+        ref_allele: ref_allele.to_string(),
+        alt_allele: alt_allele.to_string(),
+    })
+}
+
+/// Decode the `raw_value` from the `genes` database for `hgnc_id`, if any.
+fn decode_gene_record(
+    hgnc_id: &str,
+    raw_value: Option<Vec<u8>>,
+) -> Result<Option<annonars::pbs::genes::base::Record>, anyhow::Error> {
+    raw_value
+        .map(|raw_value| {
+            annonars::pbs::genes::base::Record::decode(std::io::Cursor::new(raw_value)).map_err(
+                |e| {
+                    anyhow::anyhow!(
+                        "problem decoding record from genes database for HGNC ID {}: {}",
+                        hgnc_id,
+                        e
+                    )
+                },
+            )
+        })
+        .transpose()
+}
+
 /// Utility for sequence variant annotation with annonars.
 pub struct Annotator {
     /// Annonars database bundles.
     pub annonars_dbs: AnnonarsDbs,
     /// Mapping from HGNC gene ID to modes of inheritance; from `hpo` directory.
     pub hgnc_to_moi: HgncToMoiMap,
+    /// Path to the `hpo` directory.
+    path_hpo: std::path::PathBuf,
+    /// Gene records fetched with `prefetch_genes`, `None` for genes not in the database.
+    prefetched_genes: RwLock<HashMap<String, Option<annonars::pbs::genes::base::Record>>>,
 }
 
 impl Annotator {
@@ -149,7 +217,7 @@ impl Annotator {
             )
         })?;
         let hgnc_to_moi =
-            load_hgnc_to_inheritance_map(&path.as_ref().join("hpo")).map_err(|e| {
+            load_hgnc_to_inheritance_map_cached(&path.as_ref().join("hpo")).map_err(|e| {
                 anyhow::anyhow!(
                     "problem loading HGNC to mode of inheritance map at {}: {}",
                     path.as_ref().join("hpo").display(),
@@ -159,11 +227,142 @@ impl Annotator {
         Ok(Self {
             annonars_dbs,
             hgnc_to_moi,
+            path_hpo: path.as_ref().join("hpo"),
+            prefetched_genes: Default::default(),
+        })
+    }
+
+    /// Compute the phenotype similarity of the genes to the HPO `terms` from the
+    /// `hpo` directory.
+    pub fn phenotype_scores(&self, terms: &[String]) -> Result<PhenotypeScores, anyhow::Error> {
+        PhenotypeScores::load(&self.path_hpo, terms).map_err(|e| {
+            anyhow::anyhow!(
+                "problem loading phenotype annotations at {}: {}",
+                self.path_hpo.display(),
+                e
+            )
         })
     }
 
+    /// Return the names and versions of the annonars databases with version metadata.
+    pub fn versions(&self) -> Vec<(String, String)> {
+        let dbs = &self.annonars_dbs;
+        [
+            (&dbs.cadd_meta.db_name, &dbs.cadd_meta.db_version),
+            (&dbs.dbnsfp_meta.db_name, &dbs.dbnsfp_meta.db_version),
+            (&dbs.dbsnp_meta.db_name, &dbs.dbsnp_meta.db_version),
+        ]
+        .into_iter()
+        .map(|(name, version)| (name.clone(), version.clone()))
+        .collect()
+    }
+
+    /// Warm up the block caches of the position-keyed databases for `regions`.
+    ///
+    /// Iterates over all entries in the regions with asynchronous prefetching so the
+    /// blocks are cached before the first query.  A `readahead_size` of `0` lets RocksDB
+    /// tune the readahead itself.  Returns the number of entries read.
+    ///
+    /// # Errors
+    ///
+    /// If there is a problem iterating the databases.
+    pub fn warm_up(
+        &self,
+        regions: &[GenomicRegion],
+        readahead_size: usize,
+    ) -> Result<usize, anyhow::Error> {
+        let dbs = [
+            ("clinvar", &self.annonars_dbs.clinvar_db, "clinvar"),
+            ("dbsnp", &self.annonars_dbs.dbsnp_db, "dbsnp_data"),
+            ("cadd", &self.annonars_dbs.cadd_db, "tsv_data"),
+            ("dbnsfp", &self.annonars_dbs.dbnsfp_db, "tsv_data"),
+        ];
+
+        let mut count = 0;
+        for region in regions {
+            let Some((lower, upper)) = region_key_range(region) else {
+                tracing::warn!("cannot warm up databases for contig {}", &region.chrom);
+                continue;
+            };
+            for (name, db, cf_name) in dbs.iter() {
+                let cf_data = db
+                    .cf_handle(cf_name)
+                    .ok_or_else(|| anyhow::anyhow!("could not get {} column family", cf_name))?;
+
+                let mut read_opts = rocksdb::ReadOptions::default();
+                read_opts.fill_cache(true);
+                read_opts.set_async_io(true);
+                if readahead_size > 0 {
+                    read_opts.set_readahead_size(readahead_size);
+                }
+                read_opts.set_iterate_lower_bound(lower.clone());
+                read_opts.set_iterate_upper_bound(upper.clone());
+
+                let mut iter = db.raw_iterator_cf_opt(&cf_data, read_opts);
+                iter.seek(&lower);
+                while iter.valid() {
+                    count += 1;
+                    iter.next();
+                }
+                iter.status()
+                    .map_err(|e| anyhow::anyhow!("problem warming up {} database: {}", name, e))?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Fetch the `genes` database records for `hgnc_ids` in one batch and keep them for
+    /// `query_genes`.
+    ///
+    /// Returns the number of genes found in the database.
+    ///
+    /// # Errors
+    ///
+    /// If there is a problem querying the database.
+    pub fn prefetch_genes<'a>(
+        &self,
+        hgnc_ids: impl IntoIterator<Item = &'a String>,
+    ) -> Result<usize, anyhow::Error> {
+        let cf_data = self
+            .annonars_dbs
+            .genes_db
+            .cf_handle("genes")
+            .ok_or_else(|| anyhow::anyhow!("could not get genes column family"))?;
+
+        let hgnc_ids = hgnc_ids.into_iter().collect::<Vec<_>>();
+        let raw_values = self.annonars_dbs.genes_db.multi_get_cf(
+            hgnc_ids
+                .iter()
+                .map(|hgnc_id| (&cf_data, hgnc_id.as_bytes()))
+                .collect::<Vec<_>>(),
+        );
+
+        let mut prefetched_genes = self
+            .prefetched_genes
+            .write()
+            .map_err(|e| anyhow::anyhow!("prefetched genes lock poisoned: {}", e))?;
+        let mut count = 0;
+        for (hgnc_id, raw_value) in hgnc_ids.into_iter().zip(raw_values) {
+            let raw_value = raw_value.map_err(|e| {
+                anyhow::anyhow!(
+                    "problem querying genes database for HGNC ID {}: {}",
+                    hgnc_id,
+                    e
+                )
+            })?;
+            let record = decode_gene_record(hgnc_id, raw_value)?;
+            count += usize::from(record.is_some());
+            prefetched_genes.insert(hgnc_id.clone(), record);
+        }
+
+        Ok(count)
+    }
+
     /// Query `genes` database for a given HGNC ID.
     ///
+    /// Genes fetched with `prefetch_genes` are returned without database lookup.
+    ///
     /// # Errors
     ///
     /// If there is a problem querying the database.
@@ -171,6 +370,15 @@ impl Annotator {
         &self,
         hgnc_id: &str,
     ) -> Result<Option<annonars::pbs::genes::base::Record>, anyhow::Error> {
+        if let Some(record) = self
+            .prefetched_genes
+            .read()
+            .map_err(|e| anyhow::anyhow!("prefetched genes lock poisoned: {}", e))?
+            .get(hgnc_id)
+        {
+            return Ok(record.clone());
+        }
+
         let cf_data = self
             .annonars_dbs
             .genes_db
@@ -189,19 +397,55 @@ impl Annotator {
                 )
             })?;
 
-        raw_value
-            .map(|raw_value| {
-                annonars::pbs::genes::base::Record::decode(std::io::Cursor::new(raw_value)).map_err(
-                    |e| {
-                        anyhow::anyhow!(
-                            "problem decoding record from genes database for HGNC ID {}: {}",
-                            hgnc_id,
-                            e
-                        )
-                    },
-                )
-            })
-            .transpose()
+        decode_gene_record(hgnc_id, raw_value)
+    }
+
+    /// Resolve dbSNP `rs_id` (e.g., `rs12345`) to a variant.
+    ///
+    /// # Errors
+    ///
+    /// If `rs_id` is malformed or there is a problem querying the database.
+    pub fn resolve_rsid(&self, rs_id: &str) -> Result<Option<VcfVariant>, anyhow::Error> {
+        let db = &self.annonars_dbs.dbsnp_db;
+        let cf_by_rsid = db
+            .cf_handle("dbsnp_by_rsid")
+            .ok_or_else(|| anyhow::anyhow!("could not get dbsnp_by_rsid column family"))?;
+
+        let rs_id = rs_id.to_lowercase();
+        let number: i32 = rs_id
+            .strip_prefix("rs")
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("invalid dbSNP accession: {}", rs_id))?;
+        let key = db
+            .get_cf(&cf_by_rsid, number.to_le_bytes())
+            .map_err(|e| anyhow::anyhow!("problem querying dbsnp for {}: {}", rs_id, e))?;
+        key.map(|key| vcf_variant_from_key(&key)).transpose()
+    }
+
+    /// Resolve ClinVar `accession` (e.g., `VCV000012345`) to a variant.
+    ///
+    /// A version suffix such as `.2` is ignored if the versioned accession is unknown.
+    ///
+    /// # Errors
+    ///
+    /// If there is a problem querying the database.
+    pub fn resolve_vcv(&self, accession: &str) -> Result<Option<VcfVariant>, anyhow::Error> {
+        let db = &self.annonars_dbs.clinvar_db;
+        let cf_by_accession = db
+            .cf_handle("clinvar_by_accession")
+            .ok_or_else(|| anyhow::anyhow!("could not get clinvar_by_accession column family"))?;
+
+        let accession = accession.to_uppercase();
+        let unversioned = accession.split_once('.').map(|(prefix, _)| prefix);
+        for accession in std::iter::once(accession.as_str()).chain(unversioned) {
+            let key = db.get_cf(&cf_by_accession, accession).map_err(|e| {
+                anyhow::anyhow!("problem querying clinvar for {}: {}", accession, e)
+            })?;
+            if let Some(key) = key {
+                return vcf_variant_from_key(&key).map(Some);
+            }
+        }
+        Ok(None)
     }
 
     /// Query `clinvar-minimal` database for a given variant.
@@ -311,3 +555,77 @@ impl Annotator {
         Ok(values)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::query::schema::{
+        data::VcfVariant,
+        query::{GenomicRegion, Range},
+    };
+
+    #[rstest::rstest]
+    #[case("1", 12_345, "A", "G")]
+    #[case("X", 1, "AC", "A")]
+    #[case("MT", 16_569, "T", "TA")]
+    fn vcf_variant_from_key(
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+    ) -> Result<(), anyhow::Error> {
+        let key: Vec<u8> =
+            annonars::common::keys::Var::from(chrom, pos, ref_allele, alt_allele).into();
+
+        assert_eq!(
+            super::vcf_variant_from_key(&key)?,
+            VcfVariant {
+                chrom: chrom.to_string(),
+                pos,
+                ref_allele: ref_allele.to_string(),
+                alt_allele: alt_allele.to_string(),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1", Some((100, 200)), true)]
+    #[case("chrX", None, true)]
+    #[case("chrM", Some((1, 16_569)), true)]
+    #[case("GL000192.1", None, false)]
+    fn region_key_range(
+        #[case] chrom: &str,
+        #[case] range: Option<(i32, i32)>,
+        #[case] supported: bool,
+    ) {
+        let region = GenomicRegion {
+            chrom: chrom.to_string(),
+            range: range.map(|(start, stop)| Range { start, stop }),
+        };
+
+        let key_range = super::region_key_range(&region);
+
+        assert_eq!(key_range.is_some(), supported);
+        if let Some((lower, upper)) = key_range {
+            assert!(lower < upper);
+        }
+    }
+
+    #[test]
+    fn warm_up() -> Result<(), anyhow::Error> {
+        let annotator = super::Annotator::with_path(
+            "tests/seqvars/query/db",
+            crate::common::GenomeRelease::Grch37,
+        )?;
+        let regions = vec![GenomicRegion {
+            chrom: "1".to_string(),
+            range: None,
+        }];
+
+        let count = annotator.warm_up(&regions, 0)?;
+        assert_eq!(count, annotator.warm_up(&regions, 4 * 1024 * 1024)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Replacing sample names with role-based pseudonyms in the query output.
+
+use std::io::Write as _;
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+use crate::pbs::varfish::v1::seqvars::query as pbs_query;
+
+use super::schema::query::{CaseQuery, GenotypeChoice};
+
+/// Deterministic mapping from sample name to role-based pseudonym.
+///
+/// The index is named `index` and its parents `father` and `mother`.  With a pedigree,
+/// further samples sharing a parent with the index are named `sibling1`, `sibling2`, ...
+/// and all remaining samples are named `individual1`, `individual2`, ... in the order
+/// of the query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pseudonyms {
+    /// Mapping from sample name to pseudonym.
+    pub mapping: indexmap::IndexMap<String, String>,
+}
+
+impl Pseudonyms {
+    /// Derive the pseudonyms from the samples in `query` and the optional `pedigree`.
+    pub fn new(query: &CaseQuery, pedigree: Option<&mehari::ped::PedigreeByName>) -> Self {
+        let samples = query
+            .genotype
+            .sample_genotypes
+            .keys()
+            .chain(query.quality.sample_qualities.keys())
+            .cloned()
+            .collect::<indexmap::IndexSet<_>>();
+        let with_choice = |choice: GenotypeChoice| {
+            query
+                .genotype
+                .sample_genotypes
+                .values()
+                .find(|sgc| sgc.genotype == choice)
+                .map(|sgc| sgc.sample.clone())
+        };
+        let individual =
+            |sample: &String| pedigree.and_then(|pedigree| pedigree.individuals.get(sample));
+
+        // Determine index from the query, the pedigree, or fall back to first sample.
+        let index = with_choice(GenotypeChoice::RecessiveIndex)
+            .or_else(|| {
+                samples
+                    .iter()
+                    .find(|sample| {
+                        individual(sample).map(|i| i.disease)
+                            == Some(mehari::ped::Disease::Affected)
+                    })
+                    .cloned()
+            })
+            .or_else(|| samples.first().cloned());
+        let index_individual = index.as_ref().and_then(individual);
+        let father = with_choice(GenotypeChoice::RecessiveFather)
+            .or_else(|| index_individual.and_then(|i| i.father.clone()));
+        let mother = with_choice(GenotypeChoice::RecessiveMother)
+            .or_else(|| index_individual.and_then(|i| i.mother.clone()));
+        let is_sibling = |sample: &String| {
+            let (Some(index_individual), Some(individual)) = (index_individual, individual(sample))
+            else {
+                return false;
+            };
+            (individual.father.is_some() && individual.father == index_individual.father)
+                || (individual.mother.is_some() && individual.mother == index_individual.mother)
+        };
+
+        let mut mapping = indexmap::IndexMap::new();
+        let (mut count_siblings, mut count_individuals) = (0, 0);
+        for sample in &samples {
+            let pseudonym = if Some(sample) == index.as_ref() {
+                "index".to_string()
+            } else if Some(sample) == father.as_ref() {
+                "father".to_string()
+            } else if Some(sample) == mother.as_ref() {
+                "mother".to_string()
+            } else if is_sibling(sample) {
+                count_siblings += 1;
+                format!("sibling{}", count_siblings)
+            } else {
+                count_individuals += 1;
+                format!("individual{}", count_individuals)
+            };
+            mapping.insert(sample.clone(), pseudonym);
+        }
+
+        Self { mapping }
+    }
+
+    /// Return pseudonym for `sample`; unknown samples are mapped to `unknown`.
+    pub fn pseudonym(&self, sample: &str) -> String {
+        self.mapping
+            .get(sample)
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// Replace the sample names in the protobuf `query`.
+    pub fn anonymize_query(&self, query: &mut pbs_query::CaseQuery) {
+        if let Some(genotype) = query.genotype.as_mut() {
+            for sgc in genotype.sample_genotypes.iter_mut() {
+                sgc.sample = self.pseudonym(&sgc.sample);
+            }
+        }
+        if let Some(quality) = query.quality.as_mut() {
+            for sqs in quality.sample_qualities.iter_mut() {
+                sqs.sample = self.pseudonym(&sqs.sample);
+            }
+        }
+    }
+
+    /// Replace the sample names in the output `header`.
+    pub fn anonymize_header(&self, header: &mut pbs_output::OutputHeader) {
+        if let Some(query) = header.query.as_mut() {
+            self.anonymize_query(query);
+        }
+    }
+
+    /// Replace the sample names in the output `record`.
+    pub fn anonymize_record(&self, record: &mut pbs_output::OutputRecord) {
+        if let Some(call) = record
+            .variant_annotation
+            .as_mut()
+            .and_then(|annotation| annotation.call.as_mut())
+        {
+            for call_info in call.call_infos.iter_mut() {
+                call_info.sample = self.pseudonym(&call_info.sample);
+            }
+        }
+        for rna_evidence in record.rna_evidence.iter_mut() {
+            rna_evidence.sample = self.pseudonym(&rna_evidence.sample);
+        }
+    }
+
+    /// Write the mapping as a TSV key file to `path`.
+    pub fn write_key<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+        let path = path.as_ref();
+        let mut writer = std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .map_err(|e| anyhow::anyhow!("could not create key file {}: {}", path.display(), e))?;
+        writeln!(writer, "pseudonym\tsample")?;
+        for (sample, pseudonym) in &self.mapping {
+            writeln!(writer, "{}\t{}", pseudonym, sample)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not write key file {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pseudonyms;
+    use crate::seqvars::query::schema::query::{
+        CaseQuery, GenotypeChoice, QuerySettingsGenotype, SampleGenotypeChoice,
+    };
+
+    /// Build a query with the given samples and genotype choices.
+    fn build_query(samples: &[(&str, GenotypeChoice)]) -> CaseQuery {
+        CaseQuery {
+            genotype: QuerySettingsGenotype {
+                sample_genotypes: samples
+                    .iter()
+                    .map(|(sample, genotype)| {
+                        (
+                            sample.to_string(),
+                            SampleGenotypeChoice {
+                                sample: sample.to_string(),
+                                genotype: *genotype,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Build pedigree with index, parents, and sibling plus an unrelated sample.
+    fn build_pedigree() -> mehari::ped::PedigreeByName {
+        use mehari::ped::{Disease, Individual};
+
+        let individuals = [
+            (
+                "NA12878",
+                Some("NA12891"),
+                Some("NA12892"),
+                Disease::Affected,
+            ),
+            ("NA12891", None, None, Disease::Unaffected),
+            ("NA12892", None, None, Disease::Unaffected),
+            (
+                "NA12879",
+                Some("NA12891"),
+                Some("NA12892"),
+                Disease::Unaffected,
+            ),
+            ("NA19240", None, None, Disease::Unknown),
+        ]
+        .into_iter()
+        .map(|(name, father, mother, disease)| {
+            (
+                name.to_string(),
+                Individual {
+                    family: "FAM".to_string(),
+                    name: name.to_string(),
+                    father: father.map(String::from),
+                    mother: mother.map(String::from),
+                    disease,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+        mehari::ped::PedigreeByName { individuals }
+    }
+
+    #[test]
+    fn pseudonyms_from_recessive_roles() {
+        use GenotypeChoice::*;
+
+        let query = build_query(&[
+            ("mother-1", RecessiveMother),
+            ("index-1", RecessiveIndex),
+            ("father-1", RecessiveFather),
+            ("other-1", Any),
+        ]);
+        let pseudonyms = Pseudonyms::new(&query, None);
+
+        assert_eq!(
+            pseudonyms.mapping,
+            indexmap::indexmap! {
+                String::from("mother-1") => String::from("mother"),
+                String::from("index-1") => String::from("index"),
+                String::from("father-1") => String::from("father"),
+                String::from("other-1") => String::from("individual1"),
+            }
+        );
+    }
+
+    #[test]
+    fn pseudonyms_from_pedigree() {
+        use GenotypeChoice::*;
+
+        let query = build_query(&[
+            ("NA19240", Any),
+            ("NA12892", Any),
+            ("NA12879", Any),
+            ("NA12891", Any),
+            ("NA12878", Any),
+        ]);
+        let pseudonyms = Pseudonyms::new(&query, Some(&build_pedigree()));
+
+        assert_eq!(
+            pseudonyms.mapping,
+            indexmap::indexmap! {
+                String::from("NA19240") => String::from("individual1"),
+                String::from("NA12892") => String::from("mother"),
+                String::from("NA12879") => String::from("sibling1"),
+                String::from("NA12891") => String::from("father"),
+                String::from("NA12878") => String::from("index"),
+            }
+        );
+    }
+
+    #[test]
+    fn anonymize_record() {
+        use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+        let query = build_query(&[("NA12878", GenotypeChoice::RecessiveIndex)]);
+        let pseudonyms = Pseudonyms::new(&query, None);
+        let mut record = pbs_output::OutputRecord {
+            variant_annotation: Some(pbs_output::VariantAnnotation {
+                call: Some(pbs_output::CallRelatedAnnotation {
+                    call_infos: vec![
+                        pbs_output::SampleCallInfo {
+                            sample: "NA12878".to_string(),
+                            ..Default::default()
+                        },
+                        pbs_output::SampleCallInfo {
+                            sample: "NA12879".to_string(),
+                            ..Default::default()
+                        },
+                    ],
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        pseudonyms.anonymize_record(&mut record);
+
+        let samples = record.variant_annotation.unwrap().call.unwrap().call_infos;
+        assert_eq!(samples[0].sample, "index");
+        assert_eq!(samples[1].sample, "unknown");
+    }
+}
@@ -0,0 +1,255 @@
+//! Code implementing the "seqvars query-batch" sub command.
+//!
+//! The databases are loaded once and the jobs from the manifest are run sequentially,
+//! each with the settings of `seqvars query`.
+
+use std::time::Instant;
+
+use clap::{command, Parser};
+
+use crate::common::batch;
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::common::GenomeRelease;
+
+use super::{output, Databases, LoadedQuery};
+
+/// Command line arguments for `seqvars query-batch` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Run queries for seqvars of multiple cases", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to worker database to use for querying.
+    #[arg(long)]
+    pub path_db: String,
+    /// Path to inhouse rocksdb folder as created by `seqvars aggregate`.
+    #[arg(long, alias = "path-inhouse-rocksdb")]
+    pub path_inhouse_db: Option<String>,
+    /// Path to the JSONL manifest with one job per line.
+    #[arg(long)]
+    pub manifest: String,
+    /// Path to the JSONL file to write the status of each job to.
+    #[arg(long)]
+    pub path_summary: String,
+    /// The output format / sink to write the results to.
+    #[arg(long, value_enum, default_value_t = output::OutputFormat::Jsonl)]
+    pub output_format: output::OutputFormat,
+    /// Whether to write one record per variant or one record per gene.
+    #[arg(long, value_enum, default_value_t = output::OutputMode::Variant)]
+    pub output_mode: output::OutputMode,
+    /// Maximal number of records per file for `--output-format sharded-jsonl`.
+    #[arg(long, default_value_t = 100_000)]
+    pub shard_size: usize,
+    /// Maximal number of annotated records queued for the writer thread.
+    #[arg(long, default_value_t = 1_000)]
+    pub writer_queue_size: usize,
+    /// Embed the HGNC symbol, name, and aliases of all genes referenced in the
+    /// results in a trailer record after the records.
+    #[arg(long, default_value_t = false)]
+    pub embed_gene_cache: bool,
+    /// Write canonical JSON with sorted keys and floats rounded to 7 significant digits.
+    #[arg(long, default_value_t = false)]
+    pub canonical_json: bool,
+    /// Path to the indexed reference FASTA file to write the context sequence of each
+    /// record from.
+    #[arg(long)]
+    pub path_reference: Option<String>,
+    /// Number of reference bases on each side of the variant for `--path-reference`.
+    #[arg(long, default_value_t = 50)]
+    pub context_sequence_flank: usize,
+    /// Stop at the first failing job instead of continuing with the next one.
+    #[arg(long, default_value_t = false)]
+    pub fail_fast: bool,
+
+    /// Optional maximal number of total records to write out per job.
+    #[arg(long)]
+    pub max_results: Option<usize>,
+    /// Optional seed for RNG.
+    #[arg(long)]
+    pub rng_seed: Option<u64>,
+    /// Maximal distance to TAD to consider (unused, but required when loading database).
+    #[arg(long, default_value_t = 10_000)]
+    pub max_tad_distance: i32,
+}
+
+/// One job from the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Identifier of the job in the summary; defaults to the output path.
+    pub id: Option<String>,
+    /// Path to input VCF file.
+    pub path_input: String,
+    /// Path to query JSON file.
+    pub path_query_json: String,
+    /// Path to the output file.
+    pub path_output: String,
+    /// Optional path to PED file.
+    pub path_ped: Option<String>,
+    /// The case UUID.
+    pub case_uuid: Option<uuid::Uuid>,
+    /// Result set ID.
+    pub result_set_id: Option<String>,
+}
+
+impl Job {
+    /// Return the identifier of the job.
+    pub fn id(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.path_output)
+    }
+
+    /// Build the `seqvars query` arguments for the job with the shared settings of `args`.
+    fn query_args(&self, args: &Args) -> super::Args {
+        super::Args {
+            genome_release: args.genome_release,
+            result_set_id: self.result_set_id.clone(),
+            case_uuid: self.case_uuid,
+            path_db: Some(args.path_db.clone()),
+            path_inhouse_db: args.path_inhouse_db.clone(),
+            no_databases: false,
+            path_query_json: self.path_query_json.clone(),
+            path_gene_panel: Vec::new(),
+            path_ped: self.path_ped.clone(),
+            path_input: self.path_input.clone(),
+            annotation_source: Default::default(),
+            path_output: self.path_output.clone(),
+            output_format: args.output_format,
+            output_mode: args.output_mode,
+            shard_size: args.shard_size,
+            writer_queue_size: args.writer_queue_size,
+            resume: false,
+            auto_compress: false,
+            auto_compress_sample_size: 1_000,
+            auto_compress_io_mb_per_sec: 100.0,
+            embed_gene_cache: args.embed_gene_cache,
+            canonical_json: args.canonical_json,
+            path_alignments: Vec::new(),
+            path_alignments_reference: None,
+            alignment_evidence_max_records: 100,
+            path_prior_classifications: None,
+            path_rna_evidence: None,
+            path_reference: args.path_reference.clone(),
+            context_sequence_flank: args.context_sequence_flank,
+            anonymize: false,
+            path_anonymize_key: None,
+            recessive_score: false,
+            inhouse_carrier_uuids: false,
+            max_results: args.max_results,
+            rng_seed: args.rng_seed,
+            max_tad_distance: args.max_tad_distance,
+            warm_up: false,
+            rocksdb_readahead_size: 0,
+            checkpoint_dir: None,
+            instrument: false,
+            heap_profile: None,
+            estimate_only: false,
+        }
+    }
+}
+
+/// Run one `job` with the shared databases `dbs`.
+async fn run_job(
+    args_common: &crate::common::Args,
+    args: &Args,
+    dbs: &Databases,
+    job: &Job,
+) -> Result<(), anyhow::Error> {
+    let query_args = job.query_args(args);
+    let loaded_query = LoadedQuery::load(&query_args).await?;
+    super::run_with_databases(args_common, &query_args, loaded_query, Some(dbs)).await
+}
+
+/// Main entry point for `seqvars query-batch` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = Instant::now();
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let jobs: Vec<Job> = batch::load_manifest(&args.manifest).classify(ErrorClass::Config)?;
+    tracing::info!("Loaded {} jobs from {}", jobs.len(), &args.manifest);
+    let mut summary = batch::SummaryWriter::create(&args.path_summary)?;
+
+    let dbs = Databases::load(
+        &args.path_db,
+        args.path_inhouse_db.as_deref(),
+        args.genome_release,
+        args.max_tad_distance,
+    )?;
+
+    let mut count_failed = 0;
+    for (no, job) in jobs.iter().enumerate() {
+        tracing::info!("Running job {}/{}: {}", no + 1, jobs.len(), job.id());
+        let before_job = Instant::now();
+        let result = run_job(args_common, args, &dbs, job).await;
+        if let Err(e) = &result {
+            tracing::error!("job {} failed: {:?}", job.id(), e);
+            count_failed += 1;
+        }
+        summary.write(&batch::JobSummary::new(
+            job.id(),
+            &result,
+            before_job.elapsed(),
+        ))?;
+        if args.fail_fast {
+            result?;
+        }
+    }
+
+    tracing::info!(
+        "All of `seqvars query-batch` completed in {:?}",
+        before_anything.elapsed()
+    );
+    if count_failed > 0 {
+        anyhow::bail!("{} of {} jobs failed", count_failed, jobs.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Job;
+
+    #[rstest::rstest]
+    #[case(
+        r#"{"id": "case-1", "path_input": "in.vcf", "path_query_json": "q.json", "path_output": "out.jsonl"}"#,
+        "case-1"
+    )]
+    #[case(
+        r#"{"path_input": "in.vcf", "path_query_json": "q.json", "path_output": "out.jsonl"}"#,
+        "out.jsonl"
+    )]
+    fn job_id(#[case] line: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+        let job: Job = serde_json::from_str(line)?;
+        assert_eq!(job.id(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_args() -> Result<(), anyhow::Error> {
+        let job: Job = serde_json::from_str(
+            r#"{"path_input": "in.vcf", "path_query_json": "q.json", "path_output": "out.jsonl",
+                "path_ped": "case.ped", "result_set_id": "rs-1"}"#,
+        )?;
+        let args = <super::Args as clap::Parser>::try_parse_from([
+            "query-batch",
+            "--genome-release",
+            "grch37",
+            "--path-db",
+            "db",
+            "--manifest",
+            "jobs.jsonl",
+            "--path-summary",
+            "summary.jsonl",
+        ])?;
+
+        let query_args = job.query_args(&args);
+        assert_eq!(query_args.path_db.as_deref(), Some("db"));
+        assert_eq!(query_args.path_input, "in.vcf");
+        assert_eq!(query_args.path_ped.as_deref(), Some("case.ped"));
+        assert_eq!(query_args.result_set_id.as_deref(), Some("rs-1"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//! Aggregation of the output records by gene for `--output-mode gene`.
+//!
+//! One record is written per gene with the passing variants of the gene, the worst
+//! consequence, the best value of each numeric score, and flags on whether the
+//! variants of the index are compatible with recessive inheritance.  Variants without
+//! a gene are collected in one record with empty HGNC ID.
+
+use indexmap::IndexMap;
+
+use crate::common;
+use crate::pbs::varfish::v1::seqvars::{output as pbs_output, query as pbs_query};
+
+/// Flags on recessive inheritance in the index sample.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecessiveFlags {
+    /// Name of the index sample, `None` if there is no recessive index and more than
+    /// one sample.
+    pub index: Option<String>,
+    /// Number of variants homozygous alternative (or hemizygous) in the index.
+    pub hom_alt_count: usize,
+    /// Number of variants heterozygous in the index.
+    pub het_count: usize,
+    /// Whether there is a homozygous variant or there are at least two heterozygous
+    /// variants, i.e., a compound heterozygous candidate, in the index.
+    pub compatible: bool,
+}
+
+/// Output record for one gene.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneRecord {
+    /// HGNC ID of the gene, empty for variants without gene.
+    pub hgnc_id: String,
+    /// Gene symbol.
+    pub gene_symbol: String,
+    /// Most severe consequence of the variants, if any.
+    pub worst_consequence: Option<&'static str>,
+    /// Maximal value of each numeric score over the variants.
+    pub best_scores: IndexMap<String, f64>,
+    /// Maximal phenotype match score over the variants, if any.
+    pub best_phenotype_score: Option<f32>,
+    /// Flags on recessive inheritance in the index.
+    pub recessive: RecessiveFlags,
+    /// Support of the gene for the recessive mode of the query, if scored.
+    pub recessive_support: Option<pbs_output::RecessiveSupport>,
+    /// Inheritance models of the variants, if any.
+    pub inheritance_models: Vec<&'static str>,
+    /// The passing variants of the gene in output order.
+    pub variants: Vec<pbs_output::OutputRecord>,
+}
+
+impl GeneRecord {
+    /// Add `record` to the gene with the genotype of sample `index`, if any.
+    fn add(&mut self, record: &pbs_output::OutputRecord, index: Option<&str>) {
+        use pbjson_types::value::Kind;
+
+        let annotation = record.variant_annotation.as_ref();
+        let gene = annotation.and_then(|annotation| annotation.gene.as_ref());
+
+        if let Some(worst) = gene
+            .and_then(|gene| gene.consequences.as_ref())
+            .and_then(|consequences| {
+                // The consequences are ordered by descending severity.
+                consequences
+                    .consequences
+                    .iter()
+                    .copied()
+                    .filter(|value| *value != pbs_query::Consequence::Unspecified as i32)
+                    .min()
+            })
+            .and_then(|value| pbs_query::Consequence::try_from(value).ok())
+        {
+            let is_worse = self.worst_consequence.map_or(true, |current| {
+                pbs_query::Consequence::from_str_name(current).map_or(true, |c| worst < c)
+            });
+            if is_worse {
+                self.worst_consequence = Some(worst.as_str_name());
+            }
+        }
+
+        let scores = annotation
+            .and_then(|annotation| annotation.variant.as_ref())
+            .and_then(|variant| variant.scores.as_ref())
+            .map(|scores| scores.entries.as_slice())
+            .unwrap_or_default();
+        for entry in scores {
+            if let Some(Kind::NumberValue(value)) =
+                entry.value.as_ref().and_then(|value| value.kind.as_ref())
+            {
+                self.best_scores
+                    .entry(entry.key.clone())
+                    .and_modify(|best| *best = best.max(*value))
+                    .or_insert(*value);
+            }
+        }
+
+        if let Some(score) = gene
+            .and_then(|gene| gene.phenotype_match.as_ref())
+            .map(|phenotype_match| phenotype_match.score)
+        {
+            self.best_phenotype_score = Some(
+                self.best_phenotype_score
+                    .map_or(score, |best| best.max(score)),
+            );
+        }
+
+        if let Some(index) = index {
+            let genotype = annotation
+                .and_then(|annotation| annotation.call.as_ref())
+                .and_then(|call| {
+                    call.call_infos
+                        .iter()
+                        .find(|call_info| call_info.sample == index)
+                })
+                .and_then(|call_info| call_info.genotype.as_deref())
+                .and_then(|gt| gt.parse::<common::ParsedGenotype>().ok())
+                .map(|gt| gt.genotype());
+            match genotype {
+                Some(common::Genotype::HomAlt) => self.recessive.hom_alt_count += 1,
+                Some(common::Genotype::Het) => self.recessive.het_count += 1,
+                _ => (),
+            }
+            self.recessive.compatible =
+                self.recessive.hom_alt_count > 0 || self.recessive.het_count > 1;
+        }
+
+        if self.recessive_support.is_none() {
+            self.recessive_support = gene.and_then(|gene| gene.recessive_support.clone());
+        }
+
+        if let Some(model) = record
+            .inheritance_model
+            .and_then(|value| pbs_query::InheritanceModel::try_from(value).ok())
+            .map(|model| model.as_str_name())
+        {
+            if !self.inheritance_models.contains(&model) {
+                self.inheritance_models.push(model);
+            }
+        }
+
+        self.variants.push(record.clone());
+    }
+}
+
+/// Aggregation of output records into `GeneRecord`s.
+#[derive(Debug, Default)]
+pub struct GeneAggregator {
+    /// Name of the index sample for the recessive flags, if any.
+    index: Option<String>,
+    /// The genes by HGNC ID in the order of their first variant.
+    genes: IndexMap<String, GeneRecord>,
+}
+
+impl GeneAggregator {
+    /// Create a new aggregator using sample `index` for the recessive flags.
+    pub fn new(index: Option<String>) -> Self {
+        Self {
+            index,
+            ..Default::default()
+        }
+    }
+
+    /// Add the output `record` to the record of its gene.
+    pub fn add(&mut self, record: &pbs_output::OutputRecord) {
+        let identity = record
+            .variant_annotation
+            .as_ref()
+            .and_then(|annotation| annotation.gene.as_ref())
+            .and_then(|gene| gene.identity.as_ref());
+        let hgnc_id = identity
+            .map(|identity| identity.hgnc_id.clone())
+            .unwrap_or_default();
+        let index = self.index.as_deref().or_else(|| {
+            // Fall back to the only sample of single-sample cases.
+            match record
+                .variant_annotation
+                .as_ref()
+                .and_then(|annotation| annotation.call.as_ref())
+                .map(|call| call.call_infos.as_slice())
+            {
+                Some([call_info]) => Some(call_info.sample.as_str()),
+                _ => None,
+            }
+        });
+        self.genes
+            .entry(hgnc_id.clone())
+            .or_insert_with(|| GeneRecord {
+                hgnc_id,
+                gene_symbol: identity
+                    .map(|identity| identity.gene_symbol.clone())
+                    .unwrap_or_default(),
+                recessive: RecessiveFlags {
+                    index: index.map(str::to_string),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .add(record, index);
+    }
+
+    /// Return the gene records in the order of their first variant.
+    pub fn into_records(self) -> impl Iterator<Item = GeneRecord> {
+        self.genes.into_values()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GeneAggregator, RecessiveFlags};
+    use crate::pbs::varfish::v1::seqvars::{output as pbs_output, query as pbs_query};
+
+    fn record(
+        hgnc_id: &str,
+        consequences: &[pbs_query::Consequence],
+        cadd: f64,
+        genotype: &str,
+    ) -> pbs_output::OutputRecord {
+        pbs_output::OutputRecord {
+            variant_annotation: Some(pbs_output::VariantAnnotation {
+                gene: Some(pbs_output::GeneRelatedAnnotation {
+                    identity: Some(pbs_output::GeneIdentity {
+                        hgnc_id: hgnc_id.into(),
+                        gene_symbol: format!("SYM{}", hgnc_id),
+                        ..Default::default()
+                    }),
+                    consequences: Some(pbs_output::GeneRelatedConsequences {
+                        consequences: consequences.iter().map(|c| *c as i32).collect(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                variant: Some(pbs_output::VariantRelatedAnnotation {
+                    scores: Some(pbs_output::ScoreAnnotations {
+                        entries: vec![pbs_output::ScoreEntry {
+                            key: "cadd_phred".into(),
+                            value: Some(pbjson_types::Value::from(cadd)),
+                        }],
+                    }),
+                    ..Default::default()
+                }),
+                call: Some(pbs_output::CallRelatedAnnotation {
+                    call_infos: vec![pbs_output::SampleCallInfo {
+                        sample: "index".into(),
+                        genotype: Some(genotype.into()),
+                        ..Default::default()
+                    }],
+                }),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aggregate() {
+        use pbs_query::Consequence::*;
+
+        let mut aggregator = GeneAggregator::new(None);
+        aggregator.add(&record("HGNC:1", &[MissenseVariant], 20.0, "0/1"));
+        aggregator.add(&record("HGNC:2", &[SynonymousVariant], 5.0, "1/1"));
+        aggregator.add(&record(
+            "HGNC:1",
+            &[StopGained, SpliceRegionVariant],
+            30.0,
+            "0|1",
+        ));
+
+        let genes = aggregator.into_records().collect::<Vec<_>>();
+        assert_eq!(genes.len(), 2);
+
+        assert_eq!(genes[0].hgnc_id, "HGNC:1");
+        assert_eq!(genes[0].gene_symbol, "SYMHGNC:1");
+        assert_eq!(genes[0].variants.len(), 2);
+        assert_eq!(genes[0].worst_consequence, Some("CONSEQUENCE_STOP_GAINED"));
+        assert_eq!(genes[0].best_scores["cadd_phred"], 30.0);
+        assert_eq!(
+            genes[0].recessive,
+            RecessiveFlags {
+                index: Some("index".into()),
+                hom_alt_count: 0,
+                het_count: 2,
+                compatible: true,
+            }
+        );
+
+        assert_eq!(genes[1].hgnc_id, "HGNC:2");
+        assert_eq!(
+            genes[1].worst_consequence,
+            Some("CONSEQUENCE_SYNONYMOUS_VARIANT")
+        );
+        assert_eq!(genes[1].recessive.hom_alt_count, 1);
+        assert!(genes[1].recessive.compatible);
+    }
+}
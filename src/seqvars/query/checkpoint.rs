@@ -0,0 +1,163 @@
+//! Checkpoints of the intermediate stages of `seqvars query` for `--checkpoint-dir`.
+//!
+//! Each stage writes its JSONL file to the checkpoint directory and, once the file is
+//! complete, a marker file next to it.  A re-invocation with the same settings skips the
+//! stages with markers.  The settings are identified by a fingerprint stored in the
+//! directory; if it does not match, all markers are removed and the query starts over.
+
+use std::path::{Path, PathBuf};
+
+use mehari::annotate::seqvars::ann::Consequence;
+
+/// Name of the file with the fingerprint of the settings.
+const FINGERPRINT_FILE: &str = "fingerprint.txt";
+
+/// Intermediate stage of the query, in order of execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Stage {
+    /// Passing records in input order.
+    Unsorted,
+    /// Records of the genes passing the gene-wise filters, in order of HGNC ID.
+    ByHgncFiltered,
+    /// Records of `ByHgncFiltered` sorted by coordinate.
+    ByCoord,
+}
+
+/// Statistics of the `Unsorted` stage that are needed to write the output header.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct UnsortedStats {
+    /// Number of records in the input.
+    pub count_total: usize,
+    /// Number of records passing the query.
+    pub count_passed: usize,
+    /// Number of passing records by consequence.
+    pub passed_by_consequences: Vec<(Consequence, usize)>,
+}
+
+/// The checkpoint directory of a query.
+#[derive(Debug)]
+pub struct Checkpoints {
+    /// Path to the directory.
+    dir: PathBuf,
+}
+
+impl Checkpoints {
+    /// Open the checkpoint directory `dir` for the settings with `fingerprint`.
+    ///
+    /// The directory is created if necessary.  Markers are removed if the fingerprint
+    /// does not match and for all stages after the first incomplete one.
+    pub fn open<P: AsRef<Path>>(dir: P, fingerprint: &str) -> Result<Self, anyhow::Error> {
+        use strum::IntoEnumIterator as _;
+
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            anyhow::anyhow!("could not create checkpoint directory {:?}: {}", &dir, e)
+        })?;
+        let result = Self { dir };
+
+        let path_fingerprint = result.dir.join(FINGERPRINT_FILE);
+        let matches = std::fs::read_to_string(&path_fingerprint)
+            .map(|previous| previous.trim() == fingerprint)
+            .unwrap_or(false);
+        if !matches {
+            if path_fingerprint.exists() {
+                tracing::warn!(
+                    "settings changed since checkpoints in {:?} were written, starting over",
+                    &result.dir
+                );
+            }
+            for stage in Stage::iter() {
+                result.clear(stage)?;
+            }
+            std::fs::write(&path_fingerprint, fingerprint)
+                .map_err(|e| anyhow::anyhow!("could not write {:?}: {}", &path_fingerprint, e))?;
+        }
+
+        let mut complete = true;
+        for stage in Stage::iter() {
+            complete = complete && result.is_done(stage);
+            if !complete {
+                result.clear(stage)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Return the path to the JSONL file of `stage`.
+    pub fn path(&self, stage: Stage) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", stage))
+    }
+
+    /// Return the path to the completion marker of `stage`.
+    fn path_marker(&self, stage: Stage) -> PathBuf {
+        self.dir.join(format!("{}.done", stage))
+    }
+
+    /// Return whether `stage` has been completed.
+    pub fn is_done(&self, stage: Stage) -> bool {
+        self.path_marker(stage).exists()
+    }
+
+    /// Mark `stage` as completed, storing `data` in the marker.
+    pub fn mark_done<T: serde::Serialize>(
+        &self,
+        stage: Stage,
+        data: &T,
+    ) -> Result<(), anyhow::Error> {
+        let path = self.path_marker(stage);
+        std::fs::write(&path, serde_json::to_string(data)?)
+            .map_err(|e| anyhow::anyhow!("could not write marker {:?}: {}", &path, e))
+    }
+
+    /// Load the data stored in the marker of the completed `stage`.
+    pub fn load<T: serde::de::DeserializeOwned>(&self, stage: Stage) -> Result<T, anyhow::Error> {
+        let path = self.path_marker(stage);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("could not read marker {:?}: {}", &path, e))?;
+        serde_json::from_str(&data)
+            .map_err(|e| anyhow::anyhow!("could not parse marker {:?}: {}", &path, e))
+    }
+
+    /// Remove the marker of `stage`.
+    fn clear(&self, stage: Stage) -> Result<(), anyhow::Error> {
+        let path = self.path_marker(stage);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(anyhow::anyhow!(
+                "could not remove marker {:?}: {}",
+                &path,
+                e
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Checkpoints, Stage};
+
+    #[test]
+    fn resume() -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+
+        let checkpoints = Checkpoints::open(&tmp_dir, "a")?;
+        assert!(!checkpoints.is_done(Stage::Unsorted));
+        checkpoints.mark_done(Stage::Unsorted, &42)?;
+        checkpoints.mark_done(Stage::ByCoord, &())?;
+
+        // Stages after the first incomplete one are redone.
+        let checkpoints = Checkpoints::open(&tmp_dir, "a")?;
+        assert!(checkpoints.is_done(Stage::Unsorted));
+        assert_eq!(checkpoints.load::<usize>(Stage::Unsorted)?, 42);
+        assert!(!checkpoints.is_done(Stage::ByHgncFiltered));
+        assert!(!checkpoints.is_done(Stage::ByCoord));
+
+        // Changed settings start over.
+        let checkpoints = Checkpoints::open(&tmp_dir, "b")?;
+        assert!(!checkpoints.is_done(Stage::Unsorted));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+//! Aggregation of the ClinVar submissions (SCVs) of a variant.
+//!
+//! The effective significance is the most pathogenic classification among the
+//! submissions with the highest review status, e.g., an expert panel classification
+//! takes precedence over any number of single submitter ones.  Submissions are
+//! conflicting if the ones with assertion criteria fall into more than one of the
+//! pathogenic, uncertain, and benign groups.
+
+use annonars::pbs::clinvar_data::clinvar_public::{
+    clinical_assertion::RecordStatus, SubmitterReviewStatus,
+};
+use annonars::pbs::clinvar_data::extracted_vars::ExtractedVcvRecord;
+
+/// Summary of the germline submissions of a variant.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Submissions {
+    /// Number of current germline submissions.
+    pub count: u32,
+    /// Effective germline significance description, if any submission has one.
+    pub effective_significance: Option<String>,
+    /// Whether the submissions with assertion criteria conflict.
+    pub conflicting: bool,
+}
+
+/// Return the rank of the germline significance `description`, higher is more
+/// pathogenic and `0` is used for, e.g., risk factors and drug responses.
+fn significance_rank(description: &str) -> u8 {
+    let description = description.to_lowercase();
+    if description.starts_with("pathogenic") {
+        5
+    } else if description.starts_with("likely pathogenic") {
+        4
+    } else if description.starts_with("uncertain significance") {
+        3
+    } else if description.starts_with("likely benign") {
+        2
+    } else if description.starts_with("benign") {
+        1
+    } else {
+        0
+    }
+}
+
+/// Return the number of stars of a submission with `review_status`, or `None` for
+/// flagged submissions that are ignored.
+fn stars(review_status: i32) -> Option<u8> {
+    match SubmitterReviewStatus::try_from(review_status).unwrap_or_default() {
+        SubmitterReviewStatus::FlaggedSubmission => None,
+        SubmitterReviewStatus::PracticeGuideline => Some(4),
+        SubmitterReviewStatus::ReviewedByExpertPanel => Some(3),
+        SubmitterReviewStatus::CriteriaProvidedMultipleSubmittersNoConflicts
+        | SubmitterReviewStatus::ReviewedByProfessionalSociety => Some(2),
+        SubmitterReviewStatus::CriteriaProvidedSingleSubmitter
+        | SubmitterReviewStatus::CriteriaProvidedConflictingClassifications => Some(1),
+        _ => Some(0),
+    }
+}
+
+/// Summarize the current germline submissions of all `records`.
+pub fn aggregate(records: &[ExtractedVcvRecord]) -> Submissions {
+    let submissions = records
+        .iter()
+        .flat_map(|record| record.clinical_assertions.iter())
+        .filter(|assertion| assertion.record_status != RecordStatus::Replaced as i32)
+        .filter(|assertion| assertion.record_status != RecordStatus::Removed as i32)
+        .filter_map(|assertion| {
+            let classification = assertion.classifications.as_ref()?;
+            let description = classification.germline_classification.as_ref()?;
+            Some((stars(classification.review_status)?, description))
+        })
+        .collect::<Vec<_>>();
+
+    let effective_significance = submissions
+        .iter()
+        .max_by_key(|(stars, description)| (*stars, significance_rank(description)))
+        .map(|(_, description)| description.to_string());
+    let mut groups = submissions
+        .iter()
+        .filter(|(stars, _)| *stars > 0)
+        .filter_map(|(_, description)| match significance_rank(description) {
+            4..=5 => Some("pathogenic"),
+            3 => Some("uncertain"),
+            1..=2 => Some("benign"),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    groups.sort();
+    groups.dedup();
+
+    Submissions {
+        count: submissions.len() as u32,
+        effective_significance,
+        conflicting: groups.len() > 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use annonars::pbs::clinvar_data::clinvar_public::{
+        clinical_assertion::RecordStatus, ClassificationScv, ClinicalAssertion,
+        SubmitterReviewStatus,
+    };
+    use annonars::pbs::clinvar_data::extracted_vars::ExtractedVcvRecord;
+
+    use super::Submissions;
+
+    fn assertion(
+        review_status: SubmitterReviewStatus,
+        description: &str,
+        record_status: RecordStatus,
+    ) -> ClinicalAssertion {
+        ClinicalAssertion {
+            record_status: record_status as i32,
+            classifications: Some(ClassificationScv {
+                review_status: review_status as i32,
+                germline_classification: Some(description.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::single_submitters(
+        &[
+            (SubmitterReviewStatus::CriteriaProvidedSingleSubmitter, "Likely pathogenic"),
+            (SubmitterReviewStatus::CriteriaProvidedSingleSubmitter, "Pathogenic"),
+        ],
+        Submissions { count: 2, effective_significance: Some("Pathogenic".into()), conflicting: false }
+    )]
+    #[case::expert_panel_precedence(
+        &[
+            (SubmitterReviewStatus::CriteriaProvidedSingleSubmitter, "Pathogenic"),
+            (SubmitterReviewStatus::ReviewedByExpertPanel, "Likely benign"),
+        ],
+        Submissions { count: 2, effective_significance: Some("Likely benign".into()), conflicting: true }
+    )]
+    #[case::no_criteria_no_conflict(
+        &[
+            (SubmitterReviewStatus::NoAssertionCriteriaProvided, "Benign"),
+            (SubmitterReviewStatus::CriteriaProvidedSingleSubmitter, "Uncertain significance"),
+        ],
+        Submissions {
+            count: 2,
+            effective_significance: Some("Uncertain significance".into()),
+            conflicting: false,
+        }
+    )]
+    #[case::flagged_ignored(
+        &[
+            (SubmitterReviewStatus::FlaggedSubmission, "Pathogenic"),
+            (SubmitterReviewStatus::CriteriaProvidedSingleSubmitter, "Benign"),
+        ],
+        Submissions { count: 1, effective_significance: Some("Benign".into()), conflicting: false }
+    )]
+    #[case::empty(&[], Submissions::default())]
+    fn aggregate(
+        #[case] submissions: &[(SubmitterReviewStatus, &str)],
+        #[case] expected: Submissions,
+    ) {
+        let mut clinical_assertions = submissions
+            .iter()
+            .map(|(review_status, description)| {
+                assertion(*review_status, description, RecordStatus::Current)
+            })
+            .collect::<Vec<_>>();
+        clinical_assertions.push(assertion(
+            SubmitterReviewStatus::PracticeGuideline,
+            "Pathogenic",
+            RecordStatus::Replaced,
+        ));
+        let records = vec![ExtractedVcvRecord {
+            clinical_assertions,
+            ..Default::default()
+        }];
+
+        assert_eq!(super::aggregate(&records), expected);
+    }
+}
@@ -0,0 +1,248 @@
+//! Reference context sequence around variants, e.g., for designing confirmation primers.
+//!
+//! The context is written as the flanking reference bases with the variant in brackets,
+//! e.g., `ACGT[C/T]GGAT`.  For indels, the repeat context in the reference after the
+//! variant is computed as well, as indels in repeats and homopolymers are frequent
+//! artifacts.
+
+use noodles::{core::Position, fasta};
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::evidence::contig_name_candidates;
+use super::schema::data::VcfVariant;
+
+/// Number of reference bases after the variant that are scanned for repeats.
+const REPEAT_WINDOW: usize = 100;
+
+/// Return the smallest unit that `sequence` is a repetition of.
+fn repeat_unit(sequence: &[u8]) -> &[u8] {
+    (1..=sequence.len())
+        .filter(|len| sequence.len() % len == 0)
+        .map(|len| &sequence[..len])
+        .find(|unit| sequence.chunks(unit.len()).all(|chunk| chunk == *unit))
+        .unwrap_or(sequence)
+}
+
+/// Return the inserted or deleted sequence of `variant`, if it is a left-anchored indel.
+fn indel_sequence(variant: &VcfVariant) -> Option<Vec<u8>> {
+    let ref_allele = variant.ref_allele.to_ascii_uppercase().into_bytes();
+    let alt_allele = variant.alt_allele.to_ascii_uppercase().into_bytes();
+    let (short, long) = if ref_allele.len() < alt_allele.len() {
+        (ref_allele, alt_allele)
+    } else {
+        (alt_allele, ref_allele)
+    };
+    (short.len() == 1 && long.len() > 1 && short[0] == long[0]).then(|| long[1..].to_vec())
+}
+
+/// Compute the repeat context of the inserted or deleted sequence `indel` from the
+/// reference bases `downstream` of the anchor base.
+fn repeat_context(indel: &[u8], downstream: &[u8]) -> pbs_output::RepeatContext {
+    let unit = repeat_unit(indel);
+    let repeat_count = downstream
+        .chunks(unit.len())
+        .take_while(|chunk| *chunk == unit)
+        .count();
+    let homopolymer_length = downstream
+        .first()
+        .map(|first| downstream.iter().take_while(|base| *base == first).count())
+        .unwrap_or_default();
+    pbs_output::RepeatContext {
+        repeat_unit: String::from_utf8_lossy(unit).to_string(),
+        repeat_count: repeat_count as i32,
+        homopolymer_length: homopolymer_length as i32,
+    }
+}
+
+/// Reads the reference context around variants from an indexed FASTA file.
+pub struct ContextReader {
+    /// Path to the FASTA file, for error messages.
+    path: String,
+    /// The indexed reader.
+    reader: fasta::io::IndexedReader<fasta::io::BufReader<std::fs::File>>,
+    /// Number of flanking bases on each side of the variant.
+    flank: usize,
+}
+
+impl ContextReader {
+    /// Open the indexed FASTA file at `path` for reading `flank` bases on each side.
+    pub fn new(path: &str, flank: usize) -> Result<Self, anyhow::Error> {
+        let reader = fasta::io::indexed_reader::Builder::default()
+            .build_from_path(path)
+            .map_err(|e| anyhow::anyhow!("could not open reference {}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            reader,
+            flank,
+        })
+    }
+
+    /// Return the name and length of `chrom` in the index, allowing for a differing
+    /// `chr` prefix.
+    fn contig(&self, chrom: &str) -> Option<(String, usize)> {
+        contig_name_candidates(chrom).into_iter().find_map(|name| {
+            self.reader
+                .index()
+                .iter()
+                .find(|record| record.name() == name.as_bytes())
+                .map(|record| (name, record.length() as usize))
+        })
+    }
+
+    /// Read the reference bases `start..=end` (1-based, clipped at the contig end) of
+    /// the contig of `chrom` as upper case.
+    ///
+    /// Returns `None` if the contig is not in the reference or `start` is beyond its end.
+    fn read(
+        &mut self,
+        chrom: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let Some((contig_name, contig_length)) = self.contig(chrom) else {
+            tracing::debug!("contig {} not found in {}", chrom, &self.path);
+            return Ok(None);
+        };
+        let end = end.min(contig_length);
+        if start > end {
+            return Ok(None);
+        }
+
+        let region = noodles::core::Region::new(
+            contig_name,
+            Position::try_from(start)?..=Position::try_from(end)?,
+        );
+        let record = self
+            .reader
+            .query(&region)
+            .map_err(|e| anyhow::anyhow!("could not query {}: {}", &self.path, e))?;
+        Ok(Some(record.sequence().as_ref().to_ascii_uppercase()))
+    }
+
+    /// Return the context sequence of `variant`.
+    ///
+    /// The flanks are clipped at the contig boundaries.  Returns `None` if the contig
+    /// is not in the reference.
+    pub fn context_sequence(
+        &mut self,
+        variant: &VcfVariant,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let pos = variant.pos as usize;
+        let start = pos.saturating_sub(self.flank).max(1);
+        let end = pos + variant.ref_allele.len() - 1 + self.flank;
+        let Some(sequence) = self.read(&variant.chrom, start, end)? else {
+            return Ok(None);
+        };
+        let sequence = String::from_utf8_lossy(&sequence);
+        let left_len = pos - start;
+        if sequence.len() <= left_len {
+            return Ok(None);
+        }
+
+        let right_start = (left_len + variant.ref_allele.len()).min(sequence.len());
+        Ok(Some(format!(
+            "{}[{}/{}]{}",
+            &sequence[..left_len],
+            &variant.ref_allele,
+            &variant.alt_allele,
+            &sequence[right_start..],
+        )))
+    }
+
+    /// Return the repeat context of `variant` if it is an indel.
+    ///
+    /// At most 100 reference bases after the anchor base are considered.
+    pub fn repeat_context(
+        &mut self,
+        variant: &VcfVariant,
+    ) -> Result<Option<pbs_output::RepeatContext>, anyhow::Error> {
+        let Some(indel) = indel_sequence(variant) else {
+            return Ok(None);
+        };
+        let start = variant.pos as usize + 1;
+        Ok(self
+            .read(&variant.chrom, start, start + REPEAT_WINDOW - 1)?
+            .map(|downstream| repeat_context(&indel, &downstream)))
+    }
+
+    /// Return whether `variant` is no indel or the homopolymer after it is at most
+    /// `max_length` bases long.
+    pub fn passes_max_homopolymer_length(
+        &mut self,
+        variant: &VcfVariant,
+        max_length: i32,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(self
+            .repeat_context(variant)?
+            .map(|context| context.homopolymer_length <= max_length)
+            .unwrap_or(true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::query::schema::data::VcfVariant;
+
+    fn variant(chrom: &str, pos: i32, ref_allele: &str, alt_allele: &str) -> VcfVariant {
+        VcfVariant {
+            chrom: chrom.to_string(),
+            pos,
+            ref_allele: ref_allele.to_string(),
+            alt_allele: alt_allele.to_string(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::snv("chr1", 11, "C", "T", 4, Some("GATT[C/T]GGAT"))]
+    #[case::deletion("1", 11, "CGG", "C", 2, Some("TT[CGG/C]AT"))]
+    #[case::clipped_start("1", 2, "C", "A", 4, Some("A[C/A]GTAC"))]
+    #[case::clipped_end("1", 19, "C", "A", 4, Some("TTGC[C/A]ACG"))]
+    #[case::unknown_contig("2", 11, "C", "T", 4, None)]
+    fn context_sequence(
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] flank: usize,
+        #[case] expected: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = format!("{}/ref.fa", tmpdir.to_string_lossy());
+        std::fs::write(&path, ">1\nACGTACGATTCGGATTGCCACG\n")?;
+        std::fs::write(format!("{}.fai", &path), "1\t22\t3\t22\t23\n")?;
+
+        let mut reader = super::ContextReader::new(&path, flank)?;
+        let variant = variant(chrom, pos, ref_allele, alt_allele);
+        assert_eq!(reader.context_sequence(&variant)?.as_deref(), expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::snv("T", "A", "TTTTG", None)]
+    #[case::homopolymer_insertion("G", "GT", "TTTTGA", Some(("T", 4, 4)))]
+    #[case::homopolymer_deletion("GTT", "G", "TTTTGA", Some(("T", 4, 4)))]
+    #[case::dinucleotide_insertion("G", "GCACA", "CACACAT", Some(("CA", 3, 1)))]
+    #[case::no_repeat("G", "GAC", "TTGA", Some(("AC", 0, 2)))]
+    #[case::complex("GA", "TCC", "TTGA", None)]
+    fn repeat_context(
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] downstream: &str,
+        #[case] expected: Option<(&str, i32, i32)>,
+    ) {
+        let variant = variant("1", 1, ref_allele, alt_allele);
+        let context = super::indel_sequence(&variant)
+            .map(|indel| super::repeat_context(&indel, downstream.as_bytes()));
+
+        assert_eq!(
+            context.as_ref().map(|context| (
+                context.repeat_unit.as_str(),
+                context.repeat_count,
+                context.homopolymer_length
+            )),
+            expected
+        );
+    }
+}
@@ -0,0 +1,520 @@
+//! Read evidence from alignment files for de novo and compound heterozygous candidates.
+//!
+//! For each output record (up to a limit), the reads of each sample with an alignment
+//! file are classified as supporting the reference or alternate allele and simple
+//! metrics are written to the sample's call information.  This helps to prioritize
+//! candidates for Sanger confirmation.
+
+use noodles::sam::alignment::record::cigar::op::Kind;
+use noodles::{bam, bgzf, core::Position, cram, fasta, sam};
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::schema::data::VcfVariant;
+use super::schema::query::{CaseQuery, GenotypeChoice, RecessiveMode};
+
+/// Parse `SAMPLE=PATH` value of `--path-alignments`.
+pub fn parse_path_alignment(value: &str) -> Result<(String, String), anyhow::Error> {
+    match value.split_once('=') {
+        Some((sample, path)) if !sample.is_empty() && !path.is_empty() => {
+            Ok((sample.to_string(), path.to_string()))
+        }
+        _ => anyhow::bail!("alignment path must be SAMPLE=PATH: {}", value),
+    }
+}
+
+/// Returns whether `query` selects de novo or compound heterozygous candidates.
+///
+/// De novo queries have at least one sample with a variant and one sample with a
+/// reference genotype choice.
+pub fn is_candidate_query(query: &CaseQuery) -> bool {
+    if matches!(
+        query.genotype.recessive_mode,
+        RecessiveMode::CompoundHeterozygous | RecessiveMode::Any
+    ) {
+        return true;
+    }
+
+    let enabled = query
+        .genotype
+        .sample_genotypes
+        .values()
+        .filter(|choice| choice.enabled)
+        .collect::<Vec<_>>();
+    enabled.iter().any(|choice| {
+        matches!(
+            choice.genotype,
+            GenotypeChoice::Het | GenotypeChoice::Hom | GenotypeChoice::Variant
+        )
+    }) && enabled
+        .iter()
+        .any(|choice| choice.genotype == GenotypeChoice::Ref)
+}
+
+/// Allele supported by a read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadAllele {
+    /// Reference allele.
+    Ref,
+    /// Alternate allele.
+    Alt,
+    /// Neither reference nor alternate allele.
+    Other,
+}
+
+/// Classify the allele of `variant` supported by `record`.
+///
+/// Returns `None` if the read does not cover the variant.  For indels, the read must
+/// also cover the base after the reference allele.
+fn classify_read<R>(record: &R, variant: &VcfVariant) -> std::io::Result<Option<ReadAllele>>
+where
+    R: sam::alignment::Record + ?Sized,
+{
+    let ref_allele = variant.ref_allele.as_bytes();
+    let alt_allele = variant.alt_allele.as_bytes();
+    let is_indel = ref_allele.len() != alt_allele.len();
+    let window_start = variant.pos as usize;
+    let window_end = window_start + ref_allele.len() - usize::from(!is_indel);
+
+    let (Some(start), Some(end)) = (record.alignment_start(), record.alignment_end()) else {
+        return Ok(None);
+    };
+    let (start, end) = (usize::from(start?), usize::from(end?));
+    if start > window_start || end < window_end {
+        return Ok(None);
+    }
+
+    let sequence = record.sequence().iter().collect::<Vec<_>>();
+    let mut ref_pos = start;
+    let mut read_pos = 0;
+    // Read bases at the reference allele positions, for SNVs and MNVs.
+    let mut bases = Vec::with_capacity(ref_allele.len());
+    // Indels starting in the window as `(kind, reference position, length)`.
+    let mut indels = Vec::new();
+
+    for op in record.cigar().iter() {
+        let op = op?;
+        match op.kind() {
+            Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                for i in 0..op.len() {
+                    if (window_start..=window_end).contains(&(ref_pos + i)) {
+                        bases.push(sequence.get(read_pos + i).copied().unwrap_or(b'N'));
+                    }
+                }
+            }
+            Kind::Deletion | Kind::Skip => {
+                if (window_start..=window_end).contains(&ref_pos) {
+                    indels.push((Kind::Deletion, ref_pos, op.len()));
+                } else if ref_pos < window_start && ref_pos + op.len() > window_start {
+                    return Ok(Some(ReadAllele::Other));
+                }
+            }
+            Kind::Insertion => {
+                if (window_start + 1..=window_end).contains(&ref_pos) {
+                    indels.push((Kind::Insertion, ref_pos, op.len()));
+                }
+            }
+            Kind::SoftClip | Kind::HardClip | Kind::Pad => (),
+        }
+        if op.kind().consumes_reference() {
+            ref_pos += op.len();
+        }
+        if op.kind().consumes_read() {
+            read_pos += op.len();
+        }
+        if ref_pos > window_end {
+            break;
+        }
+    }
+
+    let result = if is_indel {
+        let expected = if ref_allele.len() > alt_allele.len() {
+            (
+                Kind::Deletion,
+                window_start + alt_allele.len(),
+                ref_allele.len() - alt_allele.len(),
+            )
+        } else {
+            (
+                Kind::Insertion,
+                window_start + ref_allele.len(),
+                alt_allele.len() - ref_allele.len(),
+            )
+        };
+        match indels.as_slice() {
+            [] => ReadAllele::Ref,
+            [indel] if *indel == expected => ReadAllele::Alt,
+            _ => ReadAllele::Other,
+        }
+    } else if !indels.is_empty() {
+        ReadAllele::Other
+    } else if bases.eq_ignore_ascii_case(ref_allele) {
+        ReadAllele::Ref
+    } else if bases.eq_ignore_ascii_case(alt_allele) {
+        ReadAllele::Alt
+    } else {
+        ReadAllele::Other
+    };
+    Ok(Some(result))
+}
+
+/// Read counts for one variant in one sample.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ReadCounts {
+    /// Number of covering reads.
+    total: i32,
+    /// Number of reference allele reads.
+    ref_reads: i32,
+    /// Number of alternate allele reads on the forward strand.
+    alt_forward: i32,
+    /// Number of alternate allele reads on the reverse strand.
+    alt_reverse: i32,
+    /// Number of covering reads with mapping quality 0.
+    mq0: i32,
+}
+
+impl ReadCounts {
+    /// Count the reads from `records` that cover `variant`.
+    ///
+    /// Unmapped, secondary, supplementary, duplicate, and QC-failed reads are ignored.
+    fn from_records<I, R>(records: I, variant: &VcfVariant) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = std::io::Result<R>>,
+        R: sam::alignment::Record,
+    {
+        let mut result = Self::default();
+        for record in records {
+            let record = record.map_err(|e| anyhow::anyhow!("problem reading alignment: {}", e))?;
+            let flags = record.flags()?;
+            if flags.is_unmapped()
+                || flags.is_secondary()
+                || flags.is_supplementary()
+                || flags.is_duplicate()
+                || flags.is_qc_fail()
+            {
+                continue;
+            }
+            let Some(allele) = classify_read(&record, variant)? else {
+                continue;
+            };
+
+            result.total += 1;
+            if let Some(mapping_quality) = record.mapping_quality() {
+                if mapping_quality?.get() == 0 {
+                    result.mq0 += 1;
+                }
+            }
+            match allele {
+                ReadAllele::Ref => result.ref_reads += 1,
+                ReadAllele::Alt if flags.is_reverse_complemented() => result.alt_reverse += 1,
+                ReadAllele::Alt => result.alt_forward += 1,
+                ReadAllele::Other => (),
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl From<ReadCounts> for pbs_output::AlignmentEvidence {
+    fn from(counts: ReadCounts) -> Self {
+        let alt_reads = counts.alt_forward + counts.alt_reverse;
+        Self {
+            total_reads: counts.total,
+            ref_reads: counts.ref_reads,
+            alt_reads,
+            alt_reads_forward: counts.alt_forward,
+            alt_reads_reverse: counts.alt_reverse,
+            strand_bias: if alt_reads > 0 {
+                (counts.alt_forward - counts.alt_reverse).abs() as f64 / alt_reads as f64
+            } else {
+                0.0
+            },
+            mq0_fraction: if counts.total > 0 {
+                counts.mq0 as f64 / counts.total as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Return the candidate names of `chrom` in a header or index, in order of preference.
+pub(crate) fn contig_name_candidates(chrom: &str) -> Vec<String> {
+    let stripped = chrom.strip_prefix("chr").unwrap_or(chrom);
+    match stripped {
+        "M" | "MT" => vec![chrom.to_string(), String::from("chrM"), String::from("MT")],
+        _ => vec![
+            chrom.to_string(),
+            stripped.to_string(),
+            format!("chr{}", stripped),
+        ],
+    }
+}
+
+/// Indexed reader for a BAM or CRAM file.
+enum AlignmentReader {
+    Bam(bam::io::IndexedReader<bgzf::Reader<std::fs::File>>),
+    Cram(cram::io::IndexedReader<std::fs::File>),
+}
+
+/// Alignment file of one sample, with its header.
+struct AlignmentFile {
+    /// Path to the file, for error messages.
+    path: String,
+    /// The indexed reader.
+    reader: AlignmentReader,
+    /// The SAM header.
+    header: sam::Header,
+}
+
+impl AlignmentFile {
+    /// Open the file at `path`; CRAM files require `path_reference`.
+    fn open(path: &str, path_reference: Option<&str>) -> Result<Self, anyhow::Error> {
+        let mut reader = if path.ends_with(".cram") {
+            let path_reference = path_reference.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "reading CRAM file {} requires --path-alignments-reference",
+                    path
+                )
+            })?;
+            let fasta_reader = fasta::io::indexed_reader::Builder::default()
+                .build_from_path(path_reference)
+                .map_err(|e| {
+                    anyhow::anyhow!("could not open reference {}: {}", path_reference, e)
+                })?;
+            let repository = fasta::Repository::new(
+                fasta::repository::adapters::IndexedReader::new(fasta_reader),
+            );
+            AlignmentReader::Cram(
+                cram::io::indexed_reader::Builder::default()
+                    .set_reference_sequence_repository(repository)
+                    .build_from_path(path)
+                    .map_err(|e| anyhow::anyhow!("could not open CRAM file {}: {}", path, e))?,
+            )
+        } else {
+            AlignmentReader::Bam(
+                bam::io::indexed_reader::Builder::default()
+                    .build_from_path(path)
+                    .map_err(|e| anyhow::anyhow!("could not open BAM file {}: {}", path, e))?,
+            )
+        };
+        let header = match &mut reader {
+            AlignmentReader::Bam(reader) => reader.read_header(),
+            AlignmentReader::Cram(reader) => reader.read_header(),
+        }
+        .map_err(|e| anyhow::anyhow!("could not read header of {}: {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            reader,
+            header,
+        })
+    }
+
+    /// Return the name of `chrom` in the header, allowing for a differing `chr` prefix.
+    fn contig_name(&self, chrom: &str) -> Option<String> {
+        contig_name_candidates(chrom).into_iter().find(|name| {
+            self.header
+                .reference_sequences()
+                .keys()
+                .any(|key| key[..] == *name.as_bytes())
+        })
+    }
+
+    /// Count the reads covering `variant`.
+    fn read_counts(&mut self, variant: &VcfVariant) -> Result<ReadCounts, anyhow::Error> {
+        let Some(contig_name) = self.contig_name(&variant.chrom) else {
+            tracing::debug!("contig {} not found in {}", &variant.chrom, &self.path);
+            return Ok(ReadCounts::default());
+        };
+        let start = Position::try_from(variant.pos as usize)
+            .map_err(|e| anyhow::anyhow!("invalid position {}: {}", variant.pos, e))?;
+        let end = Position::try_from(variant.pos as usize + variant.ref_allele.len())
+            .map_err(|e| anyhow::anyhow!("invalid position {}: {}", variant.pos, e))?;
+        let region = noodles::core::Region::new(contig_name, start..=end);
+
+        let header = &self.header;
+        match &mut self.reader {
+            AlignmentReader::Bam(reader) => ReadCounts::from_records(
+                reader
+                    .query(header, &region)
+                    .map_err(|e| anyhow::anyhow!("could not query {}: {}", &self.path, e))?,
+                variant,
+            ),
+            AlignmentReader::Cram(reader) => ReadCounts::from_records(
+                reader
+                    .query(header, &region)
+                    .map_err(|e| anyhow::anyhow!("could not query {}: {}", &self.path, e))?,
+                variant,
+            ),
+        }
+    }
+}
+
+/// Annotates output records with read evidence from the samples' alignment files.
+pub struct EvidenceCollector {
+    /// Alignment files by sample name.
+    files: indexmap::IndexMap<String, AlignmentFile>,
+    /// Maximal number of records to annotate.
+    max_records: usize,
+    /// Number of records annotated so far.
+    records: usize,
+}
+
+impl EvidenceCollector {
+    /// Open the alignment files from `path_alignments` as pairs of sample name and path.
+    pub fn new(
+        path_alignments: &[(String, String)],
+        path_reference: Option<&str>,
+        max_records: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let files = path_alignments
+            .iter()
+            .map(|(sample, path)| Ok((sample.clone(), AlignmentFile::open(path, path_reference)?)))
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self {
+            files,
+            max_records,
+            records: 0,
+        })
+    }
+
+    /// Write the read evidence into the call information of `record`.
+    ///
+    /// Does nothing once `max_records` records have been annotated.
+    pub fn annotate(&mut self, record: &mut pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        if self.records >= self.max_records {
+            return Ok(());
+        }
+        self.records += 1;
+
+        let Some(vcf_variant) = record.vcf_variant.as_ref() else {
+            return Ok(());
+        };
+        let variant = VcfVariant {
+            chrom: vcf_variant.chrom.clone(),
+            pos: vcf_variant.pos,
+            ref_allele: vcf_variant.ref_allele.clone(),
+            alt_allele: vcf_variant.alt_allele.clone(),
+        };
+        let Some(call) = record
+            .variant_annotation
+            .as_mut()
+            .and_then(|annotation| annotation.call.as_mut())
+        else {
+            return Ok(());
+        };
+        for call_info in call.call_infos.iter_mut() {
+            if let Some(file) = self.files.get_mut(&call_info.sample) {
+                call_info.alignment_evidence = Some(file.read_counts(&variant)?.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles::core::Position;
+    use noodles::sam::alignment::{
+        record::{cigar::op::Kind, cigar::Op, Flags, MappingQuality},
+        record_buf::{Cigar, Sequence},
+        RecordBuf,
+    };
+
+    use super::{ReadAllele, ReadCounts};
+    use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+    use crate::seqvars::query::schema::data::VcfVariant;
+
+    fn variant(pos: i32, ref_allele: &str, alt_allele: &str) -> VcfVariant {
+        VcfVariant {
+            chrom: "1".to_string(),
+            pos,
+            ref_allele: ref_allele.to_string(),
+            alt_allele: alt_allele.to_string(),
+        }
+    }
+
+    /// Build read aligned at position 100 with the given CIGAR and sequence.
+    fn read(cigar: &[(Kind, usize)], sequence: &str, flags: Flags, mapq: u8) -> RecordBuf {
+        RecordBuf::builder()
+            .set_flags(flags)
+            .set_alignment_start(Position::try_from(100).unwrap())
+            .set_mapping_quality(MappingQuality::new(mapq).unwrap())
+            .set_cigar(
+                cigar
+                    .iter()
+                    .map(|(kind, len)| Op::new(*kind, *len))
+                    .collect::<Cigar>(),
+            )
+            .set_sequence(Sequence::from(sequence.as_bytes().to_vec()))
+            .build()
+    }
+
+    #[rstest::rstest]
+    #[case::snv_ref(variant(102, "G", "T"), &[(Kind::Match, 8)], "ACGTACGT", Some(ReadAllele::Ref))]
+    #[case::snv_alt(variant(102, "G", "T"), &[(Kind::Match, 8)], "ACTTACGT", Some(ReadAllele::Alt))]
+    #[case::snv_other(variant(102, "G", "T"), &[(Kind::Match, 8)], "ACATACGT", Some(ReadAllele::Other))]
+    #[case::snv_not_covered(variant(110, "G", "T"), &[(Kind::Match, 8)], "ACGTACGT", None)]
+    #[case::snv_deleted(variant(102, "G", "T"), &[(Kind::Match, 2), (Kind::Deletion, 1), (Kind::Match, 5)], "ACTACGT", Some(ReadAllele::Other))]
+    #[case::del_alt(variant(101, "CGT", "C"), &[(Kind::Match, 2), (Kind::Deletion, 2), (Kind::Match, 4)], "ACACGT", Some(ReadAllele::Alt))]
+    #[case::del_ref(variant(101, "CGT", "C"), &[(Kind::Match, 8)], "ACGTACGT", Some(ReadAllele::Ref))]
+    #[case::del_other_length(variant(101, "CGT", "C"), &[(Kind::Match, 2), (Kind::Deletion, 1), (Kind::Match, 5)], "ACTACGT", Some(ReadAllele::Other))]
+    #[case::ins_alt(variant(101, "C", "CAA"), &[(Kind::Match, 2), (Kind::Insertion, 2), (Kind::Match, 6)], "ACAAGTACGT", Some(ReadAllele::Alt))]
+    #[case::ins_ref(variant(101, "C", "CAA"), &[(Kind::Match, 8)], "ACGTACGT", Some(ReadAllele::Ref))]
+    fn classify_read(
+        #[case] variant: VcfVariant,
+        #[case] cigar: &[(Kind, usize)],
+        #[case] sequence: &str,
+        #[case] expected: Option<ReadAllele>,
+    ) -> Result<(), anyhow::Error> {
+        let record = read(cigar, sequence, Flags::empty(), 60);
+
+        assert_eq!(super::classify_read(&record, &variant)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_counts() -> Result<(), anyhow::Error> {
+        let cigar = [(Kind::Match, 8)];
+        let records = vec![
+            read(&cigar, "ACGTACGT", Flags::empty(), 60),
+            read(&cigar, "ACTTACGT", Flags::empty(), 60),
+            read(&cigar, "ACTTACGT", Flags::REVERSE_COMPLEMENTED, 0),
+            read(&cigar, "ACTTACGT", Flags::empty(), 60),
+            read(&cigar, "ACTTACGT", Flags::DUPLICATE, 60),
+        ];
+
+        let counts =
+            ReadCounts::from_records(records.into_iter().map(Ok), &variant(102, "G", "T"))?;
+
+        assert_eq!(
+            pbs_output::AlignmentEvidence::from(counts),
+            pbs_output::AlignmentEvidence {
+                total_reads: 4,
+                ref_reads: 1,
+                alt_reads: 3,
+                alt_reads_forward: 2,
+                alt_reads_reverse: 1,
+                strand_bias: 1.0 / 3.0,
+                mq0_fraction: 0.25,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("index=/path/to/index.bam", Some(("index", "/path/to/index.bam")))]
+    #[case("index=s3://bucket/a=b.cram", Some(("index", "s3://bucket/a=b.cram")))]
+    #[case("/path/to/index.bam", None)]
+    #[case("=/path/to/index.bam", None)]
+    fn parse_path_alignment(#[case] value: &str, #[case] expected: Option<(&str, &str)>) {
+        assert_eq!(
+            super::parse_path_alignment(value).ok(),
+            expected.map(|(sample, path)| (sample.to_string(), path.to_string()))
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Gene information embedded in the output trailer with `--embed-gene-cache`.
+//!
+//! The front-end can use it to render the results offline, without querying the genes
+//! service for each record.
+
+use std::collections::BTreeMap;
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::annonars::Annotator;
+use super::schema::data::VariantRecord;
+
+/// Collects the HGNC IDs referenced in the output records.
+#[derive(Debug, Default)]
+pub struct GeneCache {
+    /// Mapping from HGNC ID to the symbol from the variant annotation, used as
+    /// fallback for genes missing from the genes database.
+    symbols: BTreeMap<String, String>,
+}
+
+impl GeneCache {
+    /// Register the gene of `seqvar`, if any.
+    ///
+    /// This mirrors the gene selection of the gene-related annotation in the output
+    /// record.
+    pub fn register(&mut self, seqvar: &VariantRecord) {
+        if let Some(ann) = seqvar.ann_fields.first() {
+            if !ann.gene_id.is_empty() && !ann.gene_symbol.is_empty() {
+                self.symbols
+                    .entry(ann.gene_id.clone())
+                    .or_insert_with(|| ann.gene_symbol.clone());
+            }
+        }
+    }
+
+    /// Look up the registered genes in the genes database and build the trailer.
+    ///
+    /// Without `annotator`, all entries fall back to the symbols from the annotation.
+    pub fn build_trailer(
+        &self,
+        annotator: Option<&Annotator>,
+    ) -> Result<pbs_output::OutputTrailer, anyhow::Error> {
+        let gene_cache = self
+            .symbols
+            .iter()
+            .map(|(hgnc_id, symbol)| {
+                let record = annotator
+                    .map(|annotator| annotator.query_genes(hgnc_id))
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("problem querying genes database: {}", e))?
+                    .flatten();
+                Ok(entry(hgnc_id, symbol, record))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        Ok(pbs_output::OutputTrailer { gene_cache })
+    }
+}
+
+/// Build the cache entry for `hgnc_id` from the genes database `record`, falling back
+/// to `symbol` if there is no HGNC information.
+fn entry(
+    hgnc_id: &str,
+    symbol: &str,
+    record: Option<annonars::pbs::genes::base::Record>,
+) -> pbs_output::GeneCacheEntry {
+    match record.and_then(|record| record.hgnc) {
+        Some(hgnc) => pbs_output::GeneCacheEntry {
+            hgnc_id: hgnc_id.to_string(),
+            symbol: hgnc.symbol,
+            name: hgnc.name,
+            alias_symbols: hgnc.alias_symbol,
+        },
+        None => pbs_output::GeneCacheEntry {
+            hgnc_id: hgnc_id.to_string(),
+            symbol: symbol.to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use annonars::pbs::genes::base::{HgncRecord, Record};
+    use mehari::annotate::seqvars::ann::AnnField;
+
+    use super::GeneCache;
+    use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+    use crate::seqvars::query::schema::data::VariantRecord;
+
+    fn seqvar(gene_id: &str, gene_symbol: &str) -> VariantRecord {
+        VariantRecord {
+            ann_fields: vec![AnnField {
+                gene_id: gene_id.to_string(),
+                gene_symbol: gene_symbol.to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn register() {
+        let mut gene_cache = GeneCache::default();
+        gene_cache.register(&seqvar("HGNC:1100", "BRCA1"));
+        gene_cache.register(&seqvar("HGNC:1100", "BRCA1"));
+        gene_cache.register(&seqvar("HGNC:11998", "TP53"));
+        gene_cache.register(&seqvar("", ""));
+        gene_cache.register(&VariantRecord::default());
+
+        assert_eq!(
+            gene_cache.symbols.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("HGNC:1100".to_string(), "BRCA1".to_string()),
+                ("HGNC:11998".to_string(), "TP53".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry() {
+        let record = Record {
+            hgnc: Some(HgncRecord {
+                hgnc_id: "HGNC:1100".to_string(),
+                symbol: "BRCA1".to_string(),
+                name: "BRCA1 DNA repair associated".to_string(),
+                alias_symbol: vec!["RNF53".to_string(), "BRCC1".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::entry("HGNC:1100", "OLD1", Some(record)),
+            pbs_output::GeneCacheEntry {
+                hgnc_id: "HGNC:1100".to_string(),
+                symbol: "BRCA1".to_string(),
+                name: "BRCA1 DNA repair associated".to_string(),
+                alias_symbols: vec!["RNF53".to_string(), "BRCC1".to_string()],
+            }
+        );
+        assert_eq!(
+            super::entry("HGNC:1100", "OLD1", None),
+            pbs_output::GeneCacheEntry {
+                hgnc_id: "HGNC:1100".to_string(),
+                symbol: "OLD1".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+}
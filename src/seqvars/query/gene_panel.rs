@@ -0,0 +1,225 @@
+//! Gene panel files for `--path-gene-panel`.
+//!
+//! Panels are either plain text files with one gene symbol or identifier per line or
+//! PanelApp JSON exports.  The entries are merged into the gene allow list of the query
+//! and the panel provenance is written to the output header.
+
+use std::io::Read as _;
+
+use sha1::Digest as _;
+
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+/// A loaded gene panel file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Panel {
+    /// Gene identifiers of the entries.
+    pub genes: Vec<String>,
+    /// Name of the panel, if given in the file.
+    pub name: Option<String>,
+    /// Version of the panel, if given in the file.
+    pub version: Option<String>,
+    /// Hex-encoded SHA-1 digest of the file.
+    pub checksum: String,
+}
+
+impl Panel {
+    /// Return the provenance of the panel from `path` resolved to `hgnc_ids`.
+    pub fn provenance<'a>(
+        &self,
+        path: &str,
+        hgnc_ids: impl IntoIterator<Item = &'a String>,
+    ) -> pbs_output::GenePanelProvenance {
+        let mut hgnc_ids = hgnc_ids.into_iter().cloned().collect::<Vec<_>>();
+        hgnc_ids.sort();
+        pbs_output::GenePanelProvenance {
+            path: path.to_string(),
+            name: self.name.clone(),
+            version: self.version.clone(),
+            checksum: self.checksum.clone(),
+            resolved_at: Some(crate::common::now_as_pbjson_timestamp()),
+            hgnc_ids,
+        }
+    }
+}
+
+/// Load the gene panel file at `path`.
+///
+/// Files starting with `{` or `[` are read as PanelApp JSON, all others as plain text.
+pub fn load(path: &str) -> Result<Panel, anyhow::Error> {
+    let content = crate::common::s3::open_read_maybe_gz(path)
+        .and_then(|mut reader| {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            Ok(content)
+        })
+        .map_err(|e| anyhow::anyhow!("could not read gene panel file {}: {}", path, e))
+        .classify(ErrorClass::Config)?;
+    let panel = if content.trim_start().starts_with(['{', '[']) {
+        parse_panelapp(&content)
+            .map_err(|e| anyhow::anyhow!("could not parse PanelApp file {}: {}", path, e))
+            .classify(ErrorClass::Config)?
+    } else {
+        Panel {
+            genes: parse_text(&content),
+            ..Default::default()
+        }
+    };
+    Ok(Panel {
+        checksum: base16ct::lower::encode_string(&sha1::Sha1::digest(content.as_bytes())),
+        ..panel
+    })
+}
+
+/// Parse plain text panel with one gene per line.
+///
+/// Empty lines and lines starting with `#` are skipped.  Only the first column of
+/// tab, comma, or space separated lines is used.
+fn parse_text(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            line.split(|c: char| c == ',' || c.is_whitespace())
+                .find(|token| !token.is_empty())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Parse PanelApp JSON export, i.e., a panel with `genes`, a paged API response with
+/// `results`, or a plain list of gene entries.
+///
+/// The HGNC ID of each entry is preferred over its symbol.  The panel name and version
+/// are taken from the panel or, for API responses and lists, from the `panel` of the
+/// first entry that has one.
+fn parse_panelapp(content: &str) -> Result<Panel, anyhow::Error> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    let entries = match &value {
+        serde_json::Value::Array(entries) => entries,
+        serde_json::Value::Object(object) => object
+            .get("genes")
+            .or_else(|| object.get("results"))
+            .and_then(serde_json::Value::as_array)
+            .ok_or_else(|| anyhow::anyhow!("neither `genes` nor `results` list found"))?,
+        _ => anyhow::bail!("expected JSON object or list"),
+    };
+    let panel = if value.get("name").is_some() {
+        Some(&value)
+    } else {
+        entries.iter().find_map(|entry| entry.get("panel"))
+    };
+    let panel_field = |key: &str| {
+        panel
+            .and_then(|panel| panel.get(key))
+            .and_then(|value| match value {
+                serde_json::Value::String(value) => Some(value.clone()),
+                serde_json::Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            })
+    };
+
+    let mut result = Vec::new();
+    for entry in entries {
+        if entry
+            .get("entity_type")
+            .and_then(serde_json::Value::as_str)
+            .is_some_and(|entity_type| entity_type != "gene")
+        {
+            continue; // skip, e.g., STRs and regions
+        }
+        let gene_data = entry.get("gene_data");
+        let identifier = ["hgnc_id", "gene_symbol"]
+            .iter()
+            .filter_map(|key| gene_data.and_then(|gene_data| gene_data.get(key)))
+            .chain(entry.get("entity_name"))
+            .filter_map(serde_json::Value::as_str)
+            .find(|identifier| !identifier.is_empty());
+        match identifier {
+            Some(identifier) => result.push(identifier.to_string()),
+            None => tracing::warn!("skipping PanelApp entry without gene: {}", entry),
+        }
+    }
+    Ok(Panel {
+        genes: result,
+        name: panel_field("name"),
+        version: panel_field("version"),
+        checksum: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_text() {
+        let content = "# my panel\nBRCA1\n\nHGNC:11998\tTP53\n  TTN, titin\n";
+
+        assert_eq!(
+            super::parse_text(content),
+            vec!["BRCA1", "HGNC:11998", "TTN"]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::panel(r#"{"name": "Panel", "genes": [GENES]}"#)]
+    #[case::api(r#"{"count": 3, "results": [GENES]}"#)]
+    #[case::list("[GENES]")]
+    fn parse_panelapp(#[case] template: &str) -> Result<(), anyhow::Error> {
+        let genes = r#"
+            {"entity_type": "gene", "entity_name": "BRCA1",
+             "gene_data": {"hgnc_id": "HGNC:1100", "gene_symbol": "BRCA1"}},
+            {"entity_type": "gene", "entity_name": "TP53",
+             "gene_data": {"gene_symbol": "TP53"}},
+            {"entity_type": "gene", "entity_name": "TTN"},
+            {"entity_type": "str", "entity_name": "ATXN1_CAG"}
+        "#;
+
+        assert_eq!(
+            super::parse_panelapp(&template.replace("GENES", genes))?.genes,
+            vec!["HGNC:1100", "TP53", "TTN"]
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::panel(r#"{"name": "Panel", "version": "1.23", "genes": []}"#)]
+    #[case::api(
+        r#"{"results": [{"entity_name": "TTN", "panel": {"name": "Panel", "version": 1.23}}]}"#
+    )]
+    fn parse_panelapp_name_version(#[case] content: &str) -> Result<(), anyhow::Error> {
+        let panel = super::parse_panelapp(content)?;
+
+        assert_eq!(panel.name.as_deref(), Some("Panel"));
+        assert_eq!(panel.version.as_deref(), Some("1.23"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn provenance() {
+        let panel = super::Panel {
+            genes: vec!["TP53".into(), "BRCA1".into()],
+            name: Some("Panel".into()),
+            version: None,
+            checksum: "abc".into(),
+        };
+        let hgnc_ids = ["HGNC:11998".to_string(), "HGNC:1100".to_string()];
+
+        let provenance = panel.provenance("panel.txt", &hgnc_ids);
+
+        assert_eq!(provenance.path, "panel.txt");
+        assert_eq!(provenance.name.as_deref(), Some("Panel"));
+        assert_eq!(provenance.checksum, "abc");
+        assert!(provenance.resolved_at.is_some());
+        assert_eq!(provenance.hgnc_ids, vec!["HGNC:1100", "HGNC:11998"]);
+    }
+
+    #[test]
+    fn parse_panelapp_invalid() {
+        assert!(super::parse_panelapp(r#"{"name": "Panel"}"#).is_err());
+        assert!(super::parse_panelapp("[").is_err());
+    }
+}
@@ -101,6 +101,210 @@ pub fn load_hgnc_to_inheritance_map<P: AsRef<std::path::Path>>(
     Ok(result)
 }
 
+/// Name of the file in the `hpo` directory that caches the result of
+/// `load_hgnc_to_inheritance_map`.
+pub const HGNC_TO_MOI_CACHE: &str = "hgnc_to_moi.cache.json";
+
+/// Cached `HgncToMoiMap` together with the fingerprint of its sources.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CachedHgncToMoiMap {
+    /// Fingerprint of worker version and source files, see `cache_fingerprint`.
+    fingerprint: String,
+    /// The cached map.
+    hgnc_to_moi: HgncToMoiMap,
+}
+
+/// Return the fingerprint of the worker version and the size and modification time of
+/// the source files in the `hpo` directory at `path`.
+fn cache_fingerprint(path: &std::path::Path) -> Result<String, anyhow::Error> {
+    let mut result = crate::common::worker_version().to_string();
+    for name in ["phenotype_to_genes.txt", "hgnc_xlink.tsv"] {
+        let metadata = std::fs::metadata(path.join(name))
+            .map_err(|e| anyhow::anyhow!("could not access {}: {}", name, e))?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        result.push_str(&format!(
+            "|{}:{}:{}",
+            name,
+            metadata.len(),
+            modified.as_nanos()
+        ));
+    }
+    Ok(result)
+}
+
+/// Like `load_hgnc_to_inheritance_map` but use the cache file `HGNC_TO_MOI_CACHE` in
+/// the `hpo` directory if it is up to date.
+///
+/// The cache is rebuilt if the worker version or the source files changed.  Problems
+/// writing the cache, e.g., for read-only databases, are only logged.
+pub fn load_hgnc_to_inheritance_map_cached<P: AsRef<std::path::Path>>(
+    path: &P,
+) -> Result<HgncToMoiMap, anyhow::Error> {
+    let path_cache = path.as_ref().join(HGNC_TO_MOI_CACHE);
+    let fingerprint = cache_fingerprint(path.as_ref())?;
+
+    let cached = std::fs::read(&path_cache)
+        .ok()
+        .and_then(|buf| serde_json::from_slice::<CachedHgncToMoiMap>(&buf).ok());
+    match cached {
+        Some(cached) if cached.fingerprint == fingerprint => {
+            tracing::debug!("using cached HGNC to MOI map from {}", path_cache.display());
+            return Ok(cached.hgnc_to_moi);
+        }
+        Some(_) => tracing::info!("cached HGNC to MOI map is outdated, rebuilding"),
+        None => tracing::debug!("no cached HGNC to MOI map, building"),
+    }
+
+    let hgnc_to_moi = load_hgnc_to_inheritance_map(path)?;
+    let cached = CachedHgncToMoiMap {
+        fingerprint,
+        hgnc_to_moi,
+    };
+    let written = tempfile::NamedTempFile::new_in(path.as_ref())
+        .map_err(anyhow::Error::from)
+        .and_then(|mut tmp_file| {
+            serde_json::to_writer(&mut tmp_file, &cached)?;
+            tmp_file.persist(&path_cache)?;
+            Ok(())
+        });
+    if let Err(e) = written {
+        tracing::warn!(
+            "could not write HGNC to MOI map cache to {}: {}",
+            path_cache.display(),
+            e
+        );
+    }
+
+    Ok(cached.hgnc_to_moi)
+}
+
+/// Similarity of a gene to the HPO terms of a query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhenotypeMatch {
+    /// Similarity score between 0 and 1.
+    pub score: f32,
+    /// The HPO terms of the query annotated to the gene.
+    pub matched_terms: Vec<String>,
+}
+
+impl From<PhenotypeMatch> for pbs_output::PhenotypeMatch {
+    fn from(value: PhenotypeMatch) -> Self {
+        pbs_output::PhenotypeMatch {
+            score: value.score,
+            matched_terms: value.matched_terms,
+        }
+    }
+}
+
+/// Phenotype similarity of genes to the HPO terms of a query.
+///
+/// The information content of a term is `-ln(p)` with `p` the fraction of all
+/// annotated genes that are annotated to the term.  As `phenotype_to_genes.txt`
+/// propagates the annotations to the ancestor terms, a gene matches a query term if
+/// it is annotated to the term or one of its descendants.  The score of a gene is the
+/// information content of the matched query terms relative to that of all query
+/// terms, similar to Resnik's measure with the query term as the common ancestor.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhenotypeScores {
+    /// The matches by HGNC gene ID, only for genes matching any term.
+    by_hgnc_id: std::collections::HashMap<String, PhenotypeMatch>,
+}
+
+impl PhenotypeScores {
+    /// Compute the scores for the query `terms` from `phenotype_to_genes.txt` and
+    /// `hgnc_xlink.tsv` in the `hpo` directory at `path`.
+    pub fn load<P: AsRef<std::path::Path>>(
+        path: &P,
+        terms: &[String],
+    ) -> Result<Self, anyhow::Error> {
+        if terms.is_empty() {
+            return Ok(Default::default());
+        }
+        let entries =
+            phenotype_to_genes::load_entries(&path.as_ref().join("phenotype_to_genes.txt"))
+                .map_err(|e| anyhow::anyhow!("error loading phenotype_to_genes.txt: {}", e))?;
+        let ncbi_to_hgnc = hgnc_xlink::load_ncbi_to_hgnc(path.as_ref().join("hgnc_xlink.tsv"))
+            .map_err(|e| anyhow::anyhow!("error loading hgnc_xlink.tsv: {}", e))?;
+        Ok(Self::compute(&entries, &ncbi_to_hgnc, terms))
+    }
+
+    /// Compute the scores for the query `terms` from the annotation `entries`.
+    fn compute(
+        entries: &[phenotype_to_genes::Entry],
+        ncbi_to_hgnc: &std::collections::HashMap<u32, Vec<String>>,
+        terms: &[String],
+    ) -> Self {
+        let mut all_genes = std::collections::HashSet::new();
+        let mut term_genes = indexmap::IndexMap::<&str, std::collections::HashSet<&str>>::new();
+        for term in terms {
+            term_genes.insert(term.as_str(), Default::default());
+        }
+        for entry in entries {
+            let Some(hgnc_ids) = entry
+                .ncbi_gene_id
+                .and_then(|ncbi_gene_id| ncbi_to_hgnc.get(&ncbi_gene_id))
+            else {
+                continue;
+            };
+            for hgnc_id in hgnc_ids {
+                all_genes.insert(hgnc_id.as_str());
+                if let Some(genes) = term_genes.get_mut(entry.hpo_id.as_str()) {
+                    genes.insert(hgnc_id.as_str());
+                }
+            }
+        }
+
+        let mut term_ics = Vec::new();
+        for (term, genes) in &term_genes {
+            if genes.is_empty() {
+                tracing::warn!("HPO term {} has no annotated genes, ignoring", term);
+            } else {
+                let ic = (all_genes.len() as f64 / genes.len() as f64).ln();
+                term_ics.push((*term, genes, ic));
+            }
+        }
+        let total_ic = term_ics.iter().map(|(_, _, ic)| ic).sum::<f64>();
+        if total_ic <= 0.0 {
+            return Default::default();
+        }
+
+        let mut by_hgnc_id = std::collections::HashMap::<String, (f64, Vec<String>)>::new();
+        for (term, genes, ic) in &term_ics {
+            for hgnc_id in genes.iter() {
+                let (sum_ic, matched_terms) = by_hgnc_id.entry(hgnc_id.to_string()).or_default();
+                *sum_ic += ic;
+                matched_terms.push(term.to_string());
+            }
+        }
+        Self {
+            by_hgnc_id: by_hgnc_id
+                .into_iter()
+                .map(|(hgnc_id, (sum_ic, matched_terms))| {
+                    let phenotype_match = PhenotypeMatch {
+                        score: (sum_ic / total_ic) as f32,
+                        matched_terms,
+                    };
+                    (hgnc_id, phenotype_match)
+                })
+                .collect(),
+        }
+    }
+
+    /// Return the match of the gene with `hgnc_id`, if it matches any term.
+    pub fn get(&self, hgnc_id: &str) -> Option<&PhenotypeMatch> {
+        self.by_hgnc_id.get(hgnc_id)
+    }
+
+    /// Return the score of the gene with `hgnc_id`, zero if it matches no term.
+    pub fn score(&self, hgnc_id: &str) -> f32 {
+        self.get(hgnc_id)
+            .map_or(0.0, |phenotype_match| phenotype_match.score)
+    }
+}
+
 /// Code for accessing the `phenotype_to_genes.tsv` file.
 pub(super) mod phenotype_to_genes {
     /// Data structure for representing an entry of the table.
@@ -196,6 +400,95 @@ pub(super) mod hgnc_xlink {
 
 #[cfg(test)]
 mod test {
+    /// Write the sources of the HGNC to MOI map with the rows `phenotypes` to `path`.
+    fn write_hpo_dir(path: &std::path::Path, phenotypes: &[&str]) -> Result<(), anyhow::Error> {
+        std::fs::write(
+            path.join("hgnc_xlink.tsv"),
+            "hgnc_id\tensembl_gene_id\tncbi_gene_id\tgene_symbol\n\
+            HGNC:1100\tENSG00000012048\t672\tBRCA1\n\
+            HGNC:11998\tENSG00000141510\t7157\tTP53\n",
+        )?;
+        let mut content = String::from("ncbi_gene_id\tgene_symbol\thpo_id\thpo_name\n");
+        for row in phenotypes {
+            content.push_str(row);
+            content.push('\n');
+        }
+        std::fs::write(path.join("phenotype_to_genes.txt"), content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn load_hgnc_to_inheritance_map_cached() -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let path = tmp_dir.to_path_buf();
+        write_hpo_dir(
+            &path,
+            &["672\tBRCA1\tHP:0000006\tAutosomal dominant inheritance"],
+        )?;
+
+        let built = super::load_hgnc_to_inheritance_map_cached(&path)?;
+        assert!(path.join(super::HGNC_TO_MOI_CACHE).exists());
+        assert_eq!(built, super::load_hgnc_to_inheritance_map(&path)?);
+        assert_eq!(super::load_hgnc_to_inheritance_map_cached(&path)?, built);
+
+        // Changing the sources invalidates the cache.
+        write_hpo_dir(
+            &path,
+            &[
+                "672\tBRCA1\tHP:0000006\tAutosomal dominant inheritance",
+                "7157\tTP53\tHP:0000006\tAutosomal dominant inheritance",
+            ],
+        )?;
+        let rebuilt = super::load_hgnc_to_inheritance_map_cached(&path)?;
+        assert_eq!(
+            rebuilt.keys().cloned().collect::<Vec<_>>(),
+            vec!["HGNC:1100".to_string(), "HGNC:11998".to_string()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn phenotype_scores_compute() {
+        let entry = |ncbi_gene_id: u32, hpo_id: &str| super::phenotype_to_genes::Entry {
+            ncbi_gene_id: Some(ncbi_gene_id),
+            gene_symbol: String::new(),
+            hpo_id: hpo_id.to_string(),
+            hpo_name: String::new(),
+        };
+        // HP:1 is annotated to all four genes, HP:2 to one and HP:3 to two of them.
+        let entries = vec![
+            entry(1, "HP:1"),
+            entry(2, "HP:1"),
+            entry(3, "HP:1"),
+            entry(4, "HP:1"),
+            entry(1, "HP:2"),
+            entry(1, "HP:3"),
+            entry(2, "HP:3"),
+            entry(2, "HP:3"),
+        ];
+        let ncbi_to_hgnc: std::collections::HashMap<u32, Vec<String>> = (1..=4)
+            .map(|id| (id, vec![format!("HGNC:{}", id)]))
+            .collect();
+        let terms = ["HP:1", "HP:2", "HP:3", "HP:4"].map(String::from);
+
+        let scores = super::PhenotypeScores::compute(&entries, &ncbi_to_hgnc, &terms);
+
+        let ln2 = 2f32.ln();
+        let ln4 = 4f32.ln();
+        let gene1 = scores.get("HGNC:1").unwrap();
+        assert!((gene1.score - 1.0).abs() < 1e-6);
+        assert_eq!(gene1.matched_terms, vec!["HP:1", "HP:2", "HP:3"]);
+        assert!((scores.score("HGNC:2") - ln2 / (ln4 + ln2)).abs() < 1e-6);
+        assert_eq!(scores.score("HGNC:3"), 0.0);
+        assert_eq!(scores.get("HGNC:3").unwrap().matched_terms, vec!["HP:1"]);
+        assert_eq!(scores.score("HGNC:5"), 0.0);
+        assert_eq!(
+            super::PhenotypeScores::compute(&entries, &ncbi_to_hgnc, &[]),
+            Default::default()
+        );
+    }
+
     #[test]
     pub fn test_mode_of_inheritance_from_hpo_id() {
         assert_eq!(
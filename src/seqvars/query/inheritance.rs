@@ -0,0 +1,365 @@
+//! Evaluation of the inheritance models of the query.
+//!
+//! Besides the recessive mode that is handled in `passes_for_gene()`, the query can
+//! select de novo, autosomal dominant, and mitochondrial inheritance.  These need the
+//! affection status of the parents and are thus evaluated against the pedigree.  Each
+//! variant of a gene is kept if it matches any of the selected models and the first
+//! matching model is reported in the output record.
+
+use crate::common::Genotype;
+
+use super::schema::{
+    data::VariantRecord,
+    query::{InheritanceModel, QuerySettingsGenotype},
+};
+
+/// A parent of the index from the query with the affection status from the pedigree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parent {
+    /// Name of the sample.
+    pub name: String,
+    /// Whether the parent is affected.
+    pub affected: bool,
+}
+
+/// The index and its parents from the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Family {
+    /// Name of the index sample.
+    pub index: String,
+    /// The father, if in the query.
+    pub father: Option<Parent>,
+    /// The mother, if in the query.
+    pub mother: Option<Parent>,
+}
+
+/// Supporting code for `Family`.
+pub(crate) mod family {
+    /// Error type for `Family::from_pedigree()`.
+    #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+    pub enum Error {
+        #[error("Sample {0:?} from query is not in pedigree")]
+        SampleNotInPedigree(String),
+        #[error("Cannot determine index, affected samples that are not parents: {0:?}")]
+        AmbiguousIndex(Vec<String>),
+    }
+}
+
+impl Family {
+    /// Derive the family from the samples of `genotype` and `pedigree`.
+    ///
+    /// The index is the only affected sample of the query that is not a parent of
+    /// another sample of the query.
+    ///
+    /// # Errors
+    ///
+    /// * `family::Error` if a sample is not in the pedigree or the index is ambiguous.
+    pub fn from_pedigree(
+        genotype: &QuerySettingsGenotype,
+        pedigree: &mehari::ped::PedigreeByName,
+    ) -> Result<Self, family::Error> {
+        let individuals = genotype
+            .sample_genotypes
+            .keys()
+            .map(|sample| {
+                pedigree
+                    .individuals
+                    .get(sample)
+                    .ok_or_else(|| family::Error::SampleNotInPedigree(sample.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let is_parent = |name: &str| {
+            individuals.iter().any(|individual| {
+                individual.father.as_deref() == Some(name)
+                    || individual.mother.as_deref() == Some(name)
+            })
+        };
+        let candidates = individuals
+            .iter()
+            .filter(|individual| {
+                individual.disease == mehari::ped::Disease::Affected && !is_parent(&individual.name)
+            })
+            .collect::<Vec<_>>();
+        let index = match candidates.as_slice() {
+            [index] => *index,
+            _ => {
+                return Err(family::Error::AmbiguousIndex(
+                    candidates
+                        .iter()
+                        .map(|individual| individual.name.clone())
+                        .collect(),
+                ))
+            }
+        };
+
+        let parent = |name: &Option<String>| {
+            name.as_ref()
+                .filter(|name| genotype.sample_genotypes.contains_key(*name))
+                .map(|name| Parent {
+                    name: name.clone(),
+                    affected: pedigree.individuals[name].disease == mehari::ped::Disease::Affected,
+                })
+        };
+        Ok(Self {
+            index: index.name.clone(),
+            father: parent(&index.father),
+            mother: parent(&index.mother),
+        })
+    }
+
+    /// Return the variants of one gene that match any of `models` with the first
+    /// matching model.
+    pub fn filter_gene(
+        &self,
+        models: &[InheritanceModel],
+        seqvars: Vec<VariantRecord>,
+    ) -> Result<Vec<(VariantRecord, InheritanceModel)>, anyhow::Error> {
+        let mut result = Vec::new();
+        for seqvar in seqvars {
+            if let Some(model) = self.matched_model(models, &seqvar)? {
+                result.push((seqvar, model));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Return the first of `models` that `seqvar` matches, if any.
+    fn matched_model(
+        &self,
+        models: &[InheritanceModel],
+        seqvar: &VariantRecord,
+    ) -> Result<Option<InheritanceModel>, anyhow::Error> {
+        let index_gt = genotype(seqvar, &self.index)?;
+        if !matches!(index_gt, Genotype::Het | Genotype::HomAlt) {
+            return Ok(None);
+        }
+        let father_gt = self
+            .father
+            .as_ref()
+            .map(|father| genotype(seqvar, &father.name))
+            .transpose()?;
+        let mother_gt = self
+            .mother
+            .as_ref()
+            .map(|mother| genotype(seqvar, &mother.name))
+            .transpose()?;
+        let chrom = ::annonars::common::cli::canonicalize(&seqvar.vcf_variant.chrom);
+
+        for model in models {
+            let passes = match model {
+                // Both parents must be given and hom. ref.
+                InheritanceModel::DeNovo => {
+                    father_gt == Some(Genotype::HomRef) && mother_gt == Some(Genotype::HomRef)
+                }
+                // Het. index on an autosome, affected parents het. and unaffected ones
+                // hom. ref.
+                InheritanceModel::AutosomalDominant => {
+                    chrom.parse::<u32>().is_ok()
+                        && index_gt == Genotype::Het
+                        && [(&self.father, father_gt), (&self.mother, mother_gt)]
+                            .into_iter()
+                            .all(|(parent, gt)| match (parent, gt) {
+                                (Some(parent), Some(gt)) => {
+                                    gt == if parent.affected {
+                                        Genotype::Het
+                                    } else {
+                                        Genotype::HomRef
+                                    }
+                                }
+                                _ => true,
+                            })
+                }
+                // On chrMT, any given mother must carry the variant.
+                InheritanceModel::Mitochondrial => {
+                    chrom == "MT"
+                        && mother_gt
+                            .map_or(true, |gt| matches!(gt, Genotype::Het | Genotype::HomAlt))
+                }
+            };
+            if passes {
+                return Ok(Some(*model));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Return the parsed genotype of `sample`, missing calls are treated as no-call.
+fn genotype(seqvar: &VariantRecord, sample: &str) -> Result<Genotype, anyhow::Error> {
+    match seqvar
+        .call_infos
+        .get(sample)
+        .and_then(|call_info| call_info.genotype.as_ref())
+    {
+        Some(gt) => gt
+            .parse()
+            .map_err(|e| anyhow::anyhow!("could not parse genotype of {}: {}", sample, e)),
+        None => Ok(Genotype::WithNoCall),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::seqvars::query::schema::data::{CallInfo, VariantRecord, VcfVariant};
+    use crate::seqvars::query::schema::query::{
+        GenotypeChoice, InheritanceModel, QuerySettingsGenotype, SampleGenotypeChoice,
+    };
+
+    use super::{Family, Parent};
+
+    /// Build a trio pedigree with the given affection of index, father, and mother.
+    fn trio_pedigree(affected: [bool; 3]) -> mehari::ped::PedigreeByName {
+        use mehari::ped::{Disease, Individual, Sex};
+
+        let individuals = [
+            ("index", Some("father"), Some("mother"), Sex::Male),
+            ("father", None, None, Sex::Male),
+            ("mother", None, None, Sex::Female),
+        ]
+        .into_iter()
+        .zip(affected)
+        .map(|((name, father, mother, sex), affected)| {
+            (
+                name.to_string(),
+                Individual {
+                    family: "FAM".to_string(),
+                    name: name.to_string(),
+                    father: father.map(String::from),
+                    mother: mother.map(String::from),
+                    sex,
+                    disease: if affected {
+                        Disease::Affected
+                    } else {
+                        Disease::Unaffected
+                    },
+                },
+            )
+        })
+        .collect();
+        mehari::ped::PedigreeByName { individuals }
+    }
+
+    fn query_settings(samples: &[&str]) -> QuerySettingsGenotype {
+        QuerySettingsGenotype {
+            sample_genotypes: samples
+                .iter()
+                .map(|sample| {
+                    (
+                        sample.to_string(),
+                        SampleGenotypeChoice {
+                            sample: sample.to_string(),
+                            genotype: GenotypeChoice::Any,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            inheritance_models: vec![
+                InheritanceModel::DeNovo,
+                InheritanceModel::AutosomalDominant,
+                InheritanceModel::Mitochondrial,
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn seqvar(chrom: &str, trio_gts: &str) -> VariantRecord {
+        VariantRecord {
+            vcf_variant: VcfVariant {
+                chrom: chrom.to_string(),
+                pos: 100,
+                ref_allele: "A".to_string(),
+                alt_allele: "G".to_string(),
+            },
+            call_infos: ["index", "father", "mother"]
+                .into_iter()
+                .zip(trio_gts.split(','))
+                .map(|(sample, gt)| {
+                    (
+                        sample.to_string(),
+                        CallInfo {
+                            sample: sample.to_string(),
+                            genotype: Some(gt.to_string()),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn from_pedigree_affected_father() -> Result<(), anyhow::Error> {
+        let family = Family::from_pedigree(
+            &query_settings(&["index", "father"]),
+            &trio_pedigree([true, true, false]),
+        )?;
+
+        assert_eq!(
+            family,
+            Family {
+                index: "index".to_string(),
+                father: Some(Parent {
+                    name: "father".to_string(),
+                    affected: true,
+                }),
+                mother: None,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_pedigree_ambiguous() {
+        assert!(Family::from_pedigree(
+            &query_settings(&["index", "father", "mother"]),
+            &trio_pedigree([false, false, false]),
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case::de_novo("1", "0/1,0/0,0/0", [true, false, false], Some(InheritanceModel::DeNovo))]
+    #[case::de_novo_hemi("X", "1,0,0/0", [true, false, false], Some(InheritanceModel::DeNovo))]
+    #[case::inherited_unaffected("1", "0/1,0/1,0/0", [true, false, false], None)]
+    #[case::dominant_affected_father(
+        "1",
+        "0/1,0/1,0/0",
+        [true, true, false],
+        Some(InheritanceModel::AutosomalDominant)
+    )]
+    #[case::dominant_affected_ref("1", "0/1,0/0,0/0", [true, true, false], Some(InheritanceModel::DeNovo))]
+    #[case::dominant_hom_index("1", "1/1,0/1,0/0", [true, true, false], None)]
+    #[case::dominant_not_autosomal("X", "0/1,0/1,0/0", [true, true, false], None)]
+    #[case::mitochondrial("MT", "1,0,1", [true, false, false], Some(InheritanceModel::Mitochondrial))]
+    #[case::mitochondrial_chr_m(
+        "chrM",
+        "1,1,1",
+        [true, false, false],
+        Some(InheritanceModel::Mitochondrial)
+    )]
+    #[case::mitochondrial_mother_ref("MT", "1,1,0", [true, false, false], None)]
+    #[case::index_ref("1", "0/0,0/0,0/0", [true, false, false], None)]
+    fn filter_gene(
+        #[case] chrom: &str,
+        #[case] trio_gts: &str,
+        #[case] affected: [bool; 3],
+        #[case] expected: Option<InheritanceModel>,
+    ) -> Result<(), anyhow::Error> {
+        let query_settings = query_settings(&["index", "father", "mother"]);
+        let family = Family::from_pedigree(&query_settings, &trio_pedigree(affected))?;
+
+        let result = family.filter_gene(
+            &query_settings.inheritance_models,
+            vec![seqvar(chrom, trio_gts)],
+        )?;
+
+        assert_eq!(result.first().map(|(_, model)| *model), expected);
+
+        Ok(())
+    }
+}
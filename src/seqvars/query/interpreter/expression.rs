@@ -0,0 +1,216 @@
+use crate::common::expression::{self, Expression, Value};
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+use crate::seqvars::query::{
+    annonars::Annotator,
+    lof,
+    schema::data::{Ac, Af, Carriers, Hemi, VariantRecord},
+    variant_related_annotation::{score_columns, scores},
+};
+
+/// Fields that are available from the `VariantRecord` itself.
+pub const RECORD_FIELDS: &[&str] = &[
+    "chrom",
+    "pos",
+    "ref",
+    "alt",
+    "gene_symbol",
+    "nmd_escape",
+    "lof_confidence",
+    "gnomad_af",
+    "gnomad_exomes_af",
+    "gnomad_exomes_het",
+    "gnomad_exomes_hom",
+    "gnomad_exomes_hemi",
+    "gnomad_genomes_af",
+    "gnomad_genomes_het",
+    "gnomad_genomes_hom",
+    "gnomad_genomes_hemi",
+    "gnomad_mtdna_af",
+    "gnomad_mtdna_het",
+    "gnomad_mtdna_hom",
+    "helixmtdb_af",
+    "helixmtdb_het",
+    "helixmtdb_hom",
+    "inhouse_carriers",
+    "inhouse_het",
+    "inhouse_hom",
+    "inhouse_hemi",
+    "inhouse_affected",
+    "inhouse_unaffected",
+];
+
+/// Ensure that `expression` only references known fields.
+///
+/// Besides `RECORD_FIELDS`, the names of the score columns may be used.
+pub fn check_fields(expression: &Expression) -> Result<(), expression::Error> {
+    let score_columns = score_columns();
+    let known = RECORD_FIELDS
+        .iter()
+        .copied()
+        .chain(score_columns.iter().map(|column| column.name.as_str()))
+        .collect::<Vec<_>>();
+    expression.check_fields(&known)
+}
+
+/// Return whether `expression` references any score fields that need a database lookup.
+pub fn needs_scores(expression: &Expression) -> bool {
+    expression
+        .fields()
+        .iter()
+        .any(|name| !RECORD_FIELDS.contains(name))
+}
+
+/// Return the value of the field `name` for `seqvar`.
+fn record_value(seqvar: &VariantRecord, name: &str) -> Option<Value> {
+    let freqs = &seqvar.population_frequencies;
+    Some(match name {
+        "chrom" => Value::from(seqvar.vcf_variant.chrom.as_str()),
+        "pos" => Value::from(seqvar.vcf_variant.pos),
+        "ref" => Value::from(seqvar.vcf_variant.ref_allele.as_str()),
+        "alt" => Value::from(seqvar.vcf_variant.alt_allele.as_str()),
+        "gene_symbol" => Value::from(seqvar.ann_fields.first()?.gene_symbol.as_str()),
+        "nmd_escape" => Value::from(lof::nmd_escape(seqvar.ann_fields.first()?)?),
+        "lof_confidence" => match lof::lof_confidence(seqvar.ann_fields.first()?) {
+            pbs_output::LofConfidence::High => Value::from("HC"),
+            pbs_output::LofConfidence::Low => Value::from("LC"),
+            pbs_output::LofConfidence::Unspecified => return None,
+        },
+        "gnomad_af" => Value::from(freqs.gnomad_exomes.af().max(freqs.gnomad_genomes.af())),
+        "gnomad_exomes_af" => Value::from(freqs.gnomad_exomes.af()),
+        "gnomad_exomes_het" => Value::from(freqs.gnomad_exomes.het()),
+        "gnomad_exomes_hom" => Value::from(freqs.gnomad_exomes.hom()),
+        "gnomad_exomes_hemi" => Value::from(freqs.gnomad_exomes.hemi()),
+        "gnomad_genomes_af" => Value::from(freqs.gnomad_genomes.af()),
+        "gnomad_genomes_het" => Value::from(freqs.gnomad_genomes.het()),
+        "gnomad_genomes_hom" => Value::from(freqs.gnomad_genomes.hom()),
+        "gnomad_genomes_hemi" => Value::from(freqs.gnomad_genomes.hemi()),
+        "gnomad_mtdna_af" => Value::from(freqs.gnomad_mtdna.af()),
+        "gnomad_mtdna_het" => Value::from(freqs.gnomad_mtdna.het()),
+        "gnomad_mtdna_hom" => Value::from(freqs.gnomad_mtdna.hom()),
+        "helixmtdb_af" => Value::from(freqs.helixmtdb.af()),
+        "helixmtdb_het" => Value::from(freqs.helixmtdb.het()),
+        "helixmtdb_hom" => Value::from(freqs.helixmtdb.hom()),
+        "inhouse_carriers" => Value::from(freqs.inhouse.carriers()),
+        "inhouse_het" => Value::from(freqs.inhouse.het()),
+        "inhouse_hom" => Value::from(freqs.inhouse.hom()),
+        "inhouse_hemi" => Value::from(freqs.inhouse.hemi),
+        "inhouse_affected" => Value::from(freqs.inhouse.strata.as_ref()?.affected),
+        "inhouse_unaffected" => Value::from(freqs.inhouse.strata.as_ref()?.unaffected),
+        _ => return None,
+    })
+}
+
+/// Return the value of the score `name` from `scores`.
+fn score_value(scores: &pbs_output::ScoreAnnotations, name: &str) -> Option<Value> {
+    use pbjson_types::value::Kind;
+
+    let entry = scores.entries.iter().find(|entry| entry.key == name)?;
+    match entry.value.as_ref()?.kind.as_ref()? {
+        Kind::NumberValue(value) => Some(Value::Number(*value)),
+        Kind::StringValue(value) => Some(Value::String(value.clone())),
+        Kind::BoolValue(value) => Some(Value::Bool(*value)),
+        _ => None,
+    }
+}
+
+/// Determine whether the `VariantRecord` passes the expression filter.
+///
+/// Scores are only queried from `annotator` if `needs_scores` is set.
+pub fn passes(
+    expression: Option<&Expression>,
+    needs_scores: bool,
+    annotator: Option<&Annotator>,
+    seqvar: &VariantRecord,
+) -> Result<bool, anyhow::Error> {
+    let Some(expression) = expression else {
+        return Ok(true);
+    };
+
+    let score_annotations = match annotator {
+        Some(annotator) if needs_scores => scores(seqvar, annotator)?,
+        _ => None,
+    };
+    let res = expression.eval(|name| {
+        record_value(seqvar, name).or_else(|| score_value(score_annotations.as_ref()?, name))
+    });
+    if !res {
+        tracing::trace!(
+            "variant {:?} fails expression filter {:?}",
+            seqvar,
+            expression.source()
+        );
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence, Rank};
+
+    use crate::common::expression::Expression;
+    use crate::seqvars::query::schema::data::{
+        InHouseFrequencies, InHouseStrata, NuclearFrequencies, PopulationFrequencies,
+        VariantRecord, VcfVariant,
+    };
+
+    #[rstest::rstest]
+    #[case("gnomad_af < 0.001", false)]
+    #[case("gnomad_af < 0.01 && chrom == '1'", true)]
+    #[case("gnomad_exomes_het > 1 || gnomad_genomes_het > 1", true)]
+    #[case("gene_symbol == 'TTN'", false)]
+    #[case("lof_confidence == 'HC' && !nmd_escape", true)]
+    #[case("inhouse_affected == 0 && inhouse_unaffected > 0", true)]
+    fn record_value(#[case] source: &str, #[case] expected: bool) {
+        let seqvar = VariantRecord {
+            vcf_variant: VcfVariant {
+                chrom: "1".to_string(),
+                pos: 123,
+                ref_allele: "A".to_string(),
+                alt_allele: "G".to_string(),
+            },
+            ann_fields: vec![AnnField {
+                consequences: vec![Consequence::StopGained],
+                rank: Some(Rank { ord: 2, total: 5 }),
+                ..Default::default()
+            }],
+            population_frequencies: PopulationFrequencies {
+                gnomad_exomes: NuclearFrequencies {
+                    an: 1000,
+                    het: 2,
+                    ..Default::default()
+                },
+                inhouse: InHouseFrequencies {
+                    an: 20,
+                    het: 1,
+                    strata: Some(InHouseStrata {
+                        unaffected: 1,
+                        male: 1,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let expression = Expression::parse(source).unwrap();
+        assert!(super::check_fields(&expression).is_ok());
+        assert!(!super::needs_scores(&expression));
+
+        assert_eq!(
+            expression.eval(|name| super::record_value(&seqvar, name)),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("cadd_phred > 25", true, true)]
+    #[case("gnomad_af < 0.001 && revel > 0.5", true, true)]
+    #[case("gnomad_af < 0.001", true, false)]
+    #[case("unknown_score > 0.5", false, true)]
+    fn check_fields(#[case] source: &str, #[case] known: bool, #[case] needs_scores: bool) {
+        let expression = Expression::parse(source).unwrap();
+        assert_eq!(super::check_fields(&expression).is_ok(), known);
+        assert_eq!(super::needs_scores(&expression), needs_scores);
+    }
+}
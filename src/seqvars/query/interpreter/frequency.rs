@@ -1,5 +1,5 @@
 use crate::seqvars::query::schema::{
-    data::{Af, VariantRecord},
+    data::{Af, Carriers as _, VariantRecord},
     query::CaseQuery,
 };
 
@@ -53,7 +53,10 @@ pub fn passes(query: &CaseQuery, s: &VariantRecord) -> Result<bool, anyhow::Erro
                     > frequency.inhouse.max_het.expect("tested before")
                 || frequency.inhouse.max_hom.is_some()
                     && s.population_frequencies.inhouse.hom
-                        > frequency.inhouse.max_hom.expect("tested before"))
+                        > frequency.inhouse.max_hom.expect("tested before")
+                || frequency.inhouse.max_carriers.is_some()
+                    && s.population_frequencies.inhouse.carriers()
+                        > frequency.inhouse.max_carriers.expect("tested before"))
         {
             tracing::trace!(
                 "variant {:?} fails in-house frequency filter {:?}",
@@ -116,7 +119,10 @@ pub fn passes(query: &CaseQuery, s: &VariantRecord) -> Result<bool, anyhow::Erro
                         > frequency.inhouse.max_hom.expect("tested before")
                 || frequency.inhouse.max_hemi.is_some()
                     && s.population_frequencies.inhouse.hemi
-                        > frequency.inhouse.max_hemi.expect("tested before"))
+                        > frequency.inhouse.max_hemi.expect("tested before")
+                || frequency.inhouse.max_carriers.is_some()
+                    && s.population_frequencies.inhouse.carriers()
+                        > frequency.inhouse.max_carriers.expect("tested before"))
         {
             tracing::trace!(
                 "variant {:?} fails in-house frequency filter {:?}",
@@ -138,12 +144,12 @@ mod test {
 
     use crate::seqvars::query::schema::{
         data::{
-            MitochondrialFrequencies, NuclearFrequencies, PopulationFrequencies, VariantRecord,
-            VcfVariant,
+            InHouseFrequencies, MitochondrialFrequencies, NuclearFrequencies,
+            PopulationFrequencies, VariantRecord, VcfVariant,
         },
         query::{
-            CaseQuery, MitochondrialFrequencySettings, NuclearFrequencySettings,
-            QuerySettingsFrequency,
+            CaseQuery, InhouseFrequencySettings, MitochondrialFrequencySettings,
+            NuclearFrequencySettings, QuerySettingsFrequency,
         },
     };
 
@@ -629,4 +635,69 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest]
+    // carriers: pass (no filter value)
+    #[case("1", 2, 1, 0, true, None, None, None, true)]
+    // carriers: pass
+    #[case("1", 2, 1, 0, true, None, None, Some(3), true)]
+    // carriers: fail
+    #[case("1", 2, 1, 1, true, None, None, Some(3), false)]
+    // carriers: pass (fail but filter is disabled)
+    #[case("1", 2, 1, 1, false, None, None, Some(3), true)]
+    // carriers: fail on chrMT
+    #[case("MT", 2, 2, 0, true, None, None, Some(3), false)]
+    // het. count: fail
+    #[case("1", 2, 0, 0, true, Some(1), None, None, false)]
+    // hom. count: fail on chrMT
+    #[case("MT", 0, 2, 0, true, None, Some(1), None, false)]
+    #[allow(clippy::too_many_arguments)]
+    fn passes_frequency_inhouse(
+        #[case] chrom: &str,
+        #[case] seqvar_inhouse_het: i32,
+        #[case] seqvar_inhouse_hom: i32,
+        #[case] seqvar_inhouse_hemi: i32,
+        #[case] query_inhouse_enabled: bool,
+        #[case] query_inhouse_het: Option<i32>,
+        #[case] query_inhouse_hom: Option<i32>,
+        #[case] query_inhouse_carriers: Option<i32>,
+        #[case] expected_pass_all: bool,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            frequency: QuerySettingsFrequency {
+                inhouse: InhouseFrequencySettings {
+                    enabled: query_inhouse_enabled,
+                    max_het: query_inhouse_het,
+                    max_hom: query_inhouse_hom,
+                    max_hemi: None,
+                    max_carriers: query_inhouse_carriers,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let seq_var = VariantRecord {
+            population_frequencies: PopulationFrequencies {
+                inhouse: InHouseFrequencies {
+                    an: 100,
+                    het: seqvar_inhouse_het,
+                    hom: seqvar_inhouse_hom,
+                    hemi: seqvar_inhouse_hemi,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            vcf_variant: VcfVariant {
+                chrom: chrom.to_string(),
+                pos: 1,
+                ref_allele: "G".into(),
+                alt_allele: "A".into(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(super::passes(&query, &seq_var)?, expected_pass_all);
+
+        Ok(())
+    }
 }
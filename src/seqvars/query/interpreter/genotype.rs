@@ -1,5 +1,5 @@
 use crate::seqvars::query::schema::{
-    data::VariantRecord,
+    data::{CallInfo, VariantRecord},
     query::{
         considered_no_call, CaseQuery, GenotypeChoice, MatchesGenotypeStr as _,
         QuerySettingsGenotype, RecessiveMode, RecessiveParents,
@@ -329,7 +329,8 @@ fn passes_non_recessive_mode(
 ) -> Result<bool, anyhow::Error> {
     for (sample_name, genotype_choice) in query_genotype.sample_genotypes.iter() {
         // Extract genotype from call info, skip if not present.
-        let genotype = if let Some(call_info) = seqvar.call_infos.get(sample_name) {
+        let call_info = seqvar.call_infos.get(sample_name);
+        let genotype = if let Some(call_info) = call_info {
             if let Some(genotype) = call_info.genotype.as_ref() {
                 genotype
             } else {
@@ -354,7 +355,14 @@ fn passes_non_recessive_mode(
                 );
                 return Ok(false);
             }
-        } else if !genotype_choice.genotype.matches(genotype)? {
+        } else if !genotype_choice.genotype.matches(genotype)?
+            && !absent_in_unaffected(
+                query_genotype,
+                genotype_choice.genotype,
+                call_info,
+                genotype,
+            )?
+        {
             // Handle case of non-nocall genotype.
             tracing::trace!(
                 "variant {:?} fails genotype filter {:?} on sample {}",
@@ -371,6 +379,34 @@ fn passes_non_recessive_mode(
     Ok(true) // All good up to the end.
 }
 
+/// Return whether the het. `genotype` of an unaffected sample in dominant mode has an
+/// allele fraction below `max_unaffected_ab` and the variant is thus considered absent.
+///
+/// Unaffected samples are the ones with the `Ref` choice after applying the pedigree.
+fn absent_in_unaffected(
+    query_genotype: &QuerySettingsGenotype,
+    genotype_choice: GenotypeChoice,
+    call_info: Option<&CallInfo>,
+    genotype: &str,
+) -> Result<bool, anyhow::Error> {
+    let Some(max_unaffected_ab) = query_genotype
+        .dominant
+        .as_ref()
+        .and_then(|dominant| dominant.max_unaffected_ab)
+    else {
+        return Ok(false);
+    };
+    if genotype_choice != GenotypeChoice::Ref || !GenotypeChoice::Het.matches(genotype)? {
+        return Ok(false);
+    }
+    Ok(
+        match call_info.and_then(|call_info| call_info.dp.zip(call_info.ad)) {
+            Some((dp, ad)) if dp > 0 => (ad as f64 / dp as f64) < max_unaffected_ab as f64,
+            _ => false,
+        },
+    )
+}
+
 #[cfg(test)]
 mod test {
     use crate::seqvars::query::schema::data::{CallInfo, VariantRecord, VcfVariant};
@@ -509,6 +545,8 @@ mod test {
                     ..Default::default()
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
 
         let seq_var = VariantRecord {
@@ -590,6 +628,8 @@ mod test {
                     ..Default::default()
                 },
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let sample_gts = sample_gts
             .split(',')
@@ -648,6 +688,8 @@ mod test {
                     ..Default::default()
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let seq_var = VariantRecord {
             call_infos: indexmap::indexmap! {
@@ -725,6 +767,8 @@ mod test {
                     ..Default::default()
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let seq_var = VariantRecord {
             call_infos: indexmap::indexmap! {
@@ -810,6 +854,8 @@ mod test {
                     ..Default::default()
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let seq_var = VariantRecord {
             vcf_variant: VcfVariant {
@@ -859,6 +905,8 @@ mod test {
                     ..Default::default()
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let seq_var = VariantRecord {
             vcf_variant: VcfVariant {
@@ -1223,6 +1271,8 @@ mod test {
                     ..Default::default()
                 },
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
         let sample_gts = sample_gts
             .split(',')
@@ -1264,4 +1314,64 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::ref_pass("0/0", Some(0), None, true)]
+    #[case::het_fail_no_threshold("0/1", Some(2), None, false)]
+    #[case::het_pass_below("0/1", Some(2), Some(0.1), true)]
+    #[case::het_fail_above("0/1", Some(10), Some(0.1), false)]
+    #[case::het_fail_no_ad("0/1", None, Some(0.1), false)]
+    #[case::hom_fail_below("1/1", Some(2), Some(0.1), false)]
+    fn passes_non_recessive_mode_dominant_unaffected_ab(
+        #[case] father_gt: &str,
+        #[case] father_ad: Option<i32>,
+        #[case] max_unaffected_ab: Option<f32>,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        use crate::seqvars::query::schema::query::QuerySettingsDominant;
+
+        let query_genotype = QuerySettingsGenotype {
+            sample_genotypes: indexmap::indexmap! {
+                String::from(INDEX_NAME) => SampleGenotypeChoice {
+                    sample: String::from(INDEX_NAME),
+                    genotype: Variant,
+                    ..Default::default()
+                },
+                String::from(FATHER_NAME) => SampleGenotypeChoice {
+                    sample: String::from(FATHER_NAME),
+                    genotype: Ref,
+                    ..Default::default()
+                },
+            },
+            dominant: Some(QuerySettingsDominant {
+                reduced_penetrance: false,
+                max_unaffected_ab,
+            }),
+            ..Default::default()
+        };
+        let seq_var = VariantRecord {
+            call_infos: indexmap::indexmap! {
+                String::from(INDEX_NAME) =>
+                CallInfo {
+                    genotype: Some("0/1".into()),
+                    ..Default::default()
+                },
+                String::from(FATHER_NAME) =>
+                CallInfo {
+                    genotype: Some(father_gt.into()),
+                    dp: Some(40),
+                    ad: father_ad,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::passes_non_recessive_mode(&query_genotype, &seq_var)?,
+            expected
+        );
+
+        Ok(())
+    }
 }
@@ -4,17 +4,53 @@ use std::collections::HashSet;
 
 mod clinvar;
 mod consequences;
+mod expression;
 mod frequency;
 mod genes_allowlist;
 mod genotype;
+mod phenotype;
 mod quality;
 mod regions_allowlist;
+mod variant_type;
 
 use super::{
     annonars::Annotator,
+    hpo::PhenotypeScores,
     schema::{data::VariantRecord, query::CaseQuery},
 };
 
+/// Ensure that the expression of `query`, if any, only references known fields.
+pub fn check_expression(query: &CaseQuery) -> Result<(), crate::common::expression::Error> {
+    query
+        .expression
+        .as_ref()
+        .map_or(Ok(()), expression::check_fields)
+}
+
+/// Ensure that `query` can be run without databases.
+///
+/// The ClinVar presence filter, score fields in the expression, the gene allow
+/// list, and the phenotype terms need database lookups.
+pub fn check_no_databases(query: &CaseQuery) -> Result<(), anyhow::Error> {
+    if query.clinvar.presence_required {
+        anyhow::bail!("ClinVar presence filter needs databases");
+    }
+    if query
+        .expression
+        .as_ref()
+        .is_some_and(expression::needs_scores)
+    {
+        anyhow::bail!("score fields in expression need databases");
+    }
+    if !query.locus.genes.is_empty() {
+        anyhow::bail!("gene allow list needs databases");
+    }
+    if !query.phenotype.terms.is_empty() {
+        anyhow::bail!("phenotype terms need databases");
+    }
+    Ok(())
+}
+
 /// Hold data structures that support the interpretation of one `CaseQuery`
 /// to multiple `StructuralVariant` records.
 #[derive(Debug, Default)]
@@ -23,6 +59,10 @@ pub struct QueryInterpreter {
     pub query: CaseQuery,
     /// Gene allowlist with HGNC IDs.
     pub hgnc_allowlist: HashSet<String>,
+    /// Whether the query expression needs score lookups.
+    pub expression_needs_scores: bool,
+    /// Phenotype similarity of the genes to the terms of the query.
+    pub phenotype_scores: PhenotypeScores,
 }
 
 /// Result type for `QueryInterpreter::passes_genotype()`.
@@ -35,37 +75,92 @@ pub struct PassesResult {
 impl QueryInterpreter {
     /// Construct new `QueryInterpreter` with the given query settings.
     pub fn new(query: CaseQuery, hgnc_allowlist: HashSet<String>) -> Self {
+        let expression_needs_scores = query
+            .expression
+            .as_ref()
+            .is_some_and(expression::needs_scores);
         QueryInterpreter {
             query,
             hgnc_allowlist,
+            expression_needs_scores,
+            phenotype_scores: Default::default(),
         }
     }
 
     /// Determine whether the annotated `VariantRecord` passes all criteria.
+    ///
+    /// Without `annotator`, the filters that need database lookups are skipped.
     pub fn passes(
         &self,
         seqvar: &VariantRecord,
-        annotator: &Annotator,
+        annotator: Option<&Annotator>,
     ) -> Result<PassesResult, anyhow::Error> {
+        // The variant type only depends on the alleles and is checked first.
+        if !variant_type::passes(&self.query, seqvar) {
+            return Ok(PassesResult { pass_all: false });
+        }
         // Check the filters first that are cheap to compute.
         let pass_frequency = frequency::passes(&self.query, seqvar)?;
         let pass_consequences = consequences::passes(&self.query, seqvar)?;
         let pass_quality = quality::passes(&self.query, seqvar)?;
         let pass_genes_allowlist = genes_allowlist::passes(&self.hgnc_allowlist, seqvar);
+        let pass_phenotype = phenotype::passes(&self.query, &self.phenotype_scores, seqvar);
         let pass_regions_allowlist = regions_allowlist::passes(&self.query, seqvar);
         let pass_genotype = genotype::passes(&self.query, seqvar)?;
         if !pass_frequency
             || !pass_consequences
             || !pass_quality
             || !pass_genes_allowlist
+            || !pass_phenotype
             || !pass_regions_allowlist
             || !pass_genotype
         {
             return Ok(PassesResult { pass_all: false });
         }
+        // Evaluate the expression which may need a database lookup for scores.
+        if !expression::passes(
+            self.query.expression.as_ref(),
+            self.expression_needs_scores,
+            annotator,
+            seqvar,
+        )? {
+            return Ok(PassesResult { pass_all: false });
+        }
         // If we passed until here, check the presence in ClinVar which needs a database lookup.
+        let pass_clinvar = match annotator {
+            Some(annotator) => clinvar::passes(&self.query, annotator, seqvar)?,
+            None => true,
+        };
         Ok(PassesResult {
-            pass_all: clinvar::passes(&self.query, annotator, seqvar)?,
+            pass_all: pass_clinvar,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::common::expression::Expression;
+    use crate::seqvars::query::schema::query::CaseQuery;
+
+    #[rstest::rstest]
+    #[case::default(false, None, false, true)]
+    #[case::clinvar_presence(true, None, false, false)]
+    #[case::record_expression(false, Some("gnomad_af < 0.01"), false, true)]
+    #[case::score_expression(false, Some("cadd_phred > 20"), false, false)]
+    #[case::genes(false, None, true, false)]
+    fn check_no_databases(
+        #[case] presence_required: bool,
+        #[case] expression: Option<&str>,
+        #[case] with_genes: bool,
+        #[case] expected_ok: bool,
+    ) {
+        let mut query = CaseQuery::default();
+        query.clinvar.presence_required = presence_required;
+        query.expression = expression.map(|source| Expression::parse(source).unwrap());
+        if with_genes {
+            query.locus.genes = vec!["BRCA1".to_string()];
+        }
+
+        assert_eq!(super::check_no_databases(&query).is_ok(), expected_ok);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::seqvars::query::{
+    hpo::PhenotypeScores,
+    schema::{data::VariantRecord, query::CaseQuery},
+};
+
+/// Determine whether the `VariantRecord` passes the phenotype score filter.
+///
+/// The variant passes if any of its genes has a score of at least the query's
+/// `min_phenotype_score`, if set.
+pub fn passes(
+    query: &CaseQuery,
+    phenotype_scores: &PhenotypeScores,
+    seqvar: &VariantRecord,
+) -> bool {
+    let Some(min_phenotype_score) = query.phenotype.min_phenotype_score else {
+        return true;
+    };
+    let res = seqvar
+        .ann_fields
+        .iter()
+        .any(|ann_field| phenotype_scores.score(&ann_field.gene_id) >= min_phenotype_score);
+    if !res {
+        tracing::trace!(
+            "variant {:?} fails phenotype score filter {}",
+            seqvar,
+            min_phenotype_score
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::seqvars::query::{
+        hpo::PhenotypeScores,
+        schema::{
+            data::VariantRecord,
+            query::{CaseQuery, QuerySettingsPhenotype},
+        },
+    };
+    use mehari::annotate::seqvars::ann::AnnField;
+
+    #[rstest]
+    #[case::no_threshold(None, "HGNC:11998", true)]
+    #[case::matching_gene(Some(0.5), "HGNC:1100", true)]
+    #[case::partial_gene(Some(0.5), "HGNC:11998", false)]
+    #[case::unknown_gene(Some(0.0), "HGNC:1", true)]
+    #[case::intergenic(Some(0.0), "", false)]
+    fn passes(
+        #[case] min_phenotype_score: Option<f32>,
+        #[case] gene_id: &str,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        std::fs::write(
+            tmp_dir.join("hgnc_xlink.tsv"),
+            "hgnc_id\tensembl_gene_id\tncbi_gene_id\tgene_symbol\n\
+            HGNC:1100\tENSG00000012048\t672\tBRCA1\n\
+            HGNC:11998\tENSG00000141510\t7157\tTP53\n",
+        )?;
+        std::fs::write(
+            tmp_dir.join("phenotype_to_genes.txt"),
+            "ncbi_gene_id\tgene_symbol\thpo_id\thpo_name\n\
+            672\tBRCA1\tHP:0003002\tBreast carcinoma\n\
+            672\tBRCA1\tHP:0100615\tOvarian neoplasm\n\
+            7157\tTP53\tHP:0100615\tOvarian neoplasm\n\
+            7157\tTP53\tHP:0002667\tNephroblastoma\n",
+        )?;
+        let terms = vec!["HP:0003002".to_string(), "HP:0100615".to_string()];
+        let phenotype_scores = PhenotypeScores::load(&tmp_dir.to_path_buf(), &terms)?;
+        let query = CaseQuery {
+            phenotype: QuerySettingsPhenotype {
+                terms,
+                min_phenotype_score,
+            },
+            ..Default::default()
+        };
+        let seqvar = VariantRecord {
+            ann_fields: if gene_id.is_empty() {
+                vec![]
+            } else {
+                vec![AnnField {
+                    gene_id: gene_id.to_string(),
+                    ..Default::default()
+                }]
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(super::passes(&query, &phenotype_scores, &seqvar), expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use crate::seqvars::query::schema::{
+    data::VariantRecord,
+    query::{CaseQuery, VariantType},
+};
+
+/// Determine whether the `VariantRecord` passes the variant type filter.
+///
+/// The variant type is computed from the lengths of the reference and alternative
+/// allele.  If no variant types are specified, the variant passes.
+pub fn passes(query: &CaseQuery, seqvar: &VariantRecord) -> bool {
+    let variant_types = &query.consequence.variant_types;
+    if variant_types.is_empty() {
+        return true;
+    }
+
+    let variant_type = VariantType::from_alleles(
+        &seqvar.vcf_variant.ref_allele,
+        &seqvar.vcf_variant.alt_allele,
+    );
+    let result = variant_types
+        .iter()
+        .any(|query_type| query_type.selects(variant_type));
+    if !result {
+        tracing::trace!(
+            "variant {:?} of type {:?} fails variant type filter {:?}",
+            &seqvar.vcf_variant,
+            variant_type,
+            variant_types
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::seqvars::query::schema::{
+        data::{VariantRecord, VcfVariant},
+        query::{CaseQuery, QuerySettingsConsequence, VariantType},
+    };
+
+    #[rstest]
+    #[case(vec![], "A", "G", true)]
+    #[case(vec![VariantType::Snv], "A", "G", true)]
+    #[case(vec![VariantType::Snv], "A", "AT", false)]
+    #[case(vec![VariantType::Indel], "A", "AT", true)]
+    #[case(vec![VariantType::Indel], "AT", "A", true)]
+    #[case(vec![VariantType::Indel], "AT", "GC", false)]
+    #[case(vec![VariantType::Insertion], "AT", "A", false)]
+    #[case(vec![VariantType::Deletion], "AT", "A", true)]
+    #[case(vec![VariantType::Mnv, VariantType::ComplexSubstitution], "ACG", "TT", true)]
+    fn passes(
+        #[case] variant_types: Vec<VariantType>,
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            consequence: QuerySettingsConsequence {
+                variant_types,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let seqvar = VariantRecord {
+            vcf_variant: VcfVariant {
+                chrom: "1".into(),
+                pos: 100,
+                ref_allele: ref_allele.into(),
+                alt_allele: alt_allele.into(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(super::passes(&query, &seqvar), expected);
+    }
+}
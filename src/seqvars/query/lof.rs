@@ -0,0 +1,176 @@
+//! Prediction of nonsense-mediated decay (NMD) escape and loss-of-function (LoF) confidence.
+//!
+//! The prediction is recomputed from the exon rank and positions of the `ANN` field as
+//! mehari does not write it.  Escape by the 50 nt rule in the penultimate exon needs the
+//! exon coordinates and is not predicted.
+
+use mehari::annotate::seqvars::ann::{AnnField, Consequence, Message};
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+/// Consequences that introduce a premature termination codon.
+const TRUNCATING: &[Consequence] = &[Consequence::StopGained, Consequence::FrameshiftVariant];
+
+/// Consequences that are considered for the LoF confidence.
+const LOF: &[Consequence] = &[
+    Consequence::StopGained,
+    Consequence::FrameshiftVariant,
+    Consequence::SpliceDonorVariant,
+    Consequence::SpliceAcceptorVariant,
+];
+
+/// Fraction of the protein from which on truncations are considered low confidence.
+const END_TRUNCATION_FRACTION: f64 = 0.95;
+
+/// Return whether the truncating variant of `ann` escapes NMD.
+///
+/// This is `None` for variants that are not truncating or without exon rank.
+pub fn nmd_escape(ann: &AnnField) -> Option<bool> {
+    if !ann.consequences.iter().any(|csq| TRUNCATING.contains(csq)) {
+        return None;
+    }
+    let rank = ann.rank.as_ref()?;
+    // Truncations in single-exon transcripts and the last exon escape NMD.
+    Some(rank.total == 1 || rank.ord == rank.total)
+}
+
+/// Return whether the variant of `ann` truncates the last 5% of the protein.
+fn is_end_truncation(ann: &AnnField) -> bool {
+    ann.protein_pos
+        .as_ref()
+        .and_then(|pos| Some(pos.ord as f64 / pos.total? as f64))
+        .is_some_and(|fraction| fraction > END_TRUNCATION_FRACTION)
+}
+
+/// Return whether the transcript of `ann` is annotated as incomplete.
+fn is_incomplete_transcript(ann: &AnnField) -> bool {
+    ann.messages.as_ref().is_some_and(|messages| {
+        messages.iter().any(|message| {
+            matches!(
+                message,
+                Message::WarningTranscriptIncomplete | Message::WarningTranscriptsNoStartCodon
+            )
+        })
+    })
+}
+
+/// Return the LOFTEE-like LoF confidence of the variant of `ann`.
+///
+/// LoF variants are low confidence if they truncate the last 5% of the protein, escape
+/// NMD, or are on an incomplete transcript.
+pub fn lof_confidence(ann: &AnnField) -> pbs_output::LofConfidence {
+    if !ann.consequences.iter().any(|csq| LOF.contains(csq)) {
+        pbs_output::LofConfidence::Unspecified
+    } else if is_end_truncation(ann)
+        || nmd_escape(ann) == Some(true)
+        || is_incomplete_transcript(ann)
+    {
+        pbs_output::LofConfidence::Low
+    } else {
+        pbs_output::LofConfidence::High
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence, Message, Pos, Rank};
+
+    use crate::pbs::varfish::v1::seqvars::output::LofConfidence;
+
+    fn ann(
+        consequence: Consequence,
+        rank: Option<(i32, i32)>,
+        protein_pos: Option<(i32, i32)>,
+        messages: Option<Vec<Message>>,
+    ) -> AnnField {
+        AnnField {
+            consequences: vec![consequence],
+            rank: rank.map(|(ord, total)| Rank { ord, total }),
+            protein_pos: protein_pos.map(|(ord, total)| Pos {
+                ord,
+                total: Some(total),
+            }),
+            messages,
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::missense(
+        Consequence::MissenseVariant,
+        Some((3, 10)),
+        Some((100, 500)),
+        None,
+        None,
+        LofConfidence::Unspecified,
+    )]
+    #[case::stop_gained(
+        Consequence::StopGained,
+        Some((3, 10)),
+        Some((100, 500)),
+        None,
+        Some(false),
+        LofConfidence::High,
+    )]
+    #[case::frameshift_last_exon(
+        Consequence::FrameshiftVariant,
+        Some((10, 10)),
+        Some((400, 500)),
+        None,
+        Some(true),
+        LofConfidence::Low,
+    )]
+    #[case::stop_gained_single_exon(
+        Consequence::StopGained,
+        Some((1, 1)),
+        Some((100, 500)),
+        None,
+        Some(true),
+        LofConfidence::Low,
+    )]
+    #[case::stop_gained_end(
+        Consequence::StopGained,
+        Some((9, 10)),
+        Some((490, 500)),
+        None,
+        Some(false),
+        LofConfidence::Low,
+    )]
+    #[case::stop_gained_no_rank(
+        Consequence::StopGained,
+        None,
+        None,
+        None,
+        None,
+        LofConfidence::High
+    )]
+    #[case::stop_gained_incomplete(
+        Consequence::StopGained,
+        Some((3, 10)),
+        Some((100, 500)),
+        Some(vec![Message::WarningTranscriptIncomplete]),
+        Some(false),
+        LofConfidence::Low,
+    )]
+    #[case::splice_donor(
+        Consequence::SpliceDonorVariant,
+        Some((3, 9)),
+        None,
+        None,
+        None,
+        LofConfidence::High,
+    )]
+    fn nmd_escape_and_lof_confidence(
+        #[case] consequence: Consequence,
+        #[case] rank: Option<(i32, i32)>,
+        #[case] protein_pos: Option<(i32, i32)>,
+        #[case] messages: Option<Vec<Message>>,
+        #[case] expected_nmd_escape: Option<bool>,
+        #[case] expected_lof_confidence: LofConfidence,
+    ) {
+        let ann = ann(consequence, rank, protein_pos, messages);
+
+        assert_eq!(super::nmd_escape(&ann), expected_nmd_escape);
+        assert_eq!(super::lof_confidence(&ann), expected_lof_confidence);
+    }
+}
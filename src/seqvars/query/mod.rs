@@ -1,10 +1,26 @@
 //! Code implementing the "seqvars query" sub command.
 
 pub mod annonars;
+pub mod anonymize;
+pub mod batch;
+pub mod by_gene;
+pub mod checkpoint;
+pub mod clinvar;
+pub mod context;
+pub mod evidence;
+pub mod gene_cache;
+pub mod gene_panel;
 pub mod hpo;
+pub mod inheritance;
 pub mod interpreter;
+pub mod lof;
+pub mod output;
+pub mod prior;
+pub mod recessive;
+pub mod rna;
 pub mod schema;
 pub mod sorting;
+pub mod timing;
 
 use std::collections::BTreeSet;
 use std::io::{BufRead, Write};
@@ -20,17 +36,21 @@ use mehari::annotate::seqvars::CHROM_TO_CHROM_NO;
 use mehari::common::noodles::NoodlesVariantReader as _;
 use rand_core::{RngCore, SeedableRng};
 use schema::data::{TryFromVcf as _, VariantRecord};
-use schema::query::{CaseQuery, GenotypeChoice, RecessiveMode, SampleGenotypeChoice};
+use schema::query::{
+    CaseQuery, GenotypeChoice, InheritanceModel, RecessiveMode, SampleGenotypeChoice,
+};
 use thousands::Separable;
-use tokio::io::AsyncWriteExt as _;
 use uuid::Uuid;
 
 use crate::common;
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::common::query_json;
 use crate::pbs::varfish::v1::seqvars::output as pbs_output;
 use crate::pbs::varfish::v1::seqvars::query as pbs_query;
 use crate::{common::trace_rss_now, common::GenomeRelease};
 
 use self::annonars::Annotator;
+use self::output::ResultSink as _;
 use self::sorting::{ByCoordinate, ByHgncId};
 
 /// Command line arguments for `seqvars query` sub command.
@@ -47,20 +67,117 @@ pub struct Args {
     #[arg(long)]
     pub case_uuid: Option<uuid::Uuid>,
     /// Path to worker database to use for querying.
-    #[arg(long)]
-    pub path_db: String,
-    /// Path to inhouse rocksdb folder.
-    #[arg(long)]
+    #[arg(long, required_unless_present = "no_databases")]
+    pub path_db: Option<String>,
+    /// Path to inhouse rocksdb folder as created by `seqvars aggregate`.
+    #[arg(long, alias = "path-inhouse-rocksdb")]
     pub path_inhouse_db: Option<String>,
-    /// Path to query JSON file.
+    /// Write the case UUIDs of the in-house carriers of each variant to the output.
+    #[arg(long, default_value_t = false, requires = "path_inhouse_db")]
+    pub inhouse_carrier_uuids: bool,
+    /// Run without any databases, using only the ANN and frequency fields of the
+    /// ingested VCF file; ClinVar, gene, and score annotations are left empty.
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["path_db", "path_inhouse_db", "warm_up"]
+    )]
+    pub no_databases: bool,
+    /// Path to query JSON file; may be gzip-compressed, an `s3://` or `http(s)://` URL,
+    /// or `-` for stdin.
     #[arg(long)]
     pub path_query_json: String,
+    /// Path to gene panel file(s), either plain text with one gene symbol or identifier
+    /// per line or PanelApp JSON exports; their genes are added to the gene allow list.
+    #[arg(long, conflicts_with = "no_databases")]
+    pub path_gene_panel: Vec<String>,
+    /// Optional path to PED file; if given, the samples and recessive roles of the
+    /// query are checked against and derived from the pedigree.
+    #[arg(long)]
+    pub path_ped: Option<String>,
     /// Path to input TSV file.
     #[arg(long)]
     pub path_input: String,
-    /// Path to the output TSV file.
+    /// Source of the consequence annotation of the input file; `auto` uses `INFO/ANN`
+    /// if present and `INFO/CSQ` (VEP) otherwise.
+    #[arg(long, value_enum, default_value_t = schema::ann::AnnotationSource::Auto)]
+    pub annotation_source: schema::ann::AnnotationSource,
+    /// Path to the output JSONL file; bgzip-compressed and tabix-indexed if ending in
+    /// `.gz`.
     #[arg(long)]
     pub path_output: String,
+    /// The output format / sink to write the results to.
+    #[arg(long, value_enum, default_value_t = output::OutputFormat::Jsonl)]
+    pub output_format: output::OutputFormat,
+    /// Whether to write one record per variant or one record per gene with its
+    /// variants, worst consequence, best scores, and recessive flags.
+    #[arg(long, value_enum, default_value_t = output::OutputMode::Variant)]
+    pub output_mode: output::OutputMode,
+    /// Maximal number of records per file for `--output-format sharded-jsonl`.
+    #[arg(long, default_value_t = 100_000)]
+    pub shard_size: usize,
+    /// Maximal number of annotated records queued for the writer thread; annotation
+    /// blocks while the queue is full.
+    #[arg(long, default_value_t = 1_000)]
+    pub writer_queue_size: usize,
+    /// In S3 mode, keep local output and state to resume an interrupted upload; if a
+    /// previous run with the same output path left state, only retry the upload.
+    #[arg(long, default_value_t = false)]
+    pub resume: bool,
+    /// Select the output compression (none, gzip, zstd) by benchmarking the candidates on
+    /// the first records; the suffix of the codec is appended to the output path(s).
+    #[arg(long, default_value_t = false)]
+    pub auto_compress: bool,
+    /// Number of records to sample for `--auto-compress`.
+    #[arg(long, default_value_t = 1_000)]
+    pub auto_compress_sample_size: usize,
+    /// Assumed output I/O throughput in MB/s for `--auto-compress`.
+    #[arg(long, default_value_t = 100.0)]
+    pub auto_compress_io_mb_per_sec: f64,
+    /// Embed the HGNC symbol, name, and aliases of all genes referenced in the
+    /// results in a trailer record after the records.
+    #[arg(long, default_value_t = false)]
+    pub embed_gene_cache: bool,
+    /// Write canonical JSON with sorted keys and floats rounded to 7 significant digits,
+    /// e.g., for byte-identical outputs with `--rng-seed`.
+    #[arg(long, default_value_t = false)]
+    pub canonical_json: bool,
+    /// Alignment files (BAM or CRAM) as `SAMPLE=PATH` to compute read evidence from for
+    /// de novo and compound heterozygous candidates.
+    #[arg(long, value_delimiter = ',', value_parser = evidence::parse_path_alignment)]
+    pub path_alignments: Vec<(String, String)>,
+    /// Path to the indexed reference FASTA file, required for CRAM alignment files.
+    #[arg(long)]
+    pub path_alignments_reference: Option<String>,
+    /// Maximal number of records to compute read evidence for.
+    #[arg(long, default_value_t = 100)]
+    pub alignment_evidence_max_records: usize,
+    /// Path to TSV file with prior classifications of variants (columns `variant`,
+    /// `classification`, `assessor`, and `date`) to annotate the records with.
+    #[arg(long)]
+    pub path_prior_classifications: Option<String>,
+    /// Path to TSV file with RNA-seq evidence of variants (columns `sample`, `variant`,
+    /// `ase_pvalue`, and `junction_reads`) to annotate the records with.
+    #[arg(long)]
+    pub path_rna_evidence: Option<String>,
+    /// Path to the indexed reference FASTA file to write the context sequence of each
+    /// record from.
+    #[arg(long)]
+    pub path_reference: Option<String>,
+    /// Number of reference bases on each side of the variant for `--path-reference`.
+    #[arg(long, default_value_t = 50)]
+    pub context_sequence_flank: usize,
+    /// Replace sample names in the output by role-based pseudonyms (index, father,
+    /// mother, sibling1, ...).
+    #[arg(long, default_value_t = false, requires = "path_anonymize_key")]
+    pub anonymize: bool,
+    /// Path to the TSV file to write the mapping from pseudonym to sample name to.
+    #[arg(long)]
+    pub path_anonymize_key: Option<String>,
+    /// Score the support of the genes passing the recessive mode of the query and write
+    /// it to the gene-related annotation.
+    #[arg(long, default_value_t = false)]
+    pub recessive_score: bool,
 
     /// Optional maximal number of total records to write out.
     #[arg(long)]
@@ -71,6 +188,29 @@ pub struct Args {
     /// Maximal distance to TAD to consider (unused, but required when loading database).
     #[arg(long, default_value_t = 10_000)]
     pub max_tad_distance: i32,
+    /// Warm up the annonars database block caches for the regions of the locus filter
+    /// before querying.
+    #[arg(long, default_value_t = false)]
+    pub warm_up: bool,
+    /// Readahead size in bytes for the warm-up; `0` lets RocksDB tune it.
+    #[arg(long, default_value_t = 0)]
+    pub rocksdb_readahead_size: usize,
+    /// Directory to keep the intermediate stages in; optional.  A re-invocation with the
+    /// same settings resumes after the last completed stage.
+    #[arg(long)]
+    pub checkpoint_dir: Option<String>,
+    /// Record per-record latencies of the processing stages and report them in the
+    /// output statistics.
+    #[arg(long, default_value_t = false)]
+    pub instrument: bool,
+    /// Optional path prefix for heap profile dumps at the end of each query stage;
+    /// dumps require building with the `heap-profile` feature.
+    #[arg(long)]
+    pub heap_profile: Option<String>,
+    /// Only estimate runtime, temporary disk space, and memory from the input header and
+    /// index and the database sizes, print the estimate as JSON, and exit.
+    #[arg(long, default_value_t = false)]
+    pub estimate_only: bool,
 }
 
 /// Utility struct to store statistics about counts.
@@ -80,19 +220,43 @@ struct QueryStats {
     pub count_total: usize,
     pub passed_by_consequences:
         indexmap::IndexMap<mehari::annotate::seqvars::ann::Consequence, usize>,
+    pub timings: timing::StageTimings,
+    pub ann_recovery: schema::ann::AnnRecovery,
+}
+
+/// Returns whether `seqvar` is on chrX outside of the pseudoautosomal regions.
+fn is_x_non_par(seqvar: &VariantRecord, genome_release: GenomeRelease) -> bool {
+    let chrom = ::annonars::common::cli::canonicalize(&seqvar.vcf_variant.chrom)
+        .parse()
+        .unwrap_or(common::Chrom::Auto);
+    chrom == common::Chrom::X
+        && !common::GonosomalRegion::from_pos(
+            chrom,
+            seqvar.vcf_variant.pos as usize,
+            genome_release,
+        )
+        .is_some_and(|region| region.is_par())
 }
 
 /// Checks whether the variants pass through the query interpreter.
-fn passes_for_gene(query: &CaseQuery, seqvars: &Vec<VariantRecord>) -> Result<bool, anyhow::Error> {
+///
+/// On chrX outside of the PARs, a hom. alt. (i.e., hemizygous) index also passes the
+/// homozygous mode if the mother is het. and the father is hom. ref. or not given.
+fn passes_for_gene(
+    query: &CaseQuery,
+    seqvars: &Vec<VariantRecord>,
+    genome_release: GenomeRelease,
+) -> Result<bool, anyhow::Error> {
     // Short-circuit in case of disabled recessive mode.
     if query.genotype.recessive_mode == RecessiveMode::Disabled {
         return Ok(true);
     }
 
     // Extract family information for recessive mode.
-    let (index, parents) = {
+    let (index, parents, mother) = {
         let mut index = String::new();
         let mut parents = Vec::new();
+        let mut mother = None;
         for (sample_name, SampleGenotypeChoice { genotype, .. }) in
             query.genotype.sample_genotypes.iter()
         {
@@ -100,15 +264,24 @@ fn passes_for_gene(query: &CaseQuery, seqvars: &Vec<VariantRecord>) -> Result<bo
                 GenotypeChoice::RecessiveIndex => {
                     index.clone_from(sample_name);
                 }
-                GenotypeChoice::RecessiveFather | GenotypeChoice::RecessiveMother => {
+                GenotypeChoice::RecessiveFather => {
                     parents.push(sample_name.clone());
                 }
+                GenotypeChoice::RecessiveMother => {
+                    parents.push(sample_name.clone());
+                    mother = Some(sample_name.clone());
+                }
                 _ => (),
             }
         }
-        (index, parents)
+        (index, parents, mother)
     };
-    tracing::debug!("index = {}, parents ={:?}", &index, &parents);
+    tracing::debug!(
+        "index = {}, parents ={:?}, mother = {:?}",
+        &index,
+        &parents,
+        &mother
+    );
 
     // All parents must have been seen as het. and hom. ref. at least once for compound
     // heterozygous mode.
@@ -176,16 +349,30 @@ fn passes_for_gene(query: &CaseQuery, seqvars: &Vec<VariantRecord>) -> Result<bo
             continue;
         }
 
-        // We can pass in two cases:
+        // We can pass in three cases:
         //
         // 1. index hom. alt, both parents het.
         // 2. index het, one parent het., other is ref.
+        // 3. index hemizygous on chrX outside of PARs, mother het., father ref.
 
         if index_gt == common::Genotype::HomAlt {
             if matches!(
                 query.genotype.recessive_mode,
                 RecessiveMode::Homozygous | RecessiveMode::Any
             ) {
+                // Case 3: any given mother must be het. and any given father hom. ref.
+                if is_x_non_par(seqvar, genome_release)
+                    && parents.iter().all(|parent| {
+                        if Some(parent) == mother.as_ref() {
+                            het_parents.contains(parent)
+                        } else {
+                            ref_parents.contains(parent)
+                        }
+                    })
+                {
+                    return Ok(true);
+                }
+
                 // Case 1: index hom. alt, any given parent must be het.
                 if het_parents.len() != parent_gts.len() {
                     // Skip this variant, any given parent must be het.
@@ -258,19 +445,31 @@ fn passes_for_gene(query: &CaseQuery, seqvars: &Vec<VariantRecord>) -> Result<bo
 
 /// Run the `args.path_input` VCF file and run through the given `interpreter` writing to
 /// `args.path_output`.
+///
+/// The `annotator` is `None` when running with `--no-databases`.  The `family` is
+/// given when the query selects inheritance models.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all)]
 async fn run_query(
     interpreter: &interpreter::QueryInterpreter,
     pb_query: &pbs_query::CaseQuery,
     args: &Args,
-    annotator: &annonars::Annotator,
-    inhouse: &Option<inhouse::Dbs>,
+    annotator: Option<&annonars::Annotator>,
+    inhouse: Option<&inhouse::Dbs>,
+    family: Option<&inheritance::Family>,
+    pseudonyms: Option<&anonymize::Pseudonyms>,
+    gene_panels: &[pbs_output::GenePanelProvenance],
     rng: &mut rand::rngs::StdRng,
 ) -> Result<QueryStats, anyhow::Error> {
     let start_time = common::now_as_pbjson_timestamp();
     let tmp_dir = tempfile::TempDir::new()?;
+    let mut heap_profiler = common::heap_profile::HeapProfiler::new(args.heap_profile.clone())?;
 
     let chrom_to_chrom_no = &CHROM_TO_CHROM_NO;
-    let mut stats = QueryStats::default();
+    let mut stats = QueryStats {
+        timings: timing::StageTimings::new(args.instrument),
+        ..Default::default()
+    };
 
     // Buffer for generating UUIDs.
     let mut uuid_buf = [0u8; 16];
@@ -278,19 +477,83 @@ async fn run_query(
     // Open VCF file, create reader, and read header.
     let mut input_reader = common::noodles::open_vcf_reader(&args.path_input)
         .await
-        .map_err(|e| {
-            anyhow::anyhow!("could not open file {} for reading: {}", args.path_input, e)
-        })?;
-    let input_header = input_reader.read_header().await?;
+        .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", args.path_input, e))
+        .classify(ErrorClass::Input)?;
+    let input_header = input_reader
+        .read_header()
+        .await
+        .classify(ErrorClass::Input)?;
+    common::header_check::check(
+        &input_header,
+        args.genome_release,
+        args.case_uuid,
+        interpreter
+            .query
+            .genotype
+            .sample_genotypes
+            .keys()
+            .map(String::as_str),
+    )
+    .map_err(|e| anyhow::anyhow!("inconsistent input file {}: {}", args.path_input, e))
+    .classify(ErrorClass::Input)?;
+    let ann_layout = schema::ann::AnnLayout::from_header(&input_header, args.annotation_source)
+        .map_err(|e| anyhow::anyhow!("problem with annotation of {}: {}", args.path_input, e))
+        .classify(ErrorClass::Input)?;
+    tracing::info!("reading annotation from INFO/{}", ann_layout.info_key());
+
+    let mut context = args
+        .path_reference
+        .as_ref()
+        .map(|path| context::ContextReader::new(path, args.context_sequence_flank))
+        .transpose()
+        .classify(ErrorClass::Input)?;
 
-    let path_unsorted = tmp_dir.path().join("unsorted.jsonl");
-    let path_by_hgnc = tmp_dir.path().join("by_hgnc_filtered.jsonl");
-    let path_by_coord = tmp_dir.path().join("by_coord.jsonl");
-    let path_noheader = tmp_dir.path().join("noheader.jsonl");
+    let rna_evidence = args
+        .path_rna_evidence
+        .as_ref()
+        .map(rna::RnaEvidence::load)
+        .transpose()
+        .classify(ErrorClass::Input)?;
+    if let Some(rna_evidence) = rna_evidence.as_ref() {
+        tracing::info!("loaded RNA evidence for {} variants", rna_evidence.len());
+    }
+
+    // The intermediate stages are kept in the checkpoint directory if given, so an
+    // interrupted query can resume after the last completed stage.
+    let checkpoints = args
+        .checkpoint_dir
+        .as_ref()
+        .map(|dir| {
+            checkpoint_fingerprint(args, &interpreter.query, annotator)
+                .and_then(|fingerprint| checkpoint::Checkpoints::open(dir, &fingerprint))
+        })
+        .transpose()
+        .classify(ErrorClass::Config)?;
+    let stage_path = |stage: checkpoint::Stage| match checkpoints.as_ref() {
+        Some(checkpoints) => checkpoints.path(stage),
+        None => tmp_dir.path().join(format!("{}.jsonl", stage)),
+    };
+    let stage_done = |stage: checkpoint::Stage| {
+        checkpoints
+            .as_ref()
+            .is_some_and(|checkpoints| checkpoints.is_done(stage))
+    };
+    let path_unsorted = stage_path(checkpoint::Stage::Unsorted);
+    let path_by_hgnc = stage_path(checkpoint::Stage::ByHgncFiltered);
+    let path_by_coord = stage_path(checkpoint::Stage::ByCoord);
 
     // Read through input records using the query interpreter as a filter and write to
     // temporary file for unsorted records.
-    {
+    if stage_done(checkpoint::Stage::Unsorted) {
+        let unsorted_stats: checkpoint::UnsortedStats = checkpoints
+            .as_ref()
+            .expect("checked above")
+            .load(checkpoint::Stage::Unsorted)?;
+        tracing::info!("resuming after checkpoint of stage unsorted");
+        stats.count_total = unsorted_stats.count_total;
+        stats.count_passed = unsorted_stats.count_passed;
+        stats.passed_by_consequences = unsorted_stats.passed_by_consequences.into_iter().collect();
+    } else {
         // Create temporary output file.
         let mut tmp_unsorted = std::fs::File::create(&path_unsorted)
             .map(std::io::BufWriter::new)
@@ -299,19 +562,56 @@ async fn run_query(
         let mut records = input_reader.records(&input_header).await;
         while let Some(record_buf) = records.try_next().await? {
             stats.count_total += 1;
-            let record_seqvar = VariantRecord::try_from_vcf(&record_buf, &input_header)
-                .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
+            // Skip records on other chromosomes early, before any annotation.
+            if !interpreter
+                .query
+                .locus
+                .passes_chromosome(record_buf.reference_sequence_name())
+            {
+                continue;
+            }
+            let start = stats.timings.start();
+            let record_seqvar = VariantRecord::try_from_vcf_with_recovery(
+                &record_buf,
+                &input_header,
+                &ann_layout,
+                &mut stats.ann_recovery,
+            )
+            .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
+            stats.timings.record(timing::Stage::Parse, start);
             tracing::trace!("processing record {:?}", record_seqvar);
 
-            let record_seqvar = if let Some(inhouse) = inhouse.as_ref() {
-                inhouse.annotate_seqvar(record_seqvar).map_err(|e| {
-                    anyhow::anyhow!("could not annotate record with inhouse data: {}", e)
-                })?
+            let start = stats.timings.start();
+            let record_seqvar = if let Some(inhouse) = inhouse {
+                inhouse
+                    .annotate_seqvar(record_seqvar, args.inhouse_carrier_uuids)
+                    .map_err(|e| {
+                        anyhow::anyhow!("could not annotate record with inhouse data: {}", e)
+                    })?
             } else {
                 record_seqvar
             };
 
-            if interpreter.passes(&record_seqvar, annotator)?.pass_all {
+            let mut passes = interpreter.passes(&record_seqvar, annotator)?.pass_all;
+            // Indels in long homopolymers are filtered with the reference after all
+            // other criteria to limit the reference lookups.
+            if let (Some(max_length), Some(context)) = (
+                interpreter.query.consequence.max_homopolymer_length,
+                context.as_mut(),
+            ) {
+                passes = passes
+                    && context
+                        .passes_max_homopolymer_length(&record_seqvar.vcf_variant, max_length)?;
+            }
+            if let (Some(min_junction_reads), Some(rna_evidence)) = (
+                interpreter.query.consequence.min_rna_junction_reads,
+                rna_evidence.as_ref(),
+            ) {
+                passes = passes
+                    && rna_evidence.passes_min_junction_reads(&record_seqvar, min_junction_reads);
+            }
+            stats.timings.record(timing::Stage::Interpret, start);
+            if passes {
                 stats.count_passed += 1;
                 if let Some(ann) = record_seqvar.ann_fields.first() {
                     ann.consequences.iter().for_each(|csq| {
@@ -333,7 +633,22 @@ async fn run_query(
         tmp_unsorted.into_inner()?.sync_all().map_err(|e| {
             anyhow::anyhow!("could not flush temporary output file unsorted: {}", e)
         })?;
+        if let Some(checkpoints) = checkpoints.as_ref() {
+            checkpoints.mark_done(
+                checkpoint::Stage::Unsorted,
+                &checkpoint::UnsortedStats {
+                    count_total: stats.count_total,
+                    count_passed: stats.count_passed,
+                    passed_by_consequences: stats
+                        .passed_by_consequences
+                        .iter()
+                        .map(|(csq, count)| (*csq, *count))
+                        .collect(),
+                },
+            )?;
+        }
     }
+    heap_profiler.stage("iterate")?;
 
     let elem_count = 10_000; // at most 10k records in memory
 
@@ -342,8 +657,11 @@ async fn run_query(
     // - sort the records by HGNC ID using external sorting
     // - group by HGNC id
     // - keep the groups where the recessive criteria are met according to query
+    // - keep the variants that match the inheritance models of the query, if any
     // - write out the records again for later sorting by coordinate
-    {
+    if stage_done(checkpoint::Stage::ByHgncFiltered) {
+        tracing::info!("resuming after checkpoint of stage by_hgnc_filtered");
+    } else {
         let tmp_unsorted = std::fs::File::open(&path_unsorted)
             .map(std::io::BufReader::new)
             .map_err(|e| anyhow::anyhow!("could not open temporary unsorted file: {}", e))?;
@@ -375,29 +693,57 @@ async fn run_query(
                     .map(|ByHgncId { seqvar, .. }| seqvar)
                     .collect::<Vec<_>>()
             })
-            .filter(|seqvars| passes_for_gene(&interpreter.query, seqvars).unwrap())
+            .filter(|seqvars| {
+                passes_for_gene(&interpreter.query, seqvars, args.genome_release).unwrap()
+            })
             .for_each(|seqvars| {
-                seqvars.into_iter().for_each(|seqvar| {
+                let recessive_support = (args.recessive_score
+                    && interpreter.query.genotype.recessive_mode != RecessiveMode::Disabled)
+                    .then(|| {
+                        recessive::score_gene(&interpreter.query, &seqvars, args.genome_release)
+                            .unwrap()
+                    });
+                let seqvars = if let Some(family) = family {
+                    family
+                        .filter_gene(&interpreter.query.genotype.inheritance_models, seqvars)
+                        .unwrap()
+                        .into_iter()
+                        .map(|(seqvar, model)| (seqvar, Some(model)))
+                        .collect::<Vec<_>>()
+                } else {
+                    seqvars.into_iter().map(|seqvar| (seqvar, None)).collect()
+                };
+                seqvars.into_iter().for_each(|(seqvar, inheritance_model)| {
                     writeln!(
                         tmp_by_hgnc_filtered,
                         "{}",
-                        serde_json::to_string(&sorting::ByCoordinate::from(seqvar)).unwrap()
+                        serde_json::to_string(&sorting::ByCoordinate {
+                            inheritance_model,
+                            recessive_support,
+                            ..sorting::ByCoordinate::from(seqvar)
+                        })
+                        .unwrap()
                     )
                     .expect("could not write record to by_hgnc_filtered");
                 })
             });
-        tmp_by_hgnc_filtered.flush().map_err(|e| {
+        tmp_by_hgnc_filtered.into_inner()?.sync_all().map_err(|e| {
             anyhow::anyhow!(
                 "could not flush temporary output file by_hgnc_filtered: {}",
                 e
             )
         })?;
+        if let Some(checkpoints) = checkpoints.as_ref() {
+            checkpoints.mark_done(checkpoint::Stage::ByHgncFiltered, &())?;
+        }
     }
 
     // Finally:
     // - sort surviving records by coordinate
     // - generate payload with annotations
-    {
+    if stage_done(checkpoint::Stage::ByCoord) {
+        tracing::info!("resuming after checkpoint of stage by_coord");
+    } else {
         let tmp_by_hgnc_filtered = std::fs::File::open(&path_by_hgnc)
             .map(std::io::BufReader::new)
             .map_err(|e| {
@@ -422,30 +768,74 @@ async fn run_query(
 
         sorted_iter
             .map(|res| res.expect("problem reading line after sorting by HGNC ID"))
-            .for_each(|ByCoordinate { seqvar, .. }| {
-                writeln!(tmp_by_coord, "{}", serde_json::to_string(&seqvar).unwrap())
-                    .expect("could not write record to by_coord");
+            .for_each(|by_coordinate| {
+                writeln!(
+                    tmp_by_coord,
+                    "{}",
+                    serde_json::to_string(&by_coordinate).unwrap()
+                )
+                .expect("could not write record to by_coord");
             });
 
-        tmp_by_coord.flush().map_err(|e| {
+        tmp_by_coord.into_inner()?.sync_all().map_err(|e| {
             anyhow::anyhow!(
                 "could not flush temporary output file by_hgnc_filtered: {}",
                 e
             )
         })?;
+        if let Some(checkpoints) = checkpoints.as_ref() {
+            checkpoints.mark_done(checkpoint::Stage::ByCoord, &())?;
+        }
     }
-
-    // Perform the annotation and write into file without header.
+    heap_profiler.stage("sort")?;
+
+    // Perform the annotation and write the records to the output sink.
+    let resume = args.resume && common::s3::s3_mode();
+    let mut sink = output::ThreadedSink::spawn(
+        output::build_sink(
+            args.output_format,
+            args.output_mode,
+            &args.path_output,
+            args.shard_size,
+            resume.then(|| common::s3::ResumeState::dir_for(&args.path_output)),
+            args.auto_compress.then_some(output::AutoCompress {
+                sample_records: args.auto_compress_sample_size,
+                io_mb_per_sec: args.auto_compress_io_mb_per_sec,
+            }),
+            args.canonical_json,
+            interpreter.query.genotype.recessive_index().ok(),
+        )?,
+        args.writer_queue_size,
+    )?;
+    let mut gene_cache = args.embed_gene_cache.then(gene_cache::GeneCache::default);
+    let prior = args
+        .path_prior_classifications
+        .as_ref()
+        .map(prior::PriorClassifications::load)
+        .transpose()
+        .classify(ErrorClass::Input)?;
+    if let Some(prior) = prior.as_ref() {
+        tracing::info!("loaded {} prior classifications", prior.len());
+    }
+    let mut evidence = if args.path_alignments.is_empty() {
+        None
+    } else if evidence::is_candidate_query(&interpreter.query) {
+        Some(
+            evidence::EvidenceCollector::new(
+                &args.path_alignments,
+                args.path_alignments_reference.as_deref(),
+                args.alignment_evidence_max_records,
+            )
+            .classify(ErrorClass::Input)?,
+        )
+    } else {
+        tracing::warn!(
+            "ignoring --path-alignments as the query selects no de novo or compound \
+            heterozygous candidates"
+        );
+        None
+    };
     {
-        tracing::debug!("writing noheader file {}", path_noheader.display());
-        let writer = tokio::fs::OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&path_noheader)
-            .await
-            .map_err(|e| anyhow::anyhow!("could not open output file: {}", e))?;
-        let mut writer = tokio::io::BufWriter::new(writer);
         // Open reader for temporary by-coordinate file.
         let tmp_by_coord = std::fs::File::open(&path_by_coord)
             .map(std::io::BufReader::new)
@@ -458,7 +848,12 @@ async fn run_query(
             } else {
                 anyhow::bail!("error reading line from input file")
             };
-            let seqvar: VariantRecord = serde_json::from_str(&line).map_err(|e| {
+            let ByCoordinate {
+                seqvar,
+                inheritance_model,
+                recessive_support,
+                ..
+            } = serde_json::from_str(&line).map_err(|e| {
                 anyhow::anyhow!(
                     "error parsing line from input file: {:?} (line: {:?})",
                     e,
@@ -466,81 +861,183 @@ async fn run_query(
                 )
             })?;
 
+            if let Some(gene_cache) = gene_cache.as_mut() {
+                gene_cache.register(&seqvar);
+            }
             create_and_write_record(
                 seqvar,
+                inheritance_model,
+                recessive_support,
+                &interpreter.phenotype_scores,
                 annotator,
                 chrom_to_chrom_no,
-                &mut writer,
+                &mut sink,
+                evidence.as_mut(),
+                context.as_mut(),
+                prior.as_ref(),
+                rna_evidence.as_ref(),
                 args,
+                pseudonyms,
                 rng,
                 &mut uuid_buf,
-            )
-            .await?;
+                &mut stats.timings,
+            )?;
         }
-
-        // Properly flush the output file, so upload to S3 can be done if necessary.
-        writer
-            .flush()
-            .await
-            .map_err(|e| anyhow::anyhow!("could not flush output file before closing: {}", e))?;
     }
 
-    // Finally, write out records in JSONL format in JSONL format.  The first line will contain the
-    // header, the rest the records.
-    //
-    // Use output helper for semi-transparent upload to S3.
-    let out_path_helper = crate::common::s3::OutputPathHelper::new(&args.path_output)?;
-    {
-        tracing::debug!("writing file {}", out_path_helper.path_out());
-        // Open output file for writing (potentially temporary, then uploaded to S3 via helper).
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(out_path_helper.path_out())
-            .map_err(|e| anyhow::anyhow!("could not open output file: {}", e))?;
-        let mut writer = std::io::BufWriter::new(file);
-        write_header(args, pb_query, &stats, start_time, &mut writer)?;
-        // Open reader for file without header.
-        let mut reader = std::fs::File::open(&path_noheader)
-            .map(std::io::BufReader::new)
-            .map_err(|e| anyhow::anyhow!("could not open temporary no_header file: {}", e))?;
-        // Append the temporary file to the output file.
-        std::io::copy(&mut reader, &mut writer)
-            .map_err(|e| anyhow::anyhow!("could not copy temporary file to output file: {}", e))?;
-        // Properly flush the output file, so upload to S3 can be done if necessary.
-        writer
-            .flush()
-            .map_err(|e| anyhow::anyhow!("could not flush output file before closing: {}", e))?;
+    // Finally, let the sink write out the header, the records, and the trailer.  The
+    // header will be first, followed by the records and the optional trailer.
+    let mut header = build_header(
+        args,
+        pb_query,
+        &interpreter.query,
+        annotator,
+        &stats,
+        start_time,
+        gene_panels,
+    )?;
+    if let Some(pseudonyms) = pseudonyms {
+        pseudonyms.anonymize_header(&mut header);
+    }
+    let trailer = gene_cache
+        .map(|gene_cache| gene_cache.build_trailer(annotator))
+        .transpose()?;
+    let out_path_helpers = sink.finish(&header, trailer.as_ref())?;
+    heap_profiler.stage("annotate")?;
+    // Potentially upload the output files to S3.  When resuming is enabled, persist the
+    // state first so an interrupted upload can be retried with `--resume`.
+    if resume {
+        let state = common::s3::ResumeState {
+            path_out: args.path_output.clone(),
+            uploads: out_path_helpers
+                .iter()
+                .flat_map(|helper| helper.pending_uploads())
+                .collect(),
+        };
+        state.save()?;
+        state
+            .upload_and_clear()
+            .await
+            .map_err(|e| anyhow::anyhow!("could not upload output file to S3: {}", e))?;
+    } else {
+        for out_path_helper in &out_path_helpers {
+            out_path_helper
+                .upload_for_s3()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not upload output file to S3: {}", e))?;
+        }
     }
-    // Potentially upload the output file to S3.
-    out_path_helper
-        .upload_for_s3()
-        .await
-        .map_err(|e| anyhow::anyhow!("could not upload output file to S3: {}", e))?;
 
     Ok(stats)
 }
 
-/// Write the header to the output file.
-fn write_header(
+/// Return the fingerprint of the settings that determine the intermediate stages for
+/// `--checkpoint-dir`.
+fn checkpoint_fingerprint(
+    args: &Args,
+    query: &CaseQuery,
+    annotator: Option<&annonars::Annotator>,
+) -> Result<String, anyhow::Error> {
+    let mut entries = vec![
+        (
+            "varfish-worker".to_string(),
+            common::worker_version().to_string(),
+        ),
+        (
+            "genome-release".to_string(),
+            args.genome_release.to_string(),
+        ),
+        ("path-input".to_string(), args.path_input.clone()),
+        (
+            "input-size".to_string(),
+            std::fs::metadata(&args.path_input)
+                .map(|metadata| metadata.len().to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "annotation-source".to_string(),
+            format!("{:?}", args.annotation_source),
+        ),
+        (
+            "path-inhouse-db".to_string(),
+            format!("{:?}", args.path_inhouse_db),
+        ),
+        (
+            "inhouse-carrier-uuids".to_string(),
+            args.inhouse_carrier_uuids.to_string(),
+        ),
+        ("path-ped".to_string(), format!("{:?}", args.path_ped)),
+        (
+            "path-reference".to_string(),
+            format!("{:?}", args.path_reference),
+        ),
+        (
+            "path-rna-evidence".to_string(),
+            format!("{:?}", args.path_rna_evidence),
+        ),
+        (
+            "recessive-score".to_string(),
+            args.recessive_score.to_string(),
+        ),
+    ];
+    if let Some(annotator) = annotator {
+        entries.extend(annotator.versions());
+    }
+    common::fingerprint::settings_fingerprint(
+        query,
+        &entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Build the header for the output.
+///
+/// The settings fingerprint is computed from the effective `query`, i.e., after applying
+/// the pedigree and defaults, the genome release, and the versions.
+fn build_header(
     args: &Args,
     pb_query: &pbs_query::CaseQuery,
+    query: &CaseQuery,
+    annotator: Option<&annonars::Annotator>,
     stats: &QueryStats,
     start_time: pbjson_types::Timestamp,
-    writer: &mut std::io::BufWriter<std::fs::File>,
-) -> Result<(), anyhow::Error> {
-    let header = pbs_output::OutputHeader {
+    gene_panels: &[pbs_output::GenePanelProvenance],
+) -> Result<pbs_output::OutputHeader, anyhow::Error> {
+    let mut versions = vec![pbs_output::VersionEntry {
+        name: "varfish-worker".to_string(),
+        version: common::worker_version().to_string(),
+    }];
+    if let Some(annotator) = annotator {
+        versions.extend(
+            annotator
+                .versions()
+                .into_iter()
+                .map(|(name, version)| pbs_output::VersionEntry { name, version }),
+        );
+    }
+    let genome_release = format!("{}", args.genome_release);
+    let settings_fingerprint = common::fingerprint::settings_fingerprint(
+        query,
+        &std::iter::once(("genome-release", genome_release.as_str()))
+            .chain(
+                versions
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), entry.version.as_str())),
+            )
+            .collect::<Vec<_>>(),
+    )?;
+
+    Ok(pbs_output::OutputHeader {
         genome_release: Into::<pbs_output::GenomeRelease>::into(args.genome_release) as i32,
-        versions: vec![pbs_output::VersionEntry {
-            name: "varfish-worker".to_string(),
-            version: common::worker_version().to_string(),
-        }],
+        versions,
         query: Some(pb_query.clone()),
         case_uuid: args.case_uuid.unwrap_or_default().to_string(),
         statistics: Some(pbs_output::OutputStatistics {
             count_total: stats.count_total as u64,
             count_passed: stats.count_passed as u64,
+            stage_latencies: stats.timings.to_pbs(),
             passed_by_consequences: stats
                 .passed_by_consequences
                 .iter()
@@ -571,14 +1068,11 @@ fn write_header(
             })
         },
         variant_score_columns: variant_related_annotation::score_columns(),
-    };
-    writeln!(
-        writer,
-        "{}",
-        serde_json::to_string(&header)
-            .map_err(|e| anyhow::anyhow!("could not convert header to JSON: {}", e))?
-    )?;
-    Ok(())
+        // Set by the sink when the codec is selected automatically.
+        compression: None,
+        settings_fingerprint,
+        gene_panels: gene_panels.to_vec(),
+    })
 }
 
 /// Trait for records that can be constructed from a `VariantRecord` and an `Annotator`.
@@ -591,14 +1085,15 @@ trait WithSeqvarAndAnnotator: Sized {
     /// # Arguments
     ///
     /// * `seqvar` - The variant record to use for construction.
-    /// * `annotator` - The annotator to use for construction.
+    /// * `annotator` - The annotator to use for construction; `None` when running
+    ///   without databases.
     ///
     /// # Error
     ///
     /// Returns an error if the record could not be constructed.
     fn with_seqvar_and_annotator(
         seqvar: &VariantRecord,
-        annotator: &Annotator,
+        annotator: Option<&Annotator>,
     ) -> Result<Self, anyhow::Error>;
 }
 
@@ -724,16 +1219,18 @@ impl WithSeqvarAndAnnotator for pbs_output::GeneRelatedAnnotation {
 
     fn with_seqvar_and_annotator(
         seqvar: &VariantRecord,
-        annotator: &Annotator,
+        annotator: Option<&Annotator>,
     ) -> Result<Self, Self::Error> {
         if let Some(ann) = seqvar.ann_fields.first() {
             if !ann.gene_id.is_empty() && !ann.gene_symbol.is_empty() {
                 let hgnc_id = ann.gene_id.clone();
 
                 let gene_record = annotator
-                    .query_genes(&hgnc_id)
-                    .map_err(|e| anyhow::anyhow!("problem querying genes database: {}", e))?;
-                let mois = annotator.hgnc_to_moi.get(&hgnc_id);
+                    .map(|annotator| annotator.query_genes(&hgnc_id))
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("problem querying genes database: {}", e))?
+                    .flatten();
+                let mois = annotator.and_then(|annotator| annotator.hgnc_to_moi.get(&hgnc_id));
 
                 return Ok(Self {
                     identity: Some(pbs_output::GeneIdentity {
@@ -743,6 +1240,8 @@ impl WithSeqvarAndAnnotator for pbs_output::GeneRelatedAnnotation {
                     consequences: gene_related_annotation::consequences(ann)?,
                     phenotypes: gene_related_annotation::phenotypes(&gene_record, mois),
                     constraints: gene_related_annotation::constraints(&gene_record)?,
+                    recessive_support: None,
+                    phenotype_match: None,
                 });
             }
         }
@@ -818,6 +1317,8 @@ mod gene_related_annotation {
             location: location as i32,
             rank_ord,
             rank_total,
+            nmd_escape: lof::nmd_escape(ann),
+            lof_confidence: lof::lof_confidence(ann) as i32,
         }))
     }
 
@@ -1100,13 +1601,23 @@ mod variant_related_annotation {
 
     pub(crate) fn with_seqvar_and_annotator(
         seqvar: &VariantRecord,
-        annotator: &Annotator,
+        annotator: Option<&Annotator>,
     ) -> Result<pbs_output::VariantRelatedAnnotation, anyhow::Error> {
+        // Without databases, only the frequencies from the VCF file are available.
+        let Some(annotator) = annotator else {
+            return Ok(pbs_output::VariantRelatedAnnotation {
+                frequency: frequency(seqvar),
+                gonosomal_region: pbs_output::GonosomalRegion::Unspecified as i32,
+                ..Default::default()
+            });
+        };
         Ok(pbs_output::VariantRelatedAnnotation {
             dbids: dbids(seqvar, annotator)?,
             frequency: frequency(seqvar),
             clinvar: clinvar(seqvar, annotator)?,
             scores: scores(seqvar, annotator)?,
+            // Set by the caller that knows the genome release.
+            gonosomal_region: pbs_output::GonosomalRegion::Unspecified as i32,
         })
     }
 
@@ -1162,6 +1673,19 @@ mod variant_related_annotation {
                 hemialt: seqvar.population_frequencies.inhouse.hemi,
                 af: 0f32,
             }),
+            inhouse_strata: seqvar
+                .population_frequencies
+                .inhouse
+                .strata
+                .as_ref()
+                .map(|strata| pbs_output::InhouseStrata {
+                    affected: strata.affected,
+                    unaffected: strata.unaffected,
+                    unknown_affection: strata.unknown_affection,
+                    male: strata.male,
+                    female: strata.female,
+                }),
+            inhouse_carrier_uuids: seqvar.population_frequencies.inhouse.carrier_uuids.clone(),
         })
     }
 
@@ -1181,7 +1705,7 @@ mod variant_related_annotation {
                 return Ok(None);
             } else if record.records.len() > 1 {
                 tracing::warn!(
-                    "variant {:?} found list with {} entries, using first",
+                    "variant {:?} found list with {} entries, using first for aggregate",
                     seqvar,
                     record.records.len()
                 );
@@ -1204,15 +1728,18 @@ mod variant_related_annotation {
                     return Ok(None);
                 };
                 let germline_review_status = agc.review_status;
-                // TODO: search through all submitted records and pick the most significant one.
-                let effective_germline_significance_description =
-                    germline_significance_description.clone();
+                let submissions = super::clinvar::aggregate(&record.records);
+                let effective_germline_significance_description = submissions
+                    .effective_significance
+                    .unwrap_or_else(|| germline_significance_description.clone());
 
                 Ok(Some(pbs_output::ClinvarAnnotation {
                     vcv_accession,
                     germline_significance_description,
                     germline_review_status,
                     effective_germline_significance_description,
+                    submission_count: submissions.count,
+                    has_conflicting_submissions: submissions.conflicting,
                 }))
             } else {
                 tracing::trace!(
@@ -1517,7 +2044,7 @@ impl WithSeqvarAndAnnotator for pbs_output::VariantRelatedAnnotation {
 
     fn with_seqvar_and_annotator(
         seqvar: &VariantRecord,
-        annotator: &Annotator,
+        annotator: Option<&Annotator>,
     ) -> Result<Self, Self::Error> {
         variant_related_annotation::with_seqvar_and_annotator(seqvar, annotator)
     }
@@ -1528,7 +2055,7 @@ impl WithSeqvarAndAnnotator for pbs_output::CallRelatedAnnotation {
 
     fn with_seqvar_and_annotator(
         seqvar: &VariantRecord,
-        _annotator: &Annotator,
+        _annotator: Option<&Annotator>,
     ) -> Result<Self, Self::Error> {
         Ok(Self {
             call_infos: seqvar
@@ -1541,24 +2068,74 @@ impl WithSeqvarAndAnnotator for pbs_output::CallRelatedAnnotation {
                     ad: call_info.ad,
                     gq: call_info.gq,
                     ps: call_info.ps,
+                    alignment_evidence: None,
+                    gt_from_pl: call_info.gt_from_pl,
                 })
                 .collect(),
         })
     }
 }
 
-/// Create output payload and write the record to the output file.
-async fn create_and_write_record(
+/// Return the special gonosomal region that `seqvar` falls into.
+fn gonosomal_region(
+    seqvar: &VariantRecord,
+    genome_release: GenomeRelease,
+) -> pbs_output::GonosomalRegion {
+    let chrom = ::annonars::common::cli::canonicalize(&seqvar.vcf_variant.chrom)
+        .parse()
+        .unwrap_or(common::Chrom::Auto);
+    common::GonosomalRegion::from_pos(chrom, seqvar.vcf_variant.pos as usize, genome_release)
+        .map(Into::into)
+        .unwrap_or(pbs_output::GonosomalRegion::Unspecified)
+}
+
+/// Create output payload and write the record to the output sink.
+///
+/// If `evidence` is given, the read evidence is written into the call information.
+/// If `prior` is given, the prior assessment of the variant is written into the record.
+/// If `rna_evidence` is given, the RNA-seq evidence of the variant is written into the record.
+/// The `inheritance_model` matched by the variant is written into the record, as are the
+/// `recessive_support` of its gene and its match in `phenotype_scores`.
+/// Without `annotator`, only the annotation from the VCF file is written.
+#[allow(clippy::too_many_arguments)]
+fn create_and_write_record(
     seqvar: VariantRecord,
-    annotator: &Annotator,
+    inheritance_model: Option<InheritanceModel>,
+    recessive_support: Option<recessive::RecessiveSupport>,
+    phenotype_scores: &hpo::PhenotypeScores,
+    annotator: Option<&Annotator>,
     chrom_to_chrom_no: &std::collections::HashMap<String, u32>,
-    writer: &mut tokio::io::BufWriter<tokio::fs::File>,
+    sink: &mut dyn output::ResultSink,
+    evidence: Option<&mut evidence::EvidenceCollector>,
+    context: Option<&mut context::ContextReader>,
+    prior: Option<&prior::PriorClassifications>,
+    rna_evidence: Option<&rna::RnaEvidence>,
     args: &Args,
+    pseudonyms: Option<&anonymize::Pseudonyms>,
     rng: &mut rand::rngs::StdRng,
     uuid_buf: &mut [u8; 16],
+    timings: &mut timing::StageTimings,
 ) -> Result<(), anyhow::Error> {
     // Build the output record protobuf.
-    let record = pbs_output::OutputRecord {
+    let start = timings.start();
+    let prior_assessment = prior.and_then(|prior| prior.get(&seqvar.vcf_variant).cloned());
+    let phenotype_match = seqvar
+        .ann_fields
+        .first()
+        .and_then(|ann| phenotype_scores.get(&ann.gene_id))
+        .cloned();
+    let rna_evidence = rna_evidence
+        .map(|rna_evidence| rna_evidence.get(&seqvar.vcf_variant).to_vec())
+        .unwrap_or_default();
+    let (context_sequence, repeat_context) = if let Some(context) = context {
+        (
+            context.context_sequence(&seqvar.vcf_variant)?,
+            context.repeat_context(&seqvar.vcf_variant)?,
+        )
+    } else {
+        (None, None)
+    };
+    let mut record = pbs_output::OutputRecord {
         uuid: Uuid::from_bytes({
             rng.fill_bytes(uuid_buf);
             *uuid_buf
@@ -1578,17 +2155,26 @@ async fn create_and_write_record(
         }),
         variant_annotation: Some(pbs_output::VariantAnnotation {
             gene: Some(
-                pbs_output::GeneRelatedAnnotation::with_seqvar_and_annotator(&seqvar, annotator)
+                pbs_output::GeneRelatedAnnotation {
+                    recessive_support: recessive_support.map(Into::into),
+                    phenotype_match: phenotype_match.map(Into::into),
+                    ..pbs_output::GeneRelatedAnnotation::with_seqvar_and_annotator(
+                        &seqvar, annotator,
+                    )
                     .map_err(|e| {
                         anyhow::anyhow!("problem creating gene-related annotation: {}", e)
-                    })?,
-            ),
-            variant: Some(
-                pbs_output::VariantRelatedAnnotation::with_seqvar_and_annotator(&seqvar, annotator)
-                    .map_err(|e| {
-                        anyhow::anyhow!("problem creating variant-related annotation: {}", e)
-                    })?,
+                    })?
+                },
             ),
+            variant: Some(pbs_output::VariantRelatedAnnotation {
+                gonosomal_region: gonosomal_region(&seqvar, args.genome_release) as i32,
+                ..pbs_output::VariantRelatedAnnotation::with_seqvar_and_annotator(
+                    &seqvar, annotator,
+                )
+                .map_err(|e| {
+                    anyhow::anyhow!("problem creating variant-related annotation: {}", e)
+                })?
+            }),
             call: Some(
                 pbs_output::CallRelatedAnnotation::with_seqvar_and_annotator(&seqvar, annotator)
                     .map_err(|e| {
@@ -1596,30 +2182,41 @@ async fn create_and_write_record(
                     })?,
             ),
         }),
+        prior_assessment,
+        result_set_id: args.result_set_id.clone(),
+        context_sequence,
+        repeat_context,
+        rna_evidence,
+        inheritance_model: inheritance_model
+            .map(|model| pbs_query::InheritanceModel::from(model) as i32),
     };
 
-    // Write out the record to JSONL.
+    if let Some(evidence) = evidence {
+        evidence.annotate(&mut record)?;
+    }
+    if let Some(pseudonyms) = pseudonyms {
+        pseudonyms.anonymize_record(&mut record);
+    }
+    timings.record(timing::Stage::Annotate, start);
 
-    let mut buf = Vec::<u8>::new();
-    writeln!(
-        &mut buf,
-        "{}",
-        serde_json::to_string(&record)
-            .map_err(|e| anyhow::anyhow!("could not convert record to JSON: {}", e))?
-    )?;
-    writer
-        .write_all(&buf)
-        .await
-        .map_err(|e| anyhow::anyhow!("could not write record to output file: {}", e))
+    // Write out the record to the sink.
+    let start = timings.start();
+    sink.write_record(&record)
+        .map_err(|e| anyhow::anyhow!("could not write record to output: {}", e))?;
+    timings.record(timing::Stage::Serialize, start);
+
+    Ok(())
 }
 
 /// Code for accessing the in-house frequencies.
 pub(crate) mod inhouse {
     use std::{path::Path, sync::Arc};
 
-    use crate::seqvars::aggregate::ds::Counts;
+    use crate::seqvars::aggregate::ds::{self, Counts};
 
-    use super::schema::data::{InHouseFrequencies, PopulationFrequencies, VariantRecord};
+    use super::schema::data::{
+        InHouseFrequencies, InHouseStrata, PopulationFrequencies, VariantRecord,
+    };
 
     /// Meta information of the database.
     pub struct Meta {
@@ -1634,22 +2231,34 @@ pub(crate) mod inhouse {
         /// Meta information.
         #[allow(dead_code)]
         pub inhouse_meta: Meta,
+        /// Whether the database has the "carriers" column family for the strata.
+        pub has_carriers: bool,
     }
 
     /// Open RocksDb given path and column family name for data and metadata.
+    ///
+    /// The column families in `cf_optional` are only opened if the database has them.
     pub fn open_rocksdb<P: AsRef<std::path::Path>>(
         path_rocksdb: P,
         cf_data: &str,
         cf_meta: &str,
+        cf_optional: &[&str],
     ) -> Result<(Arc<rocksdb::DBWithThreadMode<rocksdb::MultiThreaded>>, Meta), anyhow::Error> {
         tracing::info!("Opening RocksDB database ...");
         let before_open = std::time::Instant::now();
-        let cf_names = &[cf_meta, cf_data];
         let resolved_path_rocksdb = annonars::common::readlink_f(&path_rocksdb)?;
+        let existing_cfs =
+            rocksdb::DB::list_cf(&rocksdb::Options::default(), &resolved_path_rocksdb)?;
+        let mut cf_names = vec![cf_meta, cf_data];
+        cf_names.extend(
+            cf_optional
+                .iter()
+                .filter(|cf| existing_cfs.iter().any(|existing| existing == *cf)),
+        );
         let db = Arc::new(rocksdb::DB::open_cf_for_read_only(
             &rocksdb::Options::default(),
             resolved_path_rocksdb,
-            cf_names,
+            &cf_names,
             true,
         )?);
         tracing::info!("  reading meta information");
@@ -1673,21 +2282,93 @@ pub(crate) mod inhouse {
         Ok((db, meta))
     }
 
+    /// Convert the in-house `counts` to frequencies.
+    ///
+    /// On chrMT, homoplasmic carriers are reported as hom. and heteroplasmic carriers
+    /// as het.
+    fn to_frequencies(counts: Counts) -> InHouseFrequencies {
+        let Counts {
+            count_homref,
+            count_hemiref,
+            count_het,
+            count_homalt,
+            count_hemialt,
+            count_heteroplasmic,
+            count_homoplasmic,
+        } = counts;
+        InHouseFrequencies {
+            an: (count_homref
+                + count_hemiref
+                + count_het
+                + count_homalt
+                + count_hemialt
+                + count_heteroplasmic
+                + count_homoplasmic) as i32,
+            hom: (count_homalt + count_homoplasmic) as i32,
+            het: (count_het + count_heteroplasmic) as i32,
+            hemi: count_hemialt as i32,
+            strata: None,
+            carrier_uuids: Vec::new(),
+        }
+    }
+
+    /// Return whether `carrier` carries a non-reference allele.
+    fn is_alt_carrier(carrier: &ds::Carrier) -> bool {
+        !matches!(
+            carrier.genotype,
+            ds::Genotype::HomRef | ds::Genotype::HemiRef
+        )
+    }
+
+    /// Return the sorted case UUIDs of the non-reference carriers in `carrier_list`.
+    fn carrier_uuids(carrier_list: &ds::CarrierList) -> Vec<String> {
+        carrier_list
+            .carriers
+            .iter()
+            .filter(|carrier| is_alt_carrier(carrier))
+            .map(|carrier| carrier.uuid)
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .map(|uuid| uuid.to_string())
+            .collect()
+    }
+
+    /// Count the non-reference carriers in `carrier_list` by affection and sex.
+    fn count_strata(carrier_list: &ds::CarrierList) -> InHouseStrata {
+        let mut result = InHouseStrata::default();
+        for carrier in &carrier_list.carriers {
+            if !is_alt_carrier(carrier) {
+                continue;
+            }
+            match carrier.strata.affection {
+                ds::Affection::Affected => result.affected += 1,
+                ds::Affection::Unaffected => result.unaffected += 1,
+                ds::Affection::Unknown => result.unknown_affection += 1,
+            }
+            match carrier.strata.sex {
+                ds::Sex::Male => result.male += 1,
+                ds::Sex::Female => result.female += 1,
+                ds::Sex::Unknown => (),
+            }
+        }
+        result
+    }
+
     impl Dbs {
         /// Initialize from path that contains the annonars databases.
         pub fn with_path<P: AsRef<Path>>(
             path: P,
             genome_release: &str,
         ) -> Result<Self, anyhow::Error> {
-            let (inhouse_db, inhouse_meta) =
-                open_rocksdb(&path, "counts", "meta").map_err(|e| {
-                    anyhow::anyhow!(
-                        "problem opening {} metadata at {}: {}",
-                        "counts",
-                        path.as_ref().as_os_str().to_string_lossy(),
-                        e
-                    )
-                })?;
+            let (inhouse_db, inhouse_meta) = open_rocksdb(&path, "counts", "meta", &["carriers"])
+                .map_err(|e| {
+                anyhow::anyhow!(
+                    "problem opening {} metadata at {}: {}",
+                    "counts",
+                    path.as_ref().as_os_str().to_string_lossy(),
+                    e
+                )
+            })?;
 
             if inhouse_meta.genome_release != genome_release {
                 anyhow::bail!(
@@ -1697,9 +2378,12 @@ pub(crate) mod inhouse {
                 );
             }
 
+            let has_carriers = inhouse_db.cf_handle("carriers").is_some();
+
             Ok(Self {
                 inhouse_db,
                 inhouse_meta,
+                has_carriers,
             })
         }
 
@@ -1720,10 +2404,61 @@ pub(crate) mod inhouse {
             Ok(res.as_ref().map(|res| Counts::from_vec(res)))
         }
 
+        /// Query for the in-house frequencies of the variant with the given key.
+        pub fn frequencies(
+            &self,
+            key: &annonars::common::keys::Var,
+        ) -> Result<Option<InHouseFrequencies>, anyhow::Error> {
+            let Some(counts) = self.query(key)? else {
+                return Ok(None);
+            };
+            Ok(Some(InHouseFrequencies {
+                strata: self.strata(key)?,
+                ..to_frequencies(counts)
+            }))
+        }
+
+        /// Query for the carrier strata, if the database has the carriers.
+        fn strata(
+            &self,
+            key: &annonars::common::keys::Var,
+        ) -> Result<Option<InHouseStrata>, anyhow::Error> {
+            Ok(self
+                .carriers(key)?
+                .map(|carrier_list| count_strata(&carrier_list)))
+        }
+
+        /// Query for the carrier list, if the database has the carriers.
+        fn carriers(
+            &self,
+            key: &annonars::common::keys::Var,
+        ) -> Result<Option<ds::CarrierList>, anyhow::Error> {
+            if !self.has_carriers {
+                return Ok(None);
+            }
+            let cf_carriers = self
+                .inhouse_db
+                .cf_handle("carriers")
+                .expect("cannot find column family 'carriers'");
+            let key: Vec<u8> = key.clone().into();
+            let res = self
+                .inhouse_db
+                .get_cf(&cf_carriers, key)
+                .map_err(|e| anyhow::anyhow!("querying in-house RocksDB failed: {}", e))?;
+            res.map(|res| {
+                ds::CarrierList::try_from(res.as_slice())
+                    .map_err(|e| anyhow::anyhow!("invalid carrier list: {}", e))
+            })
+            .transpose()
+        }
+
         /// Annotate the given seqvars record.
+        ///
+        /// If `with_carrier_uuids` is set, the case UUIDs of the carriers are added.
         pub fn annotate_seqvar(
             &self,
             record: VariantRecord,
+            with_carrier_uuids: bool,
         ) -> Result<VariantRecord, anyhow::Error> {
             let VariantRecord {
                 vcf_variant,
@@ -1739,30 +2474,19 @@ pub(crate) mod inhouse {
                 inhouse,
             } = population_frequencies;
 
-            let inhouse = if let Some(inhouse_counts) =
-                self.query(&annonars::common::keys::Var::from(
-                    &vcf_variant.chrom,
-                    vcf_variant.pos,
-                    &vcf_variant.ref_allele,
-                    &vcf_variant.alt_allele,
-                ))? {
-                let Counts {
-                    count_homref,
-                    count_hemiref,
-                    count_het,
-                    count_homalt,
-                    count_hemialt,
-                } = inhouse_counts;
-                InHouseFrequencies {
-                    an: (count_homref + count_hemiref + count_het + count_homalt + count_hemialt)
-                        as i32,
-                    hom: count_homalt as i32,
-                    het: count_het as i32,
-                    hemi: count_hemialt as i32,
-                }
-            } else {
-                inhouse
-            };
+            let key = annonars::common::keys::Var::from(
+                &vcf_variant.chrom,
+                vcf_variant.pos,
+                &vcf_variant.ref_allele,
+                &vcf_variant.alt_allele,
+            );
+            let mut inhouse = self.frequencies(&key)?.unwrap_or(inhouse);
+            if with_carrier_uuids {
+                inhouse.carrier_uuids = self
+                    .carriers(&key)?
+                    .map(|carrier_list| carrier_uuids(&carrier_list))
+                    .unwrap_or_default();
+            }
 
             Ok(VariantRecord {
                 vcf_variant,
@@ -1778,6 +2502,207 @@ pub(crate) mod inhouse {
             })
         }
     }
+
+    #[cfg(test)]
+    mod test {
+        use crate::seqvars::aggregate::ds;
+
+        use super::super::schema::data::InHouseStrata;
+
+        #[test]
+        fn count_strata() {
+            let carrier = |genotype, affection, sex| ds::Carrier {
+                uuid: uuid::Uuid::nil(),
+                family: 0,
+                index: 0,
+                genotype,
+                strata: ds::Strata { affection, sex },
+            };
+            let carrier_list = ds::CarrierList {
+                carriers: vec![
+                    carrier(ds::Genotype::Het, ds::Affection::Affected, ds::Sex::Male),
+                    carrier(
+                        ds::Genotype::HomAlt,
+                        ds::Affection::Unaffected,
+                        ds::Sex::Female,
+                    ),
+                    carrier(
+                        ds::Genotype::HemiAlt,
+                        ds::Affection::Unknown,
+                        ds::Sex::Unknown,
+                    ),
+                    carrier(ds::Genotype::HomRef, ds::Affection::Affected, ds::Sex::Male),
+                ],
+            };
+
+            assert_eq!(
+                super::count_strata(&carrier_list),
+                InHouseStrata {
+                    affected: 1,
+                    unaffected: 1,
+                    unknown_affection: 1,
+                    male: 1,
+                    female: 1,
+                }
+            );
+        }
+    }
+}
+
+/// Databases that are loaded once and shared by all queries.
+pub struct Databases {
+    /// In-memory worker databases, used for translating the gene allow list.
+    pub in_memory_dbs: crate::strucvars::query::InMemoryDbs,
+    /// Annotator with the annonars databases.
+    pub annotator: annonars::Annotator,
+    /// Optional in-house databases.
+    pub inhouse_db: Option<inhouse::Dbs>,
+}
+
+impl Databases {
+    /// Load the worker and annonars databases from `path_db` and the optional in-house
+    /// databases from `path_inhouse_db`.
+    #[tracing::instrument(skip_all)]
+    pub fn load(
+        path_db: &str,
+        path_inhouse_db: Option<&str>,
+        genome_release: GenomeRelease,
+        max_tad_distance: i32,
+    ) -> Result<Self, anyhow::Error> {
+        tracing::info!("Loading worker databases...");
+        let before_loading = Instant::now();
+        let path_worker_db = format!("{}/worker", path_db);
+        let in_memory_dbs = crate::strucvars::query::load_databases(
+            &path_worker_db,
+            genome_release,
+            max_tad_distance,
+        )
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "could not load worker databases from {}: {}",
+                path_worker_db,
+                e
+            )
+        })
+        .classify(ErrorClass::Db)?;
+        let annotator =
+            annonars::Annotator::with_path(path_db, genome_release).classify(ErrorClass::Db)?;
+        let inhouse_db = path_inhouse_db
+            .map(|path| inhouse::Dbs::with_path(path, &format!("{}", genome_release)))
+            .transpose()
+            .classify(ErrorClass::Db)?;
+        tracing::info!(
+            "...done loading databases in {:?}",
+            before_loading.elapsed()
+        );
+
+        Ok(Self {
+            in_memory_dbs,
+            annotator,
+            inhouse_db,
+        })
+    }
+}
+
+/// Query loaded from `args.path_query_json` with the pedigree applied.
+struct LoadedQuery {
+    /// The query as given in the file, written to the output header.
+    pb_query: pbs_query::CaseQuery,
+    /// The converted query.
+    query: CaseQuery,
+    /// The pedigree from `args.path_ped`, if any.
+    pedigree: Option<mehari::ped::PedigreeByName>,
+    /// The family for the inheritance models of the query, if any.
+    family: Option<inheritance::Family>,
+}
+
+impl LoadedQuery {
+    /// Load the query and pedigree given in `args`.
+    #[tracing::instrument(skip_all)]
+    async fn load(args: &Args) -> Result<Self, anyhow::Error> {
+        tracing::info!("Loading query... {}", args.path_query_json);
+        let pb_query: pbs_query::CaseQuery = query_json::read(&args.path_query_json)
+            .await
+            .classify(ErrorClass::Config)?;
+        let mut query = CaseQuery::try_from(pb_query.clone()).classify(ErrorClass::Config)?;
+        let pedigree = args
+            .path_ped
+            .as_ref()
+            .map(|path_ped| {
+                crate::common::ped::load(path_ped).map_err(|e| {
+                    anyhow::Error::new(e).context(format!("problem parsing PED file {}", path_ped))
+                })
+            })
+            .transpose()
+            .classify(ErrorClass::Config)?;
+        if let (Some(path_ped), Some(pedigree)) = (args.path_ped.as_ref(), pedigree.as_ref()) {
+            tracing::info!("Applying pedigree from {}...", path_ped);
+            query
+                .genotype
+                .apply_pedigree(pedigree)
+                .map_err(|e| anyhow::anyhow!("query does not match pedigree {}: {}", path_ped, e))
+                .classify(ErrorClass::Config)?;
+        }
+
+        let family = if query.genotype.inheritance_models.is_empty() {
+            None
+        } else if query.genotype.recessive_mode != RecessiveMode::Disabled {
+            return Err(anyhow::anyhow!(
+                "inheritance models in query cannot be combined with recessive mode"
+            ))
+            .classify(ErrorClass::Config);
+        } else if let Some(pedigree) = pedigree.as_ref() {
+            Some(
+                inheritance::Family::from_pedigree(&query.genotype, pedigree)
+                    .map_err(|e| anyhow::anyhow!("inheritance models in query: {}", e))
+                    .classify(ErrorClass::Config)?,
+            )
+        } else {
+            return Err(anyhow::anyhow!(
+                "inheritance models in query require --path-ped"
+            ))
+            .classify(ErrorClass::Config);
+        };
+        if query.genotype.dominant.is_some() && pedigree.is_none() {
+            return Err(anyhow::anyhow!(
+                "dominant mode in query requires --path-ped"
+            ))
+            .classify(ErrorClass::Config);
+        }
+
+        interpreter::check_expression(&query)
+            .map_err(|e| anyhow::anyhow!("invalid expression in query: {}", e))
+            .classify(ErrorClass::Config)?;
+        if query.consequence.max_homopolymer_length.is_some() && args.path_reference.is_none() {
+            return Err(anyhow::anyhow!(
+                "max_homopolymer_length in query requires --path-reference"
+            ))
+            .classify(ErrorClass::Config);
+        }
+        if query.consequence.min_rna_junction_reads.is_some() && args.path_rna_evidence.is_none() {
+            return Err(anyhow::anyhow!(
+                "min_rna_junction_reads in query requires --path-rna-evidence"
+            ))
+            .classify(ErrorClass::Config);
+        }
+        if query.phenotype.min_phenotype_score.is_some() && query.phenotype.terms.is_empty() {
+            return Err(anyhow::anyhow!(
+                "min_phenotype_score in query requires phenotype terms"
+            ))
+            .classify(ErrorClass::Config);
+        }
+        tracing::info!(
+            "... done loading query = {}",
+            &serde_json::to_string(&query)?
+        );
+
+        Ok(Self {
+            pb_query,
+            query,
+            pedigree,
+            family,
+        })
+    }
 }
 
 /// Main entry point for `seqvars query` sub command.
@@ -1786,6 +2711,111 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     tracing::info!("args_common = {:?}", &args_common);
     tracing::info!("args = {:?}", &args);
 
+    if args.estimate_only {
+        return print_estimate(args).await;
+    }
+
+    // Short-circuit if the local output of a previous run is complete and only the upload
+    // to S3 is missing.
+    if args.resume && common::s3::s3_mode() {
+        if let Some(state) = common::s3::ResumeState::load(&args.path_output)? {
+            tracing::info!(
+                "Found state of previous run for {}, only retrying upload",
+                &args.path_output
+            );
+            state
+                .upload_and_clear()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not upload output file to S3: {}", e))?;
+            tracing::info!(
+                "All of `seqvars query` completed in {:?}",
+                before_anything.elapsed()
+            );
+            return Ok(());
+        }
+    }
+
+    let loaded_query = LoadedQuery::load(args).await?;
+    let dbs = if args.no_databases {
+        tracing::info!("Running without databases, skipping loading...");
+        None
+    } else {
+        let path_db = args
+            .path_db
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--path-db is required without --no-databases"))
+            .classify(ErrorClass::Config)?;
+        Some(Databases::load(
+            path_db,
+            args.path_inhouse_db.as_deref(),
+            args.genome_release,
+            args.max_tad_distance,
+        )?)
+    };
+
+    run_with_databases(args_common, args, loaded_query, dbs.as_ref()).await?;
+
+    tracing::info!(
+        "All of `seqvars query` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+/// Cost model of `seqvars query` for `--estimate-only`.
+const COST_MODEL: common::estimate::CostModel = common::estimate::CostModel {
+    records_per_second: 20_000.0,
+    db_bytes_per_second: 200_000_000.0,
+    base_memory: 500_000_000,
+    memory_per_record: 2_000,
+    memory_per_db_byte: 1.5,
+    output_bytes_per_record: 2_000,
+};
+
+/// Print the resource estimate for running `seqvars query` with `args`.
+async fn print_estimate(args: &Args) -> Result<(), anyhow::Error> {
+    let input = common::estimate::InputStats::load(&args.path_input).await?;
+    let (database_size, in_memory_database_size) = match args.path_db.as_ref() {
+        Some(path_db) => (
+            common::estimate::disk_usage(path_db)
+                + args
+                    .path_inhouse_db
+                    .as_ref()
+                    .map(common::estimate::disk_usage)
+                    .unwrap_or_default(),
+            common::estimate::worker_db_usage(path_db, args.genome_release),
+        ),
+        None => (0, 0),
+    };
+    // Records are processed in shards of `--shard-size` records.
+    let records_in_memory = input.record_count.min(args.shard_size as u64);
+    common::estimate::Estimate::new(
+        &COST_MODEL,
+        input,
+        database_size,
+        in_memory_database_size,
+        records_in_memory,
+    )
+    .print()
+}
+
+/// Run the query from `loaded_query` with the already loaded databases `dbs`.
+///
+/// Without `dbs`, the query must not use any filters that need database lookups.
+#[tracing::instrument(skip_all)]
+async fn run_with_databases(
+    args_common: &crate::common::Args,
+    args: &Args,
+    loaded_query: LoadedQuery,
+    dbs: Option<&Databases>,
+) -> Result<(), anyhow::Error> {
+    let LoadedQuery {
+        pb_query,
+        query,
+        pedigree,
+        family,
+    } = loaded_query;
+
     // Initialize the random number generator from command line seed if given or local entropy
     // source.
     let mut rng = if let Some(rng_seed) = args.rng_seed {
@@ -1794,56 +2824,121 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         rand::rngs::StdRng::from_entropy()
     };
 
-    tracing::info!("Loading query... {}", args.path_query_json);
-    let pb_query: pbs_query::CaseQuery =
-        serde_json::from_reader(std::fs::File::open(&args.path_query_json)?)?;
-    let query = CaseQuery::try_from(pb_query.clone())?;
+    if dbs.is_none() {
+        interpreter::check_no_databases(&query)
+            .map_err(|e| anyhow::anyhow!("query cannot be run with --no-databases: {}", e))
+            .classify(ErrorClass::Config)?;
+    }
 
-    tracing::info!(
-        "... done loading query = {}",
-        &serde_json::to_string(&query)?
-    );
+    let pseudonyms = if args.anonymize {
+        let path_key = args
+            .path_anonymize_key
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--anonymize requires --path-anonymize-key"))?;
+        let pseudonyms = anonymize::Pseudonyms::new(&query, pedigree.as_ref());
+        tracing::info!("Writing pseudonym key file to {}", path_key);
+        pseudonyms.write_key(path_key)?;
+        Some(pseudonyms)
+    } else {
+        None
+    };
 
-    tracing::info!("Loading worker databases...");
-    let before_loading = Instant::now();
-    let path_worker_db = format!("{}/worker", &args.path_db);
-    let in_memory_dbs = crate::strucvars::query::load_databases(
-        &path_worker_db,
-        args.genome_release,
-        args.max_tad_distance,
-    )
-    .map_err(|e| {
-        anyhow::anyhow!(
-            "could not load worker databases from {}: {}",
-            path_worker_db,
-            e
-        )
-    })?;
-    let annotator = annonars::Annotator::with_path(&args.path_db, args.genome_release)?;
-    let inhouse_db = args
-        .path_inhouse_db
-        .as_ref()
-        .map(|path| inhouse::Dbs::with_path(path, &format!("{}", args.genome_release)))
-        .transpose()?;
-    tracing::info!(
-        "...done loading databases in {:?}",
-        before_loading.elapsed()
-    );
+    let (hgnc_allowlist, gene_panels, phenotype_scores) = if let Some(dbs) = dbs {
+        if args.warm_up {
+            if query.locus.genome_regions.is_empty() {
+                tracing::info!("No regions in locus filter, skipping warm-up");
+            } else {
+                tracing::info!("Warming up annonars databases...");
+                let before_warm_up = Instant::now();
+                let count = dbs
+                    .annotator
+                    .warm_up(&query.locus.genome_regions, args.rocksdb_readahead_size)
+                    .map_err(|e| anyhow::anyhow!("could not warm up annonars databases: {}", e))?;
+                tracing::info!(
+                    "...done warming up {} entries in {:?}",
+                    count.separate_with_commas(),
+                    before_warm_up.elapsed()
+                );
+            }
+        }
 
-    trace_rss_now();
+        trace_rss_now();
+
+        tracing::info!("Translating gene allow list...");
+        let mut hgnc_allowlist = crate::strucvars::query::translate_genes(
+            &query.locus.genes,
+            &dbs.in_memory_dbs,
+            args_common.strict,
+        )?;
+        let mut gene_panels = Vec::new();
+        for path in &args.path_gene_panel {
+            let panel = gene_panel::load(path)?;
+            let panel_allowlist = {
+                let _span = tracing::warn_span!("gene_panel", path = path.as_str()).entered();
+                crate::strucvars::query::translate_genes(
+                    &panel.genes,
+                    &dbs.in_memory_dbs,
+                    args_common.strict,
+                )?
+            };
+            if panel_allowlist.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "none of the {} entries of gene panel {} could be resolved",
+                    panel.genes.len(),
+                    path
+                ))
+                .classify(ErrorClass::Config);
+            }
+            tracing::info!(
+                "  added {} genes from {} entries of gene panel {}",
+                panel_allowlist.len(),
+                panel.genes.len(),
+                path
+            );
+            gene_panels.push(panel.provenance(path, &panel_allowlist));
+            hgnc_allowlist.extend(panel_allowlist);
+        }
+        if !hgnc_allowlist.is_empty() {
+            let before_prefetch = Instant::now();
+            let count = dbs
+                .annotator
+                .prefetch_genes(&hgnc_allowlist)
+                .map_err(|e| anyhow::anyhow!("could not prefetch gene records: {}", e))?;
+            tracing::info!(
+                "...prefetched {} gene records in {:?}",
+                count.separate_with_commas(),
+                before_prefetch.elapsed()
+            );
+        }
 
-    tracing::info!("Translating gene allow list...");
-    let hgnc_allowlist =
-        crate::strucvars::query::translate_genes(&query.locus.genes, &in_memory_dbs);
+        let phenotype_scores = if query.phenotype.terms.is_empty() {
+            Default::default()
+        } else {
+            tracing::info!("Computing phenotype scores...");
+            dbs.annotator
+                .phenotype_scores(&query.phenotype.terms)
+                .map_err(|e| anyhow::anyhow!("could not compute phenotype scores: {}", e))
+                .classify(ErrorClass::Db)?
+        };
+        (hgnc_allowlist, gene_panels, phenotype_scores)
+    } else {
+        Default::default()
+    };
 
     tracing::info!("Running queries...");
     let before_query = Instant::now();
     let query_stats = run_query(
-        &interpreter::QueryInterpreter::new(query, hgnc_allowlist),
-        &pb_query.clone(),
+        &interpreter::QueryInterpreter {
+            phenotype_scores,
+            ..interpreter::QueryInterpreter::new(query, hgnc_allowlist)
+        },
+        &pb_query,
         args,
-        &annotator,
-        &inhouse_db,
+        dbs.map(|dbs| &dbs.annotator),
+        dbs.and_then(|dbs| dbs.inhouse_db.as_ref()),
+        family.as_ref(),
+        pseudonyms.as_ref(),
+        &gene_panels,
         &mut rng,
     )
     .await?;
@@ -1853,17 +2948,28 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         query_stats.count_passed.separate_with_commas(),
         query_stats.count_total.separate_with_commas()
     );
+    if query_stats.count_total == 0 {
+        tracing::info!("input file has no records, wrote output with header only");
+    } else if query_stats.count_passed == 0 {
+        tracing::info!("no records passed the query, wrote output with header only");
+    }
     tracing::info!("passing records by effect type");
     for (effect, count) in query_stats.passed_by_consequences.iter() {
         tracing::info!("{:?} -- {}", effect, count);
     }
+    if query_stats.ann_recovery.total() > 0 {
+        tracing::warn!(
+            "recovered {} malformed or unknown INFO/ANN values",
+            query_stats.ann_recovery.total().separate_with_commas()
+        );
+        for (field, count) in query_stats.ann_recovery.by_field.iter() {
+            tracing::warn!("  {}: {}", field, count.separate_with_commas());
+        }
+    }
+    query_stats.timings.log();
 
     trace_rss_now();
 
-    tracing::info!(
-        "All of `seqvars query` completed in {:?}",
-        before_anything.elapsed()
-    );
     Ok(())
 }
 
@@ -1872,6 +2978,7 @@ mod test {
     use rstest::rstest;
 
     use super::schema::data::{CallInfo, VariantRecord};
+    use crate::common::GenomeRelease;
     use crate::seqvars::query::schema::query::{CaseQuery, GenotypeChoice, RecessiveMode};
 
     #[rstest]
@@ -1925,6 +3032,8 @@ mod test {
                     String::from("father") => SampleGenotypeChoice { sample: String::from("father"), genotype: GenotypeChoice::RecessiveFather, ..Default::default() },
                     String::from("mother") => SampleGenotypeChoice { sample: String::from("mother"), genotype: GenotypeChoice::RecessiveMother, ..Default::default() },
                 },
+                inheritance_models: vec![],
+                dominant: None,
             },
             ..Default::default()
         };
@@ -1956,7 +3065,80 @@ mod test {
             })
             .collect::<Vec<_>>();
 
-        assert_eq!(super::passes_for_gene(&query, &seqvars)?, passes);
+        assert_eq!(
+            super::passes_for_gene(&query, &seqvars, GenomeRelease::Grch37)?,
+            passes
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::x_hemi_ref_het_passes(RecessiveMode::Homozygous, "X", 30_000_000, "1,0/0,0/1", true)]
+    #[case::x_hom_ref_het_passes(RecessiveMode::Any, "X", 100_000_000, "1/1,0/0,0/1", true)]
+    #[case::x_hemi_het_ref_fails(RecessiveMode::Homozygous, "X", 100_000_000, "1,0/1,0/0", false)]
+    #[case::x_hemi_ref_ref_fails(RecessiveMode::Homozygous, "X", 100_000_000, "1,0/0,0/0", false)]
+    #[case::x_hemi_ref_het_comphet_fails(
+        RecessiveMode::CompoundHeterozygous,
+        "X",
+        100_000_000,
+        "1,0/0,0/1",
+        false
+    )]
+    #[case::par_hom_ref_het_fails(RecessiveMode::Homozygous, "X", 100_000, "1/1,0/0,0/1", false)]
+    #[case::auto_hom_ref_het_fails(RecessiveMode::Homozygous, "1", 100_000, "1/1,0/0,0/1", false)]
+    fn passes_for_gene_x_linked(
+        #[case] recessive_mode: RecessiveMode,
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] trio_gts: &str,
+        #[case] passes: bool,
+    ) -> Result<(), anyhow::Error> {
+        use crate::seqvars::query::schema::data::VcfVariant;
+        use crate::seqvars::query::schema::query::{QuerySettingsGenotype, SampleGenotypeChoice};
+
+        let query = CaseQuery {
+            genotype: QuerySettingsGenotype {
+                recessive_mode,
+                sample_genotypes: indexmap::indexmap! {
+                    String::from("index") => SampleGenotypeChoice { sample: String::from("index"), genotype: GenotypeChoice::RecessiveIndex, ..Default::default() },
+                    String::from("father") => SampleGenotypeChoice { sample: String::from("father"), genotype: GenotypeChoice::RecessiveFather, ..Default::default() },
+                    String::from("mother") => SampleGenotypeChoice { sample: String::from("mother"), genotype: GenotypeChoice::RecessiveMother, ..Default::default() },
+                },
+                inheritance_models: vec![],
+                dominant: None,
+            },
+            ..Default::default()
+        };
+        let gts: Vec<&str> = trio_gts.split(',').collect();
+        let seqvars = vec![VariantRecord {
+            vcf_variant: VcfVariant {
+                chrom: chrom.into(),
+                pos,
+                ref_allele: "A".into(),
+                alt_allele: "G".into(),
+            },
+            call_infos: ["index", "father", "mother"]
+                .iter()
+                .zip(gts.iter())
+                .map(|(sample, gt)| {
+                    (
+                        sample.to_string(),
+                        CallInfo {
+                            sample: sample.to_string(),
+                            genotype: Some(gt.to_string()),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }];
+
+        assert_eq!(
+            super::passes_for_gene(&query, &seqvars, GenomeRelease::Grch37)?,
+            passes
+        );
 
         Ok(())
     }
@@ -1990,7 +3172,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             genome_release: crate::common::GenomeRelease::Grch37,
-            path_db: "tests/seqvars/query/db".into(),
+            path_db: Some("tests/seqvars/query/db".into()),
             path_inhouse_db: if with_inhouse {
                 Some(
                     "tests/seqvars/query/db-dynamic/worker/seqvars/inhouse/grch37/active/rocksdb"
@@ -1999,12 +3181,43 @@ mod test {
             } else {
                 None
             },
+            no_databases: false,
             path_query_json,
+            path_gene_panel: Vec::new(),
+            path_ped: None,
             path_input,
+            annotation_source: super::schema::ann::AnnotationSource::Auto,
             path_output,
+            output_format: super::output::OutputFormat::Jsonl,
+            output_mode: super::output::OutputMode::Variant,
+            shard_size: 100_000,
+            writer_queue_size: 1_000,
+            resume: false,
+            auto_compress: false,
+            auto_compress_sample_size: 1_000,
+            auto_compress_io_mb_per_sec: 100.0,
+            embed_gene_cache: false,
+            canonical_json: false,
+            path_alignments: Vec::new(),
+            path_alignments_reference: None,
+            alignment_evidence_max_records: 100,
+            path_prior_classifications: None,
+            path_rna_evidence: None,
+            path_reference: None,
+            context_sequence_flank: 50,
+            anonymize: false,
+            path_anonymize_key: None,
+            recessive_score: false,
+            inhouse_carrier_uuids: false,
             max_results: None,
             rng_seed: Some(42),
             max_tad_distance: 10_000,
+            checkpoint_dir: None,
+            instrument: false,
+            warm_up: false,
+            rocksdb_readahead_size: 0,
+            heap_profile: None,
+            estimate_only: false,
             result_set_id: None,
             case_uuid: None,
         };
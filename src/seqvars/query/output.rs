@@ -0,0 +1,1535 @@
+//! Output sinks for the `seqvars query` results.
+//!
+//! The output always consists of one header record followed by the result records.  As the
+//! header contains the statistics and resource usage of the whole query, it is only known
+//! at the very end.  Sinks are thus required to buffer the records until `finish()` is
+//! called with the header.
+//!
+//! Output paths ending in `.gz` are written bgzip-compressed together with a tabix index
+//! of the records so region slices can be read without scanning the whole file.
+
+use std::io::{BufRead as _, Write as _};
+
+use noodles::csi::binning_index::index::reference_sequence::bin::Chunk;
+
+use crate::common::canonical_json;
+use crate::common::s3::OutputPathHelper;
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+/// Selection of the output sink on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Single JSONL file, header in the first line.
+    #[default]
+    Jsonl,
+    /// Multiple JSONL files with at most `--shard-size` records each, each with a header.
+    ShardedJsonl,
+    /// JSONL written to stdout.
+    Stdout,
+}
+
+/// Selection of the granularity of the output records on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputMode {
+    /// One record per variant.
+    #[default]
+    Variant,
+    /// One record per gene with its variants, see `super::by_gene`.
+    Gene,
+}
+
+/// Compression codec with level for the output files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// No compression.
+    #[default]
+    None,
+    /// gzip with the given level.
+    Gzip(u32),
+    /// zstd with the given level.
+    Zstd(i32),
+    /// bgzip with the default level, selected by a `.gz` output path.
+    Bgzf,
+}
+
+impl Codec {
+    /// Candidates considered for automatic selection.
+    pub const CANDIDATES: [Codec; 6] = [
+        Codec::None,
+        Codec::Gzip(1),
+        Codec::Gzip(6),
+        Codec::Zstd(1),
+        Codec::Zstd(3),
+        Codec::Zstd(9),
+    ];
+
+    /// Name of the codec.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::None => "none",
+            Codec::Gzip(_) => "gzip",
+            Codec::Zstd(_) => "zstd",
+            Codec::Bgzf => "bgzf",
+        }
+    }
+
+    /// Compression level of the codec, zero for `None` and `Bgzf`.
+    pub fn level(&self) -> i32 {
+        match self {
+            Codec::None | Codec::Bgzf => 0,
+            Codec::Gzip(level) => *level as i32,
+            Codec::Zstd(level) => *level,
+        }
+    }
+
+    /// File name suffix to append to the output path; `Bgzf` is only selected for
+    /// paths that already end in `.gz`.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Codec::None | Codec::Bgzf => "",
+            Codec::Gzip(_) => ".gz",
+            Codec::Zstd(_) => ".zst",
+        }
+    }
+
+    /// Wrap `inner` into a writer compressing with this codec.
+    fn writer<W: std::io::Write>(&self, inner: W) -> Result<CompressedWriter<W>, anyhow::Error> {
+        Ok(match self {
+            Codec::None => CompressedWriter::Plain(inner),
+            Codec::Gzip(level) => CompressedWriter::Gzip(flate2::write::GzEncoder::new(
+                inner,
+                flate2::Compression::new(*level),
+            )),
+            Codec::Zstd(level) => CompressedWriter::Zstd(
+                zstd::stream::write::Encoder::new(inner, *level)
+                    .map_err(|e| anyhow::anyhow!("could not create zstd encoder: {}", e))?,
+            ),
+            Codec::Bgzf => CompressedWriter::Bgzf(noodles::bgzf::Writer::new(inner)),
+        })
+    }
+}
+
+/// Writer that compresses with a `Codec`; `finish()` must be called at the end.
+enum CompressedWriter<W: std::io::Write> {
+    /// Uncompressed.
+    Plain(W),
+    /// gzip compressed.
+    Gzip(flate2::write::GzEncoder<W>),
+    /// zstd compressed.
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    /// bgzip compressed.
+    Bgzf(noodles::bgzf::Writer<W>),
+}
+
+impl<W: std::io::Write> CompressedWriter<W> {
+    /// Write the compression trailer and return the inner writer.
+    fn finish(self) -> Result<W, anyhow::Error> {
+        match self {
+            CompressedWriter::Plain(inner) => Ok(inner),
+            CompressedWriter::Gzip(encoder) => encoder.finish(),
+            CompressedWriter::Zstd(encoder) => encoder.finish(),
+            CompressedWriter::Bgzf(writer) => writer.finish(),
+        }
+        .map_err(|e| anyhow::anyhow!("could not finish compressed output: {}", e))
+    }
+
+    /// Return the current virtual position if bgzip compressed.
+    fn virtual_position(&self) -> Option<noodles::bgzf::VirtualPosition> {
+        match self {
+            CompressedWriter::Bgzf(writer) => Some(writer.virtual_position()),
+            _ => None,
+        }
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            CompressedWriter::Bgzf(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            CompressedWriter::Bgzf(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Settings for the automatic selection of the output codec.
+///
+/// Each candidate codec compresses the sampled records and the one minimizing the
+/// estimated time for compressing plus writing at `io_mb_per_sec` is selected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoCompress {
+    /// Number of records to sample.
+    pub sample_records: usize,
+    /// Assumed output I/O throughput in MB/s.
+    pub io_mb_per_sec: f64,
+}
+
+impl AutoCompress {
+    /// Select the codec for the `sample` of `sample_records` JSONL records.
+    pub fn select(
+        &self,
+        sample: &[u8],
+        sample_records: usize,
+    ) -> Result<(Codec, pbs_output::OutputCompression), anyhow::Error> {
+        let mut measurements = Vec::new();
+        let mut best: Option<(f64, Codec)> = None;
+        for codec in Codec::CANDIDATES {
+            let before = std::time::Instant::now();
+            let mut writer = codec.writer(Vec::with_capacity(sample.len()))?;
+            writer
+                .write_all(sample)
+                .map_err(|e| anyhow::anyhow!("could not compress sample: {}", e))?;
+            let compressed_bytes = writer.finish()?.len();
+            let elapsed = before.elapsed().as_secs_f64();
+
+            let estimated = elapsed + compressed_bytes as f64 / (self.io_mb_per_sec * 1e6);
+            // Guard against zero durations on coarse clocks.
+            let throughput_mb_per_sec = sample.len() as f64 / elapsed.max(1e-9) / 1e6;
+            tracing::debug!(
+                "codec {}:{} compresses {} to {} bytes at {:.1} MB/s",
+                codec.name(),
+                codec.level(),
+                sample.len(),
+                compressed_bytes,
+                throughput_mb_per_sec
+            );
+            measurements.push(pbs_output::CodecMeasurement {
+                codec: codec.name().to_string(),
+                level: codec.level(),
+                uncompressed_bytes: sample.len() as u64,
+                compressed_bytes: compressed_bytes as u64,
+                throughput_mb_per_sec,
+            });
+            let is_better = match best {
+                Some((best_estimated, _)) => estimated < best_estimated,
+                None => true,
+            };
+            if is_better {
+                best = Some((estimated, codec));
+            }
+        }
+
+        let (_, codec) = best.expect("have candidates");
+        tracing::info!(
+            "selected output codec {}:{} based on {} records",
+            codec.name(),
+            codec.level(),
+            sample_records
+        );
+        Ok((
+            codec,
+            pbs_output::OutputCompression {
+                codec: codec.name().to_string(),
+                level: codec.level(),
+                sample_records: sample_records as u64,
+                measurements,
+            },
+        ))
+    }
+}
+
+/// Select codec for the records in `buffer` with `auto_compress`, if any, and return
+/// it together with `header` recording the selection.
+///
+/// Output paths ending in `.gz` are always written with `Codec::Bgzf`.
+fn select_codec(
+    path_output: &str,
+    auto_compress: Option<AutoCompress>,
+    buffer: &RecordBuffer,
+    header: &pbs_output::OutputHeader,
+) -> Result<(Codec, pbs_output::OutputHeader), anyhow::Error> {
+    if path_output.ends_with(".gz") {
+        return Ok((Codec::Bgzf, header.clone()));
+    }
+    let Some(auto_compress) = auto_compress else {
+        return Ok((Codec::None, header.clone()));
+    };
+    let (sample, sample_records) = buffer.head(auto_compress.sample_records)?;
+    let (codec, compression) = auto_compress.select(&sample, sample_records)?;
+    Ok((
+        codec,
+        pbs_output::OutputHeader {
+            compression: Some(compression),
+            ..header.clone()
+        },
+    ))
+}
+
+/// Location of an output record as needed for the tabix index.
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RecordLocus {
+    /// The variant, missing for records without coordinates.
+    vcf_variant: Option<VcfLocus>,
+}
+
+/// Coordinates of the variant of an output record.
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct VcfLocus {
+    /// Chromosome name.
+    chrom: String,
+    /// 1-based start position.
+    pos: i32,
+    /// Reference allele, determines the end position.
+    ref_allele: String,
+}
+
+/// Builder for the tabix index of the records in bgzip-compressed output.
+///
+/// The records must be sorted by coordinate as written by `seqvars query`.  As the
+/// lines are JSON, the index header only marks the 1-based coordinates and the header
+/// line to skip; readers seek with the index and filter on `vcfVariant` themselves.
+struct RecordIndexer {
+    /// The tabix indexer.
+    indexer: noodles::tabix::index::Indexer,
+}
+
+impl RecordIndexer {
+    /// Create a new indexer.
+    fn new() -> Self {
+        use noodles::csi::binning_index::index::header::{
+            format::CoordinateSystem, Builder, Format,
+        };
+
+        let mut indexer = noodles::tabix::index::Indexer::default();
+        indexer.set_header(
+            Builder::vcf()
+                .set_format(Format::Generic(CoordinateSystem::Gff))
+                .set_line_skip_count(1)
+                .build(),
+        );
+        Self { indexer }
+    }
+
+    /// Add the record in `line` written to `chunk`; records without coordinates are
+    /// not indexed.
+    fn add_line(&mut self, line: &str, chunk: Chunk) -> Result<(), anyhow::Error> {
+        let RecordLocus { vcf_variant } = serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("could not parse record for indexing: {}", e))?;
+        let Some(VcfLocus {
+            chrom,
+            pos,
+            ref_allele,
+        }) = vcf_variant
+        else {
+            return Ok(());
+        };
+        let start = usize::try_from(pos)
+            .ok()
+            .and_then(noodles::core::Position::new)
+            .ok_or_else(|| anyhow::anyhow!("invalid position for indexing: {}", pos))?;
+        let end = noodles::core::Position::new(start.get() + ref_allele.len().max(1) - 1)
+            .expect("end must be positive");
+        self.indexer
+            .add_record(&chrom, start, end, chunk)
+            .map_err(|e| anyhow::anyhow!("could not add record to tabix index: {}", e))
+    }
+
+    /// Build the index and write it to `path`.
+    fn write(self, path: &str) -> Result<(), anyhow::Error> {
+        tracing::debug!("writing index {}", path);
+        noodles::tabix::write(path, &self.indexer.build())
+            .map_err(|e| anyhow::anyhow!("could not write tabix index {}: {}", path, e))
+    }
+}
+
+/// Write the record `line` to `writer` and add it to `indexer`, if any.
+fn write_record_line<W: std::io::Write>(
+    writer: &mut CompressedWriter<W>,
+    line: &str,
+    indexer: Option<&mut RecordIndexer>,
+) -> Result<(), anyhow::Error> {
+    let start = writer.virtual_position();
+    writeln!(writer, "{}", line)
+        .map_err(|e| anyhow::anyhow!("could not write record to output file: {}", e))?;
+    if let (Some(indexer), Some(start), Some(end)) = (indexer, start, writer.virtual_position()) {
+        indexer.add_line(line, Chunk::new(start, end))?;
+    }
+    Ok(())
+}
+
+/// Trait for the destination of query results.
+///
+/// The contract is as follows:
+///
+/// - `write_record()` is called for each result record in output order; implementations
+///   may buffer the records (e.g., in a temporary file) until `finish()` is called
+/// - `finish()` is called exactly once with the header and must write out header and all
+///   buffered records; it returns the helpers for the files written so the caller can
+///   upload them to S3 if necessary
+/// - calling any function after `finish()` is an error
+pub trait ResultSink {
+    /// Write out (or buffer) a single result record.
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error>;
+
+    /// Write out (or buffer) a single JSON line in place of a result record, e.g., an
+    /// aggregated record; only supported by the sinks writing JSONL.
+    fn write_line(&mut self, _line: &str) -> Result<(), anyhow::Error> {
+        anyhow::bail!("writing lines is not supported by this sink")
+    }
+
+    /// Write out the header, all buffered records, and the optional trailer.
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error>;
+}
+
+/// Construct the `ResultSink` for the given output format and path.
+///
+/// If `local_dir` is given, local files for S3 upload are written there rather than to
+/// a temporary directory that is removed on exit.
+///
+/// If `auto_compress` is given, the codec of output files is selected automatically and
+/// its suffix is appended to the file names.  Output paths ending in `.gz` are always
+/// bgzip-compressed and indexed.  Output to stdout is never compressed.
+///
+/// If `canonical_json` is set, all lines are written as canonical JSON, see
+/// `common::canonical_json`.
+///
+/// With `OutputMode::Gene`, the records are aggregated by gene with sample `index` for
+/// the recessive flags, see `GeneSink`.
+#[allow(clippy::too_many_arguments)]
+pub fn build_sink(
+    output_format: OutputFormat,
+    output_mode: OutputMode,
+    path_output: &str,
+    shard_size: usize,
+    local_dir: Option<std::path::PathBuf>,
+    auto_compress: Option<AutoCompress>,
+    canonical_json: bool,
+    index: Option<String>,
+) -> Result<Box<dyn ResultSink + Send>, anyhow::Error> {
+    if auto_compress.is_some() && path_output.ends_with(".gz") {
+        tracing::warn!("ignoring automatic compression for bgzip-compressed output");
+    }
+    let sink: Box<dyn ResultSink + Send> = match output_format {
+        OutputFormat::Jsonl => Box::new(
+            JsonlFileSink::new(path_output)?
+                .with_local_dir(local_dir)
+                .with_auto_compress(auto_compress)
+                .with_canonical_json(canonical_json),
+        ),
+        OutputFormat::ShardedJsonl => Box::new(
+            ShardedJsonlSink::new(path_output, shard_size)?
+                .with_local_dir(local_dir)
+                .with_auto_compress(auto_compress)
+                .with_canonical_json(canonical_json),
+        ),
+        OutputFormat::Stdout => {
+            if auto_compress.is_some() {
+                tracing::warn!("ignoring automatic compression for output to stdout");
+            }
+            Box::new(StdoutSink::new()?.with_canonical_json(canonical_json))
+        }
+    };
+    Ok(match output_mode {
+        OutputMode::Variant => sink,
+        OutputMode::Gene => {
+            Box::new(GeneSink::new(sink, index).with_canonical_json(canonical_json))
+        }
+    })
+}
+
+/// Construct the `OutputPathHelper` for `path_out`, using `local_dir` if given.
+fn new_path_helper(
+    path_out: &str,
+    local_dir: &Option<std::path::PathBuf>,
+) -> Result<OutputPathHelper, anyhow::Error> {
+    match local_dir {
+        Some(local_dir) => OutputPathHelper::with_local_dir(path_out, local_dir),
+        None => OutputPathHelper::new(path_out),
+    }
+}
+
+/// Buffer for records in a temporary JSONL file.
+struct RecordBuffer {
+    /// Temporary directory holding the buffer file.
+    tmp_dir: tempfile::TempDir,
+    /// Writer to the buffer file, `None` after `finish()`.
+    writer: Option<std::io::BufWriter<std::fs::File>>,
+    /// Whether to write canonical JSON.
+    canonical: bool,
+}
+
+impl RecordBuffer {
+    /// Name of the buffer file in the temporary directory.
+    const FILE_NAME: &'static str = "records.jsonl";
+
+    /// Create a new buffer in a fresh temporary directory.
+    fn new() -> Result<Self, anyhow::Error> {
+        let tmp_dir = tempfile::TempDir::new()
+            .map_err(|e| anyhow::anyhow!("could not create temporary directory: {}", e))?;
+        let writer = std::fs::File::create(tmp_dir.path().join(Self::FILE_NAME))
+            .map(std::io::BufWriter::new)
+            .map_err(|e| anyhow::anyhow!("could not create temporary record file: {}", e))?;
+        Ok(Self {
+            tmp_dir,
+            writer: Some(writer),
+            canonical: false,
+        })
+    }
+
+    /// Append a record to the buffer.
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        let line = canonical_json::to_string(record, self.canonical)
+            .map_err(|e| anyhow::anyhow!("could not convert record to JSON: {}", e))?;
+        self.write_line(&line)
+    }
+
+    /// Append a JSON line to the buffer.
+    fn write_line(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("cannot write record, sink already finished"))?;
+        writeln!(writer, "{}", line)
+            .map_err(|e| anyhow::anyhow!("could not write record to temporary file: {}", e))
+    }
+
+    /// Return the first (at most) `count` records and their number; must be called
+    /// after `take_reader()`.
+    fn head(&self, count: usize) -> Result<(Vec<u8>, usize), anyhow::Error> {
+        let reader = std::fs::File::open(self.tmp_dir.path().join(Self::FILE_NAME))
+            .map(std::io::BufReader::new)
+            .map_err(|e| anyhow::anyhow!("could not open temporary record file: {}", e))?;
+        let mut result = Vec::new();
+        let mut records = 0;
+        for line in reader.lines().take(count) {
+            let line =
+                line.map_err(|e| anyhow::anyhow!("could not read temporary record file: {}", e))?;
+            result.extend_from_slice(line.as_bytes());
+            result.push(b'\n');
+            records += 1;
+        }
+        Ok((result, records))
+    }
+
+    /// Flush the buffer and open it for reading.
+    fn take_reader(&mut self) -> Result<std::io::BufReader<std::fs::File>, anyhow::Error> {
+        let writer = self
+            .writer
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("sink already finished"))?;
+        writer
+            .into_inner()
+            .map_err(|e| anyhow::anyhow!("could not flush temporary record file: {}", e))?;
+        std::fs::File::open(self.tmp_dir.path().join(Self::FILE_NAME))
+            .map(std::io::BufReader::new)
+            .map_err(|e| anyhow::anyhow!("could not open temporary record file: {}", e))
+    }
+}
+
+/// Write the header as a JSON line to `writer`.
+fn write_header<W: std::io::Write>(
+    writer: &mut W,
+    header: &pbs_output::OutputHeader,
+    canonical: bool,
+) -> Result<(), anyhow::Error> {
+    writeln!(
+        writer,
+        "{}",
+        canonical_json::to_string(header, canonical)
+            .map_err(|e| anyhow::anyhow!("could not convert header to JSON: {}", e))?
+    )
+    .map_err(|e| anyhow::anyhow!("could not write header: {}", e))
+}
+
+/// Write the trailer, if any, as a JSON line to `writer`.
+fn write_trailer<W: std::io::Write>(
+    writer: &mut W,
+    trailer: Option<&pbs_output::OutputTrailer>,
+    canonical: bool,
+) -> Result<(), anyhow::Error> {
+    if let Some(trailer) = trailer {
+        writeln!(
+            writer,
+            "{}",
+            canonical_json::to_string(trailer, canonical)
+                .map_err(|e| anyhow::anyhow!("could not convert trailer to JSON: {}", e))?
+        )
+        .map_err(|e| anyhow::anyhow!("could not write trailer: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Create the (potentially temporary, when writing to S3) output file.
+fn create_output_file(
+    out_path_helper: &OutputPathHelper,
+) -> Result<std::io::BufWriter<std::fs::File>, anyhow::Error> {
+    tracing::debug!("writing file {}", out_path_helper.path_out());
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(out_path_helper.path_out())
+        .map(std::io::BufWriter::new)
+        .map_err(|e| anyhow::anyhow!("could not open output file: {}", e))
+}
+
+/// Write all results into one JSONL file, header first and trailer (if any) last.
+pub struct JsonlFileSink {
+    /// Path to the output file.
+    path_output: String,
+    /// Optional persistent local directory for S3 mode.
+    local_dir: Option<std::path::PathBuf>,
+    /// Optional automatic selection of the codec.
+    auto_compress: Option<AutoCompress>,
+    /// Buffer for the records.
+    buffer: RecordBuffer,
+}
+
+impl JsonlFileSink {
+    /// Create a new sink writing to `path_output`.
+    pub fn new(path_output: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            path_output: path_output.to_string(),
+            local_dir: None,
+            auto_compress: None,
+            buffer: RecordBuffer::new()?,
+        })
+    }
+
+    /// Set the persistent local directory for S3 mode.
+    pub fn with_local_dir(self, local_dir: Option<std::path::PathBuf>) -> Self {
+        Self { local_dir, ..self }
+    }
+
+    /// Set the automatic selection of the codec.
+    pub fn with_auto_compress(self, auto_compress: Option<AutoCompress>) -> Self {
+        Self {
+            auto_compress,
+            ..self
+        }
+    }
+
+    /// Set whether to write canonical JSON.
+    pub fn with_canonical_json(mut self, canonical: bool) -> Self {
+        self.buffer.canonical = canonical;
+        self
+    }
+}
+
+impl ResultSink for JsonlFileSink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        self.buffer.write_record(record)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        self.buffer.write_line(line)
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        let mut reader = self.buffer.take_reader()?;
+        let (codec, header) =
+            select_codec(&self.path_output, self.auto_compress, &self.buffer, header)?;
+        let out_path_helper = new_path_helper(
+            &format!("{}{}", self.path_output, codec.suffix()),
+            &self.local_dir,
+        )?;
+        let mut writer = codec.writer(create_output_file(&out_path_helper)?)?;
+        write_header(&mut writer, &header, self.buffer.canonical)?;
+        let mut indexer = (codec == Codec::Bgzf).then(RecordIndexer::new);
+        if let Some(indexer) = indexer.as_mut() {
+            for line in reader.lines() {
+                let line = line
+                    .map_err(|e| anyhow::anyhow!("could not read temporary record file: {}", e))?;
+                write_record_line(&mut writer, &line, Some(indexer))?;
+            }
+        } else {
+            std::io::copy(&mut reader, &mut writer).map_err(|e| {
+                anyhow::anyhow!("could not copy temporary file to output file: {}", e)
+            })?;
+        }
+        write_trailer(&mut writer, trailer, self.buffer.canonical)?;
+        writer
+            .finish()?
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not flush output file before closing: {}", e))?;
+        if let Some(indexer) = indexer {
+            indexer.write(&format!("{}.tbi", out_path_helper.path_out()))?;
+        }
+        Ok(vec![out_path_helper])
+    }
+}
+
+/// Write the results into multiple JSONL files of at most `shard_size` records.
+///
+/// The shards are named after the output path with the shard number inserted before
+/// the extension, e.g., `out.00000.jsonl`.  Each shard starts with the header and ends
+/// with the trailer (if any).  At least one shard is written, even if there are no
+/// records.
+pub struct ShardedJsonlSink {
+    /// Path to the output file that the shard paths are derived from.
+    path_output: String,
+    /// Maximal number of records per shard.
+    shard_size: usize,
+    /// Optional persistent local directory for S3 mode.
+    local_dir: Option<std::path::PathBuf>,
+    /// Optional automatic selection of the codec.
+    auto_compress: Option<AutoCompress>,
+    /// Buffer for the records.
+    buffer: RecordBuffer,
+}
+
+impl ShardedJsonlSink {
+    /// Create a new sink writing shards next to `path_output`.
+    pub fn new(path_output: &str, shard_size: usize) -> Result<Self, anyhow::Error> {
+        if shard_size == 0 {
+            anyhow::bail!("shard size must be positive");
+        }
+        Ok(Self {
+            path_output: path_output.to_string(),
+            shard_size,
+            local_dir: None,
+            auto_compress: None,
+            buffer: RecordBuffer::new()?,
+        })
+    }
+
+    /// Set the persistent local directory for S3 mode.
+    pub fn with_local_dir(self, local_dir: Option<std::path::PathBuf>) -> Self {
+        Self { local_dir, ..self }
+    }
+
+    /// Set the automatic selection of the codec.
+    pub fn with_auto_compress(self, auto_compress: Option<AutoCompress>) -> Self {
+        Self {
+            auto_compress,
+            ..self
+        }
+    }
+
+    /// Set whether to write canonical JSON.
+    pub fn with_canonical_json(mut self, canonical: bool) -> Self {
+        self.buffer.canonical = canonical;
+        self
+    }
+
+    /// Return the path of the shard with the given number.
+    pub fn shard_path(path_output: &str, shard_no: usize) -> String {
+        let (stem, ext) = match path_output.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => (stem, ext),
+            _ => (path_output, "jsonl"),
+        };
+        format!("{}.{:05}.{}", stem, shard_no, ext)
+    }
+}
+
+impl ResultSink for ShardedJsonlSink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        self.buffer.write_record(record)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        self.buffer.write_line(line)
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        let reader = self.buffer.take_reader()?;
+        let (codec, header) =
+            select_codec(&self.path_output, self.auto_compress, &self.buffer, header)?;
+        let mut result: Vec<OutputPathHelper> = Vec::new();
+        let mut current: Option<(
+            CompressedWriter<std::io::BufWriter<std::fs::File>>,
+            Option<RecordIndexer>,
+        )> = None;
+        let mut count = 0usize;
+        let canonical = self.buffer.canonical;
+
+        // Write the trailer, then finish and flush the `writer` of a complete shard and
+        // write its index, if any.
+        let close = |(mut writer, indexer): (
+            CompressedWriter<std::io::BufWriter<std::fs::File>>,
+            Option<RecordIndexer>,
+        ),
+                     out_path_helper: &OutputPathHelper| {
+            write_trailer(&mut writer, trailer, canonical)?;
+            writer.finish()?.flush().map_err(|e| {
+                anyhow::anyhow!("could not flush output file before closing: {}", e)
+            })?;
+            if let Some(indexer) = indexer {
+                indexer.write(&format!("{}.tbi", out_path_helper.path_out()))?;
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        for line in reader.lines() {
+            let line =
+                line.map_err(|e| anyhow::anyhow!("could not read temporary record file: {}", e))?;
+            if count % self.shard_size == 0 {
+                if let Some(current) = current.take() {
+                    close(current, result.last().expect("opened with shard"))?;
+                }
+                let out_path_helper = new_path_helper(
+                    &format!(
+                        "{}{}",
+                        Self::shard_path(&self.path_output, result.len()),
+                        codec.suffix()
+                    ),
+                    &self.local_dir,
+                )?;
+                let mut writer = codec.writer(create_output_file(&out_path_helper)?)?;
+                write_header(&mut writer, &header, self.buffer.canonical)?;
+                current = Some((writer, (codec == Codec::Bgzf).then(RecordIndexer::new)));
+                result.push(out_path_helper);
+            }
+            let (writer, indexer) = current.as_mut().expect("opened above");
+            write_record_line(writer, &line, indexer.as_mut())?;
+            count += 1;
+        }
+
+        if let Some(current) = current.take() {
+            close(current, result.last().expect("opened with shard"))?;
+        } else {
+            // No records, write out a single shard with only the header.
+            let out_path_helper = new_path_helper(
+                &format!(
+                    "{}{}",
+                    Self::shard_path(&self.path_output, 0),
+                    codec.suffix()
+                ),
+                &self.local_dir,
+            )?;
+            let mut writer = codec.writer(create_output_file(&out_path_helper)?)?;
+            write_header(&mut writer, &header, self.buffer.canonical)?;
+            close(
+                (writer, (codec == Codec::Bgzf).then(RecordIndexer::new)),
+                &out_path_helper,
+            )?;
+            result.push(out_path_helper);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Write all results as JSONL to stdout, header first and trailer (if any) last.
+pub struct StdoutSink {
+    /// Buffer for the records.
+    buffer: RecordBuffer,
+}
+
+impl StdoutSink {
+    /// Create a new sink writing to stdout.
+    pub fn new() -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            buffer: RecordBuffer::new()?,
+        })
+    }
+
+    /// Set whether to write canonical JSON.
+    pub fn with_canonical_json(mut self, canonical: bool) -> Self {
+        self.buffer.canonical = canonical;
+        self
+    }
+}
+
+impl ResultSink for StdoutSink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        self.buffer.write_record(record)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        self.buffer.write_line(line)
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        let mut reader = self.buffer.take_reader()?;
+        let stdout = std::io::stdout();
+        let mut writer = std::io::BufWriter::new(stdout.lock());
+        write_header(&mut writer, header, self.buffer.canonical)?;
+        std::io::copy(&mut reader, &mut writer)
+            .map_err(|e| anyhow::anyhow!("could not copy temporary file to stdout: {}", e))?;
+        write_trailer(&mut writer, trailer, self.buffer.canonical)?;
+        writer
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not flush stdout: {}", e))?;
+        Ok(Vec::new())
+    }
+}
+
+/// Sink that aggregates the records by gene and writes one line per gene to an inner
+/// JSONL sink on `finish()`.
+///
+/// The records are kept in memory until `finish()`, so this is meant for queries with
+/// moderate numbers of passing variants.
+pub struct GeneSink {
+    /// The sink to write the gene lines to.
+    inner: Box<dyn ResultSink + Send>,
+    /// Aggregation of the records by gene.
+    aggregator: super::by_gene::GeneAggregator,
+    /// Whether to write canonical JSON.
+    canonical: bool,
+}
+
+impl GeneSink {
+    /// Create a new sink writing to `inner` with sample `index` for the recessive flags.
+    pub fn new(inner: Box<dyn ResultSink + Send>, index: Option<String>) -> Self {
+        Self {
+            inner,
+            aggregator: super::by_gene::GeneAggregator::new(index),
+            canonical: false,
+        }
+    }
+
+    /// Set whether to write canonical JSON.
+    pub fn with_canonical_json(self, canonical: bool) -> Self {
+        Self { canonical, ..self }
+    }
+}
+
+impl ResultSink for GeneSink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        self.aggregator.add(record);
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        let aggregator = std::mem::take(&mut self.aggregator);
+        for gene_record in aggregator.into_records() {
+            let line = canonical_json::to_string(&gene_record, self.canonical)
+                .map_err(|e| anyhow::anyhow!("could not convert gene record to JSON: {}", e))?;
+            self.inner.write_line(&line)?;
+        }
+        self.inner.finish(header, trailer)
+    }
+}
+
+/// Message from a `ThreadedSink` to its writer thread.
+enum WriterMessage {
+    /// Write out (or buffer) the record.
+    Record(Box<pbs_output::OutputRecord>),
+    /// Finish the inner sink with the header and optional trailer.
+    Finish(
+        Box<pbs_output::OutputHeader>,
+        Option<Box<pbs_output::OutputTrailer>>,
+    ),
+}
+
+/// Result of the writer thread of a `ThreadedSink`.
+type WriterResult = Result<Vec<OutputPathHelper>, anyhow::Error>;
+
+/// Sink that hands records to another sink running in a dedicated writer thread.
+///
+/// Records are passed through a bounded channel so writing does not stall the
+/// annotation and vice versa; if the channel is full, `write_record()` blocks until the
+/// writer has caught up.  As there is one channel, the records reach the inner sink in
+/// the order of the `write_record()` calls.
+///
+/// Dropping the sink without calling `finish()` closes the channel and waits for the
+/// writer thread to hand all queued records to the inner sink before dropping it.
+pub struct ThreadedSink {
+    /// Sending end of the channel, `None` after `finish()`.
+    sender: Option<std::sync::mpsc::SyncSender<WriterMessage>>,
+    /// Handle of the writer thread, `None` after it has been joined.
+    handle: Option<std::thread::JoinHandle<WriterResult>>,
+}
+
+impl ThreadedSink {
+    /// Spawn the writer thread for `inner` with a channel of `capacity` records.
+    pub fn spawn(
+        mut inner: Box<dyn ResultSink + Send>,
+        capacity: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity);
+        let handle = std::thread::Builder::new()
+            .name("seqvars-query-writer".to_string())
+            .spawn(move || -> WriterResult {
+                for message in receiver {
+                    match message {
+                        WriterMessage::Record(record) => inner.write_record(&record)?,
+                        WriterMessage::Finish(header, trailer) => {
+                            return inner.finish(&header, trailer.as_deref());
+                        }
+                    }
+                }
+                // The channel was closed without `finish()`, all records have been handed
+                // to the inner sink.
+                Ok(Vec::new())
+            })
+            .map_err(|e| anyhow::anyhow!("could not spawn writer thread: {}", e))?;
+        Ok(Self {
+            sender: Some(sender),
+            handle: Some(handle),
+        })
+    }
+
+    /// Close the channel and wait for the writer thread to terminate.
+    fn join(&mut self) -> WriterResult {
+        self.sender.take();
+        let handle = self
+            .handle
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("writer thread already terminated"))?;
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("writer thread panicked"))?
+    }
+
+    /// Send `message` to the writer thread.
+    ///
+    /// If the writer thread has terminated because of an error, that error is returned.
+    fn send(&mut self, message: WriterMessage) -> Result<(), anyhow::Error> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("sink already finished"))?;
+        if sender.send(message).is_err() {
+            self.join()?;
+            anyhow::bail!("writer thread terminated unexpectedly");
+        }
+        Ok(())
+    }
+}
+
+impl ResultSink for ThreadedSink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        self.send(WriterMessage::Record(Box::new(record.clone())))
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        self.send(WriterMessage::Finish(
+            Box::new(header.clone()),
+            trailer.cloned().map(Box::new),
+        ))?;
+        self.join()
+    }
+}
+
+impl Drop for ThreadedSink {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            if let Err(e) = self.join() {
+                tracing::warn!("problem writing output records: {}", e);
+            }
+        }
+    }
+}
+
+/// Sink that keeps all results in memory, for testing.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    /// The header, set on `finish()`.
+    pub header: Option<pbs_output::OutputHeader>,
+    /// The trailer, if any, set on `finish()`.
+    pub trailer: Option<pbs_output::OutputTrailer>,
+    /// The records written so far.
+    pub records: Vec<pbs_output::OutputRecord>,
+}
+
+#[cfg(test)]
+impl ResultSink for MemorySink {
+    fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+        if self.header.is_some() {
+            anyhow::bail!("cannot write record, sink already finished");
+        }
+        self.records.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        header: &pbs_output::OutputHeader,
+        trailer: Option<&pbs_output::OutputTrailer>,
+    ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+        if self.header.is_some() {
+            anyhow::bail!("sink already finished");
+        }
+        self.header = Some(header.clone());
+        self.trailer = trailer.cloned();
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read as _, Write as _};
+
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        AutoCompress, Codec, JsonlFileSink, MemorySink, ResultSink, ShardedJsonlSink, ThreadedSink,
+    };
+    use crate::common::canonical_json;
+    use crate::common::s3::OutputPathHelper;
+    use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+    /// Decompress `data` written with `codec`.
+    fn decompress(codec: Codec, data: &[u8]) -> Result<String, anyhow::Error> {
+        let mut result = String::new();
+        match codec {
+            Codec::None => {
+                result = String::from_utf8(data.to_vec())?;
+            }
+            Codec::Gzip(_) | Codec::Bgzf => {
+                flate2::read::MultiGzDecoder::new(data).read_to_string(&mut result)?;
+            }
+            Codec::Zstd(_) => {
+                zstd::stream::read::Decoder::new(data)?.read_to_string(&mut result)?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn record(uuid: &str) -> pbs_output::OutputRecord {
+        pbs_output::OutputRecord {
+            uuid: uuid.to_string(),
+            case_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn header() -> pbs_output::OutputHeader {
+        pbs_output::OutputHeader {
+            case_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn trailer() -> pbs_output::OutputTrailer {
+        pbs_output::OutputTrailer {
+            gene_cache: vec![pbs_output::GeneCacheEntry {
+                hgnc_id: "HGNC:1100".to_string(),
+                symbol: "BRCA1".to_string(),
+                name: "BRCA1 DNA repair associated".to_string(),
+                alias_symbols: vec!["RNF53".to_string()],
+            }],
+        }
+    }
+
+    #[test]
+    fn memory_sink() -> Result<(), anyhow::Error> {
+        let mut sink = MemorySink::default();
+        sink.write_record(&record("a"))?;
+        sink.write_record(&record("b"))?;
+        sink.finish(&header(), None)?;
+
+        assert_eq!(sink.header, Some(header()));
+        assert_eq!(sink.records, vec![record("a"), record("b")]);
+
+        assert!(sink.write_record(&record("c")).is_err());
+        assert!(sink.finish(&header(), None).is_err());
+
+        Ok(())
+    }
+
+    /// Sink that shares the written records with the test, optionally failing to write.
+    #[derive(Debug, Default)]
+    struct SharedSink {
+        /// The records written so far.
+        records: Arc<Mutex<Vec<pbs_output::OutputRecord>>>,
+        /// Whether to fail on writing records.
+        fail: bool,
+    }
+
+    impl ResultSink for SharedSink {
+        fn write_record(&mut self, record: &pbs_output::OutputRecord) -> Result<(), anyhow::Error> {
+            if self.fail {
+                anyhow::bail!("disk full");
+            }
+            self.records.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+
+        fn finish(
+            &mut self,
+            _header: &pbs_output::OutputHeader,
+            _trailer: Option<&pbs_output::OutputTrailer>,
+        ) -> Result<Vec<OutputPathHelper>, anyhow::Error> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn threaded_sink() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let uuids = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut sink = ThreadedSink::spawn(Box::new(JsonlFileSink::new(&path_output)?), 1)?;
+        for uuid in &uuids {
+            sink.write_record(&record(uuid))?;
+        }
+        let helpers = sink.finish(&header(), Some(&trailer()))?;
+        assert_eq!(helpers.len(), 1);
+        assert!(sink.write_record(&record("x")).is_err());
+        assert!(sink.finish(&header(), None).is_err());
+
+        let expected = std::iter::once(serde_json::to_string(&header())?)
+            .chain(
+                uuids
+                    .iter()
+                    .map(|uuid| serde_json::to_string(&record(uuid)).unwrap()),
+            )
+            .chain(std::iter::once(serde_json::to_string(&trailer())?))
+            .collect::<Vec<_>>();
+        let lines = std::fs::read_to_string(&path_output)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn threaded_sink_flush_on_drop() -> Result<(), anyhow::Error> {
+        let inner = SharedSink::default();
+        let records = inner.records.clone();
+
+        let uuids = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+        {
+            let mut sink = ThreadedSink::spawn(Box::new(inner), 2)?;
+            for uuid in &uuids {
+                sink.write_record(&record(uuid))?;
+            }
+        }
+
+        assert_eq!(
+            *records.lock().unwrap(),
+            uuids.iter().map(|uuid| record(uuid)).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn threaded_sink_error() -> Result<(), anyhow::Error> {
+        let inner = SharedSink {
+            fail: true,
+            ..Default::default()
+        };
+
+        let mut sink = ThreadedSink::spawn(Box::new(inner), 1)?;
+        // The first record is accepted before the writer fails, the error surfaces at the
+        // latest on `finish()`.
+        let result = (0..10)
+            .try_for_each(|i| sink.write_record(&record(&i.to_string())))
+            .and_then(|()| sink.finish(&header(), None).map(|_| ()));
+        assert_eq!(
+            result.map_err(|e| e.to_string()),
+            Err("disk full".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_file_sink() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = JsonlFileSink::new(&path_output)?;
+        sink.write_record(&record("a"))?;
+        sink.write_record(&record("b"))?;
+        let helpers = sink.finish(&header(), None)?;
+        assert_eq!(helpers.len(), 1);
+        assert!(sink.write_record(&record("c")).is_err());
+
+        let lines = std::fs::read_to_string(&path_output)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::to_string(&header())?,
+                serde_json::to_string(&record("a"))?,
+                serde_json::to_string(&record("b"))?,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_file_sink_bgzf() -> Result<(), anyhow::Error> {
+        use noodles::csi::binning_index::{BinningIndex as _, ReferenceSequence as _};
+
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl.gz", tmpdir.to_string_lossy());
+        let record = |uuid: &str, chrom: &str, pos: i32| pbs_output::OutputRecord {
+            vcf_variant: Some(pbs_output::VcfVariant {
+                chrom: chrom.to_string(),
+                pos,
+                ref_allele: "A".to_string(),
+                alt_allele: "G".to_string(),
+                ..Default::default()
+            }),
+            ..record(uuid)
+        };
+
+        let mut sink = JsonlFileSink::new(&path_output)?;
+        sink.write_record(&record("a", "1", 100))?;
+        sink.write_record(&record("b", "1", 200))?;
+        sink.write_record(&record("c", "2", 100))?;
+        let helpers = sink.finish(&header(), Some(&trailer()))?;
+        assert_eq!(helpers.len(), 1);
+
+        assert!(crate::common::noodles::is_bgzf(&path_output)?);
+        let lines = decompress(Codec::Bgzf, &std::fs::read(&path_output)?)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::to_string(&header())?,
+                serde_json::to_string(&record("a", "1", 100))?,
+                serde_json::to_string(&record("b", "1", 200))?,
+                serde_json::to_string(&record("c", "2", 100))?,
+                serde_json::to_string(&trailer())?,
+            ]
+        );
+
+        let index = noodles::tabix::read(format!("{}.tbi", path_output))?;
+        let names = index
+            .header()
+            .expect("tabix index has header")
+            .reference_sequence_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["1", "2"]);
+        assert_eq!(
+            index
+                .reference_sequences()
+                .iter()
+                .map(|reference_sequence| reference_sequence
+                    .metadata()
+                    .map(|metadata| metadata.mapped_record_count()))
+                .collect::<Vec<_>>(),
+            vec![Some(2), Some(1)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_file_sink_trailer() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = JsonlFileSink::new(&path_output)?;
+        sink.write_record(&record("a"))?;
+        sink.finish(&header(), Some(&trailer()))?;
+
+        let lines = std::fs::read_to_string(&path_output)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::to_string(&header())?,
+                serde_json::to_string(&record("a"))?,
+                serde_json::to_string(&trailer())?,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_file_sink_canonical_json() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = JsonlFileSink::new(&path_output)?.with_canonical_json(true);
+        sink.write_record(&record("a"))?;
+        sink.finish(&header(), Some(&trailer()))?;
+
+        let lines = std::fs::read_to_string(&path_output)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                canonical_json::to_string(&header(), true)?,
+                canonical_json::to_string(&record("a"), true)?,
+                canonical_json::to_string(&trailer(), true)?,
+            ]
+        );
+        assert!(lines[1].starts_with(r#"{"caseUuid":"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sharded_jsonl_sink() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = ShardedJsonlSink::new(&path_output, 2)?;
+        for uuid in ["a", "b", "c"] {
+            sink.write_record(&record(uuid))?;
+        }
+        let helpers = sink.finish(&header(), None)?;
+        assert_eq!(helpers.len(), 2);
+
+        let shard_0 = std::fs::read_to_string(ShardedJsonlSink::shard_path(&path_output, 0))?;
+        let shard_1 = std::fs::read_to_string(ShardedJsonlSink::shard_path(&path_output, 1))?;
+        assert_eq!(shard_0.lines().count(), 3);
+        assert_eq!(shard_1.lines().count(), 2);
+        assert_eq!(
+            shard_1.lines().next(),
+            Some(serde_json::to_string(&header())?.as_str())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn sharded_jsonl_sink_empty() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = ShardedJsonlSink::new(&path_output, 2)?;
+        let helpers = sink.finish(&header(), Some(&trailer()))?;
+        assert_eq!(helpers.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(ShardedJsonlSink::shard_path(&path_output, 0))?
+                .lines()
+                .collect::<Vec<_>>(),
+            vec![
+                serde_json::to_string(&header())?,
+                serde_json::to_string(&trailer())?,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn shard_path() {
+        assert_eq!(
+            ShardedJsonlSink::shard_path("out.jsonl", 3),
+            "out.00003.jsonl"
+        );
+        assert_eq!(ShardedJsonlSink::shard_path("out", 0), "out.00000.jsonl");
+        assert_eq!(
+            ShardedJsonlSink::shard_path("some.dir/out", 1),
+            "some.dir/out.00001.jsonl"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(Codec::None)]
+    #[case(Codec::Gzip(6))]
+    #[case(Codec::Zstd(3))]
+    fn codec_writer(#[case] codec: Codec) -> Result<(), anyhow::Error> {
+        let mut writer = codec.writer(Vec::new())?;
+        writer.write_all(b"hello\nworld\n")?;
+        let data = writer.finish()?;
+
+        assert_eq!(decompress(codec, &data)?, "hello\nworld\n");
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::io_bound(1e-6, true)]
+    #[case::cpu_bound(1e12, false)]
+    fn auto_compress_select(
+        #[case] io_mb_per_sec: f64,
+        #[case] compressed: bool,
+    ) -> Result<(), anyhow::Error> {
+        let sample = (0..1_000)
+            .map(|i| {
+                format!(
+                    "{}\n",
+                    serde_json::to_string(&record(&i.to_string())).unwrap()
+                )
+            })
+            .collect::<String>();
+        let auto_compress = AutoCompress {
+            sample_records: 1_000,
+            io_mb_per_sec,
+        };
+        let (codec, compression) = auto_compress.select(sample.as_bytes(), 1_000)?;
+
+        assert_eq!(codec != Codec::None, compressed);
+        assert_eq!(compression.codec, codec.name());
+        assert_eq!(compression.sample_records, 1_000);
+        assert_eq!(compression.measurements.len(), Codec::CANDIDATES.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_file_sink_auto_compress() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.jsonl", tmpdir.to_string_lossy());
+
+        let mut sink = JsonlFileSink::new(&path_output)?.with_auto_compress(Some(AutoCompress {
+            sample_records: 1,
+            io_mb_per_sec: 1e-6,
+        }));
+        sink.write_record(&record("a"))?;
+        sink.write_record(&record("b"))?;
+        let helpers = sink.finish(&header(), None)?;
+        assert_eq!(helpers.len(), 1);
+
+        let path_written = helpers[0].path_out();
+        let codec = Codec::CANDIDATES
+            .into_iter()
+            .filter(|codec| *codec != Codec::None)
+            .find(|codec| path_written.ends_with(codec.suffix()))
+            .expect("compressed output");
+        let lines = decompress(codec, &std::fs::read(path_written)?)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let written_header: pbs_output::OutputHeader = serde_json::from_str(&lines[0])?;
+        assert_eq!(
+            written_header
+                .compression
+                .map(|compression| compression.sample_records),
+            Some(1)
+        );
+        assert_eq!(
+            lines[1..],
+            [
+                serde_json::to_string(&record("a"))?,
+                serde_json::to_string(&record("b"))?,
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Prior classifications of variants from a user-supplied TSV file.
+//!
+//! The file has the header columns `variant`, `classification`, `assessor`, and `date`.
+//! The variant is given as `CHROM:POS:REF:ALT` or `CHROM-POS-REF-ALT`.
+
+use std::collections::HashMap;
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::schema::data::VcfVariant;
+
+/// Data structure for representing an entry of the table.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Entry {
+    /// The variant as `CHROM:POS:REF:ALT` or `CHROM-POS-REF-ALT`.
+    variant: String,
+    /// The classification.
+    classification: String,
+    /// The assessor.
+    assessor: Option<String>,
+    /// The date of the assessment, ISO formatted.
+    date: Option<String>,
+}
+
+/// Return the lookup key for the given variant, with normalized chromosome and alleles.
+pub(super) fn variant_key(chrom: &str, pos: i32, ref_allele: &str, alt_allele: &str) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        annonars::common::cli::canonicalize(chrom),
+        pos,
+        ref_allele.to_uppercase(),
+        alt_allele.to_uppercase()
+    )
+}
+
+/// Parse the variant column into its lookup key.
+pub(super) fn parse_variant_key(value: &str) -> Result<String, anyhow::Error> {
+    let separator = if value.contains(':') { ':' } else { '-' };
+    let [chrom, pos, ref_allele, alt_allele]: [&str; 4] = value
+        .split(separator)
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("variant must be CHROM:POS:REF:ALT: {}", value))?;
+    let pos = pos
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid position in {}: {}", value, e))?;
+    Ok(variant_key(chrom, pos, ref_allele, alt_allele))
+}
+
+/// Prior classifications by variant.
+#[derive(Debug, Default)]
+pub struct PriorClassifications {
+    /// Mapping from variant key to the prior assessment.
+    by_variant: HashMap<String, pbs_output::PriorAssessment>,
+}
+
+impl PriorClassifications {
+    /// Load the prior classifications from the TSV file at `path`.
+    ///
+    /// If there is more than one classification for a variant, the one with the latest
+    /// date is used, the last one in the file on ties.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(true)
+            .from_reader(
+                crate::common::s3::open_read_maybe_gz(&path.as_ref().to_string_lossy()).map_err(
+                    |e| {
+                        anyhow::anyhow!(
+                            "could not open prior classifications {}: {}",
+                            path.as_ref().display(),
+                            e
+                        )
+                    },
+                )?,
+            );
+        Self::from_entries(rdr.deserialize().map(|result| {
+            result.map_err(|e| anyhow::anyhow!("problem reading prior classification: {}", e))
+        }))
+    }
+
+    /// Build from the given table `entries`.
+    fn from_entries<I>(entries: I) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = Result<Entry, anyhow::Error>>,
+    {
+        let mut by_variant: HashMap<String, pbs_output::PriorAssessment> = HashMap::new();
+        for entry in entries {
+            let entry = entry?;
+            let key = parse_variant_key(&entry.variant)?;
+            let assessment = pbs_output::PriorAssessment {
+                classification: entry.classification,
+                assessor: entry.assessor.filter(|assessor| !assessor.is_empty()),
+                date: entry.date.filter(|date| !date.is_empty()),
+            };
+            match by_variant.get(&key) {
+                Some(existing) if existing.date > assessment.date => (),
+                _ => {
+                    by_variant.insert(key, assessment);
+                }
+            }
+        }
+        Ok(Self { by_variant })
+    }
+
+    /// Return the number of variants with prior classification.
+    pub fn len(&self) -> usize {
+        self.by_variant.len()
+    }
+
+    /// Return the prior assessment of `variant`, if any.
+    pub fn get(&self, variant: &VcfVariant) -> Option<&pbs_output::PriorAssessment> {
+        self.by_variant.get(&variant_key(
+            &variant.chrom,
+            variant.pos,
+            &variant.ref_allele,
+            &variant.alt_allele,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, PriorClassifications};
+    use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+    use crate::seqvars::query::schema::data::VcfVariant;
+
+    fn entry(variant: &str, classification: &str, date: Option<&str>) -> Entry {
+        Entry {
+            variant: variant.to_string(),
+            classification: classification.to_string(),
+            assessor: Some("alice".to_string()),
+            date: date.map(String::from),
+        }
+    }
+
+    fn variant(chrom: &str, pos: i32, ref_allele: &str, alt_allele: &str) -> VcfVariant {
+        VcfVariant {
+            chrom: chrom.to_string(),
+            pos,
+            ref_allele: ref_allele.to_string(),
+            alt_allele: alt_allele.to_string(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("chr1:100:a:g", "1:100:A:G")]
+    #[case("1-100-A-G", "1:100:A:G")]
+    #[case("chrM:100:A:G", "MT:100:A:G")]
+    fn parse_variant_key(#[case] value: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+        assert_eq!(super::parse_variant_key(value)?, expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1:100:A")]
+    #[case("1:pos:A:G")]
+    fn parse_variant_key_invalid(#[case] value: &str) {
+        assert!(super::parse_variant_key(value).is_err());
+    }
+
+    #[test]
+    fn from_entries() -> Result<(), anyhow::Error> {
+        let prior = PriorClassifications::from_entries(
+            vec![
+                entry("1-100-A-G", "pathogenic", Some("2024-01-31")),
+                entry("1-100-A-G", "benign", Some("2023-01-31")),
+                entry("chrX:200:C:T", "uncertain significance", None),
+                entry("chrX:200:C:T", "likely benign", None),
+            ]
+            .into_iter()
+            .map(Ok),
+        )?;
+
+        assert_eq!(prior.len(), 2);
+        assert_eq!(
+            prior.get(&variant("chr1", 100, "A", "G")),
+            Some(&pbs_output::PriorAssessment {
+                classification: "pathogenic".to_string(),
+                assessor: Some("alice".to_string()),
+                date: Some("2024-01-31".to_string()),
+            })
+        );
+        assert_eq!(
+            prior
+                .get(&variant("X", 200, "C", "T"))
+                .map(|assessment| assessment.classification.as_str()),
+            Some("likely benign")
+        );
+        assert_eq!(prior.get(&variant("1", 100, "A", "T")), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Scoring of the support of a gene for recessive inheritance.
+//!
+//! `passes_for_gene()` only decides whether the variants of a gene are compatible with
+//! the recessive mode of the query.  The score computed here allows to rank the passing
+//! genes.  Each supporting variant is weighted with the confidence of the index and
+//! parental genotypes, i.e., the product of their genotype qualities divided by 99
+//! (capped at 1, missing qualities count as 1).  A compound heterozygous pair has the
+//! product of the weights of its variants, halved if the phase of the pair is unknown.
+//! Pairs are in trans if their variants were inherited from different parents or, if
+//! no parents are given, are on different haplotypes of the same phase set of the
+//! index; pairs in cis are ignored.  The score is the probability that at least one
+//! homozygous variant or pair is real, assuming independence.
+
+use crate::common::{self, GenomeRelease};
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::schema::{
+    data::{CallInfo, VariantRecord},
+    query::{CaseQuery, GenotypeChoice, RecessiveMode, SampleGenotypeChoice},
+};
+
+/// Support of a gene for the recessive mode of the query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecessiveSupport {
+    /// Support score between 0 and 1.
+    pub score: f32,
+    /// Number of variants supporting homozygous or hemizygous recessive inheritance.
+    pub hom_count: u32,
+    /// Number of compound heterozygous pairs in trans.
+    pub trans_pair_count: u32,
+    /// Number of compound heterozygous pairs with unknown phase.
+    pub unphased_pair_count: u32,
+}
+
+impl From<RecessiveSupport> for pbs_output::RecessiveSupport {
+    fn from(value: RecessiveSupport) -> Self {
+        Self {
+            score: value.score,
+            hom_count: value.hom_count,
+            trans_pair_count: value.trans_pair_count,
+            unphased_pair_count: value.unphased_pair_count,
+        }
+    }
+}
+
+/// Parent that a heterozygous variant of the index was inherited from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Parent {
+    Father,
+    Mother,
+}
+
+/// A heterozygous variant of the index supporting compound heterozygous inheritance.
+#[derive(Debug, Clone, Copy)]
+struct HetVariant {
+    /// Parent inherited from, if any parent is given.
+    parent: Option<Parent>,
+    /// Phase set and haplotype of the alternate allele in the index, if phased.
+    haplotype: Option<(i32, usize)>,
+    /// Confidence of the genotypes.
+    weight: f32,
+}
+
+/// Return the confidence of the genotype in `call_info`.
+fn confidence(call_info: Option<&CallInfo>) -> f32 {
+    call_info
+        .and_then(|call_info| call_info.gq)
+        .map(|gq| (gq / 99.0).clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+/// Return the parsed genotype of `sample` in `seqvar`.
+fn genotype(
+    seqvar: &VariantRecord,
+    sample: &str,
+) -> Result<Option<common::ParsedGenotype>, anyhow::Error> {
+    seqvar
+        .call_infos
+        .get(sample)
+        .and_then(|call_info| call_info.genotype.as_ref())
+        .map(|gt| gt.parse::<common::ParsedGenotype>())
+        .transpose()
+}
+
+/// Return whether the phase of `lhs` and `rhs` is known and in trans.
+fn phased_trans(lhs: &HetVariant, rhs: &HetVariant) -> Option<bool> {
+    match (lhs.haplotype, rhs.haplotype) {
+        (Some((lhs_ps, lhs_hap)), Some((rhs_ps, rhs_hap))) if lhs_ps == rhs_ps => {
+            Some(lhs_hap != rhs_hap)
+        }
+        _ => None,
+    }
+}
+
+/// Compute the support of the variants of a gene for the recessive mode of `query`.
+pub fn score_gene(
+    query: &CaseQuery,
+    seqvars: &[VariantRecord],
+    genome_release: GenomeRelease,
+) -> Result<RecessiveSupport, anyhow::Error> {
+    let mode = query.genotype.recessive_mode;
+    let role = |choice: GenotypeChoice| {
+        query
+            .genotype
+            .sample_genotypes
+            .iter()
+            .find(|(_, SampleGenotypeChoice { genotype, .. })| *genotype == choice)
+            .map(|(sample_name, _)| sample_name.as_str())
+    };
+    let (index, father, mother) = (
+        role(GenotypeChoice::RecessiveIndex).unwrap_or_default(),
+        role(GenotypeChoice::RecessiveFather),
+        role(GenotypeChoice::RecessiveMother),
+    );
+
+    let mut hom_weights = Vec::new();
+    let mut het_variants = Vec::new();
+    for seqvar in seqvars {
+        let Some(index_gt) = genotype(seqvar, index)? else {
+            continue;
+        };
+        let father_gt = father
+            .map(|father| genotype(seqvar, father))
+            .transpose()?
+            .map(|gt| {
+                gt.map(|gt| gt.genotype())
+                    .unwrap_or(common::Genotype::WithNoCall)
+            });
+        let mother_gt = mother
+            .map(|mother| genotype(seqvar, mother))
+            .transpose()?
+            .map(|gt| {
+                gt.map(|gt| gt.genotype())
+                    .unwrap_or(common::Genotype::WithNoCall)
+            });
+        if father_gt == Some(common::Genotype::HomAlt)
+            || mother_gt == Some(common::Genotype::HomAlt)
+        {
+            continue;
+        }
+
+        let weight = [Some(index), father, mother]
+            .into_iter()
+            .flatten()
+            .map(|sample| confidence(seqvar.call_infos.get(sample)))
+            .product::<f32>();
+        let is_het =
+            |gt: Option<common::Genotype>| gt.map_or(true, |gt| gt == common::Genotype::Het);
+        let is_ref =
+            |gt: Option<common::Genotype>| gt.map_or(true, |gt| gt == common::Genotype::HomRef);
+
+        match index_gt.genotype() {
+            common::Genotype::HomAlt
+                if matches!(mode, RecessiveMode::Homozygous | RecessiveMode::Any) =>
+            {
+                let x_linked = super::is_x_non_par(seqvar, genome_release)
+                    && is_het(mother_gt)
+                    && is_ref(father_gt);
+                if x_linked || (is_het(father_gt) && is_het(mother_gt)) {
+                    hom_weights.push(weight);
+                }
+            }
+            common::Genotype::Het
+                if matches!(
+                    mode,
+                    RecessiveMode::CompoundHeterozygous | RecessiveMode::Any
+                ) =>
+            {
+                use common::Genotype::{Het, HomRef};
+                let parent = match (father_gt, mother_gt) {
+                    (None, None) => None,
+                    (Some(Het), Some(HomRef)) | (Some(Het), None) | (None, Some(HomRef)) => {
+                        Some(Parent::Father)
+                    }
+                    (Some(HomRef), Some(Het)) | (None, Some(Het)) | (Some(HomRef), None) => {
+                        Some(Parent::Mother)
+                    }
+                    _ => continue,
+                };
+                let ps = seqvar
+                    .call_infos
+                    .get(index)
+                    .and_then(|call_info| call_info.ps);
+                let haplotype = ps.filter(|_| index_gt.phased).and_then(|ps| {
+                    index_gt
+                        .alleles
+                        .iter()
+                        .position(|allele| allele.is_some_and(|allele| allele > 0))
+                        .map(|haplotype| (ps, haplotype))
+                });
+                het_variants.push(HetVariant {
+                    parent,
+                    haplotype,
+                    weight,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    let mut result = RecessiveSupport {
+        hom_count: hom_weights.len() as u32,
+        ..Default::default()
+    };
+    let mut pair_weights = Vec::new();
+    for (i, lhs) in het_variants.iter().enumerate() {
+        for rhs in &het_variants[i + 1..] {
+            let trans = match (lhs.parent, rhs.parent) {
+                (Some(lhs_parent), Some(rhs_parent)) => Some(lhs_parent != rhs_parent),
+                _ => phased_trans(lhs, rhs),
+            };
+            match trans {
+                Some(true) => {
+                    result.trans_pair_count += 1;
+                    pair_weights.push(lhs.weight * rhs.weight);
+                }
+                Some(false) => (),
+                None => {
+                    result.unphased_pair_count += 1;
+                    pair_weights.push(0.5 * lhs.weight * rhs.weight);
+                }
+            }
+        }
+    }
+    result.score = 1.0
+        - hom_weights
+            .iter()
+            .chain(pair_weights.iter())
+            .map(|weight| 1.0 - weight)
+            .product::<f32>();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+    use crate::seqvars::query::schema::{
+        data::{CallInfo, VariantRecord},
+        query::{
+            CaseQuery, GenotypeChoice, QuerySettingsGenotype, RecessiveMode, SampleGenotypeChoice,
+        },
+    };
+
+    use super::RecessiveSupport;
+
+    fn query(samples: &[(&str, GenotypeChoice)]) -> CaseQuery {
+        CaseQuery {
+            genotype: QuerySettingsGenotype {
+                recessive_mode: RecessiveMode::Any,
+                sample_genotypes: samples
+                    .iter()
+                    .map(|(sample, genotype)| {
+                        (
+                            sample.to_string(),
+                            SampleGenotypeChoice {
+                                sample: sample.to_string(),
+                                genotype: *genotype,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                inheritance_models: vec![],
+                dominant: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Build a variant from comma-separated `GT:GQ:PS` values of `samples`.
+    fn seqvar(samples: &[&str], calls: &str) -> VariantRecord {
+        VariantRecord {
+            call_infos: samples
+                .iter()
+                .zip(calls.split(','))
+                .map(|(sample, call)| {
+                    let fields = call.split(':').collect::<Vec<_>>();
+                    (
+                        sample.to_string(),
+                        CallInfo {
+                            sample: sample.to_string(),
+                            genotype: Some(fields[0].into()),
+                            gq: fields.get(1).map(|gq| gq.parse().unwrap()),
+                            ps: fields.get(2).map(|ps| ps.parse().unwrap()),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::trio_trans(
+        vec!["0/1:99,0/1:99,0/0:99", "0/1:99,0/0:99,0/1:99"],
+        RecessiveSupport { score: 1.0, hom_count: 0, trans_pair_count: 1, unphased_pair_count: 0 }
+    )]
+    #[case::trio_cis(
+        vec!["0/1,0/1,0/0", "0/1,0/1,0/0"],
+        RecessiveSupport::default()
+    )]
+    #[case::trio_low_quality(
+        vec!["0/1:49.5,0/1,0/0", "0/1,0/0,0/1"],
+        RecessiveSupport { score: 0.5, hom_count: 0, trans_pair_count: 1, unphased_pair_count: 0 }
+    )]
+    #[case::trio_hom_and_pair(
+        vec!["1/1:49.5,0/1,0/1", "0/1:49.5,0/1,0/0", "0/1,0/0,0/1"],
+        RecessiveSupport { score: 0.75, hom_count: 1, trans_pair_count: 1, unphased_pair_count: 0 }
+    )]
+    #[case::trio_hom_parent(
+        vec!["1/1,1/1,0/1"],
+        RecessiveSupport::default()
+    )]
+    fn score_gene_trio(#[case] calls: Vec<&str>, #[case] expected: RecessiveSupport) {
+        let samples = ["index", "father", "mother"];
+        let query = query(&[
+            ("index", GenotypeChoice::RecessiveIndex),
+            ("father", GenotypeChoice::RecessiveFather),
+            ("mother", GenotypeChoice::RecessiveMother),
+        ]);
+        let seqvars = calls
+            .iter()
+            .map(|calls| seqvar(&samples, calls))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            super::score_gene(&query, &seqvars, GenomeRelease::Grch37).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::unphased(
+        vec!["0/1", "0/1"],
+        RecessiveSupport { score: 0.5, hom_count: 0, trans_pair_count: 0, unphased_pair_count: 1 }
+    )]
+    #[case::phased_trans(
+        vec!["0|1:99:7", "1|0:99:7"],
+        RecessiveSupport { score: 1.0, hom_count: 0, trans_pair_count: 1, unphased_pair_count: 0 }
+    )]
+    #[case::phased_cis(vec!["0|1:99:7", "0|1:99:7"], RecessiveSupport::default())]
+    #[case::different_phase_sets(
+        vec!["0|1:99:7", "1|0:99:8"],
+        RecessiveSupport { score: 0.5, hom_count: 0, trans_pair_count: 0, unphased_pair_count: 1 }
+    )]
+    fn score_gene_singleton(#[case] calls: Vec<&str>, #[case] expected: RecessiveSupport) {
+        let query = query(&[("index", GenotypeChoice::RecessiveIndex)]);
+        let seqvars = calls
+            .iter()
+            .map(|calls| seqvar(&["index"], calls))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            super::score_gene(&query, &seqvars, GenomeRelease::Grch37).unwrap(),
+            expected
+        );
+    }
+}
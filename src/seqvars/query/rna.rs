@@ -0,0 +1,226 @@
+//! RNA-seq evidence of variants from a user-supplied TSV file.
+//!
+//! The file has the header columns `sample`, `variant`, `ase_pvalue`, and
+//! `junction_reads`; the last two may be empty.  The variant is given as
+//! `CHROM:POS:REF:ALT` or `CHROM-POS-REF-ALT`.
+
+use std::collections::HashMap;
+
+use mehari::annotate::seqvars::ann::Consequence;
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+use super::{
+    prior::{parse_variant_key, variant_key},
+    schema::data::{VariantRecord, VcfVariant},
+};
+
+/// Consequences that are considered for RNA-supported splice variants.
+const SPLICE: &[Consequence] = &[
+    Consequence::SpliceAcceptorVariant,
+    Consequence::SpliceDonorVariant,
+    Consequence::SpliceDonorFifthBaseVariant,
+    Consequence::SpliceRegionVariant,
+    Consequence::SpliceDonorRegionVariant,
+    Consequence::SplicePolypyrimidineTractVariant,
+];
+
+/// Data structure for representing an entry of the table.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Entry {
+    /// The name of the sample.
+    sample: String,
+    /// The variant as `CHROM:POS:REF:ALT` or `CHROM-POS-REF-ALT`.
+    variant: String,
+    /// P-value of allele-specific expression.
+    ase_pvalue: Option<f32>,
+    /// Number of reads supporting an aberrant splice junction.
+    junction_reads: Option<i32>,
+}
+
+/// RNA-seq evidence by variant.
+#[derive(Debug, Default)]
+pub struct RnaEvidence {
+    /// Mapping from variant key to the evidence of each sample, sorted by sample.
+    by_variant: HashMap<String, Vec<pbs_output::RnaEvidence>>,
+}
+
+impl RnaEvidence {
+    /// Load the RNA-seq evidence from the TSV file at `path`.
+    ///
+    /// If there is more than one entry for a variant and sample, the last one is used.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(true)
+            .from_reader(
+                crate::common::s3::open_read_maybe_gz(&path.as_ref().to_string_lossy()).map_err(
+                    |e| {
+                        anyhow::anyhow!(
+                            "could not open RNA evidence {}: {}",
+                            path.as_ref().display(),
+                            e
+                        )
+                    },
+                )?,
+            );
+        Self::from_entries(rdr.deserialize().map(|result| {
+            result.map_err(|e| anyhow::anyhow!("problem reading RNA evidence: {}", e))
+        }))
+    }
+
+    /// Build from the given table `entries`.
+    fn from_entries<I>(entries: I) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = Result<Entry, anyhow::Error>>,
+    {
+        let mut by_variant: HashMap<String, Vec<pbs_output::RnaEvidence>> = HashMap::new();
+        for entry in entries {
+            let entry = entry?;
+            let key = parse_variant_key(&entry.variant)?;
+            let evidence = pbs_output::RnaEvidence {
+                sample: entry.sample,
+                ase_pvalue: entry.ase_pvalue,
+                junction_reads: entry.junction_reads,
+            };
+            let samples = by_variant.entry(key).or_default();
+            match samples.binary_search_by(|other| other.sample.cmp(&evidence.sample)) {
+                Ok(idx) => samples[idx] = evidence,
+                Err(idx) => samples.insert(idx, evidence),
+            }
+        }
+        Ok(Self { by_variant })
+    }
+
+    /// Return the number of variants with RNA-seq evidence.
+    pub fn len(&self) -> usize {
+        self.by_variant.len()
+    }
+
+    /// Return the RNA-seq evidence of `variant` for all samples.
+    pub fn get(&self, variant: &VcfVariant) -> &[pbs_output::RnaEvidence] {
+        self.by_variant
+            .get(&variant_key(
+                &variant.chrom,
+                variant.pos,
+                &variant.ref_allele,
+                &variant.alt_allele,
+            ))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return whether `seqvar` is a splice variant with at least `min_junction_reads`
+    /// reads supporting an aberrant splice junction in any sample.
+    pub fn passes_min_junction_reads(
+        &self,
+        seqvar: &VariantRecord,
+        min_junction_reads: i32,
+    ) -> bool {
+        let is_splice = seqvar
+            .ann_fields
+            .iter()
+            .any(|ann| ann.consequences.iter().any(|csq| SPLICE.contains(csq)));
+        is_splice
+            && self.get(&seqvar.vcf_variant).iter().any(|evidence| {
+                evidence
+                    .junction_reads
+                    .is_some_and(|junction_reads| junction_reads >= min_junction_reads)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence};
+
+    use super::{Entry, RnaEvidence};
+    use crate::seqvars::query::schema::data::{VariantRecord, VcfVariant};
+
+    fn entry(sample: &str, variant: &str, junction_reads: Option<i32>) -> Entry {
+        Entry {
+            sample: sample.to_string(),
+            variant: variant.to_string(),
+            ase_pvalue: Some(0.01),
+            junction_reads,
+        }
+    }
+
+    fn variant(chrom: &str, pos: i32, ref_allele: &str, alt_allele: &str) -> VcfVariant {
+        VcfVariant {
+            chrom: chrom.to_string(),
+            pos,
+            ref_allele: ref_allele.to_string(),
+            alt_allele: alt_allele.to_string(),
+        }
+    }
+
+    fn seqvar(vcf_variant: VcfVariant, consequence: Consequence) -> VariantRecord {
+        VariantRecord {
+            vcf_variant,
+            ann_fields: vec![AnnField {
+                consequences: vec![consequence],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn from_entries() -> Result<(), anyhow::Error> {
+        let rna = RnaEvidence::from_entries(
+            vec![
+                entry("mother", "1-100-A-G", Some(2)),
+                entry("index", "1-100-A-G", None),
+                entry("index", "chr1:100:A:G", Some(10)),
+                entry("index", "X:200:C:T", None),
+            ]
+            .into_iter()
+            .map(Ok),
+        )?;
+
+        assert_eq!(rna.len(), 2);
+        let evidence = rna.get(&variant("chr1", 100, "A", "G"));
+        assert_eq!(
+            evidence
+                .iter()
+                .map(|evidence| (evidence.sample.as_str(), evidence.junction_reads))
+                .collect::<Vec<_>>(),
+            vec![("index", Some(10)), ("mother", Some(2))]
+        );
+        assert!(rna.get(&variant("1", 100, "A", "T")).is_empty());
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(Consequence::SpliceDonorVariant, 5, true)]
+    #[case(Consequence::SpliceRegionVariant, 10, true)]
+    #[case(Consequence::SpliceDonorVariant, 11, false)]
+    #[case(Consequence::MissenseVariant, 5, false)]
+    fn passes_min_junction_reads(
+        #[case] consequence: Consequence,
+        #[case] min_junction_reads: i32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let rna = RnaEvidence::from_entries(
+            vec![entry("index", "1:100:A:G", Some(10))]
+                .into_iter()
+                .map(Ok),
+        )?;
+
+        assert_eq!(
+            rna.passes_min_junction_reads(
+                &seqvar(variant("1", 100, "A", "G"), consequence),
+                min_junction_reads
+            ),
+            expected
+        );
+        assert!(!rna.passes_min_junction_reads(
+            &seqvar(variant("1", 101, "A", "G"), consequence),
+            min_junction_reads
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,548 @@
+//! Tolerant parsing of `INFO/ANN` entries, also for files annotated by other tools.
+//!
+//! Besides the layout written by mehari, the SnpEff layout without the strand column is
+//! accepted, and the `INFO/CSQ` entries written by VEP are mapped to `AnnField`.  Values
+//! that cannot be parsed are recovered per field instead of failing the whole record, and
+//! the recoveries are counted.
+
+use indexmap::IndexMap;
+use mehari::annotate::seqvars::ann::{
+    Allele, AnnField, Consequence, FeatureBiotype, FeatureType, Message, Pos, PutativeImpact, Rank,
+};
+use noodles::vcf;
+
+/// Number of columns of the `ANN` layout written by mehari.
+const MEHARI_COLUMNS: usize = 17;
+/// Number of columns of the `ANN` layout written by SnpEff, lacking the strand.
+const SNPEFF_COLUMNS: usize = 16;
+
+/// Consequence used for terms that are unknown to mehari.
+pub const CATCH_ALL_CONSEQUENCE: Consequence = Consequence::GeneVariant;
+
+/// Counts of recovered `ANN` values by field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnnRecovery {
+    /// Number of recovered values by field name.
+    pub by_field: IndexMap<&'static str, usize>,
+}
+
+impl AnnRecovery {
+    /// Count one recovered value of `field`.
+    fn count(&mut self, field: &'static str) {
+        *self.by_field.entry(field).or_default() += 1;
+    }
+
+    /// Add the counts of `other`.
+    pub fn merge(&mut self, other: &Self) {
+        for (field, count) in &other.by_field {
+            *self.by_field.entry(field).or_default() += count;
+        }
+    }
+
+    /// Return the total number of recovered values.
+    pub fn total(&self) -> usize {
+        self.by_field.values().sum()
+    }
+}
+
+/// Parse the optional value `s` with `parse`; malformed values are counted as recovered
+/// for `field` and ignored.
+fn parse_optional<T>(
+    s: &str,
+    field: &'static str,
+    recovery: &mut AnnRecovery,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Option<T> {
+    if s.is_empty() {
+        return None;
+    }
+    let result = parse(s);
+    if result.is_none() {
+        recovery.count(field);
+    }
+    result
+}
+
+/// Parse the `&`-separated consequence terms in `s`, mapping unknown terms to
+/// `CATCH_ALL_CONSEQUENCE`.
+fn parse_consequences(s: &str, recovery: &mut AnnRecovery) -> Vec<Consequence> {
+    let mut consequences = Vec::new();
+    for term in s.split('&') {
+        let consequence = term.parse::<Consequence>().unwrap_or_else(|_| {
+            recovery.count("consequences");
+            CATCH_ALL_CONSEQUENCE
+        });
+        if !consequences.contains(&consequence) {
+            consequences.push(consequence);
+        }
+    }
+    consequences
+}
+
+/// Parse the putative impact in `s`, mapping unknown values to `MODIFIER`.
+fn parse_putative_impact(s: &str, recovery: &mut AnnRecovery) -> PutativeImpact {
+    s.parse::<PutativeImpact>().unwrap_or_else(|_| {
+        recovery.count("putative_impact");
+        PutativeImpact::Modifier
+    })
+}
+
+/// Parse one entry of `INFO/ANN`, recovering malformed values and counting them in
+/// `recovery`.
+///
+/// - unknown consequence terms are mapped to `CATCH_ALL_CONSEQUENCE`
+/// - unknown putative impacts are mapped to `MODIFIER`
+/// - the biotype `protein_coding` is mapped to `Coding`, other unknown biotypes and
+///   messages are dropped
+/// - malformed rank, position, distance, and strand values are ignored
+///
+/// # Errors
+///
+/// Only if `s` does not have the columns of the known layouts.
+pub fn parse_ann_field(s: &str, recovery: &mut AnnRecovery) -> Result<AnnField, anyhow::Error> {
+    let mut columns = s.split('|').collect::<Vec<_>>();
+    let strand = match columns.len() {
+        SNPEFF_COLUMNS => "0",
+        n if n >= MEHARI_COLUMNS => columns.remove(15),
+        n => anyhow::bail!(
+            "ANN entry has {} columns but expected {} or {}: {:?}",
+            n,
+            SNPEFF_COLUMNS,
+            MEHARI_COLUMNS,
+            s
+        ),
+    };
+    if columns.len() > SNPEFF_COLUMNS {
+        recovery.count("extra_columns");
+    }
+
+    let allele = columns[0].parse::<Allele>().unwrap_or_else(|_| {
+        recovery.count("allele");
+        Allele::Alt {
+            alternative: columns[0].to_string(),
+        }
+    });
+
+    let consequences = parse_consequences(columns[1], recovery);
+    let putative_impact = parse_putative_impact(columns[2], recovery);
+
+    let feature_biotype = columns[7]
+        .split('&')
+        .filter(|term| !term.is_empty())
+        .filter_map(|term| match term.parse::<FeatureBiotype>() {
+            Ok(biotype) => Some(biotype),
+            Err(_) if term == "protein_coding" => Some(FeatureBiotype::Coding),
+            Err(_) => {
+                recovery.count("feature_biotype");
+                None
+            }
+        })
+        .collect();
+
+    let messages = columns[15]
+        .split('&')
+        .filter(|term| !term.is_empty())
+        .filter_map(|term| match term.parse::<Message>() {
+            Ok(message) => Some(message),
+            Err(_) => {
+                recovery.count("messages");
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(AnnField {
+        allele,
+        consequences,
+        putative_impact,
+        gene_symbol: columns[3].to_string(),
+        gene_id: columns[4].to_string(),
+        feature_type: columns[5]
+            .parse::<FeatureType>()
+            .expect("FeatureType parsing is infallible"),
+        feature_id: columns[6].to_string(),
+        feature_biotype,
+        rank: parse_optional(columns[8], "rank", recovery, |s| s.parse::<Rank>().ok()),
+        hgvs_t: (!columns[9].is_empty()).then(|| columns[9].to_string()),
+        hgvs_p: (!columns[10].is_empty()).then(|| columns[10].to_string()),
+        tx_pos: parse_optional(columns[11], "tx_pos", recovery, |s| s.parse::<Pos>().ok()),
+        cds_pos: parse_optional(columns[12], "cds_pos", recovery, |s| s.parse::<Pos>().ok()),
+        protein_pos: parse_optional(columns[13], "protein_pos", recovery, |s| {
+            s.parse::<Pos>().ok()
+        }),
+        distance: parse_optional(columns[14], "distance", recovery, |s| s.parse::<i32>().ok()),
+        strand: parse_optional(strand, "strand", recovery, |s| s.parse::<i32>().ok())
+            .unwrap_or_default(),
+        messages: (!messages.is_empty()).then_some(messages),
+    })
+}
+
+/// Column indices of the `INFO/CSQ` entries written by VEP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsqLayout {
+    /// Index of each column by name.
+    columns: IndexMap<String, usize>,
+}
+
+impl CsqLayout {
+    /// Construct from the `Description` of the `INFO/CSQ` header line, which ends with
+    /// `Format: ` and the `|`-separated column names.
+    pub fn from_description(description: &str) -> Result<Self, anyhow::Error> {
+        let (_, format) = description
+            .split_once("Format: ")
+            .ok_or_else(|| anyhow::anyhow!("no column names in CSQ description"))?;
+        let columns = format
+            .trim()
+            .split('|')
+            .enumerate()
+            .map(|(idx, name)| (name.to_string(), idx))
+            .collect::<IndexMap<_, _>>();
+        for name in ["Allele", "Consequence"] {
+            if !columns.contains_key(name) {
+                anyhow::bail!("CSQ description lacks the {} column", name);
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    /// Return the value of the column `name` in `values` or the empty string.
+    fn get<'a>(&self, values: &[&'a str], name: &str) -> &'a str {
+        self.columns
+            .get(name)
+            .and_then(|idx| values.get(*idx))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Parse a VEP position such as `123`, `123-124`, or `?-124`.
+fn parse_vep_pos(s: &str) -> Option<Pos> {
+    s.split('-')
+        .find_map(|value| value.parse::<i32>().ok())
+        .map(|ord| Pos { ord, total: None })
+}
+
+/// Return the HGVS description without the reference sequence prefix.
+fn strip_hgvs_prefix(s: &str) -> Option<String> {
+    let hgvs = s.split_once(':').map(|(_, hgvs)| hgvs).unwrap_or(s);
+    (!hgvs.is_empty()).then(|| hgvs.replace("%3D", "="))
+}
+
+/// Parse one entry of `INFO/CSQ` with the columns in `layout` into an `AnnField`.
+///
+/// The HGNC ID is taken from the `HGNC_ID` column (written with `--symbol`) and the
+/// Ensembl gene ID is used if it is missing.  The biotype `protein_coding` is mapped to
+/// `Coding` and all others to `Noncoding`, the MANE columns to `ManeSelect` and
+/// `ManePlusClinical`, and the `cds_start_NF`/`cds_end_NF` flags to an incomplete
+/// transcript warning.  Values are recovered as for `parse_ann_field()`.
+pub fn parse_csq_field(
+    s: &str,
+    layout: &CsqLayout,
+    recovery: &mut AnnRecovery,
+) -> Result<AnnField, anyhow::Error> {
+    let values = s.split('|').collect::<Vec<_>>();
+    if values.len() != layout.columns.len() {
+        recovery.count("columns");
+    }
+    let get = |name: &str| layout.get(&values, name);
+
+    let gene_id = match get("HGNC_ID") {
+        "" => get("Gene").to_string(),
+        hgnc_id if hgnc_id.starts_with("HGNC:") => hgnc_id.to_string(),
+        hgnc_id => format!("HGNC:{}", hgnc_id),
+    };
+
+    let mut feature_biotype = match get("BIOTYPE") {
+        "" => Vec::new(),
+        "protein_coding" => vec![FeatureBiotype::Coding],
+        _ => vec![FeatureBiotype::Noncoding],
+    };
+    if !get("MANE_SELECT").is_empty() {
+        feature_biotype.push(FeatureBiotype::ManeSelect);
+    }
+    if !get("MANE_PLUS_CLINICAL").is_empty() {
+        feature_biotype.push(FeatureBiotype::ManePlusClinical);
+    }
+
+    let rank = match get("EXON") {
+        "" => get("INTRON"),
+        exon => exon,
+    };
+    let incomplete = get("FLAGS")
+        .split('&')
+        .any(|flag| flag == "cds_start_NF" || flag == "cds_end_NF");
+
+    Ok(AnnField {
+        allele: Allele::Alt {
+            alternative: get("Allele").to_string(),
+        },
+        consequences: parse_consequences(get("Consequence"), recovery),
+        putative_impact: parse_putative_impact(get("IMPACT"), recovery),
+        gene_symbol: get("SYMBOL").to_string(),
+        gene_id,
+        feature_type: get("Feature_type")
+            .to_lowercase()
+            .parse::<FeatureType>()
+            .expect("FeatureType parsing is infallible"),
+        feature_id: get("Feature").to_string(),
+        feature_biotype,
+        rank: parse_optional(rank, "rank", recovery, |s| s.parse::<Rank>().ok()),
+        hgvs_t: strip_hgvs_prefix(get("HGVSc")),
+        hgvs_p: strip_hgvs_prefix(get("HGVSp")),
+        tx_pos: parse_optional(get("cDNA_position"), "tx_pos", recovery, parse_vep_pos),
+        cds_pos: parse_optional(get("CDS_position"), "cds_pos", recovery, parse_vep_pos),
+        protein_pos: parse_optional(
+            get("Protein_position"),
+            "protein_pos",
+            recovery,
+            parse_vep_pos,
+        ),
+        distance: parse_optional(get("DISTANCE"), "distance", recovery, |s| {
+            s.parse::<i32>().ok()
+        }),
+        strand: parse_optional(get("STRAND"), "strand", recovery, |s| s.parse::<i32>().ok())
+            .unwrap_or_default(),
+        messages: incomplete.then(|| vec![Message::WarningTranscriptIncomplete]),
+    })
+}
+
+/// Source of the consequence annotation of the input VCF file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AnnotationSource {
+    /// Use `INFO/ANN` if declared in the header and `INFO/CSQ` otherwise.
+    #[default]
+    Auto,
+    /// `INFO/ANN` as written by mehari (or SnpEff).
+    Mehari,
+    /// `INFO/CSQ` as written by VEP.
+    Vep,
+}
+
+/// Layout of the annotation entries, resolved from the header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AnnLayout {
+    /// `INFO/ANN` entries.
+    #[default]
+    Ann,
+    /// `INFO/CSQ` entries with the given columns.
+    Csq(CsqLayout),
+}
+
+impl AnnLayout {
+    /// Resolve the layout for `source` from the `header`.
+    pub fn from_header(
+        header: &vcf::Header,
+        source: AnnotationSource,
+    ) -> Result<Self, anyhow::Error> {
+        let csq = || -> Result<Self, anyhow::Error> {
+            let info = header
+                .infos()
+                .get("CSQ")
+                .ok_or_else(|| anyhow::anyhow!("no INFO/CSQ header line for VEP annotation"))?;
+            Ok(AnnLayout::Csq(CsqLayout::from_description(
+                info.description(),
+            )?))
+        };
+        match source {
+            AnnotationSource::Mehari => Ok(AnnLayout::Ann),
+            AnnotationSource::Vep => csq(),
+            AnnotationSource::Auto => {
+                if header.infos().contains_key("ANN") || !header.infos().contains_key("CSQ") {
+                    Ok(AnnLayout::Ann)
+                } else {
+                    csq()
+                }
+            }
+        }
+    }
+
+    /// Return the key of the `INFO` field with the entries.
+    pub fn info_key(&self) -> &'static str {
+        match self {
+            AnnLayout::Ann => "ANN",
+            AnnLayout::Csq(_) => "CSQ",
+        }
+    }
+
+    /// Parse one entry `s` of the `INFO` field.
+    pub fn parse(&self, s: &str, recovery: &mut AnnRecovery) -> Result<AnnField, anyhow::Error> {
+        match self {
+            AnnLayout::Ann => parse_ann_field(s, recovery),
+            AnnLayout::Csq(layout) => parse_csq_field(s, layout, recovery),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence, FeatureBiotype, PutativeImpact};
+
+    use super::{
+        parse_ann_field, AnnLayout, AnnRecovery, AnnotationSource, CsqLayout, CATCH_ALL_CONSEQUENCE,
+    };
+
+    const MEHARI_ANN: &str = "G|missense_variant|MODERATE|BRCA1|HGNC:1100|transcript|\
+        NM_007294.4|Coding&ManeSelect|10/23|c.3113A>G|p.Glu1038Gly|3332/7088|3113/5592|\
+        1038/1863||-1|";
+
+    #[test]
+    fn mehari_layout_unchanged() -> Result<(), anyhow::Error> {
+        let mut recovery = AnnRecovery::default();
+
+        let ann = parse_ann_field(MEHARI_ANN, &mut recovery)?;
+
+        assert_eq!(ann, MEHARI_ANN.parse::<AnnField>()?);
+        assert_eq!(recovery.total(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn snpeff_layout() -> Result<(), anyhow::Error> {
+        let mut recovery = AnnRecovery::default();
+
+        let ann = parse_ann_field(
+            "G|missense_variant&foo_variant|MODERATE|BRCA1|ENSG00000012048|transcript|\
+             ENST00000357654.9|protein_coding|10/23|c.3113A>G|p.Glu1038Gly|3332/7088|\
+             3113/5592|1038/1863||WARNING_TRANSCRIPT_INCOMPLETE",
+            &mut recovery,
+        )?;
+
+        assert_eq!(
+            ann.consequences,
+            vec![Consequence::MissenseVariant, CATCH_ALL_CONSEQUENCE]
+        );
+        assert_eq!(ann.feature_biotype, vec![FeatureBiotype::Coding]);
+        assert_eq!(ann.strand, 0);
+        assert_eq!(ann.messages.map(|messages| messages.len()), Some(1));
+        assert_eq!(
+            recovery.by_field,
+            indexmap::IndexMap::from([("consequences", 1)])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_fields() -> Result<(), anyhow::Error> {
+        let mut recovery = AnnRecovery::default();
+
+        let ann = parse_ann_field(
+            "-|frameshift_variant|SEVERE|BRCA1|HGNC:1100|transcript|NM_007294.4|\
+             lincRNA|exon10|||x/7088|||abc|+|",
+            &mut recovery,
+        )?;
+
+        assert_eq!(ann.putative_impact, PutativeImpact::Modifier);
+        assert_eq!(ann.rank, None);
+        assert_eq!(ann.tx_pos, None);
+        assert_eq!(ann.distance, None);
+        assert_eq!(ann.strand, 0);
+        assert!(ann.feature_biotype.is_empty());
+        assert_eq!(
+            recovery.by_field.keys().copied().collect::<Vec<_>>(),
+            vec![
+                "allele",
+                "putative_impact",
+                "feature_biotype",
+                "rank",
+                "tx_pos",
+                "distance",
+                "strand"
+            ]
+        );
+        assert_eq!(recovery.total(), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_few_columns() {
+        let mut recovery = AnnRecovery::default();
+
+        assert!(parse_ann_field("G|missense_variant|MODERATE", &mut recovery).is_err());
+    }
+
+    const CSQ_DESCRIPTION: &str = "Consequence annotations from Ensembl VEP. Format: \
+        Allele|Consequence|IMPACT|SYMBOL|Gene|Feature_type|Feature|BIOTYPE|EXON|INTRON|\
+        HGVSc|HGVSp|cDNA_position|CDS_position|Protein_position|Amino_acids|Codons|\
+        Existing_variation|DISTANCE|STRAND|FLAGS|SYMBOL_SOURCE|HGNC_ID|MANE_SELECT";
+
+    #[test]
+    fn csq_field() -> Result<(), anyhow::Error> {
+        let layout = CsqLayout::from_description(CSQ_DESCRIPTION)?;
+        let mut recovery = AnnRecovery::default();
+
+        let ann = super::parse_csq_field(
+            "G|missense_variant&NMD_transcript_variant|MODERATE|BRCA1|ENSG00000012048|\
+             Transcript|ENST00000357654|protein_coding|10/23||ENST00000357654.9:c.3113A>G|\
+             ENSP00000350283.3:p.Glu1038Gly|3332|3113|1038|E/G|gAa/gGa|rs16941|||\
+             cds_end_NF|HGNC|HGNC:1100|NM_007294.4",
+            &layout,
+            &mut recovery,
+        )?;
+
+        assert_eq!(ann.gene_id, "HGNC:1100");
+        assert_eq!(ann.feature_type.to_string(), "transcript");
+        assert_eq!(
+            ann.feature_biotype,
+            vec![FeatureBiotype::Coding, FeatureBiotype::ManeSelect]
+        );
+        assert_eq!(ann.rank.map(|rank| (rank.ord, rank.total)), Some((10, 23)));
+        assert_eq!(ann.hgvs_t.as_deref(), Some("c.3113A>G"));
+        assert_eq!(ann.hgvs_p.as_deref(), Some("p.Glu1038Gly"));
+        assert_eq!(ann.cds_pos.map(|pos| pos.ord), Some(3113));
+        assert_eq!(ann.strand, 0);
+        assert_eq!(ann.messages.map(|messages| messages.len()), Some(1));
+        assert_eq!(
+            recovery.by_field,
+            indexmap::IndexMap::from([("consequences", 1)])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn csq_layout_missing_format() {
+        assert!(CsqLayout::from_description("Consequence annotations from Ensembl VEP").is_err());
+    }
+
+    #[rstest::rstest]
+    #[case::auto_ann(&["ANN", "CSQ"], AnnotationSource::Auto, Some("ANN"))]
+    #[case::auto_csq(&["CSQ"], AnnotationSource::Auto, Some("CSQ"))]
+    #[case::auto_none(&[], AnnotationSource::Auto, Some("ANN"))]
+    #[case::mehari(&["CSQ"], AnnotationSource::Mehari, Some("ANN"))]
+    #[case::vep(&["ANN", "CSQ"], AnnotationSource::Vep, Some("CSQ"))]
+    #[case::vep_missing(&["ANN"], AnnotationSource::Vep, None)]
+    fn layout_from_header(
+        #[case] infos: &[&str],
+        #[case] source: AnnotationSource,
+        #[case] expected: Option<&str>,
+    ) {
+        use noodles::vcf::header::record::value::{
+            map::{info::Number, info::Type, Info},
+            Map,
+        };
+
+        let mut builder = noodles::vcf::Header::builder();
+        for key in infos {
+            let description = if *key == "CSQ" {
+                CSQ_DESCRIPTION
+            } else {
+                "Functional annotations"
+            };
+            builder = builder.add_info(
+                *key,
+                Map::<Info>::new(Number::Unknown, Type::String, description),
+            );
+        }
+        let header = builder.build();
+
+        assert_eq!(
+            AnnLayout::from_header(&header, source)
+                .ok()
+                .map(|layout| layout.info_key()),
+            expected
+        );
+    }
+}
@@ -45,6 +45,9 @@ pub struct CallInfo {
     pub ad: Option<i32>,
     /// Physical phasing ID for this sample.
     pub ps: Option<i32>,
+    /// Whether the genotype was recomputed from the likelihoods in `seqvars ingest`.
+    #[serde(default)]
+    pub gt_from_pl: bool,
 }
 
 impl Eq for CallInfo {}
@@ -183,6 +186,27 @@ pub struct InHouseFrequencies {
     pub het: i32,
     /// Number of hemizygous carriers.
     pub hemi: i32,
+    /// Carriers by affection and sex, if the database has stratified carriers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strata: Option<InHouseStrata>,
+    /// Case UUIDs of the carriers, if requested.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub carrier_uuids: Vec<String>,
+}
+
+/// Number of in-house carriers by affection and sex.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct InHouseStrata {
+    /// Number of affected carriers.
+    pub affected: i32,
+    /// Number of unaffected carriers.
+    pub unaffected: i32,
+    /// Number of carriers with unknown affection.
+    pub unknown_affection: i32,
+    /// Number of male carriers.
+    pub male: i32,
+    /// Number of female carriers.
+    pub female: i32,
 }
 
 impl Ac for InHouseFrequencies {
@@ -440,6 +464,12 @@ impl TryFromVcf for CallInfos {
             } else {
                 None
             };
+            let gt_from_pl = matches!(
+                sample.get(crate::seqvars::ingest::GT_FROM_PL_KEY),
+                Some(Some(
+                    vcf::variant::record_buf::samples::sample::value::Value::Integer(1)
+                ))
+            );
 
             result.insert(
                 name.clone(),
@@ -450,6 +480,7 @@ impl TryFromVcf for CallInfos {
                     dp,
                     ad,
                     ps: phase_set,
+                    gt_from_pl,
                 },
             );
         }
@@ -463,6 +494,8 @@ impl TryFromVcf for CallInfos {
 pub struct AnnFields {
     /// Mapping from sample name to `CallInfo`.
     pub ann_fields: Vec<mehari::annotate::seqvars::ann::AnnField>,
+    /// Counts of values recovered when parsing the `ANN` entries.
+    pub recovery: super::ann::AnnRecovery,
 }
 
 /// Supporting code for `AnnFields`.
@@ -484,18 +517,32 @@ impl TryFromVcf for AnnFields {
         record: &vcf::variant::RecordBuf,
         _header: &vcf::Header,
     ) -> Result<AnnFields, ann_fields::Error> {
-        if let Some(Some(ann)) = record.info().get("ANN") {
+        Self::try_from_vcf_with_layout(record, &Default::default())
+    }
+}
+
+impl AnnFields {
+    /// Convert from the `INFO` field of `record` given by `layout`, e.g., `INFO/CSQ`
+    /// written by VEP.
+    pub fn try_from_vcf_with_layout(
+        record: &vcf::variant::RecordBuf,
+        layout: &super::ann::AnnLayout,
+    ) -> Result<AnnFields, ann_fields::Error> {
+        if let Some(Some(ann)) = record.info().get(layout.info_key()) {
             if let vcf::variant::record_buf::info::field::Value::Array(
                 vcf::variant::record_buf::info::field::value::Array::String(ann),
             ) = ann
             {
+                let mut recovery = super::ann::AnnRecovery::default();
+                let ann_fields = ann
+                    .iter()
+                    .flatten()
+                    .map(|s| layout.parse(s, &mut recovery))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| Self::Error::Parsing(format!("{}", e)))?;
                 Ok(AnnFields {
-                    ann_fields: ann
-                        .iter()
-                        .flatten()
-                        .map(|s| s.parse::<mehari::annotate::seqvars::ann::AnnField>())
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|e| Self::Error::Parsing(format!("{}", e)))?,
+                    ann_fields,
+                    recovery,
                 })
             } else {
                 Err(ann_fields::Error::InvalidTypeInfoAnn)
@@ -552,9 +599,32 @@ impl TryFromVcf for VariantRecord {
         record: &vcf::variant::RecordBuf,
         header: &vcf::Header,
     ) -> Result<Self, Self::Error> {
+        Self::try_from_vcf_with_recovery(
+            record,
+            header,
+            &Default::default(),
+            &mut Default::default(),
+        )
+    }
+}
+
+impl VariantRecord {
+    /// Convert from VCF record as `try_from_vcf()`, reading the annotation from the
+    /// `INFO` field given by `layout`, and add the counts of the values recovered when
+    /// parsing it to `recovery`.
+    pub fn try_from_vcf_with_recovery(
+        record: &vcf::variant::RecordBuf,
+        header: &vcf::Header,
+        layout: &super::ann::AnnLayout,
+        recovery: &mut super::ann::AnnRecovery,
+    ) -> Result<Self, variant_record::Error> {
         let vcf_variant = VcfVariant::try_from_vcf(record, header)?;
         let CallInfos { call_infos } = CallInfos::try_from_vcf(record, header)?;
-        let AnnFields { ann_fields } = AnnFields::try_from_vcf(record, header)?;
+        let AnnFields {
+            ann_fields,
+            recovery: ann_recovery,
+        } = AnnFields::try_from_vcf_with_layout(record, layout)?;
+        recovery.merge(&ann_recovery);
         let population_frequencies = PopulationFrequencies::try_from_vcf(record, header)?;
 
         Ok(Self {
@@ -596,6 +666,8 @@ mod tests {
             het: 20,
             hemi: 5,
             an: 65,
+            strata: None,
+            carrier_uuids: Vec::new(),
         }
     }
 
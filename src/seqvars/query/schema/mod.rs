@@ -1,4 +1,5 @@
 //! Internal Rust representations for query and data.
 
+pub mod ann;
 pub mod data;
 pub mod query;
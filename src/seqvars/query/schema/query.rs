@@ -4,6 +4,7 @@
 //! After deserialization, they are converted into the data structures defined
 //! here.
 
+use crate::common::expression::Expression;
 use crate::pbs::varfish::v1::seqvars::query as pb_query;
 
 /// Enumeration for recessvive mode queries.
@@ -58,6 +59,56 @@ impl TryFrom<pb_query::RecessiveMode> for RecessiveMode {
     }
 }
 
+/// Enumeration for inheritance models beyond the recessive mode.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum InheritanceModel {
+    /// De novo variant in the index.
+    DeNovo,
+    /// Autosomal dominant, carried by the affected parents only.
+    AutosomalDominant,
+    /// Mitochondrial, transmitted by the mother.
+    Mitochondrial,
+}
+
+/// Supporting code for `InheritanceModel`.
+pub(crate) mod inheritance_model {
+    /// Error type for `InheritanceModel::try_from()`.
+    #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+    pub enum Error {
+        #[error("Cannot convert protobuf InheritanceModel: {0:?}")]
+        UnknownInheritanceModelValue(super::pb_query::InheritanceModel),
+    }
+}
+
+impl TryFrom<pb_query::InheritanceModel> for InheritanceModel {
+    type Error = inheritance_model::Error;
+
+    fn try_from(value: pb_query::InheritanceModel) -> Result<Self, Self::Error> {
+        match value {
+            pb_query::InheritanceModel::DeNovo => Ok(InheritanceModel::DeNovo),
+            pb_query::InheritanceModel::AutosomalDominant => {
+                Ok(InheritanceModel::AutosomalDominant)
+            }
+            pb_query::InheritanceModel::Mitochondrial => Ok(InheritanceModel::Mitochondrial),
+            _ => Err(inheritance_model::Error::UnknownInheritanceModelValue(
+                value,
+            )),
+        }
+    }
+}
+
+impl From<InheritanceModel> for pb_query::InheritanceModel {
+    fn from(value: InheritanceModel) -> Self {
+        match value {
+            InheritanceModel::DeNovo => pb_query::InheritanceModel::DeNovo,
+            InheritanceModel::AutosomalDominant => pb_query::InheritanceModel::AutosomalDominant,
+            InheritanceModel::Mitochondrial => pb_query::InheritanceModel::Mitochondrial,
+        }
+    }
+}
+
 /// Enumeration type for genotype choice.
 #[derive(
     Debug,
@@ -143,8 +194,9 @@ pub fn considered_no_call(gt_str: &str) -> bool {
 /// Trait that describes whether a string matches a value.
 ///
 /// Note that we assume properly ingested VCFs with only one alternate allele.
-/// The valid genotype strings have the form "<VAL>/<VAL>", "<VAL>|<VAL>" or
-/// "<VAL>" with "<VAL>" being one of "0", "1", and ".".
+/// Genotype strings are parsed with `common::ParsedGenotype`, so phased and
+/// unphased genotypes are treated the same and any non-reference allele
+/// index counts as alternate allele.
 pub trait MatchesGenotypeStr {
     type Error;
 
@@ -170,16 +222,16 @@ impl MatchesGenotypeStr for GenotypeChoice {
     type Error = genotype_choice::MatchesError;
 
     fn matches(&self, gt_str: &str) -> Result<bool, Self::Error> {
-        let gt_str = if gt_str.starts_with('/') || gt_str.starts_with('|') {
-            &gt_str[1..]
-        } else {
-            gt_str
-        };
+        // Invalid genotype strings do not match anything, same as no-calls.
+        let genotype = gt_str
+            .parse::<crate::common::ParsedGenotype>()
+            .map(|parsed| parsed.genotype())
+            .unwrap_or(crate::common::Genotype::WithNoCall);
         Ok(match self {
             // atoms
-            GenotypeChoice::Ref => ["0", "0|0", "0/0"].contains(&gt_str),
-            GenotypeChoice::Het => ["0/1", "0|1", "1/0", "1|0"].contains(&gt_str),
-            GenotypeChoice::Hom => ["1", "1/1", "1|1"].contains(&gt_str),
+            GenotypeChoice::Ref => genotype == crate::common::Genotype::HomRef,
+            GenotypeChoice::Het => genotype == crate::common::Genotype::Het,
+            GenotypeChoice::Hom => genotype == crate::common::Genotype::HomAlt,
             // combinations
             GenotypeChoice::Variant => {
                 GenotypeChoice::Het.matches(gt_str)? || GenotypeChoice::Hom.matches(gt_str)?
@@ -262,6 +314,38 @@ pub struct QuerySettingsGenotype {
     pub recessive_mode: RecessiveMode,
     /// Mapping from sample name to sample genotype choice.
     pub sample_genotypes: indexmap::IndexMap<String, SampleGenotypeChoice>,
+    /// Inheritance models, variants must match at least one if any is given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inheritance_models: Vec<InheritanceModel>,
+    /// Dominant mode, derives the sample genotype choices from the pedigree if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dominant: Option<QuerySettingsDominant>,
+}
+
+/// Settings for the dominant mode.
+///
+/// When the pedigree is applied, affected samples must carry the variant and
+/// unaffected samples must be hom. ref., or non-hom. with `reduced_penetrance`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QuerySettingsDominant {
+    /// Allow het. variants in unaffected samples for reduced penetrance.
+    #[serde(default)]
+    pub reduced_penetrance: bool,
+    /// Maximal allele fraction of het. calls in unaffected samples to consider the
+    /// variant absent, e.g., for low-level contamination.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_unaffected_ab: Option<f32>,
+}
+
+impl Eq for QuerySettingsDominant {}
+
+impl From<pb_query::QuerySettingsDominant> for QuerySettingsDominant {
+    fn from(value: pb_query::QuerySettingsDominant) -> Self {
+        Self {
+            reduced_penetrance: value.reduced_penetrance,
+            max_unaffected_ab: value.max_unaffected_ab,
+        }
+    }
 }
 
 /// Support code for `QuerySettingsGenotype`.
@@ -284,6 +368,27 @@ pub(crate) mod query_settings_genotype {
         Mothers(Vec<String>),
     }
 
+    /// Error type for `QuerySettingsGenotype::apply_pedigree()`.
+    #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+    pub enum PedigreeError {
+        #[error("Sample {0:?} from query is not in pedigree")]
+        SampleNotInPedigree(String),
+        #[error("Cannot determine recessive index, affected samples in pedigree: {0:?}")]
+        AmbiguousIndex(Vec<String>),
+        #[error("Recessive index sample {0:?} is not affected in pedigree")]
+        IndexNotAffected(String),
+        #[error("Dominant mode cannot be combined with recessive mode")]
+        DominantWithRecessiveMode,
+        #[error("Sample {sample:?} is marked as recessive {role} but pedigree has {expected:?}")]
+        ParentMismatch {
+            sample: String,
+            role: &'static str,
+            expected: Option<String>,
+        },
+        #[error("Invalid recessive index: {0}")]
+        RecessiveIndex(#[from] RecessiveIndexError),
+    }
+
     /// Error type for `QuerySettingsGenotype::try_from()`.
     #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
     pub enum Error {
@@ -291,6 +396,10 @@ pub(crate) mod query_settings_genotype {
         UnknownRecessiveModeInt(i32),
         #[error("Cannot convert protobuf RecessiveMode: {0:?}")]
         UnknownRecessiveModeValue(super::pb_query::RecessiveMode),
+        #[error("Cannot convert i32 into protobuf InheritanceModel: {0}")]
+        UnknownInheritanceModelInt(i32),
+        #[error("Cannot convert protobuf InheritanceModel: {0:?}")]
+        UnknownInheritanceModelValue(super::pb_query::InheritanceModel),
         #[error("Sample occured twice in sample_genotypes: {0:?}")]
         DuplicateSample(String),
         #[error("Invalid sample genotype choice: {0}")]
@@ -393,6 +502,127 @@ impl QuerySettingsGenotype {
             mother: mothers.into_iter().next(),
         })
     }
+
+    /// Check the sample genotypes against `pedigree` and derive the recessive roles.
+    ///
+    /// All samples must be present in the pedigree.  In recessive mode, the index is
+    /// taken from the query or, if not given, set to the only affected sample.  The
+    /// index must be affected and its parents from the pedigree are marked as recessive
+    /// father/mother.  Parents marked in the query must match the pedigree.
+    ///
+    /// In dominant mode, the genotype choices of all samples are replaced: affected
+    /// samples must carry the variant and unaffected samples must be hom. ref. or, with
+    /// reduced penetrance, non-hom.  Samples with unknown affection status are not
+    /// constrained.
+    ///
+    /// # Errors
+    ///
+    /// * `PedigreeError` if the query is inconsistent with the pedigree.
+    pub fn apply_pedigree(
+        &mut self,
+        pedigree: &mehari::ped::PedigreeByName,
+    ) -> Result<(), query_settings_genotype::PedigreeError> {
+        use query_settings_genotype::{PedigreeError, RecessiveIndexError};
+
+        if let Some(sample) = self
+            .sample_genotypes
+            .keys()
+            .find(|sample| !pedigree.individuals.contains_key(*sample))
+        {
+            return Err(PedigreeError::SampleNotInPedigree(sample.clone()));
+        }
+
+        if let Some(dominant) = self.dominant.as_ref() {
+            if self.recessive_mode != RecessiveMode::Disabled {
+                return Err(PedigreeError::DominantWithRecessiveMode);
+            }
+            for (sample, sgc) in self.sample_genotypes.iter_mut() {
+                sgc.genotype = match pedigree.individuals[sample].disease {
+                    mehari::ped::Disease::Affected => GenotypeChoice::Variant,
+                    mehari::ped::Disease::Unaffected if dominant.reduced_penetrance => {
+                        GenotypeChoice::NonHom
+                    }
+                    mehari::ped::Disease::Unaffected => GenotypeChoice::Ref,
+                    mehari::ped::Disease::Unknown => GenotypeChoice::Any,
+                };
+            }
+            return Ok(());
+        }
+
+        if self.recessive_mode == RecessiveMode::Disabled {
+            return Ok(());
+        }
+
+        // Obtain the index from query or derive from the affected samples.
+        let index = match self.recessive_index() {
+            Ok(index) => index,
+            Err(RecessiveIndexError::NoRecessiveIndexSample) => {
+                let affected = self
+                    .sample_genotypes
+                    .keys()
+                    .filter(|sample| {
+                        pedigree.individuals[*sample].disease == mehari::ped::Disease::Affected
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if affected.len() != 1 {
+                    return Err(PedigreeError::AmbiguousIndex(affected));
+                }
+                let index = affected[0].clone();
+                self.sample_genotypes
+                    .get_mut(&index)
+                    .expect("checked above")
+                    .genotype = GenotypeChoice::RecessiveIndex;
+                index
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let individual = &pedigree.individuals[&index];
+        if individual.disease != mehari::ped::Disease::Affected {
+            return Err(PedigreeError::IndexNotAffected(index));
+        }
+
+        // Check and derive the parents.
+        for (role, choice, expected) in [
+            (
+                "father",
+                GenotypeChoice::RecessiveFather,
+                &individual.father,
+            ),
+            (
+                "mother",
+                GenotypeChoice::RecessiveMother,
+                &individual.mother,
+            ),
+        ] {
+            if let Some(sample) = self
+                .sample_genotypes
+                .values()
+                .find(|sgc| sgc.genotype == choice && Some(&sgc.sample) != expected.as_ref())
+            {
+                return Err(PedigreeError::ParentMismatch {
+                    sample: sample.sample.clone(),
+                    role,
+                    expected: expected.clone(),
+                });
+            }
+            if let Some(sgc) = expected
+                .as_ref()
+                .and_then(|parent| self.sample_genotypes.get_mut(parent))
+            {
+                if sgc.genotype == GenotypeChoice::RecessiveIndex {
+                    return Err(PedigreeError::ParentMismatch {
+                        sample: sgc.sample.clone(),
+                        role: "index",
+                        expected: Some(index),
+                    });
+                }
+                sgc.genotype = choice;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl TryFrom<pb_query::QuerySettingsGenotype> for QuerySettingsGenotype {
@@ -414,9 +644,22 @@ impl TryFrom<pb_query::QuerySettingsGenotype> for QuerySettingsGenotype {
             sample_genotypes.insert(sample_genotype.sample.clone(), sample_genotype);
         }
 
+        let inheritance_models = value
+            .inheritance_models
+            .into_iter()
+            .map(|value| {
+                let pb_model = pb_query::InheritanceModel::try_from(value)
+                    .map_err(|_| Self::Error::UnknownInheritanceModelInt(value))?;
+                InheritanceModel::try_from(pb_model)
+                    .map_err(|_| Self::Error::UnknownInheritanceModelValue(pb_model))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         Ok(Self {
             recessive_mode,
             sample_genotypes,
+            inheritance_models,
+            dominant: value.dominant.map(Into::into),
         })
     }
 }
@@ -614,12 +857,40 @@ impl From<pb_query::QuerySettingsFrequency> for QuerySettingsFrequency {
 pub enum VariantType {
     /// SNV.
     Snv,
-    /// Indel.
+    /// Indel, i.e., insertion or deletion.
     Indel,
     /// MNV.
     Mnv,
-    /// Complex substitution.
+    /// Complex substitution ("mixed").
     ComplexSubstitution,
+    /// Insertion.
+    Insertion,
+    /// Deletion.
+    Deletion,
+}
+
+impl VariantType {
+    /// Determine the variant type from the lengths of the VCF-style alleles.
+    ///
+    /// Only `Snv`, `Insertion`, `Deletion`, `Mnv`, and `ComplexSubstitution` are
+    /// returned; `Indel` is a query-level union of insertions and deletions.
+    pub fn from_alleles(ref_allele: &str, alt_allele: &str) -> Self {
+        match (ref_allele.len(), alt_allele.len()) {
+            (1, 1) => VariantType::Snv,
+            (r, a) if r == a => VariantType::Mnv,
+            (1, _) => VariantType::Insertion,
+            (_, 1) => VariantType::Deletion,
+            _ => VariantType::ComplexSubstitution,
+        }
+    }
+
+    /// Return whether the query-level variant type `self` selects variants of the
+    /// type `other` as returned by `from_alleles()`.
+    pub fn selects(self, other: VariantType) -> bool {
+        self == other
+            || (self == VariantType::Indel
+                && matches!(other, VariantType::Insertion | VariantType::Deletion))
+    }
 }
 
 /// Supporting code for `VariantType`.
@@ -641,6 +912,8 @@ impl TryFrom<pb_query::VariantType> for VariantType {
             pb_query::VariantType::Indel => Ok(VariantType::Indel),
             pb_query::VariantType::Mnv => Ok(VariantType::Mnv),
             pb_query::VariantType::ComplexSubstitution => Ok(VariantType::ComplexSubstitution),
+            pb_query::VariantType::Insertion => Ok(VariantType::Insertion),
+            pb_query::VariantType::Deletion => Ok(VariantType::Deletion),
             _ => Err(variant_type::Error::UnknownVariantTypeValue(value)),
         }
     }
@@ -1056,6 +1329,11 @@ pub struct QuerySettingsConsequence {
     pub consequences: Vec<Consequence>,
     /// Maximal distance to next exon.
     pub max_dist_to_exon: Option<i32>,
+    /// Maximal length of the reference homopolymer after indels.
+    pub max_homopolymer_length: Option<i32>,
+    /// Minimal number of RNA-seq reads supporting an aberrant splice junction; only
+    /// splice variants with this support are kept if set.
+    pub min_rna_junction_reads: Option<i32>,
 }
 
 /// Supporting code for `QuerySettingsConsequence`.
@@ -1117,6 +1395,8 @@ impl TryFrom<pb_query::QuerySettingsConsequence> for QuerySettingsConsequence {
             transcript_types,
             consequences,
             max_dist_to_exon: value.max_dist_to_exon,
+            max_homopolymer_length: value.max_homopolymer_length,
+            min_rna_junction_reads: value.min_rna_junction_reads,
         })
     }
 }
@@ -1164,6 +1444,22 @@ pub struct QuerySettingsLocus {
     pub genes: Vec<String>,
     /// List of genomic regions to limit restrict the resulting variants to.
     pub genome_regions: Vec<GenomicRegion>,
+    /// List of chromosomes to limit the resulting variants to; empty means all.
+    pub chromosomes: Vec<String>,
+}
+
+impl QuerySettingsLocus {
+    /// Return whether variants on `chrom` pass the chromosome restriction.
+    ///
+    /// The "chr" prefix is ignored when comparing.
+    pub fn passes_chromosome(&self, chrom: &str) -> bool {
+        self.chromosomes.is_empty() || {
+            let chrom = annonars::common::cli::canonicalize(chrom);
+            self.chromosomes
+                .iter()
+                .any(|other| annonars::common::cli::canonicalize(other) == chrom)
+        }
+    }
 }
 
 impl From<pb_query::QuerySettingsLocus> for QuerySettingsLocus {
@@ -1175,6 +1471,7 @@ impl From<pb_query::QuerySettingsLocus> for QuerySettingsLocus {
                 .into_iter()
                 .map(GenomicRegion::from)
                 .collect(),
+            chromosomes: value.chromosomes,
         }
     }
 }
@@ -1276,6 +1573,26 @@ impl TryFrom<pb_query::QuerySettingsClinVar> for QuerySettingsClinVar {
     }
 }
 
+/// Query settings for phenotype similarity.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QuerySettingsPhenotype {
+    /// HPO term IDs of the patient.
+    pub terms: Vec<String>,
+    /// Minimal phenotype similarity score of a variant's gene to the terms.
+    pub min_phenotype_score: Option<f32>,
+}
+
+impl Eq for QuerySettingsPhenotype {}
+
+impl From<pb_query::QuerySettingsPhenotype> for QuerySettingsPhenotype {
+    fn from(value: pb_query::QuerySettingsPhenotype) -> Self {
+        Self {
+            terms: value.terms,
+            min_phenotype_score: value.min_phenotype_score,
+        }
+    }
+}
+
 /// Query settings for one case.
 #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct CaseQuery {
@@ -1291,6 +1608,12 @@ pub struct CaseQuery {
     pub locus: QuerySettingsLocus,
     /// ClinVar query settings.
     pub clinvar: QuerySettingsClinVar,
+    /// Optional boolean expression over annotated fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<Expression>,
+    /// Phenotype query settings.
+    #[serde(default)]
+    pub phenotype: QuerySettingsPhenotype,
 }
 
 /// Supporting code for `CaseQuery`.
@@ -1306,6 +1629,8 @@ pub(crate) mod case_query {
         Consequence(#[from] super::query_settings_consequence::Error),
         #[error("Problem converting protobuf for clinvar: {0}")]
         Clinvar(#[from] super::query_settings_clinvar::Error),
+        #[error("Problem parsing expression: {0}")]
+        Expression(#[from] crate::common::expression::Error),
     }
 }
 
@@ -1320,6 +1645,8 @@ impl TryFrom<pb_query::CaseQuery> for CaseQuery {
             consequence,
             locus,
             clinvar,
+            expression,
+            phenotype,
         } = value;
 
         let genotype = QuerySettingsGenotype::try_from(genotype.unwrap_or(Default::default()))
@@ -1333,6 +1660,12 @@ impl TryFrom<pb_query::CaseQuery> for CaseQuery {
         let locus = QuerySettingsLocus::from(locus.unwrap_or(Default::default()));
         let clinvar = QuerySettingsClinVar::try_from(clinvar.unwrap_or(Default::default()))
             .map_err(Self::Error::Clinvar)?;
+        let expression = expression
+            .as_deref()
+            .map(Expression::parse)
+            .transpose()
+            .map_err(Self::Error::Expression)?;
+        let phenotype = QuerySettingsPhenotype::from(phenotype.unwrap_or(Default::default()));
 
         Ok(Self {
             genotype,
@@ -1341,6 +1674,8 @@ impl TryFrom<pb_query::CaseQuery> for CaseQuery {
             consequence,
             locus,
             clinvar,
+            expression,
+            phenotype,
         })
     }
 }
@@ -1394,6 +1729,29 @@ mod tests {
         assert!(RecessiveMode::try_from(pb_query::RecessiveMode::Unspecified).is_err());
     }
 
+    #[rstest::rstest]
+    #[case(pb_query::InheritanceModel::DeNovo, InheritanceModel::DeNovo)]
+    #[case(
+        pb_query::InheritanceModel::AutosomalDominant,
+        InheritanceModel::AutosomalDominant
+    )]
+    #[case(
+        pb_query::InheritanceModel::Mitochondrial,
+        InheritanceModel::Mitochondrial
+    )]
+    fn test_inheritance_model_try_from(
+        #[case] pb_model: pb_query::InheritanceModel,
+        #[case] model: InheritanceModel,
+    ) {
+        assert_eq!(InheritanceModel::try_from(pb_model).unwrap(), model);
+        assert_eq!(pb_query::InheritanceModel::from(model), pb_model);
+    }
+
+    #[test]
+    fn test_inheritance_model_try_from_unspecified() {
+        assert!(InheritanceModel::try_from(pb_query::InheritanceModel::Unspecified).is_err());
+    }
+
     #[test]
     fn test_genotype_choice_try_from() {
         assert_eq!(
@@ -1464,6 +1822,8 @@ mod tests {
 
         #[rstest::rstest]
         #[case::any_pass(Any, &["0", "0/0", "0|0", "1", "1/1", "1|1", "0/1", "0|1", "1/0", "1|0"], true)]
+        #[case::het_multiallelic_pass(Het, &["0/2", "2|0"], true)]
+        #[case::hom_multiallelic_pass(Hom, &["1/2", "1|2", "2/2", "2"], true)]
         #[case::any_fail(Any, &["0/.", ".", "./.", ".|."], false)]
         #[case::ref_pass(Ref, &["0", "0/0", "0|0"], true)]
         #[case::ref_fail(Ref, &[ "1", "1/1", "1|1", "0/1", "0/.", "0|1", "1/0", "1|0", ".", "./.", ".|."], false)]
@@ -1496,11 +1856,183 @@ mod tests {
         }
     }
 
+    /// Build a trio pedigree with affected index.
+    fn trio_pedigree() -> mehari::ped::PedigreeByName {
+        use mehari::ped::{Disease, Individual, Sex};
+
+        let individuals = [
+            (
+                "index",
+                Some("father"),
+                Some("mother"),
+                Sex::Male,
+                Disease::Affected,
+            ),
+            ("father", None, None, Sex::Male, Disease::Unaffected),
+            ("mother", None, None, Sex::Female, Disease::Unaffected),
+        ]
+        .into_iter()
+        .map(|(name, father, mother, sex, disease)| {
+            (
+                name.to_string(),
+                Individual {
+                    family: "FAM".to_string(),
+                    name: name.to_string(),
+                    father: father.map(String::from),
+                    mother: mother.map(String::from),
+                    sex,
+                    disease,
+                },
+            )
+        })
+        .collect();
+        mehari::ped::PedigreeByName { individuals }
+    }
+
+    /// Build genotype query settings for the trio with the given choices.
+    fn trio_query_settings(
+        recessive_mode: RecessiveMode,
+        choices: [GenotypeChoice; 3],
+    ) -> QuerySettingsGenotype {
+        QuerySettingsGenotype {
+            recessive_mode,
+            sample_genotypes: ["index", "father", "mother"]
+                .into_iter()
+                .zip(choices)
+                .map(|(sample, genotype)| {
+                    (
+                        sample.to_string(),
+                        SampleGenotypeChoice {
+                            sample: sample.to_string(),
+                            genotype,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            inheritance_models: vec![],
+            dominant: None,
+        }
+    }
+
+    #[test]
+    fn test_query_settings_genotype_apply_pedigree_derives_roles() {
+        use GenotypeChoice::*;
+
+        let mut settings = trio_query_settings(RecessiveMode::CompoundHeterozygous, [Any; 3]);
+        settings.apply_pedigree(&trio_pedigree()).unwrap();
+
+        assert_eq!(
+            settings,
+            trio_query_settings(
+                RecessiveMode::CompoundHeterozygous,
+                [RecessiveIndex, RecessiveFather, RecessiveMother]
+            )
+        );
+    }
+
+    #[test]
+    fn test_query_settings_genotype_apply_pedigree_disabled_unchanged() {
+        use GenotypeChoice::*;
+
+        let mut settings = trio_query_settings(RecessiveMode::Disabled, [Het, Ref, Ref]);
+        settings.apply_pedigree(&trio_pedigree()).unwrap();
+
+        assert_eq!(
+            settings,
+            trio_query_settings(RecessiveMode::Disabled, [Het, Ref, Ref])
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(false, [GenotypeChoice::Variant, GenotypeChoice::Ref, GenotypeChoice::Ref])]
+    #[case(true, [GenotypeChoice::Variant, GenotypeChoice::NonHom, GenotypeChoice::NonHom])]
+    fn test_query_settings_genotype_apply_pedigree_dominant(
+        #[case] reduced_penetrance: bool,
+        #[case] expected: [GenotypeChoice; 3],
+    ) {
+        let dominant = Some(QuerySettingsDominant {
+            reduced_penetrance,
+            max_unaffected_ab: None,
+        });
+        let mut settings = QuerySettingsGenotype {
+            dominant: dominant.clone(),
+            ..trio_query_settings(RecessiveMode::Disabled, [GenotypeChoice::Any; 3])
+        };
+        settings.apply_pedigree(&trio_pedigree()).unwrap();
+
+        assert_eq!(
+            settings,
+            QuerySettingsGenotype {
+                dominant,
+                ..trio_query_settings(RecessiveMode::Disabled, expected)
+            }
+        );
+
+        settings.recessive_mode = RecessiveMode::Homozygous;
+        assert_eq!(
+            settings.apply_pedigree(&trio_pedigree()),
+            Err(super::query_settings_genotype::PedigreeError::DominantWithRecessiveMode)
+        );
+    }
+
+    #[test]
+    fn test_query_settings_genotype_apply_pedigree_errors() {
+        use super::query_settings_genotype::PedigreeError;
+        use GenotypeChoice::*;
+
+        // sample not in pedigree
+        let mut settings = trio_query_settings(RecessiveMode::Disabled, [Any; 3]);
+        settings.sample_genotypes.insert(
+            "sibling".to_string(),
+            SampleGenotypeChoice {
+                sample: "sibling".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            settings.apply_pedigree(&trio_pedigree()),
+            Err(PedigreeError::SampleNotInPedigree("sibling".to_string()))
+        );
+
+        // unaffected index
+        let mut settings = trio_query_settings(RecessiveMode::Any, [Any, RecessiveIndex, Any]);
+        assert_eq!(
+            settings.apply_pedigree(&trio_pedigree()),
+            Err(PedigreeError::IndexNotAffected("father".to_string()))
+        );
+
+        // swapped parents
+        let mut settings = trio_query_settings(
+            RecessiveMode::Any,
+            [RecessiveIndex, RecessiveMother, RecessiveFather],
+        );
+        assert_eq!(
+            settings.apply_pedigree(&trio_pedigree()),
+            Err(PedigreeError::ParentMismatch {
+                sample: "mother".to_string(),
+                role: "father",
+                expected: Some("father".to_string()),
+            })
+        );
+
+        // no affected sample to derive index from
+        let mut pedigree = trio_pedigree();
+        pedigree.individuals["index"].disease = mehari::ped::Disease::Unknown;
+        let mut settings = trio_query_settings(RecessiveMode::Any, [Any; 3]);
+        assert_eq!(
+            settings.apply_pedigree(&pedigree),
+            Err(PedigreeError::AmbiguousIndex(vec![]))
+        );
+    }
+
     #[test]
     fn test_query_settings_genotype_recessive_index_none() {
         let query_settings_genotype = QuerySettingsGenotype {
             recessive_mode: RecessiveMode::CompoundHeterozygous,
             sample_genotypes: Default::default(),
+            inheritance_models: vec![],
+            dominant: None,
         };
 
         assert_eq!(
@@ -1521,6 +2053,8 @@ mod tests {
                     enabled: true,
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
 
         assert_eq!(
@@ -1547,6 +2081,8 @@ mod tests {
                     enabled: true,
                 }
             },
+            inheritance_models: vec![],
+            dominant: None,
         };
 
         assert_eq!(
@@ -1568,6 +2104,11 @@ mod tests {
                 include_no_call: true,
                 enabled: true,
             }],
+            inheritance_models: vec![pb_query::InheritanceModel::DeNovo as i32],
+            dominant: Some(pb_query::QuerySettingsDominant {
+                reduced_penetrance: true,
+                max_unaffected_ab: Some(0.1),
+            }),
         };
         let query_settings_genotype = QuerySettingsGenotype {
             recessive_mode: RecessiveMode::Disabled,
@@ -1584,6 +2125,11 @@ mod tests {
                 );
                 map
             },
+            inheritance_models: vec![InheritanceModel::DeNovo],
+            dominant: Some(QuerySettingsDominant {
+                reduced_penetrance: true,
+                max_unaffected_ab: Some(0.1),
+            }),
         };
         assert_eq!(
             QuerySettingsGenotype::try_from(pb_query_settings_genotype).unwrap(),
@@ -1838,9 +2384,45 @@ mod tests {
             VariantType::try_from(pb_query::VariantType::ComplexSubstitution).unwrap(),
             VariantType::ComplexSubstitution
         );
+        assert_eq!(
+            VariantType::try_from(pb_query::VariantType::Insertion).unwrap(),
+            VariantType::Insertion
+        );
+        assert_eq!(
+            VariantType::try_from(pb_query::VariantType::Deletion).unwrap(),
+            VariantType::Deletion
+        );
         assert!(VariantType::try_from(pb_query::VariantType::Unspecified).is_err());
     }
 
+    #[rstest::rstest]
+    #[case("A", "G", VariantType::Snv)]
+    #[case("AC", "GT", VariantType::Mnv)]
+    #[case("A", "AT", VariantType::Insertion)]
+    #[case("AT", "A", VariantType::Deletion)]
+    #[case("ACG", "TT", VariantType::ComplexSubstitution)]
+    fn test_variant_type_from_alleles(
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] expected: VariantType,
+    ) {
+        assert_eq!(VariantType::from_alleles(ref_allele, alt_allele), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(VariantType::Indel, VariantType::Insertion, true)]
+    #[case(VariantType::Indel, VariantType::Deletion, true)]
+    #[case(VariantType::Indel, VariantType::Snv, false)]
+    #[case(VariantType::Insertion, VariantType::Deletion, false)]
+    #[case(VariantType::Mnv, VariantType::Mnv, true)]
+    fn test_variant_type_selects(
+        #[case] query: VariantType,
+        #[case] other: VariantType,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(query.selects(other), expected);
+    }
+
     #[test]
     fn test_transcript_type_try_from() {
         assert_eq!(
@@ -2011,6 +2593,8 @@ mod tests {
                 pb_query::Consequence::StartLost as i32,
             ],
             max_dist_to_exon: Some(10),
+            max_homopolymer_length: None,
+            min_rna_junction_reads: None,
         };
         let query_settings_consequence = QuerySettingsConsequence {
             variant_types: vec![
@@ -2031,6 +2615,8 @@ mod tests {
                 Consequence::StartLost,
             ],
             max_dist_to_exon: Some(10),
+            max_homopolymer_length: None,
+            min_rna_junction_reads: None,
         };
         assert_eq!(
             QuerySettingsConsequence::try_from(pb_query_settings_consequence).unwrap(),
@@ -2066,6 +2652,7 @@ mod tests {
                 chrom: "chrom".to_string(),
                 range: Some(pb_query::Range { start: 1, stop: 2 }),
             }],
+            chromosomes: vec!["X".to_string()],
         };
         let query_settings_locus = QuerySettingsLocus {
             genes: vec!["gene".to_string()],
@@ -2073,6 +2660,7 @@ mod tests {
                 chrom: "chrom".to_string(),
                 range: Some(Range { start: 1, stop: 2 }),
             }],
+            chromosomes: vec!["X".to_string()],
         };
         assert_eq!(
             QuerySettingsLocus::from(pb_query_settings_locus),
@@ -2080,6 +2668,25 @@ mod tests {
         );
     }
 
+    #[rstest::rstest]
+    #[case(&[], "1", true)]
+    #[case(&["X"], "X", true)]
+    #[case(&["X"], "chrX", true)]
+    #[case(&["chrX"], "X", true)]
+    #[case(&["X"], "1", false)]
+    #[case(&["M"], "chrMT", true)]
+    fn test_query_settings_locus_passes_chromosome(
+        #[case] chromosomes: &[&str],
+        #[case] chrom: &str,
+        #[case] expected: bool,
+    ) {
+        let locus = QuerySettingsLocus {
+            chromosomes: chromosomes.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(locus.passes_chromosome(chrom), expected);
+    }
+
     #[test]
     fn test_clinvar_germline_aggregate_description_try_from() {
         assert_eq!(
@@ -2158,6 +2765,8 @@ mod tests {
                     include_no_call: true,
                     enabled: true,
                 }],
+                inheritance_models: vec![],
+                dominant: None,
             }),
             quality: Some(pb_query::QuerySettingsQuality {
                 sample_qualities: vec![pb_query::SampleQualitySettings {
@@ -2228,6 +2837,8 @@ mod tests {
                     pb_query::Consequence::StartLost as i32,
                 ],
                 max_dist_to_exon: Some(10),
+                max_homopolymer_length: None,
+                min_rna_junction_reads: None,
             }),
             locus: Some(pb_query::QuerySettingsLocus {
                 genes: vec!["gene".to_string()],
@@ -2235,6 +2846,7 @@ mod tests {
                     chrom: "chrom".to_string(),
                     range: Some(pb_query::Range { start: 1, stop: 2 }),
                 }],
+                chromosomes: vec![],
             }),
             clinvar: Some(pb_query::QuerySettingsClinVar {
                 presence_required: true,
@@ -2244,6 +2856,11 @@ mod tests {
                 ],
                 allow_conflicting_interpretations: true,
             }),
+            expression: Some("gnomad_af < 0.001".to_string()),
+            phenotype: Some(pb_query::QuerySettingsPhenotype {
+                terms: vec!["HP:0001250".to_string()],
+                min_phenotype_score: Some(0.5),
+            }),
         };
         let case_query = CaseQuery {
             genotype: QuerySettingsGenotype {
@@ -2261,6 +2878,8 @@ mod tests {
                     );
                     map
                 },
+                inheritance_models: vec![],
+                dominant: None,
             },
             quality: QuerySettingsQuality {
                 sample_qualities: {
@@ -2335,6 +2954,8 @@ mod tests {
                     Consequence::StartLost,
                 ],
                 max_dist_to_exon: Some(10),
+                max_homopolymer_length: None,
+                min_rna_junction_reads: None,
             },
             locus: QuerySettingsLocus {
                 genes: vec!["gene".to_string()],
@@ -2342,6 +2963,7 @@ mod tests {
                     chrom: "chrom".to_string(),
                     range: Some(Range { start: 1, stop: 2 }),
                 }],
+                chromosomes: vec![],
             },
             clinvar: QuerySettingsClinVar {
                 presence_required: true,
@@ -2351,6 +2973,11 @@ mod tests {
                 ],
                 allow_conflicting_interpretations: true,
             },
+            expression: Some(Expression::parse("gnomad_af < 0.001").unwrap()),
+            phenotype: QuerySettingsPhenotype {
+                terms: vec!["HP:0001250".to_string()],
+                min_phenotype_score: Some(0.5),
+            },
         };
         assert_eq!(CaseQuery::try_from(pb_case_query).unwrap(), case_query);
     }
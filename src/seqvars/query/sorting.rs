@@ -1,6 +1,7 @@
 //! Code for sorting `VariantRecord` records by HGNC ID or coordinate.
 
-use super::schema::data::VariantRecord;
+use super::recessive::RecessiveSupport;
+use super::schema::{data::VariantRecord, query::InheritanceModel};
 
 /// Helper wrapper that allows to sort `VariantRecord` by HGNC ID.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -43,10 +44,17 @@ impl Ord for ByHgncId {
 }
 
 /// Helper wrapper that allows to sort `VariantRecord` by coordinate.
+///
+/// The inheritance model matched by the variant and the recessive support of its gene
+/// are carried along.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ByCoordinate {
     pub coordinate: (String, i32),
     pub seqvar: VariantRecord,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inheritance_model: Option<InheritanceModel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recessive_support: Option<RecessiveSupport>,
 }
 
 impl From<VariantRecord> for ByCoordinate {
@@ -54,6 +62,8 @@ impl From<VariantRecord> for ByCoordinate {
         Self {
             coordinate: (val.vcf_variant.chrom.clone(), val.vcf_variant.pos),
             seqvar: val,
+            inheritance_model: None,
+            recessive_support: None,
         }
     }
 }
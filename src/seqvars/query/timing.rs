@@ -0,0 +1,139 @@
+//! Opt-in instrumentation of per-record processing latencies.
+
+use std::time::{Duration, Instant};
+
+use crate::pbs::varfish::v1::seqvars::output as pbs_output;
+
+/// Processing stage of a record.
+#[derive(enum_map::Enum, PartialEq, Eq, Clone, Copy, Debug, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Stage {
+    /// Parsing of the VCF record.
+    Parse,
+    /// Running the query interpreter, including the in-house annotation.
+    Interpret,
+    /// Creating the annotation of the output record.
+    Annotate,
+    /// Handing the output record to the sink, including waiting for the writer thread.
+    Serialize,
+}
+
+/// Latency histograms for each stage in microseconds; no-op if disabled.
+#[derive(Debug, Default)]
+pub struct StageTimings {
+    /// The histograms, `None` if disabled.
+    histograms: Option<enum_map::EnumMap<Stage, hdrhistogram::Histogram<u64>>>,
+}
+
+impl StageTimings {
+    /// Create new timings, recording only if `enabled`.
+    pub fn new(enabled: bool) -> Self {
+        let histograms = enabled.then(|| {
+            enum_map::EnumMap::from_fn(|_| {
+                // Track from 1us to 1min with 3 significant digits.
+                hdrhistogram::Histogram::new_with_bounds(1, 60_000_000, 3)
+                    .expect("invalid histogram bounds")
+            })
+        });
+        Self { histograms }
+    }
+
+    /// Return the current instant if recording, to be passed to `record()`.
+    pub fn start(&self) -> Option<Instant> {
+        self.histograms.as_ref().map(|_| Instant::now())
+    }
+
+    /// Record the time elapsed since `start` for `stage`.
+    pub fn record(&mut self, stage: Stage, start: Option<Instant>) {
+        if let (Some(histograms), Some(start)) = (self.histograms.as_mut(), start) {
+            Self::record_duration(&mut histograms[stage], start.elapsed());
+        }
+    }
+
+    /// Record `duration` into `histogram`, saturating at the bounds.
+    fn record_duration(histogram: &mut hdrhistogram::Histogram<u64>, duration: Duration) {
+        histogram.saturating_record((duration.as_micros() as u64).max(1));
+    }
+
+    /// Log the latency summary of each stage.
+    pub fn log(&self) {
+        for latency in self.to_pbs() {
+            tracing::info!(
+                "latency of {}: n={}, mean={:.1}us, p50={}us, p90={}us, p99={}us, max={}us",
+                latency.stage,
+                latency.count,
+                latency.mean_us,
+                latency.p50_us,
+                latency.p90_us,
+                latency.p99_us,
+                latency.max_us
+            );
+        }
+    }
+
+    /// Convert to protobuf stage latencies; empty if disabled.
+    pub fn to_pbs(&self) -> Vec<pbs_output::StageLatency> {
+        let Some(histograms) = self.histograms.as_ref() else {
+            return Vec::new();
+        };
+        histograms
+            .iter()
+            .map(|(stage, histogram)| pbs_output::StageLatency {
+                stage: stage.to_string(),
+                count: histogram.len(),
+                mean_us: histogram.mean(),
+                p50_us: histogram.value_at_quantile(0.5),
+                p90_us: histogram.value_at_quantile(0.9),
+                p99_us: histogram.value_at_quantile(0.99),
+                max_us: histogram.max(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Stage, StageTimings};
+
+    #[test]
+    fn disabled() {
+        let mut timings = StageTimings::new(false);
+        let start = timings.start();
+        assert!(start.is_none());
+        timings.record(Stage::Parse, start);
+
+        assert!(timings.to_pbs().is_empty());
+    }
+
+    #[test]
+    fn enabled() {
+        let mut timings = StageTimings::new(true);
+        let histograms = timings.histograms.as_mut().unwrap();
+        for micros in [10, 20, 30] {
+            StageTimings::record_duration(
+                &mut histograms[Stage::Annotate],
+                Duration::from_micros(micros),
+            );
+        }
+        timings.record(Stage::Serialize, timings.start());
+
+        let latencies = timings.to_pbs();
+        let stages = latencies
+            .iter()
+            .map(|latency| (latency.stage.as_str(), latency.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            vec![
+                ("parse", 0),
+                ("interpret", 0),
+                ("annotate", 3),
+                ("serialize", 1)
+            ]
+        );
+        assert_eq!(latencies[2].p50_us, 20);
+        assert_eq!(latencies[2].max_us, 30);
+    }
+}
@@ -0,0 +1,159 @@
+//! Implementation of `seqvars resolve` subcommand.
+
+use crate::common::{self, GenomeRelease};
+use crate::seqvars::query::annonars::Annotator;
+use crate::seqvars::query::schema::data::{VariantRecord, VcfVariant};
+
+/// Command line arguments for `seqvars resolve` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(
+    author,
+    version,
+    about = "resolve dbSNP/ClinVar accessions to variants and back",
+    long_about = None
+)]
+#[command(group(clap::ArgGroup::new("lookup").required(true).args(["rsid", "vcv", "variant"])))]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to worker database to use for querying.
+    #[arg(long)]
+    pub path_db: String,
+    /// dbSNP accession to resolve, e.g., `rs12345`.
+    #[arg(long)]
+    pub rsid: Option<String>,
+    /// ClinVar accession to resolve, e.g., `VCV000012345`.
+    #[arg(long)]
+    pub vcv: Option<String>,
+    /// Variant to resolve as `CHROM:POS:REF:ALT`.
+    #[arg(long, value_parser = parse_variant)]
+    pub variant: Option<VcfVariant>,
+}
+
+/// Parse variant from `CHROM:POS:REF:ALT` string.
+fn parse_variant(value: &str) -> Result<VcfVariant, anyhow::Error> {
+    let [chrom, pos, ref_allele, alt_allele]: [&str; 4] = value
+        .split(':')
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("variant must be CHROM:POS:REF:ALT: {}", value))?;
+    Ok(VcfVariant {
+        chrom: annonars::common::cli::canonicalize(chrom),
+        pos: pos
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid position in {}: {}", value, e))?,
+        ref_allele: ref_allele.to_uppercase(),
+        alt_allele: alt_allele.to_uppercase(),
+    })
+}
+
+/// Result of the resolution, written as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Resolution {
+    /// The resolved variant.
+    pub variant: VcfVariant,
+    /// dbSNP accessions of the variant.
+    pub rs_ids: Vec<String>,
+    /// ClinVar VCV accessions of the variant.
+    pub vcv_accessions: Vec<String>,
+}
+
+/// Resolve `variant` to its dbSNP and ClinVar accessions.
+fn resolve_variant(
+    annotator: &Annotator,
+    variant: VcfVariant,
+) -> Result<Resolution, anyhow::Error> {
+    let record = VariantRecord {
+        vcf_variant: variant.clone(),
+        ..Default::default()
+    };
+    let rs_ids = annotator
+        .query_dbsnp(&record)?
+        .map(|record| vec![format!("rs{}", record.rs_id)])
+        .unwrap_or_default();
+    let vcv_accessions = annotator
+        .query_clinvar_minimal(&record)?
+        .map(|list| {
+            list.records
+                .into_iter()
+                .filter_map(|record| record.accession)
+                .map(|accession| format!("{}.{}", accession.accession, accession.version))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Resolution {
+        variant,
+        rs_ids,
+        vcv_accessions,
+    })
+}
+
+/// Main entry point for `seqvars resolve` sub command.
+pub async fn run(args_common: &common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    tracing::info!("Opening annonars databases...");
+    let annotator = Annotator::with_path(&args.path_db, args.genome_release)?;
+
+    let variant = if let Some(rs_id) = args.rsid.as_ref() {
+        annotator
+            .resolve_rsid(rs_id)?
+            .ok_or_else(|| anyhow::anyhow!("dbSNP accession not found: {}", rs_id))?
+    } else if let Some(vcv) = args.vcv.as_ref() {
+        annotator
+            .resolve_vcv(vcv)?
+            .ok_or_else(|| anyhow::anyhow!("ClinVar accession not found: {}", vcv))?
+    } else if let Some(variant) = args.variant.as_ref() {
+        variant.clone()
+    } else {
+        unreachable!("clap ensures that one lookup argument is given")
+    };
+
+    let resolution = resolve_variant(&annotator, variant)?;
+    println!(
+        "{}",
+        serde_json::to_string(&resolution)
+            .map_err(|e| anyhow::anyhow!("could not serialize resolution: {}", e))?
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::query::schema::data::VcfVariant;
+
+    #[rstest::rstest]
+    #[case("chr1:12345:a:g", "1", 12345, "A", "G")]
+    #[case("X:1:AC:A", "X", 1, "AC", "A")]
+    #[case("chrM:100:T:TA", "MT", 100, "T", "TA")]
+    fn parse_variant(
+        #[case] value: &str,
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+    ) -> Result<(), anyhow::Error> {
+        assert_eq!(
+            super::parse_variant(value)?,
+            VcfVariant {
+                chrom: chrom.to_string(),
+                pos,
+                ref_allele: ref_allele.to_string(),
+                alt_allele: alt_allele.to_string(),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1:12345:A")]
+    #[case("1:pos:A:G")]
+    fn parse_variant_invalid(#[case] value: &str) {
+        assert!(super::parse_variant(value).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 //! Command line interface
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
@@ -21,13 +21,19 @@ use strum::IntoEnumIterator;
 use thousands::Separable;
 
 use crate::{
-    common::{self, build_chrom_map, trace_rss_now, GenomeRelease, CHROMS},
+    common::{
+        self, build_chrom_map,
+        errors::{ClassifyExt as _, ErrorClass},
+        trace_rss_now, GenomeRelease, CHROMS,
+    },
     strucvars::query::schema::SvType,
 };
 
-/// Create one file with records for each chromosome and SV type.
+/// Create one file with records for each chromosome and SV type, with the given file
+/// name `prefix`.
 fn create_tmp_files(
     tmp_dir: &tempfile::TempDir,
+    prefix: &str,
 ) -> Result<HashMap<(usize, SvType), BufWriter<File>>, anyhow::Error> {
     let mut files = HashMap::new();
 
@@ -35,7 +41,7 @@ fn create_tmp_files(
         for sv_type in SvType::iter() {
             let path = tmp_dir
                 .path()
-                .join(format!("records.chr{}.{:?}.tsv", *chrom, sv_type));
+                .join(format!("{}.chr{}.{:?}.tsv", prefix, *chrom, sv_type));
             files.insert((chrom_no, sv_type), BufWriter::new(File::create(path)?));
         }
     }
@@ -43,25 +49,63 @@ fn create_tmp_files(
     Ok(files)
 }
 
+/// Write `record` as JSON line to the file for its chromosome and SV type.
+fn write_tmp_record(
+    tmp_files: &mut HashMap<(usize, SvType), BufWriter<File>>,
+    chrom_map: &indexmap::IndexMap<String, usize>,
+    record: &super::output::Record,
+) -> Result<(), anyhow::Error> {
+    let chrom_no = *chrom_map
+        .get(&record.chromosome)
+        .expect("unknown chromosome");
+    let tmp_file = tmp_files
+        .get_mut(&(chrom_no, record.sv_type))
+        .expect("no file for chrom/sv_type");
+    to_writer(&mut *tmp_file, record)?;
+    tmp_file.write_all(b"\n")?;
+    Ok(())
+}
+
 /// Split the input into one file in `tmp_dir` for each chromosome and SV type.
 ///
+/// The records of `bg_db_records` from an existing database are written first.  The
+/// records of input files for cases in `remove_case_uuids` are written to separate
+/// files for subtracting them from the clusters.
+///
 /// Async I/O is used here because we support reading from S3.
+#[tracing::instrument(skip_all)]
 async fn split_input_by_chrom_and_sv_type(
     tmp_dir: &tempfile::TempDir,
     input_vcf_paths: Vec<String>,
     genome_release: GenomeRelease,
+    bg_db_records: Vec<super::output::Record>,
+    remove_case_uuids: &HashSet<uuid::Uuid>,
 ) -> Result<(), anyhow::Error> {
     tracing::info!("parse all input files and split them up");
-    let mut tmp_files = create_tmp_files(tmp_dir)?;
+    let mut tmp_files = create_tmp_files(tmp_dir, "records")?;
+    let mut removed_tmp_files = create_tmp_files(tmp_dir, "removed")?;
     let chrom_map = build_chrom_map();
+    for record in &bg_db_records {
+        write_tmp_record(&mut tmp_files, &chrom_map, record)?;
+    }
     let before_parsing = Instant::now();
     let mut count_files = 0;
+    let mut removed_case_uuids = HashSet::new();
     for path_input in &input_vcf_paths {
         tracing::debug!("parsing {:?}", &path_input);
         let mut input_reader = common::noodles::open_vcf_reader(path_input).await?;
         let input_header = input_reader.read_header().await?;
 
-        let (pedigree, _) = common::extract_pedigree_and_case_uuid(&input_header)?;
+        let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+        let remove = remove_case_uuids.contains(&case_uuid);
+        if remove {
+            tracing::info!(
+                "removing case {} with records from {}",
+                case_uuid,
+                path_input
+            );
+            removed_case_uuids.insert(case_uuid);
+        }
         let mut prev = std::time::Instant::now();
         let before_parsing = Instant::now();
         let mut count_records = 0;
@@ -75,16 +119,11 @@ async fn split_input_by_chrom_and_sv_type(
                 &pedigree,
             )?;
 
-            let chrom_no = *chrom_map
-                .get(&input_record.chromosome)
-                .expect("unknown chromosome");
-            let sv_type = input_record.sv_type;
-            let mut tmp_file = tmp_files
-                .get_mut(&(chrom_no, sv_type))
-                .expect("no file for chrom/sv_type");
-            #[allow(clippy::needless_borrows_for_generic_args)]
-            to_writer(&mut tmp_file, &input_record)?;
-            tmp_file.write_all(b"\n")?;
+            if remove {
+                write_tmp_record(&mut removed_tmp_files, &chrom_map, &input_record)?;
+            } else {
+                write_tmp_record(&mut tmp_files, &chrom_map, &input_record)?;
+            }
 
             // Write out progress indicator every 60 seconds.
             if prev.elapsed().as_secs() >= 60 {
@@ -110,23 +149,64 @@ async fn split_input_by_chrom_and_sv_type(
         before_parsing.elapsed()
     );
     trace_rss_now();
-    for (_, mut f) in tmp_files.drain() {
+    for case_uuid in remove_case_uuids.difference(&removed_case_uuids) {
+        tracing::warn!("no input file for case {} to remove", case_uuid);
+    }
+    for (_, mut f) in tmp_files.drain().chain(removed_tmp_files.drain()) {
+        f.flush()?;
         f.get_mut().sync_all()?
     }
     Ok(())
 }
 
+/// Return the query range for finding the clusters of `record`.
+fn cluster_query(args: &Args, record: &super::output::Record) -> std::ops::Range<i32> {
+    let begin = match record.sv_type {
+        SvType::Bnd => record.begin - 1 - args.slack_bnd,
+        SvType::Ins => record.begin - 1 - args.slack_ins,
+        _ => record.begin,
+    };
+    let end = match record.sv_type {
+        SvType::Bnd => record.begin + args.slack_bnd,
+        SvType::Ins => record.begin + args.slack_ins,
+        _ => record.end,
+    };
+    begin..end
+}
+
+/// Read the records written by `write_tmp_record()` from `reader`.
+fn read_tmp_records(reader: impl BufRead) -> Result<Vec<super::output::Record>, anyhow::Error> {
+    reader
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|e| anyhow::anyhow!("error reading line: {}", e))?;
+            serde_json::from_str(&line).map_err(|e| {
+                anyhow::anyhow!(
+                    "error parsing line from input file: {:?} (line: {:?})",
+                    e,
+                    &line
+                )
+            })
+        })
+        .collect()
+}
+
 /// Read in all records from `reader`, merge overlapping ones.
 ///
 /// The idea to merge here is to get rid of large stacks of SVs with a
 /// reciprocal overlap that is more strict than the 0.75 that is generally used
 /// for querying.  We merge with existing clusters with the reciprocal overlap
 /// is >=0.8 for all members.
+///
+/// The carriers of the `removed` records are then subtracted from the first matching
+/// cluster and clusters without carriers are dropped.  Returns the number of written
+/// records and of removed records without matching cluster.
 fn merge_to_out(
     args: &Args,
     reader: &mut BufReader<File>,
+    removed: Vec<super::output::Record>,
     writer: &mut csv::Writer<impl Write>,
-) -> Result<usize, anyhow::Error> {
+) -> Result<(usize, usize), anyhow::Error> {
     let mut clusters: Vec<Vec<usize>> = vec![];
     let mut tree: IntervalTree<i32, usize> = IntervalTree::new();
     let mut records: Vec<super::output::Record> = Vec::new();
@@ -146,17 +226,7 @@ fn merge_to_out(
             )
         })?;
 
-        let begin = match record.sv_type {
-            SvType::Bnd => record.begin - 1 - args.slack_bnd,
-            SvType::Ins => record.begin - 1 - args.slack_ins,
-            _ => record.begin,
-        };
-        let end = match record.sv_type {
-            SvType::Bnd => record.begin + args.slack_bnd,
-            SvType::Ins => record.begin + args.slack_ins,
-            _ => record.end,
-        };
-        let query = begin..end;
+        let query = cluster_query(args, &record);
         let mut found_any_cluster = false;
         for mut it_tree in tree.find_mut(&query) {
             let cluster_idx = *it_tree.data();
@@ -208,21 +278,45 @@ fn merge_to_out(
             .unwrap()
     });
 
+    // Merge the records of each cluster and subtract the removed records.
+    let mut merged = clusters
+        .iter()
+        .map(|cluster| {
+            let mut out_record = records[cluster[0]].clone();
+            for record_id in &cluster[1..] {
+                out_record.merge_into(&records[*record_id]);
+            }
+            out_record
+        })
+        .collect::<Vec<_>>();
+    let mut unmatched = 0;
+    for record in &removed {
+        let cluster_idx = tree
+            .find(&cluster_query(args, record))
+            .map(|it_tree| *it_tree.data())
+            .find(|cluster_idx| match record.sv_type {
+                SvType::Bnd | SvType::Ins => true,
+                _ => merged[*cluster_idx].overlap(record) >= args.min_overlap,
+            });
+        if let Some(cluster_idx) = cluster_idx {
+            merged[cluster_idx].subtract(record);
+        } else {
+            unmatched += 1;
+        }
+    }
+
     // Finally, write out all records in sorted order
     let mut out_records = 0;
-    for cluster in clusters {
-        let mut out_record = records[cluster[0]].clone();
-        for record_id in &cluster[1..] {
-            out_record.merge_into(&records[*record_id]);
-        }
+    for out_record in merged.into_iter().filter(|record| record.carriers > 0) {
         out_records += 1;
         writer.serialize(&out_record)?;
     }
 
-    Ok(out_records)
+    Ok((out_records, unmatched))
 }
 
 /// Perform (chrom, sv_type) wise merging of records in temporary files.
+#[tracing::instrument(skip_all)]
 fn merge_split_files(
     tmp_dir: &tempfile::TempDir,
     args: &Args,
@@ -251,16 +345,30 @@ fn merge_split_files(
     ])?;
 
     let mut out_records = 0;
+    let mut unmatched = 0;
     for chrom in CHROMS {
         for sv_type in SvType::iter() {
             let filename = format!("records.chr{}.{:?}.tsv", *chrom, sv_type);
             let path = tmp_dir.path().join(&filename);
             tracing::debug!("reading from {}", &filename);
             let mut reader = BufReader::new(File::open(path)?);
-            out_records += merge_to_out(args, &mut reader, &mut writer)?;
+            let removed = read_tmp_records(BufReader::new(File::open(
+                tmp_dir
+                    .path()
+                    .join(format!("removed.chr{}.{:?}.tsv", *chrom, sv_type)),
+            )?))?;
+            let (count, count_unmatched) = merge_to_out(args, &mut reader, removed, &mut writer)?;
+            out_records += count;
+            unmatched += count_unmatched;
         }
     }
     tracing::info!("wrote a total of {} records", out_records);
+    if unmatched > 0 {
+        tracing::warn!(
+            "could not find {} records of removed cases in the database",
+            unmatched
+        );
+    }
 
     // writer.sync_all()?;
     writer.flush()?;
@@ -293,40 +401,94 @@ pub struct Args {
     /// Padding to use for INS
     #[arg(long, default_value_t = 50)]
     pub slack_ins: i32,
-}
 
-/// Main entry point for the `strucvars txt-to-bin` command.
-pub async fn run(common_args: &common::Args, args: &Args) -> Result<(), anyhow::Error> {
-    tracing::info!("Starting `strucvars aggregate`");
-    tracing::info!("  common_args = {:?}", &common_args);
-    tracing::info!("  args = {:?}", &args);
+    /// Path to an existing binary database from `strucvars txt-to-bin` to update with
+    /// the input files instead of building from scratch.
+    #[arg(long)]
+    pub update: Option<PathBuf>,
+    /// UUIDs of cases to remove, prefix with `@` to file with line-wise UUIDs; the
+    /// records of their input files are subtracted instead of added.
+    #[arg(long, requires = "update")]
+    pub remove_case_uuid: Vec<String>,
+}
 
-    // Create final list of input paths (expand `@file.tsv`)
-    let mut input_vcf_paths = Vec::new();
-    for input_vcf in &args.path_input {
-        if let Some(path) = input_vcf.strip_prefix('@') {
+/// Expand the `@file` entries of `values` to the lines of the files.
+fn expand_at_files(values: &[String]) -> Result<Vec<String>, anyhow::Error> {
+    let mut result = Vec::new();
+    for value in values {
+        if let Some(path) = value.strip_prefix('@') {
             let path = shellexpand::tilde(&path);
             let lines = read_lines(path.into_owned())?;
             for line in lines {
-                input_vcf_paths.push(line?.clone());
+                result.push(line?.clone());
             }
         } else {
-            let path = shellexpand::tilde(&input_vcf);
-            input_vcf_paths.push(path.into_owned())
+            let path = shellexpand::tilde(&value);
+            result.push(path.into_owned())
         }
     }
+    Ok(result)
+}
+
+/// Load the records of the binary database at `path` for updating it.
+fn load_bg_db_records(path: &Path) -> Result<Vec<super::output::Record>, anyhow::Error> {
+    let bg_db: crate::pbs::varfish::v1::strucvars::bgdb::BackgroundDatabase =
+        crate::strucvars::query::intervals::read_pb(path)?;
+    bg_db
+        .records
+        .iter()
+        .map(super::output::Record::from_bg_db_record)
+        .collect()
+}
+
+/// Main entry point for the `strucvars txt-to-bin` command.
+pub async fn run(common_args: &common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("Starting `strucvars aggregate`");
+    tracing::info!("  common_args = {:?}", &common_args);
+    tracing::info!("  args = {:?}", &args);
+
+    // Create final list of input paths (expand `@file.tsv`)
+    let input_vcf_paths = expand_at_files(&args.path_input)?;
     tracing::debug!(
         "final input VCF file list is (#: {}): {:?}",
         input_vcf_paths.len(),
         &input_vcf_paths
     );
+    let remove_case_uuids = expand_at_files(&args.remove_case_uuid)?
+        .iter()
+        .map(|value| {
+            uuid::Uuid::parse_str(value.trim())
+                .map_err(|e| anyhow::anyhow!("invalid case UUID {:?}: {}", value, e))
+        })
+        .collect::<Result<HashSet<_>, _>>()
+        .classify(ErrorClass::Config)?;
+
+    // Load the records of the database to update.
+    let bg_db_records = if let Some(path) = args.update.as_ref() {
+        tracing::info!("Loading database {:?} to update...", path);
+        let records = load_bg_db_records(path).classify(ErrorClass::Input)?;
+        tracing::info!(
+            "... loaded {} records",
+            records.len().separate_with_commas()
+        );
+        records
+    } else {
+        Vec::new()
+    };
 
     trace_rss_now();
 
     // Read all input files and write all records by chromosome and SV type
     let tmp_dir = tempfile::TempDir::new()?;
     tracing::debug!("using tmpdir={:?}", &tmp_dir);
-    split_input_by_chrom_and_sv_type(&tmp_dir, input_vcf_paths, args.genome_release).await?;
+    split_input_by_chrom_and_sv_type(
+        &tmp_dir,
+        input_vcf_paths,
+        args.genome_release,
+        bg_db_records,
+        &remove_case_uuids,
+    )
+    .await?;
 
     // Read the output of the previous step by chromosome and SV type, perform
     // overlapping and merge such "compressed" data set to the final output
@@ -358,6 +520,8 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            update: None,
+            remove_case_uuid: Vec::new(),
         };
 
         run(&common_args, &args).await?;
@@ -384,6 +548,8 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            update: None,
+            remove_case_uuid: Vec::new(),
         };
 
         run(&common_args, &args).await?;
@@ -407,6 +573,8 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            update: None,
+            remove_case_uuid: Vec::new(),
         };
 
         run(&common_args, &args).await?;
@@ -416,4 +584,85 @@ mod tests {
 
         Ok(())
     }
+
+    /// Build the database from `oneline.vcf` and convert it to binary in `tmp_dir`.
+    async fn build_bin(tmp_dir: &TempDir) -> Result<std::path::PathBuf, anyhow::Error> {
+        let args = Args {
+            genome_release: GenomeRelease::Grch37,
+            path_output: tmp_dir.join("initial.tsv"),
+            path_input: vec![String::from("tests/strucvars/aggregate/oneline.vcf")],
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            update: None,
+            remove_case_uuid: Vec::new(),
+        };
+        run(&CommonArgs::default(), &args).await?;
+
+        let path_bin = tmp_dir.join("initial.bin");
+        crate::strucvars::txt_to_bin::vardbs::convert_to_bin(
+            &args.path_output,
+            &path_bin,
+            crate::strucvars::txt_to_bin::vardbs::InputFileType::InhouseDb,
+        )?;
+        Ok(path_bin)
+    }
+
+    /// Return the carrier counts of the records in the output TSV file at `path`.
+    fn carriers(path: &std::path::Path) -> Result<Vec<String>, anyhow::Error> {
+        Ok(std::fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').nth(6).unwrap_or_default().to_string())
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn run_update_add() -> Result<(), anyhow::Error> {
+        let tmp_dir = TempDir::default();
+        let path_bin = build_bin(&tmp_dir).await?;
+        let initial = carriers(&tmp_dir.join("initial.tsv"))?;
+
+        let args = Args {
+            genome_release: GenomeRelease::Grch37,
+            path_output: tmp_dir.join("out.tsv"),
+            path_input: vec![String::from("tests/strucvars/aggregate/oneline.vcf")],
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            update: Some(path_bin),
+            remove_case_uuid: Vec::new(),
+        };
+        run(&CommonArgs::default(), &args).await?;
+
+        let updated = carriers(&tmp_dir.join("out.tsv"))?;
+        assert_eq!(updated.len(), initial.len());
+        for (initial, updated) in initial.iter().zip(updated.iter()) {
+            assert_eq!(updated.parse::<u32>()?, 2 * initial.parse::<u32>()?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_update_remove() -> Result<(), anyhow::Error> {
+        let tmp_dir = TempDir::default();
+        let path_bin = build_bin(&tmp_dir).await?;
+
+        let args = Args {
+            genome_release: GenomeRelease::Grch37,
+            path_output: tmp_dir.join("out.tsv"),
+            path_input: vec![String::from("tests/strucvars/aggregate/oneline.vcf")],
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            update: Some(path_bin),
+            remove_case_uuid: vec![String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c")],
+        };
+        run(&CommonArgs::default(), &args).await?;
+
+        assert!(carriers(&tmp_dir.join("out.tsv"))?.is_empty());
+
+        Ok(())
+    }
 }
@@ -12,7 +12,8 @@ use noodles::vcf::{
 };
 
 use crate::{
-    common::{genotype_to_string, strip_gt_leading_slash, Chrom, Genotype},
+    common::{genotype_to_string, strip_gt_leading_slash, Chrom, Genotype, CHROMS},
+    pbs::varfish::v1::strucvars::bgdb::{self, BgDbRecord},
     strucvars::query::schema::SvType,
 };
 
@@ -68,6 +69,55 @@ impl Record {
         self.carriers_hemi += other.carriers_hemi;
     }
 
+    /// Subtract the carriers of `other`, e.g., of a removed case.
+    pub fn subtract(&mut self, other: &Record) {
+        self.carriers = self.carriers.saturating_sub(other.carriers);
+        self.carriers_het = self.carriers_het.saturating_sub(other.carriers_het);
+        self.carriers_hom = self.carriers_hom.saturating_sub(other.carriers_hom);
+        self.carriers_hemi = self.carriers_hemi.saturating_sub(other.carriers_hemi);
+    }
+
+    /// Convert a record of the binary database written by `strucvars txt-to-bin`.
+    ///
+    /// The binary database only stores the total number of carriers, so the het., hom.,
+    /// and hemi. counts are zero, and the paired-end orientation of breakends is lost.
+    pub fn from_bg_db_record(record: &BgDbRecord) -> Result<Self, anyhow::Error> {
+        let chrom_name = |chrom_no: i32| {
+            CHROMS
+                .get(chrom_no as usize)
+                .map(|chrom| chrom.to_string())
+                .ok_or_else(|| anyhow::anyhow!("invalid chromosome number {}", chrom_no))
+        };
+        let sv_type = match bgdb::SvType::try_from(record.sv_type)? {
+            bgdb::SvType::Del => SvType::Del,
+            bgdb::SvType::Dup => SvType::Dup,
+            bgdb::SvType::Inv => SvType::Inv,
+            bgdb::SvType::Ins => SvType::Ins,
+            bgdb::SvType::Bnd => SvType::Bnd,
+            bgdb::SvType::Cnv => SvType::Cnv,
+            bgdb::SvType::Unspecified => anyhow::bail!("unspecified SV type in {:?}", record),
+        };
+        let pe_orientation = match sv_type {
+            SvType::Del => StrandOrientation::ThreeToFive,
+            SvType::Dup => StrandOrientation::FiveToThree,
+            SvType::Inv => StrandOrientation::FiveToFive,
+            SvType::Bnd | SvType::Ins | SvType::Cnv => StrandOrientation::NotApplicable,
+        };
+
+        Ok(Self {
+            chromosome: chrom_name(record.chrom_no)?,
+            begin: record.start - 1,
+            chromosome2: chrom_name(record.chrom_no2)?,
+            end: record.stop,
+            pe_orientation,
+            sv_type,
+            carriers: record.count,
+            carriers_het: 0,
+            carriers_hom: 0,
+            carriers_hemi: 0,
+        })
+    }
+
     /// Convert VCF record into a `Record`.
     pub fn from_vcf(
         record: &vcf::variant::RecordBuf,
@@ -0,0 +1,358 @@
+//! Code implementing the "strucvars bin-to-txt" sub command.
+//!
+//! The protobuf `.bin` files written by `strucvars txt-to-bin` are converted back to
+//! TSV files for auditing.  Masked regions and gene cross-links are written in the
+//! input format of `txt-to-bin`.  Background databases and ClinVar SVs are written in
+//! a generic format with the stored fields only as the source-specific fields are not
+//! kept in the binary files.
+
+use clap::{command, Parser};
+use prost::Message;
+
+use crate::{
+    common::{trace_rss_now, CHROMS},
+    pbs::varfish::v1::strucvars::{
+        bgdb::{BackgroundDatabase, MaskedDatabase, XlinkDatabase},
+        clinvar::SvDatabase,
+    },
+};
+
+/// Select the type of the records in the binary file.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, strum::Display, PartialEq, Eq)]
+pub enum RecordType {
+    /// Background database, e.g., from in-house data, gnomAD, or DGV.
+    BgDb,
+    /// ClinVar structural variants.
+    ClinvarSv,
+    /// Masked regions.
+    MaskedRegion,
+    /// Gene cross-links.
+    Xlink,
+}
+
+/// Command line arguments for `strucvars bin-to-txt` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Convert binary protobuf files to text", long_about = None)]
+pub struct Args {
+    /// Type of the records in the binary file.
+    #[arg(long, value_enum)]
+    pub record_type: RecordType,
+    /// Path to the input BIN file.
+    #[arg(long)]
+    pub path_input: String,
+    /// Path to the output TSV file; written to stdout if not given.
+    #[arg(long)]
+    pub path_output: Option<String>,
+}
+
+/// Module with the records as written to the TSV files.
+mod output {
+    use serde::Serialize;
+
+    /// Background database record.
+    #[derive(Debug, Serialize)]
+    pub struct BgDbRecord {
+        /// Chromosome of start position.
+        pub chromosome: String,
+        /// Chromosome of end position.
+        pub chromosome2: String,
+        /// SV type, e.g., `DEL`.
+        pub sv_type: String,
+        /// 1-based start position.
+        pub start: i32,
+        /// 1-based stop position.
+        pub stop: i32,
+        /// Number of carriers (or alleles), depending on database.
+        pub count: u32,
+        /// Number of samples genotyped at the site, 0 if unknown.
+        pub total: u32,
+    }
+
+    /// ClinVar structural variant record.
+    #[derive(Debug, Serialize)]
+    pub struct ClinvarSvRecord {
+        /// Chromosome name.
+        pub chromosome: String,
+        /// 1-based start position.
+        pub start: i32,
+        /// 1-based stop position.
+        pub stop: i32,
+        /// Variation type, e.g., `DEL`.
+        pub variation_type: String,
+        /// Pathogenicity, e.g., `LIKELY_PATHOGENIC`.
+        pub pathogenicity: String,
+        /// RCV accession.
+        pub rcv: String,
+        /// VCV accession.
+        pub vcv: String,
+    }
+
+    /// Masked region in the BED format read by `txt-to-bin`.
+    #[derive(Debug, Serialize)]
+    pub struct MaskedRecord {
+        /// Chromosome name.
+        pub chromosome: String,
+        /// 0-based begin position.
+        pub begin: i32,
+        /// 1-based end position.
+        pub end: i32,
+        /// Label, not kept in the binary file.
+        pub label: &'static str,
+    }
+
+    /// Gene cross-link in the TSV format read by `txt-to-bin`.
+    #[derive(Debug, Serialize)]
+    pub struct XlinkRecord {
+        /// HGNC ID.
+        pub hgnc_id: String,
+        /// Ensembl gene ID.
+        pub ensembl_gene_id: String,
+        /// Entrez gene ID.
+        pub entrez_id: u32,
+        /// Gene symbol.
+        pub gene_symbol: String,
+    }
+}
+
+/// Return the name of chromosome `chrom_no`.
+fn chrom_name(chrom_no: i32) -> Result<String, anyhow::Error> {
+    usize::try_from(chrom_no)
+        .ok()
+        .and_then(|chrom_no| CHROMS.get(chrom_no))
+        .map(|chrom| chrom.to_string())
+        .ok_or_else(|| anyhow::anyhow!("invalid chromosome number {}", chrom_no))
+}
+
+/// Return the protobuf name of enumeration value `value` without `prefix`.
+fn enum_name<E>(value: i32, prefix: &str, as_str_name: fn(&E) -> &'static str) -> String
+where
+    E: TryFrom<i32>,
+{
+    E::try_from(value)
+        .map(|value| as_str_name(&value).trim_start_matches(prefix).to_string())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+/// Write `records` as TSV to `writer`, with header row if `has_headers`.
+fn write_records<W, R>(
+    writer: W,
+    has_headers: bool,
+    records: impl IntoIterator<Item = Result<R, anyhow::Error>>,
+) -> Result<usize, anyhow::Error>
+where
+    W: std::io::Write,
+    R: serde::Serialize,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(has_headers)
+        .from_writer(writer);
+    let mut count = 0;
+    for record in records {
+        writer.serialize(record?)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Convert the binary file `buf` with records of `record_type` to TSV written to `writer`.
+///
+/// Returns the number of records written.
+fn convert_to_txt<W>(buf: &[u8], record_type: RecordType, writer: W) -> Result<usize, anyhow::Error>
+where
+    W: std::io::Write,
+{
+    use crate::pbs::varfish::v1::strucvars::{bgdb, clinvar};
+
+    match record_type {
+        RecordType::BgDb => {
+            let db = BackgroundDatabase::decode(buf)?;
+            tracing::info!("sample count of background database: {}", db.sample_count);
+            write_records(
+                writer,
+                true,
+                db.records.into_iter().map(|record| {
+                    Ok(output::BgDbRecord {
+                        chromosome: chrom_name(record.chrom_no)?,
+                        chromosome2: chrom_name(record.chrom_no2)?,
+                        sv_type: enum_name(record.sv_type, "SV_TYPE_", bgdb::SvType::as_str_name),
+                        start: record.start,
+                        stop: record.stop,
+                        count: record.count,
+                        total: record.total,
+                    })
+                }),
+            )
+        }
+        RecordType::ClinvarSv => write_records(
+            writer,
+            true,
+            SvDatabase::decode(buf)?.records.into_iter().map(|record| {
+                Ok(output::ClinvarSvRecord {
+                    chromosome: chrom_name(record.chrom_no)?,
+                    start: record.start,
+                    stop: record.stop,
+                    variation_type: enum_name(
+                        record.variation_type,
+                        "VARIATION_TYPE_",
+                        clinvar::VariationType::as_str_name,
+                    ),
+                    pathogenicity: enum_name(
+                        record.pathogenicity,
+                        "PATHOGENICITY_",
+                        clinvar::Pathogenicity::as_str_name,
+                    ),
+                    rcv: format!("RCV{:09}", record.rcv),
+                    vcv: format!("VCV{:09}", record.vcv),
+                })
+            }),
+        ),
+        RecordType::MaskedRegion => write_records(
+            writer,
+            false,
+            MaskedDatabase::decode(buf)?
+                .records
+                .into_iter()
+                .map(|record| {
+                    Ok(output::MaskedRecord {
+                        chromosome: chrom_name(record.chrom_no)?,
+                        begin: record.start - 1,
+                        end: record.stop,
+                        label: ".",
+                    })
+                }),
+        ),
+        RecordType::Xlink => write_records(
+            writer,
+            true,
+            XlinkDatabase::decode(buf)?
+                .records
+                .into_iter()
+                .map(|record| {
+                    Ok(output::XlinkRecord {
+                        hgnc_id: record.hgnc_id,
+                        ensembl_gene_id: format!("ENSG{:011}", record.ensembl_id),
+                        entrez_id: record.entrez_id,
+                        gene_symbol: record.symbol,
+                    })
+                }),
+        ),
+    }
+}
+
+/// Main entry point for the `strucvars bin-to-txt` command.
+pub fn run(common_args: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("Starting `strucvars bin-to-txt`");
+    tracing::info!("  common_args = {:?}", &common_args);
+    tracing::info!("  args = {:?}", &args);
+
+    let buf = std::fs::read(&args.path_input)
+        .map_err(|e| anyhow::anyhow!("could not read input file {}: {}", &args.path_input, e))?;
+    trace_rss_now();
+
+    tracing::info!("Starting conversion...");
+    let writer: Box<dyn std::io::Write> = if let Some(path_output) = &args.path_output {
+        Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_output).map_err(|e| {
+                anyhow::anyhow!("could not create output file {}: {}", path_output, e)
+            })?,
+        ))
+    } else {
+        Box::new(std::io::stdout().lock())
+    };
+    let count = convert_to_txt(&buf, args.record_type, writer).map_err(|e| {
+        anyhow::anyhow!(
+            "could not convert {} as {} records: {}",
+            &args.path_input,
+            args.record_type,
+            e
+        )
+    })?;
+    tracing::info!("... done writing {} records", count);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message as _;
+
+    use super::{convert_to_txt, RecordType};
+    use crate::pbs::varfish::v1::strucvars::bgdb::{
+        BackgroundDatabase, BgDbRecord, MaskedDatabase, SvType, XlinkDatabase,
+    };
+    use crate::strucvars::txt_to_bin::{masked, xlink};
+
+    #[test]
+    fn bg_db() -> Result<(), anyhow::Error> {
+        let db = BackgroundDatabase {
+            records: vec![BgDbRecord {
+                chrom_no: 22,
+                chrom_no2: 22,
+                sv_type: SvType::Del as i32,
+                start: 1_001,
+                stop: 2_000,
+                count: 3,
+                total: 100,
+            }],
+            sample_count: 100,
+        };
+
+        let mut buf = Vec::new();
+        convert_to_txt(&db.encode_to_vec(), RecordType::BgDb, &mut buf)?;
+
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "chromosome\tchromosome2\tsv_type\tstart\tstop\tcount\ttotal\n\
+             X\tX\tDEL\t1001\t2000\t3\t100\n"
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::masked_region(
+        RecordType::MaskedRegion,
+        "tests/db/to-bin/varfish-db-downloader/features/grch37/masked/repeat.bed.gz"
+    )]
+    #[case::xlink(
+        RecordType::Xlink,
+        "tests/db/to-bin/varfish-db-downloader/genes/xlink/hgnc.tsv"
+    )]
+    fn roundtrip(
+        #[case] record_type: RecordType,
+        #[case] path_input: &str,
+    ) -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let convert_to_bin =
+            |path_input: &std::path::Path, path_output: &std::path::Path| match record_type {
+                RecordType::MaskedRegion => masked::convert_to_bin(path_input, path_output),
+                RecordType::Xlink => xlink::convert_to_bin(path_input, path_output),
+                _ => unreachable!(),
+            };
+
+        convert_to_bin(std::path::Path::new(path_input), &tmp_dir.join("first.bin"))?;
+        let first = std::fs::read(tmp_dir.join("first.bin"))?;
+        let mut txt = Vec::new();
+        let count = convert_to_txt(&first, record_type, &mut txt)?;
+        assert!(count > 0);
+        std::fs::write(tmp_dir.join("dump.tsv"), &txt)?;
+        convert_to_bin(&tmp_dir.join("dump.tsv"), &tmp_dir.join("second.bin"))?;
+        let second = std::fs::read(tmp_dir.join("second.bin"))?;
+
+        match record_type {
+            RecordType::MaskedRegion => assert_eq!(
+                MaskedDatabase::decode(first.as_slice())?,
+                MaskedDatabase::decode(second.as_slice())?
+            ),
+            RecordType::Xlink => assert_eq!(
+                XlinkDatabase::decode(first.as_slice())?,
+                XlinkDatabase::decode(second.as_slice())?
+            ),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
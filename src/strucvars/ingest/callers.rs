@@ -0,0 +1,521 @@
+//! Support for SV callers that `mehari` does not know.
+//!
+//! CNVnator and Canvas are detected from the `##source` header line and their records
+//! are converted by the `VcfRecordConverter` implementations in this module.  All other
+//! callers are guessed and converted by `mehari`.
+
+use futures::TryStreamExt as _;
+use mehari::annotate::strucvars::{
+    build_vcf_record_converter, guess_sv_caller, GenotypeInfo, SvCaller, SvSubType, SvType,
+    VarFishStrucvarTsvRecord, VcfRecordConverter,
+};
+use mehari::common::noodles::NoodlesVariantReader as _;
+use mehari::ped::{PedigreeByName, Sex};
+use noodles::vcf;
+use strum::IntoEnumIterator as _;
+use vcf::variant::record_buf::info::field::value::Array;
+use vcf::variant::record_buf::info::field::Value;
+use vcf::variant::record_buf::samples::sample;
+
+/// The SV caller of an input file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Caller {
+    /// A caller that is supported by `mehari`.
+    Mehari(SvCaller),
+    /// CNVnator read-depth CNV caller.
+    Cnvnator { version: String },
+    /// Illumina Canvas CNV caller.
+    Canvas { version: String },
+}
+
+impl Caller {
+    /// Detect CNVnator and Canvas from the `##source` line of `header`.
+    fn from_header(header: &vcf::Header) -> Option<Self> {
+        let source = header
+            .other_records()
+            .iter()
+            .find_map(|(key, values)| match values {
+                vcf::header::record::value::Collection::Unstructured(inner)
+                    if key.as_ref() == "source" =>
+                {
+                    inner.first().map(String::as_str)
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        // CNVnator's `cnvnator2VCF.pl` writes `##source=CNVnator` without version.
+        if let Some(rest) = source.strip_prefix("CNVnator") {
+            Some(Self::Cnvnator {
+                version: version_or_unknown(rest.trim().trim_start_matches('v')),
+            })
+        } else if header.infos().contains_key("natorRD") {
+            Some(Self::Cnvnator {
+                version: version_or_unknown(""),
+            })
+        } else if let Some(rest) = source.strip_prefix("Canvas") {
+            Some(Self::Canvas {
+                version: version_or_unknown(rest.trim()),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Construct the `VcfRecordConverter` for the caller.
+    pub fn converter<T: AsRef<str>>(&self, samples: &[T]) -> Box<dyn VcfRecordConverter> {
+        let samples = samples
+            .iter()
+            .map(|s| s.as_ref().to_string())
+            .collect::<Vec<_>>();
+        match self {
+            Caller::Mehari(sv_caller) => build_vcf_record_converter(sv_caller, &samples),
+            Caller::Cnvnator { version } => Box::new(CnvnatorVcfRecordConverter {
+                samples,
+                version: version.clone(),
+            }),
+            Caller::Canvas { version } => Box::new(CanvasVcfRecordConverter {
+                samples,
+                version: version.clone(),
+            }),
+        }
+    }
+}
+
+/// Return `version` or `"unknown"` if it is empty.
+fn version_or_unknown(version: &str) -> String {
+    if version.is_empty() {
+        String::from("unknown")
+    } else {
+        version.to_string()
+    }
+}
+
+/// Guess the caller of the VCF file at `path`.
+pub async fn guess_caller(path: &str) -> Result<Caller, anyhow::Error> {
+    let mut reader = crate::common::noodles::open_vcf_reader(path).await?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header of {}: {}", path, e))?;
+    if let Some(caller) = Caller::from_header(&header) {
+        return Ok(caller);
+    }
+
+    // `mehari` needs the first record for the version of some callers.
+    let mut records = reader.records(&header).await;
+    if records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading records of {}: {}", path, e))?
+        .is_none()
+    {
+        return guess_sv_caller_header_only(&header)
+            .map(Caller::Mehari)
+            .ok_or_else(|| anyhow::anyhow!("could not guess SV caller of empty file {}", path));
+    }
+
+    let mut reader = crate::common::noodles::open_vcf_reader(path).await?;
+    Ok(Caller::Mehari(guess_sv_caller(&mut reader).await?))
+}
+
+/// Guess the `mehari` caller of a file without records from its `header` only.
+///
+/// The version is not extracted as it may depend on the records.
+fn guess_sv_caller_header_only(header: &vcf::Header) -> Option<SvCaller> {
+    let mut sv_caller = SvCaller::iter().find(|caller| caller.caller_compatible(header))?;
+    match &mut sv_caller {
+        SvCaller::ClinCnv { version }
+        | SvCaller::Delly { version }
+        | SvCaller::DragenSv { version }
+        | SvCaller::DragenCnv { version }
+        | SvCaller::Gcnv { version }
+        | SvCaller::Manta { version }
+        | SvCaller::Melt { version }
+        | SvCaller::Popdel { version }
+        | SvCaller::Sniffles2 { version } => *version = version_or_unknown(""),
+    }
+    Some(sv_caller)
+}
+
+/// Fill the confidence intervals from `INFO/CIPOS` and `INFO/CIEND`, if any.
+fn fill_standard_cis(
+    vcf_record: &vcf::variant::RecordBuf,
+    tsv_record: &mut VarFishStrucvarTsvRecord,
+) -> Result<(), anyhow::Error> {
+    for (key, left, right) in [
+        (
+            vcf::variant::record::info::field::key::POSITION_CONFIDENCE_INTERVALS,
+            &mut tsv_record.start_ci_left,
+            &mut tsv_record.start_ci_right,
+        ),
+        (
+            vcf::variant::record::info::field::key::END_CONFIDENCE_INTERVALS,
+            &mut tsv_record.end_ci_left,
+            &mut tsv_record.end_ci_right,
+        ),
+    ] {
+        if let Some(Some(Value::Array(Array::Integer(values)))) = vcf_record.info().get(key) {
+            match values.as_slice() {
+                [Some(l), Some(r)] => {
+                    *left = *l;
+                    *right = *r;
+                }
+                _ => anyhow::bail!("{} must have two values", key),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the genotype of `value` as string.
+fn genotype_string(value: &sample::Value) -> Option<String> {
+    match value {
+        sample::Value::String(gt) => Some(gt.clone()),
+        sample::Value::Genotype(gt) => Some(mehari::annotate::genotype_string(
+            gt,
+            vcf::header::FileFormat::new(4, 0),
+        )),
+        _ => None,
+    }
+}
+
+/// Update the carrier counts of `tsv_record` from its genotype entries.
+///
+/// This mirrors the counting in `mehari`: calls with reference or alternative alleles
+/// are counted by genotype, no-calls by the difference of the copy number to the
+/// expected one.  Gonosomal calls of samples with unknown sex are not counted.
+fn count_carriers(tsv_record: &mut VarFishStrucvarTsvRecord, pedigree: &PedigreeByName) {
+    let is_chr_x = tsv_record.chromosome.contains('X');
+    let is_chr_y = tsv_record.chromosome.contains('Y');
+    for entry in &tsv_record.genotype.entries {
+        let sex = pedigree
+            .individuals
+            .get(&entry.name)
+            .map(|individual| individual.sex)
+            .unwrap_or_default();
+        let expected_cn = match (is_chr_x, is_chr_y, sex) {
+            (false, false, _) | (true, false, Sex::Female) => 2,
+            (true, false, Sex::Male) | (false, true, Sex::Male) => 1,
+            _ => continue,
+        };
+        let gt = entry.gt.as_deref().unwrap_or_default();
+        match (gt.contains('0'), gt.contains('1')) {
+            (false, false) => {
+                let Some(cn) = entry.cn else {
+                    continue;
+                };
+                let delta = (cn - expected_cn).abs();
+                if expected_cn == 1 {
+                    tsv_record.num_hemi_alt += delta;
+                } else if delta == 0 {
+                    tsv_record.num_hom_ref += 1;
+                } else if delta == 1 {
+                    tsv_record.num_het += 1;
+                } else {
+                    tsv_record.num_hom_alt += 1;
+                }
+            }
+            (_, true) if expected_cn == 1 => tsv_record.num_hemi_alt += 1,
+            (true, false) if expected_cn == 1 => tsv_record.num_hemi_ref += 1,
+            (false, true) => tsv_record.num_hom_alt += 1,
+            (true, false) => tsv_record.num_hom_ref += 1,
+            (true, true) => tsv_record.num_het += 1,
+        }
+    }
+}
+
+/// Conversion of CNVnator records.
+///
+/// `FORMAT/CN` is used as copy number and `INFO/natorRD`, the read depth normalized
+/// to the genome-wide average, as average normalized coverage.
+struct CnvnatorVcfRecordConverter {
+    /// The samples from the VCF file.
+    samples: Vec<String>,
+    /// The CNVnator version.
+    version: String,
+}
+
+impl VcfRecordConverter for CnvnatorVcfRecordConverter {
+    fn caller_version(&self) -> String {
+        format!("CNVNATORv{}", self.version)
+    }
+
+    fn fill_cis(
+        &self,
+        vcf_record: &vcf::variant::RecordBuf,
+        tsv_record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<(), anyhow::Error> {
+        fill_standard_cis(vcf_record, tsv_record)
+    }
+
+    fn fill_genotypes(
+        &self,
+        pedigree: &PedigreeByName,
+        vcf_record: &vcf::variant::RecordBuf,
+        tsv_record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<(), anyhow::Error> {
+        let anc = match vcf_record.info().get("natorRD") {
+            Some(Some(Value::Float(rd))) => Some(*rd),
+            _ => None,
+        };
+
+        let mut entries = Vec::with_capacity(self.samples.len());
+        for (name, sample) in self.samples.iter().zip(vcf_record.samples().values()) {
+            let mut entry = GenotypeInfo {
+                name: name.clone(),
+                anc,
+                ..Default::default()
+            };
+            for (key, value) in sample.keys().as_ref().iter().zip(sample.values().iter()) {
+                match (key.as_ref(), value) {
+                    ("GT", Some(value)) => entry.gt = genotype_string(value),
+                    ("CN", Some(sample::Value::Integer(cn))) => entry.cn = Some(*cn),
+                    _ => (),
+                }
+            }
+            entries.push(entry);
+        }
+
+        tsv_record.genotype.entries = entries;
+        count_carriers(tsv_record, pedigree);
+
+        Ok(())
+    }
+}
+
+/// Conversion of Canvas records.
+///
+/// Canvas writes `SVTYPE=CNV`, the direction is taken from `<DEL>`/`<DUP>` alleles or
+/// the `Canvas:LOSS`/`Canvas:GAIN` record IDs.  `FORMAT/CN` is used as copy number,
+/// `FORMAT/BC` (number of bins) as point count, and `FORMAT/QS` as genotype quality.
+struct CanvasVcfRecordConverter {
+    /// The samples from the VCF file.
+    samples: Vec<String>,
+    /// The Canvas version.
+    version: String,
+}
+
+impl VcfRecordConverter for CanvasVcfRecordConverter {
+    fn caller_version(&self) -> String {
+        format!("CANVASv{}", self.version)
+    }
+
+    fn fill_sv_type(
+        &self,
+        vcf_record: &vcf::variant::RecordBuf,
+        tsv_record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<(), anyhow::Error> {
+        let has_id = |infix: &str| {
+            vcf_record
+                .ids()
+                .as_ref()
+                .iter()
+                .any(|id| id.contains(infix))
+        };
+        let alt = vcf_record
+            .alternate_bases()
+            .as_ref()
+            .first()
+            .map(String::as_str);
+        tsv_record.sv_sub_type = match alt {
+            Some("<DEL>") => SvSubType::Del,
+            Some("<DUP>") => SvSubType::Dup,
+            _ if has_id(":LOSS:") => SvSubType::Del,
+            _ if has_id(":GAIN:") => SvSubType::Dup,
+            _ => SvSubType::Cnv,
+        };
+        tsv_record.sv_type = SvType::from(tsv_record.sv_sub_type);
+        tsv_record.pe_orientation = tsv_record.sv_type.into();
+
+        Ok(())
+    }
+
+    fn fill_cis(
+        &self,
+        vcf_record: &vcf::variant::RecordBuf,
+        tsv_record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<(), anyhow::Error> {
+        fill_standard_cis(vcf_record, tsv_record)
+    }
+
+    fn fill_genotypes(
+        &self,
+        pedigree: &PedigreeByName,
+        vcf_record: &vcf::variant::RecordBuf,
+        tsv_record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<(), anyhow::Error> {
+        let mut entries = Vec::with_capacity(self.samples.len());
+        for (name, sample) in self.samples.iter().zip(vcf_record.samples().values()) {
+            let mut entry = GenotypeInfo {
+                name: name.clone(),
+                ..Default::default()
+            };
+            for (key, value) in sample.keys().as_ref().iter().zip(sample.values().iter()) {
+                match (key.as_ref(), value) {
+                    ("GT", Some(value)) => entry.gt = genotype_string(value),
+                    ("CN", Some(sample::Value::Integer(cn))) => entry.cn = Some(*cn),
+                    ("BC", Some(sample::Value::Integer(bc))) => entry.pc = Some(*bc),
+                    ("BC", Some(sample::Value::Float(bc))) => entry.pc = Some(bc.round() as i32),
+                    ("QS", Some(sample::Value::Integer(qs))) => entry.gq = Some(*qs),
+                    ("QS", Some(sample::Value::Float(qs))) => entry.gq = Some(qs.round() as i32),
+                    ("FT", Some(sample::Value::String(ft))) => {
+                        entry.ft = Some(ft.split(';').map(str::to_string).collect())
+                    }
+                    _ => (),
+                }
+            }
+            entries.push(entry);
+        }
+
+        tsv_record.genotype.entries = entries;
+        count_carriers(tsv_record, pedigree);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::{SvCaller, SvType, VarFishStrucvarTsvRecord};
+    use mehari::ped::PedigreeByName;
+
+    use super::Caller;
+
+    /// Convert all records of the VCF file at `path` with the guessed caller.
+    async fn convert(path: &str) -> Result<Vec<VarFishStrucvarTsvRecord>, anyhow::Error> {
+        use futures::TryStreamExt as _;
+        use mehari::common::noodles::NoodlesVariantReader as _;
+
+        let pedigree = PedigreeByName::from_path(path.replace(".vcf", ".ped"))?;
+        let caller = super::guess_caller(path).await?;
+        let mut reader = crate::common::noodles::open_vcf_reader(path).await?;
+        let header = reader.read_header().await?;
+        let samples = header
+            .sample_names()
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let converter = caller.converter(&samples);
+
+        let mut result = Vec::new();
+        let mut records = reader.records(&header).await;
+        while let Some(record) = records.try_next().await? {
+            result.push(converter.convert(
+                &pedigree,
+                &record,
+                uuid::Uuid::nil(),
+                mehari::common::GenomeRelease::Grch37,
+            )?);
+        }
+        Ok(result)
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "tests/strucvars/ingest/cnvnator-min.vcf",
+        Caller::Cnvnator { version: "unknown".into() }
+    )]
+    #[case(
+        "tests/strucvars/ingest/canvas-min.vcf",
+        Caller::Canvas { version: "1.40.0.1613+master".into() }
+    )]
+    #[tokio::test]
+    async fn guess_caller(
+        #[case] path: &str,
+        #[case] expected: Caller,
+    ) -> Result<(), anyhow::Error> {
+        assert_eq!(super::guess_caller(path).await?, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn guess_caller_mehari() -> Result<(), anyhow::Error> {
+        assert!(matches!(
+            super::guess_caller("tests/strucvars/ingest/gcnv-min.vcf").await?,
+            Caller::Mehari(SvCaller::Gcnv { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn guess_caller_header_only() -> Result<(), anyhow::Error> {
+        assert_eq!(
+            super::guess_caller("tests/strucvars/ingest/gcnv-empty.vcf").await?,
+            Caller::Mehari(SvCaller::Gcnv {
+                version: "unknown".into()
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn convert_cnvnator() -> Result<(), anyhow::Error> {
+        let records = convert("tests/strucvars/ingest/cnvnator-min.vcf").await?;
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.sv_type, r.start, r.end))
+                .collect::<Vec<_>>(),
+            vec![
+                (SvType::Del, 1_000_001, 1_010_000),
+                (SvType::Dup, 2_000_001, 2_020_000),
+                (SvType::Del, 3_000_001, 3_005_000),
+            ]
+        );
+        assert_eq!(records[0].callers, vec![String::from("CNVNATORvunknown")]);
+        let entry = &records[0].genotype.entries[0];
+        assert_eq!(entry.gt.as_deref(), Some("0/1"));
+        assert_eq!(entry.cn, Some(1));
+        assert_eq!(entry.anc, Some(0.49));
+        assert_eq!(records[0].num_het, 1);
+        assert_eq!(records[1].genotype.entries[0].cn, Some(3));
+        assert_eq!(records[1].num_hom_alt, 1);
+        // male sample on chrX
+        assert_eq!(records[2].num_hemi_alt, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn convert_canvas() -> Result<(), anyhow::Error> {
+        let records = convert("tests/strucvars/ingest/canvas-min.vcf").await?;
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.sv_type, r.start, r.end))
+                .collect::<Vec<_>>(),
+            vec![
+                (SvType::Del, 1_000_000, 1_010_000),
+                (SvType::Dup, 2_000_000, 2_020_000),
+            ]
+        );
+        assert_eq!(
+            records[0].callers,
+            vec![String::from("CANVASv1.40.0.1613+master")]
+        );
+        assert_eq!(
+            (records[0].start_ci_left, records[0].start_ci_right),
+            (-100, 100)
+        );
+        assert_eq!(
+            (records[0].end_ci_left, records[0].end_ci_right),
+            (-150, 150)
+        );
+        let entry = &records[0].genotype.entries[0];
+        assert_eq!(entry.gt.as_deref(), Some("0/1"));
+        assert_eq!(entry.cn, Some(1));
+        assert_eq!(entry.pc, Some(20));
+        assert_eq!(entry.gq, Some(33));
+        assert_eq!(entry.ft, Some(vec![String::from("PASS")]));
+        assert_eq!(records[1].genotype.entries[0].cn, Some(3));
+        assert_eq!(records[1].num_het, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,716 @@
+//! Reconciliation of copy numbers for CNV records merged from several callers.
+//!
+//! When records of more than one caller are clustered, the merged record keeps the copy
+//! numbers of its first record.  The reconciliation collects the copy number of each
+//! caller and computes a consensus copy number per sample.  The consensus is re-estimated
+//! from the maelstrom coverage VCF file of the sample, if any, and is the median of the
+//! callers' copy numbers otherwise.
+//!
+//! Multi-allelic CNV records, e.g., from gCNV or Dragen with `<DEL>,<DUP>` alleles, are
+//! normalized to `<CNV>` records before conversion such that the copy number of each
+//! sample is kept and the genotype is derived from it.
+
+use std::collections::HashMap;
+
+use mehari::annotate::strucvars::{SvType, VarFishStrucvarTsvRecord};
+use mehari::ped::{PedigreeByName, Sex};
+use noodles::core::{Position, Region};
+use noodles::vcf;
+use noodles::vcf::variant::record::info::field::Value::Integer;
+use noodles::vcf::variant::record::samples::series;
+use noodles::vcf::variant::record_buf::samples::sample::{value::Array, Value};
+
+/// FORMAT key for the copy numbers of each caller.
+pub const CN_CALLERS: &str = "cn_callers";
+
+/// Return whether records of `sv_type` carry copy numbers.
+fn is_cnv(sv_type: SvType) -> bool {
+    matches!(sv_type, SvType::Del | SvType::Dup | SvType::Cnv)
+}
+
+/// Compute the reciprocal overlap of the 1-based, closed intervals.
+pub(super) fn reciprocal_overlap(start1: i32, end1: i32, start2: i32, end2: i32) -> f32 {
+    let overlap = std::cmp::min(end1, end2) - std::cmp::max(start1, start2) + 1;
+    if overlap <= 0 {
+        0.0
+    } else {
+        let len1 = (end1 - start1 + 1) as f32;
+        let len2 = (end2 - start2 + 1) as f32;
+        (overlap as f32 / len1).min(overlap as f32 / len2)
+    }
+}
+
+/// Copy numbers of one input record before clustering.
+#[derive(Debug, Clone, PartialEq)]
+struct CallerCopyNumbers {
+    /// The caller as written to INFO/callers by `mehari`.
+    caller: String,
+    /// The type of the SV.
+    sv_type: SvType,
+    /// 1-based start position.
+    start: i32,
+    /// 1-based end position.
+    end: i32,
+    /// Copy number by sample, in the order of the genotype entries.
+    cns: Vec<Option<i32>>,
+}
+
+/// Copy numbers of the input records by chromosome.
+#[derive(Debug, Default)]
+pub struct CopyNumberStore {
+    /// The input records with copy numbers by chromosome.
+    by_chrom: HashMap<String, Vec<CallerCopyNumbers>>,
+}
+
+impl CopyNumberStore {
+    /// Register the copy numbers of the input `record`, if it is a CNV with any.
+    pub fn insert(&mut self, record: &VarFishStrucvarTsvRecord) {
+        let Some(caller) = record.callers.first() else {
+            return;
+        };
+        if !is_cnv(record.sv_type) || record.genotype.entries.iter().all(|e| e.cn.is_none()) {
+            return;
+        }
+        self.by_chrom
+            .entry(record.chromosome.clone())
+            .or_default()
+            .push(CallerCopyNumbers {
+                caller: caller.clone(),
+                sv_type: record.sv_type,
+                start: record.start,
+                end: record.end,
+                cns: record.genotype.entries.iter().map(|e| e.cn).collect(),
+            });
+    }
+
+    /// Return the copy numbers for each caller of the merged `record`.
+    ///
+    /// The clustered input records are approximated by the records of the same type
+    /// with a reciprocal overlap of at least `min_overlap`; for each caller, the record
+    /// with the largest overlap is used.
+    fn by_caller(
+        &self,
+        record: &VarFishStrucvarTsvRecord,
+        min_overlap: f32,
+    ) -> Vec<Option<&CallerCopyNumbers>> {
+        let candidates = self
+            .by_chrom
+            .get(&record.chromosome)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        record
+            .callers
+            .iter()
+            .map(|caller| {
+                candidates
+                    .iter()
+                    .filter(|c| c.caller == *caller && c.sv_type == record.sv_type)
+                    .map(|c| {
+                        (
+                            reciprocal_overlap(c.start, c.end, record.start, record.end),
+                            c,
+                        )
+                    })
+                    .filter(|(overlap, _)| *overlap >= min_overlap)
+                    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .map(|(_, c)| c)
+            })
+            .collect()
+    }
+}
+
+/// A window of a maelstrom coverage VCF file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Window {
+    /// 1-based start position.
+    start: usize,
+    /// 1-based end position.
+    end: usize,
+    /// Mean coverage in the window.
+    coverage: f64,
+    /// Mean mapping quality in the window, if given.
+    mapq: Option<f64>,
+}
+
+/// Compute the mean of `value` over `start..=end` from `windows`.
+///
+/// Windows that are only partially covered contribute by the covered fraction, windows
+/// without a value are skipped.
+fn weighted_mean(
+    windows: &[Window],
+    start: usize,
+    end: usize,
+    value: impl Fn(&Window) -> Option<f64>,
+) -> Option<f64> {
+    let mut sum = 0f64;
+    let mut count = 0f64;
+    for window in windows {
+        let covered =
+            std::cmp::min(end, window.end) as f64 - std::cmp::max(start, window.start) as f64 + 1.0;
+        let Some(value) = value(window).filter(|_| covered > 0.0) else {
+            continue;
+        };
+        let factor = covered / (window.end - window.start + 1) as f64;
+        sum += factor * value;
+        count += factor;
+    }
+    (count > 0.0).then(|| sum / count)
+}
+
+/// Compute the mean coverage over `start..=end` from `windows`.
+fn mean_coverage(windows: &[Window], start: usize, end: usize) -> Option<f64> {
+    weighted_mean(windows, start, end, |window| Some(window.coverage))
+}
+
+/// Coverage statistics of one sample over a region.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionCoverage {
+    /// Mean coverage over the region.
+    pub mean: f64,
+    /// Mean coverage relative to the mean autosomal coverage, if known.
+    pub relative: Option<f64>,
+    /// Mean mapping quality over the region, if given in the coverage file.
+    pub mapq: Option<f64>,
+}
+
+/// Coverage track of one sample from a maelstrom coverage VCF file.
+pub struct CoverageTrack {
+    /// The name of the single sample in the file.
+    pub sample: String,
+    /// The reader of the indexed file.
+    reader: vcf::io::IndexedReader<noodles::bgzf::Reader<std::fs::File>>,
+    /// The header of the file.
+    header: vcf::Header,
+    /// The mean coverage over the autosomes, if any.
+    baseline: Option<f64>,
+}
+
+impl std::fmt::Debug for CoverageTrack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoverageTrack")
+            .field("sample", &self.sample)
+            .field("baseline", &self.baseline)
+            .finish()
+    }
+}
+
+impl CoverageTrack {
+    /// Open the tabix-indexed coverage VCF file at `path` and compute the baseline.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        let mut reader = vcf::io::indexed_reader::Builder::default()
+            .build_from_path(path)
+            .map_err(|e| anyhow::anyhow!("could not open coverage file {}: {}", path, e))?;
+        let header = reader.read_header()?;
+        let sample = match header.sample_names().iter().collect::<Vec<_>>().as_slice() {
+            [sample] => sample.to_string(),
+            _ => anyhow::bail!("coverage file {} must contain exactly one sample", path),
+        };
+
+        let mut result = Self {
+            sample,
+            reader,
+            header,
+            baseline: None,
+        };
+        result.baseline = result.compute_baseline()?;
+        tracing::debug!(
+            "baseline coverage of {} is {:?}",
+            &result.sample,
+            result.baseline
+        );
+        Ok(result)
+    }
+
+    /// Compute the mean coverage over the autosomes.
+    fn compute_baseline(&mut self) -> Result<Option<f64>, anyhow::Error> {
+        let autosomes = self
+            .header
+            .contigs()
+            .keys()
+            .filter(|name| {
+                annonars::common::cli::canonicalize(name)
+                    .parse::<u32>()
+                    .is_ok_and(|no| (1..=22).contains(&no))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut windows = Vec::new();
+        for name in autosomes {
+            windows.extend(self.windows(&Region::new(name, ..))?);
+        }
+        Ok(mean_coverage(&windows, 1, usize::MAX - 1))
+    }
+
+    /// Read the windows overlapping `region`.
+    fn windows(&mut self, region: &Region) -> Result<Vec<Window>, anyhow::Error> {
+        let header = &self.header;
+        let mut result = Vec::new();
+        for record in self.reader.query(header, region)? {
+            let record = record?;
+            let end =
+                if let Some(Some(Integer(end))) = record.info().get(header, "END").transpose()? {
+                    end as usize
+                } else {
+                    anyhow::bail!("missing INFO/END in coverage record");
+                };
+            let start = record
+                .variant_start()
+                .ok_or_else(|| anyhow::anyhow!("missing position in coverage record"))??
+                .get();
+            let sample = record
+                .samples()
+                .iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("missing sample in coverage record"))?;
+            let mut coverage = None;
+            let mut mapq = None;
+            for (key, value) in sample.iter(header).flatten() {
+                match (key, value) {
+                    ("CV", Some(series::Value::Float(value))) => coverage = Some(value as f64),
+                    ("MQ", Some(series::Value::Float(value))) => mapq = Some(value as f64),
+                    ("MQ", Some(series::Value::Integer(value))) => mapq = Some(value as f64),
+                    _ => (),
+                }
+            }
+            if let Some(coverage) = coverage {
+                result.push(Window {
+                    start,
+                    end,
+                    coverage,
+                    mapq,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Compute the coverage statistics on `chrom` in `start..=end`.
+    pub fn region_coverage(
+        &mut self,
+        chrom: &str,
+        start: i32,
+        end: i32,
+    ) -> Result<Option<RegionCoverage>, anyhow::Error> {
+        let start = std::cmp::max(start, 1) as usize;
+        let end = std::cmp::max(end, start as i32) as usize;
+        let region = Region::new(chrom, Position::try_from(start)?..=Position::try_from(end)?);
+        let windows = self.windows(&region)?;
+        let baseline = self.baseline.filter(|baseline| *baseline > 0.0);
+        Ok(
+            mean_coverage(&windows, start, end).map(|mean| RegionCoverage {
+                mean,
+                relative: baseline.map(|baseline| mean / baseline),
+                mapq: weighted_mean(&windows, start, end, |window| window.mapq),
+            }),
+        )
+    }
+
+    /// Estimate the copy number on `chrom` in `start..=end` given the `ploidy`.
+    pub fn estimate_cn(
+        &mut self,
+        chrom: &str,
+        start: i32,
+        end: i32,
+        ploidy: i32,
+    ) -> Result<Option<i32>, anyhow::Error> {
+        Ok(self
+            .region_coverage(chrom, start, end)?
+            .and_then(|coverage| coverage.relative)
+            .map(|relative| (ploidy as f64 * relative).round() as i32))
+    }
+}
+
+/// Return the expected copy number on `chrom` for `sex`, if known.
+fn ploidy(chrom: &str, sex: Sex) -> Option<i32> {
+    match (annonars::common::cli::canonicalize(chrom).as_str(), sex) {
+        ("X", Sex::Male) | ("Y", Sex::Male) => Some(1),
+        ("X", Sex::Female) => Some(2),
+        ("X", _) | ("Y", _) | ("MT", _) => None,
+        _ => Some(2),
+    }
+}
+
+/// Return whether `record` is a multi-allelic CNV with several copy number alleles.
+fn is_multi_allelic_cnv(record: &vcf::variant::RecordBuf) -> bool {
+    let alts = record.alternate_bases().as_ref();
+    alts.len() > 1
+        && alts.iter().all(|alt| {
+            let sv_type = alt
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .split(':')
+                .next()
+                .unwrap_or_default();
+            matches!(sv_type, "DEL" | "DUP" | "CNV")
+        })
+}
+
+/// Derive the `GT` value of a multi-allelic CNV call from `cn` and the `ploidy`.
+///
+/// Calls deviating by one copy from the ploidy are het., larger deviations hom. alt.
+fn cn_genotype(cn: Option<i32>, ploidy: Option<i32>) -> &'static str {
+    match (cn, ploidy) {
+        (Some(1), Some(1)) => "0",
+        (Some(_), Some(1)) => "1",
+        (Some(cn), Some(ploidy)) => match (cn - ploidy).abs() {
+            0 => "0/0",
+            1 => "0/1",
+            _ => "1/1",
+        },
+        (None, Some(1)) => ".",
+        _ => "./.",
+    }
+}
+
+/// Normalize the multi-allelic CNV `record` with the given `samples` in place.
+///
+/// The ALT alleles are replaced by `<CNV>`, `INFO/SVTYPE` is set to `CNV`, and the
+/// `GT` of each sample is derived from its `FORMAT/CN` and the ploidy given the sex in
+/// `pedigree`.  Returns whether `record` was normalized, which requires `FORMAT/CN`.
+pub fn normalize_multi_allelic(
+    record: &mut vcf::variant::RecordBuf,
+    samples: &[String],
+    pedigree: &PedigreeByName,
+) -> bool {
+    use vcf::variant::record::samples::keys::key;
+
+    if !is_multi_allelic_cnv(record) || !record.samples().keys().as_ref().contains("CN") {
+        return false;
+    }
+    let (mut keys, mut values): (_, Vec<Vec<Option<Value>>>) =
+        std::mem::take(record.samples_mut()).into();
+    if !keys.as_ref().contains(key::GENOTYPE) {
+        keys.as_mut().shift_insert(0, key::GENOTYPE.to_string());
+        values.iter_mut().for_each(|values| values.insert(0, None));
+    }
+    let gt_idx = keys.as_ref().get_index_of(key::GENOTYPE).expect("inserted");
+    let cn_idx = keys.as_ref().get_index_of("CN").expect("checked above");
+    let chrom = record.reference_sequence_name().to_string();
+    for (values, sample) in values.iter_mut().zip(samples) {
+        values.resize(keys.as_ref().len(), None);
+        let cn = match values[cn_idx] {
+            Some(Value::Integer(cn)) => Some(cn),
+            _ => None,
+        };
+        let sex = pedigree
+            .individuals
+            .get(sample)
+            .map(|individual| individual.sex)
+            .unwrap_or_default();
+        values[gt_idx] = Some(Value::String(
+            cn_genotype(cn, ploidy(&chrom, sex)).to_string(),
+        ));
+    }
+    *record.samples_mut() = vcf::variant::record_buf::Samples::new(keys, values);
+
+    *record.alternate_bases_mut() =
+        vcf::variant::record_buf::AlternateBases::from(vec!["<CNV>".to_string()]);
+    record.info_mut().insert(
+        vcf::variant::record::info::field::key::SV_TYPE.to_string(),
+        Some(vcf::variant::record_buf::info::field::Value::String(
+            "CNV".to_string(),
+        )),
+    );
+    true
+}
+
+/// Return the lower median of the given copy numbers, if any.
+fn median(cns: &[i32]) -> Option<i32> {
+    let mut cns = cns.to_vec();
+    cns.sort_unstable();
+    (!cns.is_empty()).then(|| cns[(cns.len() - 1) / 2])
+}
+
+/// Reconcile the copy numbers of merged CNV records.
+#[derive(Debug)]
+pub struct Reconciler {
+    /// The copy numbers of the input records.
+    pub store: CopyNumberStore,
+    /// The coverage tracks by sample name.
+    tracks: HashMap<String, CoverageTrack>,
+    /// The sex of each sample by name.
+    sexes: HashMap<String, Sex>,
+    /// Minimal reciprocal overlap used for clustering.
+    min_overlap: f32,
+}
+
+impl Reconciler {
+    /// Construct with the given coverage `tracks`.
+    pub fn new(pedigree: &PedigreeByName, tracks: Vec<CoverageTrack>, min_overlap: f32) -> Self {
+        Self {
+            store: Default::default(),
+            tracks: tracks
+                .into_iter()
+                .map(|track| (track.sample.clone(), track))
+                .collect(),
+            sexes: pedigree
+                .individuals
+                .iter()
+                .map(|(name, individual)| (name.clone(), individual.sex))
+                .collect(),
+            min_overlap,
+        }
+    }
+
+    /// Reconcile the copy numbers of the merged `record`.
+    ///
+    /// For CNV records from more than one caller, the consensus copy number is written
+    /// to the genotype entries and the copy numbers of each caller are returned per
+    /// sample, in the order of the callers of `record`.
+    pub fn reconcile(
+        &mut self,
+        record: &mut VarFishStrucvarTsvRecord,
+    ) -> Result<Option<Vec<Vec<Option<i32>>>>, anyhow::Error> {
+        if !is_cnv(record.sv_type) || record.callers.len() < 2 {
+            return Ok(None);
+        }
+        let by_caller = self.store.by_caller(record, self.min_overlap);
+        if by_caller.iter().all(Option::is_none) && self.tracks.is_empty() {
+            return Ok(None);
+        }
+
+        let mut result = Vec::new();
+        for (idx, entry) in record.genotype.entries.iter_mut().enumerate() {
+            let caller_cns = by_caller
+                .iter()
+                .map(|c| c.and_then(|c| c.cns.get(idx).copied().flatten()))
+                .collect::<Vec<_>>();
+            let sex = self.sexes.get(&entry.name).copied().unwrap_or_default();
+            let coverage_cn = match (
+                self.tracks.get_mut(&entry.name),
+                ploidy(&record.chromosome, sex),
+            ) {
+                (Some(track), Some(ploidy)) => {
+                    track.estimate_cn(&record.chromosome, record.start, record.end, ploidy)?
+                }
+                _ => None,
+            };
+            let consensus = coverage_cn
+                .or_else(|| median(&caller_cns.iter().flatten().copied().collect::<Vec<_>>()));
+            if consensus.is_some() {
+                entry.cn = consensus;
+            }
+            result.push(caller_cns);
+        }
+        Ok(Some(result))
+    }
+}
+
+/// Add the copy numbers of each caller per sample as `FORMAT/cn_callers` to `record`.
+pub fn add_caller_cns(record: &mut vcf::variant::RecordBuf, caller_cns: Vec<Vec<Option<i32>>>) {
+    let (mut keys, mut values): (_, Vec<Vec<Option<Value>>>) =
+        std::mem::take(record.samples_mut()).into();
+    keys.as_mut().insert(CN_CALLERS.to_string());
+    for (values, cns) in values.iter_mut().zip(caller_cns) {
+        values.push(Some(Value::Array(Array::Integer(cns))));
+    }
+    *record.samples_mut() = vcf::variant::record_buf::Samples::new(keys, values);
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::{
+        GenotypeCalls, GenotypeInfo, SvType, VarFishStrucvarTsvRecord,
+    };
+    use mehari::ped::Sex;
+
+    use super::{Reconciler, Window};
+
+    fn record(caller: &str, start: i32, end: i32, cns: &[Option<i32>]) -> VarFishStrucvarTsvRecord {
+        VarFishStrucvarTsvRecord {
+            chromosome: "1".into(),
+            start,
+            end,
+            callers: vec![caller.into()],
+            sv_type: SvType::Del,
+            genotype: GenotypeCalls {
+                entries: cns
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, cn)| GenotypeInfo {
+                        name: format!("sample{}", idx),
+                        cn: *cn,
+                        ..Default::default()
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(1, 100, 1, 100, 1.0)]
+    #[case(1, 100, 51, 150, 0.5)]
+    #[case(1, 100, 1, 200, 0.5)]
+    #[case(1, 100, 101, 200, 0.0)]
+    fn reciprocal_overlap(
+        #[case] start1: i32,
+        #[case] end1: i32,
+        #[case] start2: i32,
+        #[case] end2: i32,
+        #[case] expected: f32,
+    ) {
+        assert_eq!(
+            super::reciprocal_overlap(start1, end1, start2, end2),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("1", Sex::Male, Some(2))]
+    #[case("chrX", Sex::Male, Some(1))]
+    #[case("X", Sex::Female, Some(2))]
+    #[case("X", Sex::Unknown, None)]
+    #[case("Y", Sex::Female, None)]
+    #[case("MT", Sex::Female, None)]
+    fn ploidy(#[case] chrom: &str, #[case] sex: Sex, #[case] expected: Option<i32>) {
+        assert_eq!(super::ploidy(chrom, sex), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(&[], None)]
+    #[case(&[1], Some(1))]
+    #[case(&[3, 1], Some(1))]
+    #[case(&[1, 0, 1], Some(1))]
+    fn median(#[case] cns: &[i32], #[case] expected: Option<i32>) {
+        assert_eq!(super::median(cns), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(Some(2), Some(2), "0/0")]
+    #[case(Some(1), Some(2), "0/1")]
+    #[case(Some(3), Some(2), "0/1")]
+    #[case(Some(0), Some(2), "1/1")]
+    #[case(Some(6), Some(2), "1/1")]
+    #[case(Some(1), Some(1), "0")]
+    #[case(Some(3), Some(1), "1")]
+    #[case(None, Some(1), ".")]
+    #[case(None, Some(2), "./.")]
+    #[case(Some(2), None, "./.")]
+    fn cn_genotype(#[case] cn: Option<i32>, #[case] ploidy: Option<i32>, #[case] expected: &str) {
+        assert_eq!(super::cn_genotype(cn, ploidy), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(&["<DEL>", "<DUP>"], true)]
+    #[case(&["<DEL>"], false)]
+    #[case(&["<DEL>", "<INV>"], false)]
+    fn normalize_multi_allelic(#[case] alts: &[&str], #[case] expected: bool) {
+        use noodles::core::Position;
+        use noodles::vcf::variant::record_buf::{
+            samples::{sample::Value, Keys},
+            AlternateBases, RecordBuf, Samples,
+        };
+
+        let keys: Keys = ["GT", "CN"].into_iter().map(String::from).collect();
+        let mut record = RecordBuf::builder()
+            .set_reference_sequence_name("1")
+            .set_variant_start(Position::try_from(1_000).unwrap())
+            .set_reference_bases("N")
+            .set_alternate_bases(AlternateBases::from(
+                alts.iter().map(|alt| alt.to_string()).collect::<Vec<_>>(),
+            ))
+            .set_samples(Samples::new(
+                keys,
+                vec![
+                    vec![Some(Value::String("1/2".into())), Some(Value::Integer(5))],
+                    vec![Some(Value::String("0/1".into())), Some(Value::Integer(1))],
+                    vec![Some(Value::String("./.".into()))],
+                ],
+            ))
+            .build();
+        let samples = ["index", "father", "mother"].map(String::from);
+
+        assert_eq!(
+            super::normalize_multi_allelic(&mut record, &samples, &Default::default()),
+            expected
+        );
+        if expected {
+            assert_eq!(record.alternate_bases().as_ref(), &["<CNV>".to_string()]);
+            let gts = record
+                .samples()
+                .values()
+                .map(|sample| match sample.values().first() {
+                    Some(Some(Value::String(gt))) => gt.clone(),
+                    value => panic!("unexpected GT {:?}", value),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(gts, vec!["1/1", "0/1", "./."]);
+        }
+    }
+
+    #[test]
+    fn mean_coverage() {
+        let windows = vec![
+            Window {
+                start: 1,
+                end: 100,
+                coverage: 10.0,
+                mapq: Some(60.0),
+            },
+            Window {
+                start: 101,
+                end: 200,
+                coverage: 20.0,
+                mapq: None,
+            },
+        ];
+
+        assert_eq!(super::mean_coverage(&windows, 1, 200), Some(15.0));
+        assert_eq!(super::mean_coverage(&windows, 51, 150), Some(15.0));
+        assert_eq!(super::mean_coverage(&windows, 101, 200), Some(20.0));
+        assert_eq!(super::mean_coverage(&windows, 201, 300), None);
+        assert_eq!(
+            super::weighted_mean(&windows, 51, 150, |window| window.mapq),
+            Some(60.0)
+        );
+    }
+
+    #[test]
+    fn reconcile_without_coverage() -> Result<(), anyhow::Error> {
+        let mut reconciler = Reconciler::new(&Default::default(), vec![], 0.8);
+        reconciler
+            .store
+            .insert(&record("DELLYv1.1.3", 1_000, 2_000, &[Some(1), Some(2)]));
+        reconciler
+            .store
+            .insert(&record("GATK_GCNVv4.2", 1_050, 2_000, &[Some(0), None]));
+        reconciler
+            .store
+            .insert(&record("GATK_GCNVv4.2", 5_000, 6_000, &[Some(1), None]));
+
+        let mut merged = record("DELLYv1.1.3", 1_000, 2_000, &[Some(1), Some(2)]);
+        merged.callers.push("GATK_GCNVv4.2".into());
+        let caller_cns = reconciler.reconcile(&mut merged)?;
+
+        assert_eq!(
+            caller_cns,
+            Some(vec![vec![Some(1), Some(0)], vec![Some(2), None]])
+        );
+        assert_eq!(
+            merged
+                .genotype
+                .entries
+                .iter()
+                .map(|e| e.cn)
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(2)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reconcile_single_caller() -> Result<(), anyhow::Error> {
+        let mut reconciler = Reconciler::new(&Default::default(), vec![], 0.8);
+        let input = record("DELLYv1.1.3", 1_000, 2_000, &[Some(1)]);
+        reconciler.store.insert(&input);
+
+        let mut merged = input.clone();
+
+        assert_eq!(reconciler.reconcile(&mut merged)?, None);
+        assert_eq!(merged, input);
+
+        Ok(())
+    }
+}
@@ -3,35 +3,46 @@ use std::collections::HashSet;
 use noodles::vcf;
 use vcf::header::{record::value::map::AlternativeAllele, SampleNames};
 
+use mehari::annotate::strucvars::SvCaller;
+
 use crate::common::{add_contigs_37, add_contigs_38, GenomeRelease};
 
+use super::callers::Caller;
+
 /// Return token for caller name.
-fn caller_name(sv_caller: &mehari::annotate::strucvars::SvCaller) -> &'static str {
+pub fn caller_name(sv_caller: &Caller) -> &'static str {
     match sv_caller {
-        mehari::annotate::strucvars::SvCaller::Delly { .. } => "Delly",
-        mehari::annotate::strucvars::SvCaller::DragenSv { .. } => "DragenSv",
-        mehari::annotate::strucvars::SvCaller::DragenCnv { .. } => "DragenCnv",
-        mehari::annotate::strucvars::SvCaller::Gcnv { .. } => "Gcnv",
-        mehari::annotate::strucvars::SvCaller::Manta { .. } => "Manta",
-        mehari::annotate::strucvars::SvCaller::Melt { .. } => "Melt",
-        mehari::annotate::strucvars::SvCaller::Popdel { .. } => "Popdel",
-        mehari::annotate::strucvars::SvCaller::ClinCnv { .. } => "ClinCnv",
-        mehari::annotate::strucvars::SvCaller::Sniffles2 { .. } => "Sniffles2",
+        Caller::Mehari(sv_caller) => match sv_caller {
+            SvCaller::Delly { .. } => "Delly",
+            SvCaller::DragenSv { .. } => "DragenSv",
+            SvCaller::DragenCnv { .. } => "DragenCnv",
+            SvCaller::Gcnv { .. } => "Gcnv",
+            SvCaller::Manta { .. } => "Manta",
+            SvCaller::Melt { .. } => "Melt",
+            SvCaller::Popdel { .. } => "Popdel",
+            SvCaller::ClinCnv { .. } => "ClinCnv",
+            SvCaller::Sniffles2 { .. } => "Sniffles2",
+        },
+        Caller::Cnvnator { .. } => "Cnvnator",
+        Caller::Canvas { .. } => "Canvas",
     }
 }
 
 /// Return caller version.
-fn caller_version(sv_caller: &mehari::annotate::strucvars::SvCaller) -> String {
+fn caller_version(sv_caller: &Caller) -> String {
     match sv_caller {
-        mehari::annotate::strucvars::SvCaller::Delly { version }
-        | mehari::annotate::strucvars::SvCaller::DragenSv { version }
-        | mehari::annotate::strucvars::SvCaller::DragenCnv { version }
-        | mehari::annotate::strucvars::SvCaller::Gcnv { version }
-        | mehari::annotate::strucvars::SvCaller::Manta { version }
-        | mehari::annotate::strucvars::SvCaller::Melt { version }
-        | mehari::annotate::strucvars::SvCaller::Popdel { version }
-        | mehari::annotate::strucvars::SvCaller::ClinCnv { version }
-        | mehari::annotate::strucvars::SvCaller::Sniffles2 { version } => version.clone(),
+        Caller::Mehari(sv_caller) => match sv_caller {
+            SvCaller::Delly { version }
+            | SvCaller::DragenSv { version }
+            | SvCaller::DragenCnv { version }
+            | SvCaller::Gcnv { version }
+            | SvCaller::Manta { version }
+            | SvCaller::Melt { version }
+            | SvCaller::Popdel { version }
+            | SvCaller::ClinCnv { version }
+            | SvCaller::Sniffles2 { version } => version.clone(),
+        },
+        Caller::Cnvnator { version } | Caller::Canvas { version } => version.clone(),
     }
 }
 
@@ -39,7 +50,7 @@ fn caller_version(sv_caller: &mehari::annotate::strucvars::SvCaller) -> String {
 #[allow(clippy::too_many_arguments)]
 pub fn build_output_header(
     input_sample_names: &SampleNames,
-    input_sv_callers: &[&mehari::annotate::strucvars::SvCaller],
+    input_sv_callers: &[&Caller],
     id_mapping: Option<&indexmap::IndexMap<String, String>>,
     pedigree: Option<&mehari::ped::PedigreeByName>,
     genomebuild: GenomeRelease,
@@ -65,6 +76,14 @@ pub fn build_output_header(
         .add_info(key::SV_TYPE, Map::<Info>::from(key::SV_TYPE))
         .add_info(key::SV_LENGTHS, Map::<Info>::from(key::SV_LENGTHS))
         .add_info(key::SV_CLAIM, Map::<Info>::from(key::SV_CLAIM))
+        .add_info(
+            key::POSITION_CONFIDENCE_INTERVALS,
+            Map::<Info>::from(key::POSITION_CONFIDENCE_INTERVALS),
+        )
+        .add_info(
+            key::END_CONFIDENCE_INTERVALS,
+            Map::<Info>::from(key::END_CONFIDENCE_INTERVALS),
+        )
         .add_info(
             "callers",
             Map::<Info>::new(
@@ -99,6 +118,10 @@ pub fn build_output_header(
             vcf::variant::record::samples::keys::key::GENOTYPE,
             Map::<Format>::from(vcf::variant::record::samples::keys::key::GENOTYPE),
         )
+        .add_format(
+            vcf::variant::record::samples::keys::key::FILTER,
+            Map::<Format>::from(vcf::variant::record::samples::keys::key::FILTER),
+        )
         .add_format(
             "pec",
             Map::<Format>::new(
@@ -147,6 +170,14 @@ pub fn build_output_header(
                 "Copy number of the variant in the sample",
             ),
         )
+        .add_format(
+            "cn_callers",
+            Map::<Format>::new(
+                noodles::vcf::header::record::value::map::format::Number::Unknown,
+                format::Type::Integer,
+                "Copy numbers of the merged callers in the order of INFO/callers",
+            ),
+        )
         .add_format(
             "anc",
             Map::<Format>::new(
@@ -203,6 +234,13 @@ pub fn build_output_header(
             )
         }
 
+        let multi_family = pedigree
+            .individuals
+            .values()
+            .map(|i| &i.family)
+            .collect::<HashSet<_>>()
+            .len()
+            > 1;
         for name in input_sample_names {
             let name = if let Some(id_mapping) = id_mapping {
                 id_mapping.get(name).expect("checked earlier")
@@ -234,8 +272,11 @@ pub fn build_output_header(
                 ),
             )?;
 
-            // Add PEDIGREE entry.
+            // Add PEDIGREE entry, with family only for multi-family pedigrees.
             let mut map_builder = Map::<Other>::builder();
+            if multi_family {
+                map_builder = map_builder.insert("Family".parse()?, i.family.clone());
+            }
             if let Some(father) = i.father.as_ref() {
                 map_builder = map_builder.insert("Father".parse()?, father.clone());
             }
@@ -291,11 +332,83 @@ pub fn build_output_header(
     Ok(builder.build())
 }
 
+/// Return the namespaced version of a caller's `FILTER` value.
+///
+/// `PASS` is kept as is, all other values are prefixed with the caller name as
+/// `{caller}_{filter}` so values from different callers do not collide.
+pub fn namespaced_filter(sv_caller: &Caller, filter: &str) -> String {
+    if filter == "PASS" {
+        filter.to_string()
+    } else {
+        format!("{}_{}", caller_name(sv_caller), filter)
+    }
+}
+
+/// Add the namespaced `FILTER` header lines of the input files to `header`.
+pub fn add_caller_filters(
+    header: &mut vcf::Header,
+    input_headers: &[vcf::Header],
+    input_sv_callers: &[Caller],
+) {
+    for (input_header, sv_caller) in input_headers.iter().zip(input_sv_callers.iter()) {
+        for (id, filter) in input_header.filters() {
+            if id == "PASS" {
+                continue;
+            }
+            header
+                .filters_mut()
+                .insert(namespaced_filter(sv_caller, id), filter.clone());
+        }
+    }
+}
+
+/// Add the samples of each input file as `x-varfish-caller-samples` header lines.
+///
+/// The ID is the caller name, suffixed with `_{n}` if the same caller occurs in
+/// several input files.
+pub fn add_caller_samples(
+    header: &mut vcf::Header,
+    input_sample_names: &[indexmap::IndexSet<String>],
+    input_sv_callers: &[Caller],
+) -> Result<(), anyhow::Error> {
+    use vcf::header::record::value::{map::Other, Map};
+
+    let mut seen = std::collections::HashMap::<&str, usize>::new();
+    for (sample_names, sv_caller) in input_sample_names.iter().zip(input_sv_callers.iter()) {
+        let name = caller_name(sv_caller);
+        let count = seen.entry(name).or_default();
+        *count += 1;
+        let id = if *count == 1 {
+            name.to_string()
+        } else {
+            format!("{}_{}", name, count)
+        };
+        header.insert(
+            "x-varfish-caller-samples".parse()?,
+            vcf::header::record::Value::Map(
+                id,
+                Map::<Other>::builder()
+                    .insert("Caller".parse()?, name)
+                    .insert(
+                        "Samples".parse()?,
+                        sample_names.iter().cloned().collect::<Vec<_>>().join(";"),
+                    )
+                    .build()?,
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
+    use mehari::annotate::strucvars::SvCaller;
     use mehari::ped::PedigreeByName;
     use rstest::rstest;
 
+    use crate::strucvars::ingest::callers::Caller;
+
     #[rstest]
     #[case("tests/strucvars/ingest/delly2-min.vcf")]
     #[case("tests/strucvars/ingest/dragen-cnv-min.vcf")]
@@ -305,10 +418,10 @@ mod test {
     #[case("tests/strucvars/ingest/melt-min.vcf")]
     #[case("tests/strucvars/ingest/popdel-min.vcf")]
     #[case("tests/strucvars/ingest/sniffles2-min.vcf")]
+    #[case("tests/strucvars/ingest/cnvnator-min.vcf")]
+    #[case("tests/strucvars/ingest/canvas-min.vcf")]
     #[tokio::test]
     async fn build_output_header_37(#[case] path: &str) -> Result<(), anyhow::Error> {
-        use mehari::annotate::strucvars::guess_sv_caller;
-
         mehari::common::set_snapshot_suffix!("{}", path.split('/').last().unwrap());
         let tmpdir = temp_testdir::TempDir::default();
 
@@ -317,10 +430,7 @@ mod test {
         let input_vcf_header = noodles::vcf::io::reader::Builder::default()
             .build_from_path(path)?
             .read_header()?;
-        let sv_callers = {
-            let mut reader = mehari::common::noodles::open_variant_reader(path).await?;
-            vec![guess_sv_caller(&mut reader).await?]
-        };
+        let sv_callers = vec![crate::strucvars::ingest::callers::guess_caller(path).await?];
         let sv_caller_refs = sv_callers.iter().collect::<Vec<_>>();
         let output_vcf_header = super::build_output_header(
             input_vcf_header.sample_names(),
@@ -354,6 +464,8 @@ mod test {
     #[case("tests/strucvars/ingest/melt-min.vcf")]
     #[case("tests/strucvars/ingest/popdel-min.vcf")]
     #[case("tests/strucvars/ingest/sniffles2-min.vcf")]
+    #[case("tests/strucvars/ingest/cnvnator-min.vcf")]
+    #[case("tests/strucvars/ingest/canvas-min.vcf")]
     #[tokio::test]
     async fn build_output_header_38(#[case] path: &str) -> Result<(), anyhow::Error> {
         mehari::common::set_snapshot_suffix!("{}", path.split('/').last().unwrap());
@@ -364,10 +476,7 @@ mod test {
         let input_vcf_header = noodles::vcf::io::reader::Builder::default()
             .build_from_path(path)?
             .read_header()?;
-        let sv_callers = {
-            let mut reader = mehari::common::noodles::open_variant_reader(path).await?;
-            vec![mehari::annotate::strucvars::guess_sv_caller(&mut reader).await?]
-        };
+        let sv_callers = vec![crate::strucvars::ingest::callers::guess_caller(path).await?];
         let sv_caller_refs = sv_callers.iter().collect::<Vec<_>>();
         let output_vcf_header = super::build_output_header(
             input_vcf_header.sample_names(),
@@ -391,4 +500,44 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn namespaced_filter() {
+        let sv_caller = Caller::Mehari(SvCaller::Delly {
+            version: "1.1.3".into(),
+        });
+
+        assert_eq!(super::namespaced_filter(&sv_caller, "PASS"), "PASS");
+        assert_eq!(
+            super::namespaced_filter(&sv_caller, "LowQual"),
+            "Delly_LowQual"
+        );
+    }
+    #[test]
+    fn add_caller_samples() -> Result<(), anyhow::Error> {
+        let sv_callers = vec![
+            Caller::Mehari(SvCaller::Delly {
+                version: "1.1.3".into(),
+            }),
+            Caller::Mehari(SvCaller::Delly {
+                version: "1.1.3".into(),
+            }),
+        ];
+        let sample_names = vec![
+            ["index", "father"].iter().map(|s| s.to_string()).collect(),
+            ["index"].iter().map(|s| s.to_string()).collect(),
+        ];
+        let mut header = noodles::vcf::Header::default();
+
+        super::add_caller_samples(&mut header, &sample_names, &sv_callers)?;
+
+        let mut buf = Vec::new();
+        noodles::vcf::io::writer::Writer::new(&mut buf).write_header(&header)?;
+        let text = String::from_utf8(buf)?;
+        assert!(text
+            .contains("##x-varfish-caller-samples=<ID=Delly,Caller=Delly,Samples=index;father>"));
+        assert!(text.contains("##x-varfish-caller-samples=<ID=Delly_2,Caller=Delly,Samples=index>"));
+
+        Ok(())
+    }
 }
@@ -1,19 +1,28 @@
 //! Implementation of `strucvars ingest` subcommand.
 
+use crate::common::errors::{warn_or_fail, ClassifyExt as _, ErrorClass};
 use crate::common::noodles::open_vcf_readers;
 use crate::common::{self, worker_version, GenomeRelease};
 use crate::flush_and_shutdown;
 use futures::future::join_all;
+use futures::TryStreamExt as _;
+use mehari::annotate::seqvars::CHROM_TO_CHROM_NO;
 use mehari::annotate::strucvars::bnd::Breakend;
-use mehari::annotate::strucvars::guess_sv_caller;
 use mehari::common::noodles::{
     open_vcf_writer, AsyncVcfWriter, NoodlesVariantReader as _, VariantReader,
 };
 use noodles::vcf;
+use rand::RngCore as _;
 use rand_core::SeedableRng;
+use std::collections::HashMap;
+use std::io::Write as _;
 use tokio::io::AsyncWriteExt;
 
+mod callers;
+pub(crate) mod copy_number;
 pub mod header;
+mod quality;
+mod samples;
 
 /// Command line arguments for `strucvars ingest` subcommand.
 #[derive(Debug, clap::Parser)]
@@ -61,18 +70,193 @@ pub struct Args {
     /// Per-file identifier mapping, either a JSON or @-prefixed path to JSON.
     #[clap(long)]
     pub id_mapping: Option<String>,
+    /// Skip records that do not have `PASS` (or no value) in the `FILTER` column.
+    #[clap(long, default_value_t = false)]
+    pub only_pass: bool,
+    /// Only warn about `##contig` lengths of the input files that do not match
+    /// `--genomebuild` instead of failing.
+    #[clap(long, default_value_t = false)]
+    pub lenient: bool,
+    /// Path to JSON file with per-caller quality filters (minimal `QUAL`, minimal
+    /// paired-end/split-read support, accepted `FILTER` values) applied before
+    /// clustering; optional.  Callers without entry use the built-in defaults.
+    #[clap(long)]
+    pub caller_quality_config: Option<String>,
+}
+
+/// Counts of records skipped because of their `FILTER` value.
+#[derive(Debug, Default)]
+struct FilterSkipCounts {
+    /// Total number of skipped records.
+    pub total: usize,
+    /// Number of skipped records by (namespaced) filter value.
+    pub by_filter: indexmap::IndexMap<String, usize>,
+    /// Number of records skipped by the caller quality filters, by caller and reason.
+    pub by_quality: indexmap::IndexMap<String, usize>,
+}
+
+/// Convert the records in `reader` to JSONL files per contig in `tmp_dir`.
+///
+/// This mirrors `mehari::annotate::strucvars::run_vcf_to_jsonl()` but also
+/// considers the `FILTER` column.  The caller's `FILTER` and `FORMAT/FT`
+/// values are namespaced as `{caller}_{filter}` and the record's filters
+/// are stored in `record_filters` by SV UUID.  If `only_pass` is set then
+/// records that do not pass are skipped and counted in `skip_counts`.  The copy
+/// numbers of CNV records are registered in `copy_numbers`.  The genotype entries
+/// are aligned to `sample_names` and registered in `sample_calls` if given.  Multi-
+/// allelic CNV records are normalized to `<CNV>` records first.  Records failing the
+/// caller `quality` filters, if any, are skipped and counted in `skip_counts`.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all)]
+async fn vcf_to_jsonl(
+    pedigree: &mehari::ped::PedigreeByName,
+    reader: &mut VariantReader,
+    header: &vcf::Header,
+    sample_names: &indexmap::IndexSet<String>,
+    sv_caller: &callers::Caller,
+    tmp_dir: &tempfile::TempDir,
+    rng: &mut rand::rngs::StdRng,
+    only_pass: bool,
+    quality: Option<&quality::CallerQuality>,
+    record_filters: &mut HashMap<uuid::Uuid, Vec<String>>,
+    skip_counts: &mut FilterSkipCounts,
+    copy_numbers: &mut copy_number::CopyNumberStore,
+    mut sample_calls: Option<&mut samples::SampleCallStore>,
+) -> Result<(), anyhow::Error> {
+    let mut tmp_files = (1..=25)
+        .map(|i| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(tmp_dir.path().join(format!("chrom-{}.jsonl", i)))
+                .map(std::io::BufWriter::new)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("could not open temporary file: {}", e))?;
+
+    let samples = header
+        .sample_names()
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    let converter = sv_caller.converter(&samples);
+    let covered = sample_names
+        .iter()
+        .map(|name| header.sample_names().contains(name))
+        .collect::<Vec<_>>();
+
+    let mut uuid_buf = [0u8; 16];
+    let mut records = reader.records(header).await;
+    while let Some(mut record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading VCF record: {}", e))?
+    {
+        rng.fill_bytes(&mut uuid_buf);
+        let uuid = uuid::Uuid::from_bytes(uuid_buf);
+
+        if record.alternate_bases().as_ref().is_empty()
+            || record.alternate_bases().as_ref() == ["<*>".to_string()]
+        {
+            tracing::warn!("skipping REF-only / empty ALT record {:?}", record);
+            continue;
+        }
+        copy_number::normalize_multi_allelic(&mut record, &samples, pedigree);
+
+        let filters = record
+            .filters()
+            .as_ref()
+            .iter()
+            .map(|filter| header::namespaced_filter(sv_caller, filter))
+            .collect::<Vec<_>>();
+        if only_pass && filters.iter().any(|filter| filter != "PASS") {
+            skip_counts.total += 1;
+            for filter in filters.iter().filter(|filter| *filter != "PASS") {
+                *skip_counts.by_filter.entry(filter.clone()).or_default() += 1;
+            }
+            continue;
+        }
+        let mut skip_quality = |reason: &str| {
+            *skip_counts
+                .by_quality
+                .entry(format!("{} {}", header::caller_name(sv_caller), reason))
+                .or_default() += 1;
+        };
+        if let Some(quality) = quality {
+            if !quality.passes_qual(record.quality_score()) {
+                skip_quality("QUAL");
+                continue;
+            }
+            if !quality.passes_filters(record.filters().as_ref().iter().map(String::as_str)) {
+                skip_quality("FILTER");
+                continue;
+            }
+        }
+
+        let mut record = converter.convert(
+            pedigree,
+            &record,
+            uuid,
+            mehari::common::GenomeRelease::Grch37,
+        )?;
+        if quality.is_some_and(|quality| !quality.passes_pe_sr(&record)) {
+            skip_quality("PE/SR");
+            continue;
+        }
+        for entry in record.genotype.entries.iter_mut() {
+            if let Some(ft) = entry.ft.as_mut() {
+                *ft = ft
+                    .iter()
+                    .map(|filter| header::namespaced_filter(sv_caller, filter))
+                    .collect();
+            }
+        }
+        record.genotype.entries =
+            samples::align_entries(std::mem::take(&mut record.genotype.entries), sample_names);
+
+        if let Some(chromosome_no) = CHROM_TO_CHROM_NO.get(&record.chromosome) {
+            if !filters.is_empty() {
+                record_filters.insert(uuid, filters);
+            }
+            copy_numbers.insert(&record);
+            if let Some(sample_calls) = sample_calls.as_deref_mut() {
+                sample_calls.insert(&record, &covered);
+            }
+            let out_jsonl = &mut tmp_files[*chromosome_no as usize - 1];
+            serde_json::to_writer(&mut *out_jsonl, &record)
+                .map_err(|e| anyhow::anyhow!("could not write temporary record: {}", e))?;
+            out_jsonl.write_all(b"\n")?;
+        } else {
+            tracing::warn!(
+                "skipping record on chromosome {} (not in canonical set)",
+                record.chromosome
+            );
+        }
+    }
+
+    for tmp_file in tmp_files.iter_mut() {
+        tmp_file.flush()?;
+    }
+
+    Ok(())
 }
 
 async fn write_ingest_record(
     output_header: &vcf::Header,
     writer: &mut AsyncVcfWriter,
     input_record: &vcf::variant::RecordBuf,
+    filters: Option<&Vec<String>>,
 ) -> Result<(), anyhow::Error> {
-    // copy over CHROM, POS, REF
+    // copy over CHROM, POS, REF, and the (namespaced) FILTER values
     let builder = vcf::variant::record_buf::builder::Builder::default()
         .set_reference_sequence_name(input_record.reference_sequence_name())
         .set_variant_start(input_record.variant_start().expect("no variant_start?"))
-        .set_reference_bases(input_record.reference_bases());
+        .set_reference_bases(input_record.reference_bases())
+        .set_filters(
+            filters
+                .map(|filters| filters.iter().cloned().collect())
+                .unwrap_or_default(),
+        );
 
     // copy over first ALT allele, remove any SV sub types
     if input_record.alternate_bases().as_ref().len() != 1 {
@@ -124,7 +308,7 @@ async fn write_ingest_record(
         anyhow::bail!("unexpected alternate base type: {:?}", &alt_0)
     };
 
-    // copy over FORMAT tags, all except FT
+    // copy over FORMAT tags, FT is written as a single `;`-separated string
     let mut keys_with_value = std::collections::HashSet::<String>::new();
     let output_format_values = input_record
         .samples()
@@ -134,13 +318,22 @@ async fn write_ingest_record(
                 .as_ref()
                 .iter()
                 .zip(g.values().iter())
-                .filter(|(k, _)| k.as_str() != "FT")
                 .map(|(k, v)| {
                     if v.is_some() {
                         keys_with_value.insert(k.clone());
                     }
 
-                    v.clone()
+                    match (k.as_str(), v) {
+                        (
+                            "FT",
+                            Some(vcf::variant::record_buf::samples::sample::Value::Array(
+                                vcf::variant::record_buf::samples::sample::value::Array::String(ft),
+                            )),
+                        ) => Some(vcf::variant::record_buf::samples::sample::Value::String(
+                            ft.iter().flatten().cloned().collect::<Vec<_>>().join(";"),
+                        )),
+                        _ => v.clone(),
+                    }
                 })
                 .collect::<Vec<_>>()
         })
@@ -150,7 +343,6 @@ async fn write_ingest_record(
         .keys()
         .as_ref()
         .iter()
-        .filter(|k| k.as_str() != "FT")
         .cloned()
         .map(|k| {
             if k.as_str() == "CN" {
@@ -235,6 +427,20 @@ async fn write_ingest_record(
         }
     }
 
+    for key in [
+        vcf::variant::record::info::field::key::POSITION_CONFIDENCE_INTERVALS,
+        vcf::variant::record::info::field::key::END_CONFIDENCE_INTERVALS,
+    ] {
+        if let Some(Some(
+            value @ vcf::variant::record_buf::info::field::Value::Array(
+                vcf::variant::record_buf::info::field::value::Array::Integer(_),
+            ),
+        )) = input_record.info().get(key)
+        {
+            info.insert(key.to_string(), Some(value.clone()));
+        }
+    }
+
     fn map_caller(caller: &str) -> Result<Option<String>, anyhow::Error> {
         if caller.starts_with("DELLYv") {
             Ok(Some("Delly".to_string()))
@@ -252,6 +458,10 @@ async fn write_ingest_record(
             Ok(Some("Melt".to_string()))
         } else if caller.starts_with("SNIFFLESv") {
             Ok(Some("Sniffles".to_string()))
+        } else if caller.starts_with("CNVNATORv") {
+            Ok(Some("Cnvnator".to_string()))
+        } else if caller.starts_with("CANVASv") {
+            Ok(Some("Canvas".to_string()))
         } else {
             anyhow::bail!("unknown caller: {}", caller)
         }
@@ -296,14 +506,53 @@ async fn write_ingest_record(
         .map_err(|e| anyhow::anyhow!("Error writing VCF record: {}", e))
 }
 
+/// Convert the clustered `record` to VCF, keeping INFO/CIPOS and INFO/CIEND.
+///
+/// The conversion in `mehari` drops the confidence intervals, so they are added back
+/// here if they are non-trivial.
+fn to_vcf_record(
+    record: mehari::annotate::strucvars::VarFishStrucvarTsvRecord,
+) -> Result<vcf::variant::RecordBuf, anyhow::Error> {
+    let cis = [
+        (
+            vcf::variant::record::info::field::key::POSITION_CONFIDENCE_INTERVALS,
+            record.start_ci_left,
+            record.start_ci_right,
+        ),
+        (
+            vcf::variant::record::info::field::key::END_CONFIDENCE_INTERVALS,
+            record.end_ci_left,
+            record.end_ci_right,
+        ),
+    ];
+    let mut vcf_record: vcf::variant::RecordBuf = record.try_into()?;
+    for (key, lower, upper) in cis {
+        if (lower, upper) != (0, 0) {
+            vcf_record.info_mut().insert(
+                key.to_string(),
+                Some(vcf::variant::record_buf::info::field::Value::Array(
+                    vcf::variant::record_buf::info::field::value::Array::Integer(vec![
+                        Some(lower),
+                        Some(upper),
+                    ]),
+                )),
+            );
+        }
+    }
+    Ok(vcf_record)
+}
+
 /// Write out variants from input files.
+#[tracing::instrument(skip_all)]
 async fn process_variants(
     pedigree: &mehari::ped::PedigreeByName,
     output_header: &vcf::Header,
     output_writer: &mut AsyncVcfWriter,
     input_readers: Vec<VariantReader>,
     input_header: &[vcf::Header],
-    input_sv_callers: &[mehari::annotate::strucvars::SvCaller],
+    sample_names: &indexmap::IndexSet<String>,
+    input_sv_callers: &[callers::Caller],
+    coverage_tracks: Vec<copy_number::CoverageTrack>,
     args: &Args,
 ) -> Result<(), anyhow::Error> {
     // Initialize the random number generator from command line seed if given or local entropy
@@ -320,27 +569,65 @@ async fn process_variants(
 
     // Read through input VCF files and write out to temporary files.
     tracing::info!("converting input VCF files to temporary files...");
+    let mut record_filters = HashMap::new();
+    let mut skip_counts = FilterSkipCounts::default();
+    let mut reconciler = copy_number::Reconciler::new(pedigree, coverage_tracks, args.min_overlap);
+    let mut sample_calls = input_header
+        .iter()
+        .any(|header| header.sample_names().len() != sample_names.len())
+        .then(|| samples::SampleCallStore::new(args.min_overlap, args.slack_ins, args.slack_bnd));
+    let quality_config = args
+        .caller_quality_config
+        .as_deref()
+        .map(quality::QualityConfig::load)
+        .transpose()
+        .classify(ErrorClass::Config)?;
     let mut input_readers = input_readers;
     for (mut reader, sv_caller, header) in itertools::izip!(
         input_readers.drain(..),
         input_sv_callers.iter(),
         input_header.iter()
     ) {
-        mehari::annotate::strucvars::run_vcf_to_jsonl(
+        vcf_to_jsonl(
             pedigree,
             &mut reader,
             header,
+            sample_names,
             sv_caller,
             &tmp_dir,
-            &mut std::collections::HashMap::new(),
             &mut rng,
+            args.only_pass,
+            quality_config
+                .as_ref()
+                .map(|quality_config| quality_config.get(sv_caller))
+                .as_ref(),
+            &mut record_filters,
+            &mut skip_counts,
+            &mut reconciler.store,
+            sample_calls.as_mut(),
         )
         .await?;
     }
     tracing::info!("... done converting input files");
+    if args.only_pass {
+        tracing::info!("skipped {} non-PASS records", skip_counts.total);
+        for (filter, count) in &skip_counts.by_filter {
+            tracing::info!("  {}: {}", filter, count);
+        }
+    }
+    if quality_config.is_some() {
+        tracing::info!(
+            "skipped {} records failing the caller quality filters",
+            skip_counts.by_quality.values().sum::<usize>()
+        );
+        for (reason, count) in &skip_counts.by_quality {
+            tracing::info!("  {}: {}", reason, count);
+        }
+    }
 
     tracing::info!("clustering SVs to output...");
     // Read through temporary files by contig, cluster by overlap as configured, and write to `writer`.
+    let mut total_written = 0usize;
     for contig_no in 1..=25 {
         tracing::info!(
             "  contig: {}",
@@ -353,11 +640,25 @@ async fn process_variants(
             args.slack_bnd,
             args.min_overlap,
         )?;
-        for record in clusters {
-            write_ingest_record(output_header, output_writer, &record.try_into()?).await?;
+        for mut record in clusters {
+            // Note that the clustered record inherits the FILTER values of its first record.
+            let filters = record_filters.get(&record.sv_uuid);
+            if let Some(sample_calls) = sample_calls.as_ref() {
+                sample_calls.fill(&mut record);
+            }
+            let caller_cns = reconciler.reconcile(&mut record)?;
+            let mut vcf_record = to_vcf_record(record)?;
+            if let Some(caller_cns) = caller_cns {
+                copy_number::add_caller_cns(&mut vcf_record, caller_cns);
+            }
+            write_ingest_record(output_header, output_writer, &vcf_record, filters).await?;
+            total_written += 1;
         }
     }
     tracing::info!("... done clustering SVs to output");
+    if total_written == 0 {
+        tracing::info!("no records to write, wrote output with header only");
+    }
 
     Ok(())
 }
@@ -371,12 +672,33 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     common::trace_rss_now();
 
     tracing::info!("loading pedigree...");
-    let pedigree = mehari::ped::PedigreeByName::from_path(&args.path_ped)
-        .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?;
+    let pedigree = crate::common::ped::load(&args.path_ped)
+        .map_err(|e| {
+            anyhow::Error::new(e).context(format!("problem parsing PED file {}", &args.path_ped))
+        })
+        .classify(ErrorClass::Config)?;
     tracing::info!("pedigre = {:#?}", &pedigree);
 
+    tracing::info!("opening coverage files...");
+    let mut coverage_tracks = Vec::new();
+    for path_cov_vcf in &args.path_cov_vcf {
+        if !std::path::Path::new(path_cov_vcf).exists() {
+            warn_or_fail(
+                args_common.strict,
+                ErrorClass::Input,
+                format!("coverage file {} not found", path_cov_vcf),
+            )?;
+        } else {
+            coverage_tracks.push(
+                copy_number::CoverageTrack::from_path(path_cov_vcf).classify(ErrorClass::Input)?,
+            );
+        }
+    }
+
     tracing::info!("opening input file...");
-    let mut input_readers = open_vcf_readers(&args.path_in).await?;
+    let mut input_readers = open_vcf_readers(&args.path_in)
+        .await
+        .classify(ErrorClass::Input)?;
 
     tracing::info!("loading file identifier mappings...");
     let id_mappings = args
@@ -425,8 +747,8 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     tracing::info!("guessing SV callers...");
     let input_sv_callers = {
         let mut sv_callers = Vec::new();
-        for mut reader in open_vcf_readers(&args.path_in).await? {
-            sv_callers.push(guess_sv_caller(&mut reader).await?);
+        for path_in in &args.path_in {
+            sv_callers.push(callers::guess_caller(path_in).await?);
         }
         sv_callers
     };
@@ -441,67 +763,59 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     .into_iter()
     .collect::<Result<Vec<_>, _>>()
     .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
-    let orig_sample_names = input_headers
-        .first()
-        .expect("must have at least one input file")
-        .sample_names();
-    let sample_names = orig_sample_names
-        .iter()
-        .map(|name| {
-            if let Some(id_mappings) = &id_mappings {
-                let mapping = id_mappings
-                    .mapping_for_file(args.path_in.first().expect("count checked above"))
-                    .expect("checked above");
-                mapping
-                    .get(name)
-                    .cloned()
-                    .ok_or_else(|| anyhow::anyhow!("no mapping for sample name: {}", name))
-            } else {
-                Ok(name.clone())
-            }
-        })
-        .collect::<Result<indexmap::IndexSet<_>, _>>()?;
-    for (indexno, other_input_header) in input_headers.iter().enumerate().skip(1) {
-        let other_sample_names = if let Some(id_mappings) = &id_mappings {
+    for (input_header, path_in) in input_headers.iter().zip(args.path_in.iter()) {
+        crate::common::header_check::check_input_contig_lengths(
+            input_header,
+            args.genomebuild,
+            path_in,
+            args.lenient,
+        )?;
+    }
+
+    // Map sample names in input headers.
+    let mapped_input_headers = if let Some(id_mappings) = &id_mappings {
+        let mut mapped_input_headers = Vec::new();
+        for (i, input_header) in input_headers.iter().enumerate() {
             let mapping = id_mappings
-                .mapping_for_file(&args.path_in[indexno])
+                .mapping_for_file(&args.path_in[i])
                 .expect("checked above");
-            other_input_header
-                .sample_names()
-                .iter()
-                .map(|s| {
-                    mapping.get(s).cloned().ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "no mapping for sample name: {} in file: {}",
-                            s,
-                            &args.path_in[indexno]
-                        )
-                    })
-                })
-                .collect::<Result<indexmap::IndexSet<_>, _>>()?
-        } else {
-            other_input_header
-                .sample_names()
-                .iter()
-                .cloned()
-                .collect::<indexmap::IndexSet<_>>()
-        };
-        if other_sample_names != sample_names {
-            return Err(anyhow::anyhow!(
-                "input file #{} has different sample names than first one: {}",
-                indexno,
-                &args.path_in[indexno]
-            ));
+            let mut input_header = input_header.clone();
+            let orig_sample_names = input_header.sample_names().clone();
+            input_header.sample_names_mut().clear();
+            for sample_name in orig_sample_names {
+                let mapped_sample_name = mapping.get(&sample_name).cloned().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no mapping for sample name: {} in file: {}",
+                        sample_name,
+                        &args.path_in[i]
+                    )
+                })?;
+                input_header.sample_names_mut().insert(mapped_sample_name);
+            }
+            mapped_input_headers.push(input_header);
         }
+        mapped_input_headers
+    } else {
+        input_headers.clone()
+    };
+    let input_sample_names = mapped_input_headers
+        .iter()
+        .map(|header| header.sample_names().clone())
+        .collect::<Vec<_>>();
+    let sample_names = samples::union(&input_sample_names);
+    let differing_samples = input_sample_names
+        .iter()
+        .any(|names| names.len() != sample_names.len());
+    if differing_samples {
+        tracing::info!(
+            "input files have different samples, filling missing ones with no-calls: {:?}",
+            &input_sample_names
+        );
     }
-    let output_header = header::build_output_header(
-        orig_sample_names,
+    let mut output_header = header::build_output_header(
+        &sample_names,
         &input_sv_callers.iter().collect::<Vec<_>>(),
-        id_mappings.as_ref().map(|id_mappings| {
-            id_mappings
-                .mapping_for_file(args.path_in.first().expect("count checked above"))
-                .expect("checked above")
-        }),
+        None,
         Some(&pedigree),
         args.genomebuild,
         &args.file_date,
@@ -509,35 +823,15 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         &args.case_uuid,
     )
     .map_err(|e| anyhow::anyhow!("problem building output header: {}", e))?;
+    header::add_caller_filters(&mut output_header, &input_headers, &input_sv_callers);
+    if differing_samples {
+        header::add_caller_samples(&mut output_header, &input_sample_names, &input_sv_callers)?;
+    }
 
     // Use output file helper.
     let out_path_helper = crate::common::s3::OutputPathHelper::new(&args.path_out)?;
 
     {
-        // Map sample names in input headers.
-        let mapped_input_headers = if let Some(id_mappings) = &id_mappings {
-            let mut mapped_input_headers = Vec::new();
-            for (i, input_header) in input_headers.iter().enumerate() {
-                let mapping = id_mappings
-                    .mapping_for_file(&args.path_in[i])
-                    .expect("checked above");
-                let mut input_header = input_header.clone();
-                let orig_sample_names = input_header.sample_names().clone();
-                input_header.sample_names_mut().clear();
-                for sample_name in orig_sample_names {
-                    let mapped_sample_name =
-                        mapping.get(&sample_name).cloned().ok_or_else(|| {
-                            anyhow::anyhow!("no mapping for sample name: {}", sample_name)
-                        })?;
-                    input_header.sample_names_mut().insert(mapped_sample_name);
-                }
-                mapped_input_headers.push(input_header);
-            }
-            mapped_input_headers
-        } else {
-            input_headers.clone()
-        };
-
         // Perform actual writing
         let mut output_writer = open_vcf_writer(out_path_helper.path_out()).await?;
         output_writer
@@ -551,7 +845,9 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
             &mut output_writer,
             input_readers,
             &mapped_input_headers,
+            &sample_names,
             &input_sv_callers,
+            coverage_tracks,
             args,
         )
         .await?;
@@ -600,6 +896,9 @@ mod test {
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
             id_mapping: None,
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -638,6 +937,9 @@ mod test {
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
             id_mapping: None,
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -673,6 +975,9 @@ mod test {
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
             id_mapping: None,
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -713,6 +1018,9 @@ mod test {
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
             id_mapping: None,
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -722,6 +1030,43 @@ mod test {
         Ok(())
     }
 
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn smoke_test_empty() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let args_common = Default::default();
+        let args = super::Args {
+            max_var_count: None,
+            path_in: vec![String::from("tests/strucvars/ingest/gcnv-empty.vcf")],
+            path_cov_vcf: vec![],
+            path_ped: "tests/strucvars/ingest/gcnv-min.ped".into(),
+            genomebuild: GenomeRelease::Grch37,
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            rng_seed: Some(42),
+            file_date: String::from("20230421"),
+            case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
+            id_mapping: None,
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
+        };
+        super::run(&args_common, &args).await?;
+
+        let written = std::fs::read_to_string(&args.path_out)?;
+        assert!(written.lines().last().unwrap().starts_with("#CHROM"));
+        assert!(logs_contain("wrote output with header only"));
+
+        Ok(())
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     async fn smoke_test_singleton_with_id_mapping() -> Result<(), anyhow::Error> {
@@ -815,6 +1160,9 @@ mod test {
                 "#
                 .into(),
             ),
+            only_pass: false,
+            lenient: false,
+            caller_quality_config: None,
         };
         super::run(&args_common, &args).await?;
 
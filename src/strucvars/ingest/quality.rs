@@ -0,0 +1,218 @@
+//! Per-caller quality filters applied to the input records before clustering.
+//!
+//! The filters are configured with `--caller-quality-config` as a JSON object with one
+//! entry per caller name as used for the `FILTER` namespaces, e.g.,
+//! `{"Delly": {"min_qual": 100, "filters": ["PASS"]}}`.  An entry replaces the
+//! built-in defaults of its caller, callers without entry use the built-in defaults.
+
+use std::collections::HashMap;
+
+use mehari::annotate::strucvars::VarFishStrucvarTsvRecord;
+
+use super::{callers::Caller, header::caller_name};
+
+/// Names of the callers that can be configured.
+const CALLER_NAMES: &[&str] = &[
+    "Canvas",
+    "ClinCnv",
+    "Cnvnator",
+    "Delly",
+    "DragenCnv",
+    "DragenSv",
+    "Gcnv",
+    "Manta",
+    "Melt",
+    "Popdel",
+    "Sniffles2",
+];
+
+/// Quality thresholds for the records of one caller.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CallerQuality {
+    /// Minimal `QUAL` value; records without `QUAL` pass.
+    #[serde(default)]
+    pub min_qual: Option<f32>,
+    /// Minimal number of variant-supporting paired-end plus split reads in any sample;
+    /// records without read support values pass.
+    #[serde(default)]
+    pub min_pe_sr: Option<i32>,
+    /// Accepted (not namespaced) `FILTER` values, e.g., `["PASS"]`; all values are
+    /// accepted if not given.  Records without `FILTER` value always pass.
+    #[serde(default)]
+    pub filters: Option<Vec<String>>,
+}
+
+impl CallerQuality {
+    /// Return the built-in defaults for the caller with `name`.
+    pub fn builtin(name: &str) -> Self {
+        let pass = Some(vec!["PASS".to_string()]);
+        match name {
+            // Paired-read callers report the read support of the calls.
+            "Delly" | "DragenSv" | "Manta" => Self {
+                min_qual: None,
+                min_pe_sr: Some(3),
+                filters: pass,
+            },
+            // gCNV writes the Phred-scaled quality of the call to `QUAL`.
+            "Gcnv" => Self {
+                min_qual: Some(20.0),
+                min_pe_sr: None,
+                filters: pass,
+            },
+            _ => Self {
+                min_qual: None,
+                min_pe_sr: None,
+                filters: pass,
+            },
+        }
+    }
+
+    /// Return whether `qual` passes `min_qual`.
+    pub fn passes_qual(&self, qual: Option<f32>) -> bool {
+        match (self.min_qual, qual) {
+            (Some(min_qual), Some(qual)) => qual >= min_qual,
+            _ => true,
+        }
+    }
+
+    /// Return whether all `filters` values of a record are accepted.
+    pub fn passes_filters<'a>(&self, mut filters: impl Iterator<Item = &'a str>) -> bool {
+        match self.filters.as_ref() {
+            Some(accepted) => filters.all(|filter| accepted.iter().any(|value| value == filter)),
+            None => true,
+        }
+    }
+
+    /// Return whether the read support of any sample of `record` passes `min_pe_sr`.
+    pub fn passes_pe_sr(&self, record: &VarFishStrucvarTsvRecord) -> bool {
+        let Some(min_pe_sr) = self.min_pe_sr else {
+            return true;
+        };
+        let supports = record
+            .genotype
+            .entries
+            .iter()
+            .filter(|entry| entry.pev.is_some() || entry.srv.is_some())
+            .map(|entry| entry.pev.unwrap_or_default() + entry.srv.unwrap_or_default())
+            .collect::<Vec<_>>();
+        supports.is_empty() || supports.iter().any(|support| *support >= min_pe_sr)
+    }
+}
+
+/// Per-caller quality configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QualityConfig {
+    /// Configured thresholds by caller name, replacing the built-in defaults.
+    by_caller: HashMap<String, CallerQuality>,
+}
+
+impl QualityConfig {
+    /// Parse the configuration from the JSON string `json`.
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        let by_caller: HashMap<String, CallerQuality> = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("invalid caller quality configuration: {}", e))?;
+        if let Some(name) = by_caller
+            .keys()
+            .find(|name| !CALLER_NAMES.contains(&name.as_str()))
+        {
+            anyhow::bail!(
+                "unknown caller {} in caller quality configuration, expected one of {}",
+                name,
+                CALLER_NAMES.join(", ")
+            );
+        }
+        Ok(Self { by_caller })
+    }
+
+    /// Load the configuration from the JSON file at `path`.
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {}", path, e))?;
+        Self::from_json(&json).map_err(|e| anyhow::anyhow!("problem with {}: {}", path, e))
+    }
+
+    /// Return the thresholds for `sv_caller`.
+    pub fn get(&self, sv_caller: &Caller) -> CallerQuality {
+        let name = caller_name(sv_caller);
+        self.by_caller
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| CallerQuality::builtin(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::{GenotypeCalls, GenotypeInfo, SvCaller};
+
+    use super::*;
+
+    #[test]
+    fn config() -> Result<(), anyhow::Error> {
+        let config = QualityConfig::from_json(r#"{"Delly": {"min_qual": 100}}"#)?;
+
+        let delly = Caller::Mehari(SvCaller::Delly {
+            version: "1.1.3".into(),
+        });
+        assert_eq!(
+            config.get(&delly),
+            CallerQuality {
+                min_qual: Some(100.0),
+                ..Default::default()
+            }
+        );
+        let manta = Caller::Mehari(SvCaller::Manta {
+            version: "1.6.0".into(),
+        });
+        assert_eq!(config.get(&manta), CallerQuality::builtin("Manta"));
+
+        assert!(QualityConfig::from_json(r#"{"Unknown": {}}"#).is_err());
+        assert!(QualityConfig::from_json(r#"{"Delly": {"min_quality": 1}}"#).is_err());
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(Some(30.0), true)]
+    #[case(Some(10.0), false)]
+    #[case(None, true)]
+    fn passes_qual(#[case] qual: Option<f32>, #[case] expected: bool) {
+        assert_eq!(CallerQuality::builtin("Gcnv").passes_qual(qual), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(&["PASS"], true)]
+    #[case(&[], true)]
+    #[case(&["PASS", "LowQual"], false)]
+    fn passes_filters(#[case] filters: &[&str], #[case] expected: bool) {
+        assert_eq!(
+            CallerQuality::builtin("Delly").passes_filters(filters.iter().copied()),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(&[(Some(1), Some(2)), (None, None)], true)]
+    #[case(&[(Some(1), None), (Some(0), Some(1))], false)]
+    #[case(&[(None, None)], true)]
+    fn passes_pe_sr(#[case] supports: &[(Option<i32>, Option<i32>)], #[case] expected: bool) {
+        let record = VarFishStrucvarTsvRecord {
+            genotype: GenotypeCalls {
+                entries: supports
+                    .iter()
+                    .map(|(pev, srv)| GenotypeInfo {
+                        pev: *pev,
+                        srv: *srv,
+                        ..Default::default()
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            CallerQuality::builtin("Delly").passes_pe_sr(&record),
+            expected
+        );
+    }
+}
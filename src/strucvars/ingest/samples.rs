@@ -0,0 +1,275 @@
+//! Support for input files with different sample subsets.
+//!
+//! The genotype entries of each record are aligned to the union of the samples of all
+//! input files; samples that are not in the record's file get no-call genotypes.  When
+//! records of several callers are clustered, the merged record keeps the genotypes of
+//! its first record.  Samples that are missing from the file of the first record are
+//! then filled from the best overlapping record of a merged caller whose file has the
+//! sample.
+
+use std::collections::HashMap;
+
+use indexmap::IndexSet;
+use mehari::annotate::strucvars::{GenotypeInfo, SvType, VarFishStrucvarTsvRecord};
+
+use super::copy_number::reciprocal_overlap;
+
+/// Genotype written for samples that are not in the input file.
+pub const NO_CALL: &str = "./.";
+
+/// Return the union of the sample names in the order of first occurrence.
+pub fn union(sample_names: &[IndexSet<String>]) -> IndexSet<String> {
+    sample_names.iter().flatten().cloned().collect()
+}
+
+/// Align `entries` to `sample_names`, filling missing samples with no-call genotypes.
+pub fn align_entries(
+    entries: Vec<GenotypeInfo>,
+    sample_names: &IndexSet<String>,
+) -> Vec<GenotypeInfo> {
+    let mut by_name = entries
+        .into_iter()
+        .map(|entry| (entry.name.clone(), entry))
+        .collect::<HashMap<_, _>>();
+    sample_names
+        .iter()
+        .map(|name| {
+            by_name.remove(name).unwrap_or_else(|| GenotypeInfo {
+                name: name.clone(),
+                gt: Some(NO_CALL.to_string()),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Genotype calls of one input record before clustering.
+#[derive(Debug, Clone)]
+struct StoredCalls {
+    /// The caller as written to INFO/callers by `mehari`.
+    caller: String,
+    /// The type of the SV.
+    sv_type: SvType,
+    /// 1-based start position.
+    start: i32,
+    /// 1-based end position.
+    end: i32,
+    /// The aligned genotype entries.
+    entries: Vec<GenotypeInfo>,
+    /// Whether the file of the record has the sample, in the order of `entries`.
+    covered: Vec<bool>,
+}
+
+/// Genotype calls of the input records by chromosome.
+#[derive(Debug)]
+pub struct SampleCallStore {
+    /// The input records by chromosome.
+    by_chrom: HashMap<String, Vec<StoredCalls>>,
+    /// Chromosome and index in `by_chrom` by SV UUID.
+    by_uuid: HashMap<uuid::Uuid, (String, usize)>,
+    /// Minimal reciprocal overlap for linear SVs.
+    min_overlap: f32,
+    /// Slack around insertions.
+    slack_ins: i32,
+    /// Slack around break-ends.
+    slack_bnd: i32,
+}
+
+impl SampleCallStore {
+    /// Create a new, empty store with the clustering parameters.
+    pub fn new(min_overlap: f32, slack_ins: i32, slack_bnd: i32) -> Self {
+        Self {
+            by_chrom: HashMap::new(),
+            by_uuid: HashMap::new(),
+            min_overlap,
+            slack_ins,
+            slack_bnd,
+        }
+    }
+
+    /// Register the aligned `record` from a file with the `covered` samples.
+    pub fn insert(&mut self, record: &VarFishStrucvarTsvRecord, covered: &[bool]) {
+        let Some(caller) = record.callers.first() else {
+            return;
+        };
+        let calls = self.by_chrom.entry(record.chromosome.clone()).or_default();
+        self.by_uuid
+            .insert(record.sv_uuid, (record.chromosome.clone(), calls.len()));
+        calls.push(StoredCalls {
+            caller: caller.clone(),
+            sv_type: record.sv_type,
+            start: record.start,
+            end: record.end,
+            entries: record.genotype.entries.clone(),
+            covered: covered.to_vec(),
+        });
+    }
+
+    /// Return whether `calls` match `record` as in the clustering.
+    fn matches(&self, calls: &StoredCalls, record: &VarFishStrucvarTsvRecord) -> Option<f32> {
+        if calls.sv_type != record.sv_type || !record.callers.contains(&calls.caller) {
+            return None;
+        }
+        let slack = match record.sv_type {
+            SvType::Ins => Some(self.slack_ins),
+            SvType::Bnd => Some(self.slack_bnd),
+            _ => None,
+        };
+        let overlap = match slack {
+            Some(slack) => {
+                let distance = (calls.start - record.start).abs();
+                (distance <= slack).then(|| 1.0 - distance as f32 / (slack + 1) as f32)
+            }
+            None => Some(reciprocal_overlap(
+                calls.start,
+                calls.end,
+                record.start,
+                record.end,
+            )),
+        };
+        overlap.filter(|overlap| slack.is_some() || *overlap >= self.min_overlap)
+    }
+
+    /// Fill the samples of the merged `record` that are missing from the file of its
+    /// first record.
+    ///
+    /// Returns the number of filled samples.
+    pub fn fill(&self, record: &mut VarFishStrucvarTsvRecord) -> usize {
+        let Some(first) = self
+            .by_uuid
+            .get(&record.sv_uuid)
+            .map(|(chrom, idx)| &self.by_chrom[chrom][*idx])
+        else {
+            return 0;
+        };
+        let candidates = self
+            .by_chrom
+            .get(&record.chromosome)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let mut filled = Vec::new();
+        for (idx, covered) in first.covered.iter().enumerate() {
+            if *covered {
+                continue;
+            }
+            let best = candidates
+                .iter()
+                .filter(|calls| calls.covered.get(idx).copied().unwrap_or(false))
+                .filter_map(|calls| self.matches(calls, record).map(|overlap| (overlap, calls)))
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+            if let Some((_, calls)) = best {
+                filled.push((idx, calls.entries[idx].clone()));
+            }
+        }
+
+        let count = filled.len();
+        for (idx, entry) in filled {
+            record.genotype.entries[idx] = entry;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexSet;
+    use mehari::annotate::strucvars::{
+        GenotypeCalls, GenotypeInfo, SvType, VarFishStrucvarTsvRecord,
+    };
+
+    use super::SampleCallStore;
+
+    fn names(names: &[&str]) -> IndexSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn record(
+        caller: &str,
+        sv_type: SvType,
+        start: i32,
+        end: i32,
+        gts: &[&str],
+    ) -> VarFishStrucvarTsvRecord {
+        VarFishStrucvarTsvRecord {
+            chromosome: "1".into(),
+            start,
+            end,
+            sv_uuid: uuid::Uuid::new_v4(),
+            callers: vec![caller.into()],
+            sv_type,
+            genotype: GenotypeCalls {
+                entries: gts
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, gt)| GenotypeInfo {
+                        name: format!("sample{}", idx),
+                        gt: Some(gt.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn union() {
+        assert_eq!(
+            super::union(&[names(&["b", "a"]), names(&["c", "a"])]),
+            names(&["b", "a", "c"])
+        );
+    }
+
+    #[test]
+    fn align_entries() {
+        let entries = vec![
+            GenotypeInfo {
+                name: "c".into(),
+                gt: Some("0/1".into()),
+                ..Default::default()
+            },
+            GenotypeInfo {
+                name: "a".into(),
+                gt: Some("1/1".into()),
+                ..Default::default()
+            },
+        ];
+
+        let aligned = super::align_entries(entries, &names(&["a", "b", "c"]));
+
+        assert_eq!(
+            aligned
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.gt.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![("a", Some("1/1")), ("b", Some("./.")), ("c", Some("0/1"))]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::del_overlap(SvType::Del, 1_050, 2_000, 1)]
+    #[case::del_no_overlap(SvType::Del, 1_500, 3_000, 0)]
+    #[case::ins_within_slack(SvType::Ins, 1_020, 1_020, 1)]
+    #[case::ins_outside_slack(SvType::Ins, 1_100, 1_100, 0)]
+    fn fill(
+        #[case] sv_type: SvType,
+        #[case] start: i32,
+        #[case] end: i32,
+        #[case] expected: usize,
+    ) {
+        let mut store = SampleCallStore::new(0.8, 50, 50);
+        let end_first = if sv_type == SvType::Ins { 1_000 } else { 2_000 };
+        let first = record("MANTAv1.6", sv_type, 1_000, end_first, &["0/1", "./."]);
+        let other = record("GATK_GCNVv4.2", sv_type, start, end, &["0/1", "1/1"]);
+        store.insert(&first, &[true, false]);
+        store.insert(&other, &[true, true]);
+
+        let mut merged = first.clone();
+        merged.callers.push("GATK_GCNVv4.2".into());
+        assert_eq!(store.fill(&mut merged), expected);
+        let expected_gt = if expected == 1 { "1/1" } else { "./." };
+        assert_eq!(merged.genotype.entries[1].gt.as_deref(), Some(expected_gt));
+        assert_eq!(merged.genotype.entries[0].gt.as_deref(), Some("0/1"));
+    }
+}
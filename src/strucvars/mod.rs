@@ -1,4 +1,6 @@
 pub mod aggregate;
+pub mod bin_to_txt;
 pub mod ingest;
+pub mod qc;
 pub mod query;
 pub mod txt_to_bin;
@@ -0,0 +1,447 @@
+//! Implementation of `strucvars qc` subcommand.
+//!
+//! Computes statistics of a file written by `strucvars ingest` for storing along with
+//! the case: counts by SV type and size per sample and caller, the concordance of the
+//! callers in the merged callset, and sanity checks of the breakend coordinates.
+
+use std::collections::BTreeMap;
+
+use futures::TryStreamExt as _;
+use indexmap::IndexMap;
+use mehari::{annotate::strucvars::bnd::Breakend, common::noodles::NoodlesVariantReader as _};
+use noodles::vcf::{
+    self,
+    variant::{record::info::field::key, record_buf::info::field},
+};
+
+use crate::{
+    common::{
+        self,
+        errors::{ClassifyExt as _, ErrorClass},
+        sample_genotype, Genotype,
+    },
+    strucvars::query::schema::SvType,
+};
+
+/// Command line arguments for `strucvars qc` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "compute statistics of ingested strucvars VCF", long_about = None)]
+pub struct Args {
+    /// Path to the input VCF file written by `strucvars ingest`.
+    #[clap(long)]
+    pub path_input: String,
+    /// Optional path to the output JSON file, written to stdout if not given.
+    #[clap(long)]
+    pub path_output: Option<String>,
+}
+
+/// Size bin of an SV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub enum SizeBin {
+    /// Shorter than 1kbp.
+    #[serde(rename = "<1kb")]
+    Below1kb,
+    /// 1kbp to 10kbp.
+    #[serde(rename = "1kb-10kb")]
+    Below10kb,
+    /// 10kbp to 100kbp.
+    #[serde(rename = "10kb-100kb")]
+    Below100kb,
+    /// 100kbp to 1Mbp.
+    #[serde(rename = "100kb-1Mb")]
+    Below1Mb,
+    /// At least 1Mbp.
+    #[serde(rename = ">=1Mb")]
+    Above1Mb,
+    /// Without size, e.g., breakends.
+    #[serde(rename = "na")]
+    NotApplicable,
+}
+
+impl SizeBin {
+    /// Return the bin of an SV with `size`.
+    fn of(size: Option<u64>) -> Self {
+        match size {
+            Some(0..=999) => Self::Below1kb,
+            Some(1_000..=9_999) => Self::Below10kb,
+            Some(10_000..=99_999) => Self::Below100kb,
+            Some(100_000..=999_999) => Self::Below1Mb,
+            Some(_) => Self::Above1Mb,
+            None => Self::NotApplicable,
+        }
+    }
+}
+
+/// Counts of SVs by type and size bin.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct SvCounts {
+    /// Number of SVs.
+    pub count: u64,
+    /// Number of SVs by type and size bin.
+    pub by_type: BTreeMap<SvType, BTreeMap<SizeBin, u64>>,
+}
+
+impl SvCounts {
+    /// Count an SV of `sv_type` in `size_bin`.
+    fn add(&mut self, sv_type: SvType, size_bin: SizeBin) {
+        self.count += 1;
+        *self
+            .by_type
+            .entry(sv_type)
+            .or_default()
+            .entry(size_bin)
+            .or_default() += 1;
+    }
+}
+
+/// Statistics of one sample.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct SampleStats {
+    /// Sex from the pedigree.
+    pub sex: String,
+    /// Number of het. calls.
+    pub count_het: u64,
+    /// Number of hom. alt. calls.
+    pub count_homalt: u64,
+    /// Number of records with a no-call.
+    pub count_no_call: u64,
+    /// Counts of the records with a het. or hom. alt. call.
+    pub carriers: SvCounts,
+}
+
+/// Statistics of one caller.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct CallerStats {
+    /// Counts of the records called by the caller.
+    pub calls: SvCounts,
+    /// Number of records also called by another caller.
+    pub count_shared: u64,
+    /// Fraction of records also called by another caller.
+    pub shared_fraction: Option<f64>,
+}
+
+/// Sanity checks of the record coordinates.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct BreakendChecks {
+    /// Number of records without `INFO/END`.
+    pub count_missing_end: u64,
+    /// Number of records on one chromosome with `INFO/END` before `POS`.
+    pub count_end_before_start: u64,
+    /// Number of breakend records whose ALT allele cannot be parsed.
+    pub count_invalid_bnd: u64,
+    /// Number of breakend records whose mate in the ALT allele differs from
+    /// `INFO/chr2` and `INFO/END`.
+    pub count_bnd_mate_mismatch: u64,
+}
+
+/// The QC report of an ingested file.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Report {
+    /// The case UUID from the header.
+    pub case_uuid: uuid::Uuid,
+    /// Counts of all records.
+    pub records: SvCounts,
+    /// Statistics by sample, in file order.
+    pub samples: IndexMap<String, SampleStats>,
+    /// Statistics by caller, in order of first appearance.
+    pub callers: IndexMap<String, CallerStats>,
+    /// Number of records by the set of their callers, joined with `+`.
+    pub by_caller_set: BTreeMap<String, u64>,
+    /// Sanity checks of the record coordinates.
+    pub breakend_checks: BreakendChecks,
+}
+
+/// Return the size of the SV of `sv_type` from `pos` to `end` (1-based) on one
+/// chromosome with the `INFO/SVLEN` value `svlen`.
+fn sv_size(sv_type: SvType, pos: usize, end: Option<i32>, svlen: Option<i32>) -> Option<u64> {
+    match sv_type {
+        SvType::Bnd => None,
+        SvType::Ins => svlen.map(|svlen| svlen.unsigned_abs() as u64),
+        _ => end
+            .and_then(|end| usize::try_from(end).ok())
+            .and_then(|end| (end + 1).checked_sub(pos))
+            .map(|size| size as u64),
+    }
+}
+
+/// Return the first value of the integer `INFO` field `name` of `record`.
+fn info_integer(record: &vcf::variant::RecordBuf, name: &str) -> Option<i32> {
+    match record.info().get(name) {
+        Some(Some(field::Value::Integer(value))) => Some(*value),
+        Some(Some(field::Value::Array(field::value::Array::Integer(values)))) => {
+            values.first().copied().flatten()
+        }
+        _ => None,
+    }
+}
+
+/// Return the callers of `record` from `INFO/callers`.
+fn record_callers(record: &vcf::variant::RecordBuf) -> Vec<String> {
+    match record.info().get("callers") {
+        Some(Some(field::Value::Array(field::value::Array::String(callers)))) => {
+            callers.iter().flatten().cloned().collect()
+        }
+        Some(Some(field::Value::String(caller))) => vec![caller.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Accumulates the statistics record by record.
+#[derive(Debug, Default)]
+struct Collector {
+    /// The report being built.
+    report: Report,
+}
+
+impl Collector {
+    /// Construct collector for the samples of `header`.
+    fn new(header: &vcf::Header) -> Result<Self, anyhow::Error> {
+        let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(header)?;
+        let mut samples = IndexMap::new();
+        for name in header.sample_names() {
+            let individual = pedigree
+                .individuals
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("individual {} not found in pedigree", name))?;
+            samples.insert(
+                name.clone(),
+                SampleStats {
+                    sex: format!("{:?}", individual.sex),
+                    ..Default::default()
+                },
+            );
+        }
+
+        Ok(Self {
+            report: Report {
+                case_uuid,
+                samples,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Check the coordinates of `record` with `sv_type` and `end`.
+    fn check_breakends(
+        &mut self,
+        record: &vcf::variant::RecordBuf,
+        sv_type: SvType,
+        pos: usize,
+        end: Option<i32>,
+    ) {
+        let checks = &mut self.report.breakend_checks;
+        let chrom = record.reference_sequence_name();
+        let chrom2 = match record.info().get("chr2") {
+            Some(Some(field::Value::String(chrom2))) => chrom2.as_str(),
+            _ => chrom,
+        };
+        let Some(end) = end else {
+            checks.count_missing_end += 1;
+            return;
+        };
+
+        if sv_type == SvType::Bnd {
+            let alternative = record
+                .alternate_bases()
+                .as_ref()
+                .first()
+                .map(String::as_str)
+                .unwrap_or_default();
+            match Breakend::from_ref_alt_str(record.reference_bases(), alternative) {
+                Err(_) => checks.count_invalid_bnd += 1,
+                Ok(breakend) => {
+                    if annonars::common::cli::canonicalize(&breakend.chrom)
+                        != annonars::common::cli::canonicalize(chrom2)
+                        || breakend.pos != end
+                    {
+                        checks.count_bnd_mate_mismatch += 1;
+                    }
+                }
+            }
+        } else if chrom2 == chrom && usize::try_from(end).map_or(true, |end| end < pos) {
+            checks.count_end_before_start += 1;
+        }
+    }
+
+    /// Add the `record`.
+    fn add(&mut self, record: &vcf::variant::RecordBuf) -> Result<(), anyhow::Error> {
+        let sv_type: SvType = match record.info().get(key::SV_TYPE) {
+            Some(Some(field::Value::String(sv_type))) => sv_type.parse()?,
+            _ => anyhow::bail!("missing INFO/SVTYPE in record {:?}", &record),
+        };
+        let pos: usize = record
+            .variant_start()
+            .ok_or_else(|| anyhow::anyhow!("missing variant start in record {:?}", &record))?
+            .into();
+        let end = info_integer(record, key::END_POSITION);
+        let size_bin = SizeBin::of(sv_size(
+            sv_type,
+            pos,
+            end,
+            info_integer(record, key::SV_LENGTHS),
+        ));
+
+        self.check_breakends(record, sv_type, pos, end);
+        self.report.records.add(sv_type, size_bin);
+
+        for (stats, sample) in self
+            .report
+            .samples
+            .values_mut()
+            .zip(record.samples().values())
+        {
+            match sample_genotype(&sample)? {
+                Genotype::HomRef => continue,
+                Genotype::WithNoCall => {
+                    stats.count_no_call += 1;
+                    continue;
+                }
+                Genotype::Het => stats.count_het += 1,
+                Genotype::HomAlt => stats.count_homalt += 1,
+            }
+            stats.carriers.add(sv_type, size_bin);
+        }
+
+        let mut callers = record_callers(record);
+        callers.sort();
+        callers.dedup();
+        for caller in &callers {
+            let stats = self.report.callers.entry(caller.clone()).or_default();
+            stats.calls.add(sv_type, size_bin);
+            if callers.len() > 1 {
+                stats.count_shared += 1;
+            }
+        }
+        *self
+            .report
+            .by_caller_set
+            .entry(callers.join("+"))
+            .or_default() += 1;
+
+        Ok(())
+    }
+
+    /// Compute the fraction fields and return the report.
+    fn finish(mut self) -> Report {
+        for stats in self.report.callers.values_mut() {
+            stats.shared_fraction = (stats.calls.count > 0)
+                .then(|| stats.count_shared as f64 / stats.calls.count as f64);
+        }
+        self.report
+    }
+}
+
+/// Compute the report of the file at `args.path_input`.
+async fn build_report(args: &Args) -> Result<Report, anyhow::Error> {
+    let mut input_reader = common::noodles::open_vcf_reader(&args.path_input)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {}: {}", &args.path_input, e))?;
+    let input_header = input_reader.read_header().await.map_err(|e| {
+        anyhow::anyhow!("problem reading VCF header of {}: {}", &args.path_input, e)
+    })?;
+
+    let mut collector = Collector::new(&input_header)?;
+    let mut records = input_reader.records(&input_header).await;
+    while let Some(record) = records.try_next().await? {
+        collector.add(&record)?;
+    }
+    Ok(collector.finish())
+}
+
+/// Main entry point for `strucvars qc` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("Computing statistics of {} ...", &args.path_input);
+    let before_stats = std::time::Instant::now();
+    let report = build_report(args).await.classify(ErrorClass::Input)?;
+    tracing::info!(
+        "... done computing statistics of {} records in {:?}",
+        report.records.count,
+        before_stats.elapsed()
+    );
+
+    let json = serde_json::to_string_pretty(&report)?;
+    if let Some(path_output) = args.path_output.as_ref() {
+        std::fs::write(path_output, format!("{}\n", json))
+            .map_err(|e| anyhow::anyhow!("could not write report to {}: {}", path_output, e))?;
+    } else {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SizeBin, SvType};
+
+    #[rstest::rstest]
+    #[case(SvType::Del, 100, Some(199), None, SizeBin::Below1kb)]
+    #[case(SvType::Dup, 100, Some(1_099), None, SizeBin::Below10kb)]
+    #[case(SvType::Del, 100, Some(2_000_000), None, SizeBin::Above1Mb)]
+    #[case(SvType::Ins, 100, Some(100), Some(-5_000), SizeBin::Below10kb)]
+    #[case(SvType::Ins, 100, Some(100), None, SizeBin::NotApplicable)]
+    #[case(SvType::Bnd, 100, Some(500_000), None, SizeBin::NotApplicable)]
+    #[case(SvType::Del, 100, Some(50), None, SizeBin::NotApplicable)]
+    fn size_bin(
+        #[case] sv_type: SvType,
+        #[case] pos: usize,
+        #[case] end: Option<i32>,
+        #[case] svlen: Option<i32>,
+        #[case] expected: SizeBin,
+    ) {
+        assert_eq!(
+            SizeBin::of(super::sv_size(sv_type, pos, end, svlen)),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn build_report() -> Result<(), anyhow::Error> {
+        let args = super::Args {
+            path_input: "tests/strucvars/qc/trio.vcf".into(),
+            path_output: None,
+        };
+
+        let report = super::build_report(&args).await?;
+
+        assert_eq!(report.records.count, 5);
+        assert_eq!(
+            report.records.by_type[&SvType::Del],
+            [(SizeBin::Below1kb, 1), (SizeBin::Below100kb, 1)]
+                .into_iter()
+                .collect()
+        );
+        let index = &report.samples["index"];
+        assert_eq!(index.sex, "Male");
+        assert_eq!((index.count_het, index.count_homalt), (3, 1));
+        assert_eq!(index.count_no_call, 1);
+        assert_eq!(index.carriers.count, 4);
+        assert_eq!(report.callers["Delly"].calls.count, 4);
+        assert_eq!(report.callers["Delly"].count_shared, 2);
+        assert_eq!(report.callers["Delly"].shared_fraction, Some(0.5));
+        assert_eq!(report.callers["Manta"].shared_fraction, Some(1.0));
+        assert_eq!(
+            report.by_caller_set,
+            [
+                ("Delly".to_string(), 2),
+                ("Delly+Manta".to_string(), 2),
+                ("Popdel".to_string(), 1)
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            report.breakend_checks,
+            super::BreakendChecks {
+                count_end_before_start: 1,
+                count_bnd_mate_mismatch: 1,
+                ..Default::default()
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Code implementing the "strucvars query-batch" sub command.
+//!
+//! The worker databases and the mehari transcript database are loaded once and the jobs
+//! from the manifest are run sequentially, each with the settings of `strucvars query`.
+
+use std::time::Instant;
+
+use clap::{command, Parser};
+
+use crate::common::batch;
+use crate::common::errors::{ClassifyExt as _, ErrorClass};
+use crate::common::GenomeRelease;
+
+use super::Databases;
+
+/// Command line arguments for `strucvars query-batch` sub command.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Run queries for strucvars of multiple cases", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to worker database to use for querying.
+    #[arg(long, required = true)]
+    pub path_db: String,
+    /// Path to the JSONL manifest with one job per line.
+    #[arg(long, required = true)]
+    pub manifest: String,
+    /// Path to the JSONL file to write the status of each job to.
+    #[arg(long, required = true)]
+    pub path_summary: String,
+    /// Stop at the first failing job instead of continuing with the next one.
+    #[arg(long, default_value_t = false)]
+    pub fail_fast: bool,
+
+    /// Optional maximal number of total records to write out per job.
+    #[arg(long)]
+    pub max_results: Option<usize>,
+    /// Radius around BND sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_bnd: i32,
+    /// Radius around INS sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_ins: i32,
+    /// Minimal reciprocal overlap for SVs of the same type, used when building
+    /// the database.
+    #[arg(long, default_value_t = 0.8)]
+    pub min_overlap: f32,
+    /// Maximal distance to TAD to consider.
+    #[arg(long, default_value_t = 10_000)]
+    pub max_tad_distance: i32,
+    /// Path to a BED file with custom TADs; optional.  Selected with the `custom`
+    /// TAD set in the query JSON.
+    #[arg(long)]
+    pub path_custom_tads: Option<String>,
+    /// Length of the upstream/downstream region of transcripts.
+    #[arg(long, default_value_t = 5000)]
+    pub x_stream_size: i32,
+    /// Optional seed for RNG.
+    #[arg(long)]
+    pub rng_seed: Option<u64>,
+    /// Derive the record UUIDs from the case UUID and the variant instead of drawing
+    /// them at random, and write `mate_uuid` and `event_uuid`.
+    #[arg(long, default_value_t = false)]
+    pub deterministic_uuids: bool,
+    /// Write the payload as canonical JSON with sorted keys and floats rounded to 7
+    /// significant digits.
+    #[arg(long, default_value_t = false)]
+    pub canonical_json: bool,
+}
+
+/// One job from the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Identifier of the job in the summary; defaults to the output path.
+    pub id: Option<String>,
+    /// Path to input VCF file.
+    pub path_input: String,
+    /// Path to query JSON file.
+    pub path_query_json: String,
+    /// Path to the output TSV file.
+    pub path_output: String,
+    /// Result set ID to write into each record.
+    pub result_set_id: Option<String>,
+    /// Paths to the maelstrom coverage VCF files of the case's samples.
+    #[serde(default)]
+    pub path_cov_vcf: Vec<String>,
+    /// Path to the case's `seqvars query` result for SV and small variant compound
+    /// heterozygous candidates.
+    #[serde(default)]
+    pub path_seqvars_result: Option<String>,
+}
+
+impl Job {
+    /// Return the identifier of the job.
+    pub fn id(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.path_output)
+    }
+
+    /// Build the `strucvars query` arguments for the job with the shared settings of `args`.
+    fn query_args(&self, args: &Args) -> super::Args {
+        super::Args {
+            genome_release: args.genome_release,
+            result_set_id: self.result_set_id.clone(),
+            path_db: args.path_db.clone(),
+            path_query_json: self.path_query_json.clone(),
+            path_input: self.path_input.clone(),
+            path_output: self.path_output.clone(),
+            path_cov_vcf: self.path_cov_vcf.clone(),
+            path_seqvars_result: self.path_seqvars_result.clone(),
+            max_results: args.max_results,
+            slack_bnd: args.slack_bnd,
+            slack_ins: args.slack_ins,
+            min_overlap: args.min_overlap,
+            max_tad_distance: args.max_tad_distance,
+            path_custom_tads: args.path_custom_tads.clone(),
+            x_stream_size: args.x_stream_size,
+            rng_seed: args.rng_seed,
+            deterministic_uuids: args.deterministic_uuids,
+            canonical_json: args.canonical_json,
+            heap_profile: None,
+            estimate_only: false,
+        }
+    }
+}
+
+/// Run one `job` with the shared databases `dbs`.
+async fn run_job(
+    args_common: &crate::common::Args,
+    args: &Args,
+    dbs: &Databases,
+    job: &Job,
+) -> Result<(), anyhow::Error> {
+    let query_args = job.query_args(args);
+    let query = super::load_query(&query_args).await?;
+    super::run_with_databases(args_common, &query_args, query, dbs).await
+}
+
+/// Main entry point for `strucvars query-batch` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = Instant::now();
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let jobs: Vec<Job> = batch::load_manifest(&args.manifest).classify(ErrorClass::Config)?;
+    tracing::info!("Loaded {} jobs from {}", jobs.len(), &args.manifest);
+    let mut summary = batch::SummaryWriter::create(&args.path_summary)?;
+
+    let mut dbs = Databases::load(&args.path_db, args.genome_release, args.max_tad_distance)?;
+    if let Some(path_custom_tads) = &args.path_custom_tads {
+        dbs.in_memory_dbs
+            .tad_sets
+            .load_custom(path_custom_tads, args.max_tad_distance)
+            .classify(ErrorClass::Input)?;
+    }
+
+    let mut count_failed = 0;
+    for (no, job) in jobs.iter().enumerate() {
+        tracing::info!("Running job {}/{}: {}", no + 1, jobs.len(), job.id());
+        let before_job = Instant::now();
+        let result = run_job(args_common, args, &dbs, job).await;
+        if let Err(e) = &result {
+            tracing::error!("job {} failed: {:?}", job.id(), e);
+            count_failed += 1;
+        }
+        summary.write(&batch::JobSummary::new(
+            job.id(),
+            &result,
+            before_job.elapsed(),
+        ))?;
+        if args.fail_fast {
+            result?;
+        }
+    }
+
+    tracing::info!(
+        "All of `strucvars query-batch` completed in {:?}",
+        before_anything.elapsed()
+    );
+    if count_failed > 0 {
+        anyhow::bail!("{} of {} jobs failed", count_failed, jobs.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_manifest = format!("{}/jobs.jsonl", tmpdir.to_string_lossy());
+        let path_summary = format!("{}/summary.jsonl", tmpdir.to_string_lossy());
+        let path_output = format!("{}/out.tsv", tmpdir.to_string_lossy());
+        std::fs::write(
+            &path_manifest,
+            format!(
+                "{}\n{}\n",
+                serde_json::json!({
+                    "id": "case-3",
+                    "path_input": "tests/strucvars/query/Case_3.ingested.vcf",
+                    "path_query_json": "tests/strucvars/query/Case_3.query.json",
+                    "path_output": &path_output,
+                }),
+                serde_json::json!({
+                    "id": "missing",
+                    "path_input": "tests/strucvars/query/missing.vcf",
+                    "path_query_json": "tests/strucvars/query/Case_3.query.json",
+                    "path_output": format!("{}/missing.tsv", tmpdir.to_string_lossy()),
+                }),
+            ),
+        )?;
+
+        let args_common = Default::default();
+        let args = super::Args {
+            genome_release: crate::common::GenomeRelease::Grch37,
+            path_db: "tests/strucvars/query/db".into(),
+            manifest: path_manifest,
+            path_summary: path_summary.clone(),
+            fail_fast: false,
+            max_results: None,
+            slack_bnd: 50,
+            slack_ins: 50,
+            min_overlap: 0.8,
+            max_tad_distance: 10_000,
+            path_custom_tads: None,
+            x_stream_size: 5000,
+            rng_seed: Some(42),
+            deterministic_uuids: false,
+            canonical_json: false,
+        };
+        assert!(super::run(&args_common, &args).await.is_err());
+
+        let summary = std::fs::read_to_string(&path_summary)?
+            .lines()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0]["id"], "case-3");
+        assert_eq!(summary[0]["success"], true);
+        assert_eq!(summary[1]["id"], "missing");
+        assert_eq!(summary[1]["success"], false);
+        assert!(!std::fs::read_to_string(&path_output)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -2,30 +2,24 @@
 
 use std::{ops::Range, path::Path, time::Instant};
 
-use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
-use prost::Message;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
+use thousands::Separable;
 use tracing::info;
 
 use crate::{
-    common::{trace_rss_now, GenomeRelease, CHROMS},
+    common::{GenomeRelease, CHROMS},
     pbs::varfish::v1::strucvars::bgdb,
 };
 
 use super::{
+    intervals::{read_pb, BeginEnd, Metadata},
     schema::ChromRange,
     schema::{CaseQuery, StructuralVariant, SvType},
 };
 
-pub trait BeginEnd {
-    /// 0-base begin position
-    fn begin(&self) -> i32;
-    /// 0-based end position
-    fn end(&self) -> i32;
-}
-
 pub fn reciprocal_overlap(lhs: &impl BeginEnd, rhs: &Range<i32>) -> f32 {
     let lhs_b = lhs.begin();
     let lhs_e = lhs.end();
@@ -43,46 +37,138 @@ pub fn reciprocal_overlap(lhs: &impl BeginEnd, rhs: &Range<i32>) -> f32 {
     }
 }
 
-/// Alias for the interval tree that we use.
-type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+/// Records of a background database on one chromosome, sorted by the begin of their key.
+///
+/// Together with the running maximum of the key ends, this allows to find the
+/// overlapping records with two binary searches and a linear scan over the candidates.
+#[derive(Debug, Default, Clone)]
+pub struct ChromBgDb {
+    /// 0-based keys of the records, sorted by begin after `BgDb::index()`.
+    keys: Vec<Range<i32>>,
+    /// Running maximum of the key ends.
+    max_ends: Vec<i32>,
+    /// Records in the order of `keys`.
+    records: Vec<BgDbRecord>,
+}
+
+impl ChromBgDb {
+    /// Sort the records by the begin of their keys and compute the running maximum of ends.
+    fn index(&mut self) {
+        let mut entries = std::mem::take(&mut self.keys)
+            .into_iter()
+            .zip(std::mem::take(&mut self.records))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| (key.start, key.end));
+        (self.keys, self.records) = entries.into_iter().unzip();
+        self.max_ends = self
+            .keys
+            .iter()
+            .scan(i32::MIN, |max_end, key| {
+                *max_end = (*max_end).max(key.end);
+                Some(*max_end)
+            })
+            .collect();
+    }
+
+    /// Return the records overlapping with the 0-based `range`.
+    pub fn find(&self, range: Range<i32>) -> impl Iterator<Item = &BgDbRecord> + '_ {
+        // Records before `first` end before the range, records from `last` on begin after it.
+        let first = self.max_ends.partition_point(|&end| end <= range.start);
+        let last = self.keys.partition_point(|key| key.start < range.end);
+        (first..last.max(first))
+            .filter(move |&idx| self.keys[idx].end > range.start)
+            .map(move |idx| &self.records[idx])
+    }
+}
 
 /// Code for background database overlappers.
-#[derive(Default, Debug)]
+///
+/// The records are inserted with `insert()` and must be sorted with `index()` before
+/// querying.
+#[derive(Debug, Clone)]
 pub struct BgDb {
+    /// Metadata of the database.
+    pub meta: Metadata,
     /// Records, stored by chromosome.
-    pub records: Vec<Vec<BgDbRecord>>,
-    /// Interval trees, stored by chromosome.
-    pub trees: Vec<IntervalTree>,
+    pub chroms: Vec<ChromBgDb>,
+    /// Number of samples in the database for records without total, 0 if unknown.
+    pub sample_count: u32,
+}
+
+impl Default for BgDb {
+    fn default() -> Self {
+        Self::new("")
+    }
 }
 
 impl BgDb {
-    pub fn fetch_records(
+    /// Construct an empty database for all canonical chromosomes.
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            meta: Metadata {
+                source: source.into(),
+                record_count: 0,
+            },
+            chroms: CHROMS.iter().map(|_| ChromBgDb::default()).collect(),
+            sample_count: 0,
+        }
+    }
+
+    /// Insert `record` on chromosome `chrom_idx` with the 0-based interval `key`.
+    pub fn insert(&mut self, chrom_idx: usize, key: Range<i32>, record: BgDbRecord) {
+        self.chroms[chrom_idx].keys.push(key);
+        self.chroms[chrom_idx].records.push(record);
+        self.meta.record_count += 1;
+    }
+
+    /// Sort the records after all of them have been inserted.
+    pub fn index(&mut self) {
+        let before_building = Instant::now();
+        self.chroms.iter_mut().for_each(ChromBgDb::index);
+        tracing::debug!(
+            "done sorting {} records from {:?} in {:?}",
+            self.meta.record_count.separate_with_commas(),
+            &self.meta.source,
+            before_building.elapsed()
+        );
+    }
+
+    /// Return the records on chromosome `chrom_idx` overlapping with the 0-based `range`.
+    pub fn find(
         &self,
-        genomic_region: &ChromRange,
+        chrom_idx: usize,
+        range: Range<i32>,
+    ) -> impl Iterator<Item = &BgDbRecord> + '_ {
+        self.chroms[chrom_idx].find(range)
+    }
+
+    /// Fetch copies of the records that overlap with `chrom_range`.
+    pub fn fetch(
+        &self,
+        chrom_range: &ChromRange,
         chrom_map: &IndexMap<String, usize>,
     ) -> Vec<BgDbRecord> {
         let chrom_idx = *chrom_map
-            .get(&genomic_region.chromosome)
+            .get(&chrom_range.chromosome)
             .expect("invalid chromosome");
-        let range = genomic_region.begin..genomic_region.end;
-
-        self.trees[chrom_idx]
-            .find(range)
-            .iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
+        self.find(chrom_idx, chrom_range.begin..chrom_range.end)
             .cloned()
             .collect()
     }
 
-    pub fn count_overlaps(
-        &self,
+    /// Return the records matching `sv` together with their reciprocal overlap.
+    ///
+    /// INS and BND are matched within the slack around their position and have no
+    /// reciprocal overlap.
+    fn matching_records<'a>(
+        &'a self,
         chrom_map: &IndexMap<String, usize>,
-        enabled: bool,
         min_overlap: Option<f32>,
         slack_ins: i32,
         slack_bnd: i32,
         sv: &StructuralVariant,
-    ) -> u32 {
+    ) -> impl Iterator<Item = (&'a BgDbRecord, Option<f32>)> + 'a {
+        let sv_type = sv.sv_type;
         let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
         let range = if sv.sv_type == SvType::Ins {
             (sv.pos - slack_ins)..(sv.pos + slack_ins)
@@ -92,22 +178,128 @@ impl BgDb {
             (sv.pos - 1)..sv.end
         };
 
-        self.trees[chrom_idx]
-            .find(range.clone())
-            .iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
-            .filter(|record| record.sv_type.is_compatible(sv.sv_type))
-            .filter(|record| {
-                enabled
-                    && (record.sv_type == SvType::Ins
-                        || record.sv_type == SvType::Bnd
-                        || min_overlap.map_or(true, |min_overlap| {
-                            (reciprocal_overlap(*record, &range)) >= min_overlap
-                        }))
+        self.find(chrom_idx, range.clone())
+            .filter(move |record| record.sv_type.is_compatible(sv_type))
+            .map(move |record| {
+                let overlap = (record.sv_type != SvType::Ins && record.sv_type != SvType::Bnd)
+                    .then(|| reciprocal_overlap(record, &range));
+                (record, overlap)
             })
-            .map(|record| record.count)
+            .filter(move |(_, overlap)| match (overlap, min_overlap) {
+                (Some(overlap), Some(min_overlap)) => *overlap >= min_overlap,
+                _ => true,
+            })
+    }
+
+    pub fn count_overlaps(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> u32 {
+        if !enabled {
+            return 0;
+        }
+        self.matching_records(chrom_map, min_overlap, slack_ins, slack_bnd, sv)
+            .map(|(record, _)| record.count)
             .sum::<u32>()
     }
+
+    /// Return the largest carrier frequency of the records matching `sv`, `None` if
+    /// not `enabled`.
+    ///
+    /// The frequency of a record is its count divided by its total, or the sample count
+    /// of the database if the total is unknown.  Records without either are ignored and
+    /// the frequency is `0.0` if no record matches.
+    pub fn carrier_freq(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> Option<f32> {
+        if !enabled {
+            return None;
+        }
+        Some(
+            self.matching_records(chrom_map, min_overlap, slack_ins, slack_bnd, sv)
+                .filter_map(|(record, _)| {
+                    let total = if record.total > 0 {
+                        record.total
+                    } else {
+                        self.sample_count
+                    };
+                    (total > 0).then(|| record.count as f32 / total as f32)
+                })
+                .fold(0.0, f32::max),
+        )
+    }
+
+    /// Summarize the records matching `sv`, `None` if there are none.
+    pub fn overlap_stats(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> Option<BgDbOverlapStats> {
+        if !enabled {
+            return None;
+        }
+
+        let mut result = BgDbOverlapStats::default();
+        let mut overlaps = Vec::new();
+        for (record, overlap) in
+            self.matching_records(chrom_map, min_overlap, slack_ins, slack_bnd, sv)
+        {
+            if record.sv_type == sv.sv_type {
+                result.same_type_count += record.count;
+            } else {
+                result.compatible_type_count += record.count;
+            }
+            overlaps.extend(overlap);
+        }
+        if result.same_type_count + result.compatible_type_count == 0 {
+            return None;
+        }
+
+        overlaps.sort_by(f32::total_cmp);
+        if let (Some(&min), Some(&max)) = (overlaps.first(), overlaps.last()) {
+            let mid = overlaps.len() / 2;
+            result.min_overlap = Some(min);
+            result.max_overlap = Some(max);
+            result.median_overlap = Some(if overlaps.len() % 2 == 0 {
+                (overlaps[mid - 1] + overlaps[mid]) / 2.0
+            } else {
+                overlaps[mid]
+            });
+        }
+
+        Some(result)
+    }
+}
+
+/// Distribution of the overlaps of a structural variant with background database records.
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct BgDbOverlapStats {
+    /// Count of the matching records with the same SV type.
+    pub same_type_count: u32,
+    /// Count of the matching records with a different but compatible SV type, e.g., CNV
+    /// for DEL.
+    pub compatible_type_count: u32,
+    /// Smallest reciprocal overlap of the matching records, `None` for INS and BND.
+    pub min_overlap: Option<f32>,
+    /// Median reciprocal overlap of the matching records, `None` for INS and BND.
+    pub median_overlap: Option<f32>,
+    /// Largest reciprocal overlap of the matching records, `None` for INS and BND.
+    pub max_overlap: Option<f32>,
 }
 
 /// Information to store for background database.
@@ -121,6 +313,8 @@ pub struct BgDbRecord {
     pub sv_type: SvType,
     /// Count associated with the record.
     pub count: u32,
+    /// Number of samples genotyped at the site, 0 if unknown.
+    pub total: u32,
 }
 
 impl BeginEnd for BgDbRecord {
@@ -138,18 +332,9 @@ impl BeginEnd for BgDbRecord {
 pub fn load_bg_db_records(path: &Path) -> Result<BgDb, anyhow::Error> {
     tracing::debug!("loading binary bg db records from {:?}", path);
 
-    let before_loading = Instant::now();
-    let mut result = BgDb::default();
-    for _ in CHROMS {
-        result.records.push(Vec::new());
-        result.trees.push(IntervalTree::new());
-    }
-
-    let fcontents =
-        std::fs::read(path).map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e))?;
-    let bg_db = bgdb::BackgroundDatabase::decode(std::io::Cursor::new(fcontents))
-        .map_err(|e| anyhow::anyhow!("error decoding {:?}: {}", &path, e))?;
-    let record_count = bg_db.records.len();
+    let mut result = BgDb::new(path.display().to_string());
+    let bg_db: bgdb::BackgroundDatabase = read_pb(path)?;
+    result.sample_count = bg_db.sample_count;
 
     for record in bg_db.records.into_iter() {
         let chrom_no = record.chrom_no as usize;
@@ -163,36 +348,29 @@ pub fn load_bg_db_records(path: &Path) -> Result<BgDb, anyhow::Error> {
         };
         let key = begin..end;
 
-        result.trees[chrom_no].insert(key, result.records[chrom_no].len() as u32);
-        result.records[chrom_no].push(BgDbRecord {
-            begin: record.start - 1,
-            end: record.stop,
-            sv_type: match bgdb::SvType::try_from(record.sv_type).expect("invalid sv_type") {
-                bgdb::SvType::Unspecified => {
-                    anyhow::bail!("Invalid protobuf sv_type: {}", record.sv_type)
-                }
-                bgdb::SvType::Del => SvType::Del,
-                bgdb::SvType::Dup => SvType::Dup,
-                bgdb::SvType::Inv => SvType::Inv,
-                bgdb::SvType::Ins => SvType::Ins,
-                bgdb::SvType::Bnd => SvType::Bnd,
-                bgdb::SvType::Cnv => SvType::Cnv,
+        result.insert(
+            chrom_no,
+            key,
+            BgDbRecord {
+                begin: record.start - 1,
+                end: record.stop,
+                sv_type: match bgdb::SvType::try_from(record.sv_type).expect("invalid sv_type") {
+                    bgdb::SvType::Unspecified => {
+                        anyhow::bail!("Invalid protobuf sv_type: {}", record.sv_type)
+                    }
+                    bgdb::SvType::Del => SvType::Del,
+                    bgdb::SvType::Dup => SvType::Dup,
+                    bgdb::SvType::Inv => SvType::Inv,
+                    bgdb::SvType::Ins => SvType::Ins,
+                    bgdb::SvType::Bnd => SvType::Bnd,
+                    bgdb::SvType::Cnv => SvType::Cnv,
+                },
+                count: record.count,
+                total: record.total,
             },
-            count: record.count,
-        });
+        );
     }
-    tracing::debug!(
-        "done loading background db with {} records from {:?} in {:?}",
-        record_count,
-        path,
-        before_loading.elapsed()
-    );
-
-    let before_building = Instant::now();
-    result.trees.iter_mut().for_each(|tree| tree.index());
-    tracing::debug!("done building itrees in {:?}", before_building.elapsed());
-
-    trace_rss_now();
+    result.index();
 
     Ok(result)
 }
@@ -207,6 +385,7 @@ pub enum BgDbType {
     G1k,
     GnomadExomes,
     GnomadGenomes,
+    GnomadSv4,
     Inhouse,
 }
 
@@ -219,6 +398,7 @@ pub struct BgDbBundle {
     pub g1k: Option<BgDb>,
     pub gnomad_genomes: Option<BgDb>,
     pub gnomad_exomes: Option<BgDb>,
+    pub gnomad_sv4: Option<BgDb>,
     pub inhouse: Option<BgDb>,
 }
 
@@ -231,7 +411,14 @@ pub struct BgDbOverlaps {
     pub g1k: u32,
     pub gnomad_genomes: u32,
     pub gnomad_exomes: u32,
+    pub gnomad_sv4: u32,
     pub inhouse: u32,
+    /// Largest carrier frequency of the matching DGV gold standard records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dgv_gs_carrier_freq: Option<f32>,
+    /// Largest carrier frequency of the matching gnomAD-SV v4 records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gnomad_sv4_carrier_freq: Option<f32>,
 }
 
 impl BgDbBundle {
@@ -245,36 +432,57 @@ impl BgDbBundle {
             BgDbType::Dgv => self
                 .dgv
                 .as_ref()
-                .map(|dgv| dgv.fetch_records(genome_range, chrom_map))
+                .map(|dgv| dgv.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
             BgDbType::DgvGs => self
                 .dgv_gs
                 .as_ref()
-                .map(|dgv_gs| dgv_gs.fetch_records(genome_range, chrom_map))
+                .map(|dgv_gs| dgv_gs.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
             BgDbType::G1k => self
                 .g1k
                 .as_ref()
-                .map(|g1k| g1k.fetch_records(genome_range, chrom_map))
+                .map(|g1k| g1k.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
             BgDbType::GnomadExomes => self
                 .gnomad_exomes
                 .as_ref()
-                .map(|gnomad_exomes| gnomad_exomes.fetch_records(genome_range, chrom_map))
+                .map(|gnomad_exomes| gnomad_exomes.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
             BgDbType::GnomadGenomes => self
                 .gnomad_genomes
                 .as_ref()
-                .map(|gnomad_genomes| gnomad_genomes.fetch_records(genome_range, chrom_map))
+                .map(|gnomad_genomes| gnomad_genomes.fetch(genome_range, chrom_map))
+                .unwrap_or_default(),
+            BgDbType::GnomadSv4 => self
+                .gnomad_sv4
+                .as_ref()
+                .map(|gnomad_sv4| gnomad_sv4.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
             BgDbType::Inhouse => self
                 .inhouse
                 .as_ref()
-                .map(|inhouse| inhouse.fetch_records(genome_range, chrom_map))
+                .map(|inhouse| inhouse.fetch(genome_range, chrom_map))
                 .unwrap_or_default(),
         }
     }
 
+    /// Count overlaps for each of `svs` in parallel.
+    ///
+    /// This is meant to be called with the records of one chromosome at a time.
+    pub fn count_overlaps_batch(
+        &self,
+        svs: &[StructuralVariant],
+        query: &CaseQuery,
+        chrom_map: &IndexMap<String, usize>,
+        slack_ins: i32,
+        slack_bnd: i32,
+    ) -> Vec<BgDbOverlaps> {
+        svs.par_iter()
+            .map(|sv| self.count_overlaps(sv, query, chrom_map, slack_ins, slack_bnd))
+            .collect()
+    }
+
     pub fn count_overlaps(
         &self,
         sv: &StructuralVariant,
@@ -344,6 +552,16 @@ impl BgDbBundle {
                     sv,
                 )
             }),
+            gnomad_sv4: self.gnomad_sv4.as_ref().map_or(0, |gnomad_sv4| {
+                gnomad_sv4.count_overlaps(
+                    chrom_map,
+                    query.svdb_gnomad_sv4_enabled,
+                    query.svdb_gnomad_sv4_min_overlap,
+                    slack_ins,
+                    slack_bnd,
+                    sv,
+                )
+            }),
             inhouse: self.inhouse.as_ref().map_or(0, |inhouse| {
                 inhouse.count_overlaps(
                     chrom_map,
@@ -354,10 +572,53 @@ impl BgDbBundle {
                     sv,
                 )
             }),
+            dgv_gs_carrier_freq: self.dgv_gs.as_ref().and_then(|dgv_gs| {
+                dgv_gs.carrier_freq(
+                    chrom_map,
+                    query.svdb_dgv_gs_enabled,
+                    query.svdb_dgv_gs_min_overlap,
+                    slack_ins,
+                    slack_bnd,
+                    sv,
+                )
+            }),
+            gnomad_sv4_carrier_freq: self.gnomad_sv4.as_ref().and_then(|gnomad_sv4| {
+                gnomad_sv4.carrier_freq(
+                    chrom_map,
+                    query.svdb_gnomad_sv4_enabled,
+                    query.svdb_gnomad_sv4_min_overlap,
+                    slack_ins,
+                    slack_bnd,
+                    sv,
+                )
+            }),
         }
     }
 }
 
+impl BgDbBundle {
+    /// Summarize the in-house records overlapping with `sv`.
+    pub fn inhouse_overlap_stats(
+        &self,
+        sv: &StructuralVariant,
+        query: &CaseQuery,
+        chrom_map: &IndexMap<String, usize>,
+        slack_ins: i32,
+        slack_bnd: i32,
+    ) -> Option<BgDbOverlapStats> {
+        self.inhouse.as_ref().and_then(|inhouse| {
+            inhouse.overlap_stats(
+                chrom_map,
+                query.svdb_inhouse_enabled,
+                query.svdb_inhouse_min_overlap,
+                slack_ins,
+                slack_bnd,
+                sv,
+            )
+        })
+    }
+}
+
 // Load all background databases from database given the configuration.
 #[tracing::instrument]
 pub fn load_bg_dbs(
@@ -380,6 +641,8 @@ pub fn load_bg_dbs(
         "{}/strucvars/bgdbs/gnomad_genomes.bin",
         genome_release
     ));
+    let path_gnomad_sv4 =
+        Path::new(path_db).join(format!("{}/strucvars/bgdbs/gnomad_sv4.bin", genome_release));
     let path_inhouse = Path::new(path_db).join(format!("{}/strucvars/inhouse.bin", genome_release));
 
     let result = BgDbBundle {
@@ -407,6 +670,10 @@ pub fn load_bg_dbs(
             .exists()
             .then(|| load_bg_db_records(path_gnomad_genomes.as_path()))
             .transpose()?,
+        gnomad_sv4: path_gnomad_sv4
+            .exists()
+            .then(|| load_bg_db_records(path_gnomad_sv4.as_path()))
+            .transpose()?,
         inhouse: path_inhouse
             .exists()
             .then(|| load_bg_db_records(path_inhouse.as_path()))
@@ -415,3 +682,191 @@ pub fn load_bg_dbs(
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+    use rand::{Rng as _, SeedableRng as _};
+
+    use super::{BgDb, BgDbBundle, BgDbOverlapStats, BgDbRecord};
+    use crate::strucvars::query::schema::{CaseQuery, StructuralVariant, SvSubType, SvType};
+
+    fn record(begin: i32, end: i32, sv_type: SvType, count: u32) -> BgDbRecord {
+        BgDbRecord {
+            begin,
+            end,
+            sv_type,
+            count,
+            total: 0,
+        }
+    }
+
+    fn sv(sv_type: SvType, pos: i32, end: i32) -> StructuralVariant {
+        StructuralVariant {
+            chrom: "1".into(),
+            pos,
+            sv_type,
+            sv_sub_type: match sv_type {
+                SvType::Del => SvSubType::Del,
+                SvType::Ins => SvSubType::Ins,
+                _ => SvSubType::Dup,
+            },
+            chrom2: None,
+            end,
+            strand_orientation: StrandOrientation::NotApplicable,
+            callers: Vec::new(),
+            cipos: None,
+            ciend: None,
+            call_info: Default::default(),
+        }
+    }
+
+    #[test]
+    fn find_matches_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut db = BgDb::new("test");
+        let mut keys = Vec::new();
+        for _ in 0..1_000 {
+            let begin = rng.gen_range(0..100_000);
+            let end = begin + rng.gen_range(1..10_000);
+            db.insert(0, begin..end, record(begin, end, SvType::Del, 1));
+            keys.push(begin..end);
+        }
+        db.index();
+
+        for _ in 0..100 {
+            let begin = rng.gen_range(0..100_000);
+            let range = begin..(begin + rng.gen_range(1..5_000));
+            let mut actual = db
+                .find(0, range.clone())
+                .map(|record| (record.begin, record.end))
+                .collect::<Vec<_>>();
+            actual.sort();
+            let mut expected = keys
+                .iter()
+                .filter(|key| key.start < range.end && range.start < key.end)
+                .map(|key| (key.start, key.end))
+                .collect::<Vec<_>>();
+            expected.sort();
+
+            assert_eq!(actual, expected);
+        }
+        assert_eq!(db.find(1, 0..100_000).count(), 0);
+    }
+
+    #[test]
+    fn count_overlaps_batch() {
+        let mut db = BgDb::new("test");
+        db.insert(0, 999..2000, record(999, 2000, SvType::Del, 1));
+        db.insert(0, 1099..2000, record(1099, 2000, SvType::Del, 2));
+        db.insert(0, 999..2000, record(999, 2000, SvType::Dup, 4));
+        db.insert(0, 1498..1499, record(1499, 1499, SvType::Ins, 8));
+        db.index();
+        let bg_dbs = BgDbBundle {
+            gnomad_genomes: Some(db),
+            ..Default::default()
+        };
+        let query = CaseQuery {
+            svdb_gnomad_genomes_enabled: true,
+            svdb_gnomad_genomes_min_overlap: Some(0.95),
+            ..Default::default()
+        };
+        let chrom_map = crate::common::build_chrom_map();
+        let svs = vec![
+            sv(SvType::Del, 1000, 2000),
+            sv(SvType::Dup, 1000, 2000),
+            sv(SvType::Ins, 1500, 1500),
+            sv(SvType::Del, 5000, 6000),
+        ];
+
+        let counts = bg_dbs
+            .count_overlaps_batch(&svs, &query, &chrom_map, 50, 50)
+            .into_iter()
+            .map(|overlaps| overlaps.gnomad_genomes)
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts, vec![1, 4, 8, 0]);
+        for (sv, count) in svs.iter().zip(counts) {
+            assert_eq!(
+                bg_dbs
+                    .count_overlaps(sv, &query, &chrom_map, 50, 50)
+                    .gnomad_genomes,
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn carrier_freq() {
+        let mut db = BgDb::new("test");
+        db.sample_count = 100;
+        db.insert(0, 999..2000, record(999, 2000, SvType::Del, 10));
+        db.insert(
+            0,
+            999..2000,
+            BgDbRecord {
+                total: 1_000,
+                ..record(999, 2000, SvType::Del, 300)
+            },
+        );
+        db.insert(0, 999..2000, record(999, 2000, SvType::Dup, 50));
+        db.index();
+        let bg_dbs = BgDbBundle {
+            gnomad_sv4: Some(db),
+            ..Default::default()
+        };
+        let query = CaseQuery {
+            svdb_gnomad_sv4_enabled: true,
+            svdb_gnomad_sv4_min_overlap: Some(0.8),
+            ..Default::default()
+        };
+        let chrom_map = crate::common::build_chrom_map();
+
+        let overlaps =
+            bg_dbs.count_overlaps(&sv(SvType::Del, 1000, 2000), &query, &chrom_map, 50, 50);
+        assert_eq!(overlaps.gnomad_sv4, 310);
+        assert_eq!(overlaps.gnomad_sv4_carrier_freq, Some(0.3));
+        assert_eq!(overlaps.dgv_gs_carrier_freq, None);
+        assert_eq!(
+            bg_dbs
+                .count_overlaps(&sv(SvType::Del, 5000, 6000), &query, &chrom_map, 50, 50)
+                .gnomad_sv4_carrier_freq,
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn inhouse_overlap_stats() {
+        let mut db = BgDb::new("test");
+        db.insert(0, 999..2000, record(999, 2000, SvType::Del, 1));
+        db.insert(0, 1099..2000, record(1099, 2000, SvType::Del, 2));
+        db.insert(0, 499..2000, record(499, 2000, SvType::Cnv, 4));
+        db.insert(0, 999..2000, record(999, 2000, SvType::Dup, 8));
+        db.index();
+        let bg_dbs = BgDbBundle {
+            inhouse: Some(db),
+            ..Default::default()
+        };
+        let query = CaseQuery {
+            svdb_inhouse_enabled: true,
+            svdb_inhouse_min_overlap: Some(0.5),
+            ..Default::default()
+        };
+        let chrom_map = crate::common::build_chrom_map();
+
+        assert_eq!(
+            bg_dbs.inhouse_overlap_stats(&sv(SvType::Del, 1000, 2000), &query, &chrom_map, 50, 50),
+            Some(BgDbOverlapStats {
+                same_type_count: 3,
+                compatible_type_count: 4,
+                min_overlap: Some(1001.0 / 1501.0),
+                median_overlap: Some(901.0 / 1001.0),
+                max_overlap: Some(1.0),
+            })
+        );
+        assert_eq!(
+            bg_dbs.inhouse_overlap_stats(&sv(SvType::Del, 5000, 6000), &query, &chrom_map, 50, 50),
+            None
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Code for overlapping with ClinGen dosage-sensitive regions.
+
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{common::build_chrom_map, common::GenomeRelease};
+
+use super::{
+    intervals::{read_bed, IntervalDb},
+    schema::{StructuralVariant, SvType},
+};
+
+/// ClinGen dosage sensitivity score of a region.
+#[derive(Serialize, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DosageScore {
+    /// Sufficient evidence for dosage pathogenicity (score 3).
+    SufficientEvidence,
+    /// Some evidence for dosage pathogenicity (score 2).
+    SomeEvidence,
+    /// Little evidence for dosage pathogenicity (score 1).
+    LittleEvidence,
+    /// No evidence for dosage pathogenicity (score 0).
+    NoEvidence,
+    /// Gene associated with autosomal recessive phenotype (score 30).
+    Recessive,
+    /// Dosage sensitivity unlikely (score 40).
+    Unlikely,
+    /// Not yet evaluated.
+    #[default]
+    NotYetEvaluated,
+}
+
+impl DosageScore {
+    /// Parse the score from the numeric ClinGen value, anything else is interpreted
+    /// as "not yet evaluated".
+    pub fn from_clingen(value: &str) -> Self {
+        match value.trim() {
+            "3" => DosageScore::SufficientEvidence,
+            "2" => DosageScore::SomeEvidence,
+            "1" => DosageScore::LittleEvidence,
+            "0" => DosageScore::NoEvidence,
+            "30" => DosageScore::Recessive,
+            "40" => DosageScore::Unlikely,
+            _ => DosageScore::NotYetEvaluated,
+        }
+    }
+}
+
+/// Information to store for a ClinGen region.
+#[derive(Default, Debug, Clone)]
+pub struct Record {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position.
+    pub end: i32,
+    /// ClinGen region identifier, e.g., `ISCA-37404`.
+    pub id: String,
+    /// Haploinsufficiency score.
+    pub haplo: DosageScore,
+    /// Triplosensitivity score.
+    pub triplo: DosageScore,
+}
+
+/// Overlap of a structural variant with a ClinGen region, as written to the result.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RegionOverlap {
+    /// ClinGen region identifier.
+    pub id: String,
+    /// Haploinsufficiency score.
+    pub haplo: DosageScore,
+    /// Triplosensitivity score.
+    pub triplo: DosageScore,
+    /// Fraction of the region covered by the structural variant.
+    pub overlap: f32,
+}
+
+impl RegionOverlap {
+    /// Return whether the region is dosage-sensitive with respect to `sv_type`.
+    ///
+    /// Deletions are checked for haploinsufficiency, duplications for triplosensitivity,
+    /// and all other SV types for either.
+    pub fn is_dosage_sensitive(&self, sv_type: SvType) -> bool {
+        let haplo = self.haplo == DosageScore::SufficientEvidence;
+        let triplo = self.triplo == DosageScore::SufficientEvidence;
+        match sv_type {
+            SvType::Del => haplo,
+            SvType::Dup => triplo,
+            SvType::Inv | SvType::Ins | SvType::Bnd | SvType::Cnv => haplo || triplo,
+        }
+    }
+}
+
+/// Code for ClinGen region overlappers.
+pub type ClingenRegionDb = IntervalDb<Record>;
+
+impl ClingenRegionDb {
+    /// Return the ClinGen regions overlapping with `sv`.
+    ///
+    /// For insertions and break-ends, the region must contain the (first) breakpoint.
+    pub fn overlapping_regions(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> Vec<RegionOverlap> {
+        let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+        let range = if sv.sv_type == SvType::Ins || sv.sv_type == SvType::Bnd {
+            sv.pos.saturating_sub(1)..sv.pos
+        } else {
+            sv.pos.saturating_sub(1)..sv.end
+        };
+
+        self.find(chrom_idx, range.clone())
+            .map(|record| {
+                let ovl_len =
+                    std::cmp::min(range.end, record.end) - std::cmp::max(range.start, record.begin);
+                RegionOverlap {
+                    id: record.id.clone(),
+                    haplo: record.haplo,
+                    triplo: record.triplo,
+                    overlap: ovl_len as f32 / std::cmp::max(record.end - record.begin, 1) as f32,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Module with code for loading data from input.
+mod input {
+    use serde::Deserialize;
+
+    /// Type for record structs from input.
+    #[derive(Deserialize, Debug)]
+    pub struct Record {
+        /// Chromosome name
+        pub chrom: String,
+        /// 0-based begin position from BED.
+        pub begin: i32,
+        /// 0-based end position from BED.
+        pub end: i32,
+        /// Identifier of the region.
+        pub id: String,
+        /// Numeric haploinsufficiency score.
+        pub haplo: String,
+        /// Numeric triplosensitivity score.
+        pub triplo: String,
+    }
+}
+
+#[tracing::instrument]
+fn load_clingen_region_records(path: &Path) -> Result<ClingenRegionDb, anyhow::Error> {
+    tracing::debug!("loading ClinGen region records from {:?}...", path);
+    let chrom_map = build_chrom_map();
+
+    let mut result = ClingenRegionDb::new(path.display().to_string());
+    if !path.exists() {
+        warn!(
+            "ClinGen region file {:?} does not exist, skipping ClinGen region annotation",
+            path
+        );
+        return Ok(result);
+    }
+
+    for record in read_bed::<input::Record>(path)? {
+        let chrom_idx = *chrom_map.get(&record.chrom).expect("invalid chromosome");
+
+        let key = record.begin..record.end;
+        result.insert(
+            chrom_idx,
+            key,
+            Record {
+                begin: record.begin,
+                end: record.end,
+                id: record.id,
+                haplo: DosageScore::from_clingen(&record.haplo),
+                triplo: DosageScore::from_clingen(&record.triplo),
+            },
+        );
+    }
+    result.index();
+
+    Ok(result)
+}
+
+// Load the ClinGen region database from database given the configuration.
+#[tracing::instrument]
+pub fn load_clingen_regions(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<ClingenRegionDb, anyhow::Error> {
+    info!("Loading ClinGen region db");
+    load_clingen_region_records(
+        Path::new(path_db)
+            .join(format!("{}/strucvars/clingen_regions.bed", genome_release))
+            .as_path(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DosageScore, RegionOverlap};
+    use crate::strucvars::query::schema::{StructuralVariant, SvSubType, SvType};
+
+    #[rstest::rstest]
+    #[case("3", DosageScore::SufficientEvidence)]
+    #[case("2", DosageScore::SomeEvidence)]
+    #[case("1", DosageScore::LittleEvidence)]
+    #[case("0", DosageScore::NoEvidence)]
+    #[case("30", DosageScore::Recessive)]
+    #[case("40", DosageScore::Unlikely)]
+    #[case("Not yet evaluated", DosageScore::NotYetEvaluated)]
+    #[case("", DosageScore::NotYetEvaluated)]
+    fn dosage_score_from_clingen(#[case] value: &str, #[case] expected: DosageScore) {
+        assert_eq!(DosageScore::from_clingen(value), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(SvType::Del, true, false)]
+    #[case(SvType::Dup, false, true)]
+    #[case(SvType::Cnv, true, true)]
+    fn region_overlap_is_dosage_sensitive(
+        #[case] sv_type: SvType,
+        #[case] expected_haplo: bool,
+        #[case] expected_triplo: bool,
+    ) {
+        let haplo = RegionOverlap {
+            id: "ISCA-1".into(),
+            haplo: DosageScore::SufficientEvidence,
+            triplo: DosageScore::NoEvidence,
+            overlap: 1.0,
+        };
+        let triplo = RegionOverlap {
+            id: "ISCA-2".into(),
+            haplo: DosageScore::LittleEvidence,
+            triplo: DosageScore::SufficientEvidence,
+            overlap: 1.0,
+        };
+
+        assert_eq!(haplo.is_dosage_sensitive(sv_type), expected_haplo);
+        assert_eq!(triplo.is_dosage_sensitive(sv_type), expected_triplo);
+    }
+
+    #[test]
+    fn load_and_overlap() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_bed = tmpdir.join("clingen_regions.bed");
+        std::fs::write(
+            &path_bed,
+            "#chrom\tbegin\tend\tid\thaplo\ttriplo\n\
+            1\t1000\t2000\tISCA-1\t3\t1\n\
+            1\t5000\t6000\tISCA-2\t40\tNot yet evaluated\n",
+        )?;
+
+        let db = super::load_clingen_region_records(&path_bed)?;
+        let sv = StructuralVariant {
+            chrom: String::from("1"),
+            pos: 1501,
+            end: 5500,
+            chrom2: None,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            callers: Vec::new(),
+            strand_orientation:
+                mehari::annotate::strucvars::csq::interface::StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
+            call_info: Default::default(),
+        };
+
+        assert_eq!(
+            db.overlapping_regions(&sv, &crate::common::build_chrom_map()),
+            vec![
+                RegionOverlap {
+                    id: "ISCA-1".into(),
+                    haplo: DosageScore::SufficientEvidence,
+                    triplo: DosageScore::LittleEvidence,
+                    overlap: 0.5,
+                },
+                RegionOverlap {
+                    id: "ISCA-2".into(),
+                    haplo: DosageScore::Unlikely,
+                    triplo: DosageScore::NotYetEvaluated,
+                    overlap: 0.5,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_missing() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let db = super::load_clingen_region_records(&tmpdir.join("missing.bed"))?;
+
+        assert_eq!(db.records.len(), crate::common::CHROMS.len());
+        assert!(db.records.iter().all(Vec::is_empty));
+
+        Ok(())
+    }
+}
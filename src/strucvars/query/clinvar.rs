@@ -1,31 +1,20 @@
 //! Code for working with Clinvar SV.
 
-use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
-use prost::Message;
-use thousands::Separable;
 use tracing::{info, warn};
 
-use crate::common::{reciprocal_overlap, GenomeRelease, CHROMS};
+use crate::common::{reciprocal_overlap, GenomeRelease};
 
 use super::{
+    intervals::{read_pb, IntervalDb},
     schema::ChromRange,
     schema::{Pathogenicity, StructuralVariant, SvType},
 };
 
-/// Alias for the interval tree that we use.
-type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
-
 /// Code for known Clinvar SV database.
 ///
 /// NB that the records have 1-based coordinates whereas the trees use 0-based coordinates.
-#[derive(Default, Debug)]
-pub struct ClinvarSv {
-    /// Records, stored by chromosome.
-    pub records: Vec<Vec<crate::pbs::varfish::v1::strucvars::clinvar::SvRecord>>,
-    /// Interval trees, stored by chromosome.
-    pub trees: Vec<IntervalTree>,
-}
+pub type ClinvarSv = IntervalDb<crate::pbs::varfish::v1::strucvars::clinvar::SvRecord>;
 
 impl ClinvarSv {
     /// Fetches records of overlapping entries.
@@ -40,10 +29,8 @@ impl ClinvarSv {
             .expect("invalid chromosome");
         let range = chrom_range.begin..chrom_range.end;
 
-        self.trees[chrom_idx]
-            .find(range)
-            .iter()
-            .map(|e| self.records[chrom_idx][*e.data() as usize])
+        self.find(chrom_idx, range)
+            .copied()
             .filter(|record| {
                 record.pathogenicity >= min_patho.unwrap_or(Pathogenicity::Benign) as i32
             })
@@ -65,10 +52,7 @@ impl ClinvarSv {
         let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
         let range = sv.pos.saturating_sub(1)..sv.end;
 
-        self.trees[chrom_idx]
-            .find(range)
-            .into_iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
+        self.find(chrom_idx, range)
             .filter(|record| {
                 min_overlap.map_or(true, |min_overlap| {
                     reciprocal_overlap((record.start - 1)..record.stop, (sv.pos - 1)..sv.end)
@@ -91,40 +75,16 @@ pub fn load_clinvar_sv(
 ) -> Result<ClinvarSv, anyhow::Error> {
     info!("loading binary ClinVar SV dbs");
 
-    let before_loading = std::time::Instant::now();
-    let mut result = ClinvarSv::default();
-    for _ in CHROMS {
-        result.records.push(Vec::new());
-        result.trees.push(IntervalTree::new());
-    }
-
     let path =
         std::path::Path::new(path_db).join(format!("{}/strucvars/clinvar.bin", genome_release));
-    let fcontents =
-        std::fs::read(&path).map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e))?;
-    let bg_db = crate::pbs::varfish::v1::strucvars::clinvar::SvDatabase::decode(
-        std::io::Cursor::new(fcontents),
-    )
-    .map_err(|e| anyhow::anyhow!("error decoding {:?}: {}", &path, e))?;
+    let mut result = ClinvarSv::new(path.display().to_string());
+    let bg_db: crate::pbs::varfish::v1::strucvars::clinvar::SvDatabase = read_pb(&path)?;
 
-    let mut total_count = 0;
     for record in bg_db.records.into_iter() {
-        let chrom_no = record.chrom_no as usize;
         let key = (record.start - 1)..record.stop;
-        result.trees[chrom_no].insert(key, result.records[chrom_no].len() as u32);
-        result.records[chrom_no].push(record);
-        total_count += 1;
+        result.insert(record.chrom_no as usize, key, record);
     }
-    tracing::debug!(
-        "done loading {} clinvar SV records from {:?} in {:?}",
-        &total_count.separate_with_commas(),
-        &path,
-        before_loading.elapsed()
-    );
-
-    let before_building = std::time::Instant::now();
-    result.trees.iter_mut().for_each(|tree| tree.index());
-    tracing::debug!("done building itrees in {:?}", before_building.elapsed());
+    result.index();
 
     Ok(result)
 }
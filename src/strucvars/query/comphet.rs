@@ -0,0 +1,333 @@
+//! Compound heterozygous candidates of one structural and one small variant.
+//!
+//! The small variants are taken from a `seqvars query` result file of the same case.
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::{
+    common,
+    pbs::varfish::v1::seqvars::output as pbs_output,
+    seqvars::query::schema::query::{CaseQuery, GenotypeChoice, SampleGenotypeChoice},
+};
+
+use super::schema::CallInfo;
+
+/// Phase of a small variant relative to the structural variant.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// The variants were inherited from different parents.
+    Trans,
+    /// No parent is given or no parent could be determined.
+    Unknown,
+}
+
+/// A heterozygous small variant of the index in a gene overlapped by the structural
+/// variant.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SeqvarPartner {
+    /// UUID of the record in the `seqvars query` result.
+    pub uuid: String,
+    /// HGNC ID of the gene.
+    pub hgnc_id: String,
+    /// Chromosome of the small variant.
+    pub chrom: String,
+    /// 1-based position of the small variant.
+    pub pos: i32,
+    /// Reference allele.
+    pub ref_allele: String,
+    /// Alternative allele.
+    pub alt_allele: String,
+    /// Phase relative to the structural variant.
+    pub phase: Phase,
+}
+
+/// Parent that a heterozygous variant of the index was inherited from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Parent {
+    Father,
+    Mother,
+}
+
+/// Determine the parent from the genotypes of the parents given in the pedigree.
+///
+/// Returns `None` if the genotypes are incompatible with a heterozygous variant in
+/// the index inherited from one parent and `Some(None)` if no parent is given.
+fn parent(
+    father: Option<common::Genotype>,
+    mother: Option<common::Genotype>,
+) -> Option<Option<Parent>> {
+    use common::Genotype::{Het, HomRef};
+    match (father, mother) {
+        (None, None) => Some(None),
+        (Some(Het), Some(HomRef)) | (Some(Het), None) | (None, Some(HomRef)) => {
+            Some(Some(Parent::Father))
+        }
+        (Some(HomRef), Some(Het)) | (None, Some(Het)) | (Some(HomRef), None) => {
+            Some(Some(Parent::Mother))
+        }
+        _ => None,
+    }
+}
+
+/// Parse the `GT` value `genotype` if any.
+///
+/// Unparseable and missing genotypes are treated as no-call.
+fn parse_genotype(genotype: Option<&str>) -> common::Genotype {
+    genotype
+        .and_then(|gt| gt.parse::<common::ParsedGenotype>().ok())
+        .map(|gt| gt.genotype())
+        .unwrap_or(common::Genotype::WithNoCall)
+}
+
+/// The heterozygous small variants of the index from a `seqvars query` result by gene.
+#[derive(Debug, Default)]
+pub struct SeqvarPartners {
+    /// Name of the index sample.
+    pub index: String,
+    /// Name of the father sample, if any.
+    pub father: Option<String>,
+    /// Name of the mother sample, if any.
+    pub mother: Option<String>,
+    /// Heterozygous small variants with the parent they were inherited from by HGNC ID.
+    by_hgnc_id: HashMap<String, Vec<(Option<Parent>, SeqvarPartner)>>,
+}
+
+impl SeqvarPartners {
+    /// Load from the `seqvars query` result file at `path`.
+    ///
+    /// The index and parents are taken from the recessive roles of the query in the
+    /// result header.
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let result_set =
+            crate::seqvars::export_vcf::read_result_set(crate::diff::results::open_read(path)?)?;
+        let query = result_set
+            .header
+            .query
+            .ok_or_else(|| anyhow::anyhow!("no query in header of {}", path))
+            .and_then(|query| {
+                CaseQuery::try_from(query)
+                    .map_err(|e| anyhow::anyhow!("invalid query in header of {}: {}", path, e))
+            })?;
+        let role = |choice: GenotypeChoice| {
+            query
+                .genotype
+                .sample_genotypes
+                .iter()
+                .find(|(_, SampleGenotypeChoice { genotype, .. })| *genotype == choice)
+                .map(|(sample_name, _)| sample_name.clone())
+        };
+        let index = role(GenotypeChoice::RecessiveIndex)
+            .ok_or_else(|| anyhow::anyhow!("no recessive index in query in header of {}", path))?;
+        let mut result = Self {
+            index,
+            father: role(GenotypeChoice::RecessiveFather),
+            mother: role(GenotypeChoice::RecessiveMother),
+            ..Default::default()
+        };
+        for record in &result_set.records {
+            result.insert(record);
+        }
+        Ok(result)
+    }
+
+    /// Insert `record` if it is heterozygous in the index and compatible with
+    /// inheritance from one parent.
+    fn insert(&mut self, record: &pbs_output::OutputRecord) {
+        let (Some(vcf_variant), Some(annotation)) = (
+            record.vcf_variant.as_ref(),
+            record.variant_annotation.as_ref(),
+        ) else {
+            return;
+        };
+        let Some(hgnc_id) = annotation
+            .gene
+            .as_ref()
+            .and_then(|gene| gene.identity.as_ref())
+            .map(|identity| identity.hgnc_id.clone())
+            .filter(|hgnc_id| !hgnc_id.is_empty())
+        else {
+            return;
+        };
+        let call_infos = annotation
+            .call
+            .as_ref()
+            .map(|call| call.call_infos.as_slice())
+            .unwrap_or_default();
+        let genotype = |sample: &str| {
+            parse_genotype(
+                call_infos
+                    .iter()
+                    .find(|call_info| call_info.sample == sample)
+                    .and_then(|call_info| call_info.genotype.as_deref()),
+            )
+        };
+
+        if genotype(&self.index) != common::Genotype::Het {
+            return;
+        }
+        let Some(parent) = parent(
+            self.father.as_deref().map(genotype),
+            self.mother.as_deref().map(genotype),
+        ) else {
+            return;
+        };
+        self.by_hgnc_id.entry(hgnc_id.clone()).or_default().push((
+            parent,
+            SeqvarPartner {
+                uuid: record.uuid.clone(),
+                hgnc_id,
+                chrom: vcf_variant.chrom.clone(),
+                pos: vcf_variant.pos,
+                ref_allele: vcf_variant.ref_allele.clone(),
+                alt_allele: vcf_variant.alt_allele.clone(),
+                phase: Phase::Unknown,
+            },
+        ));
+    }
+
+    /// Return the small variants in `hgnc_ids` that may be in trans with the structural
+    /// variant with `call_info`.
+    ///
+    /// The structural variant must be heterozygous in the index.  Small variants that
+    /// were inherited from the same parent as the structural variant are in cis and
+    /// skipped.
+    pub fn partners(
+        &self,
+        call_info: &IndexMap<String, CallInfo>,
+        hgnc_ids: &[String],
+    ) -> Vec<SeqvarPartner> {
+        let genotype = |sample: &str| {
+            parse_genotype(
+                call_info
+                    .get(sample)
+                    .and_then(|call_info| call_info.genotype.as_deref()),
+            )
+        };
+        if genotype(&self.index) != common::Genotype::Het {
+            return Vec::new();
+        }
+        let Some(sv_parent) = parent(
+            self.father.as_deref().map(genotype),
+            self.mother.as_deref().map(genotype),
+        ) else {
+            return Vec::new();
+        };
+
+        let mut result = Vec::new();
+        for hgnc_id in hgnc_ids {
+            for (parent, partner) in self.by_hgnc_id.get(hgnc_id).into_iter().flatten() {
+                let phase = match (sv_parent, *parent) {
+                    (Some(lhs), Some(rhs)) if lhs == rhs => continue,
+                    (Some(_), Some(_)) => Phase::Trans,
+                    _ => Phase::Unknown,
+                };
+                result.push(SeqvarPartner {
+                    phase,
+                    ..partner.clone()
+                });
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexMap;
+
+    use super::{Parent, Phase, SeqvarPartner, SeqvarPartners};
+    use crate::strucvars::query::schema::CallInfo;
+
+    fn call_info(genotypes: &[(&str, &str)]) -> IndexMap<String, CallInfo> {
+        genotypes
+            .iter()
+            .map(|(sample, gt)| {
+                (
+                    sample.to_string(),
+                    CallInfo {
+                        genotype: Some(gt.to_string()),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn partner(uuid: &str) -> SeqvarPartner {
+        SeqvarPartner {
+            uuid: uuid.into(),
+            hgnc_id: "HGNC:1100".into(),
+            chrom: "17".into(),
+            pos: 41_245_466,
+            ref_allele: "G".into(),
+            alt_allele: "A".into(),
+            phase: Phase::Unknown,
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("0/1", "0/1", "0/0", vec![("mother", Phase::Trans)])]
+    #[case("0/1", "0/0", "0/1", vec![("father", Phase::Trans)])]
+    #[case("0/0", "0/1", "0/0", vec![])]
+    #[case("0/1", "0/1", "0/1", vec![])]
+    #[case("0/1", "./.", "0/0", vec![])]
+    fn partners_trio(
+        #[case] index_gt: &str,
+        #[case] father_gt: &str,
+        #[case] mother_gt: &str,
+        #[case] expected: Vec<(&str, Phase)>,
+    ) {
+        let mut partners = SeqvarPartners {
+            index: "index".into(),
+            father: Some("father".into()),
+            mother: Some("mother".into()),
+            ..Default::default()
+        };
+        partners.by_hgnc_id.insert(
+            "HGNC:1100".into(),
+            vec![
+                (Some(Parent::Father), partner("father")),
+                (Some(Parent::Mother), partner("mother")),
+            ],
+        );
+        let call_info = call_info(&[
+            ("index", index_gt),
+            ("father", father_gt),
+            ("mother", mother_gt),
+        ]);
+
+        let result = partners.partners(&call_info, &["HGNC:1100".into(), "HGNC:1101".into()]);
+
+        let expected = expected
+            .into_iter()
+            .map(|(uuid, phase)| SeqvarPartner {
+                phase,
+                ..partner(uuid)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn partners_singleton() {
+        let mut partners = SeqvarPartners {
+            index: "index".into(),
+            ..Default::default()
+        };
+        partners
+            .by_hgnc_id
+            .insert("HGNC:1100".into(), vec![(None, partner("single"))]);
+
+        assert_eq!(
+            partners.partners(&call_info(&[("index", "0/1")]), &["HGNC:1100".into()]),
+            vec![partner("single")]
+        );
+        assert!(partners
+            .partners(&call_info(&[("index", "1/1")]), &["HGNC:1100".into()])
+            .is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+//! Evaluation of query expressions on annotated structural variants.
+
+use crate::common::expression::{self, Expression, Value};
+
+use super::{
+    bgdbs::BgDbOverlaps, clingen::RegionOverlap, masked::MaskedBreakpointCount,
+    schema::StructuralVariant,
+};
+
+/// Fields that may be referenced in expressions.
+pub const FIELDS: &[&str] = &[
+    "chrom",
+    "pos",
+    "end",
+    "chrom2",
+    "sv_type",
+    "sv_sub_type",
+    "sv_length",
+    "dbvar_count",
+    "dgv_count",
+    "dgv_gs_count",
+    "dgv_gs_carrier_freq",
+    "g1k_count",
+    "gnomad_genomes_count",
+    "gnomad_exomes_count",
+    "gnomad_sv4_count",
+    "gnomad_sv4_carrier_freq",
+    "inhouse_count",
+    "masked_repeat",
+    "masked_segdup",
+    "gene_count",
+    "clingen_dosage_sensitive",
+];
+
+/// Ensure that `expression` only references known fields.
+pub fn check_fields(expression: &Expression) -> Result<(), expression::Error> {
+    expression.check_fields(FIELDS)
+}
+
+/// Annotations of a structural variant computed by the interpreter.
+#[derive(Debug, Clone, Copy)]
+pub struct Annotations<'a> {
+    /// Overlap counts with background databases.
+    pub counts: &'a BgDbOverlaps,
+    /// Number of masked breakpoints.
+    pub masked: &'a MaskedBreakpointCount,
+    /// HGNC IDs of overlapping genes.
+    pub ovl_hgnc_ids: &'a [String],
+    /// Overlapping ClinGen regions.
+    pub clingen_regions: &'a [RegionOverlap],
+}
+
+/// Return the serialized name of an enum value, e.g., `"DEL"`.
+fn serde_name<T: serde::Serialize>(value: &T) -> Option<Value> {
+    match serde_json::to_value(value).ok()? {
+        serde_json::Value::String(name) => Some(Value::String(name)),
+        _ => None,
+    }
+}
+
+/// Return the value of the field `name` for `sv` with the given `annotations`.
+pub fn value(sv: &StructuralVariant, annotations: &Annotations, name: &str) -> Option<Value> {
+    Some(match name {
+        "chrom" => Value::from(sv.chrom.as_str()),
+        "pos" => Value::from(sv.pos),
+        "end" => Value::from(sv.end),
+        "chrom2" => Value::from(sv.chrom2.as_deref()?),
+        "sv_type" => serde_name(&sv.sv_type)?,
+        "sv_sub_type" => serde_name(&sv.sv_sub_type)?,
+        "sv_length" => Value::from(sv.size()?),
+        "dbvar_count" => Value::from(annotations.counts.dbvar),
+        "dgv_count" => Value::from(annotations.counts.dgv),
+        "dgv_gs_count" => Value::from(annotations.counts.dgv_gs),
+        "dgv_gs_carrier_freq" => Value::from(annotations.counts.dgv_gs_carrier_freq?),
+        "g1k_count" => Value::from(annotations.counts.g1k),
+        "gnomad_genomes_count" => Value::from(annotations.counts.gnomad_genomes),
+        "gnomad_exomes_count" => Value::from(annotations.counts.gnomad_exomes),
+        "gnomad_sv4_count" => Value::from(annotations.counts.gnomad_sv4),
+        "gnomad_sv4_carrier_freq" => Value::from(annotations.counts.gnomad_sv4_carrier_freq?),
+        "inhouse_count" => Value::from(annotations.counts.inhouse),
+        "masked_repeat" => Value::from(annotations.masked.repeat),
+        "masked_segdup" => Value::from(annotations.masked.segdup),
+        "gene_count" => Value::from(annotations.ovl_hgnc_ids.len() as u32),
+        "clingen_dosage_sensitive" => Value::from(
+            annotations
+                .clingen_regions
+                .iter()
+                .any(|region| region.is_dosage_sensitive(sv.sv_type)),
+        ),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexMap;
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
+    use crate::common::expression::Expression;
+    use crate::strucvars::query::{
+        bgdbs::BgDbOverlaps,
+        schema::{StructuralVariant, SvSubType, SvType},
+    };
+
+    use super::Annotations;
+
+    #[rstest::rstest]
+    #[case("sv_type == 'DEL' && sv_length > 1000", true)]
+    #[case("sv_sub_type == 'DEL:ME'", false)]
+    #[case("gnomad_genomes_count < 5 || inhouse_count == 0", true)]
+    #[case("gene_count > 0", false)]
+    #[case("gnomad_sv4_carrier_freq < 0.01", true)]
+    #[case("chrom2 == '2'", false)]
+    #[case("!clingen_dosage_sensitive", true)]
+    fn value(#[case] source: &str, #[case] expected: bool) {
+        let sv = StructuralVariant {
+            chrom: "1".to_owned(),
+            pos: 1000,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end: 3000,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
+            call_info: IndexMap::new(),
+        };
+        let counts = BgDbOverlaps {
+            gnomad_genomes: 10,
+            gnomad_sv4_carrier_freq: Some(0.001),
+            ..Default::default()
+        };
+        let annotations = Annotations {
+            counts: &counts,
+            masked: &Default::default(),
+            ovl_hgnc_ids: &[],
+            clingen_regions: &[],
+        };
+
+        let expression = Expression::parse(source).unwrap();
+        assert!(super::check_fields(&expression).is_ok());
+        assert_eq!(
+            expression.eval(|name| super::value(&sv, &annotations, name)),
+            expected
+        );
+    }
+}
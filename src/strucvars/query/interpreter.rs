@@ -7,6 +7,8 @@ use tracing::{trace, warn};
 
 use super::{
     bgdbs::BgDbOverlaps,
+    clingen::RegionOverlap,
+    expression,
     masked::MaskedBreakpointCount,
     schema::{
         CaseQuery, Genotype, GenotypeChoice, Range, StructuralVariant, SvSubType, SvType,
@@ -25,6 +27,14 @@ pub fn overlaps(s1: i32, e1: i32, s2: i32, e2: i32) -> bool {
     s1 < e2 && e1 > s2
 }
 
+/// Returns whether the carrier frequency `freq` is within `min` and `max`; passes if the
+/// frequency is unknown.
+fn passes_carrier_freq(freq: Option<f32>, min: Option<f32>, max: Option<f32>) -> bool {
+    freq.map_or(true, |freq| {
+        min.map_or(true, |min| freq >= min) && max.map_or(true, |max| freq <= max)
+    })
+}
+
 /// Hold data structures that support the interpretation of one `CaseQuery`
 /// to multiple `StructuralVariant` records.
 #[derive(Debug)]
@@ -119,6 +129,7 @@ impl QueryInterpreter {
                         GenotypeChoice::Hom => Some(Genotype::Hom),
                         GenotypeChoice::Variant => Some(Genotype::Variant),
                         GenotypeChoice::NonVariant => Some(Genotype::NonVariant),
+                        GenotypeChoice::Mosaic => Some(Genotype::Mosaic),
                         _ => {
                             warn!("invalid matching genotype: {:?}", &criteria.genotype);
                             None
@@ -177,9 +188,17 @@ impl QueryInterpreter {
         } else {
             (true, true)
         };
+        let pass_sv_size_by_type = match (sv_size, self.query.sv_size_by_type.get(&sv.sv_type)) {
+            (Some(sv_size), Some(range)) => range.contains(sv_size),
+            _ => true,
+        };
 
-        trace!("does SV pass selection? pass_sv_type={} pass_sv_sub_type={} pass_sv_size_min={} pass_sv_size_max={}", pass_sv_type, pass_sv_sub_type, pass_sv_size_min, pass_sv_size_max);
-        pass_sv_type && pass_sv_sub_type && pass_sv_size_min && pass_sv_size_max
+        trace!("does SV pass selection? pass_sv_type={} pass_sv_sub_type={} pass_sv_size_min={} pass_sv_size_max={} pass_sv_size_by_type={}", pass_sv_type, pass_sv_sub_type, pass_sv_size_min, pass_sv_size_max, pass_sv_size_by_type);
+        pass_sv_type
+            && pass_sv_sub_type
+            && pass_sv_size_min
+            && pass_sv_size_max
+            && pass_sv_size_by_type
     }
 
     /// Determine whether an SV record passes the genomic region criteria.
@@ -285,16 +304,36 @@ impl QueryInterpreter {
             || counts.dbvar <= self.query.svdb_dbvar_max_count.unwrap_or(counts.dbvar);
         let passes_g1k = !self.query.svdb_g1k_enabled
             || counts.g1k <= self.query.svdb_g1k_max_count.unwrap_or(counts.g1k);
+        let passes_gnomad_sv4 = !self.query.svdb_gnomad_sv4_enabled
+            || (counts.gnomad_sv4
+                <= self
+                    .query
+                    .svdb_gnomad_sv4_max_count
+                    .unwrap_or(counts.gnomad_sv4)
+                && passes_carrier_freq(
+                    counts.gnomad_sv4_carrier_freq,
+                    self.query.svdb_gnomad_sv4_min_carrier_freq,
+                    self.query.svdb_gnomad_sv4_max_carrier_freq,
+                ));
+        let passes_dgv_gs = passes_dgv_gs
+            && (!self.query.svdb_dgv_gs_enabled
+                || passes_carrier_freq(
+                    counts.dgv_gs_carrier_freq,
+                    self.query.svdb_dgv_gs_min_carrier_freq,
+                    self.query.svdb_dgv_gs_max_carrier_freq,
+                ));
         let passes_inhouse = !self.query.svdb_inhouse_enabled
             || counts.inhouse <= self.query.svdb_inhouse_max_count.unwrap_or(counts.inhouse);
 
         trace!(
             "does SV pass counts? passes_dgv={}, passes_dgv_gs={}, passes_gnomad_genomes={}, \
-            passes_gnomad_exomes={}, passes_dbvar={}, passes_g1k={}, passes_inhouse={}",
+            passes_gnomad_exomes={}, passes_gnomad_sv4={}, passes_dbvar={}, passes_g1k={}, \
+            passes_inhouse={}",
             passes_dgv,
             passes_dgv_gs,
             passes_gnomad_genomes,
             passes_gnomad_exomes,
+            passes_gnomad_sv4,
             passes_dbvar,
             passes_g1k,
             passes_inhouse
@@ -304,11 +343,25 @@ impl QueryInterpreter {
             && passes_dgv_gs
             && passes_gnomad_genomes
             && passes_gnomad_exomes
+            && passes_gnomad_sv4
             && passes_dbvar
             && passes_g1k
             && passes_inhouse
     }
 
+    /// Determine whether `sv` passes the copy number ranges of the samples.
+    ///
+    /// Samples with a range must have a copy number within it, calls without a copy
+    /// number fail.
+    pub fn passes_copy_numbers(&self, sv: &StructuralVariant) -> bool {
+        self.query.copy_number_ranges.iter().all(|(sample, range)| {
+            sv.call_info
+                .get(sample)
+                .and_then(|call_info| call_info.copy_number)
+                .is_some_and(|copy_number| range.contains(copy_number))
+        })
+    }
+
     /// Determine whether the `sv` passes the gene allow list filter.
     pub fn passes_genes(&self, ovl_hgvs_ids: &[String]) -> bool {
         if let Some(hgvs_allowlist) = self.hgvs_allowlist.as_ref() {
@@ -337,20 +390,45 @@ impl QueryInterpreter {
                 .any(|effect| self.query.tx_effects.contains(effect))
     }
 
+    /// Determine whether `sv` passes the ClinGen dosage-sensitive region filter.
+    pub fn passes_clingen_regions(
+        &self,
+        sv: &StructuralVariant,
+        regions: &[RegionOverlap],
+    ) -> bool {
+        !self.query.require_clingen_dosage_region
+            || regions
+                .iter()
+                .any(|region| region.is_dosage_sensitive(sv.sv_type))
+    }
+
+    /// Determine whether `sv` with the given `annotations` passes the query expression.
+    pub fn passes_expression(
+        &self,
+        sv: &StructuralVariant,
+        annotations: &expression::Annotations,
+    ) -> bool {
+        self.query.expression.as_ref().map_or(true, |expr| {
+            expr.eval(|name| expression::value(sv, annotations, name))
+        })
+    }
+
     /// Determine whether the annotated `StructuralVariant` passes all criteria.
-    pub fn passes<CountBg, CountMasked, OvlHgvsIds, TxEffects>(
+    pub fn passes<CountBg, CountMasked, OvlHgvsIds, TxEffects, ClingenRegions>(
         &self,
         sv: &StructuralVariant,
         count_bg: &mut CountBg,
         count_masked: &mut CountMasked,
         ovl_hgvs_ids: &mut OvlHgvsIds,
         tx_effects: &mut TxEffects,
+        clingen_regions: &mut ClingenRegions,
     ) -> Result<PassesResult, anyhow::Error>
     where
         CountBg: FnMut(&StructuralVariant) -> BgDbOverlaps,
         CountMasked: FnMut(&StructuralVariant) -> MaskedBreakpointCount,
         OvlHgvsIds: FnMut(&StructuralVariant) -> Vec<String>,
         TxEffects: FnMut(&StructuralVariant) -> Vec<TranscriptEffect>,
+        ClingenRegions: FnMut(&StructuralVariant) -> Vec<RegionOverlap>,
     {
         // We first check for matching genotype.  If this succeeds then we execute the
         // overlapper for known pathogenic and then for frequency in background.
@@ -360,22 +438,46 @@ impl QueryInterpreter {
             return Ok(Default::default());
         }
 
-        let passes_result = self.passes_genotype(sv, &count_masked(sv))?;
+        let masked = count_masked(sv);
+        let passes_result = self.passes_genotype(sv, &masked)?;
         if !passes_result.pass_all {
-            Ok(Default::default())
-        } else if !self.passes_genes(&ovl_hgvs_ids(sv)) {
+            return Ok(Default::default());
+        }
+        if !self.passes_copy_numbers(sv) {
+            trace!("... SV does not pass copy number ranges");
+            return Ok(Default::default());
+        }
+        let ovl_hgnc_ids = ovl_hgvs_ids(sv);
+        if !self.passes_genes(&ovl_hgnc_ids) {
             trace!("... SV does not gene allow list filter");
-            Ok(Default::default())
-        } else if !self.passes_counts(&count_bg(sv)) {
+            return Ok(Default::default());
+        }
+        let counts = count_bg(sv);
+        if !self.passes_counts(&counts) {
             trace!("... SV does not pass bg counts filter");
-            Ok(Default::default())
-        } else if !self.passes_effects(&tx_effects(sv)) {
+            return Ok(Default::default());
+        }
+        if !self.passes_effects(&tx_effects(sv)) {
             trace!("... SV does not pass tx effect filter");
-            Ok(Default::default())
-        } else {
-            trace!("... SV passes filter");
-            Ok(passes_result)
+            return Ok(Default::default());
+        }
+        let regions = clingen_regions(sv);
+        if !self.passes_clingen_regions(sv, &regions) {
+            trace!("... SV does not pass ClinGen region filter");
+            return Ok(Default::default());
         }
+        let annotations = expression::Annotations {
+            counts: &counts,
+            masked: &masked,
+            ovl_hgnc_ids: &ovl_hgnc_ids,
+            clingen_regions: &regions,
+        };
+        if !self.passes_expression(sv, &annotations) {
+            trace!("... SV does not pass expression filter");
+            return Ok(Default::default());
+        }
+        trace!("... SV passes filter");
+        Ok(passes_result)
     }
 }
 
@@ -384,8 +486,9 @@ mod tests {
     use indexmap::IndexMap;
     use mehari::annotate::strucvars::csq::interface::StrandOrientation;
 
+    use crate::common::expression::Expression;
     use crate::strucvars::query::schema::{
-        CallInfo, GenomicRegion, GenotypeChoice, GenotypeCriteria,
+        CallInfo, CopyNumberRange, GenomicRegion, GenotypeChoice, GenotypeCriteria, SvSizeRange,
     };
 
     use super::*;
@@ -423,6 +526,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -446,6 +551,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -469,6 +576,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -493,6 +602,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -516,6 +627,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -539,12 +652,111 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
         assert!(!interpreter.passes_selection(&sv_fail));
     }
 
+    #[rstest::rstest]
+    #[case(SvType::Del, 101, true)]
+    #[case(SvType::Del, 1_001, false)]
+    #[case(SvType::Dup, 101, false)]
+    #[case(SvType::Dup, 1_001, true)]
+    #[case(SvType::Inv, 1_001, true)]
+    fn test_query_interpreter_passes_size_by_type(
+        #[case] sv_type: SvType,
+        #[case] end: i32,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            sv_size_by_type: IndexMap::from([
+                (
+                    SvType::Del,
+                    SvSizeRange {
+                        min: None,
+                        max: Some(500),
+                    },
+                ),
+                (
+                    SvType::Dup,
+                    SvSizeRange {
+                        min: Some(500),
+                        max: None,
+                    },
+                ),
+            ]),
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 100,
+            sv_type,
+            sv_sub_type: match sv_type {
+                SvType::Del => SvSubType::Del,
+                SvType::Dup => SvSubType::Dup,
+                _ => SvSubType::Inv,
+            },
+            chrom2: None,
+            end,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
+            call_info: IndexMap::new(),
+        };
+
+        assert_eq!(interpreter.passes_selection(&sv), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(Some(5), true)]
+    #[case(Some(4), true)]
+    #[case(Some(2), false)]
+    #[case(None, false)]
+    fn test_query_interpreter_passes_copy_numbers(
+        #[case] copy_number: Option<u32>,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            copy_number_ranges: IndexMap::from([(
+                "sample".to_string(),
+                CopyNumberRange {
+                    min: Some(4),
+                    max: None,
+                },
+            )]),
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 100,
+            sv_type: SvType::Cnv,
+            sv_sub_type: SvSubType::Cnv,
+            chrom2: None,
+            end: 10_000,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::NotApplicable,
+            cipos: None,
+            ciend: None,
+            call_info: IndexMap::from([(
+                "sample".to_string(),
+                CallInfo {
+                    copy_number,
+                    ..Default::default()
+                },
+            )]),
+        };
+
+        assert_eq!(interpreter.passes_copy_numbers(&sv), expected);
+    }
+
     #[test]
     fn test_query_interpreter_passes_genomic_region_pass_linear_overlap() {
         let query = CaseQuery {
@@ -562,6 +774,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -585,6 +799,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -608,6 +824,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -631,6 +849,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -654,6 +874,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -677,6 +899,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -700,6 +924,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -723,6 +949,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -746,6 +974,8 @@ mod tests {
             end: 1000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -769,6 +999,8 @@ mod tests {
             end: 1000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -792,6 +1024,8 @@ mod tests {
             end: 100,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -815,6 +1049,8 @@ mod tests {
             end: 1000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -838,6 +1074,8 @@ mod tests {
             end: 1000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -861,6 +1099,8 @@ mod tests {
             end: 1000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
 
@@ -896,6 +1136,7 @@ mod tests {
             g1k: 5,
             inhouse: 5,
             dbvar: 5,
+            ..Default::default()
         };
 
         assert!(interpreter.passes_counts(&counts_pass));
@@ -930,11 +1171,47 @@ mod tests {
             g1k: 11,
             inhouse: 11,
             dbvar: 11,
+            ..Default::default()
         };
 
         assert!(!interpreter.passes_counts(&counts_fail));
     }
 
+    #[rstest::rstest]
+    #[case::no_thresholds(None, None, Some(0.2), true)]
+    #[case::below_max(None, Some(0.01), Some(0.005), true)]
+    #[case::above_max(None, Some(0.01), Some(0.02), false)]
+    #[case::below_min(Some(0.01), None, Some(0.0), false)]
+    #[case::unknown_freq(Some(0.01), Some(0.02), None, true)]
+    fn test_query_interpreter_passes_counts_carrier_freq(
+        #[case] min_carrier_freq: Option<f32>,
+        #[case] max_carrier_freq: Option<f32>,
+        #[case] carrier_freq: Option<f32>,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            svdb_gnomad_sv4_enabled: true,
+            svdb_gnomad_sv4_min_carrier_freq: min_carrier_freq,
+            svdb_gnomad_sv4_max_carrier_freq: max_carrier_freq,
+            svdb_dgv_gs_enabled: true,
+            svdb_dgv_gs_min_carrier_freq: min_carrier_freq,
+            svdb_dgv_gs_max_carrier_freq: max_carrier_freq,
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let gnomad_sv4 = BgDbOverlaps {
+            gnomad_sv4_carrier_freq: carrier_freq,
+            ..Default::default()
+        };
+        assert_eq!(interpreter.passes_counts(&gnomad_sv4), expected);
+        let dgv_gs = BgDbOverlaps {
+            dgv_gs_carrier_freq: carrier_freq,
+            ..Default::default()
+        };
+        assert_eq!(interpreter.passes_counts(&dgv_gs), expected);
+    }
+
     #[test]
     fn test_query_interpreter_pass_genotype_fail_no_match() -> Result<(), anyhow::Error> {
         let query = CaseQuery {
@@ -983,6 +1260,8 @@ mod tests {
             end: 2000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::from([("sample".to_owned(), call_info.clone())]),
         };
 
@@ -1119,6 +1398,8 @@ mod tests {
             end: 12345,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::NotApplicable,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::from([(
                 "sample".to_owned(),
                 CallInfo {
@@ -1194,6 +1475,8 @@ mod tests {
             end: 2000,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::from([("sample".to_owned(), call_info)]),
         };
 
@@ -1219,6 +1502,8 @@ mod tests {
             end: 200,
             callers: Vec::new(),
             strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
         let counts_pass = BgDbOverlaps {
@@ -1229,6 +1514,7 @@ mod tests {
             g1k: 5,
             inhouse: 5,
             dbvar: 5,
+            ..Default::default()
         };
 
         assert!(
@@ -1238,6 +1524,7 @@ mod tests {
                     &mut |_sv| counts_pass.clone(),
                     &mut |_sv| { Default::default() },
                     &mut |_sv| { Default::default() },
+                    &mut |_sv| { Default::default() },
                     &mut |_sv| { Default::default() }
                 )?
                 .pass_all
@@ -1245,4 +1532,110 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::het(GenotypeChoice::Any, 0.5, Some(Genotype::Het))]
+    #[case::mosaic(GenotypeChoice::Any, 0.85, Some(Genotype::Mosaic))]
+    #[case::mosaic_selected(GenotypeChoice::Mosaic, 0.85, Some(Genotype::Mosaic))]
+    #[case::het_not_mosaic(GenotypeChoice::Mosaic, 0.5, None)]
+    #[case::ref_(GenotypeChoice::Any, 0.99, None)]
+    fn test_query_interpreter_pass_genotype_mosaic(
+        #[case] genotype: GenotypeChoice,
+        #[case] average_normalized_cov: f32,
+        #[case] expected: Option<Genotype>,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            genotype: IndexMap::from([("sample".to_owned(), genotype)]),
+            genotype_criteria: vec![
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    min_allele_fraction: Some(0.35),
+                    ..GenotypeCriteria::new(GenotypeChoice::Het)
+                },
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    min_allele_fraction: Some(0.05),
+                    max_allele_fraction: Some(0.35),
+                    ..GenotypeCriteria::new(GenotypeChoice::Mosaic)
+                },
+            ],
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 1000,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end: 2000,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
+            call_info: IndexMap::from([(
+                "sample".to_owned(),
+                CallInfo {
+                    average_normalized_cov: Some(average_normalized_cov),
+                    ..Default::default()
+                },
+            )]),
+        };
+
+        let result = interpreter.passes_genotype(&sv, &Default::default())?;
+        assert_eq!(result.pass_all, expected.is_some());
+        assert_eq!(result.effective["sample"], expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(None, true)]
+    #[case(Some("sv_length > 50 && dgv_count < 10"), true)]
+    #[case(Some("sv_length > 50 && dgv_count < 5"), false)]
+    fn test_query_interpreter_passes_expression(
+        #[case] expression: Option<&str>,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            expression: expression.map(Expression::parse).transpose()?,
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 100,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end: 200,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
+            call_info: IndexMap::new(),
+        };
+        let counts = BgDbOverlaps {
+            dgv: 5,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            interpreter
+                .passes(
+                    &sv,
+                    &mut |_sv| counts.clone(),
+                    &mut |_sv| { Default::default() },
+                    &mut |_sv| { Default::default() },
+                    &mut |_sv| { Default::default() },
+                    &mut |_sv| { Default::default() }
+                )?
+                .pass_all,
+            expected
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,196 @@
+//! Generic per-chromosome interval database that the annotation tracks build upon.
+
+use std::{ops::Range, path::Path, time::Instant};
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use indexmap::IndexMap;
+use mehari::common::io::std::open_read_maybe_gz;
+use serde::de::DeserializeOwned;
+use thousands::Separable;
+
+use crate::common::{trace_rss_now, CHROMS};
+
+use super::schema::ChromRange;
+
+/// Alias for the interval tree that we use.
+pub type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+
+/// Access to the 0-based begin and end positions of a record.
+pub trait BeginEnd {
+    /// 0-base begin position
+    fn begin(&self) -> i32;
+    /// 0-based end position
+    fn end(&self) -> i32;
+}
+
+/// Metadata of an `IntervalDb`.
+#[derive(Debug, Default, Clone)]
+pub struct Metadata {
+    /// Source of the records, e.g., the path of the loaded file.
+    pub source: String,
+    /// Total number of records.
+    pub record_count: usize,
+}
+
+/// Records stored by chromosome together with interval trees for overlap queries.
+///
+/// The records are inserted with `insert()` and the trees must be built with `index()`
+/// before querying.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IntervalDb<T> {
+    /// Metadata of the database.
+    #[serde(skip)]
+    pub meta: Metadata,
+    /// Records, stored by chromosome.
+    pub records: Vec<Vec<T>>,
+    /// Interval trees, stored by chromosome.
+    pub trees: Vec<IntervalTree>,
+}
+
+impl<T> Default for IntervalDb<T> {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl<T> IntervalDb<T> {
+    /// Construct an empty database for all canonical chromosomes.
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            meta: Metadata {
+                source: source.into(),
+                record_count: 0,
+            },
+            records: CHROMS.iter().map(|_| Vec::new()).collect(),
+            trees: CHROMS.iter().map(|_| IntervalTree::new()).collect(),
+        }
+    }
+
+    /// Insert `record` on chromosome `chrom_idx` with the 0-based interval `key`.
+    pub fn insert(&mut self, chrom_idx: usize, key: Range<i32>, record: T) {
+        self.trees[chrom_idx].insert(key, self.records[chrom_idx].len() as u32);
+        self.records[chrom_idx].push(record);
+        self.meta.record_count += 1;
+    }
+
+    /// Build the interval trees after all records have been inserted.
+    pub fn index(&mut self) {
+        let before_building = Instant::now();
+        self.trees.iter_mut().for_each(|tree| tree.index());
+        tracing::debug!(
+            "done building itrees for {} records from {:?} in {:?}",
+            self.meta.record_count.separate_with_commas(),
+            &self.meta.source,
+            before_building.elapsed()
+        );
+    }
+
+    /// Return the records on chromosome `chrom_idx` overlapping with the 0-based `range`.
+    pub fn find(&self, chrom_idx: usize, range: Range<i32>) -> impl Iterator<Item = &T> + '_ {
+        self.trees[chrom_idx]
+            .find(range)
+            .into_iter()
+            .map(move |e| &self.records[chrom_idx][*e.data() as usize])
+    }
+
+    /// Return whether any record on chromosome `chrom_idx` overlaps with the 0-based `range`.
+    pub fn any(&self, chrom_idx: usize, range: Range<i32>) -> bool {
+        !self.trees[chrom_idx].find(range).is_empty()
+    }
+
+    /// Fetch copies of the records that overlap with `chrom_range`.
+    pub fn fetch(&self, chrom_range: &ChromRange, chrom_map: &IndexMap<String, usize>) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let chrom_idx = *chrom_map
+            .get(&chrom_range.chromosome)
+            .expect("invalid chromosome");
+        self.find(chrom_idx, chrom_range.begin..chrom_range.end)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Read and decode a protobuf message from the binary file at `path`, as created by
+/// `strucvars txt-to-bin`.
+pub fn read_pb<M: prost::Message + Default>(path: &Path) -> Result<M, anyhow::Error> {
+    let before_loading = Instant::now();
+    let fcontents =
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e))?;
+    let result = M::decode(std::io::Cursor::new(fcontents))
+        .map_err(|e| anyhow::anyhow!("error decoding {:?}: {}", &path, e))?;
+    tracing::debug!("done reading {:?} in {:?}", path, before_loading.elapsed());
+
+    trace_rss_now();
+
+    Ok(result)
+}
+
+/// Read the records from the BED file at `path`, optionally gzip-compressed.
+///
+/// The header is written as comment and ignored.
+pub fn read_bed<R: DeserializeOwned>(path: &Path) -> Result<Vec<R>, anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false) // BED has no header
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
+    reader
+        .deserialize()
+        .map(|record| record.map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::IntervalDb;
+    use crate::strucvars::query::schema::ChromRange;
+
+    #[rstest::fixture]
+    fn db() -> IntervalDb<&'static str> {
+        let mut db = IntervalDb::new("test");
+        db.insert(0, 0..10, "a");
+        db.insert(0, 5..15, "b");
+        db.insert(1, 100..110, "c");
+        db.index();
+        db
+    }
+
+    #[rstest::rstest]
+    #[case(0, 0..5, vec!["a"])]
+    #[case(0, 6..12, vec!["a", "b"])]
+    #[case(0, 15..20, vec![])]
+    #[case(1, 0..200, vec!["c"])]
+    fn find(
+        db: IntervalDb<&'static str>,
+        #[case] chrom_idx: usize,
+        #[case] range: std::ops::Range<i32>,
+        #[case] expected: Vec<&'static str>,
+    ) {
+        let mut result = db
+            .find(chrom_idx, range.clone())
+            .cloned()
+            .collect::<Vec<_>>();
+        result.sort();
+
+        assert_eq!(result, expected);
+        assert_eq!(db.any(chrom_idx, range), !expected.is_empty());
+    }
+
+    #[rstest::rstest]
+    fn fetch(db: IntervalDb<&'static str>) {
+        let result = db.fetch(
+            &ChromRange {
+                chromosome: String::from("chr2"),
+                begin: 90,
+                end: 101,
+            },
+            &crate::common::build_chrom_map(),
+        );
+
+        assert_eq!(result, vec!["c"]);
+        assert_eq!(db.meta.record_count, 3);
+        assert_eq!(db.meta.source, "test");
+    }
+}
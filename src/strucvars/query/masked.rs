@@ -1,34 +1,20 @@
 //! Overlap with masked regions (e.g., repeats, segmental duplications)
 
-use std::{path::Path, time::Instant};
+use std::path::Path;
 
-use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
-use prost::Message;
 use tracing::info;
 
-use crate::{
-    common::{trace_rss_now, GenomeRelease, CHROMS},
-    pbs,
-};
+use crate::{common::GenomeRelease, pbs};
 
 use super::{
-    bgdbs::BeginEnd,
+    intervals::{read_pb, BeginEnd, IntervalDb},
     schema::ChromRange,
     schema::{StructuralVariant, SvType},
 };
 
-/// Alias for the interval tree that we use.
-type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
-
 /// Code for masked regions overlappers.
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-pub struct MaskedDb {
-    /// Records, stored by chromosome.
-    pub records: Vec<Vec<MaskedDbRecord>>,
-    /// Interval trees, stored by chromosome.
-    pub trees: Vec<IntervalTree>,
-}
+pub type MaskedDb = IntervalDb<MaskedDbRecord>;
 
 /// Result for `MaskedDb::fetch_records`.
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
@@ -62,18 +48,8 @@ impl MaskedDb {
         let range_right = genomic_region.end.saturating_sub(1)..genomic_region.end;
 
         FetchRecordsResult {
-            left: self.trees[chrom_idx]
-                .find(range_left)
-                .iter()
-                .map(|e| &self.records[chrom_idx][*e.data() as usize])
-                .cloned()
-                .collect(),
-            right: self.trees[chrom_idx]
-                .find(range_right)
-                .iter()
-                .map(|e| &self.records[chrom_idx][*e.data() as usize])
-                .cloned()
-                .collect(),
+            left: self.find(chrom_idx, range_left).cloned().collect(),
+            right: self.find(chrom_idx, range_right).cloned().collect(),
         }
     }
 
@@ -101,8 +77,8 @@ impl MaskedDb {
             (sv.pos..(sv.pos + 1), sv.end.saturating_sub(1)..sv.end)
         };
 
-        let any_left = !self.trees[chrom_idx].find(range_left).is_empty();
-        let any_right = !self.trees[chrom_idx].find(range_right).is_empty();
+        let any_left = self.any(chrom_idx, range_left);
+        let any_right = self.any(chrom_idx, range_right);
 
         (any_left as u32) + (any_right as u32)
     }
@@ -132,39 +108,21 @@ impl BeginEnd for MaskedDbRecord {
 pub fn load_masked_db_records(path: &Path) -> Result<MaskedDb, anyhow::Error> {
     tracing::debug!("loading binary masked db records from {:?}", path);
 
-    let before_loading = Instant::now();
-    let mut result = MaskedDb::default();
-    for _ in CHROMS {
-        result.records.push(Vec::new());
-        result.trees.push(IntervalTree::new());
-    }
-
-    let fcontents =
-        std::fs::read(path).map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e))?;
-    let masked_db =
-        pbs::varfish::v1::strucvars::bgdb::MaskedDatabase::decode(std::io::Cursor::new(fcontents))
-            .map_err(|e| anyhow::anyhow!("error decoding {:?}: {}", &path, e))?;
+    let mut result = MaskedDb::new(path.display().to_string());
+    let masked_db: pbs::varfish::v1::strucvars::bgdb::MaskedDatabase = read_pb(path)?;
 
     for record in masked_db.records.into_iter() {
-        let chrom_no = record.chrom_no as usize;
         let key = (record.start - 1)..record.stop;
-        result.trees[chrom_no].insert(key, result.records[chrom_no].len() as u32);
-        result.records[chrom_no].push(MaskedDbRecord {
-            begin: record.start - 1,
-            end: record.stop,
-        });
+        result.insert(
+            record.chrom_no as usize,
+            key,
+            MaskedDbRecord {
+                begin: record.start - 1,
+                end: record.stop,
+            },
+        );
     }
-    tracing::debug!(
-        "done loading masked dbs from {:?} in {:?}",
-        path,
-        before_loading.elapsed()
-    );
-
-    let before_building = Instant::now();
-    result.trees.iter_mut().for_each(|tree| tree.index());
-    tracing::debug!("done building itrees in {:?}", before_building.elapsed());
-
-    trace_rss_now();
+    result.index();
 
     Ok(result)
 }
@@ -252,9 +210,10 @@ mod test {
                     end: 110,
                 },
             ]],
-            trees: vec![super::IntervalTree::from_iter(
+            trees: vec![crate::strucvars::query::intervals::IntervalTree::from_iter(
                 vec![(0..10, 0), (5..15, 1), (100..110, 2)].into_iter(),
             )],
+            ..Default::default()
         }
     }
 
@@ -304,6 +263,8 @@ mod test {
             callers: Vec::new(),
             strand_orientation:
                 mehari::annotate::strucvars::csq::interface::StrandOrientation::ThreeToFive,
+            cipos: None,
+            ciend: None,
             call_info: Default::default(),
         };
 
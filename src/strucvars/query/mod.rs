@@ -1,17 +1,22 @@
 //! Code implementing the "strucvars query" sub command.
 
+pub mod batch;
 pub mod bgdbs;
+pub mod clingen;
 pub mod clinvar;
+pub mod comphet;
+pub mod expression;
 pub mod genes;
 pub mod interpreter;
+pub mod intervals;
 pub mod masked;
 pub mod pathogenic;
 pub mod schema;
 pub mod tads;
+pub mod tx_effects;
 
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
-    fs::File,
     time::Instant,
 };
 
@@ -24,8 +29,9 @@ use mehari::{
         seqvars::{provider::TxIntervalTrees, CHROM_TO_CHROM_NO},
         strucvars::csq::interface::StrandOrientation,
     },
+    common::estimate::{disk_usage, worker_db_usage, CostModel, Estimate, InputStats},
     common::noodles::open_vcf_reader,
-    pbs::txs::{Strand, Transcript, TxSeqDatabase},
+    pbs::txs::TxSeqDatabase,
 };
 
 use noodles::vcf;
@@ -35,27 +41,33 @@ use thousands::Separable;
 use uuid::Uuid;
 
 use crate::{
-    common::{build_chrom_map, numeric_gene_id, trace_rss_now},
-    common::{GenomeRelease, TadSet as TadSetChoice},
+    common::canonical_json,
+    common::errors::{warn_or_fail, ClassifyExt as _, ErrorClass},
+    common::header_check::header_case_uuid,
+    common::query_json,
+    common::{build_chrom_map, numeric_gene_id, trace_rss_now, uuid_v5, GenomeRelease},
+    strucvars::ingest::copy_number::CoverageTrack,
     strucvars::query::{
-        interpreter::QueryInterpreter, pathogenic::Record as KnownPathogenicRecord,
-        schema::CaseQuery, schema::StructuralVariant,
+        comphet::{SeqvarPartner, SeqvarPartners},
+        interpreter::QueryInterpreter,
+        pathogenic::Record as KnownPathogenicRecord,
+        schema::CaseQuery,
+        schema::StructuralVariant,
     },
 };
 
 use self::{
-    bgdbs::{load_bg_dbs, BgDbBundle, BgDbOverlaps},
+    bgdbs::{load_bg_dbs, BgDbBundle, BgDbOverlapStats, BgDbOverlaps},
+    clingen::{load_clingen_regions, ClingenRegionDb, RegionOverlap},
     clinvar::{load_clinvar_sv, ClinvarSv},
     genes::{load_gene_db, GeneDb},
     masked::{load_masked_dbs, MaskedBreakpointCount, MaskedDbBundle},
     pathogenic::{load_patho_dbs, PathoDbBundle},
-    schema::{CallInfo, SvSubType, SvType, TranscriptEffect},
-    tads::{load_tads, TadSetBundle},
+    schema::{CallInfo, SvSizeClass, SvSubType, SvType, TranscriptEffect},
+    tads::{load_tads, BoundaryDistRange, TadSetBundle},
+    tx_effects::{compute_tx_effects, AffectedExons, GeneOverlap, Windows},
 };
 
-/// Length of the upstream/downstream region.
-static X_STREAM: i32 = 5000;
-
 /// Command line arguments for `strucvars query` sub command.
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Run query for strucvars", long_about = None)]
@@ -63,10 +75,14 @@ pub struct Args {
     /// Genome release to assume.
     #[arg(long, value_enum)]
     pub genome_release: GenomeRelease,
+    /// Result set ID to write into each record.
+    #[arg(long)]
+    pub result_set_id: Option<String>,
     /// Path to worker database to use for querying.
     #[arg(long, required = true)]
     pub path_db: String,
-    /// Path to query JSON file.
+    /// Path to query JSON file; may be gzip-compressed, an `s3://` or `http(s)://` URL,
+    /// or `-` for stdin.
     #[arg(long, required = true)]
     pub path_query_json: String,
     /// Path to input TSV file.
@@ -92,9 +108,41 @@ pub struct Args {
     /// Maximal distance to TAD to consider.
     #[arg(long, default_value_t = 10_000)]
     pub max_tad_distance: i32,
+    /// Path to a BED file with custom TADs; optional.  Selected with the `custom`
+    /// TAD set in the query JSON.
+    #[arg(long)]
+    pub path_custom_tads: Option<String>,
+    /// Length of the upstream/downstream region of transcripts.
+    #[arg(long, default_value_t = 5000)]
+    pub x_stream_size: i32,
     /// Optional seed for RNG.
     #[arg(long)]
     pub rng_seed: Option<u64>,
+    /// Derive the record UUIDs from the case UUID and the variant instead of drawing
+    /// them at random, and write `mate_uuid` and `event_uuid`.
+    #[arg(long, default_value_t = false)]
+    pub deterministic_uuids: bool,
+    /// Write the payload as canonical JSON with sorted keys and floats rounded to 7
+    /// significant digits.
+    #[arg(long, default_value_t = false)]
+    pub canonical_json: bool,
+    /// Optional path prefix for heap profile dumps after loading and querying; dumps
+    /// require building with the `heap-profile` feature.
+    #[arg(long)]
+    pub heap_profile: Option<String>,
+    /// Only estimate runtime, temporary disk space, and memory from the input header and
+    /// index and the database sizes, print the estimate as JSON, and exit.
+    #[arg(long, default_value_t = false)]
+    pub estimate_only: bool,
+    /// Path to coverage VCF files from maelstrom, one per sample; optional.  Used
+    /// for the `*_cov_*` genotype criteria and annotated into the call info.
+    #[arg(long)]
+    pub path_cov_vcf: Vec<String>,
+    /// Path to a `seqvars query` JSONL result of the same case; optional.  SVs that are
+    /// heterozygous in the index are annotated with the heterozygous small variants in
+    /// the overlapping genes that may be in trans.
+    #[arg(long)]
+    pub path_seqvars_result: Option<String>,
 }
 
 /// Gene information.
@@ -121,6 +169,12 @@ struct GeneTranscriptEffects {
     gene: Gene,
     /// Transcript effects for the gene.
     transcript_effects: Vec<TranscriptEffect>,
+    /// Affected exons of the gene's transcripts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    affected_exons: Vec<AffectedExons>,
+    /// Overlap with the extent of the gene's transcripts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gene_overlap: Option<GeneOverlap>,
 }
 
 /// The structured result information of the result record.
@@ -137,6 +191,8 @@ struct ResultPayload {
     tad_genes: Vec<Gene>,
     /// Overlapping known pathogenic SV records.
     known_pathogenic: Vec<KnownPathogenicRecord>,
+    /// Overlapping ClinGen dosage regions.
+    clingen_regions: Vec<RegionOverlap>,
     /// Information about the call support from the structural variant.
     call_info: IndexMap<String, CallInfo>,
     /// Whether there is an overlap with a disease gene in the overlap.
@@ -145,14 +201,29 @@ struct ResultPayload {
     tad_disease_gene: bool,
     /// The size of the SV, None for ins and BND
     sv_length: Option<u32>,
+    /// The size class of the SV, None for ins and BND.
+    sv_size_class: Option<SvSizeClass>,
     /// Overlap counts with background databases.
     overlap_counts: BgDbOverlaps,
+    /// Distribution of the overlaps with the in-house background database.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inhouse_overlaps: Option<BgDbOverlapStats>,
     /// Overlap counts with masked sequenced.
     masked_breakpoints: MaskedBreakpointCount,
     /// Distance to next TAD boundary.
     tad_boundary_distance: Option<u32>,
+    /// Range of the distance to the next TAD boundary given the breakpoint confidence
+    /// intervals.
+    tad_boundary_distance_range: Option<BoundaryDistRange>,
     /// Effects on the transcripts per gene.
     tx_effects: Vec<GeneTranscriptEffects>,
+    /// Heterozygous small variants in the overlapping genes that may be in trans, for
+    /// compound heterozygous candidates.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    comphet_seqvars: Vec<SeqvarPartner>,
+    /// Number of samples by copy number, for calls with copy numbers.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    cn_distribution: BTreeMap<u32, u32>,
 }
 
 /// A result record from the query.
@@ -172,6 +243,63 @@ struct ResultRecord {
     sv_type: SvType,
     sv_sub_type: SvSubType,
     payload: String,
+    result_set_id: Option<String>,
+    mate_uuid: Option<Uuid>,
+    event_uuid: Option<Uuid>,
+}
+
+/// Deterministic UUIDs of a result record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RecordUuids {
+    /// UUID of the record.
+    sodar_uuid: Uuid,
+    /// UUID of the mate record, for break-ends.
+    mate_uuid: Option<Uuid>,
+    /// UUID of the event, shared by break-end mates.
+    event_uuid: Uuid,
+}
+
+impl RecordUuids {
+    /// Derive the UUIDs of `sv` in `namespace`, e.g., the case UUID.
+    ///
+    /// Break-ends are identified by their two ends only, so the `mate_uuid` of one mate
+    /// is the `sodar_uuid` of the other and both share the `event_uuid`.
+    fn new(namespace: &Uuid, result_set_id: Option<&str>, sv: &StructuralVariant) -> Self {
+        let prefix = result_set_id.unwrap_or_default();
+        let this_end = format!("{}:{}", &sv.chrom, sv.pos);
+        let other_end = format!("{}:{}", sv.chrom2.as_ref().unwrap_or(&sv.chrom), sv.end);
+        let (first_end, second_end) = if this_end <= other_end {
+            (&this_end, &other_end)
+        } else {
+            (&other_end, &this_end)
+        };
+        let event_uuid = uuid_v5(
+            namespace,
+            &format!("{prefix}|event|{:?}|{first_end}|{second_end}", sv.sv_type),
+        );
+        if sv.sv_type == SvType::Bnd {
+            Self {
+                sodar_uuid: uuid_v5(namespace, &format!("{prefix}|BND|{this_end}|{other_end}")),
+                mate_uuid: Some(uuid_v5(
+                    namespace,
+                    &format!("{prefix}|BND|{other_end}|{this_end}"),
+                )),
+                event_uuid,
+            }
+        } else {
+            Self {
+                sodar_uuid: uuid_v5(
+                    namespace,
+                    &format!(
+                        "{prefix}|{:?}|{:?}|{this_end}|{other_end}|{:?}",
+                        sv.sv_type, sv.sv_sub_type, sv.strand_orientation
+                    ),
+                ),
+                mate_uuid: None,
+                event_uuid,
+            }
+        }
+    }
 }
 
 fn resolve_hgvs_id(gene_db: &GeneDb, hgvs_id: &str) -> Vec<Gene> {
@@ -213,6 +341,7 @@ struct QueryStats {
 
 /// Run the `args.path_input` VCF file and run through the given `interpreter` writing to
 /// `args.path_output`.
+#[tracing::instrument(skip_all)]
 async fn run_query(
     interpreter: &QueryInterpreter,
     args: &Args,
@@ -220,15 +349,52 @@ async fn run_query(
     mehari_tx_db: &TxSeqDatabase,
     mehari_tx_idx: &TxIntervalTrees,
     chrom_to_acc: &HashMap<String, String>,
+    coverage_tracks: &mut HashMap<String, CoverageTrack>,
+    seqvar_partners: Option<&SeqvarPartners>,
     rng: &mut rand::rngs::StdRng,
 ) -> Result<QueryStats, anyhow::Error> {
     let chrom_to_chrom_no = &CHROM_TO_CHROM_NO;
     let chrom_map = build_chrom_map();
+    let windows = Windows {
+        x_stream: args.x_stream_size,
+        ..Default::default()
+    };
     let mut stats = QueryStats::default();
+    let tad_set = dbs
+        .tad_sets
+        .get(interpreter.query.tad_set.unwrap_or_default())
+        .classify(ErrorClass::Config)?;
 
     // Open VCF file, create reader, and read header.
-    let mut input_reader = open_vcf_reader(&args.path_input).await?;
-    let input_header = input_reader.read_header().await?;
+    let mut input_reader = open_vcf_reader(&args.path_input)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", args.path_input, e))
+        .classify(ErrorClass::Input)?;
+    let input_header = input_reader
+        .read_header()
+        .await
+        .classify(ErrorClass::Input)?;
+    crate::common::header_check::check(
+        &input_header,
+        args.genome_release,
+        None,
+        interpreter.query.genotype.keys().map(String::as_str),
+    )
+    .map_err(|e| anyhow::anyhow!("inconsistent input file {}: {}", args.path_input, e))
+    .classify(ErrorClass::Input)?;
+    let uuid_namespace = if args.deterministic_uuids {
+        let case_uuid = header_case_uuid(&input_header)
+            .map_err(|e| anyhow::anyhow!("invalid input file {}: {}", args.path_input, e))
+            .classify(ErrorClass::Input)?;
+        Some(case_uuid.unwrap_or_else(|| {
+            tracing::warn!(
+                "no x-varfish-case-uuid in VCF header, record UUIDs are not unique across cases"
+            );
+            Uuid::nil()
+        }))
+    } else {
+        None
+    };
 
     // Create output TSV writer.
     let mut csv_writer = csv::WriterBuilder::new()
@@ -237,241 +403,354 @@ async fn run_query(
         .quote_style(csv::QuoteStyle::Never)
         .from_path(&args.path_output)?;
 
-    // Read through input records using the query interpreter as a filter
+    // Read through input records using the query interpreter as a filter.  The records
+    // are processed by chromosome such that the background database overlaps can be
+    // counted for all of them at once.
     let mut record_buf = vcf::variant::RecordBuf::default();
+    let mut next_sv = None;
+    let mut chunk = Vec::new();
     loop {
-        let bytes_read = input_reader
-            .read_record_buf(&input_header, &mut record_buf)
-            .await
-            .map_err(|e| anyhow::anyhow!("problem reading VCF file {}: {}", &args.path_input, e))?;
-        if bytes_read == 0 {
+        chunk.extend(next_sv.take());
+        loop {
+            let bytes_read = input_reader
+                .read_record_buf(&input_header, &mut record_buf)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!("problem reading VCF file {}: {}", &args.path_input, e)
+                })?;
+            if bytes_read == 0 {
+                break; // EOF
+            }
+
+            stats.count_total += 1;
+            // Skip records on other chromosomes early, before any annotation.
+            if !interpreter
+                .query
+                .passes_chromosome(record_buf.reference_sequence_name())
+            {
+                continue;
+            }
+            let record_sv = StructuralVariant::from_vcf(&record_buf, &input_header)
+                .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
+            if chunk
+                .first()
+                .map(|sv: &StructuralVariant| sv.chrom != record_sv.chrom)
+                .unwrap_or(false)
+            {
+                next_sv = Some(record_sv);
+                break; // next chromosome
+            }
+            chunk.push(record_sv);
+        }
+        if chunk.is_empty() {
             break; // EOF
         }
 
-        stats.count_total += 1;
-        let record_sv = StructuralVariant::from_vcf(&record_buf, &input_header)
-            .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
-
-        tracing::trace!("processing record {:?}", record_sv);
+        let all_overlap_counts = dbs.bg_dbs.count_overlaps_batch(
+            &chunk,
+            &interpreter.query,
+            &chrom_map,
+            args.slack_ins,
+            args.slack_bnd,
+        );
+        for (mut record_sv, overlap_counts) in chunk.drain(..).zip(all_overlap_counts) {
+            tracing::trace!("processing record {:?}", record_sv);
+
+            annotate_coverage(&mut record_sv, coverage_tracks)
+                .map_err(|e| anyhow::anyhow!("could not read coverage: {}", e))
+                .classify(ErrorClass::Input)?;
+
+            let mut result_payload = ResultPayload {
+                call_info: record_sv.call_info.clone(),
+                callers: record_sv.callers.clone(),
+                ..ResultPayload::default()
+            };
 
-        let mut result_payload = ResultPayload {
-            call_info: record_sv.call_info.clone(),
-            callers: record_sv.callers.clone(),
-            ..ResultPayload::default()
-        };
+            let mut ovl_hgnc_ids = Vec::new();
+
+            let chrom = chrom_to_acc
+                .get(&annonars::common::cli::canonicalize(&record_sv.chrom))
+                .expect("invalid chromosome");
+            let chrom_idx = *mehari_tx_idx
+                .contig_to_idx
+                .get(chrom)
+                .expect("cannot map idx");
+
+            let passes = interpreter.passes(
+                &record_sv,
+                &mut |sv: &StructuralVariant| {
+                    result_payload.inhouse_overlaps = dbs.bg_dbs.inhouse_overlap_stats(
+                        sv,
+                        &interpreter.query,
+                        &chrom_map,
+                        args.slack_ins,
+                        args.slack_bnd,
+                    );
+                    result_payload.overlap_counts = overlap_counts.clone();
+                    overlap_counts.clone()
+                },
+                &mut |sv: &StructuralVariant| {
+                    result_payload.masked_breakpoints =
+                        dbs.masked.masked_breakpoint_count(sv, &chrom_map);
+                    result_payload.masked_breakpoints.clone()
+                },
+                &mut |sv: &StructuralVariant| {
+                    let sv_query: std::ops::Range<i32> =
+                        if matches!(sv.sv_type, SvType::Ins | SvType::Bnd) {
+                            sv.pos.saturating_sub(1)..sv.pos
+                        } else {
+                            sv.pos.saturating_sub(1)..sv.end
+                        };
+
+                    ovl_hgnc_ids =
+                        overlapping_hgnc_ids(mehari_tx_db, mehari_tx_idx, chrom_idx, sv_query);
+                    ovl_hgnc_ids.sort();
+                    ovl_hgnc_ids.dedup();
+                    ovl_hgnc_ids.clone()
+                },
+                &mut |sv: &StructuralVariant| {
+                    result_payload.tx_effects = compute_tx_effects(
+                        sv,
+                        mehari_tx_db,
+                        mehari_tx_idx,
+                        &dbs.genes,
+                        chrom_to_acc,
+                        &windows,
+                    );
+                    let mut res = Vec::new();
+                    for tx_effect in &result_payload.tx_effects {
+                        res.extend(tx_effect.transcript_effects.iter())
+                    }
+                    res.sort();
+                    res.dedup();
+                    res
+                },
+                &mut |sv: &StructuralVariant| {
+                    result_payload.clingen_regions =
+                        dbs.clingen_regions.overlapping_regions(sv, &chrom_map);
+                    result_payload.clingen_regions.clone()
+                },
+            )?;
 
-        let mut ovl_hgnc_ids = Vec::new();
-
-        let chrom = chrom_to_acc
-            .get(&annonars::common::cli::canonicalize(&record_sv.chrom))
-            .expect("invalid chromosome");
-        let chrom_idx = *mehari_tx_idx
-            .contig_to_idx
-            .get(chrom)
-            .expect("cannot map idx");
-
-        let passes = interpreter.passes(
-            &record_sv,
-            &mut |sv: &StructuralVariant| {
-                result_payload.overlap_counts = dbs.bg_dbs.count_overlaps(
-                    sv,
-                    &interpreter.query,
-                    &chrom_map,
-                    args.slack_ins,
-                    args.slack_bnd,
-                );
-                result_payload.overlap_counts.clone()
-            },
-            &mut |sv: &StructuralVariant| {
-                result_payload.masked_breakpoints =
-                    dbs.masked.masked_breakpoint_count(sv, &chrom_map);
-                result_payload.masked_breakpoints.clone()
-            },
-            &mut |sv: &StructuralVariant| {
-                let sv_query: std::ops::Range<i32> =
-                    if matches!(sv.sv_type, SvType::Ins | SvType::Bnd) {
-                        sv.pos.saturating_sub(1)..sv.pos
-                    } else {
-                        sv.pos.saturating_sub(1)..sv.end
-                    };
-
-                ovl_hgnc_ids =
-                    overlapping_hgnc_ids(mehari_tx_db, mehari_tx_idx, chrom_idx, sv_query);
-                ovl_hgnc_ids.sort();
-                ovl_hgnc_ids.dedup();
-                ovl_hgnc_ids.clone()
-            },
-            &mut |sv: &StructuralVariant| {
-                result_payload.tx_effects =
-                    compute_tx_effects(sv, mehari_tx_db, mehari_tx_idx, &dbs.genes, chrom_to_acc);
-                let mut res = Vec::new();
-                for tx_effect in &result_payload.tx_effects {
-                    res.extend(tx_effect.transcript_effects.iter())
+            if passes.pass_all {
+                if record_sv.sv_type != SvType::Ins && record_sv.sv_type != SvType::Bnd {
+                    let sv_length = (record_sv.end - record_sv.pos + 1) as u32;
+                    result_payload.sv_length = Some(sv_length);
+                    result_payload.sv_size_class = Some(SvSizeClass::from(sv_length));
                 }
-                res.sort();
-                res.dedup();
-                res
-            },
-        )?;
 
-        if passes.pass_all {
-            if record_sv.sv_type != SvType::Ins && record_sv.sv_type != SvType::Bnd {
-                result_payload.sv_length = Some((record_sv.end - record_sv.pos + 1) as u32);
-            }
+                // Copy effective and compatible genotypes to output.
+                for (sample, compatible) in passes.compatible.iter() {
+                    let call_info = result_payload
+                        .call_info
+                        .get_mut(sample)
+                        .expect("must exist");
+                    call_info.effective_genotype =
+                        *passes.effective.get(sample).expect("must exist");
+                    call_info.matched_gt_criteria = Some(compatible.clone());
+                }
 
-            // Copy effective and compatible genotypes to output.
-            for (sample, compatible) in passes.compatible.iter() {
-                let call_info = result_payload
+                for copy_number in result_payload
                     .call_info
-                    .get_mut(sample)
-                    .expect("must exist");
-                call_info.effective_genotype = *passes.effective.get(sample).expect("must exist");
-                call_info.matched_gt_criteria = Some(compatible.clone());
-            }
-
-            // Count passing record in statistics
-            stats.count_passed += 1;
-            *stats.by_sv_type.entry(record_sv.sv_type).or_default() += 1;
-
-            // Get overlaps with known pathogenic SVs and ClinVar SVs
-            result_payload.known_pathogenic =
-                dbs.patho_dbs.overlapping_records(&record_sv, &chrom_map);
-            result_payload.clinvar_ovl_rcvs = dbs
-                .clinvar_sv
-                .overlapping_rcvs(
-                    &record_sv,
-                    &chrom_map,
-                    interpreter.query.clinvar_sv_min_pathogenicity,
-                    interpreter.query.clinvar_sv_min_overlap,
-                )
-                .into_iter()
-                .map(|rcv| format!("RCV{rcv:09}"))
-                .collect();
-
-            // Get genes in overlapping TADs
-            let tad_hgnc_ids = {
-                let hgnc_ids: HashSet<_> = HashSet::from_iter(ovl_hgnc_ids.iter());
-                let tads =
-                    dbs.tad_sets
-                        .overlapping_tads(TadSetChoice::Hesc, &record_sv, &chrom_map);
-                let mut tad_hgvs_ids = Vec::new();
-                tads.iter()
-                    .map(|tad| {
-                        overlapping_hgnc_ids(
-                            mehari_tx_db,
-                            mehari_tx_idx,
-                            chrom_idx,
-                            (tad.begin - 1)..tad.end,
-                        )
-                    })
-                    .for_each(|mut v| tad_hgvs_ids.append(&mut v));
-                let tad_hgvs_ids: HashSet<_> = HashSet::from_iter(tad_hgvs_ids.into_iter());
-                let mut tad_hgvs_ids = Vec::from_iter(tad_hgvs_ids);
-                tad_hgvs_ids.retain(|hgvs_id| !hgnc_ids.contains(hgvs_id));
-                tad_hgvs_ids.sort();
-                tad_hgvs_ids
-            };
-            result_payload.tad_boundary_distance =
-                dbs.tad_sets
-                    .boundary_dist(TadSetChoice::Hesc, &record_sv, &chrom_map);
+                    .values()
+                    .filter_map(|call_info| call_info.copy_number)
+                {
+                    *result_payload
+                        .cn_distribution
+                        .entry(copy_number)
+                        .or_default() += 1;
+                }
 
-            // Convert the genes into more verbose records and put them into the result
-            ovl_hgnc_ids.iter().for_each(|hgvs_id| {
-                result_payload
+                // Count passing record in statistics
+                stats.count_passed += 1;
+                *stats.by_sv_type.entry(record_sv.sv_type).or_default() += 1;
+
+                // Get overlaps with known pathogenic SVs and ClinVar SVs
+                result_payload.known_pathogenic =
+                    dbs.patho_dbs.overlapping_records(&record_sv, &chrom_map);
+                result_payload.clinvar_ovl_rcvs = dbs
+                    .clinvar_sv
+                    .overlapping_rcvs(
+                        &record_sv,
+                        &chrom_map,
+                        interpreter.query.clinvar_sv_min_pathogenicity,
+                        interpreter.query.clinvar_sv_min_overlap,
+                    )
+                    .into_iter()
+                    .map(|rcv| format!("RCV{rcv:09}"))
+                    .collect();
+
+                // Get genes in overlapping TADs
+                let tad_hgnc_ids = {
+                    let hgnc_ids: HashSet<_> = HashSet::from_iter(ovl_hgnc_ids.iter());
+                    let tads = tad_set.overlapping_tads(&record_sv, &chrom_map);
+                    let mut tad_hgvs_ids = Vec::new();
+                    tads.iter()
+                        .map(|tad| {
+                            overlapping_hgnc_ids(
+                                mehari_tx_db,
+                                mehari_tx_idx,
+                                chrom_idx,
+                                (tad.begin - 1)..tad.end,
+                            )
+                        })
+                        .for_each(|mut v| tad_hgvs_ids.append(&mut v));
+                    let tad_hgvs_ids: HashSet<_> = HashSet::from_iter(tad_hgvs_ids.into_iter());
+                    let mut tad_hgvs_ids = Vec::from_iter(tad_hgvs_ids);
+                    tad_hgvs_ids.retain(|hgvs_id| !hgnc_ids.contains(hgvs_id));
+                    tad_hgvs_ids.sort();
+                    tad_hgvs_ids
+                };
+                result_payload.tad_boundary_distance =
+                    tad_set.boundary_dist(&record_sv, &chrom_map);
+                result_payload.tad_boundary_distance_range =
+                    tad_set.boundary_dist_range(&record_sv, &chrom_map);
+
+                // Convert the genes into more verbose records and put them into the result
+                ovl_hgnc_ids.iter().for_each(|hgvs_id| {
+                    result_payload
+                        .ovl_genes
+                        .append(&mut resolve_hgvs_id(&dbs.genes, hgvs_id))
+                });
+                result_payload.ovl_disease_gene = result_payload
                     .ovl_genes
-                    .append(&mut resolve_hgvs_id(&dbs.genes, hgvs_id))
-            });
-            result_payload.ovl_disease_gene = result_payload
-                .ovl_genes
-                .iter()
-                .any(|gene| gene.is_disease_gene);
-            tad_hgnc_ids.iter().for_each(|hgvs_id| {
-                result_payload
+                    .iter()
+                    .any(|gene| gene.is_disease_gene);
+                if let Some(seqvar_partners) = seqvar_partners {
+                    result_payload.comphet_seqvars =
+                        seqvar_partners.partners(&record_sv.call_info, &ovl_hgnc_ids);
+                }
+                tad_hgnc_ids.iter().for_each(|hgvs_id| {
+                    result_payload
+                        .tad_genes
+                        .append(&mut resolve_hgvs_id(&dbs.genes, hgvs_id))
+                });
+                result_payload.tad_disease_gene = result_payload
                     .tad_genes
-                    .append(&mut resolve_hgvs_id(&dbs.genes, hgvs_id))
-            });
-            result_payload.tad_disease_gene = result_payload
-                .tad_genes
-                .iter()
-                .any(|gene| gene.is_disease_gene);
-
-            if let Some(max_results) = args.max_results {
-                if stats.count_total > max_results {
-                    warn!(
-                        "stopping writing {} records but there are more results!",
-                        stats.count_total
-                    );
+                    .iter()
+                    .any(|gene| gene.is_disease_gene);
+
+                if let Some(max_results) = args.max_results {
+                    if stats.count_total > max_results {
+                        warn!(
+                            "stopping writing {} records but there are more results!",
+                            stats.count_total
+                        );
+                    }
                 }
-            }
 
-            let (bin, bin2) = if record_sv.sv_type == SvType::Bnd {
-                (
-                    mehari::annotate::seqvars::binning::bin_from_range(
-                        record_sv.pos as i32 - 2,
-                        record_sv.pos as i32 - 1,
-                    )? as u32,
-                    mehari::annotate::seqvars::binning::bin_from_range(
-                        record_sv.end as i32 - 1,
-                        record_sv.end as i32,
-                    )? as u32,
-                )
-            } else if record_sv.sv_type == SvType::Ins {
-                (
-                    mehari::annotate::seqvars::binning::bin_from_range(
-                        record_sv.pos as i32 - 2,
-                        record_sv.pos as i32 - 1,
-                    )? as u32,
-                    0,
-                )
-            } else {
-                (
-                    mehari::annotate::seqvars::binning::bin_from_range(
-                        record_sv.pos as i32 - 1,
-                        record_sv.end as i32,
-                    )? as u32,
-                    0,
-                )
-            };
+                let (bin, bin2) = if record_sv.sv_type == SvType::Bnd {
+                    (
+                        mehari::annotate::seqvars::binning::bin_from_range(
+                            record_sv.pos as i32 - 2,
+                            record_sv.pos as i32 - 1,
+                        )? as u32,
+                        mehari::annotate::seqvars::binning::bin_from_range(
+                            record_sv.end as i32 - 1,
+                            record_sv.end as i32,
+                        )? as u32,
+                    )
+                } else if record_sv.sv_type == SvType::Ins {
+                    (
+                        mehari::annotate::seqvars::binning::bin_from_range(
+                            record_sv.pos as i32 - 2,
+                            record_sv.pos as i32 - 1,
+                        )? as u32,
+                        0,
+                    )
+                } else {
+                    (
+                        mehari::annotate::seqvars::binning::bin_from_range(
+                            record_sv.pos as i32 - 1,
+                            record_sv.end as i32,
+                        )? as u32,
+                        0,
+                    )
+                };
 
-            // Finally, write out the record.
-            let mut uuid_buf = [0u8; 16];
-            rng.fill_bytes(&mut uuid_buf);
-            csv_writer
-                .serialize(&ResultRecord {
-                    sodar_uuid: Uuid::from_bytes(uuid_buf),
-                    release: match args.genome_release {
-                        GenomeRelease::Grch37 => "GRCh37".into(),
-                        GenomeRelease::Grch38 => "GRCh38".into(),
-                    },
-                    chromosome: record_sv.chrom.clone(),
-                    chromosome_no: *chrom_to_chrom_no
-                        .get(&record_sv.chrom)
-                        .expect("invalid chromosome") as i32,
-                    start: record_sv.pos,
-                    bin,
-                    chromosome2: record_sv
-                        .chrom2
-                        .as_ref()
-                        .unwrap_or(&record_sv.chrom)
-                        .clone(),
-                    chromosome_no2: *chrom_to_chrom_no
-                        .get(&record_sv.chrom)
-                        .expect("invalid chromosome") as i32,
-                    bin2,
-                    end: record_sv.end,
-                    pe_orientation: record_sv.strand_orientation,
-                    sv_type: record_sv.sv_type,
-                    sv_sub_type: record_sv.sv_sub_type,
-                    payload: serde_json::to_string(&result_payload)
-                        .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
-                })
-                .map_err(|e| anyhow::anyhow!("could not write record: {}", e))?;
+                // Finally, write out the record.
+                let uuids = uuid_namespace.as_ref().map(|uuid_namespace| {
+                    RecordUuids::new(uuid_namespace, args.result_set_id.as_deref(), &record_sv)
+                });
+                let sodar_uuid = uuids.map(|uuids| uuids.sodar_uuid).unwrap_or_else(|| {
+                    let mut uuid_buf = [0u8; 16];
+                    rng.fill_bytes(&mut uuid_buf);
+                    Uuid::from_bytes(uuid_buf)
+                });
+                csv_writer
+                    .serialize(&ResultRecord {
+                        sodar_uuid,
+                        release: match args.genome_release {
+                            GenomeRelease::Grch37 => "GRCh37".into(),
+                            GenomeRelease::Grch38 => "GRCh38".into(),
+                        },
+                        chromosome: record_sv.chrom.clone(),
+                        chromosome_no: *chrom_to_chrom_no
+                            .get(&record_sv.chrom)
+                            .expect("invalid chromosome")
+                            as i32,
+                        start: record_sv.pos,
+                        bin,
+                        chromosome2: record_sv
+                            .chrom2
+                            .as_ref()
+                            .unwrap_or(&record_sv.chrom)
+                            .clone(),
+                        chromosome_no2: *chrom_to_chrom_no
+                            .get(&record_sv.chrom)
+                            .expect("invalid chromosome")
+                            as i32,
+                        bin2,
+                        end: record_sv.end,
+                        pe_orientation: record_sv.strand_orientation,
+                        sv_type: record_sv.sv_type,
+                        sv_sub_type: record_sv.sv_sub_type,
+                        payload: canonical_json::to_string(&result_payload, args.canonical_json)
+                            .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
+                        result_set_id: args.result_set_id.clone(),
+                        mate_uuid: uuids.and_then(|uuids| uuids.mate_uuid),
+                        event_uuid: uuids.map(|uuids| uuids.event_uuid),
+                    })
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))?;
+            }
         }
     }
 
+    // The `csv` writer writes the header together with the first record only.
+    if stats.count_passed == 0 {
+        csv_writer
+            .write_record(result_columns()?)
+            .map_err(|e| anyhow::anyhow!("could not write header: {}", e))?;
+    }
+    csv_writer
+        .flush()
+        .map_err(|e| anyhow::anyhow!("could not flush output file: {}", e))?;
+
     Ok(stats)
 }
 
+/// Return the column names of the TSV output, derived from the fields of `ResultRecord`.
+pub(crate) fn result_columns() -> Result<Vec<String>, anyhow::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .quote_style(csv::QuoteStyle::Never)
+        .from_writer(Vec::new());
+    writer
+        .serialize(ResultRecord::default())
+        .map_err(|e| anyhow::anyhow!("could not serialize record: {}", e))?;
+    let buf = writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("could not flush record: {}", e))?;
+    Ok(String::from_utf8(buf)?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split('\t')
+        .map(String::from)
+        .collect())
+}
+
 /// Generate `Gene` record for a given Entrez gene ID.
 fn construct_gene(entrez_id: u32, gene_db: &GeneDb) -> Gene {
     let idx = gene_db
@@ -490,321 +769,6 @@ fn construct_gene(entrez_id: u32, gene_db: &GeneDb) -> Gene {
     }
 }
 
-/// Ad-hoc data structure for `tx_regions`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct TxRegion {
-    // 0-based begin position
-    begin: i32,
-    // 0-based end position
-    end: i32,
-    // "arbitrary" number
-    no: usize,
-    // effect of the transcript (encodes region type)
-    effect: TranscriptEffect,
-}
-
-/// Return list of half-open intervals for a given transcript.
-fn tx_regions(tx: &Transcript) -> Vec<TxRegion> {
-    assert_eq!(
-        tx.genome_alignments.len(),
-        1,
-        "only one alignment supported"
-    );
-    if tx.genome_alignments[0].exons.is_empty() {
-        // no exons? skip!
-        return Vec::new();
-    }
-
-    let mut result = Vec::new();
-    let mut tx_start = None;
-    let mut tx_end = None;
-    let genome_alignment = &tx.genome_alignments[0];
-
-    // Loop over all exons to determine leftmost and rightmost genome position.
-    for exon_alignment in &genome_alignment.exons {
-        if let Some(value) = tx_start {
-            if exon_alignment.alt_start_i < value {
-                tx_start = Some(exon_alignment.alt_start_i);
-            }
-        } else {
-            tx_start = Some(exon_alignment.alt_start_i);
-        }
-        if let Some(value) = tx_end {
-            if exon_alignment.alt_end_i > value {
-                tx_end = Some(exon_alignment.alt_end_i);
-            }
-        } else {
-            tx_end = Some(exon_alignment.alt_end_i);
-        }
-    }
-
-    let tx_start = tx_start.expect("must have been set");
-    let tx_end = tx_end.expect("must have been set");
-
-    // Perform the actual region extraction.
-    let mut prev_alt_end_i = 0;
-    for (no, exon_alignment) in genome_alignment.exons.iter().enumerate() {
-        if exon_alignment.alt_start_i == tx_start {
-            // is first, register upstream/downstream
-            result.push(TxRegion {
-                begin: exon_alignment.alt_start_i - X_STREAM,
-                end: exon_alignment.alt_start_i - 1,
-                no,
-                effect: if genome_alignment.strand == Strand::Plus as i32 {
-                    TranscriptEffect::UpstreamVariant
-                } else {
-                    TranscriptEffect::DownstreamVariant
-                },
-            });
-        } else {
-            // is not first, register splice region on the left boundary
-            result.push(TxRegion {
-                begin: (exon_alignment.alt_start_i - 1) - 8,
-                end: (exon_alignment.alt_start_i - 1) + 3,
-                no,
-                effect: TranscriptEffect::SpliceRegionVariant,
-            })
-        }
-
-        if exon_alignment.alt_end_i == tx_end {
-            // is last, register upstream/downstream
-            result.push(TxRegion {
-                begin: exon_alignment.alt_end_i,
-                end: exon_alignment.alt_end_i + X_STREAM,
-                no,
-                effect: if genome_alignment.strand == Strand::Plus as i32 {
-                    TranscriptEffect::DownstreamVariant
-                } else {
-                    TranscriptEffect::UpstreamVariant
-                },
-            });
-        } else {
-            // is not last, register splice region on the right boundary
-            result.push(TxRegion {
-                begin: exon_alignment.alt_end_i - 3,
-                end: exon_alignment.alt_end_i + 8,
-                no,
-                effect: TranscriptEffect::SpliceRegionVariant,
-            })
-        }
-
-        // register the exon
-        result.push(TxRegion {
-            begin: exon_alignment.alt_start_i - 1,
-            end: exon_alignment.alt_end_i,
-            no,
-            effect: TranscriptEffect::ExonVariant,
-        });
-
-        if exon_alignment.alt_start_i != tx_start {
-            // is not first exon, register intron "right" of it
-            result.push(TxRegion {
-                begin: prev_alt_end_i,
-                end: exon_alignment.alt_start_i - 1,
-                no,
-                effect: TranscriptEffect::IntronVariant,
-            });
-        }
-
-        // store end of prev exon for next intron's start
-        prev_alt_end_i = exon_alignment.alt_end_i;
-    }
-
-    result
-}
-
-/// Return the transcript region / effect for the given breakpoint.
-fn gene_tx_effects_for_bp(tx: &Transcript, pos: i32) -> Vec<TranscriptEffect> {
-    // Obtain list of regions for transcript.
-    let regions = tx_regions(tx);
-
-    // Determine how this relates to the breakpoint.
-    let pos = pos - 1; // 1-based to 0-based
-    let mut result = regions
-        .iter()
-        .filter(|r| r.begin <= pos && pos < r.end)
-        .map(|r| r.effect)
-        .collect::<Vec<_>>();
-    if result.is_empty() {
-        result.push(TranscriptEffect::IntergenicVariant);
-    } else {
-        result.sort();
-        result.dedup();
-    }
-    result
-}
-
-/// Return the transcript region / effect for the given range.
-fn gene_tx_effect_for_range(tx: &Transcript, pos: i32, end: i32) -> Vec<TranscriptEffect> {
-    // Obtain list of regions for transcript.
-    let regions = tx_regions(tx);
-
-    // Determine how this relates to the left and right breakpoints.
-    let pos = pos - 1; // 1-based to 0-based
-    let mut result = regions
-        .iter()
-        .filter(|region| pos < region.end && region.begin < end)
-        .map(|region| region.effect)
-        .collect::<Vec<_>>();
-
-    // Remove any duplicates.
-    result.sort();
-    result.dedup();
-
-    // If we have both upstream and downstream then the full transcript is affected.
-    if result.contains(&TranscriptEffect::UpstreamVariant)
-        && result.contains(&TranscriptEffect::DownstreamVariant)
-    {
-        result.push(TranscriptEffect::TranscriptVariant);
-    }
-
-    result
-}
-
-/// Helper that computes effects on transcripts for a single breakend, e.g., one side of BND or INS.
-fn compute_tx_effects_for_breakpoint(
-    sv: &StructuralVariant,
-    mehari_tx_db: &TxSeqDatabase,
-    mehari_tx_idx: &TxIntervalTrees,
-    gene_db: &GeneDb,
-    chrom_to_acc: &HashMap<String, String>,
-) -> Vec<GeneTranscriptEffects> {
-    // Shortcut to the `TranscriptDb`.
-    let tx_db = mehari_tx_db
-        .tx_db
-        .as_ref()
-        .expect("transcripts must be present");
-    // Compute canonical chromosome name and map to accession.
-    let chrom = chrom_to_acc.get(&annonars::common::cli::canonicalize(&sv.chrom));
-    if chrom.is_none() {
-        return Default::default();
-    }
-    let chrom = chrom.expect("chromosome must be known at this point");
-    // Create range to query the interval trees for.
-    let query = (sv.pos - X_STREAM)..(sv.pos + X_STREAM);
-
-    if let Some(idx) = mehari_tx_idx.contig_to_idx.get(chrom) {
-        let mut effects_by_gene: HashMap<_, Vec<_>> = HashMap::new();
-
-        // Collect all transcripts that overlap the INS and compute the effect of the INS on
-        // the transcript.
-        let tree = &mehari_tx_idx.trees[*idx];
-        for it in tree.find(query) {
-            let tx = &tx_db.transcripts[*it.data() as usize];
-            if let Some(&idx) = gene_db.xlink.from_hgnc.get(&tx.gene_id) {
-                let entrez_id = gene_db.xlink.records[idx as usize].entrez_id;
-                effects_by_gene
-                    .entry(entrez_id)
-                    .or_default()
-                    .extend(gene_tx_effects_for_bp(tx, sv.pos));
-            } else {
-                tracing::warn!("could not resolve HGNC gene ID {:?}", tx.gene_id)
-            }
-        }
-
-        // Deduplicate results.
-        effects_by_gene.iter_mut().for_each(|(_, v)| {
-            v.sort();
-            v.dedup()
-        });
-
-        // Convert the results into the final format.
-        effects_by_gene
-            .into_iter()
-            .map(|(entrez_id, transcript_effects)| GeneTranscriptEffects {
-                gene: construct_gene(entrez_id, gene_db),
-                transcript_effects,
-            })
-            .collect()
-    } else {
-        // We do not have any transcripts for this chromosome.
-        Default::default()
-    }
-}
-
-/// Compute effect for linear SVs.
-fn compute_tx_effects_for_linear(
-    sv: &StructuralVariant,
-    mehari_tx_db: &TxSeqDatabase,
-    mehari_tx_idx: &TxIntervalTrees,
-    gene_db: &GeneDb,
-    chrom_to_acc: &HashMap<String, String>,
-) -> Vec<GeneTranscriptEffects> {
-    // Shortcut to the `TranscriptDb`.
-    let tx_db = mehari_tx_db
-        .tx_db
-        .as_ref()
-        .expect("transcripts must be present");
-    // Compute canonical chromosome name and map to accession.
-    let chrom = chrom_to_acc.get(&annonars::common::cli::canonicalize(&sv.chrom));
-    if chrom.is_none() {
-        return Default::default();
-    }
-    let chrom = chrom.expect("chromosome must be known at this point");
-    // Create range to query the interval trees for.
-    let query = (sv.pos - X_STREAM)..(sv.end + X_STREAM);
-
-    if let Some(idx) = mehari_tx_idx.contig_to_idx.get(chrom) {
-        let mut effects_by_gene: HashMap<_, Vec<_>> = HashMap::new();
-
-        // Collect all transcripts that overlap the SV and compute the effect of the SV on
-        // the transcript.
-        let tree = &mehari_tx_idx.trees[*idx];
-        for it in tree.find(query) {
-            let tx = &tx_db.transcripts[*it.data() as usize];
-            if let Some(&idx) = gene_db.xlink.from_hgnc.get(&tx.gene_id) {
-                let entrez_id = gene_db.xlink.records[idx as usize].entrez_id;
-                effects_by_gene
-                    .entry(entrez_id)
-                    .or_default()
-                    .extend(gene_tx_effect_for_range(tx, sv.pos, sv.end));
-            } else {
-                tracing::warn!("could not resolve HGNC gene ID {:?}", tx.gene_id)
-            }
-        }
-
-        // Deduplicate results.
-        effects_by_gene.iter_mut().for_each(|(_, v)| {
-            v.sort();
-            v.dedup()
-        });
-
-        // Convert the results into the final format.
-        effects_by_gene
-            .into_iter()
-            .map(|(entrez_id, transcript_effects)| GeneTranscriptEffects {
-                gene: construct_gene(entrez_id, gene_db),
-                transcript_effects,
-            })
-            .collect()
-    } else {
-        // We do not have any transcripts for this chromosome.
-        Default::default()
-    }
-}
-
-/// Compute effect(s) of `sv` on transcript of genes.
-fn compute_tx_effects(
-    sv: &StructuralVariant,
-    mehari_tx_db: &TxSeqDatabase,
-    mehari_tx_idx: &TxIntervalTrees,
-    gene_db: &GeneDb,
-    chrom_to_acc: &HashMap<String, String>,
-) -> Vec<GeneTranscriptEffects> {
-    match sv.sv_type {
-        SvType::Ins | SvType::Bnd => compute_tx_effects_for_breakpoint(
-            sv,
-            mehari_tx_db,
-            mehari_tx_idx,
-            gene_db,
-            chrom_to_acc,
-        ),
-        SvType::Del | SvType::Dup | SvType::Inv | SvType::Cnv => {
-            compute_tx_effects_for_linear(sv, mehari_tx_db, mehari_tx_idx, gene_db, chrom_to_acc)
-        }
-    }
-}
-
 /// Compute overlapping HGNC gene IDs for a given interval.
 pub fn overlapping_hgnc_ids(
     tx_seq_db: &TxSeqDatabase,
@@ -832,10 +796,18 @@ pub struct InMemoryDbs {
     pub masked: MaskedDbBundle,
     pub genes: GeneDb,
     pub clinvar_sv: ClinvarSv,
+    pub clingen_regions: ClingenRegionDb,
 }
 
 /// Translate gene allow list to gene identifiers from in-memory dbs.
-pub fn translate_genes(genes: &Vec<String>, dbs: &InMemoryDbs) -> HashSet<String> {
+///
+/// Genes that cannot be mapped are skipped with a warning or, if `strict`, lead to a
+/// configuration error.
+pub fn translate_genes(
+    genes: &Vec<String>,
+    dbs: &InMemoryDbs,
+    strict: bool,
+) -> Result<HashSet<String>, anyhow::Error> {
     let mut result = HashSet::new();
 
     let re_entrez = regex::Regex::new(r"^\d+").expect("invalid regex in source code");
@@ -862,7 +834,11 @@ pub fn translate_genes(genes: &Vec<String>, dbs: &InMemoryDbs) -> HashSet<String
                     }
                 }
             } else {
-                warn!("Cannot map candidate Entrez gene identifier {}", &gene);
+                warn_or_fail(
+                    strict,
+                    ErrorClass::Config,
+                    format!("Cannot map candidate Entrez gene identifier {}", &gene),
+                )?;
                 continue;
             }
         } else if re_ensembl.is_match(gene) {
@@ -873,7 +849,11 @@ pub fn translate_genes(genes: &Vec<String>, dbs: &InMemoryDbs) -> HashSet<String
                     }
                 };
             } else {
-                warn!("Cannot map candidate ENSEMBL gene identifier {}", &gene);
+                warn_or_fail(
+                    strict,
+                    ErrorClass::Config,
+                    format!("Cannot map candidate ENSEMBL gene identifier {}", &gene),
+                )?;
                 continue;
             }
         } else if re_hgnc.is_match(gene) {
@@ -884,17 +864,25 @@ pub fn translate_genes(genes: &Vec<String>, dbs: &InMemoryDbs) -> HashSet<String
                     }
                 }
             } else {
-                warn!("Cannot map candidate HGNC gene identifier {}", &gene);
+                warn_or_fail(
+                    strict,
+                    ErrorClass::Config,
+                    format!("Cannot map candidate HGNC gene identifier {}", &gene),
+                )?;
                 continue;
             }
         } else if let Some(gene_id) = symbol_to_id.get(gene) {
             result.insert(gene_id.clone());
         } else {
-            warn!("Could not map candidate gene symbol {}", &gene);
+            warn_or_fail(
+                strict,
+                ErrorClass::Config,
+                format!("Could not map candidate gene symbol {}", &gene),
+            )?;
         }
     }
 
-    result
+    Ok(result)
 }
 
 /// Load database from the given path with the given genome release.
@@ -910,94 +898,300 @@ pub fn load_databases(
         masked: load_masked_dbs(path_worker_db, genome_release)?,
         genes: load_gene_db(path_worker_db, genome_release)?,
         clinvar_sv: load_clinvar_sv(path_worker_db, genome_release)?,
+        clingen_regions: load_clingen_regions(path_worker_db, genome_release)?,
     })
 }
 
-/// Main entry point for `sv query` sub command.
-pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
-    let before_anything = Instant::now();
-    tracing::info!("args_common = {:?}", &args_common);
-    tracing::info!("args = {:?}", &args);
+/// Databases that are loaded once and shared by all queries.
+pub struct Databases {
+    /// In-memory worker databases.
+    pub in_memory_dbs: InMemoryDbs,
+    /// The mehari transcript database.
+    pub mehari_tx_db: TxSeqDatabase,
+    /// Interval trees over the transcripts of `mehari_tx_db`.
+    pub mehari_tx_idx: TxIntervalTrees,
+    /// Mapping from canonical chromosome name to RefSeq accession.
+    pub chrom_to_acc: HashMap<String, String>,
+}
 
-    // Initialize the random number generator from command line seed if given or local entropy
-    // source.
-    let mut rng = if let Some(rng_seed) = args.rng_seed {
-        rand::rngs::StdRng::seed_from_u64(rng_seed)
-    } else {
-        rand::rngs::StdRng::from_entropy()
-    };
+impl Databases {
+    /// Load the worker and mehari databases from `path_db`.
+    #[tracing::instrument(skip_all)]
+    pub fn load(
+        path_db: &str,
+        genome_release: GenomeRelease,
+        max_tad_distance: i32,
+    ) -> Result<Self, anyhow::Error> {
+        tracing::info!("Loading worker databases...");
+        let before_loading = Instant::now();
+        let path_worker_db = format!("{}/worker", path_db);
+        let in_memory_dbs = load_databases(&path_worker_db, genome_release, max_tad_distance)
+            .classify(ErrorClass::Db)?;
+        tracing::info!(
+            "...done loading databases in {:?}",
+            before_loading.elapsed()
+        );
+
+        trace_rss_now();
+
+        tracing::info!("Loading mehari tx database...");
+        let before_loading = Instant::now();
+        let path_mehari_tx_db = format!(
+            "{}/mehari/{}/txs.bin.zst",
+            path_db,
+            &genome_release.to_string()
+        );
+        tracing::debug!("  path = {}", &path_mehari_tx_db);
+        let mehari_tx_db =
+            mehari::annotate::seqvars::load_tx_db(&path_mehari_tx_db).classify(ErrorClass::Db)?;
+        tracing::info!(
+            "...done loading mehari tx database in {:?}",
+            before_loading.elapsed()
+        );
+        tracing::info!("Building mehari index data structures...");
+        let before_building = Instant::now();
+        let mehari_tx_idx = TxIntervalTrees::new(&mehari_tx_db, genome_release.into());
+        let chrom_to_acc = ASSEMBLY_INFOS[genome_release.into()]
+            .sequences
+            .iter()
+            .map(|record| {
+                (
+                    annonars::common::cli::canonicalize(&record.name),
+                    record.refseq_ac.clone(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        tracing::info!(
+            "...done building mehari index data structures in {:?}",
+            before_building.elapsed()
+        );
+
+        trace_rss_now();
+
+        Ok(Self {
+            in_memory_dbs,
+            mehari_tx_db,
+            mehari_tx_idx,
+            chrom_to_acc,
+        })
+    }
+}
 
+/// Load the query from `args.path_query_json`.
+#[tracing::instrument(skip_all)]
+async fn load_query(args: &Args) -> Result<CaseQuery, anyhow::Error> {
     tracing::info!("Loading query...");
-    let query: CaseQuery = serde_json::from_reader(File::open(&args.path_query_json)?)?;
+    let query: CaseQuery = query_json::read(&args.path_query_json)
+        .await
+        .classify(ErrorClass::Config)?;
+    query
+        .expression
+        .as_ref()
+        .map_or(Ok(()), expression::check_fields)
+        .map_err(|e| anyhow::anyhow!("invalid expression in query: {}", e))
+        .classify(ErrorClass::Config)?;
     tracing::info!(
         "... done loading query = {}",
         &serde_json::to_string(&query)?
     );
+    Ok(query)
+}
 
-    tracing::info!("Loading worker databases...");
-    let before_loading = Instant::now();
-    let path_worker_db = format!("{}/worker", &args.path_db);
-    let dbs = load_databases(&path_worker_db, args.genome_release, args.max_tad_distance)?;
-    tracing::info!(
-        "...done loading databases in {:?}",
-        before_loading.elapsed()
-    );
+/// Cost model of `strucvars query` for `--estimate-only`.
+const COST_MODEL: CostModel = CostModel {
+    records_per_second: 1_000.0,
+    db_bytes_per_second: 200_000_000.0,
+    base_memory: 500_000_000,
+    memory_per_record: 1_000,
+    memory_per_db_byte: 3.0,
+    output_bytes_per_record: 5_000,
+};
 
-    trace_rss_now();
+/// Print the resource estimate for running `strucvars query` with `args`.
+async fn print_estimate(args: &Args) -> Result<(), anyhow::Error> {
+    let input = InputStats::load(&args.path_input).await?;
+    // All worker databases and the mehari transcript database are loaded into memory.
+    let in_memory_database_size = worker_db_usage(&args.path_db, args.genome_release)
+        + disk_usage(format!(
+            "{}/mehari/{}/txs.bin.zst",
+            args.path_db, args.genome_release
+        ));
+    // Records are processed chromosome by chromosome.
+    let records_in_memory = input
+        .record_counts
+        .values()
+        .copied()
+        .max()
+        .unwrap_or(input.record_count);
+    Estimate::new(
+        &COST_MODEL,
+        input,
+        in_memory_database_size,
+        in_memory_database_size,
+        records_in_memory,
+    )
+    .print()
+}
+
+/// Main entry point for `sv query` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = Instant::now();
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+    if args.estimate_only {
+        return print_estimate(args).await;
+    }
+
+    let mut heap_profiler =
+        crate::common::heap_profile::HeapProfiler::new(args.heap_profile.clone())?;
+
+    let query = load_query(args).await?;
+    let mut dbs = Databases::load(&args.path_db, args.genome_release, args.max_tad_distance)?;
+    if let Some(path_custom_tads) = &args.path_custom_tads {
+        dbs.in_memory_dbs
+            .tad_sets
+            .load_custom(path_custom_tads, args.max_tad_distance)
+            .classify(ErrorClass::Input)?;
+    }
+    heap_profiler.stage("load")?;
+
+    run_with_databases(args_common, args, query, &dbs).await?;
+    heap_profiler.stage("query")?;
 
-    tracing::info!("Loading mehari tx database...");
-    let before_loading = Instant::now();
-    let path_mehari_tx_db = format!(
-        "{}/mehari/{}/txs.bin.zst",
-        &args.path_db,
-        &args.genome_release.to_string()
-    );
-    tracing::debug!("  path = {}", &path_mehari_tx_db);
-    let mehari_tx_db = mehari::annotate::seqvars::load_tx_db(&path_mehari_tx_db)?;
     tracing::info!(
-        "...done loading mehari tx database in {:?}",
-        before_loading.elapsed()
+        "All of `strucvars query` completed in {:?}",
+        before_anything.elapsed()
     );
-    tracing::info!("Building mehari index data structures...");
-    let before_building = Instant::now();
-    let mehari_tx_idx = TxIntervalTrees::new(&mehari_tx_db, args.genome_release.into());
-    let chrom_to_acc = ASSEMBLY_INFOS[args.genome_release.into()]
-        .sequences
-        .iter()
-        .map(|record| {
-            (
-                annonars::common::cli::canonicalize(&record.name),
-                record.refseq_ac.clone(),
-            )
+    Ok(())
+}
+
+/// Load the coverage tracks from `args.path_cov_vcf` by sample name.
+fn load_coverage_tracks(
+    args: &Args,
+    query: &CaseQuery,
+    strict: bool,
+) -> Result<HashMap<String, CoverageTrack>, anyhow::Error> {
+    let mut result = HashMap::new();
+    for path_cov_vcf in &args.path_cov_vcf {
+        tracing::info!("Opening coverage file {}...", path_cov_vcf);
+        let track = CoverageTrack::from_path(path_cov_vcf)
+            .map_err(|e| e.context(format!("could not open coverage file {}", path_cov_vcf)))
+            .classify(ErrorClass::Input)?;
+        if !query.genotype.contains_key(&track.sample) {
+            warn_or_fail(
+                strict,
+                ErrorClass::Input,
+                format!(
+                    "sample {} of coverage file {} is not in the query",
+                    &track.sample, path_cov_vcf
+                ),
+            )?;
+        }
+        result.insert(track.sample.clone(), track);
+    }
+    Ok(result)
+}
+
+/// Annotate the call info of `record_sv` with the coverage statistics of each sample
+/// from `coverage_tracks`.
+///
+/// This is only done for linear SVs, the coverage is not informative for INS and BND.
+fn annotate_coverage(
+    record_sv: &mut StructuralVariant,
+    coverage_tracks: &mut HashMap<String, CoverageTrack>,
+) -> Result<(), anyhow::Error> {
+    if coverage_tracks.is_empty() || matches!(record_sv.sv_type, SvType::Ins | SvType::Bnd) {
+        return Ok(());
+    }
+    for (sample, call_info) in record_sv.call_info.iter_mut() {
+        let Some(track) = coverage_tracks.get_mut(sample) else {
+            continue;
+        };
+        if let Some(coverage) =
+            track.region_coverage(&record_sv.chrom, record_sv.pos, record_sv.end)?
+        {
+            call_info.coverage_mean = Some(coverage.mean as f32);
+            call_info.coverage_relative = coverage.relative.map(|relative| relative as f32);
+            call_info.coverage_mapq = coverage.mapq.map(|mapq| mapq as f32);
+        }
+    }
+    Ok(())
+}
+
+/// Load the small variants for compound heterozygous candidates from
+/// `args.path_seqvars_result`, if given.
+fn load_seqvar_partners(
+    args: &Args,
+    query: &CaseQuery,
+) -> Result<Option<SeqvarPartners>, anyhow::Error> {
+    let Some(path_seqvars_result) = &args.path_seqvars_result else {
+        return Ok(None);
+    };
+    tracing::info!("Loading seqvars result {}...", path_seqvars_result);
+    let seqvar_partners = SeqvarPartners::load(path_seqvars_result)
+        .map_err(|e| {
+            e.context(format!(
+                "could not load seqvars result {}",
+                path_seqvars_result
+            ))
         })
-        .collect::<HashMap<_, _>>();
-    tracing::info!(
-        "...done building mehari index data structures in {:?}",
-        before_building.elapsed()
-    );
+        .classify(ErrorClass::Input)?;
+    if !query.genotype.contains_key(&seqvar_partners.index) {
+        return Err(anyhow::anyhow!(
+            "index sample {} of seqvars result {} is not in the query",
+            seqvar_partners.index,
+            path_seqvars_result
+        ))
+        .classify(ErrorClass::Config);
+    }
+    Ok(Some(seqvar_partners))
+}
 
-    trace_rss_now();
+/// Run `query` with the already loaded databases `dbs`.
+#[tracing::instrument(skip_all)]
+async fn run_with_databases(
+    args_common: &crate::common::Args,
+    args: &Args,
+    query: CaseQuery,
+    dbs: &Databases,
+) -> Result<(), anyhow::Error> {
+    // Initialize the random number generator from command line seed if given or local entropy
+    // source.
+    let mut rng = if let Some(rng_seed) = args.rng_seed {
+        rand::rngs::StdRng::seed_from_u64(rng_seed)
+    } else {
+        rand::rngs::StdRng::from_entropy()
+    };
 
     tracing::info!("Translating gene allow list...");
     let hgvs_allowlist = if let Some(gene_allowlist) = &query.gene_allowlist {
         if gene_allowlist.is_empty() {
             None
         } else {
-            Some(translate_genes(gene_allowlist, &dbs))
+            Some(translate_genes(
+                gene_allowlist,
+                &dbs.in_memory_dbs,
+                args_common.strict,
+            )?)
         }
     } else {
         None
     };
 
+    let mut coverage_tracks = load_coverage_tracks(args, &query, args_common.strict)?;
+    let seqvar_partners = load_seqvar_partners(args, &query)?;
+
     tracing::info!("Running queries...");
     let before_query = Instant::now();
     let query_stats = run_query(
         &QueryInterpreter::new(query, hgvs_allowlist),
         args,
-        &dbs,
-        &mehari_tx_db,
-        &mehari_tx_idx,
-        &chrom_to_acc,
+        &dbs.in_memory_dbs,
+        &dbs.mehari_tx_db,
+        &dbs.mehari_tx_idx,
+        &dbs.chrom_to_acc,
+        &mut coverage_tracks,
+        seqvar_partners.as_ref(),
         &mut rng,
     )
     .await?;
@@ -1007,6 +1201,11 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         query_stats.count_passed.separate_with_commas(),
         query_stats.count_total.separate_with_commas()
     );
+    if query_stats.count_total == 0 {
+        tracing::info!("input file has no records, wrote output with header only");
+    } else if query_stats.count_passed == 0 {
+        tracing::info!("no records passed the query, wrote output with header only");
+    }
     tracing::info!("passing records by SV type");
     for (sv_type, count) in query_stats.by_sv_type.iter() {
         tracing::info!("{:?} -- {}", sv_type, count);
@@ -1014,15 +1213,13 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     trace_rss_now();
 
-    tracing::info!(
-        "All of `strucvars query` completed in {:?}",
-        before_anything.elapsed()
-    );
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
     #[tracing_test::traced_test]
     #[tokio::test]
     async fn smoke_test() -> Result<(), anyhow::Error> {
@@ -1032,6 +1229,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             genome_release: crate::common::GenomeRelease::Grch37,
+            result_set_id: None,
             path_db: "tests/strucvars/query/db".into(),
             path_query_json: "tests/strucvars/query/Case_3.query.json".into(),
             path_input: "tests/strucvars/query/Case_3.ingested.vcf".into(),
@@ -1041,7 +1239,15 @@ mod test {
             slack_ins: 50,
             min_overlap: 0.8,
             max_tad_distance: 10_000,
+            path_custom_tads: None,
+            x_stream_size: 5000,
             rng_seed: Some(42),
+            deterministic_uuids: false,
+            canonical_json: false,
+            heap_profile: None,
+            estimate_only: false,
+            path_cov_vcf: vec![],
+            path_seqvars_result: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -1049,4 +1255,65 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn result_columns() -> Result<(), anyhow::Error> {
+        let columns = super::result_columns()?;
+        assert_eq!(columns.len(), 17);
+        assert_eq!(columns[0], "sodar_uuid");
+        assert_eq!(columns[16], "event_uuid");
+
+        Ok(())
+    }
+
+    fn sv(
+        sv_type: super::SvType,
+        chrom: &str,
+        pos: i32,
+        chrom2: Option<&str>,
+        end: i32,
+    ) -> super::StructuralVariant {
+        super::StructuralVariant {
+            chrom: chrom.into(),
+            pos,
+            sv_type,
+            sv_sub_type: match sv_type {
+                super::SvType::Bnd => super::SvSubType::Bnd,
+                _ => super::SvSubType::Del,
+            },
+            chrom2: chrom2.map(Into::into),
+            end,
+            strand_orientation: StrandOrientation::ThreeToFive,
+            callers: Vec::new(),
+            cipos: None,
+            ciend: None,
+            call_info: Default::default(),
+        }
+    }
+
+    #[test]
+    fn record_uuids() {
+        let namespace = uuid::Uuid::parse_str("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c").unwrap();
+        let bnd = sv(super::SvType::Bnd, "1", 1000, Some("5"), 2000);
+        let mate = sv(super::SvType::Bnd, "5", 2000, Some("1"), 1000);
+        let del = sv(super::SvType::Del, "1", 1000, None, 2000);
+
+        let bnd_uuids = super::RecordUuids::new(&namespace, Some("rs-1"), &bnd);
+        let mate_uuids = super::RecordUuids::new(&namespace, Some("rs-1"), &mate);
+        let del_uuids = super::RecordUuids::new(&namespace, Some("rs-1"), &del);
+
+        assert_eq!(
+            bnd_uuids,
+            super::RecordUuids::new(&namespace, Some("rs-1"), &bnd)
+        );
+        assert_eq!(bnd_uuids.mate_uuid, Some(mate_uuids.sodar_uuid));
+        assert_eq!(mate_uuids.mate_uuid, Some(bnd_uuids.sodar_uuid));
+        assert_eq!(bnd_uuids.event_uuid, mate_uuids.event_uuid);
+        assert_eq!(del_uuids.mate_uuid, None);
+        assert_ne!(del_uuids.sodar_uuid, bnd_uuids.sodar_uuid);
+        assert_ne!(
+            del_uuids.sodar_uuid,
+            super::RecordUuids::new(&namespace, Some("rs-2"), &del).sodar_uuid
+        );
+    }
 }
@@ -2,25 +2,18 @@
 
 use std::path::Path;
 
-use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
-use mehari::common::io::std::open_read_maybe_gz;
 use serde::Serialize;
 use tracing::{info, warn};
 
-use crate::{
-    common::GenomeRelease,
-    common::{build_chrom_map, CHROMS},
-};
+use crate::{common::build_chrom_map, common::GenomeRelease};
 
 use super::{
+    intervals::{read_bed, IntervalDb},
     schema::ChromRange,
     schema::{StructuralVariant, SvType},
 };
 
-/// Alias for the interval tree that we use.
-type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
-
 /// Information to store for known pathogenic SV database.
 #[derive(Default, Debug, Serialize, Clone)]
 pub struct Record {
@@ -35,33 +28,9 @@ pub struct Record {
 }
 
 /// Code for known pathogenic SV database overlappers.
-#[derive(Default, Debug)]
-pub struct PathoDb {
-    /// Records, stored by chromosome.
-    pub records: Vec<Vec<Record>>,
-    /// Interval trees, stored by chromosome.
-    pub trees: Vec<IntervalTree>,
-}
+pub type PathoDb = IntervalDb<Record>;
 
 impl PathoDb {
-    pub fn fetch_records(
-        &self,
-        chrom_range: &ChromRange,
-        chrom_map: &IndexMap<String, usize>,
-    ) -> Vec<Record> {
-        let chrom_idx = *chrom_map
-            .get(&chrom_range.chromosome)
-            .expect("invalid chromosome");
-        let range = chrom_range.begin..chrom_range.end;
-
-        self.trees[chrom_idx]
-            .find(range)
-            .iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
-            .cloned()
-            .collect()
-    }
-
     pub fn overlapping_records(
         &self,
         sv: &StructuralVariant,
@@ -74,12 +43,7 @@ impl PathoDb {
         let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
         let range = sv.pos.saturating_sub(1)..sv.end;
 
-        self.trees[chrom_idx]
-            .find(range)
-            .iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
-            .cloned()
-            .collect()
+        self.find(chrom_idx, range).cloned().collect()
     }
 }
 
@@ -95,7 +59,7 @@ impl PathoDbBundle {
         chrom_range: &ChromRange,
         chrom_map: &IndexMap<String, usize>,
     ) -> Vec<Record> {
-        self.mms.fetch_records(chrom_range, chrom_map)
+        self.mms.fetch(chrom_range, chrom_map)
     }
 
     pub fn overlapping_records(
@@ -130,40 +94,23 @@ fn load_patho_db_records(path: &Path) -> Result<PathoDb, anyhow::Error> {
     tracing::debug!("loading pathogenic SV records from {:?}...", path);
     let chrom_map = build_chrom_map();
 
-    let mut result = PathoDb::default();
-    for _ in CHROMS {
-        result.records.push(Vec::new());
-        result.trees.push(IntervalTree::new());
-    }
-
-    // Setup CSV reader for BED file - header is written as comment and must be
-    // ignored.
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false) // BED has no header
-        .comment(Some(b'#'))
-        .delimiter(b'\t')
-        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
-    let mut total_count = 0;
-    for record in reader.deserialize() {
-        let record: input::Record = record?;
+    let mut result = PathoDb::new(path.display().to_string());
+    for record in read_bed::<input::Record>(path)? {
         let chrom_idx = *chrom_map.get(&record.chrom).expect("invalid chromosome");
 
         let key = record.begin..record.end;
-        result.trees[chrom_idx].insert(key, result.records[chrom_idx].len() as u32);
-        result.records[chrom_idx].push(Record {
-            begin: record.begin,
-            end: record.end,
-            sv_type: SvType::Cnv,
-            id: record.id,
-        });
-
-        total_count += 1;
+        result.insert(
+            chrom_idx,
+            key,
+            Record {
+                begin: record.begin,
+                end: record.end,
+                sv_type: SvType::Cnv,
+                id: record.id,
+            },
+        );
     }
-    result.trees.iter_mut().for_each(|tree| tree.index());
-    tracing::debug!(
-        "... done loading {} records and building trees",
-        total_count
-    );
+    result.index();
 
     Ok(result)
 }
@@ -1,7 +1,7 @@
 //! Supporting code for SV query definition.
 
 use crate::{
-    common::{genotype_to_string, TadSet},
+    common::{expression::Expression, genotype_to_string, TadSet},
     pbs::varfish::v1::strucvars::clinvar,
 };
 use indexmap::IndexMap;
@@ -293,6 +293,8 @@ pub enum GenotypeChoice {
     NonVariant,
     /// Non-reference genotype
     NonReference,
+    /// Variant genotype with low allele fraction, e.g., mosaic CNV
+    Mosaic,
 }
 
 /// Enum for effective genotypes.
@@ -307,6 +309,8 @@ pub enum Genotype {
     Hom,
     /// Heterozygous genotype
     Het,
+    /// Variant genotype with low allele fraction, e.g., mosaic CNV
+    Mosaic,
     /// Not wild-type genotype
     Variant,
     /// Reference genotype
@@ -486,6 +490,8 @@ pub struct GenotypeCriteria {
 
     /// The FORMAT/GT field should be one of, unless None
     pub gt_one_of: Option<Vec<String>>,
+    /// All (namespaced) FORMAT/FT or FILTER values should be one of, unless None
+    pub ft_one_of: Option<Vec<String>>,
 
     /// Minimal genotype quality as returned by caller
     pub min_gq: Option<f32>,
@@ -545,10 +551,31 @@ pub struct GenotypeCriteria {
     pub min_amq: Option<f32>,
     /// Maximal average mapping quality
     pub max_amq: Option<f32>,
+    /// Minimal copy number, the consensus of merged callers if reconciled at ingest
+    pub min_cn: Option<u32>,
+    /// Maximal copy number, the consensus of merged callers if reconciled at ingest
+    pub max_cn: Option<u32>,
+    /// Minimal allele fraction, see `CallInfo::allele_fraction()`
+    pub min_allele_fraction: Option<f32>,
+    /// Maximal allele fraction, see `CallInfo::allele_fraction()`
+    pub max_allele_fraction: Option<f32>,
+    /// Minimal mean coverage over the SV from the coverage VCF
+    pub min_cov_avg: Option<f32>,
+    /// Maximal mean coverage over the SV from the coverage VCF
+    pub max_cov_avg: Option<f32>,
+    /// Minimal mean coverage over the SV relative to the sample's autosomal mean
+    pub min_cov_rel: Option<f32>,
+    /// Maximal mean coverage over the SV relative to the sample's autosomal mean
+    pub max_cov_rel: Option<f32>,
+    /// Minimal mean mapping quality over the SV from the coverage VCF
+    pub min_cov_mq: Option<f32>,
 
     /// Whether missing genotype call leads to filter out variant
     #[serde(default = "default_as_true")]
     pub missing_gt_ok: bool,
+    /// Whether missing filter information leads to filter out variant
+    #[serde(default = "default_as_true")]
+    pub missing_ft_ok: bool,
     /// Whether missing genotype quality information leads filter out variant
     #[serde(default = "default_as_true")]
     pub missing_gq_ok: bool,
@@ -568,6 +595,15 @@ pub struct GenotypeCriteria {
     /// Whether missing mapping quality information leads to filter out variant
     #[serde(default = "default_as_true")]
     pub missing_amq_ok: bool,
+    /// Whether missing copy number leads to filter out variant
+    #[serde(default = "default_as_true")]
+    pub missing_cn_ok: bool,
+    /// Whether missing allele fraction leads to filter out variant
+    #[serde(default = "default_as_true")]
+    pub missing_allele_fraction_ok: bool,
+    /// Whether missing coverage VCF information leads to filter out variant
+    #[serde(default = "default_as_true")]
+    pub missing_cov_ok: bool,
 
     /// An optional comment
     pub comment: Option<String>,
@@ -585,6 +621,7 @@ impl GenotypeCriteria {
             max_brk_segdup: None,
             max_brk_segduprepeat: None,
             gt_one_of: None,
+            ft_one_of: None,
             min_gq: None,
             min_pr_cov: None,
             max_pr_cov: None,
@@ -614,14 +651,27 @@ impl GenotypeCriteria {
             max_rd_dev: None,
             min_amq: None,
             max_amq: None,
+            min_cn: None,
+            max_cn: None,
+            min_allele_fraction: None,
+            max_allele_fraction: None,
+            min_cov_avg: None,
+            max_cov_avg: None,
+            min_cov_rel: None,
+            max_cov_rel: None,
+            min_cov_mq: None,
             comment: None,
             missing_gt_ok: true,
+            missing_ft_ok: true,
             missing_gq_ok: true,
             missing_pr_ok: true,
             missing_sr_ok: true,
             missing_srpr_ok: true,
             missing_rd_dev_ok: true,
             missing_amq_ok: true,
+            missing_cn_ok: true,
+            missing_allele_fraction_ok: true,
+            missing_cov_ok: true,
         }
     }
 
@@ -669,6 +719,17 @@ impl GenotypeCriteria {
                 .map_or(self.missing_gt_ok, |gt| gt_one_of.contains(gt))
         });
 
+        // ft -- genotype filters
+
+        let pass_ft_one_of = self.ft_one_of.as_ref().map_or(true, |ft_one_of| {
+            call_info
+                .filters
+                .as_ref()
+                .map_or(self.missing_ft_ok, |filters| {
+                    filters.iter().all(|ft| ft_one_of.contains(ft))
+                })
+        });
+
         // gq -- genotype quality
 
         let pass_min_gq = self.min_gq.map_or(true, |min_gq| {
@@ -1030,7 +1091,59 @@ impl GenotypeCriteria {
                 })
         });
 
+        // cn -- copy number
+
+        let pass_min_cn = self.min_cn.map_or(true, |min_cn| {
+            call_info
+                .copy_number
+                .map_or(self.missing_cn_ok, |copy_number| copy_number >= min_cn)
+        });
+        let pass_max_cn = self.max_cn.map_or(true, |max_cn| {
+            call_info
+                .copy_number
+                .map_or(self.missing_cn_ok, |copy_number| copy_number <= max_cn)
+        });
+
+        // allele fraction -- from reads or coverage, see `CallInfo::allele_fraction()`
+
+        let allele_fraction = call_info.allele_fraction();
+        let pass_min_allele_fraction = self.min_allele_fraction.map_or(true, |min_af| {
+            allele_fraction.map_or(self.missing_allele_fraction_ok, |af| af >= min_af)
+        });
+        let pass_max_allele_fraction = self.max_allele_fraction.map_or(true, |max_af| {
+            allele_fraction.map_or(self.missing_allele_fraction_ok, |af| af <= max_af)
+        });
+
+        // cov -- statistics from the coverage VCF, see `CallInfo::coverage_mean` etc.
+
+        let pass_min_cov_avg = self.min_cov_avg.map_or(true, |min_cov_avg| {
+            call_info
+                .coverage_mean
+                .map_or(self.missing_cov_ok, |cov| cov >= min_cov_avg)
+        });
+        let pass_max_cov_avg = self.max_cov_avg.map_or(true, |max_cov_avg| {
+            call_info
+                .coverage_mean
+                .map_or(self.missing_cov_ok, |cov| cov <= max_cov_avg)
+        });
+        let pass_min_cov_rel = self.min_cov_rel.map_or(true, |min_cov_rel| {
+            call_info
+                .coverage_relative
+                .map_or(self.missing_cov_ok, |cov| cov >= min_cov_rel)
+        });
+        let pass_max_cov_rel = self.max_cov_rel.map_or(true, |max_cov_rel| {
+            call_info
+                .coverage_relative
+                .map_or(self.missing_cov_ok, |cov| cov <= max_cov_rel)
+        });
+        let pass_min_cov_mq = self.min_cov_mq.map_or(true, |min_cov_mq| {
+            call_info
+                .coverage_mapq
+                .map_or(self.missing_cov_ok, |mq| mq >= min_cov_mq)
+        });
+
         pass_gt_one_of
+            && pass_ft_one_of
             && pass_min_gq
             && pass_min_pr_cov
             && pass_max_pr_cov
@@ -1060,6 +1173,15 @@ impl GenotypeCriteria {
             && pass_max_rd_dev
             && pass_min_amq
             && pass_max_amq
+            && pass_min_cn
+            && pass_max_cn
+            && pass_min_allele_fraction
+            && pass_max_allele_fraction
+            && pass_min_cov_avg
+            && pass_max_cov_avg
+            && pass_min_cov_rel
+            && pass_max_cov_rel
+            && pass_min_cov_mq
     }
 
     pub fn is_masked_pass(&self, masked_count: &MaskedBreakpointCount) -> bool {
@@ -1079,6 +1201,64 @@ impl GenotypeCriteria {
     }
 }
 
+/// Minimal and maximal size of an SV.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct SvSizeRange {
+    /// The minimal SV size to consider.
+    pub min: Option<u32>,
+    /// The maximal SV size to consider.
+    pub max: Option<u32>,
+}
+
+impl SvSizeRange {
+    /// Return whether `sv_size` is within the range.
+    pub fn contains(&self, sv_size: u32) -> bool {
+        self.min.map_or(true, |min| sv_size >= min) && self.max.map_or(true, |max| sv_size <= max)
+    }
+}
+
+/// Minimal and maximal copy number of a sample.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct CopyNumberRange {
+    /// The minimal copy number to consider.
+    pub min: Option<u32>,
+    /// The maximal copy number to consider.
+    pub max: Option<u32>,
+}
+
+impl CopyNumberRange {
+    /// Return whether `copy_number` is within the range.
+    pub fn contains(&self, copy_number: u32) -> bool {
+        self.min.map_or(true, |min| copy_number >= min)
+            && self.max.map_or(true, |max| copy_number <= max)
+    }
+}
+
+/// Size class of an SV.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SvSizeClass {
+    /// Smaller than 1kb.
+    Small,
+    /// From 1kb to smaller than 50kb.
+    Medium,
+    /// From 50kb to smaller than 1Mb.
+    Large,
+    /// At least 1Mb.
+    VeryLarge,
+}
+
+impl From<u32> for SvSizeClass {
+    fn from(sv_size: u32) -> Self {
+        match sv_size {
+            0..=999 => SvSizeClass::Small,
+            1_000..=49_999 => SvSizeClass::Medium,
+            50_000..=999_999 => SvSizeClass::Large,
+            _ => SvSizeClass::VeryLarge,
+        }
+    }
+}
+
 /// Define a query for structural variants from a case.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct CaseQuery {
@@ -1094,6 +1274,12 @@ pub struct CaseQuery {
     pub svdb_dgv_gs_min_overlap: Option<f32>,
     /// The maximal number of carriers for querying DGV gold standard.
     pub svdb_dgv_gs_max_count: Option<u32>,
+    /// The minimal carrier frequency for querying DGV gold standard.
+    #[serde(default)]
+    pub svdb_dgv_gs_min_carrier_freq: Option<f32>,
+    /// The maximal carrier frequency for querying DGV gold standard.
+    #[serde(default)]
+    pub svdb_dgv_gs_max_carrier_freq: Option<f32>,
     /// Whether to enable SVDB overlap queries with gnomAD SV.
     pub svdb_gnomad_genomes_enabled: bool,
     /// The minimal reciprocal overlap for querying gnomAD SV.
@@ -1106,6 +1292,21 @@ pub struct CaseQuery {
     pub svdb_gnomad_exomes_min_overlap: Option<f32>,
     /// The maximal number of carriers for querying gnomAD exomes/ExAC.
     pub svdb_gnomad_exomes_max_count: Option<u32>,
+    /// Whether to enable SVDB overlap queries with gnomAD-SV v4.
+    #[serde(default)]
+    pub svdb_gnomad_sv4_enabled: bool,
+    /// The minimal reciprocal overlap for querying gnomAD-SV v4.
+    #[serde(default)]
+    pub svdb_gnomad_sv4_min_overlap: Option<f32>,
+    /// The maximal number of carriers for querying gnomAD-SV v4.
+    #[serde(default)]
+    pub svdb_gnomad_sv4_max_count: Option<u32>,
+    /// The minimal carrier frequency for querying gnomAD-SV v4.
+    #[serde(default)]
+    pub svdb_gnomad_sv4_min_carrier_freq: Option<f32>,
+    /// The maximal carrier frequency for querying gnomAD-SV v4.
+    #[serde(default)]
+    pub svdb_gnomad_sv4_max_carrier_freq: Option<f32>,
     /// Whether to enable SVDB overlap queries with dbVar.
     pub svdb_dbvar_enabled: bool,
     /// The minimal reciprocal overlap for querying dbVar.
@@ -1130,10 +1331,17 @@ pub struct CaseQuery {
     /// Minimal pathogenicity when overlapping with ClinVar SVs.
     pub clinvar_sv_min_pathogenicity: Option<Pathogenicity>,
 
+    /// Whether to require overlap with a ClinGen dosage-sensitive region.
+    #[serde(default)]
+    pub require_clingen_dosage_region: bool,
+
     /// The minimal SV size to consider.
     pub sv_size_min: Option<u32>,
     /// The maximal SV size to consider.
     pub sv_size_max: Option<u32>,
+    /// Minimal and maximal SV sizes to consider for each SV type.
+    #[serde(default)]
+    pub sv_size_by_type: IndexMap<SvType, SvSizeRange>,
 
     /// The SV types to consider.
     pub sv_types: Vec<SvType>,
@@ -1147,6 +1355,9 @@ pub struct CaseQuery {
     /// Genomic region to limit consideration to.
     #[serde(deserialize_with = "deserialize_genomic_region")]
     pub genomic_region: Option<Vec<GenomicRegion>>,
+    /// Chromosomes to limit consideration to, "chr" prefix is ignored; empty means all.
+    #[serde(default)]
+    pub chromosomes: Vec<String>,
 
     /// Regulatory region padding to use.
     pub regulatory_overlap: i32,
@@ -1158,18 +1369,26 @@ pub struct CaseQuery {
     /// Custom regulatory maps configuration.
     pub regulatory_custom_configs: Vec<RegulatoryCustomConfig>,
 
-    /// Name of the TAD set to use for annotation, if any.
+    /// Name of the TAD set to use for annotation; hESC if not given.
     pub tad_set: Option<TadSet>,
 
     /// Genotype choices
     pub genotype: IndexMap<String, GenotypeChoice>,
     /// Criteria for filtering CNVs.
     pub genotype_criteria: Vec<GenotypeCriteria>,
+    /// Copy number ranges by sample; SVs must have a copy number within the range for
+    /// each of these samples.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub copy_number_ranges: IndexMap<String, CopyNumberRange>,
 
     /// The mode for recessive inheritance.
     pub recessive_mode: Option<RecessiveMode>,
     /// The index to use for recessive inheritance.
     pub recessive_index: Option<String>,
+
+    /// Optional boolean expression over annotated fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<Expression>,
 }
 
 fn deserialize_genomic_region<'de, D>(
@@ -1220,6 +1439,18 @@ where
     }))
 }
 
+impl CaseQuery {
+    /// Return whether SVs on `chrom` pass the chromosome restriction.
+    pub fn passes_chromosome(&self, chrom: &str) -> bool {
+        self.chromosomes.is_empty() || {
+            let chrom = annonars::common::cli::canonicalize(chrom);
+            self.chromosomes
+                .iter()
+                .any(|other| annonars::common::cli::canonicalize(other) == chrom)
+        }
+    }
+}
+
 impl Default for CaseQuery {
     fn default() -> Self {
         CaseQuery {
@@ -1229,12 +1460,19 @@ impl Default for CaseQuery {
             svdb_dgv_gs_enabled: false,
             svdb_dgv_gs_min_overlap: None,
             svdb_dgv_gs_max_count: None,
+            svdb_dgv_gs_min_carrier_freq: None,
+            svdb_dgv_gs_max_carrier_freq: None,
             svdb_gnomad_genomes_enabled: false,
             svdb_gnomad_genomes_min_overlap: None,
             svdb_gnomad_genomes_max_count: None,
             svdb_gnomad_exomes_enabled: false,
             svdb_gnomad_exomes_min_overlap: None,
             svdb_gnomad_exomes_max_count: None,
+            svdb_gnomad_sv4_enabled: false,
+            svdb_gnomad_sv4_min_overlap: None,
+            svdb_gnomad_sv4_max_count: None,
+            svdb_gnomad_sv4_min_carrier_freq: None,
+            svdb_gnomad_sv4_max_carrier_freq: None,
             svdb_dbvar_enabled: false,
             svdb_dbvar_min_overlap: None,
             svdb_dbvar_max_count: None,
@@ -1246,12 +1484,15 @@ impl Default for CaseQuery {
             svdb_inhouse_max_count: None,
             sv_size_min: None,
             sv_size_max: None,
+            sv_size_by_type: IndexMap::new(),
             sv_types: SvType::vec_all(),
             sv_sub_types: SvSubType::vec_all(),
             clinvar_sv_min_overlap: None,
             clinvar_sv_min_pathogenicity: None,
+            require_clingen_dosage_region: false,
             gene_allowlist: None,
             genomic_region: None,
+            chromosomes: vec![],
             regulatory_overlap: 100,
             regulatory_ensembl_features: None,
             regulatory_vista_validation: None,
@@ -1259,9 +1500,11 @@ impl Default for CaseQuery {
             tad_set: None,
             genotype: IndexMap::new(),
             genotype_criteria: vec![],
+            copy_number_ranges: IndexMap::new(),
             recessive_mode: None,
             recessive_index: None,
             tx_effects: TranscriptEffect::vec_all(),
+            expression: None,
         }
     }
 }
@@ -1293,6 +1536,40 @@ pub struct CallInfo {
     pub point_count: Option<u32>,
     /// Average mapping quality, if applicable
     pub average_mapping_quality: Option<f32>,
+    /// Filter values from FORMAT/FT, falling back to FILTER, if applicable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<Vec<String>>,
+    /// Mean coverage over the SV from the coverage VCF, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_mean: Option<f32>,
+    /// Mean coverage over the SV relative to the autosomal mean, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_relative: Option<f32>,
+    /// Mean mapping quality over the SV from the coverage VCF, if given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_mapq: Option<f32>,
+}
+
+impl CallInfo {
+    /// Return the estimated fraction of variant alleles, if any.
+    ///
+    /// This is the fraction of variant paired-end and split reads if any such
+    /// coverage is given.  Otherwise, it is derived from the deviation of the
+    /// average normalized coverage or, as a last resort, of the integer copy
+    /// number from the diploid state.  A heterozygous call thus has a fraction
+    /// of about 0.5 and mosaic calls have lower values.
+    pub fn allele_fraction(&self) -> Option<f32> {
+        let reads_cov = self.paired_end_cov.unwrap_or(0) + self.split_read_cov.unwrap_or(0);
+        let reads_var = self.paired_end_var.unwrap_or(0) + self.split_read_var.unwrap_or(0);
+        if reads_cov > 0 {
+            Some((reads_var as f32 / reads_cov as f32).min(1.0))
+        } else if let Some(average_normalized_cov) = self.average_normalized_cov {
+            Some((average_normalized_cov - 1.0).abs().min(1.0))
+        } else {
+            self.copy_number
+                .map(|copy_number| ((copy_number as f32 - 2.0).abs() / 2.0).min(1.0))
+        }
+    }
 }
 
 /// Definition of a structural variant with per-sample genotype calls.
@@ -1316,6 +1593,12 @@ pub struct StructuralVariant {
     pub end: i32,
     /// The strand orientation of the structural variant.
     pub strand_orientation: StrandOrientation,
+    /// Confidence interval around `pos` from INFO/CIPOS, if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cipos: Option<(i32, i32)>,
+    /// Confidence interval around `end` from INFO/CIEND, if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ciend: Option<(i32, i32)>,
 
     /// The callers of the variant.
     pub callers: Vec<String>,
@@ -1418,6 +1701,9 @@ impl StructuralVariant {
             anyhow::bail!("no INFO/callers in VCF record")
         };
 
+        let cipos = Self::confidence_interval(record, key::POSITION_CONFIDENCE_INTERVALS)?;
+        let ciend = Self::confidence_interval(record, key::END_CONFIDENCE_INTERVALS)?;
+
         let call_info = Self::build_call_info(record, header)?;
 
         Ok(Self {
@@ -1428,11 +1714,29 @@ impl StructuralVariant {
             chrom2,
             end,
             strand_orientation,
+            cipos,
+            ciend,
             callers,
             call_info,
         })
     }
 
+    /// Return the confidence interval from the INFO field `key`, if any.
+    fn confidence_interval(
+        record: &vcf::variant::RecordBuf,
+        key: &str,
+    ) -> Result<Option<(i32, i32)>, anyhow::Error> {
+        match record.info().get(key) {
+            Some(Some(vcf::variant::record_buf::info::field::Value::Array(
+                vcf::variant::record_buf::info::field::value::Array::Integer(values),
+            ))) => match values.as_slice() {
+                [Some(lower), Some(upper)] if lower <= upper => Ok(Some((*lower, *upper))),
+                _ => anyhow::bail!("invalid INFO/{} in VCF record: {:?}", key, values),
+            },
+            _ => Ok(None),
+        }
+    }
+
     /// Build call information.
     fn build_call_info(
         record: &vcf::variant::RecordBuf,
@@ -1440,8 +1744,25 @@ impl StructuralVariant {
     ) -> Result<IndexMap<String, CallInfo>, anyhow::Error> {
         let mut result = IndexMap::new();
 
+        // The record's FILTER values are used for samples without FORMAT/FT.
+        let record_filters = if record.filters().as_ref().is_empty() {
+            None
+        } else {
+            Some(
+                record
+                    .filters()
+                    .as_ref()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            )
+        };
+
         for (name, sample) in header.sample_names().iter().zip(record.samples().values()) {
-            let mut call_info = CallInfo::default();
+            let mut call_info = CallInfo {
+                filters: record_filters.clone(),
+                ..Default::default()
+            };
 
             for (key, value) in record.samples().keys().as_ref().iter().zip(sample.values()) {
                 let value = if let Some(value) = value.as_ref() {
@@ -1458,6 +1779,13 @@ impl StructuralVariant {
                         call_info.genotype =
                             Some((genotype_to_string(&gt)?.as_str()[1..]).to_string());
                     }
+                    (
+                        key::FILTER,
+                        vcf::variant::record_buf::samples::sample::Value::String(filters),
+                    ) => {
+                        call_info.filters =
+                            Some(filters.split(';').map(|s| s.to_string()).collect());
+                    }
                     (
                         key::CONDITIONAL_GENOTYPE_QUALITY,
                         vcf::variant::record_buf::samples::sample::Value::Float(quality),
@@ -1810,6 +2138,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sv_size_class_serde_smoke() {
+        assert_tokens(
+            &SvSizeClass::VeryLarge,
+            &[Token::UnitVariant {
+                name: "SvSizeClass",
+                variant: "very_large",
+            }],
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(1, SvSizeClass::Small)]
+    #[case(999, SvSizeClass::Small)]
+    #[case(1_000, SvSizeClass::Medium)]
+    #[case(49_999, SvSizeClass::Medium)]
+    #[case(50_000, SvSizeClass::Large)]
+    #[case(999_999, SvSizeClass::Large)]
+    #[case(1_000_000, SvSizeClass::VeryLarge)]
+    fn test_sv_size_class_from_size(#[case] sv_size: u32, #[case] expected: SvSizeClass) {
+        assert_eq!(SvSizeClass::from(sv_size), expected);
+    }
+
+    #[test]
+    fn test_sv_size_range_contains() {
+        let range = SvSizeRange {
+            min: Some(50),
+            max: Some(500),
+        };
+        assert!(!range.contains(49));
+        assert!(range.contains(50));
+        assert!(range.contains(500));
+        assert!(!range.contains(501));
+        assert!(SvSizeRange::default().contains(u32::MAX));
+    }
+
+    #[rstest::rstest]
+    #[case(&[], "chr1", true)]
+    #[case(&["X"], "chrX", true)]
+    #[case(&["chrX"], "X", true)]
+    #[case(&["X"], "chr1", false)]
+    fn test_case_query_passes_chromosome(
+        #[case] chromosomes: &[&str],
+        #[case] chrom: &str,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            chromosomes: chromosomes.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(query.passes_chromosome(chrom), expected);
+    }
+
     #[test]
     fn test_tad_set_serde_smoke() {
         assert_tokens(
@@ -1891,6 +2272,57 @@ mod tests {
         assert!(crit.is_call_info_pass(&pass_info));
     }
 
+    #[test]
+    fn test_genotype_criteria_is_call_info_pass_ft() {
+        let crit = GenotypeCriteria {
+            ft_one_of: Some(vec!["PASS".to_owned()]),
+            ..GenotypeCriteria::new(GenotypeChoice::Het)
+        };
+
+        assert!(crit.is_call_info_pass(&CallInfo {
+            filters: Some(vec!["PASS".to_owned()]),
+            ..Default::default()
+        }));
+        assert!(!crit.is_call_info_pass(&CallInfo {
+            filters: Some(vec!["PASS".to_owned(), "Delly_LowQual".to_owned()]),
+            ..Default::default()
+        }));
+        assert!(crit.is_call_info_pass(&CallInfo::default()));
+        assert!(!GenotypeCriteria {
+            missing_ft_ok: false,
+            ..crit
+        }
+        .is_call_info_pass(&CallInfo::default()));
+    }
+
+    #[test]
+    fn test_genotype_criteria_is_call_info_pass_cn() {
+        let crit = GenotypeCriteria {
+            min_cn: Some(1),
+            max_cn: Some(1),
+            ..GenotypeCriteria::new(GenotypeChoice::Het)
+        };
+
+        assert!(crit.is_call_info_pass(&CallInfo {
+            copy_number: Some(1),
+            ..Default::default()
+        }));
+        assert!(!crit.is_call_info_pass(&CallInfo {
+            copy_number: Some(0),
+            ..Default::default()
+        }));
+        assert!(!crit.is_call_info_pass(&CallInfo {
+            copy_number: Some(3),
+            ..Default::default()
+        }));
+        assert!(crit.is_call_info_pass(&CallInfo::default()));
+        assert!(!GenotypeCriteria {
+            missing_cn_ok: false,
+            ..crit
+        }
+        .is_call_info_pass(&CallInfo::default()));
+    }
+
     #[test]
     fn test_genotype_criteria_is_call_info_fail() {
         let crit = GenotypeCriteria {
@@ -1957,6 +2389,101 @@ mod tests {
         }));
     }
 
+    #[rstest::rstest]
+    #[case::reads(Some(20), Some(3), Some(10), Some(1), None, None, Some(0.133_333_34))]
+    #[case::paired_end_only(Some(10), Some(5), None, None, Some(0.5), Some(1), Some(0.5))]
+    #[case::coverage(None, None, None, None, Some(1.2), Some(2), Some(0.2))]
+    #[case::copy_number(None, None, None, None, None, Some(0), Some(1.0))]
+    #[case::missing(None, None, None, None, None, None, None)]
+    fn test_call_info_allele_fraction(
+        #[case] paired_end_cov: Option<u32>,
+        #[case] paired_end_var: Option<u32>,
+        #[case] split_read_cov: Option<u32>,
+        #[case] split_read_var: Option<u32>,
+        #[case] average_normalized_cov: Option<f32>,
+        #[case] copy_number: Option<u32>,
+        #[case] expected: Option<f32>,
+    ) {
+        let call_info = CallInfo {
+            paired_end_cov,
+            paired_end_var,
+            split_read_cov,
+            split_read_var,
+            average_normalized_cov,
+            copy_number,
+            ..Default::default()
+        };
+
+        match (call_info.allele_fraction(), expected) {
+            (Some(actual), Some(expected)) => assert!((actual - expected).abs() < 1e-6),
+            (actual, expected) => assert_eq!(actual, expected),
+        }
+    }
+
+    #[test]
+    fn test_genotype_criteria_is_call_info_pass_allele_fraction() {
+        let crit = GenotypeCriteria {
+            min_allele_fraction: Some(0.05),
+            max_allele_fraction: Some(0.3),
+            ..GenotypeCriteria::new(GenotypeChoice::Mosaic)
+        };
+
+        assert!(crit.is_call_info_pass(&CallInfo {
+            average_normalized_cov: Some(0.85),
+            ..Default::default()
+        }));
+        assert!(!crit.is_call_info_pass(&CallInfo {
+            average_normalized_cov: Some(0.5),
+            ..Default::default()
+        }));
+        assert!(!crit.is_call_info_pass(&CallInfo {
+            average_normalized_cov: Some(0.99),
+            ..Default::default()
+        }));
+        assert!(crit.is_call_info_pass(&CallInfo::default()));
+        assert!(!GenotypeCriteria {
+            missing_allele_fraction_ok: false,
+            ..crit
+        }
+        .is_call_info_pass(&CallInfo::default()));
+    }
+
+    #[rstest::rstest]
+    #[case::pass(Some(12.0), Some(0.4), Some(55.0), true)]
+    #[case::high_cov(Some(30.0), Some(1.0), Some(55.0), false)]
+    #[case::high_rel(Some(12.0), Some(0.7), Some(55.0), false)]
+    #[case::low_mq(Some(12.0), Some(0.4), Some(10.0), false)]
+    #[case::no_mq(Some(12.0), Some(0.4), None, true)]
+    #[case::missing(None, None, None, true)]
+    fn test_genotype_criteria_is_call_info_pass_coverage(
+        #[case] coverage_mean: Option<f32>,
+        #[case] coverage_relative: Option<f32>,
+        #[case] coverage_mapq: Option<f32>,
+        #[case] expected: bool,
+    ) {
+        let crit = GenotypeCriteria {
+            max_cov_avg: Some(20.0),
+            max_cov_rel: Some(0.6),
+            min_cov_mq: Some(30.0),
+            ..GenotypeCriteria::new(GenotypeChoice::Het)
+        };
+        let call_info = CallInfo {
+            coverage_mean,
+            coverage_relative,
+            coverage_mapq,
+            ..Default::default()
+        };
+
+        assert_eq!(crit.is_call_info_pass(&call_info), expected);
+        if coverage_mean.is_none() {
+            assert!(!GenotypeCriteria {
+                missing_cov_ok: false,
+                ..crit
+            }
+            .is_call_info_pass(&call_info));
+        }
+    }
+
     #[test]
     fn test_case_query_serde_smoke() {
         let query: CaseQuery = CaseQuery::default();
@@ -1992,6 +2519,8 @@ mod tests {
             end: 200,
             strand_orientation: StrandOrientation::ThreeToFive,
             callers: Vec::new(),
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
         assert_eq!(sv.size().unwrap(), 101);
@@ -2008,6 +2537,8 @@ mod tests {
             end: 100,
             strand_orientation: StrandOrientation::ThreeToFive,
             callers: Vec::new(),
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
         assert!(sv.size().is_none());
@@ -2024,6 +2555,8 @@ mod tests {
             end: 200,
             strand_orientation: StrandOrientation::ThreeToFive,
             callers: Vec::new(),
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
         assert!(sv.size().is_none());
@@ -2040,6 +2573,8 @@ mod tests {
             end: 245,
             strand_orientation: StrandOrientation::ThreeToFive,
             callers: Vec::new(),
+            cipos: None,
+            ciend: None,
             call_info: IndexMap::new(),
         };
         insta::assert_snapshot!(serde_json::to_string_pretty(&sv).unwrap());
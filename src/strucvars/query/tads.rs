@@ -2,25 +2,21 @@
 
 use std::path::Path;
 
-use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
-use mehari::common::io::std::open_read_maybe_gz;
 use tracing::info;
 
 use crate::{
-    common::{build_chrom_map, CHROMS},
+    common::build_chrom_map,
     common::{GenomeRelease, TadSet as TadSetChoice},
 };
 
 use super::{
     interpreter::{BND_SLACK, INS_SLACK},
+    intervals::{read_bed, IntervalDb},
     schema::ChromRange,
     schema::{StructuralVariant, SvType},
 };
 
-/// Alias for the interval tree that we use.
-type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
-
 /// Information to store for a TAD set entry.
 #[derive(Default, Clone, Debug)]
 pub struct Record {
@@ -32,19 +28,24 @@ pub struct Record {
     pub end: i32,
 }
 
+/// Range of the distance to the next TAD boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct BoundaryDistRange {
+    /// Smallest possible distance.
+    pub min: u32,
+    /// Largest possible distance.
+    pub max: u32,
+}
+
 /// TAD set overlapping information.
 #[derive(Default, Debug)]
 pub struct TadSet {
-    /// Records, stored by chromosome.
-    pub records: Vec<Vec<Record>>,
-    /// Interval trees, stored by chromosome.
-    pub records_trees: Vec<IntervalTree>,
+    /// TAD records.
+    pub records: IntervalDb<Record>,
     /// Maximal distance to boundary to track.
     pub boundary_max_dist: i32,
-    /// Boundaries, stored by chromosome.
-    pub boundaries: Vec<Vec<i32>>,
-    /// Interval triees to boundaries, stored by chromosome.
-    pub boundaries_trees: Vec<IntervalTree>,
+    /// TAD boundary positions.
+    pub boundaries: IntervalDb<i32>,
 }
 
 impl TadSet {
@@ -54,25 +55,7 @@ impl TadSet {
         chrom_range: &ChromRange,
         chrom_map: &IndexMap<String, usize>,
     ) -> Vec<Record> {
-        let mut result = Vec::new();
-
-        let queries = {
-            let chrom_idx = *chrom_map
-                .get(&chrom_range.chromosome)
-                .expect("invalid chromosome");
-            vec![(chrom_idx, chrom_range.begin..chrom_range.end)]
-        };
-
-        for (chrom_idx, query) in queries {
-            self.records_trees[chrom_idx]
-                .find(query.clone())
-                .iter()
-                .for_each(|cursor| {
-                    result.push(self.records[chrom_idx][*cursor.data() as usize].clone())
-                });
-        }
-
-        result
+        self.records.fetch(chrom_range, chrom_map)
     }
 
     pub fn overlapping_tads(
@@ -109,17 +92,33 @@ impl TadSet {
         };
 
         for (chrom_idx, query) in queries {
-            self.records_trees[chrom_idx]
-                .find(query.clone())
-                .iter()
-                .for_each(|cursor| {
-                    result.push(self.records[chrom_idx][*cursor.data() as usize].clone())
-                });
+            result.extend(self.records.find(chrom_idx, query).cloned());
         }
 
         result
     }
 
+    /// Return the breakpoints of `sv` as chromosome index, position, and confidence
+    /// interval relative to the position.
+    fn breakpoints(
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> Vec<(usize, i32, (i32, i32))> {
+        let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+        let cipos = sv.cipos.unwrap_or_default();
+        let ciend = sv.ciend.unwrap_or_default();
+        match sv.sv_type {
+            SvType::Bnd => {
+                let chrom_idx2 = *chrom_map
+                    .get(sv.chrom2.as_ref().expect("no chrom2?"))
+                    .expect("invalid chromosome");
+                vec![(chrom_idx, sv.pos, cipos), (chrom_idx2, sv.end, ciend)]
+            }
+            SvType::Ins => vec![(chrom_idx, sv.pos, cipos)],
+            _ => vec![(chrom_idx, sv.pos, cipos), (chrom_idx, sv.end, ciend)],
+        }
+    }
+
     pub fn boundary_dist(
         &self,
         sv: &StructuralVariant,
@@ -127,100 +126,93 @@ impl TadSet {
     ) -> Option<u32> {
         let delta = self.boundary_max_dist;
 
-        let queries = {
-            let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
-            match sv.sv_type {
-                SvType::Bnd => {
-                    let chrom_idx2 = *chrom_map
-                        .get(sv.chrom2.as_ref().expect("no chrom2?"))
-                        .expect("invalid chromosome");
-                    vec![
-                        (
-                            chrom_idx,
-                            sv.pos.saturating_sub(delta)..sv.pos.saturating_add(delta),
-                            sv.pos,
-                        ),
-                        (
-                            chrom_idx2,
-                            sv.end.saturating_sub(delta)..sv.end.saturating_add(delta),
-                            sv.end,
-                        ),
-                    ]
-                }
-                SvType::Ins => vec![(
-                    chrom_idx,
-                    sv.pos.saturating_sub(delta)..sv.pos.saturating_add(delta),
-                    sv.pos,
-                )],
-                _ => vec![
-                    (
-                        chrom_idx,
-                        sv.pos.saturating_sub(delta)..sv.pos.saturating_add(delta),
-                        sv.pos,
-                    ),
-                    (
+        let mut dists = Vec::new();
+        for (chrom_idx, pos, _) in Self::breakpoints(sv, chrom_map) {
+            dists.extend(
+                self.boundaries
+                    .find(
                         chrom_idx,
-                        sv.end.saturating_sub(delta)..sv.end.saturating_add(delta),
-                        sv.end,
-                    ),
-                ],
-            }
-        };
+                        pos.saturating_sub(delta)..pos.saturating_add(delta),
+                    )
+                    .map(|boundary| pos.abs_diff(*boundary)),
+            );
+        }
+        dists.into_iter().min()
+    }
 
-        let mut dists = Vec::new();
-        for (chrom_idx, r, pos) in queries {
-            self.boundaries_trees[chrom_idx]
-                .find(r.clone())
-                .iter()
-                .for_each(|cursor| {
-                    let boundary = self.boundaries[chrom_idx][*cursor.data() as usize];
-                    dists.push(pos.abs_diff(boundary));
+    /// Compute the range of the distance to the next TAD boundary, taking the
+    /// confidence intervals of the breakpoints into account.
+    ///
+    /// The minimal distance is 0 if a boundary lies within a confidence interval, i.e.,
+    /// the SV may cross the boundary.  The maximal distance is an upper bound of the
+    /// distance to the next boundary for any breakpoint position within the intervals.
+    pub fn boundary_dist_range(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> Option<BoundaryDistRange> {
+        let delta = self.boundary_max_dist;
+
+        let mut result: Option<BoundaryDistRange> = None;
+        for (chrom_idx, pos, (ci_lower, ci_upper)) in Self::breakpoints(sv, chrom_map) {
+            let lower = pos.saturating_add(ci_lower);
+            let upper = pos.saturating_add(ci_upper);
+            let query = lower.saturating_sub(delta)..upper.saturating_add(delta);
+            for boundary in self.boundaries.find(chrom_idx, query) {
+                let min = if (lower..=upper).contains(boundary) {
+                    0
+                } else {
+                    lower.abs_diff(*boundary).min(upper.abs_diff(*boundary))
+                };
+                let max = lower.abs_diff(*boundary).max(upper.abs_diff(*boundary));
+                result = Some(match result {
+                    Some(range) => BoundaryDistRange {
+                        min: range.min.min(min),
+                        max: range.max.min(max),
+                    },
+                    None => BoundaryDistRange { min, max },
                 });
+            }
         }
-        dists.into_iter().min()
+        result
     }
 }
 
-/// Bundle of TAD sets packaged with VarFish.
+/// Bundle of the TAD sets available for querying.
 #[derive(Default, Debug)]
 pub struct TadSetBundle {
+    /// TADs from hESC, always packaged with VarFish.
     pub hesc: TadSet,
+    /// TADs from IMR90, if packaged with the database.
+    pub imr90: Option<TadSet>,
+    /// TADs given by the user with `--path-custom-tads`, if any.
+    pub custom: Option<TadSet>,
 }
 
 impl TadSetBundle {
-    pub fn fetch_tads(
-        &self,
-        tad_set: TadSetChoice,
-        chrom_range: &ChromRange,
-        chrom_map: &IndexMap<String, usize>,
-    ) -> Vec<Record> {
+    /// Return the TAD set for `tad_set`, or an error if it has not been loaded.
+    pub fn get(&self, tad_set: TadSetChoice) -> Result<&TadSet, anyhow::Error> {
         match tad_set {
-            TadSetChoice::Hesc => self.hesc.fetch_tads(chrom_range, chrom_map),
+            TadSetChoice::Hesc => Some(&self.hesc),
+            TadSetChoice::Imr90 => self.imr90.as_ref(),
+            TadSetChoice::Custom => self.custom.as_ref(),
         }
+        .ok_or_else(|| match tad_set {
+            TadSetChoice::Custom => {
+                anyhow::anyhow!("custom TAD set selected but no --path-custom-tads given")
+            }
+            _ => anyhow::anyhow!("TAD set {} is not available in the database", tad_set),
+        })
     }
 
-    pub fn overlapping_tads(
-        &self,
-        tad_set: TadSetChoice,
-        sv: &StructuralVariant,
-        chrom_map: &IndexMap<String, usize>,
-    ) -> Vec<Record> {
-        match tad_set {
-            TadSetChoice::Hesc => self.hesc.overlapping_tads(sv, chrom_map),
-        }
-    }
-
-    pub fn boundary_dist(
-        &self,
-        tad_set: TadSetChoice,
-        sv: &StructuralVariant,
-        chrom_map: &IndexMap<String, usize>,
-    ) -> Option<u32> {
-        match tad_set {
-            TadSetChoice::Hesc => self.hesc.boundary_dist(sv, chrom_map),
-        }
+    /// Load the custom TAD set from the BED file at `path`.
+    pub fn load_custom(&mut self, path: &str, max_tad_distance: i32) -> Result<(), anyhow::Error> {
+        info!("Loading custom TAD set from {}", path);
+        self.custom = Some(load_tad_sets(Path::new(path), max_tad_distance)?);
+        Ok(())
     }
 }
+
 /// Module with code for loading data from input.
 mod input {
     use serde::Deserialize;
@@ -243,67 +235,42 @@ fn load_tad_sets(path: &Path, boundary_max_dist: i32) -> Result<TadSet, anyhow::
     let chrom_map = build_chrom_map();
 
     let mut result = TadSet {
+        records: IntervalDb::new(path.display().to_string()),
         boundary_max_dist,
-        ..Default::default()
+        boundaries: IntervalDb::new(path.display().to_string()),
     };
-    for _ in CHROMS {
-        result.records.push(Vec::new());
-        result.records_trees.push(IntervalTree::new());
-        result.boundaries.push(Vec::new());
-        result.boundaries_trees.push(IntervalTree::new());
-    }
 
-    // Setup CSV reader for BED file - header is written as comment and must be
-    // ignored.
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false) // BED has no header
-        .comment(Some(b'#'))
-        .delimiter(b'\t')
-        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
-    let mut total_count = 0;
-    for (i, record) in reader.deserialize().enumerate() {
-        let record: input::Record = record?;
-        let chrom_idx = *chrom_map.get(&record.chrom).expect("invalid chromosome");
+    for (i, record) in read_bed::<input::Record>(path)?.into_iter().enumerate() {
+        let chrom_idx = *chrom_map.get(&record.chrom).ok_or_else(|| {
+            anyhow::anyhow!("invalid chromosome {} in {}", &record.chrom, path.display())
+        })?;
 
         // TAD interval
         {
             let key = record.begin..record.end;
-            result.records_trees[chrom_idx].insert(key, result.records[chrom_idx].len() as u32);
-            result.records[chrom_idx].push(Record {
-                chrom_no: chrom_idx as u32,
-                begin: record.begin,
-                end: record.end,
-            });
+            result.records.insert(
+                chrom_idx,
+                key,
+                Record {
+                    chrom_no: chrom_idx as u32,
+                    begin: record.begin,
+                    end: record.end,
+                },
+            );
         }
 
         // TAD boundary
         {
             if i == 0 {
                 let key = record.begin.saturating_sub(1)..(record.begin + 1);
-                result.boundaries_trees[chrom_idx]
-                    .insert(key, result.boundaries[chrom_idx].len() as u32);
-                result.boundaries[chrom_idx].push(record.begin);
+                result.boundaries.insert(chrom_idx, key, record.begin);
             }
             let key = record.end.saturating_sub(1)..(record.end + 1);
-            result.boundaries_trees[chrom_idx]
-                .insert(key, result.boundaries[chrom_idx].len() as u32);
-            result.boundaries[chrom_idx].push(record.end);
+            result.boundaries.insert(chrom_idx, key, record.end);
         }
-
-        total_count += 1;
     }
-    result
-        .records_trees
-        .iter_mut()
-        .for_each(|tree| tree.index());
-    result
-        .boundaries_trees
-        .iter_mut()
-        .for_each(|tree| tree.index());
-    tracing::debug!(
-        "... done loading {} records and building trees",
-        total_count
-    );
+    result.records.index();
+    result.boundaries.index();
 
     Ok(result)
 }
@@ -316,6 +283,7 @@ pub fn load_tads(
     max_tad_distance: i32,
 ) -> Result<TadSetBundle, anyhow::Error> {
     info!("Loading TAD sets dbs");
+    let path_imr90 = Path::new(path_db).join(format!("{}/tads/imr90.bed", genome_release));
     let result = TadSetBundle {
         hesc: load_tad_sets(
             Path::new(path_db)
@@ -323,7 +291,108 @@ pub fn load_tads(
                 .as_path(),
             max_tad_distance,
         )?,
+        imr90: if path_imr90.exists() {
+            Some(load_tad_sets(&path_imr90, max_tad_distance)?)
+        } else {
+            info!("No IMR90 TAD set at {}", path_imr90.display());
+            None
+        },
+        custom: None,
     };
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexMap;
+
+    use super::{BoundaryDistRange, TadSet, TadSetBundle};
+    use crate::common::{build_chrom_map, TadSet as TadSetChoice};
+    use crate::strucvars::query::{
+        intervals::IntervalDb,
+        schema::{StrandOrientation, StructuralVariant, SvSubType, SvType},
+    };
+
+    fn tad_set(boundaries: &[i32]) -> TadSet {
+        let mut result = TadSet {
+            records: IntervalDb::new("test"),
+            boundary_max_dist: 1000,
+            boundaries: IntervalDb::new("test"),
+        };
+        for boundary in boundaries {
+            result
+                .boundaries
+                .insert(0, (boundary - 1)..(boundary + 1), *boundary);
+        }
+        result.records.index();
+        result.boundaries.index();
+        result
+    }
+
+    fn sv(
+        pos: i32,
+        end: i32,
+        cipos: Option<(i32, i32)>,
+        ciend: Option<(i32, i32)>,
+    ) -> StructuralVariant {
+        StructuralVariant {
+            chrom: "1".to_owned(),
+            pos,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end,
+            strand_orientation: StrandOrientation::ThreeToFive,
+            cipos,
+            ciend,
+            callers: Vec::new(),
+            call_info: IndexMap::new(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(sv(10_100, 20_000, None, None), Some(100), Some((100, 100)))]
+    #[case(sv(10_100, 20_000, Some((-50, 50)), None), Some(100), Some((50, 150)))]
+    #[case(sv(10_100, 20_000, Some((-200, 50)), None), Some(100), Some((0, 150)))]
+    #[case(sv(9_500, 20_000, None, Some((-10_100, 100))), Some(500), Some((0, 500)))]
+    #[case(sv(15_000, 20_000, None, None), None, None)]
+    #[case(sv(15_000, 20_000, Some((-4_500, 0)), None), None, Some((500, 5_000)))]
+    fn boundary_dist(
+        #[case] sv: StructuralVariant,
+        #[case] expected_dist: Option<u32>,
+        #[case] expected_range: Option<(u32, u32)>,
+    ) {
+        let tad_set = tad_set(&[10_000, 30_000]);
+        let chrom_map = build_chrom_map();
+
+        assert_eq!(tad_set.boundary_dist(&sv, &chrom_map), expected_dist);
+        assert_eq!(
+            tad_set.boundary_dist_range(&sv, &chrom_map),
+            expected_range.map(|(min, max)| BoundaryDistRange { min, max })
+        );
+    }
+
+    #[test]
+    fn custom_tads() -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let path = tmp_dir.join("custom.bed");
+        std::fs::write(&path, "chr1\t10000\t20000\nchr1\t20000\t30000\n")?;
+
+        let mut bundle = TadSetBundle::default();
+        assert!(bundle.get(TadSetChoice::Custom).is_err());
+        assert!(bundle.get(TadSetChoice::Imr90).is_err());
+        bundle.load_custom(&path.to_string_lossy(), 1000)?;
+
+        let chrom_map = build_chrom_map();
+        let tad_set = bundle.get(TadSetChoice::Custom)?;
+        let sv = sv(15_000, 20_100, None, None);
+        assert_eq!(tad_set.overlapping_tads(&sv, &chrom_map).len(), 2);
+        assert_eq!(tad_set.boundary_dist(&sv, &chrom_map), Some(100));
+
+        std::fs::write(&path, "chrUn\t10000\t20000\n")?;
+        assert!(bundle.load_custom(&path.to_string_lossy(), 1000).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,495 @@
+//! Computation of the effects of structural variants on transcripts.
+
+use std::collections::HashMap;
+
+use mehari::{
+    annotate::seqvars::provider::TxIntervalTrees,
+    pbs::txs::{Strand, Transcript, TxSeqDatabase},
+};
+use serde::Serialize;
+
+use super::{
+    construct_gene,
+    genes::GeneDb,
+    schema::{StructuralVariant, SvType, TranscriptEffect},
+    GeneTranscriptEffects,
+};
+
+/// Sizes of the regions around transcripts and exon boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Windows {
+    /// Length of the upstream/downstream region.
+    pub x_stream: i32,
+    /// Number of exonic bases in the splice region.
+    pub splice_exonic: i32,
+    /// Number of intronic bases in the splice region.
+    pub splice_intronic: i32,
+}
+
+impl Default for Windows {
+    fn default() -> Self {
+        Self {
+            x_stream: 5000,
+            splice_exonic: 3,
+            splice_intronic: 8,
+        }
+    }
+}
+
+/// Region of a transcript as computed by `tx_regions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TxRegion {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// 0-based end position.
+    pub end: i32,
+    /// 1-based number of the exon; introns have the number of the exon right of them.
+    pub exon_no: i32,
+    /// Effect on the transcript (encodes region type).
+    pub effect: TranscriptEffect,
+}
+
+/// Return list of half-open intervals for a given transcript.
+pub fn tx_regions(tx: &Transcript, windows: &Windows) -> Vec<TxRegion> {
+    assert_eq!(
+        tx.genome_alignments.len(),
+        1,
+        "only one alignment supported"
+    );
+    let genome_alignment = &tx.genome_alignments[0];
+    if genome_alignment.exons.is_empty() {
+        // no exons? skip!
+        return Vec::new();
+    }
+
+    // Determine leftmost and rightmost genome position.
+    let tx_start = genome_alignment
+        .exons
+        .iter()
+        .map(|exon_alignment| exon_alignment.alt_start_i)
+        .min()
+        .expect("must have exons");
+    let tx_end = genome_alignment
+        .exons
+        .iter()
+        .map(|exon_alignment| exon_alignment.alt_end_i)
+        .max()
+        .expect("must have exons");
+    let is_plus = genome_alignment.strand == Strand::Plus as i32;
+
+    // Perform the actual region extraction.
+    let mut result = Vec::new();
+    let mut prev_alt_end_i = 0;
+    for exon_alignment in &genome_alignment.exons {
+        let exon_no = exon_alignment.ord + 1;
+        if exon_alignment.alt_start_i == tx_start {
+            // is first, register upstream/downstream
+            result.push(TxRegion {
+                begin: exon_alignment.alt_start_i - windows.x_stream,
+                end: exon_alignment.alt_start_i - 1,
+                exon_no,
+                effect: if is_plus {
+                    TranscriptEffect::UpstreamVariant
+                } else {
+                    TranscriptEffect::DownstreamVariant
+                },
+            });
+        } else {
+            // is not first, register splice region on the left boundary
+            result.push(TxRegion {
+                begin: (exon_alignment.alt_start_i - 1) - windows.splice_intronic,
+                end: (exon_alignment.alt_start_i - 1) + windows.splice_exonic,
+                exon_no,
+                effect: TranscriptEffect::SpliceRegionVariant,
+            })
+        }
+
+        if exon_alignment.alt_end_i == tx_end {
+            // is last, register upstream/downstream
+            result.push(TxRegion {
+                begin: exon_alignment.alt_end_i,
+                end: exon_alignment.alt_end_i + windows.x_stream,
+                exon_no,
+                effect: if is_plus {
+                    TranscriptEffect::DownstreamVariant
+                } else {
+                    TranscriptEffect::UpstreamVariant
+                },
+            });
+        } else {
+            // is not last, register splice region on the right boundary
+            result.push(TxRegion {
+                begin: exon_alignment.alt_end_i - windows.splice_exonic,
+                end: exon_alignment.alt_end_i + windows.splice_intronic,
+                exon_no,
+                effect: TranscriptEffect::SpliceRegionVariant,
+            })
+        }
+
+        // register the exon
+        result.push(TxRegion {
+            begin: exon_alignment.alt_start_i - 1,
+            end: exon_alignment.alt_end_i,
+            exon_no,
+            effect: TranscriptEffect::ExonVariant,
+        });
+
+        if exon_alignment.alt_start_i != tx_start {
+            // is not first exon, register intron "left" of it
+            result.push(TxRegion {
+                begin: prev_alt_end_i,
+                end: exon_alignment.alt_start_i - 1,
+                exon_no,
+                effect: TranscriptEffect::IntronVariant,
+            });
+        }
+
+        // store end of prev exon for next intron's start
+        prev_alt_end_i = exon_alignment.alt_end_i;
+    }
+
+    result
+}
+
+/// Impact of a structural variant on a single transcript.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxImpact {
+    /// Sorted effects on the transcript, empty if no region of the transcript is hit.
+    pub effects: Vec<TranscriptEffect>,
+    /// Sorted 1-based numbers of the affected exons.
+    pub exons: Vec<i32>,
+}
+
+/// Compute the impact of the 1-based range `pos..=end` on `tx`.
+///
+/// Breakpoints, e.g., one side of a BND or an INS, are passed with `end == pos`.
+pub fn tx_impact(tx: &Transcript, pos: i32, end: i32, windows: &Windows) -> TxImpact {
+    let pos = pos - 1; // 1-based to 0-based
+    let regions = tx_regions(tx, windows)
+        .into_iter()
+        .filter(|region| pos < region.end && region.begin < end)
+        .collect::<Vec<_>>();
+
+    let mut effects = regions
+        .iter()
+        .map(|region| region.effect)
+        .collect::<Vec<_>>();
+    // If we have both upstream and downstream then the full transcript is affected.
+    if effects.contains(&TranscriptEffect::UpstreamVariant)
+        && effects.contains(&TranscriptEffect::DownstreamVariant)
+    {
+        effects.push(TranscriptEffect::TranscriptVariant);
+    }
+    effects.sort();
+    effects.dedup();
+
+    let mut exons = regions
+        .iter()
+        .filter(|region| region.effect == TranscriptEffect::ExonVariant)
+        .map(|region| region.exon_no)
+        .collect::<Vec<_>>();
+    exons.sort();
+    exons.dedup();
+
+    TxImpact { effects, exons }
+}
+
+/// Exons of one transcript that are affected by a structural variant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AffectedExons {
+    /// Accession of the transcript.
+    pub tx_accession: String,
+    /// Sorted 1-based numbers of the affected exons.
+    pub exons: Vec<i32>,
+    /// Total number of exons of the transcript.
+    pub exon_count: usize,
+}
+
+/// Overlap of a structural variant with the extent of a gene.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct GeneOverlap {
+    /// Fraction of the gene covered by the variant, zero for breakpoints (INS/BND).
+    pub fraction: f64,
+    /// Whether the start breakpoint falls inside the gene.
+    pub start_in_gene: bool,
+    /// Whether the end breakpoint falls inside the gene; equal to `start_in_gene` for
+    /// INS and BND.
+    pub end_in_gene: bool,
+    /// Whether the whole gene is contained in the variant.
+    pub contained: bool,
+}
+
+/// Return the 0-based, half-open extent of the genome alignment of `tx`.
+pub(crate) fn tx_extent(tx: &Transcript) -> Option<(i32, i32)> {
+    let exons = &tx.genome_alignments.first()?.exons;
+    let begin = exons.iter().map(|exon| exon.alt_start_i - 1).min()?;
+    let end = exons.iter().map(|exon| exon.alt_end_i).max()?;
+    Some((begin, end))
+}
+
+/// Compute the overlap of the 1-based range `pos..=end` with the 0-based, half-open
+/// gene `extent`.
+///
+/// Breakpoints, e.g., one side of a BND or an INS, are passed with `is_breakpoint`.
+pub fn gene_overlap(extent: (i32, i32), pos: i32, end: i32, is_breakpoint: bool) -> GeneOverlap {
+    let (gene_begin, gene_end) = extent;
+    let begin = pos - 1; // 1-based to 0-based
+    if is_breakpoint {
+        let in_gene = gene_begin <= begin && begin < gene_end;
+        return GeneOverlap {
+            fraction: 0.0,
+            start_in_gene: in_gene,
+            end_in_gene: in_gene,
+            contained: false,
+        };
+    }
+
+    let overlap = (end.min(gene_end) - begin.max(gene_begin)).max(0);
+    GeneOverlap {
+        fraction: if gene_end > gene_begin {
+            f64::from(overlap) / f64::from(gene_end - gene_begin)
+        } else {
+            0.0
+        },
+        start_in_gene: gene_begin < begin && begin < gene_end,
+        end_in_gene: gene_begin < end && end < gene_end,
+        contained: begin <= gene_begin && gene_end <= end,
+    }
+}
+
+/// Compute effect(s) of `sv` on transcript of genes.
+///
+/// INS and BND are considered at their start breakpoint only, all other SV types
+/// with their full range.
+pub(super) fn compute_tx_effects(
+    sv: &StructuralVariant,
+    mehari_tx_db: &TxSeqDatabase,
+    mehari_tx_idx: &TxIntervalTrees,
+    gene_db: &GeneDb,
+    chrom_to_acc: &HashMap<String, String>,
+    windows: &Windows,
+) -> Vec<GeneTranscriptEffects> {
+    // Shortcut to the `TranscriptDb`.
+    let tx_db = mehari_tx_db
+        .tx_db
+        .as_ref()
+        .expect("transcripts must be present");
+    // Compute canonical chromosome name and map to accession.
+    let Some(idx) = chrom_to_acc
+        .get(&annonars::common::cli::canonicalize(&sv.chrom))
+        .and_then(|chrom| mehari_tx_idx.contig_to_idx.get(chrom))
+    else {
+        // We do not have any transcripts for this chromosome.
+        return Default::default();
+    };
+    let is_breakpoint = matches!(sv.sv_type, SvType::Ins | SvType::Bnd);
+    let end = if is_breakpoint { sv.pos } else { sv.end };
+    // Create range to query the interval trees for.
+    let query = (sv.pos - windows.x_stream)..(end + windows.x_stream);
+
+    // Collect all transcripts that overlap the SV and compute the effect of the SV on
+    // the transcript.
+    let mut effects_by_gene: HashMap<_, GeneTranscriptEffects> = HashMap::new();
+    let mut extent_by_gene: HashMap<_, (i32, i32)> = HashMap::new();
+    let tree = &mehari_tx_idx.trees[*idx];
+    for it in tree.find(query) {
+        let tx = &tx_db.transcripts[*it.data() as usize];
+        if let Some(&idx) = gene_db.xlink.from_hgnc.get(&tx.gene_id) {
+            let entrez_id = gene_db.xlink.records[idx as usize].entrez_id;
+            let mut impact = tx_impact(tx, sv.pos, end, windows);
+            if is_breakpoint && impact.effects.is_empty() {
+                impact.effects.push(TranscriptEffect::IntergenicVariant);
+            }
+
+            let gene_effects =
+                effects_by_gene
+                    .entry(entrez_id)
+                    .or_insert_with(|| GeneTranscriptEffects {
+                        gene: construct_gene(entrez_id, gene_db),
+                        ..Default::default()
+                    });
+            gene_effects.transcript_effects.extend(impact.effects);
+            if let Some((begin, end)) = tx_extent(tx) {
+                let extent = extent_by_gene.entry(entrez_id).or_insert((begin, end));
+                *extent = (extent.0.min(begin), extent.1.max(end));
+            }
+            if !impact.exons.is_empty() {
+                gene_effects.affected_exons.push(AffectedExons {
+                    tx_accession: tx.id.clone(),
+                    exons: impact.exons,
+                    exon_count: tx.genome_alignments[0].exons.len(),
+                });
+            }
+        } else {
+            tracing::warn!("could not resolve HGNC gene ID {:?}", tx.gene_id)
+        }
+    }
+
+    // Deduplicate results and convert them into the final format.
+    effects_by_gene
+        .into_iter()
+        .map(|(entrez_id, mut gene_effects)| {
+            gene_effects.gene_overlap = extent_by_gene
+                .get(&entrez_id)
+                .map(|extent| gene_overlap(*extent, sv.pos, end, is_breakpoint));
+            gene_effects.transcript_effects.sort();
+            gene_effects.transcript_effects.dedup();
+            gene_effects
+                .affected_exons
+                .sort_by(|a, b| a.tx_accession.cmp(&b.tx_accession));
+            gene_effects
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::pbs::txs::{ExonAlignment, GenomeAlignment, Strand, Transcript};
+    use rstest::rstest;
+
+    use super::{GeneOverlap, TranscriptEffect::*, TxImpact, TxRegion, Windows};
+
+    /// Transcript with exons at 1,001-1,100 and 2,001-2,100.
+    fn transcript(strand: Strand) -> Transcript {
+        let exons: Vec<_> = [(1001, 1100), (2001, 2100)]
+            .into_iter()
+            .enumerate()
+            .map(|(ord, (alt_start_i, alt_end_i))| ExonAlignment {
+                alt_start_i,
+                alt_end_i,
+                ord: ord as i32,
+                ..Default::default()
+            })
+            .collect();
+        Transcript {
+            id: "NM_000001.1".into(),
+            gene_id: "HGNC:1".into(),
+            genome_alignments: vec![GenomeAlignment {
+                contig: "NC_000001.10".into(),
+                strand: strand as i32,
+                exons,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tx_regions() {
+        let region = |begin, end, exon_no, effect| TxRegion {
+            begin,
+            end,
+            exon_no,
+            effect,
+        };
+
+        assert_eq!(
+            super::tx_regions(&transcript(Strand::Plus), &Default::default()),
+            vec![
+                region(-3999, 1000, 1, UpstreamVariant),
+                region(1097, 1108, 1, SpliceRegionVariant),
+                region(1000, 1100, 1, ExonVariant),
+                region(1992, 2003, 2, SpliceRegionVariant),
+                region(2100, 7100, 2, DownstreamVariant),
+                region(2000, 2100, 2, ExonVariant),
+                region(1100, 2000, 2, IntronVariant),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::exon_1(Strand::Plus, 1050, 1050, vec![ExonVariant], vec![1])]
+    #[case::intron(Strand::Plus, 1500, 1500, vec![IntronVariant], vec![])]
+    #[case::splice_donor(Strand::Plus, 1105, 1105, vec![SpliceRegionVariant, IntronVariant], vec![])]
+    #[case::splice_acceptor(Strand::Plus, 2002, 2002, vec![ExonVariant, SpliceRegionVariant], vec![2])]
+    #[case::upstream(Strand::Plus, 500, 500, vec![UpstreamVariant], vec![])]
+    #[case::downstream(Strand::Minus, 500, 500, vec![DownstreamVariant], vec![])]
+    #[case::far_away(Strand::Plus, 10_000, 10_000, vec![], vec![])]
+    #[case::range_exon_2(Strand::Plus, 1900, 2050, vec![ExonVariant, SpliceRegionVariant, IntronVariant], vec![2])]
+    #[case::range_full(
+        Strand::Plus,
+        900,
+        2200,
+        vec![TranscriptVariant, ExonVariant, SpliceRegionVariant, IntronVariant, UpstreamVariant, DownstreamVariant],
+        vec![1, 2],
+    )]
+    fn tx_impact(
+        #[case] strand: Strand,
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] effects: Vec<super::TranscriptEffect>,
+        #[case] exons: Vec<i32>,
+    ) {
+        assert_eq!(
+            super::tx_impact(&transcript(strand), pos, end, &Default::default()),
+            TxImpact { effects, exons }
+        );
+    }
+
+    #[test]
+    fn tx_impact_windows() {
+        let windows = Windows {
+            x_stream: 100,
+            splice_exonic: 1,
+            splice_intronic: 2,
+        };
+        let tx = transcript(Strand::Plus);
+
+        assert_eq!(
+            super::tx_impact(&tx, 500, 500, &windows),
+            TxImpact::default()
+        );
+        assert_eq!(
+            super::tx_impact(&tx, 950, 950, &windows).effects,
+            vec![UpstreamVariant]
+        );
+        assert_eq!(
+            super::tx_impact(&tx, 1105, 1105, &windows).effects,
+            vec![IntronVariant]
+        );
+        assert_eq!(
+            super::tx_impact(&tx, 1102, 1102, &windows).effects,
+            vec![SpliceRegionVariant, IntronVariant]
+        );
+    }
+
+    #[test]
+    fn tx_extent() {
+        assert_eq!(
+            super::tx_extent(&transcript(Strand::Plus)),
+            Some((1000, 2100))
+        );
+    }
+
+    #[rstest]
+    #[case::contained(900, 2200, false, 1.0, false, false, true)]
+    #[case::exact(1001, 2100, false, 1.0, false, false, true)]
+    #[case::start_in_intron(1551, 2200, false, 0.5, true, false, false)]
+    #[case::end_in_exon_1(901, 1055, false, 0.05, false, true, false)]
+    #[case::within(1101, 1210, false, 0.1, true, true, false)]
+    #[case::outside(3001, 4000, false, 0.0, false, false, false)]
+    #[case::breakpoint_in_gene(1500, 1500, true, 0.0, true, true, false)]
+    #[case::breakpoint_outside(500, 500, true, 0.0, false, false, false)]
+    fn gene_overlap(
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] is_breakpoint: bool,
+        #[case] fraction: f64,
+        #[case] start_in_gene: bool,
+        #[case] end_in_gene: bool,
+        #[case] contained: bool,
+    ) {
+        let overlap = super::gene_overlap((1000, 2100), pos, end, is_breakpoint);
+
+        assert!((overlap.fraction - fraction).abs() < 1e-9);
+        assert_eq!(
+            overlap,
+            GeneOverlap {
+                fraction: overlap.fraction,
+                start_in_gene,
+                end_in_gene,
+                contained,
+            }
+        );
+    }
+}
@@ -67,6 +67,10 @@ pub struct Args {
     /// Path to output BIN file.
     #[arg(long)]
     pub path_output: PathBuf,
+    /// Number of samples in the background database, used for the carrier frequencies
+    /// of records without per-site sample counts (e.g., DGV gold standard).
+    #[arg(long)]
+    pub sample_count: Option<u32>,
 }
 
 /// Main entry point for the `strucvars txt-to-bin` command.
@@ -84,36 +88,55 @@ pub fn run(common_args: &crate::common::Args, args: &Args) -> Result<(), anyhow:
             &args.path_input,
             &args.path_output,
             InputFileType::InhouseDb,
+            args.sample_count,
+        )?,
+        InputType::StrucvarDbVar => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Dbvar,
+            args.sample_count,
+        )?,
+        InputType::StrucvarDgv => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Dgv,
+            args.sample_count,
+        )?,
+        InputType::StrucvarDgvGs => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::DgvGs,
+            args.sample_count,
+        )?,
+        InputType::StrucvarExacCnv => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Exac,
+            args.sample_count,
+        )?,
+        InputType::StrucvarG1k => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::G1k,
+            args.sample_count,
         )?,
-        InputType::StrucvarDbVar => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Dbvar)?
-        }
-        InputType::StrucvarDgv => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Dgv)?
-        }
-        InputType::StrucvarDgvGs => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::DgvGs)?
-        }
-        InputType::StrucvarExacCnv => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Exac)?
-        }
-        InputType::StrucvarG1k => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::G1k)?
-        }
         InputType::StrucvarGnomadSv2 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadSv2,
+            args.sample_count,
         )?,
         InputType::StrucvarGnomadCnv4 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadCnv4,
+            args.sample_count,
         )?,
         InputType::StrucvarGnomadSv4 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadSv4,
+            args.sample_count,
         )?,
         InputType::MaskedRegion => masked::convert_to_bin(&args.path_input, &args.path_output)?,
         InputType::Xlink => xlink::convert_to_bin(&args.path_input, &args.path_output)?,
@@ -138,6 +161,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::ClinvarSv,
@@ -145,6 +174,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/clinvar/clinvar-svs.jsonl.gz",
             ),
             path_output: tmp_dir.join("clinvar.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -157,6 +187,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarInhouse,
@@ -164,6 +200,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/inhouse.tsv",
             ),
             path_output: tmp_dir.join("strucvar_inhouse.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -176,6 +213,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarDbVar,
@@ -183,6 +226,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dbvar.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dbvar.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -195,6 +239,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarDgv,
@@ -202,6 +252,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dgv.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dgv.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -214,6 +265,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarDgvGs,
@@ -221,6 +278,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dgv_gs.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dgv_gs.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -233,6 +291,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarExacCnv,
@@ -240,6 +304,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/exac.bed.gz",
             ),
             path_output: tmp_dir.join("exac.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -252,6 +317,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarG1k,
@@ -259,6 +330,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/g1k.bed.gz",
             ),
             path_output: tmp_dir.join("g1k.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -271,6 +343,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarGnomadSv2,
@@ -278,6 +356,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/gnomad_sv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -290,6 +369,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarGnomadCnv4,
@@ -297,6 +382,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-cnv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad-cnv.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -309,6 +395,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::StrucvarGnomadSv4,
@@ -316,6 +408,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-sv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad-sv.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -328,6 +421,12 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::MaskedRegion,
@@ -335,6 +434,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/features/grch37/masked/repeat.bed.gz",
             ),
             path_output: tmp_dir.join("masked.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
@@ -347,11 +447,18 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            threads: 1,
+            strict: false,
+            otel_endpoint: None,
+            s3_part_size: 64,
+            s3_upload_attempts: 3,
+            s3_retry_delay: 1.0,
         };
         let args = Args {
             input_type: InputType::Xlink,
             path_input: String::from("tests/db/to-bin/varfish-db-downloader/genes/xlink/hgnc.tsv"),
             path_output: tmp_dir.join("xlink.bin"),
+            sample_count: None,
         };
 
         super::run(&common_args, &args)?;
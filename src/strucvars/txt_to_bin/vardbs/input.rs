@@ -115,26 +115,22 @@ pub struct GnomadSv4Record {
     /// The structural vairant type
     pub svtype: String,
     /// Number of male homozygous reference allele carriers.
-    #[allow(dead_code)]
     pub male_n_homref: u32,
     /// Number of male heterozygous alternate allele carriers.
     pub male_n_het: u32,
     /// Number of male homozygous alternate allele carriers.
     pub male_n_homalt: u32,
     /// Number of male hemizygous alternate allele carriers.
-    #[allow(dead_code)]
     pub male_n_hemiref: u32,
     /// Number of male hemizygous reference allele carriers.
     pub male_n_hemialt: u32,
     /// Number of female homozygous reference allele carriers.
-    #[allow(dead_code)]
     pub female_n_homref: u32,
     /// Number of female heterozygous alternate allele carriers.
     pub female_n_het: u32,
     /// Number of female homozygous alternate allele carriers.
     pub female_n_homalt: u32,
     /// Number of samples at this site (CNV only).
-    #[allow(dead_code)]
     pub cnv_n_total: u32,
     /// Number of samples with a CNV at this site (CNV only).
     pub cnv_n_var: u32,
@@ -172,6 +168,8 @@ pub struct InputRecord {
     pub end: i32,
     /// Number of carriers (or alleles), depending on database.
     pub count: u32,
+    /// Number of samples genotyped at the site, 0 if unknown.
+    pub total: u32,
 }
 
 impl TryInto<Option<InputRecord>> for InhouseDbRecord {
@@ -185,6 +183,7 @@ impl TryInto<Option<InputRecord>> for InhouseDbRecord {
             begin: self.begin,
             end: self.end,
             count: self.carriers,
+            total: 0,
         }))
     }
 }
@@ -217,6 +216,7 @@ impl TryInto<Option<InputRecord>> for DbVarRecord {
             end: self.end,
             sv_type,
             count: 1,
+            total: 0,
         }))
     }
 }
@@ -257,6 +257,7 @@ impl TryInto<Option<InputRecord>> for DgvRecord {
             end: self.end,
             sv_type,
             count: self.observed_gains + self.observed_losses,
+            total: 0,
         }))
     }
 }
@@ -280,6 +281,7 @@ impl TryInto<Option<InputRecord>> for DgvGsRecord {
             end: self.end_outer,
             sv_type,
             count: self.num_carriers,
+            total: 0,
         }))
     }
 }
@@ -303,6 +305,7 @@ impl TryInto<Option<InputRecord>> for ExacRecord {
             end: self.end,
             sv_type,
             count: 1,
+            total: 0,
         }))
     }
 }
@@ -331,6 +334,7 @@ impl TryInto<Option<InputRecord>> for GnomadSv2Record {
             end: self.end,
             sv_type,
             count: self.n_homalt + self.n_het,
+            total: 0,
         }))
     }
 }
@@ -353,6 +357,7 @@ impl TryInto<Option<InputRecord>> for GnomadCnv4Record {
                 }
             },
             count: self.n_var,
+            total: 0,
         }))
     }
 }
@@ -384,6 +389,19 @@ impl TryInto<Option<InputRecord>> for GnomadSv4Record {
                 + self.female_n_het
                 + self.female_n_homalt
                 + self.cnv_n_var,
+            // CNV sites only have the CNV counts, all other sites the genotype counts.
+            total: if self.cnv_n_total > 0 {
+                self.cnv_n_total
+            } else {
+                self.male_n_homref
+                    + self.male_n_het
+                    + self.male_n_homalt
+                    + self.male_n_hemiref
+                    + self.male_n_hemialt
+                    + self.female_n_homref
+                    + self.female_n_het
+                    + self.female_n_homalt
+            },
         }))
     }
 }
@@ -411,6 +429,7 @@ impl TryInto<Option<InputRecord>> for G1kRecord {
             end: self.end,
             sv_type,
             count: self.n_homalt + self.n_het,
+            total: 0,
         }))
     }
 }
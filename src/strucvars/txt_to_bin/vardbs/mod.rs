@@ -70,6 +70,7 @@ where
                 start: record.begin + 1,
                 stop: record.end,
                 count: record.count,
+                total: record.total,
             });
         }
     }
@@ -96,10 +97,14 @@ pub fn deserialize_branch(
 }
 
 /// Perform conversion to protobuf `.bin` file.
+///
+/// The `sample_count` of the database is used for computing carrier frequencies of the
+/// records without a per-site total.
 pub fn convert_to_bin<P, Q>(
     path_input_tsv: P,
     path_output: Q,
     input_type: InputFileType,
+    sample_count: Option<u32>,
 ) -> Result<(), anyhow::Error>
 where
     P: AsRef<Path>,
@@ -117,7 +122,10 @@ where
     let before_parsing = Instant::now();
 
     let records = deserialize_branch(input_type, &mut reader)?;
-    let bg_db = BackgroundDatabase { records };
+    let bg_db = BackgroundDatabase {
+        records,
+        sample_count: sample_count.unwrap_or_default(),
+    };
 
     tracing::debug!(
         "total time spent reading {} records: {:?}",